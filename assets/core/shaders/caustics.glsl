@@ -0,0 +1,22 @@
+// Shared by the single and multisampled caustics shaders, they define load_depth.
+
+#include "library/common.glsl"
+#include "library/lighting.glsl"
+#include "library/water.glsl"
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+
+// The blend state multiplies the frame with the color, 1.0 leaves it as it is.
+void main() {
+    o_color = vec4(1.0);
+    float depth = load_depth(ivec2(gl_FragCoord.xy));
+    // Caustics only come from the sun, the first directional light.
+    if (depth >= 1.0 || light_num.x < 1.0) {
+        return;
+    }
+
+    vec4 world = inverse(view_projection) * vec4(i_uv.x * 2.0 - 1.0, 1.0 - i_uv.y * 2.0, depth, 1.0);
+    vec3 L = normalize(directional_lights[0].direction.xyz);
+    o_color = vec4(vec3(water_caustics(world.xyz / world.w, L)), 1.0);
+}
@@ -0,0 +1,2 @@
+post/post_vert.glsl
+caustics_frag.glsl
\ No newline at end of file
@@ -0,0 +1,11 @@
+#version 450
+#extension GL_GOOGLE_include_directive : enable
+
+layout(set = 0, binding = 0) uniform texture2D t_depth;
+layout(set = 0, binding = 1) uniform sampler s_depth;
+
+float load_depth(ivec2 coord) {
+    return texelFetch(sampler2D(t_depth, s_depth), coord, 0).r;
+}
+
+#include "caustics.glsl"
@@ -0,0 +1,12 @@
+#version 450
+#extension GL_GOOGLE_include_directive : enable
+
+layout(set = 0, binding = 0) uniform texture2DMS t_depth;
+layout(set = 0, binding = 1) uniform sampler s_depth;
+
+// The first sample is close enough, the pattern is soft anyway.
+float load_depth(ivec2 coord) {
+    return texelFetch(sampler2DMS(t_depth, s_depth), coord, 0).r;
+}
+
+#include "caustics.glsl"
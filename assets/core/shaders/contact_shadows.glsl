@@ -0,0 +1,98 @@
+// Shared by the single and multisampled contact shadow shaders, they define load_depth.
+
+#include "library/common.glsl"
+#include "library/lighting.glsl"
+#include "library/clusters.glsl"
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+
+const int CONTACT_STEPS = 16;
+// Depth buffer samples more than this in front of the ray are thin gaps, not occluders.
+const float CONTACT_THICKNESS = 0.2;
+
+float view_depth_at(mat4 inverse_projection, float depth) {
+    vec4 position = inverse_projection * vec4(0.0, 0.0, depth, 1.0);
+    return -position.z / position.w;
+}
+
+float interleaved_noise(vec2 pixel) {
+    return fract(52.9829189 * fract(dot(pixel, vec2(0.06711056, 0.00583715))));
+}
+
+// Marches from the position towards the light, 1.0 when the ray ends up behind the depth buffer.
+float contact_shadow(vec3 position, vec3 L, float ray_length, float offset) {
+    mat4 inverse_projection = inverse(projection);
+    vec3 ray_step = L * ray_length / float(CONTACT_STEPS);
+    vec3 ray = position + ray_step * offset;
+    for (int i = 0; i < CONTACT_STEPS; ++i) {
+        ray += ray_step;
+        vec4 ray_view = view * vec4(ray, 1.0);
+        vec4 clip = projection * ray_view;
+        vec2 ndc = clip.xy / clip.w;
+        vec2 uv = vec2(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        if (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+            return 0.0;
+        }
+        // Split-screen viewports don't start at the corner of the screen.
+        ivec2 pixel = ivec2(cluster_counts.yz + uv * cluster_screen.xy);
+        float scene_depth = view_depth_at(inverse_projection, load_depth(pixel));
+        float ray_depth = -ray_view.z;
+        // The small bias keeps surfaces from shadowing themselves.
+        float difference = ray_depth - scene_depth;
+        if (difference > ray_depth * 0.002 && difference < CONTACT_THICKNESS) {
+            // Shadows fade out towards the end of the ray instead of stopping hard.
+            return 1.0 - float(i) / float(CONTACT_STEPS);
+        }
+    }
+    return 0.0;
+}
+
+// The blend state multiplies the frame with the color, 1.0 leaves it as it is.
+// Everything the light touches is darkened, ambient light included, so keep the rays short.
+void main() {
+    o_color = vec4(1.0);
+    float depth = load_depth(ivec2(gl_FragCoord.xy));
+    if (depth >= 1.0) {
+        return;
+    }
+
+    vec4 world = inverse(view_projection) * vec4(i_uv.x * 2.0 - 1.0, 1.0 - i_uv.y * 2.0, depth, 1.0);
+    vec3 position = world.xyz / world.w;
+    float offset = interleaved_noise(gl_FragCoord.xy);
+    float shadow = 0.0;
+
+    for (int i = 0; i < int(light_num.x) && i < MAX_LIGHTS / 2; ++i) {
+        DirectionalLight light = directional_lights[i];
+        if (light.direction.w > 0.0) {
+            vec3 L = normalize(light.direction.xyz);
+            shadow = max(shadow, contact_shadow(position, L, light.direction.w, offset));
+        }
+    }
+
+    // Point and spot lights only shadow as much as they light the pixel.
+    float view_depth = -(view * vec4(position, 1.0)).z;
+    uint cluster = cluster_offset(cluster_index(gl_FragCoord.xy, view_depth));
+    uint cluster_light_count = cluster_lights[cluster];
+    for (uint i = 0; i < cluster_light_count; ++i) {
+        Light light = lights[cluster_lights[cluster + 1 + i]];
+        if (light.spot.y <= 0.0) {
+            continue;
+        }
+        vec3 to_light = light.position.xyz - position;
+        float distance = length(to_light);
+        vec3 L = to_light / max(distance, 0.0001);
+        float attenuation = light_falloff(distance, light.position.w);
+        if (light.color.w > 0.5) {
+            attenuation *= spot_falloff(light, L);
+        }
+        float luminance = dot(light.color.rgb, vec3(0.2126, 0.7152, 0.0722));
+        float strength = clamp(attenuation * luminance, 0.0, 1.0);
+        if (strength > 0.0) {
+            float ray_length = min(light.spot.y, distance);
+            shadow = max(shadow, contact_shadow(position, L, ray_length, offset) * strength);
+        }
+    }
+
+    o_color = vec4(vec3(1.0 - shadow), 1.0);
+}
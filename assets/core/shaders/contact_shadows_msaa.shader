@@ -0,0 +1,2 @@
+post/post_vert.glsl
+contact_shadows_msaa_frag.glsl
\ No newline at end of file
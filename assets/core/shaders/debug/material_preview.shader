@@ -0,0 +1,2 @@
+../post/post_vert.glsl
+material_preview_frag.glsl
\ No newline at end of file
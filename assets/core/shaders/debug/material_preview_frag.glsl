@@ -0,0 +1,43 @@
+#version 450
+
+#include "preview.glsl"
+
+// A sphere lit by a single light, close enough to the PBR shader to spot broken textures and
+// values.
+void main() {
+    vec2 position = vec2(i_uv.x, 1.0 - i_uv.y) * 2.0 - 1.0;
+    float radius = dot(position, position);
+    if (radius > 1.0) {
+        outColor = vec4(checkerboard(), 1.0);
+        return;
+    }
+    vec3 n = vec3(position, sqrt(1.0 - radius));
+    vec2 uv = vec2(atan(n.x, n.z) / (2.0 * PI) + 0.5, acos(n.y) / PI);
+    uv = uv * uv_transform.zw + uv_transform.xy;
+    vec4 base = texture(sampler2D(t_preview, s_preview), uv) * color;
+
+    float metallic = clamp(info.x, 0.0, 1.0);
+    float roughness = clamp(info.y, 0.04, 1.0);
+    vec3 v = vec3(0.0, 0.0, 1.0);
+    vec3 l = normalize(vec3(-0.5, 0.7, 0.6));
+    vec3 h = normalize(l + v);
+    float n_dot_l = max(dot(n, l), 0.0);
+    float n_dot_v = max(dot(n, v), 0.0001);
+    float n_dot_h = max(dot(n, h), 0.0);
+    float v_dot_h = max(dot(v, h), 0.0);
+
+    vec3 f0 = mix(vec3(0.04), base.rgb, metallic);
+    float a2 = pow(roughness, 4.0);
+    float d = a2 / (PI * pow(n_dot_h * n_dot_h * (a2 - 1.0) + 1.0, 2.0));
+    float k = pow(roughness + 1.0, 2.0) / 8.0;
+    float g = n_dot_l / (n_dot_l * (1.0 - k) + k) * n_dot_v / (n_dot_v * (1.0 - k) + k);
+    vec3 f = f0 + (1.0 - f0) * pow(1.0 - v_dot_h, 5.0);
+    vec3 specular = d * g * f / max(4.0 * n_dot_l * n_dot_v, 0.0001);
+    vec3 diffuse = (1.0 - f) * (1.0 - metallic) * base.rgb / PI;
+    vec3 ambient = mix(base.rgb * (1.0 - metallic), f0, 0.5) * 0.15;
+
+    vec3 lit = (diffuse + specular) * n_dot_l * 3.0 + ambient + emissive.rgb;
+    // Keeps bright highlights from clipping.
+    lit = lit / (1.0 + lit);
+    outColor = vec4(mix(checkerboard(), lit, base.a), 1.0);
+}
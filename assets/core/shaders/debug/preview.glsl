@@ -0,0 +1,23 @@
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform Preview {
+    // (mip, decode sRGB, single channel or -1, unused)
+    vec4 settings;
+    vec4 channels;
+    // The material's values, only used by the material preview.
+    vec4 color;
+    vec4 info;
+    vec4 emissive;
+    vec4 uv_transform;
+};
+layout(set = 0, binding = 1) uniform sampler s_preview;
+layout(set = 0, binding = 2) uniform texture2D t_preview;
+
+const float PI = 3.14159265359;
+
+// Transparent parts are shown over a checkerboard.
+vec3 checkerboard() {
+    vec2 cell = floor(gl_FragCoord.xy / 16.0);
+    return vec3(mod(cell.x + cell.y, 2.0) > 0.5 ? 0.4 : 0.2);
+}
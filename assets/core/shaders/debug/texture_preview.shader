@@ -0,0 +1,2 @@
+../post/post_vert.glsl
+texture_preview_frag.glsl
\ No newline at end of file
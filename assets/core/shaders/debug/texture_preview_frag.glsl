@@ -0,0 +1,23 @@
+#version 450
+
+#include "preview.glsl"
+
+vec3 srgb_to_linear(vec3 color) {
+    vec3 low = color / 12.92;
+    vec3 high = pow((color + 0.055) / 1.055, vec3(2.4));
+    return mix(low, high, step(0.04045, color));
+}
+
+void main() {
+    vec4 texel = textureLod(sampler2D(t_preview, s_preview), i_uv, settings.x);
+    // Shows linear data the way it's stored instead of the way shaders read it.
+    if (settings.y > 0.5) {
+        texel.rgb = srgb_to_linear(clamp(texel.rgb, 0.0, 1.0));
+    }
+    if (settings.z >= 0.0) {
+        outColor = vec4(vec3(texel[int(settings.z)]), 1.0);
+        return;
+    }
+    float alpha = mix(1.0, texel.a, channels.a);
+    outColor = vec4(mix(checkerboard(), texel.rgb * channels.rgb, alpha), 1.0);
+}
@@ -0,0 +1 @@
+deformation_comp.glsl
\ No newline at end of file
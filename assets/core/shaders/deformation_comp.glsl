@@ -0,0 +1,46 @@
+#version 450
+
+// These need to match src/graphics/deformation.rs
+#define MAX_DEFORMERS 16
+
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+struct Deformer {
+    // (position, radius)
+    vec4 position;
+    // (depth, unused..)
+    vec4 info;
+};
+
+layout(set = 0, binding = 0) uniform Deformation {
+    // (center x, center z, half width, half depth)
+    vec4 surface;
+    // (deformer count, max depth, depth recovered this frame, unused)
+    vec4 info;
+    Deformer deformers[MAX_DEFORMERS];
+};
+
+// How deep the surface is pressed in.
+layout(set = 0, binding = 1, r32f) uniform image2D heightfield;
+
+void main() {
+    ivec2 size = imageSize(heightfield);
+    ivec2 texel = ivec2(gl_GlobalInvocationID.xy);
+    if (texel.x >= size.x || texel.y >= size.y) {
+        return;
+    }
+    vec2 uv = (vec2(texel) + 0.5) / vec2(size);
+    vec2 position = surface.xy + (uv * 2.0 - 1.0) * surface.zw;
+
+    // Tracks slowly fill back in.
+    float depth = max(imageLoad(heightfield, texel).r - info.z, 0.0);
+    for (int i = 0; i < int(info.x); ++i) {
+        Deformer deformer = deformers[i];
+        float radius = deformer.position.w;
+        float distance = length(position - deformer.position.xz);
+        // Round footprints with soft edges.
+        float footprint = 1.0 - smoothstep(radius * 0.5, radius, distance);
+        depth = max(depth, deformer.info.x * footprint);
+    }
+    imageStore(heightfield, texel, vec4(min(depth, info.y), 0.0, 0.0, 0.0));
+}
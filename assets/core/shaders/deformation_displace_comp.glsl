@@ -0,0 +1,89 @@
+#version 450
+
+layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+// MeshVertexData is 12 floats, (position, normal, uv, tangent).
+const uint VERTEX_STRIDE = 12;
+// AttributeVertexData is 6 floats, (color, uv1).
+const uint ATTRIBUTE_STRIDE = 6;
+
+layout(set = 0, binding = 0) uniform Displace {
+    mat4 world;
+    mat4 inverse_world;
+    // (center x, center z, half width, half depth)
+    vec4 surface;
+    vec4 trampled_color;
+    // (max depth, vertex count, unused..)
+    vec4 info;
+};
+
+// Vec3s aren't padded in vertex buffers so they're read as floats.
+layout(std430, set = 0, binding = 1) readonly buffer RestVertices {
+    float rest_vertices[];
+};
+layout(std430, set = 0, binding = 2) buffer Vertices {
+    float vertices[];
+};
+layout(std430, set = 0, binding = 3) readonly buffer RestAttributes {
+    float rest_attributes[];
+};
+layout(std430, set = 0, binding = 4) buffer Attributes {
+    float attributes[];
+};
+layout(set = 0, binding = 5, r32f) uniform readonly image2D heightfield;
+
+// Nothing outside of the heightfield is pressed in.
+float depth_at(vec2 position) {
+    vec2 uv = (position - surface.xy) / max(surface.zw, vec2(0.0001)) * 0.5 + 0.5;
+    if (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+        return 0.0;
+    }
+    ivec2 size = imageSize(heightfield);
+    ivec2 texel = clamp(ivec2(uv * vec2(size)), ivec2(0), size - 1);
+    return imageLoad(heightfield, texel).r;
+}
+
+void main() {
+    uint index = gl_GlobalInvocationID.x;
+    if (index >= uint(info.y)) {
+        return;
+    }
+
+    uint v = index * VERTEX_STRIDE;
+    for (uint i = 0; i < VERTEX_STRIDE; ++i) {
+        vertices[v + i] = rest_vertices[v + i];
+    }
+    vec3 position = vec3(rest_vertices[v], rest_vertices[v + 1], rest_vertices[v + 2]);
+    vec3 normal = vec3(rest_vertices[v + 3], rest_vertices[v + 4], rest_vertices[v + 5]);
+
+    vec3 world_position = (world * vec4(position, 1.0)).xyz;
+    float depth = depth_at(world_position.xz);
+
+    // The slope of the heightfield tilts the normals into the tracks.
+    vec2 texel_size = surface.zw * 2.0 / vec2(imageSize(heightfield));
+    vec2 x = vec2(texel_size.x, 0.0);
+    vec2 z = vec2(0.0, texel_size.y);
+    vec2 slope = vec2(
+        depth_at(world_position.xz + x) - depth_at(world_position.xz - x),
+        depth_at(world_position.xz + z) - depth_at(world_position.xz - z)
+    ) / (texel_size * 2.0);
+    vec3 world_normal = normalize(mat3(world) * normal) + vec3(slope.x, 0.0, slope.y);
+
+    world_position.y -= depth;
+    position = (inverse_world * vec4(world_position, 1.0)).xyz;
+    normal = normalize(transpose(mat3(world)) * world_normal);
+    vertices[v] = position.x;
+    vertices[v + 1] = position.y;
+    vertices[v + 2] = position.z;
+    vertices[v + 3] = normal.x;
+    vertices[v + 4] = normal.y;
+    vertices[v + 5] = normal.z;
+
+    // The trampled layer shows through where the surface is pressed in.
+    uint a = index * ATTRIBUTE_STRIDE;
+    float trampled = clamp(depth / max(info.x, 0.0001), 0.0, 1.0);
+    vec4 tint = mix(vec4(1.0), trampled_color, trampled);
+    for (uint i = 0; i < ATTRIBUTE_STRIDE; ++i) {
+        attributes[a + i] = rest_attributes[a + i] * (i < 4 ? tint[i] : 1.0);
+    }
+}
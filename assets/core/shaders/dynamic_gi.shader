@@ -0,0 +1 @@
+dynamic_gi_comp.glsl
\ No newline at end of file
@@ -0,0 +1,52 @@
+#version 450
+
+// Turns the cube map a dynamic GI probe rendered into the six sides of it's ambient cube,
+// see library/dynamic_gi.glsl and src/graphics/dynamic_gi.rs.
+
+layout(local_size_x = 6, local_size_y = 1, local_size_z = 1) in;
+
+layout(set = 0, binding = 0) uniform ProbeUpdate {
+    // (probe index, hysteresis, unused, unused)
+    vec4 info;
+};
+layout(set = 0, binding = 1) uniform textureCube radiance;
+layout(set = 0, binding = 2) uniform sampler radiance_sampler;
+layout(set = 0, binding = 3) buffer Probes {
+    // Six per probe in the order X+ X- Y+ Y- Z+ Z-, w is 1.0 once the probe has been updated.
+    vec4 probes[];
+};
+
+const uint SAMPLES = 256u;
+const float PI = 3.14159265359;
+
+// Spreads the samples evenly over the sphere.
+vec3 sphere_direction(uint i) {
+    float y = 1.0 - (float(i) + 0.5) / float(SAMPLES) * 2.0;
+    float radius = sqrt(1.0 - y * y);
+    float phi = PI * (3.0 - sqrt(5.0)) * float(i);
+    return vec3(cos(phi) * radius, y, sin(phi) * radius);
+}
+
+void main() {
+    uint side = gl_LocalInvocationID.x;
+    vec3 normal = vec3(0.0);
+    normal[side / 2u] = side % 2u == 0u ? 1.0 : -1.0;
+
+    vec3 irradiance = vec3(0.0);
+    for (uint i = 0u; i < SAMPLES; ++i) {
+        vec3 direction = sphere_direction(i);
+        float cos_theta = dot(normal, direction);
+        if (cos_theta > 0.0) {
+            irradiance += textureLod(samplerCube(radiance, radiance_sampler), direction, 0.0).rgb
+                * cos_theta;
+        }
+    }
+    // The same scale as the irradiance cube maps, a white sky gives 1.0.
+    irradiance *= 4.0 / float(SAMPLES);
+
+    uint index = uint(info.x) * 6u + side;
+    vec4 previous = probes[index];
+    // A probe's first update doesn't have anything to blend with.
+    float hysteresis = previous.w > 0.0 ? info.y : 0.0;
+    probes[index] = vec4(mix(irradiance, previous.rgb, hysteresis), 1.0);
+}
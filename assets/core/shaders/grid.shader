@@ -0,0 +1,2 @@
+grid_vert.glsl
+grid_frag.glsl
\ No newline at end of file
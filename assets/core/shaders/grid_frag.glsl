@@ -0,0 +1,70 @@
+#version 450
+
+layout(location = 0) in vec3 v_near;
+layout(location = 1) in vec3 v_far;
+layout(location = 0) out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform Globals {
+    mat4 view_projection;
+    vec4 camera_pos;
+    mat4 view;
+    mat4 projection;
+};
+
+layout(set = 1, binding = 0) uniform Grid {
+    vec4 minor_color;
+    vec4 major_color;
+    // height, cell size, cells per major line, fade distance
+    vec4 params;
+    vec4 flags;
+};
+
+// 1.0 on a line and 0.0 away from it, about a pixel wide at any distance.
+float grid_lines(vec2 coord, out vec2 derivative) {
+    derivative = fwidth(coord);
+    vec2 grid = abs(fract(coord - 0.5) - 0.5) / derivative;
+    return 1.0 - min(min(grid.x, grid.y), 1.0);
+}
+
+void main() {
+    vec3 ray = v_far - v_near;
+    float t = (params.x - v_near.y) / ray.y;
+    if (t <= 0.0 || abs(ray.y) < 0.000001) {
+        discard;
+    }
+    vec3 position = v_near + ray * t;
+
+    vec4 clip = view_projection * vec4(position, 1.0);
+    gl_FragDepth = clip.z / clip.w;
+
+    vec2 minor_derivative;
+    vec2 major_derivative;
+    float minor = grid_lines(position.xz / params.y, minor_derivative);
+    float major = grid_lines(position.xz / (params.y * params.z), major_derivative);
+    // Once cells are only a few pixels wide the lines turn into noise.
+    minor *= 1.0 - smoothstep(0.2, 0.5, max(minor_derivative.x, minor_derivative.y));
+    major *= 1.0 - smoothstep(0.2, 0.5, max(major_derivative.x, major_derivative.y));
+
+    vec4 color = minor_color;
+    color.a *= minor;
+    if (major > 0.0) {
+        color = mix(color, major_color, major);
+        color.a = max(minor_color.a * minor, major_color.a * major);
+    }
+    if (flags.x > 0.5) {
+        vec2 axis_width = major_derivative * params.y * params.z;
+        if (abs(position.z) < axis_width.y) {
+            color = vec4(0.9, 0.2, 0.2, major_color.a);
+        }
+        if (abs(position.x) < axis_width.x) {
+            color = vec4(0.2, 0.4, 0.9, major_color.a);
+        }
+    }
+
+    float distance = length(position.xz - camera_pos.xz);
+    color.a *= 1.0 - smoothstep(params.w * 0.5, params.w, distance);
+    if (color.a <= 0.001) {
+        discard;
+    }
+    outColor = color;
+}
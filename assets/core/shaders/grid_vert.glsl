@@ -0,0 +1,31 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform Globals {
+    mat4 view_projection;
+    vec4 camera_pos;
+    mat4 view;
+    mat4 projection;
+};
+
+layout(location = 0) out vec3 v_near;
+layout(location = 1) out vec3 v_far;
+
+vec3 unproject(mat4 inverse_view_projection, vec2 position, float depth) {
+    vec4 world = inverse_view_projection * vec4(position, depth, 1.0);
+    return world.xyz / world.w;
+}
+
+void main() {
+    vec2 pos = vec2(0.0);
+    switch(gl_VertexIndex) {
+        case 0: pos = vec2(-1.0, -1.0); break;
+        case 1: pos = vec2( 3.0, -1.0); break;
+        case 2: pos = vec2(-1.0,  3.0); break;
+    }
+    // The fragment shader follows the ray between these to the grid plane.
+    mat4 inverse_view_projection = inverse(view_projection);
+    // Our projections map depth from -1 to 1.
+    v_near = unproject(inverse_view_projection, pos, -1.0);
+    v_far = unproject(inverse_view_projection, pos, 1.0);
+    gl_Position = vec4(pos, 0.0, 1.0);
+}
@@ -0,0 +1,37 @@
+#ifndef AO_VOLUME_INCLUDES
+#define AO_VOLUME_INCLUDES
+
+// Include cookies.glsl first, the volume is sampled with the cookie sampler.
+
+// This needs to match AoVolumeUniform in src/graphics/ao_volume.rs.
+layout(set = 1, binding = 14) uniform AoVolume {
+    // (origin, intensity), the intensity is 0.0 when it's off.
+    vec4 ao_origin;
+    // (size, unused)
+    vec4 ao_size;
+    // (voxels along x, y and z, MAX_AO_VOXELS)
+    vec4 ao_counts;
+};
+
+// Baked volumes only fill the corner of the texture, ao_counts says how much of it.
+layout(set = 1, binding = 15) uniform texture3D ao_volume;
+
+// How much of the ambient light reaches a surface, 1.0 outside of the volume.
+float ao_volume_occlusion(vec3 position, vec3 N) {
+    if (ao_origin.w <= 0.0) {
+        return 1.0;
+    }
+    vec3 voxel = ao_size.xyz / ao_counts.xyz;
+    // The surface's own voxels are solid, so it's sampled a voxel out.
+    position += N * max(voxel.x, max(voxel.y, voxel.z));
+    vec3 local = (position - ao_origin.xyz) / ao_size.xyz;
+    if (any(lessThan(local, vec3(0.0))) || any(greaterThan(local, vec3(1.0)))) {
+        return 1.0;
+    }
+    // Keep the filtering inside the baked voxels.
+    vec3 texel = clamp(local * ao_counts.xyz, vec3(0.5), ao_counts.xyz - 0.5);
+    float visibility = texture(sampler3D(ao_volume, cookie_sampler), texel / ao_counts.w).r;
+    return clamp(mix(1.0, visibility, ao_origin.w), 0.0, 1.0);
+}
+
+#endif
@@ -0,0 +1,87 @@
+#ifndef CLUSTER_INCLUDES
+#define CLUSTER_INCLUDES
+
+// These need to match src/graphics/light_clusters.rs
+const uint CLUSTER_X = 16;
+const uint CLUSTER_Y = 9;
+const uint CLUSTER_Z = 24;
+const uint MAX_LIGHTS_PER_CLUSTER = 64;
+
+// The light culling compute pass binds these to set 0 and writes the cluster lights.
+#ifndef CLUSTER_SET
+#define CLUSTER_SET 1
+#endif
+#ifndef CLUSTER_ACCESS
+#define CLUSTER_ACCESS readonly
+#endif
+
+struct Light {
+    // (position, range)
+    vec4 position;
+    // (color * intensity, 0 for point lights and 1 for spot lights)
+    vec4 color;
+    // (direction, cos of the outer angle)
+    vec4 direction;
+    // (cos of the inner angle, contact shadow length or 0.0, shadow tile or -1.0, unused)
+    vec4 spot;
+    // (the light's right axis, first cookie layer or -1.0)
+    vec4 cookie;
+};
+
+layout(std430, set = CLUSTER_SET, binding = 6) readonly buffer Lights {
+    Light lights[];
+};
+
+layout(set = CLUSTER_SET, binding = 7) uniform ClusterData {
+    mat4 cluster_inverse_projection;
+    mat4 cluster_view;
+    // (viewport width, viewport height, near, far)
+    vec4 cluster_screen;
+    // (light count, viewport x, viewport y, unused)
+    vec4 cluster_counts;
+};
+
+// Each cluster stores it's light count followed by MAX_LIGHTS_PER_CLUSTER light indices.
+layout(std430, set = CLUSTER_SET, binding = 8) CLUSTER_ACCESS buffer ClusterLights {
+    uint cluster_lights[];
+};
+
+// Depth slices are exponential so clusters stay roughly cube shaped.
+uint cluster_slice(float view_depth) {
+    float near = cluster_screen.z;
+    float far = cluster_screen.w;
+    float slice = log(max(view_depth, near) / near) / log(far / near) * float(CLUSTER_Z);
+    return min(uint(max(slice, 0.0)), CLUSTER_Z - 1);
+}
+
+float cluster_slice_depth(uint slice) {
+    float near = cluster_screen.z;
+    float far = cluster_screen.w;
+    return near * pow(far / near, float(slice) / float(CLUSTER_Z));
+}
+
+uint cluster_index(vec2 frag_coord, float view_depth) {
+    // Split-screen viewports don't start at the corner of the screen.
+    vec2 tile = (frag_coord - cluster_counts.yz) / cluster_screen.xy * vec2(CLUSTER_X, CLUSTER_Y);
+    uint x = min(uint(max(tile.x, 0.0)), CLUSTER_X - 1);
+    uint y = min(uint(max(tile.y, 0.0)), CLUSTER_Y - 1);
+    return x + y * CLUSTER_X + cluster_slice(view_depth) * CLUSTER_X * CLUSTER_Y;
+}
+
+uint cluster_offset(uint cluster) {
+    return cluster * (MAX_LIGHTS_PER_CLUSTER + 1);
+}
+
+// Smoothly fades the light out to nothing at it's range.
+float light_falloff(float distance, float range) {
+    float ratio = distance / range;
+    float window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return window * window / (distance * distance + 1.0);
+}
+
+float spot_falloff(Light light, vec3 L) {
+    float cos_angle = dot(-L, light.direction.xyz);
+    return smoothstep(light.direction.w, light.spot.x, cos_angle);
+}
+
+#endif
@@ -0,0 +1,52 @@
+#ifndef COOKIE_INCLUDES
+#define COOKIE_INCLUDES
+
+// Include clusters.glsl first, it has the Light struct.
+
+// Spot light cookies take one layer and point light cookies six, one per cube face.
+layout(set = 1, binding = 10) uniform texture2DArray light_cookies;
+layout(set = 1, binding = 11) uniform sampler cookie_sampler;
+
+// The face and uv a direction points at, the same layout as face_coordinates in
+// src/graphics/cubemap.rs.
+vec3 cookie_cube_uv(vec3 direction) {
+    vec3 a = abs(direction);
+    vec4 face;
+    if (a.x >= a.y && a.x >= a.z) {
+        face = direction.x > 0.0 ? vec4(0.0, -direction.z, -direction.y, a.x)
+                                 : vec4(1.0, direction.z, -direction.y, a.x);
+    } else if (a.y >= a.z) {
+        face = direction.y > 0.0 ? vec4(2.0, direction.x, direction.z, a.y)
+                                 : vec4(3.0, direction.x, -direction.z, a.y);
+    } else {
+        face = direction.z > 0.0 ? vec4(4.0, direction.x, -direction.y, a.z)
+                                 : vec4(5.0, -direction.x, -direction.y, a.z);
+    }
+    return vec3((face.yz / max(face.w, 0.000001) + 1.0) * 0.5, face.x);
+}
+
+// Tints the light reaching a point, L points from the point to the light.
+vec3 light_cookie(Light light, vec3 L) {
+    if (light.cookie.w < 0.0) {
+        return vec3(1.0);
+    }
+    vec3 forward = light.direction.xyz;
+    vec3 right = light.cookie.xyz;
+    vec3 up = cross(right, forward);
+    // The direction from the light in it's own space, it looks down -Z.
+    vec3 local = vec3(dot(-L, right), dot(-L, up), dot(-L, -forward));
+    if (light.color.w > 0.5) {
+        // Spot light cookies cover the outer cone.
+        float cos_outer = light.direction.w;
+        float tan_outer = sqrt(max(1.0 - cos_outer * cos_outer, 0.0)) / max(cos_outer, 0.0001);
+        vec2 projected = local.xy / max(-local.z, 0.0001) / tan_outer;
+        vec2 uv = vec2(projected.x, -projected.y) * 0.5 + 0.5;
+        vec3 coordinates = vec3(uv, light.cookie.w);
+        return texture(sampler2DArray(light_cookies, cookie_sampler), coordinates).rgb;
+    }
+    vec3 cube = cookie_cube_uv(local);
+    vec3 coordinates = vec3(cube.xy, light.cookie.w + cube.z);
+    return texture(sampler2DArray(light_cookies, cookie_sampler), coordinates).rgb;
+}
+
+#endif
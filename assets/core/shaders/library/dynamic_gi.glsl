@@ -0,0 +1,62 @@
+#ifndef DYNAMIC_GI_INCLUDES
+#define DYNAMIC_GI_INCLUDES
+
+// This needs to match DynamicGIUniform in src/graphics/dynamic_gi.rs.
+layout(set = 1, binding = 12) uniform DynamicGI {
+    // (origin, intensity), the intensity is 0.0 when it's off.
+    vec4 gi_origin;
+    // (spacing, unused)
+    vec4 gi_spacing;
+    // (probes along x, y and z, unused)
+    vec4 gi_counts;
+};
+
+// Six per probe in the order X+ X- Y+ Y- Z+ Z-, w is 1.0 once the probe has been updated.
+layout(set = 1, binding = 13) readonly buffer DynamicGIProbes {
+    vec4 gi_probes[];
+};
+
+// The irradiance of a probe's ambient cube facing N, w is 1.0 once the probe has been updated.
+vec4 gi_probe_irradiance(int probe, vec3 N) {
+    int first = probe * 6;
+    vec3 weights = N * N;
+    vec4 x = gi_probes[first + (N.x < 0.0 ? 1 : 0)];
+    vec4 y = gi_probes[first + (N.y < 0.0 ? 3 : 2)];
+    vec4 z = gi_probes[first + (N.z < 0.0 ? 5 : 4)];
+    return vec4(x.rgb * weights.x + y.rgb * weights.y + z.rgb * weights.z, x.w);
+}
+
+// Blends the eight probes around the position. `fallback` is used outside the grid and
+// faded into where the probes haven't been updated yet.
+vec3 dynamic_gi_irradiance(vec3 position, vec3 N, vec3 fallback) {
+    if (gi_origin.w <= 0.0) {
+        return fallback;
+    }
+    ivec3 counts = ivec3(gi_counts.xyz);
+    vec3 grid = (position - gi_origin.xyz) / gi_spacing.xyz;
+    // A single layer of probes covers everything along that axis.
+    grid = mix(grid, vec3(0.0), lessEqual(counts, ivec3(1)));
+    if (any(lessThan(grid, vec3(0.0))) || any(greaterThan(grid, vec3(counts - 1)))) {
+        return fallback;
+    }
+
+    ivec3 base = min(ivec3(grid), max(counts - 2, ivec3(0)));
+    vec3 t = clamp(grid - vec3(base), 0.0, 1.0);
+    vec3 irradiance = vec3(0.0);
+    float total = 0.0;
+    for (int i = 0; i < 8; ++i) {
+        ivec3 offset = ivec3(i & 1, (i >> 1) & 1, (i >> 2) & 1);
+        ivec3 cell = min(base + offset, counts - 1);
+        vec3 weights = mix(1.0 - t, t, vec3(offset));
+        vec4 probe = gi_probe_irradiance(cell.x + (cell.y + cell.z * counts.y) * counts.x, N);
+        float weight = weights.x * weights.y * weights.z * probe.w;
+        irradiance += probe.rgb * weight;
+        total += weight;
+    }
+    if (total < 0.0001) {
+        return fallback;
+    }
+    return mix(fallback, irradiance / total * gi_origin.w, clamp(total, 0.0, 1.0));
+}
+
+#endif
@@ -0,0 +1,30 @@
+#ifndef OCEAN_INCLUDES
+#define OCEAN_INCLUDES
+
+// This needs to match src/graphics/ocean.rs
+#define OCEAN_FFT_SIZE 256
+
+layout(set = 0, binding = 0) uniform Ocean {
+    // (sea height, patch size, choppiness, time)
+    vec4 surface;
+    // (foam threshold, foam left this frame, wind x, wind z)
+    vec4 foam;
+    vec4 deep_color;
+    vec4 scatter_color;
+    vec4 horizon_color;
+    vec4 sky_color;
+    vec4 foam_color;
+    // (grid resolution, unused..)
+    vec4 info;
+};
+
+vec2 complex_mul(vec2 a, vec2 b) {
+    return vec2(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
+}
+
+// Multiplies by i.
+vec2 complex_i(vec2 a) {
+    return vec2(-a.y, a.x);
+}
+
+#endif
@@ -0,0 +1,168 @@
+#ifndef SHADOW_INCLUDES
+#define SHADOW_INCLUDES
+
+const int SHADOW_CASCADES = 4;
+const float SHADOW_MAP_SIZE = 2048.0;
+// Spot lights share the layer after the cascades, each one gets a quarter of it.
+const int MAX_SPOT_SHADOWS = 4;
+
+layout(set = 1, binding = 2) uniform ShadowData {
+    mat4 shadow_cascades[SHADOW_CASCADES];
+    // The view space distance where each cascade ends.
+    vec4 shadow_splits;
+    // (depth bias, slope bias, normal bias, light index or -1 when there are no shadows)
+    vec4 shadow_bias;
+    // (filter: 0 = hard, 1 = pcf, 2 = pcss, kernel size or filter samples, light size, blocker samples)
+    vec4 shadow_filter;
+    mat4 spot_shadow_matrices[MAX_SPOT_SHADOWS];
+    // (depth bias, slope bias, normal bias, pcf kernel size) of each spot light.
+    vec4 spot_shadow_bias[MAX_SPOT_SHADOWS];
+};
+
+layout(set = 1, binding = 3) uniform texture2DArray shadow_map;
+layout(set = 1, binding = 4) uniform samplerShadow shadow_compare_sampler;
+layout(set = 1, binding = 5) uniform sampler shadow_depth_sampler;
+
+float shadow_compare(vec2 uv, int cascade, float depth) {
+    return texture(sampler2DArrayShadow(shadow_map, shadow_compare_sampler), vec4(uv, float(cascade), depth));
+}
+
+// Evenly spreads samples over a disk, phi rotates the disk per pixel to hide banding.
+vec2 vogel_disk(int index, int count, float phi) {
+    float r = sqrt((float(index) + 0.5) / float(count));
+    float theta = float(index) * 2.4 + phi;
+    return r * vec2(cos(theta), sin(theta));
+}
+
+float interleaved_gradient_noise(vec2 position) {
+    return fract(52.9829189 * fract(dot(position, vec2(0.06711056, 0.00583715))));
+}
+
+float shadow_pcf(vec2 uv, int cascade, float depth, int kernel_size) {
+    float texel = 1.0 / SHADOW_MAP_SIZE;
+    float half_kernel = float(kernel_size - 1) * 0.5;
+    float sum = 0.0;
+    for (int x = 0; x < kernel_size; ++x) {
+        for (int y = 0; y < kernel_size; ++y) {
+            vec2 offset = (vec2(float(x), float(y)) - half_kernel) * texel;
+            sum += shadow_compare(uv + offset, cascade, depth);
+        }
+    }
+    return sum / float(kernel_size * kernel_size);
+}
+
+float shadow_pcss(vec2 uv, int cascade, float depth) {
+    int filter_samples = int(shadow_filter.y);
+    float light_size = shadow_filter.z;
+    int blocker_samples = int(shadow_filter.w);
+
+    // Cascades are orthographic so we can pull the scale straight out of the matrix.
+    float world_to_uv = 0.5 * shadow_cascades[cascade][0][0];
+    float depth_to_world = 1.0 / abs(shadow_cascades[cascade][2][2]);
+    float phi = interleaved_gradient_noise(gl_FragCoord.xy) * 6.28318530718;
+
+    // Find the average depth of anything blocking the light.
+    float search_radius = light_size * world_to_uv;
+    float blocker_depth = 0.0;
+    int blockers = 0;
+    for (int i = 0; i < blocker_samples; ++i) {
+        vec2 offset = vogel_disk(i, blocker_samples, phi) * search_radius;
+        float sample_depth = texture(sampler2DArray(shadow_map, shadow_depth_sampler), vec3(uv + offset, float(cascade))).r;
+        if (sample_depth < depth) {
+            blocker_depth += sample_depth;
+            blockers += 1;
+        }
+    }
+    if (blockers == 0) {
+        return 1.0;
+    }
+    blocker_depth /= float(blockers);
+
+    // The further the receiver is from the blocker the wider the penumbra.
+    float penumbra = (depth - blocker_depth) * depth_to_world * light_size * world_to_uv;
+    penumbra = max(penumbra, 1.0 / SHADOW_MAP_SIZE);
+    float sum = 0.0;
+    for (int i = 0; i < filter_samples; ++i) {
+        vec2 offset = vogel_disk(i, filter_samples, phi) * penumbra;
+        sum += shadow_compare(uv + offset, cascade, depth);
+    }
+    return sum / float(filter_samples);
+}
+
+// Returns how lit a surface is by the shadow casting directional light, 1.0 is fully lit.
+float shadow_factor(vec3 world_pos, vec3 normal, float NdotL, float view_depth) {
+    int cascade = -1;
+    for (int i = 0; i < SHADOW_CASCADES; ++i) {
+        if (view_depth < shadow_splits[i]) {
+            cascade = i;
+            break;
+        }
+    }
+    if (cascade < 0) {
+        return 1.0;
+    }
+
+    // Push the lookup out along the normal, more so at grazing angles.
+    vec3 offset_pos = world_pos + normal * shadow_bias.z * (1.0 - NdotL);
+    vec4 light_space = shadow_cascades[cascade] * vec4(offset_pos, 1.0);
+    vec3 coords = light_space.xyz / light_space.w;
+    if (coords.z > 1.0) {
+        return 1.0;
+    }
+    vec2 uv = vec2(coords.x * 0.5 + 0.5, 0.5 - coords.y * 0.5);
+
+    float slope = sqrt(max(1.0 - NdotL * NdotL, 0.0)) / max(NdotL, 0.05);
+    float depth = coords.z - shadow_bias.x * (1.0 + shadow_bias.y * min(slope, 10.0));
+
+    // The reduced render path only takes a single hardware filtered sample.
+#ifndef REDUCED
+    if (shadow_filter.x > 1.5) {
+        return shadow_pcss(uv, cascade, depth);
+    } else if (shadow_filter.x > 0.5) {
+        return shadow_pcf(uv, cascade, depth, int(shadow_filter.y));
+    }
+#endif
+    return shadow_compare(uv, cascade, depth);
+}
+
+// Returns how lit a surface is by a spot light with a shadow tile, 1.0 is fully lit.
+// L points from the surface to the light.
+float spot_shadow_factor(int tile, vec3 world_pos, vec3 normal, vec3 L, float distance, float NdotL) {
+    vec4 bias = spot_shadow_bias[tile];
+    // Perspective depth isn't linear, so the depth bias moves the lookup towards the light by a
+    // fraction of the distance to it instead.
+    float slope = sqrt(max(1.0 - NdotL * NdotL, 0.0)) / max(NdotL, 0.05);
+    vec3 offset_pos = world_pos + normal * bias.z * (1.0 - NdotL)
+        + L * bias.x * distance * (1.0 + bias.y * min(slope, 10.0));
+    vec4 light_space = spot_shadow_matrices[tile] * vec4(offset_pos, 1.0);
+    if (light_space.w <= 0.0) {
+        return 1.0;
+    }
+    vec3 coords = light_space.xyz / light_space.w;
+    if (coords.z > 1.0 || any(greaterThan(abs(coords.xy), vec2(1.0)))) {
+        return 1.0;
+    }
+    vec2 tile_min = vec2(float(tile % 2), float(tile / 2)) * 0.5;
+    vec2 uv = tile_min + vec2(coords.x * 0.5 + 0.5, 0.5 - coords.y * 0.5) * 0.5;
+    // Keep the filter from reading the neighbouring tiles.
+    float texel = 1.0 / SHADOW_MAP_SIZE;
+    vec2 uv_min = tile_min + texel * 0.5;
+    vec2 uv_max = tile_min + 0.5 - texel * 0.5;
+
+#ifdef REDUCED
+    int kernel_size = 1;
+#else
+    int kernel_size = int(bias.w);
+#endif
+    float half_kernel = float(kernel_size - 1) * 0.5;
+    float sum = 0.0;
+    for (int x = 0; x < kernel_size; ++x) {
+        for (int y = 0; y < kernel_size; ++y) {
+            vec2 offset = (vec2(float(x), float(y)) - half_kernel) * texel;
+            sum += shadow_compare(clamp(uv + offset, uv_min, uv_max), SHADOW_CASCADES, coords.z);
+        }
+    }
+    return sum / float(kernel_size * kernel_size);
+}
+
+#endif
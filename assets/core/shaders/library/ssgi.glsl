@@ -0,0 +1,24 @@
+#ifndef SSGI_INCLUDES
+#define SSGI_INCLUDES
+
+layout(set = 0, binding = 0) uniform Ssgi {
+    mat4 view_projection;
+    mat4 inverse_view_projection;
+    mat4 previous_view_projection;
+    // (camera position, intensity)
+    vec4 camera_position;
+    // (radius, samples, steps, history weight)
+    vec4 info;
+    // (frame width, frame height, frame, unused)
+    vec4 screen;
+    // The view's (x, y, width, height) in uv space.
+    vec4 viewport;
+};
+
+// The lit frame when gathering, the gathered light when compositing.
+layout(set = 0, binding = 2) uniform texture2D t_color;
+// Last frame's gathered light.
+layout(set = 0, binding = 3) uniform texture2D t_history;
+layout(set = 0, binding = 4) uniform sampler s_linear;
+
+#endif
@@ -0,0 +1,77 @@
+#ifndef WATER_INCLUDES
+#define WATER_INCLUDES
+
+// This needs to match src/graphics/water.rs
+const int MAX_WATER_VOLUMES = 4;
+
+struct WaterVolume {
+    // The box, w in min is the caustics intensity and in max the wetness height.
+    vec4 box_min;
+    vec4 box_max;
+    // (caustics scale, caustics speed, unused..)
+    vec4 caustics;
+};
+
+layout(set = 1, binding = 9) uniform WaterVolumes {
+    WaterVolume water_volumes[MAX_WATER_VOLUMES];
+    // (volume count, time in seconds, unused..)
+    vec4 water_info;
+};
+
+bool inside_water_column(WaterVolume volume, vec3 position) {
+    return all(greaterThanEqual(position, volume.box_min.xyz))
+        && all(lessThanEqual(position.xz, volume.box_max.xz));
+}
+
+// 1.0 under water, fading out to 0.0 at the wetness height above the surface.
+float water_wetness(vec3 position) {
+    float wetness = 0.0;
+    for (int i = 0; i < int(water_info.x) && i < MAX_WATER_VOLUMES; ++i) {
+        WaterVolume volume = water_volumes[i];
+        if (!inside_water_column(volume, position)) {
+            continue;
+        }
+        float above = position.y - volume.box_max.y;
+        wetness = max(wetness, 1.0 - clamp(above / max(volume.box_max.w, 0.0001), 0.0, 1.0));
+    }
+    return wetness;
+}
+
+// Bright lines where the waves focus the light, a few layers of warped ridges moving
+// against each other.
+float caustic_pattern(vec2 uv, float time) {
+    vec2 p = uv * 6.2831;
+    float pattern = 0.0;
+    for (int i = 0; i < 3; ++i) {
+        float t = time * (1.0 + 0.3 * float(i));
+        p += vec2(sin(t + p.y * 0.7), cos(t * 0.8 + p.x * 0.6));
+        pattern += pow(1.0 - abs(sin(p.x) * sin(p.y)), 8.0);
+    }
+    return pattern / 3.0;
+}
+
+// How much the sun is brightened by caustics at `position`, 1.0 above water. The caustics
+// pass multiplies the frame with it. `light_direction` points towards the light.
+float water_caustics(vec3 position, vec3 light_direction) {
+    float caustics = 1.0;
+    for (int i = 0; i < int(water_info.x) && i < MAX_WATER_VOLUMES; ++i) {
+        WaterVolume volume = water_volumes[i];
+        if (!inside_water_column(volume, position) || position.y > volume.box_max.y) {
+            continue;
+        }
+        // Follow the light up to the surface so the pattern is projected along it.
+        float depth = volume.box_max.y - position.y;
+        vec2 surface = position.xz + light_direction.xz / max(light_direction.y, 0.1) * depth;
+        float pattern = caustic_pattern(
+            surface / max(volume.caustics.x, 0.0001),
+            water_info.y * volume.caustics.y
+        );
+        // Deeper down the light spreads out and the pattern gets softer.
+        float focus = mix(0.5, pattern, 1.0 / (1.0 + depth * 0.25));
+        // Light between the lines goes into them, so the average stays about the same.
+        caustics = 1.0 + volume.box_min.w * (focus * 2.0 - 1.0);
+    }
+    return max(caustics, 0.0);
+}
+
+#endif
@@ -0,0 +1,64 @@
+#version 450
+
+#define CLUSTER_SET 0
+#define CLUSTER_ACCESS
+#include "library/clusters.glsl"
+
+// One workgroup per depth slice.
+layout(local_size_x = 16, local_size_y = 9, local_size_z = 1) in;
+
+vec3 unproject(vec2 ndc, float z) {
+    vec4 view = cluster_inverse_projection * vec4(ndc, z, 1.0);
+    return view.xyz / view.w;
+}
+
+// Where the ray through `ndc` is `depth` in front of the camera, works for orthographic cameras too.
+vec3 point_at_depth(vec2 ndc, float depth) {
+    vec3 near_point = unproject(ndc, -1.0);
+    vec3 far_point = unproject(ndc, 1.0);
+    float t = (depth + near_point.z) / (near_point.z - far_point.z);
+    return mix(near_point, far_point, t);
+}
+
+void main() {
+    uvec3 cluster = gl_GlobalInvocationID;
+    uint index = cluster.x + cluster.y * CLUSTER_X + cluster.z * CLUSTER_X * CLUSTER_Y;
+
+    // Tiles start at the top of the screen but NDC y points up.
+    vec2 tile_size = 2.0 / vec2(CLUSTER_X, CLUSTER_Y);
+    vec2 ndc_min = vec2(-1.0 + float(cluster.x) * tile_size.x, 1.0 - float(cluster.y + 1) * tile_size.y);
+    vec2 ndc_max = ndc_min + tile_size;
+    float depths[2] = float[](cluster_slice_depth(cluster.z), cluster_slice_depth(cluster.z + 1));
+
+    // View space bounds of the cluster.
+    vec3 aabb_min = vec3(1e30);
+    vec3 aabb_max = vec3(-1e30);
+    for (int d = 0; d < 2; ++d) {
+        vec3 corners[4] = vec3[](
+            point_at_depth(ndc_min, depths[d]),
+            point_at_depth(ndc_max, depths[d]),
+            point_at_depth(vec2(ndc_min.x, ndc_max.y), depths[d]),
+            point_at_depth(vec2(ndc_max.x, ndc_min.y), depths[d])
+        );
+        for (int c = 0; c < 4; ++c) {
+            aabb_min = min(aabb_min, corners[c]);
+            aabb_max = max(aabb_max, corners[c]);
+        }
+    }
+
+    uint offset = cluster_offset(index);
+    uint count = 0;
+    uint light_count = uint(cluster_counts.x);
+    for (uint i = 0; i < light_count && count < MAX_LIGHTS_PER_CLUSTER; ++i) {
+        Light light = lights[i];
+        vec3 center = (cluster_view * vec4(light.position.xyz, 1.0)).xyz;
+        vec3 closest = clamp(center, aabb_min, aabb_max);
+        vec3 delta = closest - center;
+        // Spot lights are tested with the sphere around their whole range.
+        if (dot(delta, delta) <= light.position.w * light.position.w) {
+            cluster_lights[offset + 1 + count] = i;
+            count += 1;
+        }
+    }
+    cluster_lights[offset] = count;
+}
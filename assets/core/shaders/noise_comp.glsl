@@ -0,0 +1,113 @@
+#version 450
+
+// Matches src/graphics/noise.rs so CPU and GPU noise look the same.
+
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+layout(set = 0, binding = 0) uniform NoiseSettings {
+    // (kind, seed, octaves, unused), kind 0 = perlin, 1 = simplex, 2 = worley
+    uvec4 settings;
+    // (frequency, lacunarity, gain, unused)
+    vec4 fbm;
+};
+
+layout(set = 0, binding = 1, rgba8) uniform writeonly image2D noise_image;
+
+uint hash(int x, int y, uint seed) {
+    uint h = seed ^ (uint(x) * 0x27d4eb2du) ^ (uint(y) * 0x165667b1u);
+    h = (h ^ (h >> 15)) * 0x2c1b3c6du;
+    h = (h ^ (h >> 12)) * 0x297a2d39u;
+    return h ^ (h >> 15);
+}
+
+float gradient(uint h, vec2 p) {
+    const float D = 0.70710678;
+    const vec2 gradients[8] = vec2[8](
+        vec2(1.0, 0.0), vec2(-1.0, 0.0), vec2(0.0, 1.0), vec2(0.0, -1.0),
+        vec2(D, D), vec2(-D, D), vec2(D, -D), vec2(-D, -D)
+    );
+    return dot(gradients[h & 7u], p);
+}
+
+float perlin(vec2 p, uint seed) {
+    vec2 cell = floor(p);
+    vec2 f = p - cell;
+    ivec2 i = ivec2(cell);
+
+    float n00 = gradient(hash(i.x, i.y, seed), f);
+    float n10 = gradient(hash(i.x + 1, i.y, seed), f - vec2(1.0, 0.0));
+    float n01 = gradient(hash(i.x, i.y + 1, seed), f - vec2(0.0, 1.0));
+    float n11 = gradient(hash(i.x + 1, i.y + 1, seed), f - vec2(1.0, 1.0));
+
+    vec2 u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+    float value = mix(mix(n00, n10, u.x), mix(n01, n11, u.x), u.y) * 1.41421356;
+    return clamp(value, -1.0, 1.0);
+}
+
+float simplex_corner(uint h, vec2 p) {
+    float t = 0.5 - dot(p, p);
+    return t < 0.0 ? 0.0 : t * t * t * t * gradient(h, p);
+}
+
+float simplex(vec2 p, uint seed) {
+    const float SKEW = 0.3660254;
+    const float UNSKEW = 0.21132487;
+
+    vec2 cell = floor(p + (p.x + p.y) * SKEW);
+    vec2 p0 = p - (cell - (cell.x + cell.y) * UNSKEW);
+    ivec2 o = p0.x > p0.y ? ivec2(1, 0) : ivec2(0, 1);
+    vec2 p1 = p0 - vec2(o) + UNSKEW;
+    vec2 p2 = p0 - 1.0 + 2.0 * UNSKEW;
+    ivec2 i = ivec2(cell);
+
+    float value = simplex_corner(hash(i.x, i.y, seed), p0)
+        + simplex_corner(hash(i.x + o.x, i.y + o.y, seed), p1)
+        + simplex_corner(hash(i.x + 1, i.y + 1, seed), p2);
+    return clamp(value * 70.0, -1.0, 1.0);
+}
+
+float worley(vec2 p, uint seed) {
+    ivec2 cell = ivec2(floor(p));
+    float closest = 1e20;
+    for (int y = -1; y <= 1; ++y) {
+        for (int x = -1; x <= 1; ++x) {
+            ivec2 neighbour = cell + ivec2(x, y);
+            uint h = hash(neighbour.x, neighbour.y, seed);
+            vec2 point = vec2(neighbour) + vec2(float(h & 0xffffu), float(h >> 16)) / 65535.0;
+            closest = min(closest, distance(point, p));
+        }
+    }
+    return min(closest, 1.0);
+}
+
+float sample_octave(vec2 p, uint seed) {
+    if (settings.x == 0u) {
+        return perlin(p, seed) * 0.5 + 0.5;
+    } else if (settings.x == 1u) {
+        return simplex(p, seed) * 0.5 + 0.5;
+    }
+    return worley(p, seed);
+}
+
+void main() {
+    ivec2 size = imageSize(noise_image);
+    ivec2 pixel = ivec2(gl_GlobalInvocationID.xy);
+    if (pixel.x >= size.x || pixel.y >= size.y) {
+        return;
+    }
+
+    vec2 position = vec2(pixel) / vec2(size);
+    float frequency = fbm.x;
+    float amplitude = 1.0;
+    float total = 0.0;
+    float weight = 0.0;
+    for (uint octave = 0u; octave < settings.z; ++octave) {
+        total += sample_octave(position * frequency, settings.y + octave) * amplitude;
+        weight += amplitude;
+        frequency *= fbm.y;
+        amplitude *= fbm.z;
+    }
+
+    float value = total / weight;
+    imageStore(noise_image, pixel, vec4(value, value, value, 1.0));
+}
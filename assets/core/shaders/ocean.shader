@@ -0,0 +1,2 @@
+ocean_vert.glsl
+ocean_frag.glsl
\ No newline at end of file
@@ -0,0 +1 @@
+ocean_fft_comp.glsl
\ No newline at end of file
@@ -0,0 +1,74 @@
+#version 450
+
+// This needs to match src/graphics/ocean.rs
+#define OCEAN_FFT_SIZE 256
+#define OCEAN_FFT_BITS 8
+
+// Every thread does two of the butterflies in each step.
+layout(local_size_x = 128, local_size_y = 1, local_size_z = 1) in;
+
+layout(set = 0, binding = 0) uniform FftInfo {
+    // x is 0.0 for rows and 1.0 for columns.
+    vec4 fft_info;
+};
+
+layout(set = 0, binding = 1, rgba32f) uniform image2D spectrum_a;
+layout(set = 0, binding = 2, rgba32f) uniform image2D spectrum_b;
+
+// Two complex numbers per vec4, like the textures.
+shared vec4 line_a[OCEAN_FFT_SIZE];
+shared vec4 line_b[OCEAN_FFT_SIZE];
+
+const float PI = 3.14159265358979323;
+
+vec2 complex_mul(vec2 a, vec2 b) {
+    return vec2(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
+}
+
+vec4 complex_mul2(vec4 a, vec2 b) {
+    return vec4(complex_mul(a.xy, b), complex_mul(a.zw, b));
+}
+
+ivec2 line_texel(uint line, uint index) {
+    return fft_info.x < 0.5 ? ivec2(index, line) : ivec2(line, index);
+}
+
+void main() {
+    uint line = gl_WorkGroupID.x;
+    uint thread = gl_LocalInvocationID.x;
+
+    // Loaded in bit reversed order so the butterflies can work in place.
+    for (uint i = thread; i < OCEAN_FFT_SIZE; i += 128) {
+        uint reversed = bitfieldReverse(i) >> (32 - OCEAN_FFT_BITS);
+        line_a[reversed] = imageLoad(spectrum_a, line_texel(line, i));
+        line_b[reversed] = imageLoad(spectrum_b, line_texel(line, i));
+    }
+    memoryBarrierShared();
+    barrier();
+
+    // An inverse transform, the twiddles turn the other way and there's no scaling.
+    for (uint span = 1; span < OCEAN_FFT_SIZE; span *= 2) {
+        for (uint butterfly = thread; butterfly < OCEAN_FFT_SIZE / 2; butterfly += 128) {
+            uint position = butterfly % span;
+            uint top = (butterfly / span) * span * 2 + position;
+            uint bottom = top + span;
+            float angle = PI * float(position) / float(span);
+            vec2 twiddle = vec2(cos(angle), sin(angle));
+            vec4 top_a = line_a[top];
+            vec4 top_b = line_b[top];
+            vec4 bottom_a = complex_mul2(line_a[bottom], twiddle);
+            vec4 bottom_b = complex_mul2(line_b[bottom], twiddle);
+            line_a[top] = top_a + bottom_a;
+            line_a[bottom] = top_a - bottom_a;
+            line_b[top] = top_b + bottom_b;
+            line_b[bottom] = top_b - bottom_b;
+        }
+        memoryBarrierShared();
+        barrier();
+    }
+
+    for (uint i = thread; i < OCEAN_FFT_SIZE; i += 128) {
+        imageStore(spectrum_a, line_texel(line, i), line_a[i]);
+        imageStore(spectrum_b, line_texel(line, i), line_b[i]);
+    }
+}
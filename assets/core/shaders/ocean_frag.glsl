@@ -0,0 +1,49 @@
+#version 450
+#extension GL_GOOGLE_include_directive : enable
+
+#include "library/common.glsl"
+#include "library/lighting.glsl"
+#include "library/ocean.glsl"
+
+// (normal x, normal z, foam, jacobian)
+layout(set = 0, binding = 2) uniform texture2D normal_map;
+layout(set = 0, binding = 3) uniform sampler ocean_sampler;
+
+layout(location = 0) in vec3 i_position;
+layout(location = 1) in vec2 i_uv;
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec4 normal_foam = texture(sampler2D(normal_map, ocean_sampler), i_uv);
+    vec3 N = normalize(vec3(normal_foam.x, 1.0, normal_foam.y));
+    vec3 V = normalize(camera_pos.xyz - i_position);
+    // From below the surface is seen from the other side.
+    if (camera_pos.y < surface.x) {
+        N = -N;
+    }
+    float NdotV = max(dot(N, V), 0.0);
+    float fresnel = 0.02 + 0.98 * pow(1.0 - NdotV, 5.0);
+
+    // There's no environment to reflect, the sky is a gradient from the horizon up.
+    vec3 R = reflect(-V, N);
+    vec3 sky = mix(horizon_color.rgb, sky_color.rgb, sqrt(clamp(abs(R.y), 0.0, 1.0)));
+
+    // Crests are thin enough for light to get through.
+    float crest = clamp((i_position.y - surface.x) * 0.25, 0.0, 1.0);
+    vec3 water = deep_color.rgb + scatter_color.rgb * crest;
+
+    vec3 light = vec3(0.0);
+    vec3 specular = vec3(0.0);
+    if (light_num.x > 0.0) {
+        // Only the sun, the first directional light, lights the ocean.
+        DirectionalLight sun = get_directional_light(0);
+        vec3 L = normalize(sun.direction.xyz);
+        vec3 H = normalize(L + V);
+        vec3 radiance = sun.color.rgb * 10.0;
+        light = radiance * (max(dot(N, L), 0.0) * 0.5 + 0.5);
+        specular = radiance * pow(max(dot(N, H), 0.0), 512.0) * fresnel * 8.0;
+    }
+    vec3 color = mix(water * light, sky, fresnel) + specular;
+    color = mix(color, foam_color.rgb * light, clamp(normal_foam.z, 0.0, 1.0));
+    outColor = vec4(color, 1.0);
+}
@@ -0,0 +1 @@
+ocean_maps_comp.glsl
\ No newline at end of file
@@ -0,0 +1,42 @@
+#version 450
+#extension GL_GOOGLE_include_directive : enable
+
+#include "library/ocean.glsl"
+
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+layout(set = 0, binding = 1, rgba32f) uniform readonly image2D spectrum_a;
+layout(set = 0, binding = 2, rgba32f) uniform readonly image2D spectrum_b;
+// (x offset, height, z offset, unused)
+layout(set = 0, binding = 3, rgba32f) uniform writeonly image2D displacement_map;
+// (normal x, normal z, foam, jacobian)
+layout(set = 0, binding = 4, rgba16f) uniform image2D normal_map;
+
+void main() {
+    ivec2 texel = ivec2(gl_GlobalInvocationID.xy);
+    if (texel.x >= OCEAN_FFT_SIZE || texel.y >= OCEAN_FFT_SIZE) {
+        return;
+    }
+    // The spectrum is centered on k = 0, which flips the sign of every other texel.
+    float flip = ((texel.x + texel.y) & 1) == 0 ? 1.0 : -1.0;
+    vec4 a = imageLoad(spectrum_a, texel) * flip;
+    vec4 b = imageLoad(spectrum_b, texel) * flip;
+    float choppiness = surface.z;
+
+    float height = a.x;
+    vec2 offset = vec2(a.y, a.z) * choppiness;
+    imageStore(displacement_map, texel, vec4(offset.x, height, offset.y, 0.0));
+
+    // Moving the surface sideways stretches and squashes it, the slopes follow.
+    float dxdx = 1.0 + choppiness * b.y;
+    float dzdz = 1.0 + choppiness * b.z;
+    float dxdz = choppiness * b.w;
+    vec2 slope = vec2(a.w / max(dxdx, 0.1), b.x / max(dzdz, 0.1));
+    vec3 normal = normalize(vec3(-slope.x, 1.0, -slope.y));
+
+    // Where the jacobian gets small the waves fold over themselves and break.
+    float jacobian = dxdx * dzdz - dxdz * dxdz;
+    float previous = imageLoad(normal_map, texel).z * foam.y;
+    float new_foam = clamp((foam.x - jacobian) / max(foam.x, 0.0001), 0.0, 1.0);
+    imageStore(normal_map, texel, vec4(normal.x, normal.z, max(previous, new_foam), jacobian));
+}
@@ -0,0 +1 @@
+ocean_spectrum_comp.glsl
\ No newline at end of file
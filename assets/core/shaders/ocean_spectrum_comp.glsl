@@ -0,0 +1,55 @@
+#version 450
+#extension GL_GOOGLE_include_directive : enable
+
+#include "library/ocean.glsl"
+
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+// (h0(k), conjugate of h0(-k)) per texel, from src/graphics/ocean.rs
+layout(set = 0, binding = 1) readonly buffer InitialSpectrum {
+    vec4 initial_spectrum[];
+};
+
+// Four complex spectra in two textures, each pair is packed as a + i * b so one inverse FFT
+// turns it into two real maps:
+// a: (height + i * x displacement, z displacement + i * x slope)
+// b: (z slope + i * dDx/dx, dDz/dz + i * dDx/dz)
+layout(set = 0, binding = 2, rgba32f) uniform writeonly image2D spectrum_a;
+layout(set = 0, binding = 3, rgba32f) uniform writeonly image2D spectrum_b;
+
+const float PI = 3.14159265358979323;
+const float GRAVITY = 9.81;
+
+void main() {
+    ivec2 texel = ivec2(gl_GlobalInvocationID.xy);
+    if (texel.x >= OCEAN_FFT_SIZE || texel.y >= OCEAN_FFT_SIZE) {
+        return;
+    }
+    vec2 k = vec2(texel - OCEAN_FFT_SIZE / 2) * 2.0 * PI / surface.y;
+    float k_length = max(length(k), 0.000001);
+
+    // Deep water waves travel at sqrt(g * k).
+    float phase = sqrt(GRAVITY * k_length) * surface.w;
+    vec2 rotation = vec2(cos(phase), sin(phase));
+    vec4 h0 = initial_spectrum[texel.y * OCEAN_FFT_SIZE + texel.x];
+    vec2 h = complex_mul(h0.xy, rotation) + complex_mul(h0.zw, vec2(rotation.x, -rotation.y));
+
+    // The surface is pushed sideways towards the crests, -i * k / |k| * h.
+    vec2 unit_k = k / k_length;
+    vec2 displacement_x = -complex_i(h) * unit_k.x;
+    vec2 displacement_z = -complex_i(h) * unit_k.y;
+    vec2 slope_x = complex_i(h) * k.x;
+    vec2 slope_z = complex_i(h) * k.y;
+    vec2 dxdx = h * k.x * unit_k.x;
+    vec2 dzdz = h * k.y * unit_k.y;
+    vec2 dxdz = h * k.x * unit_k.y;
+
+    imageStore(spectrum_a, texel, vec4(
+        h + complex_i(displacement_x),
+        displacement_z + complex_i(slope_x)
+    ));
+    imageStore(spectrum_b, texel, vec4(
+        slope_z + complex_i(dxdx),
+        dzdz + complex_i(dxdz)
+    ));
+}
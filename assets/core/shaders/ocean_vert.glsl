@@ -0,0 +1,73 @@
+#version 450
+#extension GL_GOOGLE_include_directive : enable
+
+#include "library/common.glsl"
+#include "library/ocean.glsl"
+
+// (x offset, height, z offset, unused), it tiles every patch size units.
+layout(set = 0, binding = 1) uniform texture2D displacement_map;
+
+layout(location = 0) out vec3 o_position;
+layout(location = 1) out vec2 o_uv;
+
+// Cells one past the edges of the screen, so waves don't pull the border into view.
+const float SCREEN_MARGIN = 1.2;
+
+const ivec2 CORNERS[6] = ivec2[](
+    ivec2(0, 0), ivec2(1, 0), ivec2(0, 1),
+    ivec2(0, 1), ivec2(1, 0), ivec2(1, 1)
+);
+
+vec3 unproject(mat4 inverse_view_projection, vec2 position, float depth) {
+    vec4 world = inverse_view_projection * vec4(position, depth, 1.0);
+    return world.xyz / world.w;
+}
+
+vec3 fetch_displacement(ivec2 texel) {
+    return texelFetch(displacement_map, texel & (OCEAN_FFT_SIZE - 1), 0).xyz;
+}
+
+// Rgba32 floats can't be filtered, so it's done by hand.
+vec3 sample_displacement(vec2 uv) {
+    vec2 texel = uv * OCEAN_FFT_SIZE - 0.5;
+    ivec2 base = ivec2(floor(texel));
+    vec2 t = texel - vec2(base);
+    vec3 top = mix(fetch_displacement(base), fetch_displacement(base + ivec2(1, 0)), t.x);
+    vec3 bottom = mix(
+        fetch_displacement(base + ivec2(0, 1)),
+        fetch_displacement(base + ivec2(1, 1)),
+        t.x
+    );
+    return mix(top, bottom, t.y);
+}
+
+void main() {
+    int cells = max(int(info.x) - 1, 1);
+    int quad = gl_VertexIndex / 6;
+    ivec2 cell = ivec2(quad % cells, quad / cells) + CORNERS[gl_VertexIndex % 6];
+    vec2 screen = (vec2(cell) / float(cells) * 2.0 - 1.0) * SCREEN_MARGIN;
+
+    // Our projections map depth from -1 to 1.
+    mat4 inverse_view_projection = inverse(view_projection);
+    vec3 near = unproject(inverse_view_projection, screen, -1.0);
+    vec3 far = unproject(inverse_view_projection, screen, 1.0);
+    vec3 ray = far - near;
+    vec3 direction = normalize(ray);
+
+    // Rays that never reach the water end up on it at the far plane, along the horizon.
+    float max_distance = length(ray);
+    float travel = max_distance;
+    if (abs(direction.y) > 0.00001) {
+        float t = (surface.x - near.y) / direction.y;
+        if (t > 0.0) {
+            travel = min(t, max_distance);
+        }
+    }
+    vec3 position = near + direction * travel;
+    position.y = surface.x;
+
+    o_uv = position.xz / surface.y;
+    position += sample_displacement(o_uv);
+    o_position = position;
+    gl_Position = view_projection * vec4(position, 1.0);
+}
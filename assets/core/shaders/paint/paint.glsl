@@ -0,0 +1,15 @@
+#ifndef PAINT_INCLUDES
+#define PAINT_INCLUDES
+
+layout(set = 0, binding = 0) uniform Paint {
+    mat4 world;
+    // (position, radius)
+    vec4 brush;
+    vec4 brush_color;
+    // (direction, hardness)
+    vec4 brush_direction;
+    // (uses direction, unused..)
+    vec4 brush_info;
+};
+
+#endif
@@ -0,0 +1,2 @@
+paint_vert.glsl
+paint_frag.glsl
\ No newline at end of file
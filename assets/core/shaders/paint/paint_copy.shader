@@ -0,0 +1,2 @@
+paint_copy_vert.glsl
+paint_copy_frag.glsl
\ No newline at end of file
@@ -0,0 +1,10 @@
+#version 450
+
+layout(location = 0) out vec2 o_uv;
+
+// A single triangle that covers the whole texture, draw it with 3 vertices.
+void main() {
+    vec2 position = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    gl_Position = vec4(position * 2.0 - 1.0, 0.0, 1.0);
+    o_uv = vec2(position.x, 1.0 - position.y);
+}
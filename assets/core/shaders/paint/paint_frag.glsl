@@ -0,0 +1,25 @@
+#version 450
+
+#include "paint.glsl"
+
+layout(location = 0) in vec3 i_position;
+layout(location = 1) in vec3 i_normal;
+
+layout(location = 0) out vec4 o_color;
+
+void main() {
+    float radius = brush.w;
+    float distance = length(i_position - brush.xyz);
+    float hardness = brush_direction.w;
+    float strength = 1.0 - smoothstep(radius * hardness, radius, distance);
+
+    // Directional brushes only reach surfaces facing into them.
+    if (brush_info.x > 0.5) {
+        strength *= clamp(dot(normalize(i_normal), -brush_direction.xyz), 0.0, 1.0);
+    }
+
+    if (strength <= 0.0) {
+        discard;
+    }
+    o_color = vec4(brush_color.rgb, brush_color.a * strength);
+}
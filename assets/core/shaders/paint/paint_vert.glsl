@@ -0,0 +1,18 @@
+#version 450
+
+#include "paint.glsl"
+
+layout(location = 0) in vec3 i_Pos;
+layout(location = 1) in vec3 i_Normal;
+layout(location = 2) in vec2 i_Uv;
+
+layout(location = 0) out vec3 o_position;
+layout(location = 1) out vec3 o_normal;
+
+// Places each vertex at it's UV coordinate so the triangles land where they sample the texture.
+void main() {
+    vec4 position = world * vec4(i_Pos, 1.0);
+    o_position = position.xyz / position.w;
+    o_normal = mat3(world) * i_Normal;
+    gl_Position = vec4(i_Uv.x * 2.0 - 1.0, 1.0 - i_Uv.y * 2.0, 0.0, 1.0);
+}
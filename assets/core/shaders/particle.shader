@@ -0,0 +1,2 @@
+particle_vert.glsl
+particle_frag.glsl
\ No newline at end of file
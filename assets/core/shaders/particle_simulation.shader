@@ -0,0 +1 @@
+particle_simulation_comp.glsl
\ No newline at end of file
@@ -0,0 +1,57 @@
+#version 450
+
+// Matches `simulate` in src/graphics/particles.rs, it's used when compute isn't available.
+
+layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+struct Particle {
+    // (position, age)
+    vec4 position_age;
+    // (velocity, lifetime)
+    vec4 velocity_lifetime;
+};
+
+layout(set = 0, binding = 0) uniform Simulation {
+    // (acceleration, delta time)
+    vec4 acceleration_dt;
+    vec4 speeds;
+    vec4 speed_times;
+    // (particle count, unused, unused, unused)
+    vec4 info;
+};
+
+layout(set = 0, binding = 1) buffer Particles {
+    Particle particles[];
+};
+
+float sample_curve(vec4 values, vec4 times, float t) {
+    float value = values[0];
+    for (int i = 1; i < 4; ++i) {
+        float start = times[i - 1];
+        if (t >= start) {
+            float blend = clamp((t - start) / max(times[i] - start, 0.0001), 0.0, 1.0);
+            value = mix(values[i - 1], values[i], blend);
+        }
+    }
+    return value;
+}
+
+void main() {
+    uint index = gl_GlobalInvocationID.x;
+    if (index >= uint(info.x)) {
+        return;
+    }
+    Particle particle = particles[index];
+    float age = particle.position_age.w;
+    float lifetime = particle.velocity_lifetime.w;
+    if (age >= lifetime) {
+        return;
+    }
+
+    float dt = acceleration_dt.w;
+    float speed = sample_curve(speeds, speed_times, age / lifetime);
+    vec3 velocity = particle.velocity_lifetime.xyz + acceleration_dt.xyz * dt;
+    vec3 position = particle.position_age.xyz + velocity * speed * dt;
+    particles[index].position_age = vec4(position, age + dt);
+    particles[index].velocity_lifetime = vec4(velocity, lifetime);
+}
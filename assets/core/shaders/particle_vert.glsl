@@ -0,0 +1,70 @@
+#version 450
+
+layout(location = 0) in vec4 i_position_age;
+layout(location = 1) in vec4 i_velocity_lifetime;
+
+layout(location = 0) out vec2 o_uv;
+layout(location = 1) out vec4 o_color;
+
+layout(set = 0, binding = 0) uniform Globals {
+    mat4 view_projection;
+    vec4 camera_pos;
+    mat4 view;
+    mat4 projection;
+};
+
+layout(set = 1, binding = 0) uniform Emitter {
+    vec4 colors[4];
+    vec4 color_times;
+    vec4 sizes;
+    vec4 size_times;
+};
+
+// Same as the simulation shader and `Curve::sample`.
+float sample_curve(vec4 values, vec4 times, float t) {
+    float value = values[0];
+    for (int i = 1; i < 4; ++i) {
+        float start = times[i - 1];
+        if (t >= start) {
+            float blend = clamp((t - start) / max(times[i] - start, 0.0001), 0.0, 1.0);
+            value = mix(values[i - 1], values[i], blend);
+        }
+    }
+    return value;
+}
+
+vec4 sample_color(float t) {
+    vec4 value = colors[0];
+    for (int i = 1; i < 4; ++i) {
+        float start = color_times[i - 1];
+        if (t >= start) {
+            float blend = clamp((t - start) / max(color_times[i] - start, 0.0001), 0.0, 1.0);
+            value = mix(colors[i - 1], colors[i], blend);
+        }
+    }
+    return value;
+}
+
+void main() {
+    float age = i_position_age.w;
+    float lifetime = i_velocity_lifetime.w;
+    if (age >= lifetime) {
+        // Dead particles collapse outside the clip volume.
+        o_uv = vec2(0.0);
+        o_color = vec4(0.0);
+        gl_Position = vec4(0.0, 0.0, -2.0, 1.0);
+        return;
+    }
+    float life = age / lifetime;
+
+    // Triangle strip corners (-1, -1), (1, -1), (-1, 1), (1, 1).
+    vec2 corner = vec2(float(gl_VertexIndex & 1), float(gl_VertexIndex >> 1)) * 2.0 - 1.0;
+    vec3 right = vec3(view[0][0], view[1][0], view[2][0]);
+    vec3 up = vec3(view[0][1], view[1][1], view[2][1]);
+    float size = sample_curve(sizes, size_times, life);
+    vec3 position = i_position_age.xyz + (right * corner.x + up * corner.y) * size * 0.5;
+
+    o_uv = vec2(corner.x, -corner.y) * 0.5 + 0.5;
+    o_color = sample_color(life);
+    gl_Position = view_projection * vec4(position, 1.0);
+}
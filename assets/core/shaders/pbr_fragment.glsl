@@ -4,27 +4,69 @@
 #include "library/lighting.glsl"
 #include "library/pbr.glsl"
 #include "library/common.glsl"
+#include "library/shadow.glsl"
+#include "library/clusters.glsl"
+#include "library/cookies.glsl"
+#include "library/dynamic_gi.glsl"
+#include "library/ao_volume.glsl"
+#include "library/water.glsl"
 
 layout(set = 2, binding = 0) uniform Material {
     vec4 color;
     // (metallic, roughness, metallic_amount, roughness_amount)
     vec4 pbr_info;
+    // (normal encoding (0 = RGB, 1 = RG), normal scale, occlusion strength, receives shadows)
+    vec4 normal_info;
+    // (alpha cutoff, is masked, alpha to coverage, is blended)
+    vec4 alpha_info;
+    // (r, g, b, wetness)
+    vec4 emissive;
+    // (offset x, offset y, scale x, scale y)
+    vec4 uv_transform;
+    // (rotation, unused..)
+    vec4 uv_rotation;
 };
 
 layout(set = 2, binding = 1) uniform sampler tex_sampler;
 layout(set = 2, binding = 2) uniform texture2D main_map;
 layout(set = 2, binding = 3) uniform texture2D normal_map;
 layout(set = 2, binding = 4) uniform texture2D metallic_roughness_map;
+layout(set = 2, binding = 5) uniform texture2D emissive_map;
+layout(set = 2, binding = 6) uniform texture2D occlusion_map;
 
 layout(set = 3, binding = 0) uniform textureCube irradiance_cube_map;
 layout(set = 3, binding = 1) uniform textureCube spec_cube_map;
 layout(set = 3, binding = 2) uniform texture2D spec_brdf_map;
 
+#define MAX_REFLECTION_PROBES 4
+struct ReflectionProbe {
+    // xyz is where the probe was captured, w the blend distance.
+    vec4 position;
+    // The influence box, w in min is 1.0 for box projection.
+    vec4 box_min;
+    vec4 box_max;
+};
+layout(set = 3, binding = 3) uniform textureCube reflection_cube_map_0;
+layout(set = 3, binding = 4) uniform textureCube reflection_cube_map_1;
+layout(set = 3, binding = 5) uniform textureCube reflection_cube_map_2;
+layout(set = 3, binding = 6) uniform textureCube reflection_cube_map_3;
+layout(set = 3, binding = 7) uniform ReflectionProbes {
+    ReflectionProbe reflection_probes[MAX_REFLECTION_PROBES];
+    // x is the number of probes.
+    vec4 reflection_probe_count;
+};
+
 layout(location = 0) in vec2 i_uv;
 layout(location = 1) in vec3 i_normal;
 layout(location = 2) in vec3 i_position;
 layout(location = 3) in vec3 i_tangent;
 layout(location = 4) in float i_tbn_handedness;
+// Per instance tint or vertex color, white when there's neither.
+layout(location = 5) in vec4 i_color;
+// 0.0 for entities that don't receive shadows.
+layout(location = 6) in float i_receive_shadows;
+// The second uv set, a copy of the first for meshes without one.
+layout(location = 7) in vec2 i_uv1;
 layout(location = 0) out vec4 outColor;
 
 const float roughnessRescale = 1.0;
@@ -64,7 +106,8 @@ shade (float vdotn,
        vec3 diffuseColor,
        vec3 diffuseIBL, 
        vec3 specularIBL,
-       vec3 normal)
+       vec3 normal,
+       float occlusion)
 {
     roughness = roughness * (1.0 / roughnessRescale);
 
@@ -75,31 +118,53 @@ shade (float vdotn,
     vec3 diffColor = diffuseColor.rgb * (1.0 - metalness);
     vec3 specColor = mix(dielectricColor.rgb, diffuseColor.rgb, metalness) * specularIntensity;
    
-    // TODO: Add AO.
-    // diffuseIBL.rgb = lerp(diffuseIBL.rgb * 0.3f, diffuseIBL.rgb, bakedAO);
-    
     vec3 albedoByDiffuse = diffColor.rgb * diffuseIBL.rgb;
 
-    vec3 litColor =  (albedoByDiffuse.rgb + (metalSpecularIBL * (specColor * brdfTerm.x + (brdfTerm.y)))); // * bakedAO;
-    return litColor;
+    vec3 litColor =  (albedoByDiffuse.rgb + (metalSpecularIBL * (specColor * brdfTerm.x + (brdfTerm.y))));
+    return litColor * occlusion;
 } 
 
-vec3 Uncharted2ToneMapping(vec3 color)
-{
-	float A = 0.15;
-	float B = 0.50;
-	float C = 0.10;
-	float D = 0.20;
-	float E = 0.02;
-	float F = 0.30;
-	float W = 11.2;
-	float exposure = 2.;
-	color *= exposure;
-	color = ((color * (A * color + C * B) + D * E) / (color * (A * color + B) + D * F)) - E / F;
-	float white = ((W * (A * W + C * B) + D * E) / (W * (A * W + B) + D * F)) - E / F;
-	color /= white;
-	color = pow(color, vec3(1. / 1.2));
-	return color;
+vec3 sample_reflection_probe(int index, vec3 direction, float lod) {
+    switch (index) {
+        case 0: return textureLod(samplerCube(reflection_cube_map_0, tex_sampler), direction, lod).rgb;
+        case 1: return textureLod(samplerCube(reflection_cube_map_1, tex_sampler), direction, lod).rgb;
+        case 2: return textureLod(samplerCube(reflection_cube_map_2, tex_sampler), direction, lod).rgb;
+        default: return textureLod(samplerCube(reflection_cube_map_3, tex_sampler), direction, lod).rgb;
+    }
+}
+
+// 1.0 well inside the probe's box, fading to 0.0 at it's edges.
+float reflection_probe_weight(ReflectionProbe probe, vec3 position) {
+    vec3 inside = min(position - probe.box_min.xyz, probe.box_max.xyz - position);
+    float distance = min(inside.x, min(inside.y, inside.z));
+    return clamp(distance / max(probe.position.w, 0.0001), 0.0, 1.0);
+}
+
+// Finds where the reflection ray leaves the box and looks that up from the capture position.
+vec3 box_project(ReflectionProbe probe, vec3 position, vec3 R) {
+    if (probe.box_min.w < 0.5) {
+        return R;
+    }
+    vec3 first = (probe.box_max.xyz - position) / R;
+    vec3 second = (probe.box_min.xyz - position) / R;
+    vec3 furthest = max(first, second);
+    float distance = min(furthest.x, min(furthest.y, furthest.z));
+    return position + R * distance - probe.position.xyz;
+}
+
+// Blends the reflection probes the position is inside of over the environment's reflections.
+vec3 reflections(vec3 environment, vec3 position, vec3 R, float lod) {
+    vec3 color = vec3(0.0);
+    float remaining = 1.0;
+    for (int i = 0; i < int(reflection_probe_count.x) && i < MAX_REFLECTION_PROBES; ++i) {
+        ReflectionProbe probe = reflection_probes[i];
+        float weight = reflection_probe_weight(probe, position) * remaining;
+        if (weight > 0.0) {
+            color += sample_reflection_probe(i, box_project(probe, position, R), lod) * weight;
+            remaining -= weight;
+        }
+    }
+    return color + environment * remaining;
 }
 
 float DistributionGGX(vec3 N, vec3 H, float roughness)
@@ -141,17 +206,88 @@ vec3 fresnelSchlick(float cosTheta, vec3 F0)
     return F0 + (1.0 - F0) * pow(1.0 - cosTheta, 5.0);
 }
 
+// Light reflected towards the viewer for a light of radiance 1.0 coming from L.
+vec3 cook_torrance(vec3 N, vec3 V, vec3 L, vec3 F0, vec3 albedo, float roughness, float metallic)
+{
+    vec3 H = normalize(V + L);
+    float NDF = DistributionGGX(N, H, roughness);        
+    float G   = GeometrySmith(N, V, L, roughness);      
+    vec3 F    = fresnelSchlick(max(dot(H, V), 0.0), F0);       
+    
+    vec3 kS = F;
+    vec3 kD = vec3(1.0) - kS;
+    kD *= 1.0 - metallic;	  
+    
+    vec3 numerator    = NDF * G * F;
+    float denominator = 4.0 * max(dot(N, V), 0.0) * max(dot(N, L), 0.0);
+    vec3 specular     = numerator / max(denominator, 0.001);  
+    
+    float NdotL = max(dot(N, L), 0.0);                
+    return (kD * albedo / PI + specular) * NdotL;
+}
+
+// Same order as KHR_texture_transform, scale then rotate then offset.
+vec2 transform_uv(vec2 uv) {
+    float s = sin(uv_rotation.x);
+    float c = cos(uv_rotation.x);
+    uv *= uv_transform.zw;
+    uv = vec2(c * uv.x + s * uv.y, -s * uv.x + c * uv.y);
+    return uv + uv_transform.xy;
+}
+
 void main() {
-    vec3 main_color = texture(sampler2D(main_map, tex_sampler), i_uv).rgb * color.rgb;
+    vec2 uv = transform_uv(i_uv);
+#ifdef WORLD_GRID
+    // A world space checker for blocking out levels, the uv scale is the cell size. The
+    // position is pushed into the surface so faces on a cell edge don't flicker.
+    vec3 grid_position = i_position - normalize(i_normal) * 0.001;
+    vec3 cell = floor(grid_position / max(uv_transform.z, 0.0001));
+    float checker = mod(cell.x + cell.y + cell.z, 2.0);
+    vec4 main_sample = vec4(vec3(mix(0.6, 1.0, checker)), 1.0);
+#else
+    vec4 main_sample = texture(sampler2D(main_map, tex_sampler), uv);
+#endif
+    vec3 main_color = main_sample.rgb * color.rgb * i_color.rgb;
+
+    float alpha = 1.0;
+    if (alpha_info.y > 0.5) {
+        alpha = main_sample.a * color.a * i_color.a;
+        if (alpha_info.z > 0.5) {
+            // Sharpen alpha around the cutoff so alpha to coverage gives crisp but smooth edges.
+            alpha = (alpha - alpha_info.x) / max(fwidth(alpha), 0.0001) + 0.5;
+        } else if (alpha < alpha_info.x) {
+            discard;
+        }
+    } else if (alpha_info.w > 0.5) {
+        alpha = main_sample.a * color.a * i_color.a;
+    }
     
-    vec2 metallic_roughness = texture(sampler2D(metallic_roughness_map, tex_sampler), i_uv).bg;
+    vec2 metallic_roughness = texture(sampler2D(metallic_roughness_map, tex_sampler), uv).bg;
     float metallic = mix(metallic_roughness.x, pbr_info.x, pbr_info.z);
     float roughness = mix(metallic_roughness.y, pbr_info.y, pbr_info.w);
+
+    // Wet surfaces soak up light and get a film of water on top.
+    float wetness = water_wetness(i_position) * emissive.w;
+    main_color *= mix(1.0, 0.5, wetness);
+    roughness = mix(roughness, 0.1, wetness);
     
-    vec3 normal = texture(sampler2D(normal_map, tex_sampler), i_uv).rgb;
+#ifdef NO_NORMAL_MAP
+    vec3 normal = vec3(0.0, 0.0, 1.0);
+#else
+    vec3 normal = texture(sampler2D(normal_map, tex_sampler), uv).rgb;
     normal = normal * 2.0 - 1.0;
+    if (normal_info.x > 0.5) {
+        // Two channel normal maps only store X and Y so we rebuild Z.
+        normal.z = sqrt(max(1.0 - dot(normal.xy, normal.xy), 0.0));
+    }
+    normal.xy *= normal_info.y;
+#endif
     vec3 V = normalize(camera_pos.xyz - i_position.xyz);
     vec3 N = normalize(i_normal);
+    // Back faces of double sided materials are lit from their own side.
+    if (!gl_FrontFacing) {
+        N = -N;
+    }
     vec3 T = normalize(i_tangent);
     vec3 B = cross(N, T) * i_tbn_handedness;
     mat3 TBN = mat3(T, B, N);
@@ -162,6 +298,10 @@ void main() {
 
     vec3 ambient_irradiance = texture(samplerCube(irradiance_cube_map, tex_sampler), N).rgb;
     vec3 ambient_spec = textureLod(samplerCube(spec_cube_map, tex_sampler), R, roughness * MAX_SPEC_LOD).rgb;
+#ifndef REDUCED
+    ambient_spec = reflections(ambient_spec, i_position, R, roughness * MAX_SPEC_LOD);
+    ambient_irradiance = dynamic_gi_irradiance(i_position, N, ambient_irradiance);
+#endif
     // vec2 env_brdf = texture(sampler2D(spec_brdf_map, tex_sampler), vec2(NdotV, roughness)).rg;
 
     // vec3 f0 = mix(vec3(0.04), main_color.xyz, metallic);
@@ -179,41 +319,64 @@ void main() {
     roughness = mix(roughness, 1.0 - roughness, 0.0);
     metallic = mix(metallic, 1.0 - metallic, 0.0);
 
-    // TODO: Point-lights?
-    vec3 ambient = shade(VdotN, roughness, metallic, main_color.rgb, ambient_irradiance, ambient_spec, N);
+#ifdef OCCLUSION_UV1
+    // Baked AO and lightmaps have their own unwrap, the texture transform isn't used.
+    float occlusion = texture(sampler2D(occlusion_map, tex_sampler), i_uv1).r;
+#else
+    float occlusion = texture(sampler2D(occlusion_map, tex_sampler), uv).r;
+#endif
+    occlusion = mix(1.0, occlusion, normal_info.z);
+#ifndef REDUCED
+    occlusion *= ao_volume_occlusion(i_position, N);
+#endif
+    vec3 ambient = shade(VdotN, roughness, metallic, main_color.rgb, ambient_irradiance, ambient_spec, N, occlusion);
 
     vec3 F0 = vec3(0.04); 
     F0 = mix(F0, main_color, metallic);
 
     // Directional Lighting
     vec3 light_acc = vec3(0.0);
+    int shadow_light = int(shadow_bias.w);
+    float view_depth = -(view * vec4(i_position, 1.0)).z;
     for (int i=0; i < int(light_num.x) && i < MAX_LIGHTS; ++i) {
 
         DirectionalLight light = directional_lights[i];
         // calculate per-light radiance
         vec3 L = normalize(light.direction.xyz);
-        vec3 H = normalize(V + L);
         vec3 radiance = light.color.xyz * 10;        
         
-        // cook-torrance brdf
-        float NDF = DistributionGGX(N, H, roughness);        
-        float G   = GeometrySmith(N, V, L, roughness);      
-        vec3 F    = fresnelSchlick(max(dot(H, V), 0.0), F0);       
-        
-        vec3 kS = F;
-        vec3 kD = vec3(1.0) - kS;
-        kD *= 1.0 - metallic;	  
-        
-        vec3 numerator    = NDF * G * F;
-        float denominator = 4.0 * max(dot(N, V), 0.0) * max(dot(N, L), 0.0);
-        vec3 specular     = numerator / max(denominator, 0.001);  
-            
-        // add to outgoing radiance Lo
         float NdotL = max(dot(N, L), 0.0);                
-        light_acc += (kD * main_color / PI + specular) * radiance * NdotL; 
+        float shadow = 1.0;
+        if (i == shadow_light && i_receive_shadows * normal_info.w > 0.5) {
+            shadow = shadow_factor(i_position, normalize(i_normal), NdotL, view_depth);
+        }
+        light_acc += cook_torrance(N, V, L, F0, main_color, roughness, metallic) * radiance * shadow; 
+    }
+
+    // Point and spot lights, only the ones touching this fragment's cluster are checked.
+    uint offset = cluster_offset(cluster_index(gl_FragCoord.xy, view_depth));
+    uint cluster_light_count = cluster_lights[offset];
+    for (uint i = 0; i < cluster_light_count; ++i) {
+        Light light = lights[cluster_lights[offset + 1 + i]];
+        vec3 to_light = light.position.xyz - i_position;
+        float distance = length(to_light);
+        vec3 L = to_light / max(distance, 0.0001);
+        float attenuation = light_falloff(distance, light.position.w);
+        if (light.color.w > 0.5) {
+            attenuation *= spot_falloff(light, L);
+            int shadow_tile = int(light.spot.z);
+            if (shadow_tile >= 0 && attenuation > 0.0 && i_receive_shadows * normal_info.w > 0.5) {
+                float NdotL = max(dot(N, L), 0.0);
+                attenuation *= spot_shadow_factor(shadow_tile, i_position, normalize(i_normal), L, distance, NdotL);
+            }
+        }
+        vec3 radiance = light.color.rgb * light_cookie(light, L) * attenuation;
+        light_acc += cook_torrance(N, V, L, F0, main_color, roughness, metallic) * radiance;
     }
 
-    vec3 color = Uncharted2ToneMapping(ambient + light_acc);
+    // Left in HDR, post processing tonemaps the frame.
+    vec3 emissive_color = emissive.rgb * texture(sampler2D(emissive_map, tex_sampler), uv).rgb;
+    vec3 color = ambient + light_acc + emissive_color;
 
-    outColor = vec4(color, 1.0);
+    outColor = vec4(color, alpha);
 }
\ No newline at end of file
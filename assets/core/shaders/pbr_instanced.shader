@@ -0,0 +1,2 @@
+pbr_instanced_vertex.glsl
+pbr_fragment.glsl
\ No newline at end of file
@@ -0,0 +1,33 @@
+#version 450
+
+#include "library/common.glsl"
+
+layout(location = 0) in vec3 i_Pos;
+layout(location = 1) in vec3 i_normal;
+layout(location = 2) in vec2 i_uv;
+layout(location = 3) in vec4 i_tangent;
+// Per instance data, the world matrix takes up 4 locations.
+layout(location = 4) in mat4 i_world;
+layout(location = 8) in vec4 i_color;
+layout(location = 0) out vec2 v_TexCoord;
+layout(location = 1) out vec3 o_normal;
+layout(location = 2) out vec3 o_position;
+layout(location = 3) out vec3 o_tangent;
+layout(location = 4) out float o_tbn_handedness;
+layout(location = 5) out vec4 o_color;
+// Entities that don't receive shadows aren't drawn instanced.
+layout(location = 6) out float o_receive_shadows;
+layout(location = 7) out vec2 o_uv1;
+
+void main() {
+    v_TexCoord = vec2(i_uv.x, i_uv.y);
+    mat3 normalMatrix = mat3(transpose(inverse(i_world)));
+    o_position = (i_world * vec4(i_Pos, 1.0)).xyz;
+    o_normal = normalMatrix * i_normal.xyz;
+    o_tangent = normalMatrix * i_tangent.xyz;
+    o_tbn_handedness = i_tangent.w;
+    o_color = i_color;
+    o_receive_shadows = 1.0;
+    o_uv1 = i_uv;
+    gl_Position = view_projection * i_world * vec4(i_Pos, 1.0);
+}
@@ -0,0 +1,2 @@
+pbr_fragment.glsl
+pbr_skinned_vertex.glsl
\ No newline at end of file
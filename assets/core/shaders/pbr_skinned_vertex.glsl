@@ -0,0 +1,59 @@
+#version 450
+
+#include "library/common.glsl"
+
+const int MAX_JOINTS = 128;
+
+layout(location = 0) in vec3 i_Pos;
+layout(location = 1) in vec3 i_normal;
+layout(location = 2) in vec2 i_uv;
+layout(location = 3) in vec4 i_tangent;
+layout(location = 4) in uvec4 i_joints;
+layout(location = 5) in vec4 i_weights;
+#ifdef VERTEX_ATTRIBUTES
+// Vertex colors and the second uv set, from the mesh's attribute buffer.
+layout(location = 9) in vec4 i_vertex_color;
+layout(location = 10) in vec2 i_uv1;
+#endif
+layout(location = 0) out vec2 v_TexCoord;
+layout(location = 1) out vec3 o_normal;
+layout(location = 2) out vec3 o_position;
+layout(location = 3) out vec3 o_tangent;
+layout(location = 4) out float o_tbn_handedness;
+layout(location = 5) out vec4 o_color;
+layout(location = 6) out float o_receive_shadows;
+layout(location = 7) out vec2 o_uv1;
+
+layout(set = 0, binding = 0) uniform Locals {
+    mat4 world;
+    // (receives shadows, unused..)
+    vec4 locals_info;
+};
+
+layout(set = 0, binding = 1) uniform Joints {
+    mat4 joints[MAX_JOINTS];
+};
+
+void main() {
+    mat4 skin = i_weights.x * joints[i_joints.x]
+        + i_weights.y * joints[i_joints.y]
+        + i_weights.z * joints[i_joints.z]
+        + i_weights.w * joints[i_joints.w];
+    mat4 skinned_world = world * skin;
+
+    v_TexCoord = vec2(i_uv.x, i_uv.y);
+    mat3 normalMatrix = mat3(transpose(inverse(skinned_world)));
+    o_position = (skinned_world * vec4(i_Pos, 1.0)).xyz;
+    o_normal = normalMatrix * i_normal.xyz;
+    o_tangent = normalMatrix * i_tangent.xyz;
+    o_tbn_handedness = i_tangent.w;
+#ifdef VERTEX_ATTRIBUTES
+    o_color = i_vertex_color;
+    o_uv1 = i_uv1;
+#else
+    o_color = vec4(1.0);
+    o_uv1 = i_uv;
+#endif
+    o_receive_shadows = locals_info.x;
+    gl_Position = view_projection * skinned_world * vec4(i_Pos, 1.0);
+}
@@ -0,0 +1,20 @@
+#version 450
+
+#include "post.glsl"
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+
+// A 9 tap gaussian, done in 5 samples by letting the sampler blend neighbouring texels.
+const float offsets[3] = float[](0.0, 1.3846153846, 3.2307692308);
+const float weights[3] = float[](0.2270270270, 0.3162162162, 0.0702702703);
+
+void main() {
+    vec2 step = texel.xy * texel.zw;
+    vec3 color = sample_source(i_uv) * weights[0];
+    for (int i = 1; i < 3; ++i) {
+        color += sample_source(i_uv + step * offsets[i]) * weights[i];
+        color += sample_source(i_uv - step * offsets[i]) * weights[i];
+    }
+    o_color = vec4(color, 1.0);
+}
@@ -0,0 +1,2 @@
+post_vert.glsl
+bloom_threshold_frag.glsl
\ No newline at end of file
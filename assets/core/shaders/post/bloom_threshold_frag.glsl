@@ -0,0 +1,14 @@
+#version 450
+
+#include "post.glsl"
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+
+void main() {
+    vec3 color = sample_source(i_uv);
+    // Scale instead of subtracting so the glow keeps the color of what's bright.
+    float brightness = max(color.r, max(color.g, color.b));
+    float contribution = max(brightness - settings.w, 0.0) / max(brightness, 0.0001);
+    o_color = vec4(color * contribution, 1.0);
+}
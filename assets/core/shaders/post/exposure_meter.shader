@@ -0,0 +1,2 @@
+post_vert.glsl
+exposure_meter_frag.glsl
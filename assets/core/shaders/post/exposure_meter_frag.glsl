@@ -0,0 +1,45 @@
+#version 450
+
+#include "post.glsl"
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+
+// Keep in sync with EXPOSURE_METER_SIZE.
+const float METER_SIZE = 64.0;
+const int TAPS = 4;
+
+float metering_weight(vec2 uv) {
+    // Distance from the middle in screen heights.
+    float aspect = texel.w / texel.z;
+    float travel = length((uv - 0.5) * vec2(aspect, 1.0));
+    int mode = int(metering.x);
+    if (mode == 1) {
+        float falloff = max(1.0 - travel / 0.8, 0.0);
+        return falloff * falloff;
+    } else if (mode == 2) {
+        return 1.0 - smoothstep(metering.y * 0.9, metering.y, travel);
+    }
+    return 1.0;
+}
+
+// Each texel of the meter covers a block of the frame, a few taps spread over it is plenty.
+void main() {
+    float weight = metering_weight(i_uv);
+    if (metering.z > 0.5) {
+        weight *= texture(sampler2D(t_second, s_linear), i_uv).r;
+    }
+
+    float log_luminance = 0.0;
+    for (int y = 0; y < TAPS; y++) {
+        for (int x = 0; x < TAPS; x++) {
+            vec2 offset = (vec2(x, y) + 0.5) / float(TAPS) - 0.5;
+            vec3 color = sample_source(i_uv + offset / METER_SIZE);
+            float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+            log_luminance += log2(max(luminance, 0.0001));
+        }
+    }
+    log_luminance /= float(TAPS * TAPS);
+
+    o_color = vec4(log_luminance * weight, weight, 0.0, 1.0);
+}
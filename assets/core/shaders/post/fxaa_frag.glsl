@@ -0,0 +1,56 @@
+#version 450
+
+#include "post.glsl"
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+
+const float FXAA_SPAN_MAX = 8.0;
+const float FXAA_REDUCE_MUL = 1.0 / 8.0;
+const float FXAA_REDUCE_MIN = 1.0 / 128.0;
+
+// Edges are found on perceived brightness, sqrt gets us close to gamma space.
+float luma(vec3 color) {
+    return sqrt(dot(color, vec3(0.299, 0.587, 0.114)));
+}
+
+void main() {
+    vec2 texel_size = texel.zw;
+    vec3 rgb_nw = sample_source(i_uv + vec2(-1.0, -1.0) * texel_size);
+    vec3 rgb_ne = sample_source(i_uv + vec2(1.0, -1.0) * texel_size);
+    vec3 rgb_sw = sample_source(i_uv + vec2(-1.0, 1.0) * texel_size);
+    vec3 rgb_se = sample_source(i_uv + vec2(1.0, 1.0) * texel_size);
+    vec3 rgb_m = sample_source(i_uv);
+
+    float luma_nw = luma(rgb_nw);
+    float luma_ne = luma(rgb_ne);
+    float luma_sw = luma(rgb_sw);
+    float luma_se = luma(rgb_se);
+    float luma_m = luma(rgb_m);
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    // Blur along the edge, which runs across the luma gradient.
+    vec2 dir = vec2(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se)
+    );
+    float dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN);
+    float inverse_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * inverse_dir_min, vec2(-FXAA_SPAN_MAX), vec2(FXAA_SPAN_MAX)) * texel_size;
+
+    vec3 rgb_a = 0.5 * (
+        sample_source(i_uv + dir * (1.0 / 3.0 - 0.5)) +
+        sample_source(i_uv + dir * (2.0 / 3.0 - 0.5)));
+    vec3 rgb_b = rgb_a * 0.5 + 0.25 * (
+        sample_source(i_uv + dir * -0.5) +
+        sample_source(i_uv + dir * 0.5));
+
+    // If the wider blur picked up something outside the local range it crossed another edge.
+    float luma_b = luma(rgb_b);
+    if (luma_b < luma_min || luma_b > luma_max) {
+        o_color = vec4(finish(rgb_a, i_uv), 1.0);
+    } else {
+        o_color = vec4(finish(rgb_b, i_uv), 1.0);
+    }
+}
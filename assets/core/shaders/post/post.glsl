@@ -0,0 +1,58 @@
+#ifndef POST_INCLUDES
+#define POST_INCLUDES
+
+layout(set = 0, binding = 0) uniform texture2D t_source;
+layout(set = 0, binding = 1) uniform texture2D t_second;
+layout(set = 0, binding = 2) uniform sampler s_linear;
+
+layout(set = 0, binding = 3) uniform PostProcess {
+    // (exposure, tonemapper: 0 = none, 1 = aces, 2 = reinhard, bloom intensity, bloom threshold)
+    vec4 settings;
+    // (blur direction, 1 / size of t_source)
+    vec4 texel;
+    // (metering: 0 = average, 1 = center weighted, 2 = spot, spot radius, 1 if t_second is a mask)
+    vec4 metering;
+    // (grain intensity, 1 to dither, sharpness, time), zero except in the last pass
+    vec4 final_pass;
+    // (warp: 0 = none, 1 = panini, 2 = fisheye, distance or strength, scale, tan(fov / 2))
+    vec4 warp;
+};
+
+vec3 sample_source(vec2 uv) {
+    return texture(sampler2D(t_source, s_linear), uv).rgb;
+}
+
+float hash(vec2 p) {
+    vec3 p3 = fract(vec3(p.xyx) * 0.1031);
+    p3 += dot(p3, p3.yzx + 33.33);
+    return fract((p3.x + p3.y) * p3.z);
+}
+
+// White noise minus it's neighbours keeps only the high frequencies, which is close to blue
+// noise and doesn't need a texture. It's roughly between -1 and 1.
+float blue_noise(vec2 pixel) {
+    float neighbours = hash(pixel + vec2(-1.0, 0.0)) + hash(pixel + vec2(1.0, 0.0))
+        + hash(pixel + vec2(0.0, -1.0)) + hash(pixel + vec2(0.0, 1.0));
+    return hash(pixel) - neighbours * 0.25;
+}
+
+// Film grain and dithering, every pass that can be the last one calls this.
+vec3 finish(vec3 color, vec2 uv) {
+    vec2 pixel = floor(uv / texel.zw);
+    if (final_pass.x > 0.0) {
+        // The grain changes 24 times a second like film, and shows the most in the mid tones.
+        float frame = floor(final_pass.w * 24.0);
+        float grain = hash(pixel + frame * vec2(17.0, 59.0)) - 0.5;
+        float luminance = clamp(dot(color, vec3(0.2126, 0.7152, 0.0722)), 0.0, 1.0);
+        color += grain * final_pass.x * sqrt(luminance);
+    }
+    if (final_pass.y > 0.0) {
+        // The swap chain stores gamma encoded values, that's where the 8 bit steps are even.
+        vec3 encoded = pow(max(color, 0.0), vec3(1.0 / 2.2));
+        encoded += blue_noise(pixel) / 255.0;
+        color = pow(max(encoded, 0.0), vec3(2.2));
+    }
+    return clamp(color, 0.0, 1.0);
+}
+
+#endif
@@ -0,0 +1,2 @@
+post_vert.glsl
+sharpen_frag.glsl
@@ -0,0 +1,25 @@
+#version 450
+
+#include "post.glsl"
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+
+// Contrast adaptive sharpening, after AMD's FidelityFX CAS. Each pixel is pushed away from it's
+// neighbours, less so where the neighbourhood already has a lot of contrast.
+void main() {
+    vec2 texel_size = texel.zw;
+    vec3 up = sample_source(i_uv + vec2(0.0, -1.0) * texel_size);
+    vec3 left = sample_source(i_uv + vec2(-1.0, 0.0) * texel_size);
+    vec3 center = sample_source(i_uv);
+    vec3 right = sample_source(i_uv + vec2(1.0, 0.0) * texel_size);
+    vec3 down = sample_source(i_uv + vec2(0.0, 1.0) * texel_size);
+
+    vec3 min_rgb = min(min(min(up, left), min(center, right)), down);
+    vec3 max_rgb = max(max(max(up, left), max(center, right)), down);
+    vec3 amount = sqrt(clamp(min(min_rgb, 1.0 - max_rgb) / max(max_rgb, 0.0001), 0.0, 1.0));
+    vec3 weight = amount * (-1.0 / mix(8.0, 5.0, final_pass.z));
+
+    vec3 color = (center + (up + left + right + down) * weight) / (1.0 + 4.0 * weight);
+    o_color = vec4(finish(color, i_uv), 1.0);
+}
@@ -0,0 +1,2 @@
+post_vert.glsl
+tonemap_frag.glsl
\ No newline at end of file
@@ -0,0 +1,33 @@
+#version 450
+
+#include "post.glsl"
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve.
+vec3 aces(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+vec3 reinhard(vec3 x) {
+    return x / (1.0 + x);
+}
+
+void main() {
+    vec3 color = sample_source(i_uv);
+    if (settings.z > 0.0) {
+        color += texture(sampler2D(t_second, s_linear), i_uv).rgb * settings.z;
+    }
+    color *= settings.x;
+
+    int tonemapper = int(settings.y);
+    if (tonemapper == 1) {
+        color = aces(color);
+    } else if (tonemapper == 2) {
+        color = reinhard(color);
+    }
+
+    // The swap chain is sRGB so the hardware does the gamma correction.
+    o_color = vec4(finish(clamp(color, 0.0, 1.0), i_uv), 1.0);
+}
@@ -0,0 +1,2 @@
+post_vert.glsl
+warp_frag.glsl
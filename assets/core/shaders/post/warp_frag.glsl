@@ -0,0 +1,54 @@
+#version 450
+
+#include "post.glsl"
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+
+// Where a point on the panini image lands on the rectilinear one, both are in view space on the
+// plane one unit in front of the camera. From Unity's post processing stack.
+vec2 panini(vec2 view_position, float d) {
+    float view_distance = 1.0 + d;
+    float view_hypotenuse_sq = view_position.x * view_position.x + view_distance * view_distance;
+    float intersection = view_position.x * d;
+    float discriminant = view_hypotenuse_sq - intersection * intersection;
+    float cylinder_distance_minus_d =
+        (-intersection * view_position.x + view_distance * sqrt(discriminant))
+        / view_hypotenuse_sq;
+    float cylinder_distance = cylinder_distance_minus_d + d;
+    vec2 cylinder_position = view_position * (cylinder_distance / view_distance);
+    return cylinder_position / (cylinder_distance - d);
+}
+
+// The distance from the middle is proportional to the angle, like a fisheye lens. The corners
+// stay where they are.
+vec2 fisheye(vec2 view_position, vec2 extents) {
+    float radius = length(view_position);
+    if (radius < 0.0001) {
+        return view_position;
+    }
+    float corner = length(extents);
+    float angle = radius / corner * atan(corner);
+    return view_position / radius * tan(angle);
+}
+
+void main() {
+    float aspect = texel.w / texel.z;
+    vec2 extents = vec2(warp.w * aspect, warp.w);
+    vec2 view_position = (i_uv * 2.0 - 1.0) * extents * warp.z;
+
+    vec2 source_position = view_position;
+    int mode = int(warp.x);
+    if (mode == 1) {
+        source_position = panini(view_position, warp.y);
+    } else if (mode == 2) {
+        source_position = mix(view_position, fisheye(view_position, extents), warp.y);
+    }
+
+    vec2 uv = source_position / extents * 0.5 + 0.5;
+    vec3 color = vec3(0.0);
+    if (all(greaterThanEqual(uv, vec2(0.0))) && all(lessThanEqual(uv, vec2(1.0)))) {
+        color = sample_source(uv);
+    }
+    o_color = vec4(finish(color, i_uv), 1.0);
+}
@@ -0,0 +1,2 @@
+shadow_frag.glsl
+shadow_vert.glsl
\ No newline at end of file
@@ -0,0 +1,5 @@
+#version 450
+
+// Shadow maps only write depth, the pipeline skips this stage.
+void main() {
+}
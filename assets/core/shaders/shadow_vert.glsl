@@ -0,0 +1,19 @@
+#version 450
+
+layout(location = 0) in vec3 i_Pos;
+
+layout(set = 0, binding = 0) uniform Locals {
+    mat4 world;
+};
+
+layout(set = 1, binding = 0) uniform ShadowCaster {
+    mat4 light_view_projection;
+};
+
+void main() {
+    gl_Position = light_view_projection * world * vec4(i_Pos, 1.0);
+#ifdef DEPTH_CLAMP
+    // Pancaking, the light's projection is orthographic so w is always 1.
+    gl_Position.z = max(gl_Position.z, 0.0);
+#endif
+}
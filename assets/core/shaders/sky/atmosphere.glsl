@@ -0,0 +1,189 @@
+#ifndef ATMOSPHERE_INCLUDES
+#define ATMOSPHERE_INCLUDES
+
+// Distances are in km, positions are relative to the planet's center.
+layout(set = 0, binding = 0) uniform Globals {
+    mat4 view_projection;
+    vec4 camera_pos;
+    mat4 view;
+    mat4 projection;
+};
+
+layout(set = 1, binding = 0) uniform AtmosphereUniform {
+    // (direction, cosine of the sun's angular radius)
+    vec4 sun_direction;
+    // (color * intensity, sun disc intensity)
+    vec4 sun_color;
+    // (camera position, units per km)
+    vec4 camera_position;
+    // (bottom radius, top radius, mie g, aerial perspective scale)
+    vec4 radii;
+    // (scattering, scale height)
+    vec4 rayleigh;
+    // (scattering, absorption, scale height, unused)
+    vec4 mie;
+    vec4 ozone;
+    vec4 ground_albedo;
+};
+layout(set = 1, binding = 1) uniform sampler s_atmosphere;
+layout(set = 1, binding = 2) uniform texture2D t_transmittance;
+layout(set = 1, binding = 3) uniform texture2D t_multi_scattering;
+
+const float PI = 3.14159265359;
+
+struct Medium {
+    vec3 rayleigh_scattering;
+    vec3 mie_scattering;
+    vec3 extinction;
+};
+
+Medium sample_medium(float altitude) {
+    float rayleigh_density = exp(-altitude / rayleigh.w);
+    float mie_density = exp(-altitude / mie.z);
+    float ozone_density = max(0.0, 1.0 - abs(altitude - 25.0) / 15.0);
+
+    Medium medium;
+    medium.rayleigh_scattering = rayleigh.xyz * rayleigh_density;
+    medium.mie_scattering = vec3(mie.x * mie_density);
+    medium.extinction = medium.rayleigh_scattering
+        + vec3((mie.x + mie.y) * mie_density)
+        + ozone.xyz * ozone_density;
+    return medium;
+}
+
+// Distance to the nearest hit in front of the ray on a sphere around the planet, or -1.
+float ray_sphere(vec3 origin, vec3 direction, float radius) {
+    float b = dot(origin, direction);
+    float c = dot(origin, origin) - radius * radius;
+    float discriminant = b * b - c;
+    if (discriminant < 0.0) {
+        return -1.0;
+    }
+    float root = sqrt(discriminant);
+    if (-b - root > 0.0) {
+        return -b - root;
+    }
+    return -b + root > 0.0 ? -b + root : -1.0;
+}
+
+// How far a ray goes through the atmosphere, stopping at the ground.
+float atmosphere_distance(vec3 origin, vec3 direction) {
+    float ground = ray_sphere(origin, direction, radii.x);
+    if (ground > 0.0) {
+        return ground;
+    }
+    return max(ray_sphere(origin, direction, radii.y), 0.0);
+}
+
+// Turns a world position into a position in the atmosphere, world y = 0 is the ground.
+vec3 to_atmosphere(vec3 world_position) {
+    vec3 position = world_position / camera_position.w + vec3(0.0, radii.x, 0.0);
+    float height = clamp(length(position), radii.x + 0.001, radii.y - 0.001);
+    return normalize(position) * height;
+}
+
+// Same parameterization as the CPU side in atmosphere.rs.
+vec2 transmittance_uv(float height, float cos_zenith) {
+    float bottom = radii.x;
+    float top = radii.y;
+    float horizon = sqrt(top * top - bottom * bottom);
+    float rho = sqrt(max(height * height - bottom * bottom, 0.0));
+    float discriminant = height * height * (cos_zenith * cos_zenith - 1.0) + top * top;
+    float distance = max(-height * cos_zenith + sqrt(max(discriminant, 0.0)), 0.0);
+    float distance_min = top - height;
+    float distance_max = rho + horizon;
+    return vec2((distance - distance_min) / (distance_max - distance_min), rho / horizon);
+}
+
+vec3 transmittance(float height, float cos_zenith) {
+    vec2 uv = transmittance_uv(height, cos_zenith);
+    return texture(sampler2D(t_transmittance, s_atmosphere), uv).rgb;
+}
+
+vec3 multi_scattering(float height, float cos_sun) {
+    vec2 uv = vec2(cos_sun * 0.5 + 0.5, (height - radii.x) / (radii.y - radii.x));
+    return texture(sampler2D(t_multi_scattering, s_atmosphere), uv).rgb;
+}
+
+float rayleigh_phase(float cos_theta) {
+    return 3.0 / (16.0 * PI) * (1.0 + cos_theta * cos_theta);
+}
+
+// Cornette-Shanks, henyey-greenstein with a better fit for back scattering.
+float mie_phase(float cos_theta, float g) {
+    float g2 = g * g;
+    float k = 3.0 / (8.0 * PI) * (1.0 - g2) / (2.0 + g2);
+    return k * (1.0 + cos_theta * cos_theta) / pow(1.0 + g2 - 2.0 * g * cos_theta, 1.5);
+}
+
+// Light scattered towards the origin along a ray and the transmittance of the ray.
+vec3 integrate_scattering(vec3 origin, vec3 direction, vec3 sun, float max_distance, int steps,
+        out vec3 throughput) {
+    float cos_theta = dot(direction, sun);
+    float phase_rayleigh = rayleigh_phase(cos_theta);
+    float phase_mie = mie_phase(cos_theta, radii.z);
+    float step_size = max_distance / float(steps);
+
+    vec3 luminance = vec3(0.0);
+    throughput = vec3(1.0);
+    for (int i = 0; i < steps; i++) {
+        vec3 position = origin + direction * ((float(i) + 0.5) * step_size);
+        float height = length(position);
+        float cos_sun = dot(position / height, sun);
+        Medium medium = sample_medium(height - radii.x);
+
+        float shadow = ray_sphere(position, sun, radii.x) > 0.0 ? 0.0 : 1.0;
+        vec3 scattering = medium.rayleigh_scattering + medium.mie_scattering;
+        vec3 in_scattered = shadow * transmittance(height, cos_sun)
+            * (medium.rayleigh_scattering * phase_rayleigh + medium.mie_scattering * phase_mie)
+            + multi_scattering(height, cos_sun) * scattering;
+
+        // Integrated analytically over the step.
+        vec3 step_transmittance = exp(-medium.extinction * step_size);
+        vec3 extinction = max(medium.extinction, vec3(1e-6));
+        luminance += throughput * (in_scattered - in_scattered * step_transmittance) / extinction;
+        throughput *= step_transmittance;
+    }
+    return luminance * sun_color.rgb;
+}
+
+// The sky view LUT stores more texels near the horizon, u is the angle around from the sun.
+// From Hillaire's "A Scalable and Production Ready Sky and Atmosphere Rendering Technique".
+vec2 sky_view_uv(float height, float cos_view_zenith, float cos_light_view) {
+    float horizon = sqrt(max(height * height - radii.x * radii.x, 0.0));
+    float beta = acos(horizon / height);
+    float zenith_horizon_angle = PI - beta;
+    float view_zenith_angle = acos(clamp(cos_view_zenith, -1.0, 1.0));
+
+    vec2 uv;
+    if (view_zenith_angle < zenith_horizon_angle) {
+        float coord = 1.0 - sqrt(max(1.0 - view_zenith_angle / zenith_horizon_angle, 0.0));
+        uv.y = coord * 0.5;
+    } else {
+        float coord = sqrt(max((view_zenith_angle - zenith_horizon_angle) / beta, 0.0));
+        uv.y = coord * 0.5 + 0.5;
+    }
+    uv.x = sqrt(-cos_light_view * 0.5 + 0.5);
+    return uv;
+}
+
+// The inverse of sky_view_uv, returns (cos view zenith, cos light view).
+vec2 sky_view_params(float height, vec2 uv) {
+    float horizon = sqrt(max(height * height - radii.x * radii.x, 0.0));
+    float beta = acos(horizon / height);
+    float zenith_horizon_angle = PI - beta;
+
+    float cos_view_zenith;
+    if (uv.y < 0.5) {
+        float coord = 1.0 - 2.0 * uv.y;
+        coord = 1.0 - coord * coord;
+        cos_view_zenith = cos(zenith_horizon_angle * coord);
+    } else {
+        float coord = uv.y * 2.0 - 1.0;
+        cos_view_zenith = cos(zenith_horizon_angle + beta * coord * coord);
+    }
+    float coord = uv.x * uv.x;
+    return vec2(cos_view_zenith, -(coord * 2.0 - 1.0));
+}
+
+#endif
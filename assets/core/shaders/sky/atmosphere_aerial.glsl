@@ -0,0 +1,29 @@
+// Shared by the single and multisampled aerial perspective shaders, they define load_depth.
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+
+// The blend state does frame * alpha + color, alpha being how much of the geometry shows through.
+void main() {
+    float depth = load_depth(ivec2(gl_FragCoord.xy));
+    // The sky already has it's scattering.
+    if (depth >= 1.0 || radii.w <= 0.0) {
+        o_color = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    vec4 world = inverse(view_projection) * vec4(i_uv.x * 2.0 - 1.0, 1.0 - i_uv.y * 2.0, depth, 1.0);
+    vec3 origin = to_atmosphere(camera_pos.xyz);
+    vec3 target = to_atmosphere(world.xyz / world.w);
+    if (length(target - origin) < 0.000001) {
+        o_color = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+    vec3 direction = normalize(target - origin);
+    float distance = min(length(target - origin) * radii.w, atmosphere_distance(origin, direction));
+
+    vec3 throughput;
+    vec3 luminance = integrate_scattering(origin, direction, sun_direction.xyz, distance, 8,
+        throughput);
+    o_color = vec4(luminance, dot(throughput, vec3(1.0 / 3.0)));
+}
@@ -0,0 +1,11 @@
+#version 450
+
+#include "atmosphere.glsl"
+
+layout(set = 2, binding = 0) uniform texture2D t_depth;
+
+float load_depth(ivec2 coord) {
+    return texelFetch(sampler2D(t_depth, s_atmosphere), coord, 0).r;
+}
+
+#include "atmosphere_aerial.glsl"
@@ -0,0 +1,2 @@
+../post/post_vert.glsl
+atmosphere_aerial_msaa_frag.glsl
@@ -0,0 +1,12 @@
+#version 450
+
+#include "atmosphere.glsl"
+
+layout(set = 2, binding = 0) uniform texture2DMS t_depth;
+
+// The first sample is close enough for fog.
+float load_depth(ivec2 coord) {
+    return texelFetch(sampler2DMS(t_depth, s_atmosphere), coord, 0).r;
+}
+
+#include "atmosphere_aerial.glsl"
@@ -0,0 +1,39 @@
+#version 450
+
+#include "atmosphere.glsl"
+
+layout(set = 2, binding = 0) uniform texture2D t_sky_view;
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+
+void main() {
+    vec4 far = inverse(view_projection) * vec4(i_uv.x * 2.0 - 1.0, 1.0 - i_uv.y * 2.0, 1.0, 1.0);
+    vec3 direction = normalize(far.xyz / far.w - camera_pos.xyz);
+
+    float height = length(camera_position.xyz);
+    vec3 up = camera_position.xyz / height;
+    float cos_view_zenith = dot(direction, up);
+
+    // The angle around from the sun, measured flat on the horizon.
+    vec3 flat_direction = direction - up * cos_view_zenith;
+    vec3 flat_sun = sun_direction.xyz - up * dot(sun_direction.xyz, up);
+    float cos_light_view = 1.0;
+    if (length(flat_direction) > 0.0001 && length(flat_sun) > 0.0001) {
+        cos_light_view = dot(normalize(flat_direction), normalize(flat_sun));
+    }
+
+    vec2 uv = sky_view_uv(height, cos_view_zenith, cos_light_view);
+    vec3 luminance = texture(sampler2D(t_sky_view, s_atmosphere), uv).rgb;
+
+    // The sun disc, darkened towards the edge.
+    float cos_sun = dot(direction, sun_direction.xyz);
+    if (cos_sun > sun_direction.w && ray_sphere(camera_position.xyz, direction, radii.x) < 0.0) {
+        float edge = clamp((1.0 - cos_sun) / (1.0 - sun_direction.w), 0.0, 1.0);
+        float limb_darkening = sqrt(1.0 - edge * edge) * 0.6 + 0.4;
+        luminance += sun_color.rgb * sun_color.w * limb_darkening
+            * transmittance(height, cos_view_zenith);
+    }
+
+    o_color = vec4(luminance, 1.0);
+}
@@ -0,0 +1,2 @@
+../post/post_vert.glsl
+atmosphere_sky_view_frag.glsl
@@ -0,0 +1,29 @@
+#version 450
+
+#include "atmosphere.glsl"
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+
+// Drawn in a space where the camera is straight up from the planet's center and the sun is
+// in the xy plane, the sky only depends on the angles to the zenith and the sun.
+void main() {
+    float height = length(camera_position.xyz);
+    vec3 up = camera_position.xyz / height;
+    float cos_sun = dot(up, sun_direction.xyz);
+    vec3 sun = vec3(sqrt(max(1.0 - cos_sun * cos_sun, 0.0)), cos_sun, 0.0);
+
+    vec2 params = sky_view_params(height, i_uv);
+    float sin_view_zenith = sqrt(max(1.0 - params.x * params.x, 0.0));
+    vec3 direction = vec3(
+        sin_view_zenith * params.y,
+        params.x,
+        sin_view_zenith * sqrt(max(1.0 - params.y * params.y, 0.0))
+    );
+    vec3 origin = vec3(0.0, height, 0.0);
+
+    vec3 throughput;
+    vec3 luminance = integrate_scattering(origin, direction, sun,
+        atmosphere_distance(origin, direction), 30, throughput);
+    o_color = vec4(luminance, 1.0);
+}
@@ -0,0 +1,24 @@
+#ifndef CLOUD_INCLUDES
+#define CLOUD_INCLUDES
+
+layout(set = 0, binding = 0) uniform CloudUniform {
+    mat4 inverse_view_projection;
+    mat4 previous_view_projection;
+    // (camera position, time)
+    vec4 camera_position;
+    vec4 sun_direction;
+    // (sun color, ambient)
+    vec4 sun_color;
+    // (bottom, top, coverage, density)
+    vec4 layer;
+    // (wind x, wind z, 1 / scale, unused)
+    vec4 wind;
+    // (steps, light steps, frame, history weight)
+    vec4 info;
+};
+layout(set = 0, binding = 1) uniform texture3D t_noise;
+// Last frame's clouds while raymarching, this frame's clouds while compositing.
+layout(set = 0, binding = 2) uniform texture2D t_clouds;
+layout(set = 0, binding = 3) uniform sampler s_clouds;
+
+#endif
@@ -0,0 +1,2 @@
+clouds_vert.glsl
+clouds_frag.glsl
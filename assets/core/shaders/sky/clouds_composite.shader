@@ -0,0 +1,2 @@
+clouds_vert.glsl
+clouds_composite_frag.glsl
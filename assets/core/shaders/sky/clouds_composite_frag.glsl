@@ -0,0 +1,11 @@
+#version 450
+
+#include "clouds.glsl"
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+
+// The blend state does frame * alpha + color, alpha being how much light gets through the clouds.
+void main() {
+    o_color = texture(sampler2D(t_clouds, s_clouds), i_uv);
+}
@@ -0,0 +1,119 @@
+#version 450
+
+#include "clouds.glsl"
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+
+const float PI = 3.14159265359;
+
+float remap(float value, float old_min, float old_max, float new_min, float new_max) {
+    return new_min + (value - old_min) / (old_max - old_min) * (new_max - new_min);
+}
+
+// Interleaved gradient noise, offsets the first step differently per pixel and frame.
+float jitter(vec2 pixel, float frame) {
+    pixel += frame * 5.588238;
+    return fract(52.9829189 * fract(dot(pixel, vec2(0.06711056, 0.00583715))));
+}
+
+// Henyey-Greenstein phase, clouds scatter most light forwards.
+float phase(float cos_theta, float g) {
+    float g2 = g * g;
+    return (1.0 - g2) / (4.0 * PI * pow(1.0 + g2 - 2.0 * g * cos_theta, 1.5));
+}
+
+float cloud_density(vec3 position) {
+    float height = clamp((position.y - layer.x) / (layer.y - layer.x), 0.0, 1.0);
+    // Round off the bottom and thin out the top of the layer.
+    float gradient = smoothstep(0.0, 0.15, height) * smoothstep(1.0, 0.5, height);
+
+    vec3 uvw = (position + vec3(wind.x, 0.0, wind.y) * camera_position.w) * wind.z;
+    vec4 noise = texture(sampler3D(t_noise, s_clouds), uvw);
+    float shape = remap(noise.r * gradient, 1.0 - layer.z, 1.0, 0.0, 1.0);
+    if (shape <= 0.0) {
+        return 0.0;
+    }
+    // Eat away at the edges with the finer noise, the top of the clouds keeps more detail.
+    vec4 detail = texture(sampler3D(t_noise, s_clouds), uvw * 4.0);
+    float erosion = mix(detail.g, 1.0 - detail.g, height) * 0.35;
+    return clamp(remap(shape, erosion, 1.0, 0.0, 1.0), 0.0, 1.0) * layer.w;
+}
+
+// How much sunlight reaches a point in the clouds.
+float light_transmittance(vec3 position, vec3 sun, float step_size) {
+    int light_steps = int(info.y);
+    float density = 0.0;
+    for (int i = 0; i < light_steps; i++) {
+        position += sun * step_size;
+        density += cloud_density(position);
+    }
+    return exp(-density * step_size);
+}
+
+// Distances along the ray where it enters and leaves the layer, x > y when it misses.
+vec2 intersect_layer(vec3 origin, vec3 direction) {
+    if (abs(direction.y) < 0.0001) {
+        bool inside = origin.y > layer.x && origin.y < layer.y;
+        return inside ? vec2(0.0, 1.0e5) : vec2(1.0, 0.0);
+    }
+    float t_bottom = (layer.x - origin.y) / direction.y;
+    float t_top = (layer.y - origin.y) / direction.y;
+    float t_enter = max(min(t_bottom, t_top), 0.0);
+    float t_exit = max(t_bottom, t_top);
+    // Stop marching near the horizon, it's far away and would need a lot of steps.
+    return vec2(t_enter, min(t_exit, t_enter + (layer.y - layer.x) * 8.0));
+}
+
+void main() {
+    vec2 ndc = vec2(i_uv.x * 2.0 - 1.0, 1.0 - i_uv.y * 2.0);
+    vec4 far = inverse_view_projection * vec4(ndc, 1.0, 1.0);
+    vec3 origin = camera_position.xyz;
+    vec3 direction = normalize(far.xyz / far.w - origin);
+    vec3 sun = normalize(sun_direction.xyz);
+
+    vec4 result = vec4(0.0, 0.0, 0.0, 1.0);
+    vec2 range = intersect_layer(origin, direction);
+    if (range.x < range.y) {
+        int steps = int(info.x);
+        float step_size = (range.y - range.x) / float(steps);
+        float light_step = (layer.y - layer.x) / max(info.y, 1.0) * 0.5;
+        float t = range.x + step_size * jitter(gl_FragCoord.xy, info.z);
+        float sun_phase = mix(phase(dot(direction, sun), 0.6), phase(dot(direction, sun), -0.3), 0.3);
+
+        vec3 scattered = vec3(0.0);
+        float transmittance = 1.0;
+        for (int i = 0; i < steps; i++) {
+            vec3 position = origin + direction * t;
+            float density = cloud_density(position);
+            if (density > 0.0) {
+                float height = clamp((position.y - layer.x) / (layer.y - layer.x), 0.0, 1.0);
+                vec3 sun_light = sun_color.rgb * light_transmittance(position, sun, light_step) * sun_phase * 4.0;
+                // Cheap stand in for light bouncing around inside the cloud, brighter near the top.
+                vec3 ambient = sun_color.rgb * sun_color.w * mix(0.5, 1.0, height);
+                float step_transmittance = exp(-density * step_size);
+                // Energy conserving integration from Frostbite's volumetrics.
+                vec3 light = (sun_light + ambient) * density;
+                scattered += transmittance * (light - light * step_transmittance) / max(density, 0.0001);
+                transmittance *= step_transmittance;
+                if (transmittance < 0.01) {
+                    break;
+                }
+            }
+            t += step_size;
+        }
+        result = vec4(scattered, transmittance);
+    }
+
+    // Reproject the clouds as if they were infinitely far away, close enough for a sky layer.
+    if (info.w > 0.0) {
+        vec4 previous = previous_view_projection * vec4(origin + direction * 1.0e4, 1.0);
+        vec2 previous_uv = previous.xy / previous.w * vec2(0.5, -0.5) + 0.5;
+        if (previous.w > 0.0 && all(greaterThanEqual(previous_uv, vec2(0.0))) && all(lessThanEqual(previous_uv, vec2(1.0)))) {
+            vec4 history = texture(sampler2D(t_clouds, s_clouds), previous_uv);
+            result = mix(result, history, info.w);
+        }
+    }
+
+    o_color = result;
+}
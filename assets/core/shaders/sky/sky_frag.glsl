@@ -22,25 +22,14 @@ struct DirectionalLight {
     vec4 color;
 };
 
-struct PointLight {
-    vec4 position;
-    vec4 color;
-    vec4 attenuation;
-};
-
 layout (set = 0, binding = 1) uniform LightingData {
     vec4 light_num;
     DirectionalLight directional_lights[MAX_LIGHTS / 2];
-    PointLight point_lights[MAX_LIGHTS / 2];
 };
 
 DirectionalLight get_directional_light(int index) {
     return directional_lights[index];
 }
-
-PointLight get_point_light(int index) {
-    return point_lights[index];
-}
 // END LIGHTING
 
 layout(location = 0) in vec3 v_Uv;
@@ -0,0 +1,2 @@
+sprite_vert.glsl
+sprite_frag.glsl
\ No newline at end of file
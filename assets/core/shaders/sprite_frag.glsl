@@ -0,0 +1,16 @@
+#version 450
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 1) in vec4 i_color;
+layout(location = 0) out vec4 outColor;
+
+layout(set = 1, binding = 0) uniform sampler sprite_sampler;
+layout(set = 1, binding = 1) uniform texture2D sprite_texture;
+
+void main() {
+    vec4 color = texture(sampler2D(sprite_texture, sprite_sampler), i_uv) * i_color;
+    if (color.a <= 0.0) {
+        discard;
+    }
+    outColor = color;
+}
@@ -0,0 +1,18 @@
+#version 450
+
+layout(location = 0) in vec3 i_position;
+layout(location = 1) in vec2 i_uv;
+layout(location = 2) in vec4 i_color;
+
+layout(location = 0) out vec2 o_uv;
+layout(location = 1) out vec4 o_color;
+
+layout(set = 0, binding = 0) uniform Globals {
+    mat4 view_projection;
+};
+
+void main() {
+    o_uv = i_uv;
+    o_color = i_color;
+    gl_Position = view_projection * vec4(i_position, 1.0);
+}
@@ -0,0 +1,123 @@
+// Shared by the single and multisampled SSGI shaders, they define load_depth.
+
+layout(location = 0) in vec2 i_uv;
+layout(location = 0) out vec4 o_color;
+
+const float PI = 3.14159265358979323;
+
+float hash(vec2 p) {
+    vec3 p3 = fract(vec3(p.xyx) * 0.1031);
+    p3 += dot(p3, p3.yzx + 33.33);
+    return fract((p3.x + p3.y) * p3.z);
+}
+
+// uv goes from 0 to 1 over the view, not the whole frame.
+ivec2 view_pixel(vec2 uv) {
+    ivec2 pixel = ivec2((viewport.xy + uv * viewport.zw) * screen.xy);
+    return clamp(pixel, ivec2(0), ivec2(screen.xy) - 1);
+}
+
+vec3 world_position(vec2 uv, float depth) {
+    vec4 world = inverse_view_projection * vec4(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    return world.xyz / world.w;
+}
+
+vec3 position_at(vec2 uv) {
+    return world_position(uv, load_depth(view_pixel(uv)));
+}
+
+// Returns the view uv and depth of a world position.
+vec3 project(mat4 matrix, vec3 position) {
+    vec4 clip = matrix * vec4(position, 1.0);
+    vec3 ndc = clip.xyz / clip.w;
+    return vec3(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5, ndc.z);
+}
+
+bool inside(vec2 uv) {
+    return all(greaterThanEqual(uv, vec2(0.0))) && all(lessThanEqual(uv, vec2(1.0)));
+}
+
+// Cosine weighted, so averaging what the rays hit gives the light reaching the surface.
+vec3 hemisphere_direction(vec3 normal, vec2 random) {
+    float phi = random.x * 2.0 * PI;
+    float sin_theta = sqrt(random.y);
+    vec3 tangent = normalize(abs(normal.y) < 0.99 ? cross(normal, vec3(0.0, 1.0, 0.0))
+                                                  : cross(normal, vec3(1.0, 0.0, 0.0)));
+    vec3 bitangent = cross(normal, tangent);
+    vec3 local = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, sqrt(1.0 - random.y));
+    return normalize(tangent * local.x + bitangent * local.y + normal * local.z);
+}
+
+// Marches along the ray and returns the lit color of the first surface it goes behind.
+vec3 trace(vec3 origin, vec3 direction, float offset) {
+    float radius = info.x;
+    int steps = int(info.z);
+    for (int i = 0; i < steps; ++i) {
+        float distance = radius * (float(i) + offset) / float(steps);
+        vec3 ray = origin + direction * distance;
+        vec3 projected = project(view_projection, ray);
+        if (!inside(projected.xy)) {
+            break;
+        }
+        float scene_depth = load_depth(view_pixel(projected.xy));
+        // Surfaces much further than a step in front of the ray are gaps it passes behind.
+        vec3 scene = world_position(projected.xy, scene_depth);
+        if (projected.z > scene_depth && length(scene - ray) < radius / float(steps) * 2.0) {
+            vec2 frame_uv = viewport.xy + projected.xy * viewport.zw;
+            return textureLod(sampler2D(t_color, s_linear), frame_uv, 0.0).rgb;
+        }
+    }
+    return vec3(0.0);
+}
+
+void main() {
+    o_color = vec4(0.0, 0.0, 0.0, 1.0);
+    // The targets cover the whole frame, only the first view gets bounce light.
+    vec2 uv = (i_uv - viewport.xy) / viewport.zw;
+    if (!inside(uv)) {
+        return;
+    }
+    float depth = load_depth(view_pixel(uv));
+    if (depth >= 1.0) {
+        return;
+    }
+
+    // There's no normal buffer, the normal comes from the neighbouring depths.
+    vec3 position = world_position(uv, depth);
+    vec2 texel = 1.0 / (viewport.zw * screen.xy);
+    vec3 dx = position_at(uv + vec2(texel.x, 0.0)) - position;
+    vec3 dy = position_at(uv + vec2(0.0, texel.y)) - position;
+    vec3 normal = normalize(cross(dy, dx));
+    vec3 to_camera = camera_position.xyz - position;
+    if (dot(normal, to_camera) < 0.0) {
+        normal = -normal;
+    }
+    // Pushed off the surface so the rays don't hit where they start.
+    vec3 origin = position + normal * info.x * 0.02;
+
+    int samples = int(info.y);
+    float frame = screen.z;
+    vec3 gathered = vec3(0.0);
+    for (int i = 0; i < samples; ++i) {
+        vec2 seed = gl_FragCoord.xy + vec2(float(i) * 7.0 + frame * 13.0, frame * 5.0);
+        vec2 random = vec2(hash(seed), hash(seed + 91.7));
+        vec3 direction = hemisphere_direction(normal, random);
+        gathered += trace(origin, direction, hash(seed + 37.1));
+    }
+    gathered /= float(samples);
+
+    // There's no albedo buffer, the surface's hue from the lit frame stands in for it.
+    vec2 frame_uv = viewport.xy + uv * viewport.zw;
+    vec3 color = textureLod(sampler2D(t_color, s_linear), frame_uv, 0.0).rgb;
+    vec3 tint = color / max(max(color.r, max(color.g, color.b)), 0.0001);
+    gathered *= tint;
+
+    // Blend with last frame's light where this surface was.
+    vec3 previous = project(previous_view_projection, position);
+    if (info.w > 0.0 && inside(previous.xy)) {
+        vec2 history_uv = viewport.xy + previous.xy * viewport.zw;
+        vec3 history = textureLod(sampler2D(t_history, s_linear), history_uv, 0.0).rgb;
+        gathered = mix(gathered, history, info.w);
+    }
+    o_color = vec4(gathered, 1.0);
+}
@@ -0,0 +1,2 @@
+post/post_vert.glsl
+ssgi_frag.glsl
\ No newline at end of file
@@ -0,0 +1,13 @@
+#version 450
+#extension GL_GOOGLE_include_directive : enable
+
+#include "library/ssgi.glsl"
+
+layout(location = 0) out vec4 o_color;
+
+// Added on top of the frame by the blend state.
+void main() {
+    vec2 uv = gl_FragCoord.xy / screen.xy;
+    vec3 light = textureLod(sampler2D(t_color, s_linear), uv, 0.0).rgb;
+    o_color = vec4(light * camera_position.w, 1.0);
+}
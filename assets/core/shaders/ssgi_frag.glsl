@@ -0,0 +1,12 @@
+#version 450
+#extension GL_GOOGLE_include_directive : enable
+
+#include "library/ssgi.glsl"
+
+layout(set = 0, binding = 1) uniform texture2D t_depth;
+
+float load_depth(ivec2 coord) {
+    return texelFetch(sampler2D(t_depth, s_linear), coord, 0).r;
+}
+
+#include "ssgi.glsl"
@@ -0,0 +1,13 @@
+#version 450
+#extension GL_GOOGLE_include_directive : enable
+
+#include "library/ssgi.glsl"
+
+layout(set = 0, binding = 1) uniform texture2DMS t_depth;
+
+// The first sample is close enough, the bounce light is blurry anyway.
+float load_depth(ivec2 coord) {
+    return texelFetch(sampler2DMS(t_depth, s_linear), coord, 0).r;
+}
+
+#include "ssgi.glsl"
@@ -0,0 +1,25 @@
+layout(location = 0) in vec2 i_uv;
+layout(location = 1) in vec4 i_color;
+layout(location = 0) out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform Transform {
+    mat4 ortho;
+    vec4 info;
+};
+layout(set = 0, binding = 1) uniform sampler glyph_sampler;
+layout(set = 0, binding = 2) uniform texture2D glyph_atlas;
+
+// The glyph's color premultiplied by it's coverage, nothing where the scene is in front.
+vec4 glyph_color(float scene_depth) {
+    if (gl_FragCoord.z > scene_depth) {
+        return vec4(0.0);
+    }
+    float alpha = texture(sampler2D(glyph_atlas, glyph_sampler), i_uv).r;
+    if (info.x > 0.5) {
+        // Distance field, the edge is at 0.5. Smooth over about a pixel at any size.
+        float width = max(fwidth(alpha) * 0.5, 0.0001);
+        alpha = smoothstep(0.5 - width, 0.5 + width, alpha);
+    }
+    alpha *= i_color.a;
+    return vec4(i_color.rgb * alpha, alpha);
+}
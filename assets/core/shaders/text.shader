@@ -0,0 +1,2 @@
+text_vert.glsl
+text_frag.glsl
\ No newline at end of file
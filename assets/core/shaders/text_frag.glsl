@@ -0,0 +1,13 @@
+#version 450
+
+#include "text.glsl"
+
+layout(set = 1, binding = 0) uniform texture2D t_depth;
+
+void main() {
+    float depth = texelFetch(sampler2D(t_depth, glyph_sampler), ivec2(gl_FragCoord.xy), 0).r;
+    outColor = glyph_color(depth);
+    if (outColor.a <= 0.0) {
+        discard;
+    }
+}
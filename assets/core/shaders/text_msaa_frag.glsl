@@ -0,0 +1,14 @@
+#version 450
+
+#include "text.glsl"
+
+layout(set = 1, binding = 0) uniform texture2DMS t_depth;
+
+// The first sample is close enough to hide text behind geometry.
+void main() {
+    float depth = texelFetch(sampler2DMS(t_depth, glyph_sampler), ivec2(gl_FragCoord.xy), 0).r;
+    outColor = glyph_color(depth);
+    if (outColor.a <= 0.0) {
+        discard;
+    }
+}
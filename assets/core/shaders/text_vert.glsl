@@ -0,0 +1,41 @@
+#version 450
+
+layout(location = 0) in vec3 left_top;
+layout(location = 1) in vec2 right_bottom;
+layout(location = 2) in vec2 tex_left_top;
+layout(location = 3) in vec2 tex_right_bottom;
+layout(location = 4) in vec4 color;
+
+layout(location = 0) out vec2 o_uv;
+layout(location = 1) out vec4 o_color;
+
+layout(set = 0, binding = 0) uniform Transform {
+    mat4 ortho;
+    vec4 info;
+};
+
+void main() {
+    vec2 position = vec2(0.0);
+    // Build the quad from the vertex index, drawn as a triangle strip.
+    switch (gl_VertexIndex) {
+        case 0:
+            position = left_top.xy;
+            o_uv = tex_left_top;
+            break;
+        case 1:
+            position = vec2(right_bottom.x, left_top.y);
+            o_uv = vec2(tex_right_bottom.x, tex_left_top.y);
+            break;
+        case 2:
+            position = vec2(left_top.x, right_bottom.y);
+            o_uv = vec2(tex_left_top.x, tex_right_bottom.y);
+            break;
+        case 3:
+            position = right_bottom;
+            o_uv = tex_right_bottom;
+            break;
+    }
+
+    o_color = color;
+    gl_Position = ortho * vec4(position, left_top.z, 1.0);
+}
@@ -4,7 +4,11 @@ layout(location = 0) in vec3 i_Pos;
 layout(location = 1) in vec3 i_normal;
 layout(location = 2) in vec2 i_uv;
 layout(location = 3) in vec4 i_tangent;
+#ifdef VERTEX_ATTRIBUTES
+layout(location = 9) in vec4 i_vertex_color;
+#endif
 layout(location = 0) out vec2 v_TexCoord;
+layout(location = 1) out vec4 o_color;
 
 layout(set = 1, binding = 0) uniform Globals {
     mat4 view_projection;
@@ -16,5 +20,10 @@ layout(set = 0, binding = 0) uniform Locals {
 
 void main() {
     v_TexCoord = i_uv;
+#ifdef VERTEX_ATTRIBUTES
+    o_color = i_vertex_color;
+#else
+    o_color = vec4(1.0);
+#endif
     gl_Position = view_projection * world * vec4(i_Pos, 1.0);
 }
\ No newline at end of file
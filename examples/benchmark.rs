@@ -7,10 +7,8 @@ use winit::{
     event_loop::ControlFlow,
 };
 
-use harmony::scene::components::{
-    CameraData, DirectionalLightData, LightType, Material, Mesh, Transform,
-};
-use harmony::scene::{resources::DeltaTime, Scene};
+use harmony::scene::components::{CameraData, DirectionalLightData, LightType, Transform};
+use harmony::scene::{resources::DeltaTime, spawn_batch, MeshBundle, Scene};
 use harmony::{
     graphics::resources::{ProbeFormat, ProbeQuality},
     WinitState,
@@ -64,26 +62,26 @@ impl harmony::AppState for AppState {
         // 1. Mesh - This is our reference to let the renderer know which asset to use from the asset pipeline.
         // 2. Material - GLTF files come with their own materials this is a reference to which material globally
         // we are picking from the asset manager. In the future we'll have an API to retrieve the material index
-        // in a friendly way. For now we only have 1 GLTF file and 1 material in the file so our material index is 0.
+        // in a friendly way. The built in materials come first so we looked the index up above.
         // 3. The transform which allows us to render the mesh using it's world cords. This also includes stuff like
         // rotation and scale.
+        // Bundles let us spawn all of the cubes with a single insert.
         let size = 50;
         let scale = 3.0;
+        let mut cubes = Vec::new();
         for x in 0..size {
             for y in 0..size {
                 let mut transform = Transform::new(app);
                 transform.position.x = x as f32 * scale;
                 transform.position.y = y as f32 * scale;
-                app.current_scene.world.insert(
-                    (),
-                    vec![(
-                        Mesh::new("cube.gltf"),
-                        Material::new(cube_material_index), // Need to be an index to the material
-                        transform,                          // Transform
-                    )],
-                );
+                cubes.push(MeshBundle::new(
+                    "cube.gltf",
+                    cube_material_index, // Need to be an index to the material
+                    transform,
+                ));
             }
         }
+        spawn_batch(&mut app.current_scene.world, cubes);
 
         // Here we create our skybox entity and populate it with a HDR skybox texture.
         // create skybox first for now this *has* to be done in load.
@@ -113,6 +111,7 @@ impl harmony::AppState for AppState {
             LightType::Directional(DirectionalLightData {
                 direction: Vec3::new(0.0, 1.0, -0.5),
                 color: Vec3::new(1.0, 1.0, 1.0),
+                ..Default::default()
             }),
             light_transform,
         );
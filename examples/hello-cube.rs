@@ -9,12 +9,14 @@ use winit::{
 };
 
 use harmony::scene::{
-    components::{CameraData, DirectionalLightData, LightType, Material, Mesh, Transform},
+    components::{
+        CameraData, DirectionalLightData, LightType, Material, Mesh, OrbitCamera, PointLightData,
+        Transform,
+    },
     resources::DeltaTime,
     Scene,
 };
 use harmony::{
-    core::input::{Input, MouseButton},
     graphics::resources::{ProbeFormat, ProbeQuality},
     WinitState,
 };
@@ -49,40 +51,11 @@ fn create_rotate_system() -> Box<dyn Schedulable> {
         })
 }
 
-fn create_camera_orbit_system() -> Box<dyn Schedulable> {
-    SystemBuilder::new("Camera Orbit")
-        .read_resource::<DeltaTime>()
-        .read_resource::<Input>()
-        .with_query(<Write<CameraData>>::query())
-        .build(|_, mut world, (delta_time, input), camera_query| {
-            for mut camera in camera_query.iter_mut(&mut world) {
-                if !input.is_mouse_button_down(MouseButton::Left) {
-                    continue;
-                }
-                camera.yaw += input.mouse_delta.x * 0.5 * delta_time.0;
-                camera.pitch += input.mouse_delta.y * 0.5 * delta_time.0;
-                camera.pitch = camera
-                    .pitch
-                    .max(-std::f32::consts::FRAC_PI_2 + 0.0001)
-                    .min(std::f32::consts::FRAC_PI_2 - 0.0001);
-                let eye = Vec3::new(0.0, 0.0, 0.0)
-                    + (5.0
-                        * nalgebra::Vector3::new(
-                            camera.yaw.sin() * camera.pitch.cos(),
-                            camera.pitch.sin(),
-                            camera.yaw.cos() * camera.pitch.cos(),
-                        ));
-                camera.position = eye;
-                camera.update_view(eye, Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
-            }
-        })
-}
-
 impl harmony::AppState for AppState {
     fn load(&mut self, app: &mut harmony::Application) {
         let scheduler_builder = Schedule::builder()
             .add_system(create_rotate_system())
-            .add_system(create_camera_orbit_system());
+            .add_system(harmony::scene::systems::camera_controller::create());
         app.current_scene = Scene::new(None, Some(scheduler_builder));
 
         // We need to find the material index for the material that automatically gets created when loading in the GLTF.
@@ -99,7 +72,7 @@ impl harmony::AppState for AppState {
         // 1. Mesh - This is our reference to let the renderer know which asset to use from the asset pipeline.
         // 2. Material - GLTF files come with their own materials this is a reference to which material globally
         // we are picking from the asset manager. In the future we'll have an API to retrieve the material index
-        // in a friendly way. For now we only have 1 GLTF file and 1 material in the file so our material index is 0.
+        // in a friendly way. The built in materials come first so we looked the index up above.
         // 3. The transform which allows us to render the mesh using it's world cords. This also includes stuff like
         // rotation and scale.
         let transform = Transform::new(app);
@@ -137,55 +110,42 @@ impl harmony::AppState for AppState {
             LightType::Directional(DirectionalLightData {
                 direction: Vec3::new(0.0, 1.0, 0.0),
                 color: Vec3::new(0.9, 0.55, 0.42),
+                casts_shadows: true,
+                ..Default::default()
             }),
             light_transform,
         );
 
         // Add red point light to our scene.
-        // Uncomment this code to see point light.
-        // Point lights currently don't work.
-        // let mut transform = Transform::new(app);
-        // transform.position = Vec3::new(-5.0, 0.0, 0.0);
-        // harmony::scene::entities::light::create(
-        //     &mut scene.world,
-        //     LightType::Point(PointLightData {
-        //         color: Vec3::new(1.0, 0.0, 0.0),
-        //         attenuation: 10.0,
-        //     }),
-        //     transform,
-        // );
+        let mut transform = Transform::new(app);
+        transform.position = Vec3::new(-2.0, 0.0, 0.0);
+        harmony::scene::entities::light::create(
+            &mut scene.world,
+            LightType::Point(PointLightData {
+                color: Vec3::new(1.0, 0.0, 0.0),
+                intensity: 10.0,
+                range: 10.0,
+                ..Default::default()
+            }),
+            transform,
+        );
 
         let actual_window_size = app.get_window_actual_size();
 
         // TODO: Add other camera types other than perspective.
         // We can't render anything without a camera. Add one here.
         // Thankfully we have a method to help that makes it easy!
-        let mut camera_data = CameraData::new_perspective(
+        let camera_data = CameraData::new_perspective(
             70.0,
             actual_window_size.width,
             actual_window_size.height,
             0.01,
             10.0,
         );
-        camera_data.position = Vec3::new(0.0, 0.0, 5.0);
-        camera_data.update_view(
-            camera_data.position,     // This is our camera's "position".
-            Vec3::new(0.0, 0.0, 0.0), // Where the camera is looking at.
-            Vec3::new(0.0, 1.0, 0.0), // Our camera's up vector.
-        );
-        harmony::scene::entities::camera::create(&mut app.current_scene.world, camera_data);
-    }
-
-    fn resize(&mut self, app: &mut harmony::Application) {
-        let world = &mut app.current_scene.world;
-        // This is kinda of a hacky soultion. It might be better to have this be handled internally for each camera.
-        let query = <(Write<CameraData>,)>::query();
-        for mut camera in query.iter_mut(world) {
-            camera.0.resize(
-                app.renderer.size.width as f32,
-                app.renderer.size.height as f32,
-            );
-        }
+        // Drag with the left mouse button to orbit around the cube and scroll to zoom.
+        // Cameras are resized with the window automatically.
+        let orbit = OrbitCamera::new(Vec3::new(0.0, 0.0, 0.0), 5.0).with_distance_range(2.0, 9.0);
+        app.current_scene.world.insert((), vec![(camera_data, orbit)]);
     }
 }
 
@@ -1,13 +1,8 @@
 use log;
 use nalgebra_glm::{Vec2, Vec3};
 
-use harmony::WinitState;
 use imgui::{im_str, Condition};
-use winit::{
-    dpi::LogicalSize,
-    event::{Event, WindowEvent},
-    event_loop::ControlFlow,
-};
+use winit::dpi::LogicalSize;
 
 struct WindowSize {
     width: u32,
@@ -79,36 +74,14 @@ fn main() {
         .filter_module("harmony", log::LevelFilter::Info)
         .init();
 
-    let (wb, event_loop) = WinitState::create(
-        "Harmony - Hello World",
-        LogicalSize::new(WINDOW_SIZE.width, WINDOW_SIZE.height),
-    );
-
     // Tell harmony where our asset path is.
     let asset_path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/").to_string();
-    let mut application = harmony::Application::new(wb, &event_loop, asset_path, vec![]);
-    let mut app_state = AppState::new();
-    // Call application load to have harmony load all the required assets.
-    application.load(&mut app_state);
 
-    // Standard winit event loop here.
-    event_loop.run(move |event, _, control_flow| {
-        // Here is where the harmony does most of the work and it accepts events from winit.
-        application.run(&mut app_state, &event, control_flow);
-        match event {
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                winit::event::WindowEvent::KeyboardInput {
-                    input:
-                        winit::event::KeyboardInput {
-                            virtual_keycode: Some(winit::event::VirtualKeyCode::Escape),
-                            ..
-                        },
-                    ..
-                } => *control_flow = winit::event_loop::ControlFlow::Exit,
-                _ => {}
-            },
-            _ => (),
-        };
-    });
+    // The builder creates the window, loads the assets and runs the event loop for us.
+    harmony::AppBuilder::new(
+        "Harmony - Hello World",
+        LogicalSize::new(WINDOW_SIZE.width, WINDOW_SIZE.height),
+    )
+    .with_asset_path(asset_path)
+    .run(AppState::new());
 }
@@ -0,0 +1,106 @@
+use nalgebra_glm::Vec3;
+
+/// What an agent is currently trying to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Behavior {
+    /// Stand still, neighbors can still push us around.
+    Idle,
+    Seek(Vec3),
+    Flee {
+        target: Vec3,
+        panic_distance: f32,
+    },
+    Arrive {
+        target: Vec3,
+        slowing_radius: f32,
+    },
+    Wander {
+        radius: f32,
+        distance: f32,
+        /// How much the wander angle can change per second, in radians.
+        jitter: f32,
+    },
+    /// Follows a list of waypoints, for example a path from a navmesh or any other path finder.
+    /// We arrive at the last waypoint unless `looped` is set.
+    FollowPath {
+        waypoints: Vec<Vec3>,
+        /// How close we need to get to a waypoint before moving on to the next one.
+        waypoint_radius: f32,
+        looped: bool,
+    },
+}
+
+impl Default for Behavior {
+    fn default() -> Self {
+        Behavior::Idle
+    }
+}
+
+/// An entity that moves itself using steering behaviors.
+/// The steering system moves the entity's transform, so don't move it yourself.
+#[derive(Debug, Clone)]
+pub struct Agent {
+    pub behavior: Behavior,
+    pub max_speed: f32,
+    pub max_acceleration: f32,
+    pub velocity: Vec3,
+    /// How strongly we avoid other agents within `neighbor_radius`.
+    pub separation: f32,
+    /// How strongly we stay close to other agents within `neighbor_radius`.
+    pub cohesion: f32,
+    pub neighbor_radius: f32,
+    /// Turns the transform so it's +Z axis faces the direction we're moving in.
+    pub face_movement: bool,
+    pub(crate) wander_angle: f32,
+    pub(crate) random_state: u32,
+    pub(crate) current_waypoint: usize,
+}
+
+impl Default for Agent {
+    fn default() -> Self {
+        Self {
+            behavior: Behavior::Idle,
+            max_speed: 4.0,
+            max_acceleration: 8.0,
+            velocity: Vec3::zeros(),
+            separation: 0.0,
+            cohesion: 0.0,
+            neighbor_radius: 2.0,
+            face_movement: true,
+            wander_angle: 0.0,
+            random_state: 0,
+            current_waypoint: 0,
+        }
+    }
+}
+
+impl Agent {
+    pub fn new(behavior: Behavior) -> Self {
+        Self {
+            behavior,
+            ..Self::default()
+        }
+    }
+
+    /// Changes the behavior, this also restarts path following.
+    pub fn set_behavior(&mut self, behavior: Behavior) {
+        self.behavior = behavior;
+        self.current_waypoint = 0;
+    }
+
+    /// The waypoint we're currently heading towards when following a path.
+    pub fn current_waypoint(&self) -> usize {
+        self.current_waypoint
+    }
+
+    /// Returns a random number between -1 and 1, used for wandering.
+    pub(crate) fn next_random(&mut self) -> f32 {
+        // xorshift32
+        let mut x = self.random_state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.random_state = x;
+        (x as f32 / std::u32::MAX as f32) * 2.0 - 1.0
+    }
+}
@@ -0,0 +1,331 @@
+use legion::prelude::Entity;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::{Blackboard, BlackboardValue};
+
+/// The result of ticking a node.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Failure,
+    Running,
+}
+
+/// How a behavior tree is described in a `.bt.ron` asset.
+/// Example:
+/// ```ron
+/// Selector([
+///     Sequence([Check("enemy_visible", Bool(true)), Leaf("attack")]),
+///     Sequence([Leaf("patrol"), Wait(2.0)]),
+/// ])
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub enum NodeDesc {
+    /// Runs children in order until one fails.
+    Sequence(Vec<NodeDesc>),
+    /// Runs children in order until one succeeds.
+    Selector(Vec<NodeDesc>),
+    /// Ticks every child each frame, succeeds once `required_successes` children succeed.
+    Parallel {
+        required_successes: usize,
+        children: Vec<NodeDesc>,
+    },
+    /// Turns success into failure and the other way around.
+    Invert(Box<NodeDesc>),
+    /// Succeeds once the child finishes, even if it failed.
+    AlwaysSucceed(Box<NodeDesc>),
+    /// Runs the child `times` times, or forever if `None`. Stops early if the child fails.
+    Repeat {
+        times: Option<u32>,
+        child: Box<NodeDesc>,
+    },
+    /// Runs for the given number of seconds then succeeds.
+    Wait(f32),
+    /// Succeeds if the blackboard has the given value.
+    Check(String, BlackboardValue),
+    /// Writes a value into the blackboard and succeeds.
+    Set(String, BlackboardValue),
+    /// A leaf registered from code with `LeafRegistry::register`.
+    Leaf(String),
+}
+
+#[derive(Debug)]
+enum Node {
+    Sequence(Vec<usize>),
+    Selector(Vec<usize>),
+    Parallel(usize, Vec<usize>),
+    Invert(usize),
+    AlwaysSucceed(usize),
+    Repeat(Option<u32>, usize),
+    Wait(f32),
+    Check(String, BlackboardValue),
+    Set(String, BlackboardValue),
+    Leaf(String),
+}
+
+/// A behavior tree loaded from an asset, it's shared between every entity running it.
+#[derive(Debug)]
+pub struct BehaviorTree {
+    nodes: Vec<Node>,
+    root: usize,
+}
+
+impl BehaviorTree {
+    pub fn new(desc: &NodeDesc) -> Self {
+        let mut nodes = Vec::new();
+        let root = Self::add_node(&mut nodes, desc);
+        Self { nodes, root }
+    }
+
+    pub fn from_ron(source: &str) -> Result<Self, ron::de::Error> {
+        let desc: NodeDesc = ron::de::from_str(source)?;
+        Ok(Self::new(&desc))
+    }
+
+    fn add_node(nodes: &mut Vec<Node>, desc: &NodeDesc) -> usize {
+        let node = match desc {
+            NodeDesc::Sequence(children) => Node::Sequence(Self::add_children(nodes, children)),
+            NodeDesc::Selector(children) => Node::Selector(Self::add_children(nodes, children)),
+            NodeDesc::Parallel {
+                required_successes,
+                children,
+            } => Node::Parallel(*required_successes, Self::add_children(nodes, children)),
+            NodeDesc::Invert(child) => Node::Invert(Self::add_node(nodes, child)),
+            NodeDesc::AlwaysSucceed(child) => Node::AlwaysSucceed(Self::add_node(nodes, child)),
+            NodeDesc::Repeat { times, child } => Node::Repeat(*times, Self::add_node(nodes, child)),
+            NodeDesc::Wait(seconds) => Node::Wait(*seconds),
+            NodeDesc::Check(key, value) => Node::Check(key.clone(), value.clone()),
+            NodeDesc::Set(key, value) => Node::Set(key.clone(), value.clone()),
+            NodeDesc::Leaf(name) => Node::Leaf(name.clone()),
+        };
+        nodes.push(node);
+        nodes.len() - 1
+    }
+
+    fn add_children(nodes: &mut Vec<Node>, children: &[NodeDesc]) -> Vec<usize> {
+        children
+            .iter()
+            .map(|child| Self::add_node(nodes, child))
+            .collect()
+    }
+}
+
+/// Passed to custom leaves when they're ticked.
+pub struct LeafContext<'a> {
+    pub entity: Entity,
+    pub blackboard: &'a mut Blackboard,
+    pub delta_time: f32,
+}
+
+type LeafFn = Box<dyn Fn(&mut LeafContext<'_>) -> Status + Send + Sync>;
+
+/// Holds the custom leaves behavior trees can call by name.
+#[derive(Default)]
+pub struct LeafRegistry {
+    leaves: HashMap<String, LeafFn>,
+}
+
+impl LeafRegistry {
+    /// Registers a leaf, trees use it with `Leaf("name")`.
+    pub fn register<T, F>(&mut self, name: T, leaf: F)
+    where
+        T: Into<String>,
+        F: Fn(&mut LeafContext<'_>) -> Status + Send + Sync + 'static,
+    {
+        self.leaves.insert(name.into(), Box::new(leaf));
+    }
+
+    fn tick(&self, name: &str, context: &mut LeafContext<'_>) -> Status {
+        match self.leaves.get(name) {
+            Some(leaf) => leaf(context),
+            None => {
+                log::warn!("Behavior Tree: Could not find leaf {}.", name);
+                Status::Failure
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct NodeState {
+    running_child: usize,
+    count: u32,
+    timer: f32,
+}
+
+/// Runs a behavior tree for an entity, the steering system or your own systems can act on what it writes to the blackboard.
+pub struct BehaviorTreeRunner {
+    tree: Arc<BehaviorTree>,
+    pub blackboard: Blackboard,
+    /// The status from the last tick.
+    pub status: Status,
+    states: Vec<NodeState>,
+}
+
+impl BehaviorTreeRunner {
+    pub fn new(tree: Arc<BehaviorTree>) -> Self {
+        let states = vec![NodeState::default(); tree.nodes.len()];
+        Self {
+            tree,
+            blackboard: Blackboard::default(),
+            status: Status::Running,
+            states,
+        }
+    }
+
+    /// Restarts the tree from the root, the blackboard is kept.
+    pub fn reset(&mut self) {
+        for state in self.states.iter_mut() {
+            *state = NodeState::default();
+        }
+    }
+
+    pub(crate) fn tick(&mut self, entity: Entity, delta_time: f32, leaves: &LeafRegistry) {
+        let tree = self.tree.clone();
+        let mut context = LeafContext {
+            entity,
+            blackboard: &mut self.blackboard,
+            delta_time,
+        };
+        self.status = Self::tick_node(&tree, tree.root, &mut self.states, &mut context, leaves);
+    }
+
+    fn tick_node(
+        tree: &BehaviorTree,
+        index: usize,
+        states: &mut Vec<NodeState>,
+        context: &mut LeafContext<'_>,
+        leaves: &LeafRegistry,
+    ) -> Status {
+        match &tree.nodes[index] {
+            Node::Sequence(children) | Node::Selector(children) => {
+                // A sequence keeps going while children succeed, a selector while they fail.
+                let continue_on = match &tree.nodes[index] {
+                    Node::Sequence(_) => Status::Success,
+                    _ => Status::Failure,
+                };
+                let start = states[index].running_child;
+                for (i, child) in children.iter().enumerate().skip(start) {
+                    let status = Self::tick_node(tree, *child, states, context, leaves);
+                    if status == Status::Running {
+                        states[index].running_child = i;
+                        return Status::Running;
+                    }
+                    if status != continue_on {
+                        states[index].running_child = 0;
+                        return status;
+                    }
+                }
+                states[index].running_child = 0;
+                continue_on
+            }
+            Node::Parallel(required_successes, children) => {
+                let mut successes = 0;
+                let mut failures = 0;
+                for child in children.iter() {
+                    match Self::tick_node(tree, *child, states, context, leaves) {
+                        Status::Success => successes += 1,
+                        Status::Failure => failures += 1,
+                        Status::Running => (),
+                    }
+                }
+                if successes >= *required_successes {
+                    Status::Success
+                } else if failures > children.len().saturating_sub(*required_successes) {
+                    Status::Failure
+                } else {
+                    Status::Running
+                }
+            }
+            Node::Invert(child) => match Self::tick_node(tree, *child, states, context, leaves) {
+                Status::Success => Status::Failure,
+                Status::Failure => Status::Success,
+                Status::Running => Status::Running,
+            },
+            Node::AlwaysSucceed(child) => {
+                match Self::tick_node(tree, *child, states, context, leaves) {
+                    Status::Running => Status::Running,
+                    _ => Status::Success,
+                }
+            }
+            Node::Repeat(times, child) => {
+                match Self::tick_node(tree, *child, states, context, leaves) {
+                    Status::Running => Status::Running,
+                    Status::Failure => {
+                        states[index].count = 0;
+                        Status::Failure
+                    }
+                    Status::Success => {
+                        states[index].count += 1;
+                        match times {
+                            Some(times) if states[index].count >= *times => {
+                                states[index].count = 0;
+                                Status::Success
+                            }
+                            _ => Status::Running,
+                        }
+                    }
+                }
+            }
+            Node::Wait(seconds) => {
+                states[index].timer += context.delta_time;
+                if states[index].timer >= *seconds {
+                    states[index].timer = 0.0;
+                    Status::Success
+                } else {
+                    Status::Running
+                }
+            }
+            Node::Check(key, value) => {
+                if context.blackboard.get(key) == Some(value) {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            }
+            Node::Set(key, value) => {
+                context.blackboard.set(key.clone(), value.clone());
+                Status::Success
+            }
+            Node::Leaf(name) => leaves.tick(name, context),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::components::Name;
+    use legion::prelude::*;
+
+    #[test]
+    fn test_sequence_waits_then_runs_leaf() {
+        let tree = BehaviorTree::from_ron(
+            r#"Sequence([Wait(1.0), Set("done", Bool(true)), Leaf("count")])"#,
+        )
+        .unwrap();
+        let mut leaves = LeafRegistry::default();
+        leaves.register("count", |context: &mut LeafContext<'_>| {
+            let count = context.blackboard.get_float("count").unwrap_or(0.0);
+            context
+                .blackboard
+                .set("count", BlackboardValue::Float(count + 1.0));
+            Status::Success
+        });
+
+        let mut world = Universe::new().create_world();
+        let entity = world.insert((), vec![(Name::new("npc"),)])[0];
+        let mut runner = BehaviorTreeRunner::new(Arc::new(tree));
+
+        runner.tick(entity, 0.6, &leaves);
+        assert_eq!(runner.status, Status::Running);
+        assert!(!runner.blackboard.get_bool("done"));
+
+        runner.tick(entity, 0.6, &leaves);
+        assert_eq!(runner.status, Status::Success);
+        assert!(runner.blackboard.get_bool("done"));
+        assert_eq!(runner.blackboard.get_float("count"), Some(1.0));
+    }
+}
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A value stored in a blackboard.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BlackboardValue {
+    Bool(bool),
+    Int(i32),
+    Float(f32),
+    String(String),
+    Vector([f32; 3]),
+}
+
+/// Per entity memory for a behavior tree.
+/// Gameplay systems write what the entity knows about the world here and leaves read it back.
+#[derive(Debug, Default, Clone)]
+pub struct Blackboard {
+    values: HashMap<String, BlackboardValue>,
+}
+
+impl Blackboard {
+    pub fn get(&self, key: &str) -> Option<&BlackboardValue> {
+        self.values.get(key)
+    }
+
+    pub fn set<T: Into<String>>(&mut self, key: T, value: BlackboardValue) {
+        self.values.insert(key.into(), value);
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<BlackboardValue> {
+        self.values.remove(key)
+    }
+
+    pub fn get_bool(&self, key: &str) -> bool {
+        match self.values.get(key) {
+            Some(BlackboardValue::Bool(value)) => *value,
+            _ => false,
+        }
+    }
+
+    pub fn get_float(&self, key: &str) -> Option<f32> {
+        match self.values.get(key) {
+            Some(BlackboardValue::Float(value)) => Some(*value),
+            Some(BlackboardValue::Int(value)) => Some(*value as f32),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,15 @@
+pub mod steering;
+
+mod agent;
+pub use agent::{Agent, Behavior};
+
+mod blackboard;
+pub use blackboard::{Blackboard, BlackboardValue};
+
+mod behavior_tree;
+pub use behavior_tree::{
+    BehaviorTree, BehaviorTreeRunner, LeafContext, LeafRegistry, NodeDesc, Status,
+};
+
+mod system;
+pub use system::{create_behavior_tree_system, create_steering_system};
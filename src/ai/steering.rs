@@ -0,0 +1,120 @@
+use nalgebra_glm::Vec3;
+
+// Steering behaviors, each one returns the acceleration needed to reach the behavior's desired velocity.
+// Based on Craig Reynolds' "Steering Behaviors For Autonomous Characters".
+
+/// Move towards the target at full speed.
+pub fn seek(position: Vec3, velocity: Vec3, target: Vec3, max_speed: f32) -> Vec3 {
+    let to_target = target - position;
+    if to_target.magnitude_squared() < std::f32::EPSILON {
+        return -velocity;
+    }
+    to_target.normalize() * max_speed - velocity
+}
+
+/// Move away from the target at full speed, only while it's within `panic_distance`.
+pub fn flee(
+    position: Vec3,
+    velocity: Vec3,
+    target: Vec3,
+    max_speed: f32,
+    panic_distance: f32,
+) -> Vec3 {
+    let from_target = position - target;
+    let distance = from_target.magnitude();
+    if distance > panic_distance {
+        return Vec3::zeros();
+    }
+    if distance < std::f32::EPSILON {
+        return Vec3::zeros();
+    }
+    from_target.normalize() * max_speed - velocity
+}
+
+/// Like seek but slows down once within `slowing_radius` so we come to a stop at the target.
+pub fn arrive(
+    position: Vec3,
+    velocity: Vec3,
+    target: Vec3,
+    max_speed: f32,
+    slowing_radius: f32,
+) -> Vec3 {
+    let to_target = target - position;
+    let distance = to_target.magnitude();
+    if distance < std::f32::EPSILON {
+        return -velocity;
+    }
+    let speed = max_speed * (distance / slowing_radius.max(std::f32::EPSILON)).min(1.0);
+    to_target / distance * speed - velocity
+}
+
+/// Wander around by seeking a point on a circle in front of us.
+/// `angle` is where on the circle we're heading, nudge it a little each frame to get smooth random motion.
+/// Only moves on the XZ plane.
+pub fn wander(velocity: Vec3, max_speed: f32, radius: f32, distance: f32, angle: f32) -> Vec3 {
+    let heading = if velocity.magnitude_squared() > std::f32::EPSILON {
+        velocity.normalize()
+    } else {
+        Vec3::new(0.0, 0.0, 1.0)
+    };
+    let circle_center = heading * distance;
+    let displacement = Vec3::new(angle.cos() * radius, 0.0, angle.sin() * radius);
+    let desired = circle_center + displacement;
+    if desired.magnitude_squared() < std::f32::EPSILON {
+        return Vec3::zeros();
+    }
+    desired.normalize() * max_speed - velocity
+}
+
+/// Push away from neighbors, closer neighbors push harder.
+pub fn separation(position: Vec3, neighbors: &[Vec3], max_speed: f32) -> Vec3 {
+    let mut push = Vec3::zeros();
+    for neighbor in neighbors {
+        let away = position - neighbor;
+        let distance_squared = away.magnitude_squared();
+        if distance_squared > std::f32::EPSILON {
+            push += away / distance_squared;
+        }
+    }
+    if push.magnitude_squared() < std::f32::EPSILON {
+        return Vec3::zeros();
+    }
+    push.normalize() * max_speed
+}
+
+/// Steer towards the center of our neighbors.
+pub fn cohesion(position: Vec3, velocity: Vec3, neighbors: &[Vec3], max_speed: f32) -> Vec3 {
+    if neighbors.is_empty() {
+        return Vec3::zeros();
+    }
+    let center = neighbors
+        .iter()
+        .fold(Vec3::zeros(), |sum, neighbor| sum + neighbor)
+        / neighbors.len() as f32;
+    seek(position, velocity, center, max_speed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arrive_slows_down_near_target() {
+        let target = Vec3::new(10.0, 0.0, 0.0);
+        let far = arrive(Vec3::zeros(), Vec3::zeros(), target, 4.0, 5.0);
+        let near = arrive(Vec3::new(9.0, 0.0, 0.0), Vec3::zeros(), target, 4.0, 5.0);
+        assert!((far.magnitude() - 4.0).abs() < 0.0001);
+        assert!((near.magnitude() - 0.8).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_flee_ignores_far_targets() {
+        let target = Vec3::new(10.0, 0.0, 0.0);
+        assert_eq!(
+            flee(Vec3::zeros(), Vec3::zeros(), target, 4.0, 5.0),
+            Vec3::zeros()
+        );
+        let close = flee(Vec3::new(8.0, 0.0, 0.0), Vec3::zeros(), target, 4.0, 5.0);
+        assert!(close.x < 0.0);
+    }
+}
@@ -0,0 +1,139 @@
+use legion::prelude::*;
+use nalgebra_glm::Vec3;
+
+use super::{steering, Agent, Behavior, BehaviorTreeRunner, LeafRegistry};
+use crate::scene::{components::Transform, resources::DeltaTime};
+
+/// Ticks every `BehaviorTreeRunner` once per update.
+/// Add this to your scene's schedule builder before any systems that read the blackboards.
+pub fn create_behavior_tree_system() -> Box<dyn Schedulable> {
+    SystemBuilder::new("behavior_trees")
+        .read_resource::<DeltaTime>()
+        .read_resource::<LeafRegistry>()
+        .with_query(<Write<BehaviorTreeRunner>>::query())
+        .build(|_, mut world, (delta_time, leaves), query| {
+            for (entity, mut runner) in query.iter_entities_mut(&mut world) {
+                runner.tick(entity, delta_time.0, &leaves);
+            }
+        })
+}
+
+/// Moves every `Agent` using it's steering behavior.
+/// Add this to your scene's schedule builder.
+pub fn create_steering_system() -> Box<dyn Schedulable> {
+    SystemBuilder::new("steering")
+        .read_resource::<DeltaTime>()
+        .with_query(<(Write<Agent>, Write<Transform>)>::query())
+        .build(|_, mut world, delta_time, query| {
+            let delta_time = delta_time.0;
+
+            let positions: Vec<Vec3> = query
+                .iter_mut(&mut world)
+                .map(|(_, transform)| transform.position)
+                .collect();
+
+            for (index, (entity, (mut agent, mut transform))) in
+                query.iter_entities_mut(&mut world).enumerate()
+            {
+                if agent.random_state == 0 {
+                    agent.random_state = entity.index() + 1;
+                }
+
+                let position = transform.position;
+                let velocity = agent.velocity;
+                let max_speed = agent.max_speed;
+
+                let mut acceleration = match agent.behavior.clone() {
+                    Behavior::Idle => -velocity,
+                    Behavior::Seek(target) => steering::seek(position, velocity, target, max_speed),
+                    Behavior::Flee {
+                        target,
+                        panic_distance,
+                    } => steering::flee(position, velocity, target, max_speed, panic_distance),
+                    Behavior::Arrive {
+                        target,
+                        slowing_radius,
+                    } => steering::arrive(position, velocity, target, max_speed, slowing_radius),
+                    Behavior::Wander {
+                        radius,
+                        distance,
+                        jitter,
+                    } => {
+                        agent.wander_angle += agent.next_random() * jitter * delta_time;
+                        steering::wander(velocity, max_speed, radius, distance, agent.wander_angle)
+                    }
+                    Behavior::FollowPath {
+                        waypoints,
+                        waypoint_radius,
+                        looped,
+                    } => {
+                        if waypoints.is_empty() {
+                            -velocity
+                        } else {
+                            let mut current = agent.current_waypoint.min(waypoints.len() - 1);
+                            let is_last = current == waypoints.len() - 1;
+                            if (waypoints[current] - position).magnitude() < waypoint_radius
+                                && (!is_last || looped)
+                            {
+                                current = (current + 1) % waypoints.len();
+                            }
+                            agent.current_waypoint = current;
+
+                            if current == waypoints.len() - 1 && !looped {
+                                steering::arrive(
+                                    position,
+                                    velocity,
+                                    waypoints[current],
+                                    max_speed,
+                                    waypoint_radius,
+                                )
+                            } else {
+                                steering::seek(position, velocity, waypoints[current], max_speed)
+                            }
+                        }
+                    }
+                };
+
+                if agent.separation > 0.0 || agent.cohesion > 0.0 {
+                    let neighbor_radius_squared = agent.neighbor_radius * agent.neighbor_radius;
+                    let neighbors: Vec<Vec3> = positions
+                        .iter()
+                        .enumerate()
+                        .filter(|(other, other_position)| {
+                            *other != index
+                                && (*other_position - position).magnitude_squared()
+                                    < neighbor_radius_squared
+                        })
+                        .map(|(_, other_position)| *other_position)
+                        .collect();
+                    acceleration +=
+                        steering::separation(position, &neighbors, max_speed) * agent.separation;
+                    acceleration += steering::cohesion(position, velocity, &neighbors, max_speed)
+                        * agent.cohesion;
+                }
+
+                let acceleration = clamp_magnitude(acceleration, agent.max_acceleration);
+                agent.velocity =
+                    clamp_magnitude(agent.velocity + acceleration * delta_time, agent.max_speed);
+                transform.position += agent.velocity * delta_time;
+
+                if agent.face_movement {
+                    let flat_velocity = Vec3::new(agent.velocity.x, 0.0, agent.velocity.z);
+                    if flat_velocity.magnitude_squared() > 0.0001 {
+                        let yaw = flat_velocity.x.atan2(flat_velocity.z);
+                        transform.rotation =
+                            nalgebra_glm::quat_angle_axis(yaw, &Vec3::new(0.0, 1.0, 0.0));
+                    }
+                }
+            }
+        })
+}
+
+fn clamp_magnitude(vector: Vec3, max: f32) -> Vec3 {
+    let magnitude = vector.magnitude();
+    if magnitude > max && magnitude > 0.0 {
+        vector * (max / magnitude)
+    } else {
+        vector
+    }
+}
@@ -0,0 +1,186 @@
+use legion::prelude::*;
+use winit::{
+    dpi::LogicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+};
+
+use crate::{graphics::RenderSettings, AppState, Application, WindowSettings};
+
+/// When a system added with `AppBuilder::add_system_to_stage` runs.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Stage {
+    /// Runs every update before the scene's game schedule.
+    PreUpdate,
+    /// Runs every update right after the scene's game schedule.
+    Update,
+    /// Runs every update after `Update`.
+    PostUpdate,
+    /// Runs once per frame with the render systems, after meshes have been drawn.
+    Render,
+}
+
+/// The schedules for the update stages, these live on the application so they
+/// survive the scene being replaced.
+pub(crate) struct Stages {
+    pub pre_update: Schedule,
+    pub update: Schedule,
+    pub post_update: Schedule,
+}
+
+impl Default for Stages {
+    fn default() -> Self {
+        Self {
+            pre_update: Schedule::builder().build(),
+            update: Schedule::builder().build(),
+            post_update: Schedule::builder().build(),
+        }
+    }
+}
+
+pub(crate) type ShutdownHook = Box<dyn FnMut(&mut Application)>;
+type ResourceInsert = Box<dyn FnOnce(&mut Resources)>;
+
+/// Sets up and runs an `Application`.
+/// ```ignore
+/// AppBuilder::new("My Game", LogicalSize::new(1024, 768))
+///     .with_asset_path("assets/")
+///     .add_system_to_stage(Stage::Update, create_player_system())
+///     .run(MyAppState::new());
+/// ```
+pub struct AppBuilder {
+    window_settings: WindowSettings,
+    asset_path: String,
+    startup_systems: Vec<Box<dyn Schedulable>>,
+    stage_systems: Vec<(Stage, Box<dyn Schedulable>)>,
+    resources: Vec<ResourceInsert>,
+    shutdown_hooks: Vec<ShutdownHook>,
+    render_settings: RenderSettings,
+}
+
+impl AppBuilder {
+    pub fn new<T: Into<String>>(title: T, size: LogicalSize<u32>) -> Self {
+        Self {
+            window_settings: WindowSettings::new(title, size),
+            asset_path: "assets/".to_string(),
+            startup_systems: Vec::new(),
+            stage_systems: Vec::new(),
+            resources: Vec::new(),
+            shutdown_hooks: Vec::new(),
+            render_settings: RenderSettings::default(),
+        }
+    }
+
+    /// Path to the asset folder, defaults to "assets/".
+    pub fn with_asset_path<T: Into<String>>(mut self, asset_path: T) -> Self {
+        self.asset_path = asset_path.into();
+        self
+    }
+
+    /// Renderer options like the MSAA sample count, most are fixed once the window is created.
+    /// The present mode can be changed later with `Application::set_present_mode`, the sample
+    /// count can't, see `RenderSettings::sample_count`.
+    pub fn with_render_settings(mut self, render_settings: RenderSettings) -> Self {
+        self.render_settings = render_settings;
+        self
+    }
+
+    /// Window options like fullscreen, replaces the title and size passed to `new`.
+    pub fn with_window_settings(mut self, window_settings: WindowSettings) -> Self {
+        self.window_settings = window_settings;
+        self
+    }
+
+    /// Adds a system that runs once after everything has loaded.
+    pub fn add_startup_system(mut self, system: Box<dyn Schedulable>) -> Self {
+        self.startup_systems.push(system);
+        self
+    }
+
+    /// Adds a system to the `Update` stage.
+    pub fn add_system(self, system: Box<dyn Schedulable>) -> Self {
+        self.add_system_to_stage(Stage::Update, system)
+    }
+
+    pub fn add_system_to_stage(mut self, stage: Stage, system: Box<dyn Schedulable>) -> Self {
+        self.stage_systems.push((stage, system));
+        self
+    }
+
+    /// Inserts a resource, replacing any resource of the same type the engine added.
+    pub fn insert_resource<T: Resource>(mut self, resource: T) -> Self {
+        self.resources
+            .push(Box::new(move |resources: &mut Resources| {
+                resources.insert(resource);
+            }));
+        self
+    }
+
+    /// Adds a function that runs when the application shuts down, before the GPU is flushed.
+    /// Useful for saving settings or other state.
+    pub fn add_shutdown_hook<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&mut Application) + 'static,
+    {
+        self.shutdown_hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Creates the window and application without loading anything.
+    pub fn build(self, event_loop: &EventLoop<()>) -> Application {
+        let window_builder = self.window_settings.window_builder(event_loop);
+
+        let mut render_systems = Vec::new();
+        let mut pre_update = Schedule::builder();
+        let mut update = Schedule::builder();
+        let mut post_update = Schedule::builder();
+        for (stage, system) in self.stage_systems {
+            match stage {
+                Stage::PreUpdate => pre_update = pre_update.add_system(system),
+                Stage::Update => update = update.add_system(system),
+                Stage::PostUpdate => post_update = post_update.add_system(system),
+                Stage::Render => render_systems.push(system),
+            }
+        }
+
+        let mut application = Application::new_with_settings(
+            window_builder,
+            event_loop,
+            self.asset_path,
+            render_systems,
+            self.render_settings,
+        );
+        application.stages = Stages {
+            pre_update: pre_update.build(),
+            update: update.build(),
+            post_update: post_update.build(),
+        };
+        application.startup_systems = self.startup_systems;
+        application.shutdown_hooks = self.shutdown_hooks;
+        for insert in self.resources {
+            insert(&mut application.resources);
+        }
+        application
+    }
+
+    /// Builds the application, loads it and runs the event loop until the window is closed.
+    pub fn run<T>(self, mut app_state: T) -> !
+    where
+        T: AppState + 'static,
+    {
+        let event_loop = EventLoop::new();
+        let mut application = self.build(&event_loop);
+        application.load(&mut app_state);
+
+        event_loop.run(move |event, _, control_flow| {
+            application.run(&mut app_state, &event, control_flow);
+            if let Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } = event
+            {
+                *control_flow = ControlFlow::Exit;
+            }
+        })
+    }
+}
@@ -8,22 +8,25 @@ use imgui::*;
 use legion::prelude::*;
 
 use crate::{
-    core::input::Input,
+    core::{input::Input, AppEvents, DiagnosticEvent, FrameStats, HitchDetector, MemoryStats},
     graphics::{
         self,
         material::Skybox,
         pipeline_manager::PipelineManager,
-        resources::{CurrentRenderTarget, GPUResourceManager, ProbeManager},
+        resources::{CurrentRenderTarget, GPUResourceManager, ProbeManager, TextRenderer},
         systems::create_render_schedule_builder,
-        RenderGraph, Renderer,
+        frame_export::FrameExporter,
+        FrameExport, FrameOutput, RenderGraph, RenderSettings, Renderer, TextureViewer,
     },
+    app_builder::{ShutdownHook, Stages},
     scene::Scene,
-    AssetManager, TransformCount,
+    AssetManager, TransformCount, WindowMode,
 };
 use graphics::{
     material::skybox::SkyboxType,
     pipelines::{LinePipelineDesc, UnlitPipelineDesc},
-    CommandBufferQueue, CommandQueueItem, renderer::{DEPTH_FORMAT, DepthTexture},
+    CommandBufferQueue, CommandQueueItem,
+    renderer::{create_depth_texture, DepthTexture},
 };
 use nalgebra_glm::Vec2;
 
@@ -39,6 +42,8 @@ pub trait AppState {
     fn update_ui(&mut self, _app: &mut Application) {}
     /// A function to help draw your UI. PLease see hello-world for an example.
     fn draw_ui(&mut self, _ui: &mut imgui::Ui<'_>, _screen_size: Vec2) {}
+    /// Called once when the application shuts down, before the shutdown hooks run.
+    fn shutdown(&mut self, _app: &mut Application) {}
 }
 
 pub struct Application {
@@ -64,6 +69,13 @@ pub struct Application {
     pub(crate) imgui_renderer: imgui_wgpu::Renderer,
     last_cursor: Option<imgui::MouseCursor>,
     last_frame: Instant,
+    pub(crate) stages: Stages,
+    pub(crate) startup_systems: Vec<Box<dyn Schedulable>>,
+    pub(crate) shutdown_hooks: Vec<ShutdownHook>,
+    is_shut_down: bool,
+    frame_export: Option<FrameExporter>,
+    #[cfg(feature = "gamepad")]
+    gamepad_nav: Option<crate::core::GamepadNav>,
 }
 
 impl Application {
@@ -79,30 +91,106 @@ impl Application {
         window_builder: winit::window::WindowBuilder,
         event_loop: &EventLoop<()>,
         asset_path: T,
-        mut render_systems: Vec<Box<dyn Schedulable>>,
+        render_systems: Vec<Box<dyn Schedulable>>,
+    ) -> Self
+    where
+        T: Into<String>,
+    {
+        Self::new_with_settings(
+            window_builder,
+            event_loop,
+            asset_path,
+            render_systems,
+            RenderSettings::default(),
+        )
+    }
+
+    /// Same as `new` but lets you pick renderer options like the MSAA sample count.
+    /// Settings the adapter doesn't support are lowered, the `RenderSettings` resource has
+    /// the ones actually used.
+    pub fn new_with_settings<T>(
+        window_builder: winit::window::WindowBuilder,
+        event_loop: &EventLoop<()>,
+        asset_path: T,
+        render_systems: Vec<Box<dyn Schedulable>>,
+        render_settings: RenderSettings,
     ) -> Self
     where
         T: Into<String>,
     {
-        let scene = Scene::new(None, None);
         let window = window_builder.build(event_loop).unwrap();
         let size = window.inner_size();
+        Self::create(asset_path.into(), render_systems, |resources| {
+            futures::executor::block_on(Renderer::new(window, size, resources, render_settings))
+        })
+    }
+
+    /// Creates an application without a window, frames are drawn into a texture of the given
+    /// size instead. Drive it with `render_frame` and read frames back with
+    /// `renderer.capture_frame`, handy for tests and tools. There's no UI when headless.
+    pub fn new_headless<T>(
+        width: u32,
+        height: u32,
+        asset_path: T,
+        render_systems: Vec<Box<dyn Schedulable>>,
+        render_settings: RenderSettings,
+    ) -> Self
+    where
+        T: Into<String>,
+    {
+        let size = winit::dpi::PhysicalSize::new(width, height);
+        Self::create(asset_path.into(), render_systems, |resources| {
+            futures::executor::block_on(Renderer::new_headless(size, resources, render_settings))
+        })
+    }
+
+    fn create<F>(
+        asset_path: String,
+        mut render_systems: Vec<Box<dyn Schedulable>>,
+        create_renderer: F,
+    ) -> Self
+    where
+        F: FnOnce(&mut Resources) -> Renderer,
+    {
+        let scene = Scene::new(None, None);
 
         // Add resources
         let mut resources = Resources::default();
         resources.insert(crate::scene::resources::DeltaTime(0.05));
+        resources.insert(crate::scene::resources::EntityIndex::default());
+        resources.insert(crate::scene::resources::Sequencer::default());
+        resources.insert(crate::scene::resources::Pointer::default());
+        resources.insert(crate::scene::resources::Visibility::default());
+        resources.insert(crate::ai::LeafRegistry::default());
+        resources.insert(crate::core::Random::default());
         resources.insert(PipelineManager::new());
 
-        let renderer = futures::executor::block_on(Renderer::new(window, size, &mut resources));
+        let renderer = create_renderer(&mut resources);
 
-        let asset_manager = AssetManager::new(asset_path.into());
+        let asset_manager = AssetManager::new(asset_path);
 
         let mut render_schedule_builder = create_render_schedule_builder();
-        render_schedule_builder =
-            render_schedule_builder.add_system(crate::graphics::systems::mesh::create());
+        render_schedule_builder = render_schedule_builder
+            .add_system(crate::graphics::systems::paint::create())
+            .add_system(crate::graphics::systems::deformation::create())
+            .add_system(crate::graphics::systems::material_instance::create())
+            .add_system(crate::graphics::systems::shader_variants::create())
+            .add_system(crate::graphics::systems::shadow::create())
+            .add_system(crate::graphics::systems::skinning::create())
+            .add_system(crate::graphics::systems::light_culling::create())
+            .add_system(crate::graphics::systems::clouds::create())
+            .add_system(crate::graphics::systems::particles::create())
+            .add_system(crate::graphics::systems::mesh::create())
+            .add_system(crate::graphics::systems::contact_shadows::create())
+            .add_system(crate::graphics::systems::ssgi::create())
+            .add_system(crate::graphics::systems::water::create())
+            .add_system(crate::graphics::systems::ocean::create())
+            .add_system(crate::graphics::systems::grid::create())
+            .add_system(crate::graphics::systems::sprite::create())
+            .add_system(crate::graphics::systems::debug::create())
+            .add_system(crate::graphics::systems::post_process::create());
 
-        for index in 0..render_systems.len() {
-            let system = render_systems.remove(index);
+        for system in render_systems.drain(..) {
             render_schedule_builder = render_schedule_builder.add_system(system);
         }
 
@@ -112,23 +200,73 @@ impl Application {
             .build();
         resources.insert(asset_manager);
 
+        let (frames_in_flight, uniform_arena) = {
+            let device = resources.get::<wgpu::Device>().unwrap();
+            let frames_in_flight = resources.get::<RenderSettings>().unwrap().frames_in_flight;
+            (
+                graphics::FramesInFlight::new(&device, frames_in_flight),
+                graphics::UniformArena::new(&device, frames_in_flight),
+            )
+        };
+        resources.insert(frames_in_flight);
+        resources.insert(uniform_arena);
+
         resources.insert(TransformCount(0));
         resources.insert(CurrentRenderTarget(None));
 
         resources.insert(Input::new());
+        resources.insert(AppEvents::default());
+        resources.insert(graphics::FrustumCulling::default());
+        resources.insert(graphics::instancing::StaticCache::default());
+        resources.insert(graphics::PostProcessing::default());
+        resources.insert(graphics::Clouds::default());
+        resources.insert(graphics::Atmosphere::default());
+        resources.insert(graphics::ScreenSpaceGI::default());
+        resources.insert(graphics::DynamicGI::default());
+        resources.insert(graphics::AoVolume::default());
+        resources.insert(graphics::DebugDraw::default());
+        resources.insert(graphics::EditorGrid::default());
+        resources.insert(graphics::SpriteRenderer::default());
+        resources.insert(graphics::ParticleRenderer::default());
+        resources.insert(graphics::SurfaceDeformation::default());
+        resources.insert(graphics::Ocean::default());
+        resources.insert(graphics::light_cookies::LightCookies::default());
+        resources.insert(MemoryStats::default());
+        resources.insert(FrameStats::default());
+        resources.insert(HitchDetector::default());
+        resources.insert(graphics::SplitScreen::default());
+        resources.insert(crate::scene::history::History::default());
+        resources.insert(crate::scene::CloneRegistry::default());
+        resources.insert(crate::scene::ComponentRegistry::default());
+        resources.insert(crate::scene::RemovalHooks::default());
 
-        let hidpi_factor = renderer.window.scale_factor();
+        let hidpi_factor = renderer
+            .window
+            .as_ref()
+            .map_or(1.0, |window| window.scale_factor());
         let mut imgui = imgui::Context::create();
         let mut platform = imgui_winit_support::WinitPlatform::init(&mut imgui);
-        platform.attach_window(
-            imgui.io_mut(),
-            &renderer.window,
-            imgui_winit_support::HiDpiMode::Default,
-        );
+        if let Some(window) = renderer.window.as_ref() {
+            platform.attach_window(
+                imgui.io_mut(),
+                window,
+                imgui_winit_support::HiDpiMode::Default,
+            );
+        }
         imgui.set_ini_filename(None);
 
+        // Lets the UI be navigated with the arrow keys, space and escape, and with a gamepad
+        // when the `gamepad` feature is on.
+        // Use `ui.set_item_default_focus()` to pick which widget gets focus first.
+        imgui.io_mut().config_flags |= imgui::ConfigFlags::NAV_ENABLE_KEYBOARD;
+        #[cfg(feature = "gamepad")]
+        let gamepad_nav = crate::core::GamepadNav::new(imgui.io_mut());
+
+        // The font is rasterized for the window's DPI and scaled back down, the UI scale
+        // makes it bigger or smaller from there.
+        let ui_scale = resources.get::<RenderSettings>().unwrap().ui_scale;
         let font_size = (13.0 * hidpi_factor) as f32;
-        imgui.io_mut().font_global_scale = (1.0 / hidpi_factor) as f32;
+        imgui.io_mut().font_global_scale = ui_scale / hidpi_factor as f32;
 
         imgui.fonts().add_font(&[FontSource::DefaultFontData {
             config: Some(imgui::FontConfig {
@@ -181,6 +319,13 @@ impl Application {
             imgui_renderer,
             last_frame,
             last_cursor: None,
+            stages: Stages::default(),
+            startup_systems: Vec::new(),
+            shutdown_hooks: Vec::new(),
+            is_shut_down: false,
+            frame_export: None,
+            #[cfg(feature = "gamepad")]
+            gamepad_nav,
         }
     }
 
@@ -195,8 +340,13 @@ impl Application {
     }
 
     /// A function to help get the actual screen size as a LogicalSize<f32>
+    /// When headless this is the size of the texture frames are drawn into.
     pub fn get_window_actual_size(&self) -> winit::dpi::LogicalSize<f32> {
-        let size = self.renderer.window.inner_size();
+        let size = self
+            .renderer
+            .window
+            .as_ref()
+            .map_or(self.renderer.size, |window| window.inner_size());
         winit::dpi::LogicalSize {
             width: size.width as f32,
             height: size.height as f32,
@@ -216,7 +366,8 @@ impl Application {
             let mut asset_manager = self.resources.get_mut::<AssetManager>().unwrap();
             let device = self.resources.get::<wgpu::Device>().unwrap();
             let mut queue = self.resources.get_mut::<wgpu::Queue>().unwrap();
-            asset_manager.load(&device, &mut queue);
+            let resource_manager = self.resources.get::<GPUResourceManager>().unwrap();
+            asset_manager.load(&device, &mut queue, &resource_manager);
         }
 
         {
@@ -225,14 +376,15 @@ impl Application {
         }
 
         {
-            let asset_manager = self.resources.get_mut::<AssetManager>().unwrap();
+            let mut asset_manager = self.resources.get_mut::<AssetManager>().unwrap();
             let mut render_graph = self.resources.get_mut::<RenderGraph>().unwrap();
             let mut resource_manager = self.resources.get_mut::<GPUResourceManager>().unwrap();
             let device = self.resources.get::<wgpu::Device>().unwrap();
             let sc_desc = self.resources.get::<wgpu::SwapChainDescriptor>().unwrap();
+            let sample_count = self.resources.get::<RenderSettings>().unwrap().sample_count;
 
             // Unlit pipeline
-            let unlit_pipeline_desc = UnlitPipelineDesc::default();
+            let unlit_pipeline_desc = UnlitPipelineDesc::new(sample_count);
             render_graph.add(
                 &asset_manager,
                 &device,
@@ -246,8 +398,56 @@ impl Application {
                 false,
             );
 
+            // Unlit meshes with vertex colors.
+            let unlit_attributes_desc =
+                UnlitPipelineDesc::new(sample_count).with_vertex_attributes(true);
+            let unlit_attributes_defines = unlit_attributes_desc.shader_defines();
+            asset_manager
+                .load_shader_variant(&device, "unlit.shader", &unlit_attributes_defines)
+                .unwrap();
+            render_graph.add(
+                &asset_manager,
+                &device,
+                &sc_desc,
+                &mut resource_manager,
+                "unlit_vertex_attributes",
+                unlit_attributes_desc,
+                vec!["skybox"],
+                true,
+                None,
+                false,
+            );
+
+            // Probes render single sampled.
+            if sample_count > 1 {
+                render_graph.add(
+                    &asset_manager,
+                    &device,
+                    &sc_desc,
+                    &mut resource_manager,
+                    "unlit_single_sampled",
+                    UnlitPipelineDesc::new(1),
+                    vec!["skybox"],
+                    true,
+                    None,
+                    false,
+                );
+                render_graph.add(
+                    &asset_manager,
+                    &device,
+                    &sc_desc,
+                    &mut resource_manager,
+                    "unlit_vertex_attributes_single_sampled",
+                    UnlitPipelineDesc::new(1).with_vertex_attributes(true),
+                    vec!["skybox"],
+                    true,
+                    None,
+                    false,
+                );
+            }
+
             // Line pipeline
-            let line_pipeline_desc = LinePipelineDesc::default();
+            let line_pipeline_desc = LinePipelineDesc::new(sample_count);
             render_graph.add(
                 &asset_manager,
                 &device,
@@ -262,28 +462,115 @@ impl Application {
             );
         }
 
-        // Global Node
-        {
-            let mut pipeline_manager = self.resources.get_mut::<PipelineManager>().unwrap();
-            pipeline_manager.add_node("globals", vec![]);
-        }
-
         // Create new pipelines
         crate::graphics::pipelines::skybox::create(&self.resources);
         crate::graphics::pipelines::realtime_sky::create(&self.resources);
         
+        // Shadow pipeline, PBR samples the shadow maps so it needs to exist first.
+        super::graphics::pipelines::shadow::create(&self.resources);
+
         // PBR pipeline
         super::graphics::pipelines::pbr::create(&self.resources);
 
+        // Text pipeline
+        super::graphics::pipelines::text::create(&self.resources);
+        {
+            let text_renderer = {
+                let asset_manager = self.resources.get::<AssetManager>().unwrap();
+                let resource_manager = self.resources.get::<GPUResourceManager>().unwrap();
+                let device = self.resources.get::<wgpu::Device>().unwrap();
+                let render_settings = self.resources.get::<RenderSettings>().unwrap();
+                TextRenderer::new(
+                    &device,
+                    &asset_manager,
+                    resource_manager.get_bind_group_layout("text").unwrap(),
+                    render_settings.sdf_text,
+                    render_settings.ui_scale,
+                )
+            };
+            self.resources.insert(text_renderer);
+        }
+
+        // Light culling compute pipeline
+        super::graphics::pipelines::light_culling::create(&mut self.resources);
+
+        // Post processing pipelines
+        super::graphics::pipelines::post_process::create(&mut self.resources);
+
+        // Texture painting pipelines
+        super::graphics::pipelines::paint::create(&mut self.resources);
+
+        // Noise compute pipeline
+        super::graphics::pipelines::noise::create(&mut self.resources);
+
+        // Volumetric cloud pipelines
+        super::graphics::pipelines::clouds::create(&mut self.resources);
+
+        // Atmospheric scattering pipelines
+        super::graphics::pipelines::atmosphere::create(&mut self.resources);
+
+        // Debug line pipelines
+        super::graphics::pipelines::debug::create(&self.resources);
+
+        // Editor grid pipeline
+        super::graphics::pipelines::grid::create(&self.resources);
+
+        // Sprite pipeline
+        super::graphics::pipelines::sprite::create(&self.resources);
+
+        // Particle simulation and drawing pipelines
+        super::graphics::pipelines::particle::create(&self.resources);
+
+        // Snow and sand deformation compute pipelines
+        super::graphics::pipelines::deformation::create(&self.resources);
+
+        // Ocean wave simulation and drawing pipelines
+        super::graphics::pipelines::ocean::create(&mut self.resources);
+
+        // Water caustics pipeline
+        super::graphics::pipelines::water::create(&mut self.resources);
+
+        // Contact shadow pipeline
+        super::graphics::pipelines::contact_shadows::create(&mut self.resources);
+
+        // Screen-space GI pipelines
+        super::graphics::pipelines::ssgi::create(&mut self.resources);
+
+        // Dynamic GI probe update pipeline
+        super::graphics::pipelines::dynamic_gi::create(&mut self.resources);
+
+        // Texture viewer and material preview pipelines
+        super::graphics::pipelines::texture_viewer::create(&mut self.resources);
+
+        // Built-in passes, they run in the order needed by the attachments they use.
+        // User passes added in load can read or write the same attachments to slot in between.
+        {
+            let sample_count = self.resources.get::<RenderSettings>().unwrap().sample_count;
+            let ldr_format = self.resources.get::<wgpu::SwapChainDescriptor>().unwrap().format;
+            let mut pipeline_manager = self.resources.get_mut::<PipelineManager>().unwrap();
+            pipeline_manager.add_builtin_passes(sample_count, ldr_format);
+        }
+
         // Run user code.
         app_state.load(self);
 
+        // Added after user code so post processing comes after anything else that draws to the frame.
+        {
+            let mut pipeline_manager = self.resources.get_mut::<PipelineManager>().unwrap();
+            pipeline_manager.add_post_process_passes();
+        }
+
         // Once materials have been created we need to create more info for them.
         {
             let mut asset_manager = self.resources.get_mut::<AssetManager>().unwrap();
             let device = self.resources.get::<wgpu::Device>().unwrap();
             let mut resource_manager = self.resources.get_mut::<GPUResourceManager>().unwrap();
-            asset_manager.load_materials(&device, &mut resource_manager);
+            let pipeline_manager = self.resources.get::<PipelineManager>().unwrap();
+            let alpha_to_coverage = pipeline_manager
+                .get("pbr_masked", None)
+                .map(|pipeline| pipeline.desc.alpha_to_coverage_enabled)
+                .unwrap_or(false);
+            asset_manager.load_materials(&device, &mut resource_manager, alpha_to_coverage);
         }
 
         {
@@ -306,6 +593,40 @@ impl Application {
                 }
             }
         }
+
+        // Startup systems only ever run once.
+        if !self.startup_systems.is_empty() {
+            let mut startup_schedule_builder = Schedule::builder();
+            for system in self.startup_systems.drain(..) {
+                startup_schedule_builder = startup_schedule_builder.add_system(system);
+            }
+            startup_schedule_builder
+                .build()
+                .execute(&mut self.current_scene.world, &mut self.resources);
+        }
+    }
+
+    /// Shuts the application down, this runs `AppState::shutdown`, the shutdown hooks
+    /// and then waits for the GPU to finish any work that was submitted.
+    /// Called automatically when winit's event loop is destroyed, calling it more than once does nothing.
+    pub fn shutdown<T>(&mut self, app_state: &mut T)
+    where
+        T: AppState,
+    {
+        if self.is_shut_down {
+            return;
+        }
+        self.is_shut_down = true;
+
+        self.stop_frame_export();
+        app_state.shutdown(self);
+        let mut shutdown_hooks = std::mem::replace(&mut self.shutdown_hooks, Vec::new());
+        for hook in shutdown_hooks.iter_mut() {
+            hook(self);
+        }
+
+        let device = self.resources.get::<wgpu::Device>().unwrap();
+        device.poll(wgpu::Maintain::Wait);
     }
 
     /// Run's the application which means two things.
@@ -316,154 +637,457 @@ impl Application {
     ///
     /// * `app_state` - The app state you created which should implement the AppState trait.
     /// * `event` - The event data as a reference from winit.
-    /// * `control_flow` - a mutable reference to winit's control flow.
+    /// * `control_flow` - a mutable reference to winit's control flow, set to `Exit` when `AppEvents::exit` is called
+    /// and to `Wait` while the window is minimized.
     ///
     pub fn run<T>(
         &mut self,
         app_state: &mut T,
         event: &Event<'_, ()>,
-        _control_flow: &mut ControlFlow, // TODO: Figure out if we actually will use this...
+        control_flow: &mut ControlFlow,
     ) where
         T: AppState,
     {
         {
             let mut input = self.resources.get_mut::<Input>().unwrap();
             input.update_events(event);
+            let mut app_events = self.resources.get_mut::<AppEvents>().unwrap();
+            app_events.update_events(event);
         }
 
         match event {
             Event::MainEventsCleared => {
-                let mut frame_time = self.clock.elapsed().as_secs_f32() - self.elapsed_time;
-                self.frame_time = frame_time * 1000.0;
+                self.frame(app_state, control_flow);
+            }
+            Event::WindowEvent {
+                event: winit::event::WindowEvent::Resized(size),
+                ..
+            } => {
+                // Minimizing resizes the window to nothing, keep the old swap chain around.
+                if size.width == 0 || size.height == 0 {
+                    return;
+                }
                 {
-                    self.last_frame = self.imgui.io_mut().update_delta_time(self.last_frame);
+                    let mut sc_desc = self
+                        .resources
+                        .get_mut::<wgpu::SwapChainDescriptor>()
+                        .unwrap();
+                    sc_desc.width = size.width;
+                    sc_desc.height = size.height;
+                    self.renderer.size = *size;
                 }
+                self.recreate_swap_chain();
+
+                app_state.resize(self);
+            }
+            Event::LoopDestroyed => {
+                self.shutdown(app_state);
+            }
+            _ => (),
+        }
+        if let Some(window) = self.renderer.window.as_ref() {
+            self.platform
+                .handle_event(self.imgui.io_mut(), window, &event);
+        }
+    }
+
+    /// Builds the pipelines and bind groups that would otherwise be made the first time
+    /// something is drawn, so new objects don't cause a hitch when they show up. Call it from
+    /// your loading screen once `AssetManager::is_loading` is false, it's safe to call again
+    /// after loading more. Returns how many pipelines were built.
+    pub fn warm_up(&mut self) -> usize {
+        let _scope = self.resources.get::<FrameStats>().unwrap().scope("warm_up");
+        let built = graphics::systems::shader_variants::warm_up(&self.resources);
+        log::info!("Warm up built {} pipelines.", built);
+        built
+    }
+
+    /// Changes vsync while running, the swap chain is recreated right away.
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+        self.resources
+            .get_mut::<RenderSettings>()
+            .unwrap()
+            .present_mode = present_mode;
+        self.resources
+            .get_mut::<wgpu::SwapChainDescriptor>()
+            .unwrap()
+            .present_mode = present_mode;
+        self.recreate_swap_chain();
+    }
+
+    /// Makes screen text and the imgui UI bigger or smaller, see `RenderSettings::ui_scale`.
+    pub fn set_ui_scale(&mut self, ui_scale: f32) {
+        if ui_scale.is_nan() || ui_scale <= 0.0 {
+            return;
+        }
+        self.resources.get_mut::<RenderSettings>().unwrap().ui_scale = ui_scale;
+        if let Some(mut text_renderer) = self.resources.get_mut::<TextRenderer>() {
+            text_renderer.set_scale(ui_scale);
+        }
+        let hidpi_factor = self
+            .renderer
+            .window
+            .as_ref()
+            .map_or(1.0, |window| window.scale_factor());
+        self.imgui.io_mut().font_global_scale = ui_scale / hidpi_factor as f32;
+    }
+
+    /// Switches between windowed, borderless and exclusive fullscreen on the monitor the window
+    /// is on. The swap chain is recreated when the window resizes. Does nothing when headless.
+    pub fn set_window_mode(&mut self, mode: WindowMode) {
+        if let Some(window) = self.renderer.window.as_ref() {
+            window.set_fullscreen(mode.fullscreen(window.current_monitor()));
+        }
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        self.renderer
+            .window
+            .as_ref()
+            .map_or(WindowMode::Windowed, |window| {
+                WindowMode::from_fullscreen(window.fullscreen())
+            })
+    }
+
+    /// Lets the user resize the window, does nothing when headless.
+    pub fn set_resizable(&mut self, resizable: bool) {
+        if let Some(window) = self.renderer.window.as_ref() {
+            window.set_resizable(resizable);
+        }
+    }
+
+    // Recreates the swap chain and depth buffer from the swap chain descriptor. Attachments
+    // sized to the frame follow on their own the next time the passes are prepared. The sample
+    // count is fixed at startup, there's no setter for it since every pipeline that draws into
+    // the frame would have to be rebuilt.
+    fn recreate_swap_chain(&mut self) {
+        let depth_texture = {
+            let device = self.resources.get::<wgpu::Device>().unwrap();
+            let sc_desc = self.resources.get::<wgpu::SwapChainDescriptor>().unwrap();
+            self.renderer.swap_chain = self
+                .renderer
+                .surface
+                .as_ref()
+                .map(|surface| device.create_swap_chain(surface, &sc_desc));
+
+            let sample_count = self.resources.get::<RenderSettings>().unwrap().sample_count;
+            create_depth_texture(&device, sc_desc.width, sc_desc.height, sample_count)
+        };
+        self.resources
+            .insert(DepthTexture(depth_texture.create_default_view()));
+    }
+
+    /// Starts rendering offline, see `FrameExport`. From the next frame on the simulation steps
+    /// a fixed amount per frame and every frame is written out, it stops by itself after
+    /// `frame_count` frames. Fails if the folder can't be created or ffmpeg can't be started.
+    /// An export that's already running is finished first.
+    pub fn start_frame_export(&mut self, export: FrameExport) -> std::io::Result<()> {
+        self.stop_frame_export();
+        let size = self.renderer.size;
+        self.frame_export = Some(FrameExporter::start(export, size.width, size.height)?);
+        Ok(())
+    }
+
+    /// Writes out the frames that are still being read back and goes back to real time.
+    pub fn stop_frame_export(&mut self) {
+        if let Some(frame_export) = self.frame_export.take() {
+            frame_export.finish(&mut self.renderer, &self.resources);
+            // The export ran slower or faster than the clock, don't catch up on the difference.
+            self.elapsed_time = self.clock.elapsed().as_secs_f32();
+        }
+    }
+
+    pub fn is_exporting_frames(&self) -> bool {
+        self.frame_export.is_some()
+    }
+
+    /// Updates and draws a single frame without winit's event loop, for applications created
+    /// with `new_headless`. Returns false once `AppEvents::exit` has been called.
+    pub fn render_frame<T>(&mut self, app_state: &mut T) -> bool
+    where
+        T: AppState,
+    {
+        let mut control_flow = ControlFlow::Poll;
+        self.frame(app_state, &mut control_flow);
+        control_flow != ControlFlow::Exit
+    }
+
+    fn frame<T>(&mut self, app_state: &mut T, control_flow: &mut ControlFlow)
+    where
+        T: AppState,
+    {
+        let mut frame_time = match self.frame_export.as_ref() {
+            Some(frame_export) => frame_export.frame_duration(),
+            None => self.clock.elapsed().as_secs_f32() - self.elapsed_time,
+        };
+        self.frame_time = frame_time * 1000.0;
+        {
+            self.last_frame = self.imgui.io_mut().update_delta_time(self.last_frame);
+            // Clicking on the UI shouldn't go through to the scene behind it.
+            let mut pointer = self
+                .resources
+                .get_mut::<crate::scene::resources::Pointer>()
+                .unwrap();
+            pointer.ui_has_mouse = self.imgui.io().want_capture_mouse;
+        }
 
-                while frame_time > 0.0 {
-                    self.delta_time = f32::min(frame_time, self.fixed_timestep);
+        // Publish what was gathered during the last frame.
+        {
+            let mut frame_stats = self.resources.get_mut::<FrameStats>().unwrap();
+            let mut hitch_detector = self.resources.get_mut::<HitchDetector>().unwrap();
+            let mut asset_manager = self.resources.get_mut::<AssetManager>().unwrap();
+            let mut pipeline_manager = self.resources.get_mut::<PipelineManager>().unwrap();
+            // Hitches are broken down with the frame stats' timings.
+            frame_stats.enabled |= hitch_detector.enabled;
+            if frame_stats.enabled {
+                frame_stats.finish_frame(self.frame_time, asset_manager.gpu_texture_memory());
+            }
+            let events = asset_manager.take_diagnostic_events().into_iter().chain(
+                pipeline_manager
+                    .take_created_pipelines()
+                    .into_iter()
+                    .map(DiagnosticEvent::PipelineCreated),
+            );
+            hitch_detector.finish_frame(self.frame_time, &frame_stats.scopes, events);
+        }
 
-                    self.current_scene
-                        .update(self.delta_time, &mut self.resources);
+        // Hand out frame captures that finished reading back, this also lets any
+        // `GpuReadback` futures finish.
+        {
+            let device = self.resources.get::<wgpu::Device>().unwrap();
+            device.poll(wgpu::Maintain::Poll);
+            self.renderer.poll_captures();
+        }
+        if let Some(frame_export) = self.frame_export.as_mut() {
+            frame_export.write_ready();
+        }
 
-                    {
-                        let mut input = self.resources.get_mut::<Input>().unwrap();
-                        input.clear();
-                    }
+        // Don't start recording until there's a free frame slot.
+        {
+            let _scope = self.resources.get::<FrameStats>().unwrap().scope("frame_wait");
+            let device = self.resources.get::<wgpu::Device>().unwrap();
+            let frames_in_flight = self.resources.get::<graphics::FramesInFlight>().unwrap();
+            frames_in_flight.begin_frame(&device);
+        }
 
-                    app_state.update_ui(self);
+        // Upload any assets that finished loading in the background.
+        {
+            let mut asset_manager = self.resources.get_mut::<AssetManager>().unwrap();
+            let device = self.resources.get::<wgpu::Device>().unwrap();
+            let mut queue = self.resources.get_mut::<wgpu::Queue>().unwrap();
+            let mut resource_manager =
+                self.resources.get_mut::<GPUResourceManager>().unwrap();
+            asset_manager.update(&device, &mut queue, &mut resource_manager);
 
-                    frame_time -= self.delta_time;
-                    self.elapsed_time += self.delta_time;
+            let reloaded_shaders =
+                asset_manager.hot_reload(&device, &mut queue, &mut resource_manager);
+            if !reloaded_shaders.is_empty() {
+                let mut pipeline_manager =
+                    self.resources.get_mut::<PipelineManager>().unwrap();
+                for shader in reloaded_shaders.iter() {
+                    pipeline_manager.reload_shader(
+                        shader,
+                        &device,
+                        &asset_manager,
+                        &resource_manager,
+                    );
                 }
+            }
+        }
+
+        let update_scope = self.resources.get::<FrameStats>().unwrap().scope("update");
+        while frame_time > 0.0 {
+            self.delta_time = f32::min(frame_time, self.fixed_timestep);
+
+            self.current_scene
+                .update(self.delta_time, &mut self.resources, &mut self.stages);
 
+            {
+                let mut input = self.resources.get_mut::<Input>().unwrap();
+                input.clear();
+                let mut app_events = self.resources.get_mut::<AppEvents>().unwrap();
+                app_events.clear();
+            }
+
+            app_state.update_ui(self);
+
+            frame_time -= self.delta_time;
+            self.elapsed_time += self.delta_time;
+        }
+        drop(update_scope);
+
+        {
+            let mut memory_stats = self.resources.get_mut::<MemoryStats>().unwrap();
+            if memory_stats.enabled {
+                memory_stats.update(&self.current_scene.world, &self.resources);
+            }
+        }
+
+        {
+            let app_events = self.resources.get::<AppEvents>().unwrap();
+            if app_events.exit_requested() {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+            // There's nothing to draw to while minimized or suspended, so wait for
+            // the next event instead of spinning.
+            if app_events.is_paused() {
+                *control_flow = ControlFlow::Wait;
+                return;
+            }
+            *control_flow = ControlFlow::Poll;
+        }
+
+        #[cfg(feature = "gamepad")]
+        {
+            if let Some(gamepad_nav) = self.gamepad_nav.as_mut() {
+                gamepad_nav.update(self.imgui.io_mut());
+            }
+        }
+
+        // There's no UI when headless.
+        let ui = match self.renderer.window.as_ref() {
+            Some(window) => {
                 self.platform
-                    .prepare_frame(self.imgui.io_mut(), &self.renderer.window)
+                    .prepare_frame(self.imgui.io_mut(), window)
                     .expect("Failed to prepare frame");
-                let mut ui = self.imgui.frame();
+                Some(self.imgui.frame())
+            }
+            None => None,
+        };
 
-                // Store current frame buffer.
-                {
-                    let output = Arc::new(self.renderer.render());
-                    self.resources.insert(output);
-                }
+        // Make sure the transient attachments match the window size.
+        {
+            let device = self.resources.get::<wgpu::Device>().unwrap();
+            let mut pipeline_manager = self.resources.get_mut::<PipelineManager>().unwrap();
+            pipeline_manager.prepare_attachments(
+                &device,
+                self.renderer.size.width,
+                self.renderer.size.height,
+            );
+        }
 
-                // First update our probes if we need to.
-                {
-                    self.probe_manager
-                        .render(&mut self.resources, &mut self.current_scene);
-                }
+        // Store current frame buffer.
+        {
+            let _scope = self.resources.get::<FrameStats>().unwrap().scope("swap_chain");
+            let output = Arc::new(self.renderer.render());
+            self.resources.insert(output);
+        }
 
-                // Allow user to render UI stuff.
-                let scale = self.renderer.window.scale_factor() as f32;
-                app_state.draw_ui(
-                    &mut ui,
-                    Vec2::new(
-                        self.renderer.size.width as f32 / scale,
-                        self.renderer.size.height as f32 / scale,
-                    ),
-                );
+        // First update our probes if we need to.
+        {
+            let _scope = self.resources.get::<FrameStats>().unwrap().scope("probes");
+            self.probe_manager
+                .render(&mut self.resources, &mut self.current_scene);
+        }
 
-                // Draw UI.
-                {
-                    let device = self.resources.get::<wgpu::Device>().unwrap();
-                    let frame = self.resources.get::<Arc<wgpu::SwapChainOutput>>().unwrap();
-                    let command_buffer_queue = self.resources.get::<CommandBufferQueue>().unwrap();
-                    let mut encoder: wgpu::CommandEncoder =
-                        device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                            label: Some("UI"),
-                        });
-
-                    if self.last_cursor != ui.mouse_cursor() {
-                        self.last_cursor = ui.mouse_cursor();
-                        self.platform.prepare_render(&ui, &self.renderer.window);
-                    }
-
-                    self.imgui_renderer
-                        .render(ui.render(), &device, &mut encoder, &frame.view)
-                        .expect("Rendering failed");
-
-                    command_buffer_queue
-                        .push(CommandQueueItem {
-                            buffer: encoder.finish(),
-                            name: "UI".to_string(),
-                        })
-                        .unwrap();
-                }
+        // Then the dynamic GI probes, they see the reflection probes' light.
+        {
+            let _scope = self.resources.get::<FrameStats>().unwrap().scope("dynamic_gi");
+            graphics::dynamic_gi::update(&mut self.resources, &mut self.current_scene);
+        }
+        graphics::ao_volume::update(&mut self.resources);
 
-                // Next render's our scene.
-                self.render_schedule
-                    .execute(&mut self.current_scene.world, &mut self.resources);
+        if let (Some(mut ui), Some(window)) = (ui, self.renderer.window.as_ref()) {
+            let _scope = self.resources.get::<FrameStats>().unwrap().scope("ui");
+            // Allow user to render UI stuff.
+            let scale = window.scale_factor() as f32;
+            app_state.draw_ui(
+                &mut ui,
+                Vec2::new(
+                    self.renderer.size.width as f32 / scale,
+                    self.renderer.size.height as f32 / scale,
+                ),
+            );
+            {
+                let memory_stats = self.resources.get::<MemoryStats>().unwrap();
+                if memory_stats.enabled {
+                    memory_stats.draw_ui(&ui);
+                }
+            }
+            {
+                let mut texture_viewer = self.resources.get_mut::<TextureViewer>().unwrap();
+                if texture_viewer.enabled {
+                    let asset_manager = self.resources.get::<AssetManager>().unwrap();
+                    texture_viewer.update(
+                        &self.resources.get::<wgpu::Device>().unwrap(),
+                        &mut self.resources.get_mut::<wgpu::Queue>().unwrap(),
+                        &asset_manager,
+                        &self.resources.get::<PipelineManager>().unwrap(),
+                        &self.resources.get::<GPUResourceManager>().unwrap(),
+                        &mut self.imgui_renderer,
+                    );
+                    texture_viewer.draw_ui(&ui, &asset_manager);
+                }
+            }
 
-                // We need to let the swap drop so the frame renderers.
-                let _swap_chain_output = self
-                    .resources
-                    .remove::<Arc<wgpu::SwapChainOutput>>()
-                    .unwrap();
+            // Draw UI.
+            let device = self.resources.get::<wgpu::Device>().unwrap();
+            let frame = self.resources.get::<Arc<FrameOutput>>().unwrap();
+            let command_buffer_queue = self.resources.get::<CommandBufferQueue>().unwrap();
+            let mut encoder: wgpu::CommandEncoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("UI"),
+                });
 
-                self.renderer.window.request_redraw();
+            if self.last_cursor != ui.mouse_cursor() {
+                self.last_cursor = ui.mouse_cursor();
+                self.platform.prepare_render(&ui, window);
             }
-            Event::WindowEvent {
-                event: winit::event::WindowEvent::Resized(size),
-                ..
-            } => {
-                {
-                    let device = self.resources.get::<wgpu::Device>().unwrap();
-                    let mut sc_desc = self
-                        .resources
-                        .get_mut::<wgpu::SwapChainDescriptor>()
-                        .unwrap();
 
-                    sc_desc.width = size.width;
-                    sc_desc.height = size.height;
-                    self.renderer.size = *size;
-                    self.renderer.swap_chain =
-                        device.create_swap_chain(&self.renderer.surface, &sc_desc);
-                }
+            self.imgui_renderer
+                .render(ui.render(), &device, &mut encoder, frame.view())
+                .expect("Rendering failed");
 
-                // Resize depth buffer too
-                let depth_texture = {
-                    let device = self.resources.get::<wgpu::Device>().unwrap();
-                    device.create_texture(&wgpu::TextureDescriptor {
-                        size: wgpu::Extent3d {
-                            width: size.width,
-                            height: size.height,
-                            depth: 1,
-                        },
-                        mip_level_count: 1,
-                        sample_count: 1,
-                        dimension: wgpu::TextureDimension::D2,
-                        format: DEPTH_FORMAT,
-                        usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
-                        label: None,
-                    })
-                };
-                self.resources.insert(DepthTexture(depth_texture.create_default_view()));
-                
-                app_state.resize(self);
+            command_buffer_queue
+                .push(CommandQueueItem {
+                    buffer: encoder.finish(),
+                    name: "UI".to_string(),
+                })
+                .unwrap();
+        }
+
+        {
+            let frame_stats = self.resources.get::<FrameStats>().unwrap();
+            if frame_stats.enabled && frame_stats.show_overlay {
+                let mut text_renderer = self.resources.get_mut::<TextRenderer>().unwrap();
+                frame_stats.queue_overlay(&mut text_renderer);
             }
-            _ => (),
         }
-        self.platform
-            .handle_event(self.imgui.io_mut(), &self.renderer.window, &event);
+
+        // Next render's our scene.
+        let render_scope = self.resources.get::<FrameStats>().unwrap().scope("render");
+        if let Some(frame_export) = self.frame_export.as_mut() {
+            frame_export.request_frame(&mut self.renderer);
+        }
+        self.renderer.prepare_capture(&self.resources);
+        self.render_schedule
+            .execute(&mut self.current_scene.world, &mut self.resources);
+        self.renderer.finish_capture(&self.resources);
+        {
+            let device = self.resources.get::<wgpu::Device>().unwrap();
+            let queue = self.resources.get::<wgpu::Queue>().unwrap();
+            let mut uniform_arena = self.resources.get_mut::<graphics::UniformArena>().unwrap();
+            self.resources
+                .get_mut::<graphics::FramesInFlight>()
+                .unwrap()
+                .end_frame(&device, &queue, &mut uniform_arena);
+        }
+        drop(render_scope);
+
+        // We need to let the swap drop so the frame renderers.
+        let _swap_chain_output = self.resources.remove::<Arc<FrameOutput>>().unwrap();
+
+        if self
+            .frame_export
+            .as_ref()
+            .map_or(false, |frame_export| frame_export.is_finished())
+        {
+            self.stop_frame_export();
+        }
+
+        if let Some(window) = self.renderer.window.as_ref() {
+            window.request_redraw();
+        }
     }
 }
@@ -0,0 +1,99 @@
+use std::{error::Error, fmt, io};
+
+/// Everything that can go wrong when loading or looking up an asset.
+#[derive(Debug)]
+pub enum AssetError {
+    /// The file couldn't be opened or read.
+    Io { path: String, error: io::Error },
+    /// The file was read but it's contents are invalid, for example a corrupt image.
+    Decode { path: String, message: String },
+    /// The file is valid but uses something we don't support.
+    UnsupportedFormat { path: String, message: String },
+    /// No asset of that kind was loaded under the given name.
+    MissingKey { kind: &'static str, key: String },
+    /// A shader that doesn't compile, `excerpt` is the offending line with a marker under it.
+    Shader {
+        path: String,
+        line: usize,
+        column: usize,
+        message: String,
+        excerpt: String,
+    },
+}
+
+impl AssetError {
+    pub(crate) fn io<T: Into<String>>(path: T, error: io::Error) -> Self {
+        AssetError::Io {
+            path: path.into(),
+            error,
+        }
+    }
+
+    pub(crate) fn decode<T: Into<String>, M: ToString>(path: T, message: M) -> Self {
+        AssetError::Decode {
+            path: path.into(),
+            message: message.to_string(),
+        }
+    }
+
+    pub(crate) fn unsupported<T: Into<String>, M: ToString>(path: T, message: M) -> Self {
+        AssetError::UnsupportedFormat {
+            path: path.into(),
+            message: message.to_string(),
+        }
+    }
+
+    pub(crate) fn missing<T: Into<String>>(kind: &'static str, key: T) -> Self {
+        AssetError::MissingKey {
+            kind,
+            key: key.into(),
+        }
+    }
+
+    pub(crate) fn from_image<T: Into<String>>(path: T, error: image::ImageError) -> Self {
+        match error {
+            image::ImageError::IoError(error) => Self::io(path, error),
+            image::ImageError::Unsupported(error) => Self::unsupported(path, error),
+            error => Self::decode(path, error),
+        }
+    }
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetError::Io { path, error } => {
+                write!(f, "Asset Error: Could not read {}: {}", path, error)
+            }
+            AssetError::Decode { path, message } => {
+                write!(f, "Asset Error: Could not decode {}: {}", path, message)
+            }
+            AssetError::UnsupportedFormat { path, message } => {
+                write!(f, "Asset Error: {} is not supported: {}", path, message)
+            }
+            AssetError::MissingKey { kind, key } => {
+                write!(f, "Asset Error: Could not find {} {} asset!", key, kind)
+            }
+            AssetError::Shader {
+                path,
+                line,
+                column,
+                message,
+                excerpt,
+            } => write!(
+                f,
+                "Asset Error: Could not compile {}:{}:{}: {}\n{}",
+                path, line, column, message, excerpt
+            ),
+        }
+    }
+}
+
+impl Error for AssetError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AssetError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
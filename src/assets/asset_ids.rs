@@ -0,0 +1,76 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::core::Uuid;
+
+/// Where the asset manager looks for the ids, relative to the asset folder.
+pub const ASSET_IDS_FILE: &str = "asset_ids.ron";
+
+/// Stable ids for asset files, kept in `asset_ids.ron` next to the assets:
+/// `{"6f1c8a52-0d3e-4b7a-9c41-2e5f7a8b9c0d": "rock.gltf"}`
+/// When a file is renamed only it's entry changes, anything that stored the id still finds it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct AssetIds {
+    names: BTreeMap<Uuid, String>,
+    ids: HashMap<String, Uuid>,
+}
+
+impl AssetIds {
+    pub fn from_ron(source: &str) -> Result<Self, ron::de::Error> {
+        let names: BTreeMap<Uuid, String> = ron::de::from_str(source)?;
+        let ids = names.iter().map(|(id, name)| (name.clone(), *id)).collect();
+        Ok(Self { names, ids })
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::ser::Error> {
+        ron::ser::to_string_pretty(&self.names, ron::ser::PrettyConfig::default())
+    }
+
+    pub fn id(&self, file_name: &str) -> Option<Uuid> {
+        self.ids.get(file_name).copied()
+    }
+
+    pub fn name(&self, id: Uuid) -> Option<&str> {
+        self.names.get(&id).map(String::as_str)
+    }
+
+    /// The file's id, a new random one if it doesn't have one yet.
+    pub fn assign(&mut self, file_name: &str) -> Uuid {
+        if let Some(id) = self.id(file_name) {
+            return id;
+        }
+        let id = Uuid::new();
+        self.names.insert(id, file_name.to_string());
+        self.ids.insert(file_name.to_string(), id);
+        id
+    }
+
+    /// Moves an id to a renamed file, false if the old name didn't have one.
+    pub fn rename(&mut self, old_name: &str, new_name: &str) -> bool {
+        match self.ids.remove(old_name) {
+            Some(id) => {
+                self.names.insert(id, new_name.to_string());
+                self.ids.insert(new_name.to_string(), id);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asset_ids() {
+        let mut ids = AssetIds::default();
+        let rock = ids.assign("rock.gltf");
+        assert_eq!(ids.assign("rock.gltf"), rock);
+        assert!(ids.rename("rock.gltf", "boulder.gltf"));
+        assert_eq!(ids.name(rock), Some("boulder.gltf"));
+        assert_eq!(ids.id("rock.gltf"), None);
+
+        let loaded = AssetIds::from_ron(&ids.to_ron().unwrap()).unwrap();
+        assert_eq!(loaded, ids);
+    }
+}
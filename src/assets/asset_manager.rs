@@ -1,117 +1,1300 @@
+use crossbeam::channel::{self, Receiver, TryRecvError};
 use log::*;
-use std::collections::HashMap;
-use walkdir::WalkDir;
+use rayon::prelude::*;
+use std::any::TypeId;
+use std::collections::{HashMap, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use crate::core::Font;
+use super::{
+    asset_ids::{AssetIds, ASSET_IDS_FILE},
+    file_watcher::FileWatcher,
+    import_cache::ImportCache,
+    validation::{check_texture, AssetStats, ValidationIssue, ValidationReport, ValidationRules},
+    AssetError, Handle, LoadState, Migrations, UploadBudget, VirtualFileSystem,
+};
+use crate::ai::BehaviorTree;
+use crate::core::{AssetMemory, DiagnosticEvent, Font, SharedTextures, Uuid};
 use crate::graphics::{
-    material::{Image, Material, Shader},
+    material::{
+        image::DecodedImage, BuiltinMaterial, FallbackTexture, Image, ImageInfo, Material,
+        MaterialDesc, SamplerInfo, Shader, ShaderDefines, TextureSlot,
+    },
     mesh::Mesh,
     resources::GPUResourceManager,
+    MeshImport,
 };
 
+// Load states are keyed by the asset's type too, an image and a mesh can share a file name.
+fn load_key<T: 'static>(name: &str) -> (TypeId, String) {
+    (TypeId::of::<T>(), name.to_string())
+}
+
 pub struct AssetManager {
     path: String,
+    vfs: VirtualFileSystem,
     shaders: HashMap<String, Shader>,
-    fonts: HashMap<String, Font>,
+    pub(crate) fonts: HashMap<String, Font>,
     meshes: HashMap<String, Mesh>,
     pub(crate) images: HashMap<String, Image>,
     pub(crate) materials: HashMap<u32, Material>,
+    // The index each `.material.ron` file was loaded into.
+    material_names: HashMap<String, u32>,
+    behavior_trees: HashMap<String, Arc<BehaviorTree>>,
+    image_infos: HashMap<String, ImageInfo>,
+    import_cache: Option<ImportCache>,
+    mesh_imports: HashMap<String, MeshImport>,
+    mesh_buffer_usage: wgpu::BufferUsage,
+    // LODs generated for meshes that don't come with their own.
+    mesh_lod_levels: usize,
+    load_states: HashMap<(TypeId, String), LoadState>,
+    pending_images: HashMap<String, Receiver<Result<DecodedImage, AssetError>>>,
+    pending_meshes: HashMap<String, Receiver<Result<(Mesh, Vec<Material>), AssetError>>>,
+    // Images that finished decoding but didn't fit in a frame's upload budget yet.
+    decoded_images: VecDeque<(String, DecodedImage)>,
+    upload_budget: UploadBudget,
+    // Meshes that finished importing but are waiting on their textures.
+    imported_meshes: Vec<(String, Mesh, Vec<Material>)>,
+    // Set once `load_materials` has run, materials from meshes loaded after that need their bind groups created on upload.
+    materials_loaded: bool,
+    alpha_to_coverage: bool,
+    // The folder each shader was loaded from, used to recompile them.
+    shader_paths: HashMap<String, String>,
+    // The shader and defines each compiled variant was made from, see `load_shader_variant`.
+    shader_variants: HashMap<String, (String, ShaderDefines)>,
+    file_watcher: Option<FileWatcher>,
+    errors: Vec<AssetError>,
+    // Set when an image was replaced, material bind groups are rebuilt on the next update.
+    images_changed: bool,
+    // Bumped every time the material bind groups are rebuilt.
+    bind_group_generation: u32,
+    // The image that owns the texture for each content hash, see `upload_image`.
+    texture_hashes: HashMap<u64, String>,
+    shared_textures: SharedTextures,
+    migrations: Migrations,
+    asset_ids: AssetIds,
+    // Uploads and compiles since the last frame, for the `HitchDetector`.
+    diagnostic_events: Vec<DiagnosticEvent>,
 }
 
 impl AssetManager {
     pub fn new(path: String) -> Self {
+        let mut vfs = VirtualFileSystem::new();
+        vfs.mount_directory(&path);
         AssetManager {
             path,
+            vfs,
             shaders: HashMap::new(),
             fonts: HashMap::new(),
             meshes: HashMap::new(),
             images: HashMap::new(),
             materials: HashMap::new(),
+            material_names: HashMap::new(),
+            behavior_trees: HashMap::new(),
+            image_infos: HashMap::new(),
+            import_cache: None,
+            mesh_imports: HashMap::new(),
+            mesh_buffer_usage: wgpu::BufferUsage::empty(),
+            mesh_lod_levels: 0,
+            load_states: HashMap::new(),
+            pending_images: HashMap::new(),
+            pending_meshes: HashMap::new(),
+            decoded_images: VecDeque::new(),
+            upload_budget: UploadBudget::default(),
+            imported_meshes: Vec::new(),
+            materials_loaded: false,
+            alpha_to_coverage: false,
+            shader_paths: HashMap::new(),
+            shader_variants: HashMap::new(),
+            file_watcher: None,
+            errors: Vec::new(),
+            images_changed: false,
+            bind_group_generation: 0,
+            texture_hashes: HashMap::new(),
+            shared_textures: SharedTextures::default(),
+            migrations: Migrations::default(),
+            asset_ids: AssetIds::default(),
+            diagnostic_events: Vec::new(),
         }
     }
 
-    pub fn load(&mut self, device: &wgpu::Device, queue: &mut wgpu::Queue) {
+    /// Mounts an asset pack made with `build_pack` on top of the asset folder, files in the
+    /// pack win over files on disk. Must be called before `load` to load what's inside.
+    pub fn mount_pack<T: AsRef<Path>>(&mut self, path: T) -> Result<(), AssetError> {
+        self.vfs.mount_pack(path)
+    }
+
+    /// The files assets are read from, the asset folder and any mounted packs.
+    pub fn vfs(&self) -> &VirtualFileSystem {
+        &self.vfs
+    }
+
+    /// Upgrades scene, timeline and your own RON files written by older versions.
+    pub fn migrations(&self) -> &Migrations {
+        &self.migrations
+    }
+
+    /// Register migrations for the engine's formats or add your own here.
+    pub fn migrations_mut(&mut self) -> &mut Migrations {
+        &mut self.migrations
+    }
+
+    /// The stable id of an asset file from `asset_ids.ron`, see `assign_asset_id`.
+    pub fn asset_id(&self, file_name: &str) -> Option<Uuid> {
+        self.asset_ids.id(file_name)
+    }
+
+    /// The file an id belongs to, even if the file was renamed since the id was stored.
+    pub fn asset_name(&self, id: Uuid) -> Option<&str> {
+        self.asset_ids.name(id)
+    }
+
+    /// Gives a file a new random id unless it already has one, save them with `save_asset_ids`.
+    pub fn assign_asset_id(&mut self, file_name: &str) -> Uuid {
+        self.asset_ids.assign(file_name)
+    }
+
+    /// Keeps a file's id after it's been renamed, false if it didn't have one.
+    pub fn rename_asset_id(&mut self, old_name: &str, new_name: &str) -> bool {
+        self.asset_ids.rename(old_name, new_name)
+    }
+
+    /// Writes the ids to `asset_ids.ron` in the asset folder, it's read again by `load`.
+    pub fn save_asset_ids(&self) -> Result<(), AssetError> {
+        let path = self.resolve_path(ASSET_IDS_FILE);
+        let source = self
+            .asset_ids
+            .to_ron()
+            .map_err(|error| AssetError::decode(ASSET_IDS_FILE, error))?;
+        std::fs::write(&path, source).map_err(|error| AssetError::io(ASSET_IDS_FILE, error))
+    }
+
+    /// Overrides the texture usage for an image, must be called before `load`.
+    pub fn set_image_usage<T>(&mut self, file_name: T, usage: wgpu::TextureUsage)
+    where
+        T: Into<String>,
+    {
+        let file_name = file_name.into();
+        let info = ImageInfo::for_file(&file_name);
+        self.image_infos.entry(file_name).or_insert(info).usage = usage;
+    }
+
+    /// Sets the import operations (resize, flip, etc) for an image, must be called before `load`.
+    pub fn set_image_info<T>(&mut self, file_name: T, info: ImageInfo)
+    where
+        T: Into<String>,
+    {
+        self.image_infos.insert(file_name.into(), info);
+    }
+
+    /// Caches images with import operations in `folder` once they're processed, so later loads
+    /// read the result instead of resizing, flipping, etc again. Must be called before `load`.
+    pub fn set_import_cache<T: Into<PathBuf>>(&mut self, folder: T) {
+        self.import_cache = Some(ImportCache::new(folder));
+    }
+
+    /// Sets how a mesh file's up axis, units and uvs are converted, must be called before `load`
+    /// or `load_mesh`.
+    pub fn set_mesh_import<T>(&mut self, file_name: T, import: MeshImport)
+    where
+        T: Into<String>,
+    {
+        self.mesh_imports.insert(file_name.into(), import);
+    }
+
+    /// Extra buffer usage added to every mesh's vertex and index buffers, must be called before `load`.
+    pub fn set_mesh_buffer_usage(&mut self, usage: wgpu::BufferUsage) {
+        self.mesh_buffer_usage = usage;
+    }
+
+    /// Limits how much of what `load_image` and `load_mesh` finish loading is uploaded each
+    /// frame, the rest waits for the next frames. Can be changed at any time.
+    pub fn set_upload_budget(&mut self, upload_budget: UploadBudget) {
+        self.upload_budget = upload_budget;
+    }
+
+    /// Generates this many LODs for meshes that don't have any, see `Mesh::generate_lods`.
+    /// Off by default, must be called before `load`.
+    pub fn set_mesh_lod_levels(&mut self, levels: usize) {
+        self.mesh_lod_levels = levels;
+    }
+
+    /// Loads everything in the asset folder.
+    /// Files that fail to load are skipped, their errors are logged and kept in `load_errors`.
+    /// Images with the same sampler settings share their sampler through `resource_manager`.
+    /// Images are decoded in parallel on rayon's thread pool and uploaded in batches.
+    pub fn load(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &mut wgpu::Queue,
+        resource_manager: &GPUResourceManager,
+    ) {
         let mut init_encoder =
             device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
-        for entry in WalkDir::new(&self.path) {
-            let entry = entry.expect("Error: Could not access file.");
-            let file_name = entry.file_name().to_str().unwrap();
-            let full_file_path = str::replace(
-                entry.path().to_str().unwrap_or_else(|| {
-                    panic!(format!(
-                        "Error: could not get full file path: {}",
-                        file_name
-                    ))
-                }),
-                file_name,
-                "",
-            );
-            if file_name.ends_with(".shader") {
-                let shader =
-                    Shader::new(&device, full_file_path.to_string(), file_name.to_string());
-                self.shaders.insert(file_name.to_string(), shader);
-                info!("Compiled shader: {}", file_name);
+        // Materials fall back to these when a texture is missing or fails to load.
+        let fallback_sampler = resource_manager.get_sampler(device, &SamplerInfo::default());
+        for fallback in FallbackTexture::ALL.iter() {
+            let image = fallback.create_image(device, &mut init_encoder, fallback_sampler.clone());
+            self.images.insert(fallback.name().to_string(), image);
+        }
+        for builtin in BuiltinMaterial::ALL.iter() {
+            self.materials.insert(builtin.index(), builtin.create());
+        }
+
+        if self.vfs.exists(ASSET_IDS_FILE) {
+            let asset_ids = self.vfs.read_to_string(ASSET_IDS_FILE).and_then(|source| {
+                AssetIds::from_ron(&source)
+                    .map_err(|error| AssetError::decode(ASSET_IDS_FILE, error))
+            });
+            match asset_ids {
+                Ok(asset_ids) => self.asset_ids = asset_ids,
+                Err(error) => self.push_error(error),
             }
-            if file_name.ends_with(".ttf") || file_name.ends_with(".otf") {
-                let font = Font::new(
+        }
+
+        let mut image_paths = Vec::new();
+        for file_path in self.vfs.files() {
+            let file_name = Self::file_name(&file_path);
+            let file_name = file_name.as_str();
+            // The folder inside the asset folder or pack, ends with a `/` unless it's the root.
+            let full_file_path = &file_path[..file_path.len() - file_name.len()];
+            if file_name.ends_with(".shader") {
+                // Kept even if it doesn't compile so hot reload can pick up the fix.
+                self.shader_paths
+                    .insert(file_name.to_string(), full_file_path.to_string());
+                let shader = Shader::new(
                     &device,
-                    format!("{}{}", full_file_path, file_name).to_string(),
+                    &self.vfs,
+                    full_file_path.to_string(),
+                    file_name.to_string(),
                 );
-                self.fonts.insert(file_name.to_string(), font);
-                info!("Loaded font: {}", file_name);
+                match shader {
+                    Ok(shader) => {
+                        self.shaders.insert(file_name.to_string(), shader);
+                        info!("Compiled shader: {}", file_name);
+                    }
+                    Err(error) => {
+                        // Pipelines still get built, everything using it just shows up magenta.
+                        self.shaders
+                            .insert(file_name.to_string(), Shader::error(device));
+                        self.push_error(error);
+                    }
+                }
+            }
+            if file_name.ends_with(".ttf") || file_name.ends_with(".otf") {
+                match self.vfs.read(&file_path) {
+                    Ok(data) => {
+                        let font = Font { data };
+                        self.fonts.insert(file_name.to_string(), font);
+                        info!("Loaded font: {}", file_name);
+                    }
+                    Err(error) => self.push_error(error),
+                }
+            }
+            if file_name.ends_with(".bt.ron") {
+                let tree = self.vfs.read_to_string(&file_path).and_then(|source| {
+                    BehaviorTree::from_ron(&source)
+                        .map_err(|error| AssetError::decode(&file_path, error))
+                });
+                match tree {
+                    Ok(tree) => {
+                        self.behavior_trees
+                            .insert(file_name.to_string(), Arc::new(tree));
+                        info!("Loaded behavior tree: {}", file_name);
+                    }
+                    Err(error) => self.push_error(error),
+                }
+            }
+            if file_name.ends_with(".material.ron") {
+                let desc = self.vfs.read_to_string(&file_path).and_then(|source| {
+                    MaterialDesc::from_ron(&source)
+                        .map_err(|error| AssetError::decode(&file_path, error))
+                });
+                match desc {
+                    Ok(desc) => {
+                        let index = self.materials.len() as u32;
+                        self.materials
+                            .insert(index, Material::PBR(desc.create(index)));
+                        self.material_names.insert(file_name.to_string(), index);
+                        info!("Loaded material: {}", file_name);
+                    }
+                    Err(error) => self.push_error(error),
+                }
             }
-            if file_name.ends_with(".gltf") {
+            if file_name.ends_with(".gltf")
+                || file_name.ends_with(".glb")
+                || file_name.ends_with(".obj")
+            {
                 let current_index = self.materials.len() as u32;
-                let (mesh, materials) = Mesh::new(
-                    &device,
-                    format!("{}{}", full_file_path, file_name),
-                    current_index,
-                );
-                let mut index = current_index;
-                for material in materials {
-                    self.materials.insert(index, material);
-                    index += 1;
+                let vfs = &self.vfs;
+                let import = self.mesh_imports.get(file_name).copied().unwrap_or_default();
+                let imported =
+                    Mesh::import_with(&file_path, current_index, &import, &|path| vfs.read(path));
+                match imported {
+                    Ok((mut mesh, materials)) => {
+                        if mesh.lods.is_empty() {
+                            mesh.generate_lods(self.mesh_lod_levels);
+                        }
+                        mesh.create_buffers(device, self.mesh_buffer_usage);
+                        self.add_embedded_images(
+                            device,
+                            &mut init_encoder,
+                            resource_manager,
+                            &mut mesh,
+                        );
+                        let mut index = current_index;
+                        for material in materials {
+                            self.materials.insert(index, material);
+                            index += 1;
+                        }
+                        self.meshes.insert(file_name.to_string(), mesh);
+                        info!("Loaded mesh: {}", file_name);
+                    }
+                    Err(error) => self.push_error(error),
                 }
-                self.meshes.insert(file_name.to_string(), mesh);
-                info!("Loaded mesh: {}", file_name);
             }
             if file_name.ends_with(".png")
                 || file_name.ends_with(".jpg")
                 || file_name.ends_with(".hdr")
+                || file_name.ends_with(".dds")
+                || file_name.ends_with(".ktx2")
             {
-                let image = Image::new(
-                    &device,
-                    &mut init_encoder,
-                    format!("{}{}", full_file_path, file_name),
-                    file_name.to_string(),
-                );
-                self.images.insert(file_name.to_string(), image);
-                info!("Loaded image: {}", file_name);
+                image_paths.push(file_path.clone());
             }
         }
         queue.submit(Some(init_encoder.finish()));
+
+        self.load_images(device, queue, resource_manager, &image_paths);
+    }
+
+    // Decoding is the slow part of loading images, so they're decoded on every core a batch at
+    // a time. Each batch is uploaded with it's own encoder and submitted before the next one is
+    // decoded, which keeps the decoded pixels in memory bounded.
+    fn load_images(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &mut wgpu::Queue,
+        resource_manager: &GPUResourceManager,
+        image_paths: &[String],
+    ) {
+        let batch_size = rayon::current_num_threads() * 2;
+        for batch in image_paths.chunks(batch_size) {
+            let (vfs, image_infos) = (&self.vfs, &self.image_infos);
+            let import_cache = self.import_cache.as_ref();
+            let decoded: Vec<_> = batch
+                .par_iter()
+                .map(|file_path| {
+                    let file_name = Self::file_name(file_path);
+                    let default_info = ImageInfo::for_file(&file_name);
+                    let info = image_infos.get(&file_name).unwrap_or(&default_info);
+                    let decoded = vfs.read(file_path).and_then(|bytes| {
+                        Self::decode_image(import_cache, file_path, &bytes, info)
+                    });
+                    (file_name, info.usage, info.sampler, decoded)
+                })
+                .collect();
+
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("image upload"),
+            });
+            for (file_name, usage, sampler_info, decoded) in decoded {
+                match decoded {
+                    Ok(decoded) => {
+                        self.upload_image(
+                            device,
+                            &mut encoder,
+                            resource_manager,
+                            file_name.clone(),
+                            decoded,
+                            usage,
+                            sampler_info,
+                        );
+                        info!("Loaded image: {}", file_name);
+                    }
+                    Err(error) => self.push_error(error),
+                }
+            }
+            queue.submit(Some(encoder.finish()));
+        }
+    }
+
+    // Uploads the images packed inside a .glb so the mesh's materials can find them.
+    fn add_embedded_images(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        resource_manager: &GPUResourceManager,
+        mesh: &mut Mesh,
+    ) {
+        for (name, decoded) in mesh.embedded_images.drain(..) {
+            self.upload_image(
+                device,
+                encoder,
+                resource_manager,
+                name,
+                decoded,
+                Image::DEFAULT_USAGE,
+                SamplerInfo::default(),
+            );
+        }
+    }
+
+    // Uploads a decoded image, if an identical one is already loaded it's texture is shared
+    // instead of uploading another copy.
+    fn upload_image(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        resource_manager: &GPUResourceManager,
+        name: String,
+        decoded: DecodedImage,
+        usage: wgpu::TextureUsage,
+        sampler_info: SamplerInfo,
+    ) {
+        let hash = decoded.content_hash(usage);
+        // The old image under this name won't be around to share anymore.
+        self.texture_hashes.retain(|_, owner| *owner != name);
+        let sampler = resource_manager.get_sampler(device, &sampler_info);
+        let original = self
+            .texture_hashes
+            .get(&hash)
+            .and_then(|owner| self.images.get(owner));
+        let image = match original {
+            Some(original) => {
+                self.shared_textures.images += 1;
+                self.shared_textures.saved_bytes += decoded.size();
+                original.share(name.clone(), decoded, sampler_info, sampler)
+            }
+            None => {
+                self.texture_hashes.insert(hash, name.clone());
+                Image::from_decoded(
+                    device,
+                    encoder,
+                    name.clone(),
+                    decoded,
+                    usage,
+                    sampler_info,
+                    sampler,
+                )
+            }
+        };
+        self.images.insert(name, image);
+    }
+
+    /// Images that are using another image's texture because their contents are the same.
+    pub fn shared_textures(&self) -> SharedTextures {
+        self.shared_textures
+    }
+
+    /// GPU memory used by every image, textures shared between images are counted once.
+    pub fn gpu_texture_memory(&self) -> usize {
+        let mut textures = std::collections::HashSet::new();
+        self.images
+            .values()
+            .filter(|image| textures.insert(Arc::as_ptr(&image.texture)))
+            .map(Image::gpu_size)
+            .sum()
+    }
+
+    /// Checks everything that's loaded against `rules` and gathers the problems into a single
+    /// report, along with totals for the loaded assets. Call it after `load`, assets that are
+    /// still loading aren't checked.
+    pub fn validate(&self, rules: &ValidationRules) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        for image in self.images.values() {
+            // Render and storage textures are sized for the screen or the effect using them.
+            let is_target = image.usage.intersects(
+                wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::STORAGE,
+            );
+            if !is_target {
+                let (width, height) = (image.extent.width, image.extent.height);
+                report
+                    .issues
+                    .extend(check_texture(&image.name, width, height, rules));
+            }
+        }
+
+        let mut stats = AssetStats {
+            images: self.images.len(),
+            texture_memory: self.gpu_texture_memory(),
+            meshes: self.meshes.len(),
+            materials: self.materials.len(),
+            ..AssetStats::default()
+        };
+        for (name, mesh) in self.meshes.iter() {
+            for (index, sub_mesh) in mesh.sub_meshes.iter().enumerate() {
+                stats.sub_meshes += 1;
+                stats.vertices += sub_mesh.vertices.len();
+                stats.triangles += sub_mesh.triangles().count();
+                if !sub_mesh.has_uvs() {
+                    report.issues.push(ValidationIssue::MissingUvs {
+                        mesh: name.clone(),
+                        sub_mesh: index,
+                    });
+                }
+                if sub_mesh.generated_tangents() {
+                    report.issues.push(ValidationIssue::GeneratedTangents {
+                        mesh: name.clone(),
+                        sub_mesh: index,
+                    });
+                }
+            }
+        }
+        report.stats = stats;
+
+        for (index, material) in self.materials.iter() {
+            let textures = match material {
+                Material::PBR(data) => data.textures().to_vec(),
+                Material::Unlit(data) => vec![&data.main_texture],
+            };
+            for texture in textures {
+                if !self.images.contains_key(texture) && !self.is_image_pending(texture) {
+                    report.issues.push(ValidationIssue::MissingImage {
+                        material: *index,
+                        image: texture.clone(),
+                    });
+                }
+            }
+        }
+
+        report.sort();
+        report
+    }
+
+    /// CPU memory held by loaded assets and meshes waiting to be uploaded.
+    pub fn cpu_memory(&self) -> AssetMemory {
+        let mut memory = AssetMemory::default();
+        for image in self.images.values() {
+            if let Some(levels) = image.cpu_data() {
+                memory.images += levels.iter().map(Vec::len).sum::<usize>();
+            }
+        }
+        for mesh in self.meshes.values() {
+            memory.meshes += mesh.cpu_size();
+        }
+        // Embedded images are counted as images rather than meshes until they're uploaded.
+        for (_, mesh, _) in self.imported_meshes.iter() {
+            let images: usize = mesh
+                .embedded_images
+                .iter()
+                .map(|(_, image)| image.size())
+                .sum();
+            memory.images += images;
+            memory.meshes += mesh.cpu_size() - images;
+        }
+        for font in self.fonts.values() {
+            memory.fonts += font.data.capacity();
+        }
+        memory
+    }
+
+    /// Errors from assets that failed to load, either in `load` or asynchronously.
+    pub fn load_errors(&self) -> &[AssetError] {
+        &self.errors
+    }
+
+    pub(crate) fn take_diagnostic_events(&mut self) -> Vec<DiagnosticEvent> {
+        std::mem::take(&mut self.diagnostic_events)
+    }
+
+    fn push_error(&mut self, error: AssetError) {
+        error!("{}", error);
+        self.errors.push(error);
+    }
+
+    /// Starts loading an image on rayon's thread pool and returns a handle to it right away.
+    /// The path is relative to the asset folder, the image is stored under it's file name.
+    /// The image is uploaded to the GPU during the first frame after it finished decoding.
+    pub fn load_image<T>(&mut self, path: T) -> Handle<Image>
+    where
+        T: Into<String>,
+    {
+        let path = path.into();
+        let file_name = Self::file_name(&path);
+        let handle = Handle::new(file_name.clone());
+        let key = load_key::<Image>(&file_name);
+        if self.load_states.contains_key(&key) || self.images.contains_key(&file_name) {
+            return handle;
+        }
+
+        let vfs = self.vfs.clone();
+        let import_cache = self.import_cache.clone();
+        let info = self
+            .image_infos
+            .get(&file_name)
+            .cloned()
+            .unwrap_or_else(|| ImageInfo::for_file(&file_name));
+        let (sender, receiver) = channel::unbounded();
+        // Rayon aborts on a panic, catching it drops the sender so the load is marked as failed.
+        rayon::spawn(move || {
+            let _ = panic::catch_unwind(AssertUnwindSafe(move || {
+                let decoded = vfs.read(&path).and_then(|bytes| {
+                    Self::decode_image(import_cache.as_ref(), &path, &bytes, &info)
+                });
+                let _ = sender.send(decoded);
+            }));
+        });
+
+        self.pending_images.insert(file_name, receiver);
+        self.load_states.insert(key, LoadState::Loading);
+        handle
+    }
+
+    /// Starts loading a glTF, .glb or OBJ mesh on rayon's thread pool and returns a handle to it
+    /// right away.
+    /// The path is relative to the asset folder, the mesh is stored under it's file name.
+    /// The mesh waits for any of it's textures that are still loading before it's uploaded.
+    /// Wait until it's `Loaded` before giving an entity a `Mesh` component that uses it.
+    pub fn load_mesh<T>(&mut self, path: T) -> Handle<Mesh>
+    where
+        T: Into<String>,
+    {
+        let path = path.into();
+        let file_name = Self::file_name(&path);
+        let handle = Handle::new(file_name.clone());
+        let key = load_key::<Mesh>(&file_name);
+        if self.load_states.contains_key(&key) || self.meshes.contains_key(&file_name) {
+            return handle;
+        }
+
+        let vfs = self.vfs.clone();
+        let import = self.mesh_imports.get(&file_name).copied().unwrap_or_default();
+        let lod_levels = self.mesh_lod_levels;
+        let (sender, receiver) = channel::unbounded();
+        rayon::spawn(move || {
+            let _ = panic::catch_unwind(AssertUnwindSafe(move || {
+                // Material indices are assigned once the mesh is uploaded.
+                let mut imported = Mesh::import_with(&path, 0, &import, &|path| vfs.read(path));
+                if let Ok((mesh, _)) = imported.as_mut() {
+                    if mesh.lods.is_empty() {
+                        mesh.generate_lods(lod_levels);
+                    }
+                }
+                let _ = sender.send(imported);
+            }));
+        });
+
+        self.pending_meshes.insert(file_name, receiver);
+        self.load_states.insert(key, LoadState::Loading);
+        handle
+    }
+
+    /// Turns a path relative to the asset folder into a full path.
+    pub fn resolve_path<T: AsRef<Path>>(&self, path: T) -> PathBuf {
+        Path::new(&self.path).join(path)
+    }
+
+    /// Returns the load state of an asset requested with `load_image` or `load_mesh`.
+    /// Assets loaded by `load` are always `Loaded`.
+    pub fn get_load_state<T: 'static>(&self, handle: &Handle<T>) -> LoadState {
+        self.load_states
+            .get(&load_key::<T>(handle.name()))
+            .copied()
+            .unwrap_or(LoadState::Loaded)
+    }
+
+    /// Returns true while any async loads are still running.
+    pub fn is_loading(&self) -> bool {
+        !self.pending_images.is_empty()
+            || !self.decoded_images.is_empty()
+            || !self.pending_meshes.is_empty()
+            || !self.imported_meshes.is_empty()
+    }
+
+    // Still decoding or waiting for room in the upload budget.
+    fn is_image_pending(&self, file_name: &str) -> bool {
+        self.pending_images.contains_key(file_name)
+            || self.decoded_images.iter().any(|(name, _)| name == file_name)
+    }
+
+    /// Uploads any assets that have finished decoding, called once per frame.
+    pub(crate) fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &mut wgpu::Queue,
+        resource_manager: &mut GPUResourceManager,
+    ) {
+        self.refresh_bind_groups(device, resource_manager);
+
+        if !self.is_loading() {
+            return;
+        }
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("asset upload"),
+        });
+
+        let mut finished_images = Vec::new();
+        for (file_name, receiver) in self.pending_images.iter() {
+            match receiver.try_recv() {
+                Ok(decoded) => finished_images.push((file_name.clone(), decoded)),
+                Err(TryRecvError::Disconnected) => finished_images.push((
+                    file_name.clone(),
+                    Err(AssetError::decode(file_name, "loading panicked")),
+                )),
+                Err(TryRecvError::Empty) => (),
+            }
+        }
+        for (file_name, decoded) in finished_images {
+            self.pending_images.remove(&file_name);
+            match decoded {
+                Ok(decoded) => self.decoded_images.push_back((file_name, decoded)),
+                Err(error) => {
+                    self.load_states
+                        .insert(load_key::<Image>(&file_name), LoadState::Failed);
+                    self.push_error(error);
+                }
+            }
+        }
+
+        // Whatever doesn't fit in this frame's budget waits for the next one.
+        let mut budget = self.upload_budget.start();
+        while budget.has_room() {
+            let (file_name, decoded) = match self.decoded_images.pop_front() {
+                Some(decoded) => decoded,
+                None => break,
+            };
+            budget.record(decoded.size());
+            let (usage, sampler_info) = self
+                .image_infos
+                .get(&file_name)
+                .map(|info| (info.usage, info.sampler))
+                .unwrap_or((Image::DEFAULT_USAGE, SamplerInfo::default()));
+            self.upload_image(
+                device,
+                &mut encoder,
+                resource_manager,
+                file_name.clone(),
+                decoded,
+                usage,
+                sampler_info,
+            );
+            self.load_states
+                .insert(load_key::<Image>(&file_name), LoadState::Loaded);
+            info!("Loaded image: {}", file_name);
+            self.diagnostic_events
+                .push(DiagnosticEvent::AssetUpload(file_name));
+        }
+
+        let mut finished_meshes = Vec::new();
+        for (file_name, receiver) in self.pending_meshes.iter() {
+            match receiver.try_recv() {
+                Ok(imported) => finished_meshes.push((file_name.clone(), imported)),
+                Err(TryRecvError::Disconnected) => finished_meshes.push((
+                    file_name.clone(),
+                    Err(AssetError::decode(file_name, "loading panicked")),
+                )),
+                Err(TryRecvError::Empty) => (),
+            }
+        }
+        for (file_name, imported) in finished_meshes {
+            self.pending_meshes.remove(&file_name);
+            match imported {
+                Ok((mesh, materials)) => self.imported_meshes.push((file_name, mesh, materials)),
+                Err(error) => {
+                    self.load_states
+                        .insert(load_key::<Mesh>(&file_name), LoadState::Failed);
+                    self.push_error(error);
+                }
+            }
+        }
+
+        let imported_meshes = std::mem::replace(&mut self.imported_meshes, Vec::new());
+        for (file_name, mut mesh, materials) in imported_meshes {
+            let waiting_on_textures = materials.iter().any(|material| match material {
+                Material::PBR(data) => data
+                    .textures()
+                    .iter()
+                    .any(|texture| self.is_image_pending(texture)),
+                Material::Unlit(data) => self.is_image_pending(&data.main_texture),
+            });
+            if waiting_on_textures || !budget.has_room() {
+                self.imported_meshes.push((file_name, mesh, materials));
+                continue;
+            }
+            budget.record(mesh.cpu_size());
+
+            self.add_embedded_images(device, &mut encoder, resource_manager, &mut mesh);
+            let start_index = self.materials.len() as u32;
+            let lod_sub_meshes = mesh.lods.iter_mut().flat_map(|lod| lod.sub_meshes.iter_mut());
+            for sub_mesh in mesh.sub_meshes.iter_mut().chain(lod_sub_meshes) {
+                sub_mesh.material_index += start_index;
+            }
+            mesh.create_buffers(device, self.mesh_buffer_usage);
+
+            for mut material in materials {
+                match &mut material {
+                    Material::PBR(data) => {
+                        data.index += start_index;
+                        if self.materials_loaded {
+                            data.alpha_to_coverage = self.alpha_to_coverage;
+                            let bind_group = data.create_bind_group(
+                                &self.images,
+                                device,
+                                resource_manager
+                                    .get_bind_group_layout("pbr_material_layout")
+                                    .unwrap(),
+                            );
+                            resource_manager.add_multi_bind_group("pbr", bind_group, data.index);
+                        }
+                    }
+                    // glTF materials using KHR_materials_unlit.
+                    Material::Unlit(data) => {
+                        data.index += start_index;
+                        if self.materials_loaded {
+                            data.create_bind_group(
+                                &self.images,
+                                device,
+                                resource_manager
+                                    .get_bind_group_layout("unlit_material")
+                                    .unwrap(),
+                            );
+                        }
+                    }
+                }
+                let index = self.materials.len() as u32;
+                self.materials.insert(index, material);
+            }
+
+            self.meshes.insert(file_name.clone(), mesh);
+            self.load_states
+                .insert(load_key::<Mesh>(&file_name), LoadState::Loaded);
+            info!("Loaded mesh: {}", file_name);
+            self.diagnostic_events
+                .push(DiagnosticEvent::AssetUpload(file_name));
+        }
+
+        queue.submit(Some(encoder.finish()));
+    }
+
+    /// Watches the asset folder and reloads shaders, images and `.material.ron` files when they
+    /// change on disk. Pipelines using a reloaded shader are rebuilt and materials get new bind
+    /// groups. Meant for development.
+    pub fn enable_hot_reload(&mut self) {
+        if self.file_watcher.is_none() {
+            self.file_watcher = Some(FileWatcher::new(self.path.clone()));
+        }
+    }
+
+    /// Reloads any changed shaders, images and materials, returns the names of the shaders that
+    /// were recompiled.
+    pub(crate) fn hot_reload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &mut wgpu::Queue,
+        resource_manager: &mut GPUResourceManager,
+    ) -> Vec<String> {
+        let changed_files = match self.file_watcher.as_mut() {
+            Some(file_watcher) => file_watcher.changed_files(),
+            None => return Vec::new(),
+        };
+        if changed_files.is_empty() {
+            return Vec::new();
+        }
+
+        let mut shaders_to_reload = Vec::new();
+        let mut images_to_reload = Vec::new();
+        let mut materials_reloaded = false;
+        for path in changed_files.iter() {
+            let file_name = Self::file_name(path.to_str().unwrap_or(""));
+            if file_name.ends_with(".shader") && self.shaders.contains_key(&file_name) {
+                shaders_to_reload.push(file_name);
+            } else if file_name.ends_with(".glsl") {
+                // Recompile the shaders that use this file, if none do it's probably an include.
+                let users: Vec<String> = self
+                    .shader_paths
+                    .iter()
+                    .filter(|(shader, path)| {
+                        self.vfs
+                            .read_to_string(&format!("{}{}", path, shader))
+                            .map(|contents| contents.contains(&file_name))
+                            .unwrap_or(false)
+                    })
+                    .map(|(shader, _)| shader.clone())
+                    .collect();
+                if users.is_empty() {
+                    shaders_to_reload.extend(self.shader_paths.keys().cloned());
+                } else {
+                    shaders_to_reload.extend(users);
+                }
+            } else if self.images.contains_key(&file_name) {
+                images_to_reload.push((file_name, path.clone()));
+            } else if let Some(index) = self.material_names.get(&file_name).copied() {
+                // A broken file keeps the old material until it's fixed.
+                let desc = std::fs::read_to_string(path)
+                    .map_err(|error| AssetError::io(path.display().to_string(), error))
+                    .and_then(|source| {
+                        MaterialDesc::from_ron(&source)
+                            .map_err(|error| AssetError::decode(&file_name, error))
+                    });
+                match desc {
+                    Ok(desc) => {
+                        let mut material = desc.create(index);
+                        material.alpha_to_coverage = self.alpha_to_coverage;
+                        self.materials.insert(index, Material::PBR(material));
+                        materials_reloaded = true;
+                        info!("Reloaded material: {}", file_name);
+                    }
+                    Err(error) => error!("{}", error),
+                }
+            }
+        }
+        shaders_to_reload.sort();
+        shaders_to_reload.dedup();
+
+        let mut reloaded_shaders = Vec::new();
+        for shader_name in shaders_to_reload {
+            let path = self.shader_paths.get(&shader_name).unwrap().clone();
+            // Keep the old shader and it's pipelines around if the new one doesn't compile.
+            match Shader::new(device, &self.vfs, path.clone(), shader_name.clone()) {
+                Ok(shader) => {
+                    self.shaders.insert(shader_name.clone(), shader);
+                    info!("Reloaded shader: {}", shader_name);
+                    self.diagnostic_events
+                        .push(DiagnosticEvent::ShaderCompiled(shader_name.clone()));
+                }
+                Err(error) => {
+                    error!("{}", error);
+                    continue;
+                }
+            }
+            // Variants are rebuilt along with their pipelines, one that doesn't compile
+            // anymore keeps it's old shader.
+            for (variant_name, (_, defines)) in self
+                .shader_variants
+                .iter()
+                .filter(|(_, (shader, _))| *shader == shader_name)
+            {
+                let shader = Shader::with_defines(
+                    device,
+                    &self.vfs,
+                    path.clone(),
+                    shader_name.clone(),
+                    defines,
+                );
+                match shader {
+                    Ok(shader) => {
+                        self.shaders.insert(variant_name.clone(), shader);
+                    }
+                    Err(error) => error!("{}", error),
+                }
+            }
+            reloaded_shaders.push(shader_name);
+        }
+
+        if !images_to_reload.is_empty() {
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("hot reload"),
+            });
+            for (file_name, path) in images_to_reload.iter() {
+                let default_info = ImageInfo::for_file(file_name);
+                let info = self.image_infos.get(file_name).unwrap_or(&default_info);
+                let (usage, sampler_info) = (info.usage, info.sampler);
+                // The file might still be half written, in that case we'll pick it up on the next change.
+                match Image::decode(path.to_str().unwrap(), info) {
+                    Ok(decoded) => {
+                        self.upload_image(
+                            device,
+                            &mut encoder,
+                            resource_manager,
+                            file_name.clone(),
+                            decoded,
+                            usage,
+                            sampler_info,
+                        );
+                        info!("Reloaded image: {}", file_name);
+                        self.diagnostic_events
+                            .push(DiagnosticEvent::AssetUpload(file_name.clone()));
+                    }
+                    Err(error) => error!("{}", error),
+                }
+            }
+            queue.submit(Some(encoder.finish()));
+        }
+
+        if (materials_reloaded || !images_to_reload.is_empty()) && self.materials_loaded {
+            self.rebuild_material_bind_groups(device, resource_manager);
+        }
+
+        reloaded_shaders
+    }
+
+    /// Rebuilds the material bind groups if an image was replaced since they were made.
+    pub(crate) fn refresh_bind_groups(
+        &mut self,
+        device: &wgpu::Device,
+        resource_manager: &mut GPUResourceManager,
+    ) {
+        if self.images_changed && self.materials_loaded {
+            self.images_changed = false;
+            self.rebuild_material_bind_groups(device, resource_manager);
+        }
+    }
+
+    /// Recreates every material's bind group so they point at the current images.
+    fn rebuild_material_bind_groups(
+        &mut self,
+        device: &wgpu::Device,
+        resource_manager: &mut GPUResourceManager,
+    ) {
+        for material in self.materials.values_mut() {
+            match material {
+                Material::Unlit(unlit_material) => {
+                    unlit_material.create_bind_group(
+                        &self.images,
+                        device,
+                        resource_manager
+                            .get_bind_group_layout("unlit_material")
+                            .unwrap(),
+                    );
+                }
+                Material::PBR(pbr_material) => {
+                    let bind_group = pbr_material.create_bind_group(
+                        &self.images,
+                        device,
+                        resource_manager
+                            .get_bind_group_layout("pbr_material_layout")
+                            .unwrap(),
+                    );
+                    resource_manager.add_multi_bind_group("pbr", bind_group, pbr_material.index);
+                }
+            }
+        }
+        self.bind_group_generation += 1;
+    }
+
+    // Material instances compare this to know when to rebuild their bind groups.
+    pub(crate) fn bind_group_generation(&self) -> u32 {
+        self.bind_group_generation
+    }
+
+    /// Adds an image or replaces the one with the same name, materials using it pick it up
+    /// on the next frame. Useful for procedurally generated textures.
+    pub fn insert_image(&mut self, image: Image) -> Handle<Image> {
+        let name = image.name.clone();
+        self.texture_hashes.retain(|_, owner| *owner != name);
+        self.images.insert(name.clone(), image);
+        self.load_states.remove(&load_key::<Image>(&name));
+        self.images_changed = true;
+        Handle::new(name)
+    }
+
+    /// Adds a mesh made in code, like `Mesh::plane`, or replaces the one with the same name.
+    /// It's buffers are created here if it doesn't have them yet.
+    pub fn insert_mesh<T>(
+        &mut self,
+        device: &wgpu::Device,
+        name: T,
+        mut mesh: Mesh,
+    ) -> Handle<Mesh>
+    where
+        T: Into<String>,
+    {
+        let name = name.into();
+        if mesh
+            .sub_meshes
+            .iter()
+            .any(|sub_mesh| sub_mesh.vertex_buffer.is_none())
+        {
+            mesh.create_buffers(device, self.mesh_buffer_usage);
+        }
+        self.meshes.insert(name.clone(), mesh);
+        self.load_states.remove(&load_key::<Mesh>(&name));
+        Handle::new(name)
+    }
+
+    /// Removes a mesh added with `insert_mesh`, entities still using it need another one
+    /// before the next frame.
+    pub fn remove_mesh<T: Into<String>>(&mut self, name: T) -> Option<Mesh> {
+        let name = name.into();
+        self.load_states.remove(&load_key::<Mesh>(&name));
+        self.meshes.remove(&name)
+    }
+
+    /// Removes an image, materials still using it fall back to a `FallbackTexture`.
+    pub fn remove_image<T: Into<String>>(&mut self, name: T) -> Option<Image> {
+        let name = name.into();
+        self.texture_hashes.retain(|_, owner| *owner != name);
+        let image = self.images.remove(&name);
+        if image.is_some() {
+            self.images_changed = true;
+        }
+        image
+    }
+
+    /// Creates an image you can render into and use as a material texture.
+    /// See `Image::new_render_texture`.
+    pub fn create_render_texture<T>(
+        &mut self,
+        device: &wgpu::Device,
+        name: T,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Handle<Image>
+    where
+        T: Into<String>,
+    {
+        self.insert_image(Image::new_render_texture(device, name, width, height, format))
+    }
+
+    /// Creates an image compute shaders can write to and use as a material texture.
+    /// See `Image::new_storage_texture`.
+    pub fn create_storage_texture<T>(
+        &mut self,
+        device: &wgpu::Device,
+        name: T,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Handle<Image>
+    where
+        T: Into<String>,
+    {
+        self.insert_image(Image::new_storage_texture(device, name, width, height, format))
+    }
+
+    /// Recreates a render texture at a new size, materials using it are updated on the next frame.
+    pub fn resize_render_texture(
+        &mut self,
+        device: &wgpu::Device,
+        handle: &Handle<Image>,
+        width: u32,
+        height: u32,
+    ) -> Result<(), AssetError> {
+        let image = self.try_get_image(handle.name())?;
+        if image.extent.width == width && image.extent.height == height {
+            return Ok(());
+        }
+        let format = image.format;
+        self.insert_image(Image::new_render_texture(
+            device,
+            handle.name(),
+            width,
+            height,
+            format,
+        ));
+        Ok(())
+    }
+
+    /// Points a PBR material's texture slot at an image, the bind group is refreshed on the next frame.
+    pub fn set_material_texture(
+        &mut self,
+        material_index: u32,
+        slot: TextureSlot,
+        image: &Handle<Image>,
+    ) -> Result<(), AssetError> {
+        match self.materials.get_mut(&material_index) {
+            Some(Material::PBR(pbr_material)) => {
+                pbr_material.set_texture(slot, image.name());
+                self.images_changed = true;
+                Ok(())
+            }
+            _ => Err(AssetError::missing(
+                "pbr material",
+                format!("@index {}", material_index),
+            )),
+        }
+    }
+
+    fn file_name(path: &str) -> String {
+        Path::new(path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(path)
+            .to_string()
+    }
+
+    // Goes through the import cache when there is one.
+    fn decode_image(
+        import_cache: Option<&ImportCache>,
+        path: &str,
+        bytes: &[u8],
+        info: &ImageInfo,
+    ) -> Result<DecodedImage, AssetError> {
+        match import_cache {
+            Some(import_cache) => import_cache.decode(path, bytes, info),
+            None => Image::decode_bytes(path, bytes, info),
+        }
+    }
+
+    /// The folder the engine's own shaders live in inside the virtual file system.
+    pub(crate) fn core_shader_path(&self) -> String {
+        "core/shaders/".to_string()
+    }
+
+    /// Compiles `shader` with extra defines, it's stored under `ShaderDefines::variant_name`.
+    /// Does nothing if the variant already exists. Like regular shaders, variants that don't
+    /// compile are replaced with the error shader.
+    pub fn load_shader_variant(
+        &mut self,
+        device: &wgpu::Device,
+        shader: &str,
+        defines: &ShaderDefines,
+    ) -> Result<(), AssetError> {
+        let variant_name = defines.variant_name(shader);
+        if self.shaders.contains_key(&variant_name) {
+            return Ok(());
+        }
+        let path = self
+            .shader_paths
+            .get(shader)
+            .ok_or_else(|| AssetError::missing("shader", shader))?
+            .clone();
+
+        let compiled =
+            Shader::with_defines(device, &self.vfs, path, shader.to_string(), defines);
+        self.shader_variants
+            .insert(variant_name.clone(), (shader.to_string(), defines.clone()));
+        match compiled {
+            Ok(compiled) => {
+                self.shaders.insert(variant_name.clone(), compiled);
+                info!("Compiled shader: {}", variant_name);
+                self.diagnostic_events
+                    .push(DiagnosticEvent::ShaderCompiled(variant_name.clone()));
+            }
+            Err(error) => {
+                self.shaders.insert(variant_name, Shader::error(device));
+                self.push_error(error);
+            }
+        }
+        Ok(())
     }
 
     pub fn get_shader<'a, T>(&'a self, key: T) -> &'a Shader
+    where
+        T: Into<String>,
+    {
+        self.try_get_shader(key).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_get_shader<T>(&self, key: T) -> Result<&Shader, AssetError>
     where
         T: Into<String>,
     {
         let key = key.into();
-        self.shaders.get(&key).expect(&format!(
-            "Asset Error: Could not find {} shader asset!",
-            &key
-        ))
+        self.shaders
+            .get(&key)
+            .ok_or_else(|| AssetError::missing("shader", key))
     }
 
     pub fn get_mesh<T>(&self, key: T) -> &Mesh
+    where
+        T: Into<String>,
+    {
+        self.try_get_mesh(key).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_get_mesh<T>(&self, key: T) -> Result<&Mesh, AssetError>
     where
         T: Into<String>,
     {
         let key = key.into();
         self.meshes
             .get(&key)
-            .expect(&format!("Asset Error: Could not find {} mesh asset!", &key))
+            .ok_or_else(|| AssetError::missing("mesh", key))
     }
 
     pub fn get_meshes(&self) -> Vec<&Mesh> {
@@ -123,10 +1306,19 @@ impl AssetManager {
     }
 
     pub fn get_material(&self, index: u32) -> &Material {
-        self.materials.get(&index).expect(&format!(
-            "Asset Error: Could not find material @index {} asset!",
-            index
-        ))
+        self.try_get_material(index)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_get_material(&self, index: u32) -> Result<&Material, AssetError> {
+        self.materials
+            .get(&index)
+            .ok_or_else(|| AssetError::missing("material", format!("@index {}", index)))
+    }
+
+    /// The index of a material loaded from a `.material.ron` file.
+    pub fn material_index(&self, file_name: &str) -> Option<u32> {
+        self.material_names.get(file_name).copied()
     }
 
     pub fn get_materials_mut(&mut self) -> Vec<&mut Material> {
@@ -138,14 +1330,20 @@ impl AssetManager {
     }
 
     pub fn get_image<T>(&self, key: T) -> &Image
+    where
+        T: Into<String>,
+    {
+        self.try_get_image(key).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_get_image<T>(&self, key: T) -> Result<&Image, AssetError>
     where
         T: Into<String>,
     {
         let key = key.into();
-        self.images.get(&key).expect(&format!(
-            "Asset Error: Could not find {} image asset!",
-            &key
-        ))
+        self.images
+            .get(&key)
+            .ok_or_else(|| AssetError::missing("image", key))
     }
 
     pub fn get_image_option<T>(&self, key: T) -> Option<&Image>
@@ -160,14 +1358,40 @@ impl AssetManager {
         self.images.values().collect()
     }
 
+    pub fn get_behavior_tree<T>(&self, key: T) -> Arc<BehaviorTree>
+    where
+        T: Into<String>,
+    {
+        self.try_get_behavior_tree(key)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_get_behavior_tree<T>(&self, key: T) -> Result<Arc<BehaviorTree>, AssetError>
+    where
+        T: Into<String>,
+    {
+        let key = key.into();
+        self.behavior_trees
+            .get(&key)
+            .cloned()
+            .ok_or_else(|| AssetError::missing("behavior tree", key))
+    }
+
     pub fn get_font<T>(&self, key: T) -> &Font
+    where
+        T: Into<String>,
+    {
+        self.try_get_font(key).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_get_font<T>(&self, key: T) -> Result<&Font, AssetError>
     where
         T: Into<String>,
     {
         let key = key.into();
         self.fonts
             .get(&key)
-            .expect(&format!("Asset Error: Could not find {} font asset!", &key))
+            .ok_or_else(|| AssetError::missing("font", key))
     }
 
     pub fn get_font_mut<T>(&mut self, key: T) -> &mut Font
@@ -177,7 +1401,8 @@ impl AssetManager {
         let key = key.into();
         self.fonts
             .get_mut(&key)
-            .expect(&format!("Asset Error: Could not find {} font asset!", &key))
+            .ok_or_else(|| AssetError::missing("font", key))
+            .unwrap_or_else(|e| panic!("{}", e))
     }
 
     pub fn get_fonts(&self) -> Vec<&Font> {
@@ -188,7 +1413,10 @@ impl AssetManager {
         &mut self,
         device: &wgpu::Device,
         resource_manager: &mut GPUResourceManager,
+        alpha_to_coverage: bool,
     ) {
+        self.materials_loaded = true;
+        self.alpha_to_coverage = alpha_to_coverage;
         let mut current_bind_group = None;
         let mut current_index = 0;
         for material in self.materials.values_mut() {
@@ -207,6 +1435,7 @@ impl AssetManager {
                     let pbr_bind_group_layout = resource_manager
                         .get_bind_group_layout("pbr_material_layout")
                         .unwrap();
+                    pbr_material.alpha_to_coverage = alpha_to_coverage;
                     current_bind_group = Some(pbr_material.create_bind_group(
                         &self.images,
                         device,
@@ -0,0 +1,58 @@
+use crossbeam::channel::{self, Receiver};
+use log::error;
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Watches the asset folder for files that were modified on disk.
+/// The OS watcher lives on it's own thread, it isn't `Send` on every platform.
+pub(crate) struct FileWatcher {
+    receiver: Receiver<PathBuf>,
+}
+
+impl FileWatcher {
+    /// Editors tend to write a file in a few steps, events are held back until it's settled.
+    const DEBOUNCE: Duration = Duration::from_millis(100);
+
+    pub fn new(path: String) -> Self {
+        let (sender, receiver) = channel::unbounded();
+        std::thread::Builder::new()
+            .name("file watcher".to_string())
+            .spawn(move || {
+                let (notify_sender, notify_receiver) = std::sync::mpsc::channel();
+                let mut watcher = match notify::watcher(notify_sender, Self::DEBOUNCE) {
+                    Ok(watcher) => watcher,
+                    Err(error) => {
+                        error!("Could not start the file watcher: {}", error);
+                        return;
+                    }
+                };
+                if let Err(error) = watcher.watch(&path, RecursiveMode::Recursive) {
+                    error!("Could not watch {}: {}", path, error);
+                    return;
+                }
+                for event in notify_receiver {
+                    let changed = match event {
+                        DebouncedEvent::Create(path) | DebouncedEvent::Write(path) => path,
+                        DebouncedEvent::Rename(_, path) => path,
+                        _ => continue,
+                    };
+                    // Stops once the asset manager is gone.
+                    if sender.send(changed).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("Asset Error: Could not spawn the file watcher thread!");
+
+        Self { receiver }
+    }
+
+    /// Returns the files that changed since the last check.
+    pub fn changed_files(&mut self) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = self.receiver.try_iter().collect();
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+}
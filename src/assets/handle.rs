@@ -0,0 +1,53 @@
+use std::marker::PhantomData;
+
+/// The state of an asset that was requested through one of the async `load_*` functions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoadState {
+    /// The asset is still being decoded or hasn't been uploaded to the GPU yet.
+    Loading,
+    /// The asset is ready to use.
+    Loaded,
+    /// Something went wrong while loading, check the log for more info.
+    Failed,
+}
+
+/// A typed reference to an asset which might still be loading.
+/// Use `AssetManager::get_load_state` to check on it.
+pub struct Handle<T> {
+    name: String,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    pub(crate) fn new(name: String) -> Self {
+        Self {
+            name,
+            marker: PhantomData,
+        }
+    }
+
+    /// The name the asset is stored under in the asset manager.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.name.clone())
+    }
+}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Handle").field(&self.name).finish()
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl<T> Eq for Handle<T> {}
@@ -0,0 +1,194 @@
+use log::*;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use super::AssetError;
+use crate::graphics::material::{image::DecodedImage, Image, ImageInfo};
+
+const MAGIC: &[u8; 4] = b"HIMG";
+const VERSION: u32 = 1;
+
+/// A folder of images that already went through their import operations (resize, flip, etc),
+/// so loading them again skips the processing. Entries are keyed by a hash of the source file
+/// and it's `ImageInfo`, changing either one imports the image again.
+/// Only images with import operations are cached, others are decoded like usual.
+#[derive(Debug, Clone)]
+pub(crate) struct ImportCache {
+    folder: PathBuf,
+}
+
+impl ImportCache {
+    pub fn new<T: Into<PathBuf>>(folder: T) -> Self {
+        Self {
+            folder: folder.into(),
+        }
+    }
+
+    /// Decodes an image, reading it from the cache when it was imported before and writing it
+    /// to the cache when it wasn't. Cache files that can't be read or written are skipped.
+    pub fn decode(
+        &self,
+        path: &str,
+        bytes: &[u8],
+        info: &ImageInfo,
+    ) -> Result<DecodedImage, AssetError> {
+        if !info.has_operations() {
+            return Image::decode_bytes(path, bytes, info);
+        }
+
+        let file = self
+            .folder
+            .join(format!("{:016x}.himg", info.import_hash(path, bytes)));
+        if file.exists() {
+            match read_image(&file) {
+                Ok(mut decoded) => {
+                    decoded.keep_cpu_data = info.keep_cpu_data;
+                    return Ok(decoded);
+                }
+                Err(error) => warn!("Re-importing {}, cached import failed: {}", path, error),
+            }
+        }
+
+        let decoded = Image::decode_bytes(path, bytes, info)?;
+        if let Err(error) = write_image(&file, &decoded) {
+            warn!("Couldn't cache the import of {}: {}", path, error);
+        }
+        Ok(decoded)
+    }
+}
+
+fn format_id(format: wgpu::TextureFormat) -> Option<u8> {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm => Some(0),
+        wgpu::TextureFormat::Rgba8UnormSrgb => Some(1),
+        wgpu::TextureFormat::Rgba32Float => Some(2),
+        _ => None,
+    }
+}
+
+fn id_format(id: u8) -> Option<wgpu::TextureFormat> {
+    match id {
+        0 => Some(wgpu::TextureFormat::Rgba8Unorm),
+        1 => Some(wgpu::TextureFormat::Rgba8UnormSrgb),
+        2 => Some(wgpu::TextureFormat::Rgba32Float),
+        _ => None,
+    }
+}
+
+// Written to a temporary file first so a loading thread never reads half an entry.
+fn write_image(file: &Path, decoded: &DecodedImage) -> io::Result<()> {
+    let format = match format_id(decoded.format) {
+        Some(format) => format,
+        None => return Ok(()),
+    };
+    fs::create_dir_all(file.parent().unwrap())?;
+    let temp = file.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&temp)?);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&[format])?;
+    writer.write_all(&decoded.extent.width.to_le_bytes())?;
+    writer.write_all(&decoded.extent.height.to_le_bytes())?;
+    writer.write_all(&(decoded.levels.len() as u32).to_le_bytes())?;
+    for level in decoded.levels.iter() {
+        writer.write_all(&(level.len() as u64).to_le_bytes())?;
+        writer.write_all(level)?;
+    }
+    writer.flush()?;
+    drop(writer);
+    fs::rename(&temp, file)
+}
+
+fn read_image(file: &Path) -> io::Result<DecodedImage> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut reader = BufReader::new(File::open(file)?);
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a cached image"));
+    }
+    if read_u32(&mut reader)? != VERSION {
+        return Err(invalid("unknown cached image version"));
+    }
+    let mut format = [0; 1];
+    reader.read_exact(&mut format)?;
+    let format = id_format(format[0]).ok_or_else(|| invalid("unknown texture format"))?;
+    let width = read_u32(&mut reader)?;
+    let height = read_u32(&mut reader)?;
+    let level_count = read_u32(&mut reader)?;
+    let mut levels = Vec::with_capacity(level_count as usize);
+    for _ in 0..level_count {
+        let size = read_u64(&mut reader)?;
+        let mut level = Vec::with_capacity(size as usize);
+        (&mut reader).take(size).read_to_end(&mut level)?;
+        if level.len() as u64 != size {
+            return Err(invalid("truncated cached image"));
+        }
+        levels.push(level);
+    }
+
+    Ok(DecodedImage {
+        levels,
+        extent: wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        },
+        format,
+        keep_cpu_data: false,
+    })
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::material::Channel;
+    use image::{ImageBuffer, ImageOutputFormat, Rgba};
+
+    #[test]
+    fn test_import_cache() {
+        let folder = std::env::temp_dir().join(format!("harmony_import_{}", std::process::id()));
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgba8(ImageBuffer::from_pixel(4, 2, Rgba([10, 20, 30, 255])))
+            .write_to(&mut bytes, ImageOutputFormat::Png)
+            .unwrap();
+        let info = ImageInfo {
+            flip_vertical: true,
+            swizzle: Some([Channel::B, Channel::G, Channel::R, Channel::A]),
+            ..ImageInfo::default()
+        };
+
+        let cache = ImportCache::new(&folder);
+        let imported = cache.decode("wall.png", &bytes, &info).unwrap();
+        assert_eq!(&imported.levels[0][0..4], &[30, 20, 10, 255]);
+        assert_eq!(fs::read_dir(&folder).unwrap().count(), 1);
+
+        let cached = cache.decode("wall.png", &bytes, &info).unwrap();
+        assert_eq!(cached.levels, imported.levels);
+        assert_eq!(cached.format, imported.format);
+        assert_eq!(cached.extent.width, 4);
+
+        // Different import settings are a different entry.
+        let info = ImageInfo {
+            max_size: Some(2),
+            ..info
+        };
+        cache.decode("wall.png", &bytes, &info).unwrap();
+        assert_eq!(fs::read_dir(&folder).unwrap().count(), 2);
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,249 @@
+use std::{collections::HashMap, ops::Range};
+
+type MigrateFn = Box<dyn Fn(&str) -> Result<String, String> + Send + Sync>;
+
+/// The version `SceneDesc` files are written with.
+pub const SCENE_VERSION: u32 = 1;
+/// The version `Timeline` files are written with.
+pub const TIMELINE_VERSION: u32 = 1;
+
+/// Upgrades RON files written by older versions of a format so they keep loading, the asset
+/// manager has one. Files keep a `version` field in their outermost parentheses, files without
+/// one are version 1. When a format changes it's version goes up and a migration from the old
+/// version is registered, loading runs every migration from the file's version up in order.
+/// The engine's formats are "scene" and "timeline", your own settings or save files can be
+/// versioned the same way by adding a format.
+/// ```ignore
+/// let migrations = asset_manager.migrations_mut();
+/// migrations.add_format("settings", 2);
+/// migrations.register("settings", 1, |source| Ok(source.replace("vsync:", "present_mode:")));
+/// let settings: Settings = ron::de::from_str(&migrations.migrate("settings", &source)?)?;
+/// ```
+pub struct Migrations {
+    formats: HashMap<String, u32>,
+    steps: HashMap<(String, u32), MigrateFn>,
+}
+
+impl Default for Migrations {
+    fn default() -> Self {
+        let mut migrations = Self {
+            formats: HashMap::new(),
+            steps: HashMap::new(),
+        };
+        migrations.add_format("scene", SCENE_VERSION);
+        migrations.add_format("timeline", TIMELINE_VERSION);
+        migrations
+    }
+}
+
+impl Migrations {
+    /// Adds a format or changes it's current version.
+    pub fn add_format(&mut self, format: &str, current_version: u32) {
+        self.formats.insert(format.to_string(), current_version);
+    }
+
+    pub fn current_version(&self, format: &str) -> Option<u32> {
+        self.formats.get(format).copied()
+    }
+
+    /// Registers the upgrade of a `format` file from `from_version` to the version after it.
+    /// It gets the whole file and returns the upgraded one, the version field is updated
+    /// afterwards.
+    pub fn register<F>(&mut self, format: &str, from_version: u32, migrate: F)
+    where
+        F: Fn(&str) -> Result<String, String> + Send + Sync + 'static,
+    {
+        self.steps
+            .insert((format.to_string(), from_version), Box::new(migrate));
+    }
+
+    /// Brings `source` up to the format's current version. Fails if the file is newer than
+    /// this engine or a migration is missing.
+    pub fn migrate(&self, format: &str, source: &str) -> Result<String, String> {
+        let current = self
+            .current_version(format)
+            .ok_or_else(|| format!("{} isn't a known format.", format))?;
+        let version = find_version(source).map_or(1, |(version, _)| version);
+        if version > current {
+            return Err(format!(
+                "The file is {} version {}, this engine only reads up to version {}.",
+                format, version, current
+            ));
+        }
+        let mut source = source.to_string();
+        for from in version..current {
+            let step = self
+                .steps
+                .get(&(format.to_string(), from))
+                .ok_or_else(|| format!("There's no {} migration from version {}.", format, from))?;
+            source = set_version(&step(&source)?, from + 1);
+        }
+        Ok(source)
+    }
+}
+
+fn is_identifier(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+// Where the string, char literal or comment starting at `i` ends, None if there isn't one.
+// Block comments nest like they do in Rust.
+fn skip_ignored(bytes: &[u8], i: usize) -> Option<usize> {
+    let mut end = i + 1;
+    match bytes[i] {
+        quote @ b'"' | quote @ b'\'' => {
+            while end < bytes.len() && bytes[end] != quote {
+                end += if bytes[end] == b'\\' { 2 } else { 1 };
+            }
+            Some(end + 1)
+        }
+        b'/' if bytes.get(i + 1) == Some(&b'/') => {
+            while end < bytes.len() && bytes[end] != b'\n' {
+                end += 1;
+            }
+            Some(end)
+        }
+        b'/' if bytes.get(i + 1) == Some(&b'*') => {
+            let mut depth = 1;
+            end += 1;
+            while end < bytes.len() && depth > 0 {
+                if bytes[end..].starts_with(b"/*") {
+                    depth += 1;
+                    end += 2;
+                } else if bytes[end..].starts_with(b"*/") {
+                    depth -= 1;
+                    end += 2;
+                } else {
+                    end += 1;
+                }
+            }
+            Some(end)
+        }
+        _ => None,
+    }
+}
+
+// The `version: N` field in the outermost parentheses and where the number is.
+fn find_version(source: &str) -> Option<(u32, Range<usize>)> {
+    let bytes = source.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if let Some(end) = skip_ignored(bytes, i) {
+            i = end;
+            continue;
+        }
+        match bytes[i] {
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => depth -= 1,
+            _ if depth == 1
+                && bytes[i..].starts_with(b"version")
+                && (i == 0 || !is_identifier(bytes[i - 1])) =>
+            {
+                let after_name = i + "version".len();
+                let rest = &source[after_name..];
+                let colon = after_name + rest.len() - rest.trim_start().len();
+                if bytes.get(colon) == Some(&b':') {
+                    let value = &source[colon + 1..];
+                    let start = colon + 1 + value.len() - value.trim_start().len();
+                    let end = start
+                        + source[start..]
+                            .bytes()
+                            .take_while(|byte| byte.is_ascii_digit())
+                            .count();
+                    return source[start..end]
+                        .parse()
+                        .ok()
+                        .map(|version| (version, start..end));
+                }
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+    None
+}
+
+// The first parenthesis outside of comments and strings.
+fn find_open(source: &str) -> Option<usize> {
+    let bytes = source.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match skip_ignored(bytes, i) {
+            Some(end) => i = end,
+            None if bytes[i] == b'(' => return Some(i),
+            None => i += 1,
+        }
+    }
+    None
+}
+
+// Replaces the version number, or adds the field right after the first parenthesis.
+fn set_version(source: &str, version: u32) -> String {
+    match find_version(source) {
+        Some((_, range)) => format!(
+            "{}{}{}",
+            &source[..range.start],
+            version,
+            &source[range.end..]
+        ),
+        None => match find_open(source) {
+            Some(open) => format!(
+                "{}version: {},{}",
+                &source[..open + 1],
+                version,
+                &source[open + 1..]
+            ),
+            None => source.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_version() {
+        assert_eq!(find_version("(version: 3, a: 1)").map(|(v, _)| v), Some(3));
+        assert_eq!(find_version("(a: (version: 3), b: \"version: 4\")"), None);
+        assert_eq!(
+            find_version("// version: 2\n(versions: 5, version:7)")
+                .unwrap()
+                .0,
+            7
+        );
+        assert_eq!(set_version("(version: 3, a: 1)", 10), "(version: 10, a: 1)");
+        assert_eq!(set_version("(a: 1)", 2), "(version: 2,a: 1)");
+    }
+
+    #[test]
+    fn test_find_version_skips_comments() {
+        let source = "/* é */ (a: 'é', c: '\\'', version: 4)";
+        assert_eq!(find_version(source).map(|(v, _)| v), Some(4));
+        assert_eq!(find_version("(/* version: 9 */ a: 1)"), None);
+        assert_eq!(
+            find_version("(/* /* */ version: 9 */ version: 2)")
+                .unwrap()
+                .0,
+            2
+        );
+        assert_eq!(
+            set_version("/* ( */ (a: 1)", 2),
+            "/* ( */ (version: 2,a: 1)"
+        );
+    }
+
+    #[test]
+    fn test_migrate() {
+        let mut migrations = Migrations::default();
+        migrations.add_format("settings", 3);
+        migrations.register("settings", 1, |source| Ok(source.replace("old", "middle")));
+        migrations.register("settings", 2, |source| Ok(source.replace("middle", "new")));
+        let migrated = migrations.migrate("settings", "(old: true)").unwrap();
+        assert_eq!(migrated, "(version: 3,new: true)");
+        assert_eq!(migrations.migrate("settings", &migrated).unwrap(), migrated);
+        assert!(migrations.migrate("settings", "(version: 4)").is_err());
+        assert!(migrations.migrate("unknown", "()").is_err());
+    }
+}
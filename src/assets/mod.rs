@@ -1,2 +1,30 @@
 mod asset_manager;
 pub use asset_manager::AssetManager;
+
+mod asset_ids;
+pub use asset_ids::ASSET_IDS_FILE;
+
+mod asset_error;
+pub use asset_error::AssetError;
+
+mod file_watcher;
+
+mod handle;
+pub use handle::{Handle, LoadState};
+
+mod import_cache;
+
+mod migration;
+pub use migration::{Migrations, SCENE_VERSION, TIMELINE_VERSION};
+
+mod pack;
+pub use pack::{build_pack, AssetPack};
+
+mod upload_budget;
+pub use upload_budget::UploadBudget;
+
+mod vfs;
+pub use vfs::VirtualFileSystem;
+
+mod validation;
+pub use validation::{AssetStats, Severity, ValidationIssue, ValidationReport, ValidationRules};
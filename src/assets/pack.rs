@@ -0,0 +1,227 @@
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use super::{vfs::directory_files, AssetError};
+
+const MAGIC: &[u8; 4] = b"HPAK";
+const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+struct PackEntry {
+    offset: u64,
+    size: u64,
+    // Bytes in the pack, smaller than `size` if it's compressed.
+    stored_size: u64,
+    compressed: bool,
+}
+
+/// A read only archive of asset files made by `build_pack`, mount it with
+/// `AssetManager::mount_pack` to ship a game as one file.
+/// The layout is a small header, the file contents one after the other and an index at the
+/// end listing every file's path, offset and size. Files are deflated when that makes them
+/// smaller, images that are already compressed are usually stored as they are.
+/// Only the index is kept in memory, files are read from disk as they're loaded.
+pub struct AssetPack {
+    path: PathBuf,
+    entries: BTreeMap<String, PackEntry>,
+}
+
+impl AssetPack {
+    pub fn open<T: AsRef<Path>>(path: T) -> Result<Self, AssetError> {
+        let path = path.as_ref().to_path_buf();
+        let path_name = path.to_string_lossy().to_string();
+        let mut file = File::open(&path).map_err(|error| AssetError::io(&path_name, error))?;
+        let entries = read_index(&mut file).map_err(|error| match error.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+                AssetError::decode(&path_name, error)
+            }
+            _ => AssetError::io(&path_name, error),
+        })?;
+        Ok(Self { path, entries })
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    /// The paths of the packed files, sorted.
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, AssetError> {
+        let entry = self
+            .entries
+            .get(path)
+            .ok_or_else(|| AssetError::missing("packed file", path))?;
+        let read = || -> io::Result<Vec<u8>> {
+            let mut file = File::open(&self.path)?;
+            file.seek(SeekFrom::Start(entry.offset))?;
+            let mut stored = file.take(entry.stored_size);
+            let mut data = Vec::with_capacity(entry.size as usize);
+            if entry.compressed {
+                DeflateDecoder::new(&mut stored).read_to_end(&mut data)?;
+            } else {
+                stored.read_to_end(&mut data)?;
+            }
+            if data.len() as u64 != entry.size {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "truncated file"));
+            }
+            Ok(data)
+        };
+        read().map_err(|error| AssetError::io(path, error))
+    }
+}
+
+/// Packs every file in `source` into a single archive at `output`, paths inside the pack are
+/// relative to `source`. Returns how many files were packed.
+pub fn build_pack<S, O>(source: S, output: O, compress: bool) -> Result<usize, AssetError>
+where
+    S: AsRef<Path>,
+    O: AsRef<Path>,
+{
+    let source = source.as_ref();
+    let output_name = output.as_ref().to_string_lossy().to_string();
+    let mut file =
+        File::create(output.as_ref()).map_err(|error| AssetError::io(&output_name, error))?;
+    let io_error = |error: io::Error| AssetError::io(&output_name, error);
+
+    // The index offset is filled in once the files are written.
+    file.write_all(MAGIC).map_err(io_error)?;
+    file.write_all(&VERSION.to_le_bytes()).map_err(io_error)?;
+    file.write_all(&0u64.to_le_bytes()).map_err(io_error)?;
+
+    let mut files = directory_files(source);
+    // Packing into the source folder shouldn't pack the pack.
+    files.retain(|path| source.join(path) != output.as_ref());
+    files.sort();
+    let mut entries = BTreeMap::new();
+    let mut offset = (MAGIC.len() + 4 + 8) as u64;
+    for path in files {
+        let full_path = source.join(&path);
+        let data = std::fs::read(&full_path)
+            .map_err(|error| AssetError::io(full_path.to_string_lossy(), error))?;
+        let deflated = if compress {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&data).map_err(io_error)?;
+            let deflated = encoder.finish().map_err(io_error)?;
+            Some(deflated).filter(|deflated| deflated.len() < data.len())
+        } else {
+            None
+        };
+        let stored = deflated.as_ref().unwrap_or(&data);
+        file.write_all(stored).map_err(io_error)?;
+        entries.insert(
+            path,
+            PackEntry {
+                offset,
+                size: data.len() as u64,
+                stored_size: stored.len() as u64,
+                compressed: deflated.is_some(),
+            },
+        );
+        offset += stored.len() as u64;
+    }
+
+    write_index(&mut file, &entries).map_err(io_error)?;
+    file.seek(SeekFrom::Start((MAGIC.len() + 4) as u64))
+        .and_then(|_| file.write_all(&offset.to_le_bytes()))
+        .map_err(io_error)?;
+    Ok(entries.len())
+}
+
+fn write_index(file: &mut File, entries: &BTreeMap<String, PackEntry>) -> io::Result<()> {
+    file.write_all(&(entries.len() as u32).to_le_bytes())?;
+    for (path, entry) in entries.iter() {
+        file.write_all(&(path.len() as u32).to_le_bytes())?;
+        file.write_all(path.as_bytes())?;
+        file.write_all(&entry.offset.to_le_bytes())?;
+        file.write_all(&entry.size.to_le_bytes())?;
+        file.write_all(&entry.stored_size.to_le_bytes())?;
+        file.write_all(&[entry.compressed as u8])?;
+    }
+    Ok(())
+}
+
+fn read_index(file: &mut File) -> io::Result<BTreeMap<String, PackEntry>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not an asset pack"));
+    }
+    if read_u32(file)? != VERSION {
+        return Err(invalid("unknown asset pack version"));
+    }
+    let index_offset = read_u64(file)?;
+    file.seek(SeekFrom::Start(index_offset))?;
+
+    let count = read_u32(file)?;
+    let mut entries = BTreeMap::new();
+    for _ in 0..count {
+        let mut path = vec![0; read_u32(file)? as usize];
+        file.read_exact(&mut path)?;
+        let path = String::from_utf8(path).map_err(|_| invalid("path isn't utf-8"))?;
+        let offset = read_u64(file)?;
+        let size = read_u64(file)?;
+        let stored_size = read_u64(file)?;
+        let mut compressed = [0; 1];
+        file.read_exact(&mut compressed)?;
+        entries.insert(
+            path,
+            PackEntry {
+                offset,
+                size,
+                stored_size,
+                compressed: compressed[0] != 0,
+            },
+        );
+    }
+    Ok(entries)
+}
+
+fn read_u32(file: &mut File) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    file.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(file: &mut File) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    file.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_roundtrip() {
+        let root = std::env::temp_dir().join(format!("harmony_pack_{}", std::process::id()));
+        let source = root.join("assets");
+        std::fs::create_dir_all(source.join("shaders")).unwrap();
+        let text = "layout(location = 0) out vec4 color;\n".repeat(64);
+        std::fs::write(source.join("shaders").join("sky.glsl"), &text).unwrap();
+        std::fs::write(source.join("noise.bin"), [7u8, 1, 200]).unwrap();
+
+        let pack_path = root.join("assets.hpak");
+        assert_eq!(build_pack(&source, &pack_path, true).unwrap(), 2);
+        let pack = AssetPack::open(&pack_path).unwrap();
+        assert_eq!(
+            pack.files().collect::<Vec<_>>(),
+            ["noise.bin", "shaders/sky.glsl"]
+        );
+        // Repeated text deflates, three bytes don't.
+        assert!(pack.entries["shaders/sky.glsl"].compressed);
+        assert!(!pack.entries["noise.bin"].compressed);
+        assert_eq!(pack.read("shaders/sky.glsl").unwrap(), text.as_bytes());
+        assert_eq!(pack.read("noise.bin").unwrap(), [7, 1, 200]);
+        assert!(pack.read("missing.png").is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+use std::time::{Duration, Instant};
+
+/// Limits how much finished loads the asset manager uploads to the GPU each frame, so a big
+/// level load is spread over a few frames instead of stalling one. At least one asset is
+/// uploaded every frame no matter how big it is. There's no limit by default.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct UploadBudget {
+    /// Bytes of image and mesh data per frame.
+    pub bytes_per_frame: Option<usize>,
+    /// Time spent creating textures and buffers per frame.
+    pub time_per_frame: Option<Duration>,
+}
+
+impl UploadBudget {
+    pub fn new(bytes_per_frame: usize, time_per_frame: Duration) -> Self {
+        Self {
+            bytes_per_frame: Some(bytes_per_frame),
+            time_per_frame: Some(time_per_frame),
+        }
+    }
+
+    pub fn with_bytes_per_frame(mut self, bytes_per_frame: usize) -> Self {
+        self.bytes_per_frame = Some(bytes_per_frame);
+        self
+    }
+
+    pub fn with_time_per_frame(mut self, time_per_frame: Duration) -> Self {
+        self.time_per_frame = Some(time_per_frame);
+        self
+    }
+
+    pub(crate) fn start(&self) -> UploadTracker {
+        UploadTracker {
+            budget: *self,
+            started: Instant::now(),
+            bytes: 0,
+            uploads: 0,
+        }
+    }
+}
+
+/// What a frame has uploaded so far.
+pub(crate) struct UploadTracker {
+    budget: UploadBudget,
+    started: Instant,
+    bytes: usize,
+    uploads: usize,
+}
+
+impl UploadTracker {
+    /// True if another upload fits in this frame.
+    pub fn has_room(&self) -> bool {
+        if self.uploads == 0 {
+            return true;
+        }
+        let bytes_left = self
+            .budget
+            .bytes_per_frame
+            .map_or(true, |bytes| self.bytes < bytes);
+        let time_left = self
+            .budget
+            .time_per_frame
+            .map_or(true, |time| self.started.elapsed() < time);
+        bytes_left && time_left
+    }
+
+    pub fn record(&mut self, bytes: usize) {
+        self.bytes += bytes;
+        self.uploads += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upload_budget() {
+        let mut tracker = UploadBudget::default().start();
+        tracker.record(1 << 30);
+        assert!(tracker.has_room());
+
+        let mut tracker = UploadBudget::default().with_bytes_per_frame(100).start();
+        assert!(tracker.has_room());
+        // The first upload always goes through, even if it's bigger than the budget.
+        tracker.record(150);
+        assert!(!tracker.has_room());
+
+        let mut tracker = UploadBudget::default()
+            .with_time_per_frame(Duration::from_secs(0))
+            .start();
+        assert!(tracker.has_room());
+        tracker.record(1);
+        assert!(!tracker.has_room());
+    }
+}
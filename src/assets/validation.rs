@@ -0,0 +1,239 @@
+use std::fmt;
+
+use crate::graphics::GpuCapabilities;
+
+/// What `AssetManager::validate` checks for.
+#[derive(Debug, Clone)]
+pub struct ValidationRules {
+    /// Textures with a side larger than this are reported.
+    pub max_texture_size: u32,
+    /// Report textures that aren't a power of two on both sides, they can't be streamed by
+    /// dropping mip levels.
+    pub require_power_of_two: bool,
+}
+
+impl ValidationRules {
+    /// The default rules with the texture size limit of the GPU that's in use.
+    pub fn for_capabilities(capabilities: &GpuCapabilities) -> Self {
+        Self {
+            max_texture_size: capabilities.max_texture_size,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for ValidationRules {
+    fn default() -> Self {
+        Self {
+            max_texture_size: 4096,
+            require_power_of_two: true,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Works fine but costs something at load or run time.
+    Info,
+    Warning,
+    /// Something will render wrong.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationIssue {
+    NonPowerOfTwoTexture {
+        image: String,
+        width: u32,
+        height: u32,
+    },
+    OversizedTexture {
+        image: String,
+        width: u32,
+        height: u32,
+    },
+    /// The sub mesh has no texture coordinates, textures show up as a single texel.
+    MissingUvs { mesh: String, sub_mesh: usize },
+    /// The file had no tangents so they were generated on load.
+    GeneratedTangents { mesh: String, sub_mesh: usize },
+    /// The material uses an image that isn't loaded, a fallback texture is used instead.
+    MissingImage { material: u32, image: String },
+}
+
+impl ValidationIssue {
+    pub fn severity(&self) -> Severity {
+        match self {
+            ValidationIssue::NonPowerOfTwoTexture { .. } => Severity::Warning,
+            ValidationIssue::OversizedTexture { .. } => Severity::Warning,
+            ValidationIssue::MissingUvs { .. } => Severity::Warning,
+            ValidationIssue::GeneratedTangents { .. } => Severity::Info,
+            ValidationIssue::MissingImage { .. } => Severity::Error,
+        }
+    }
+
+    /// The name of the image or mesh, or the index of the material, the issue is about.
+    pub fn asset(&self) -> String {
+        match self {
+            ValidationIssue::NonPowerOfTwoTexture { image, .. }
+            | ValidationIssue::OversizedTexture { image, .. } => image.clone(),
+            ValidationIssue::MissingUvs { mesh, .. }
+            | ValidationIssue::GeneratedTangents { mesh, .. } => mesh.clone(),
+            ValidationIssue::MissingImage { material, .. } => format!("material {}", material),
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::NonPowerOfTwoTexture { width, height, .. } => {
+                write!(f, "{}x{} isn't a power of two", width, height)
+            }
+            ValidationIssue::OversizedTexture { width, height, .. } => {
+                write!(f, "{}x{} is larger than the limit", width, height)
+            }
+            ValidationIssue::MissingUvs { sub_mesh, .. } => {
+                write!(f, "sub mesh {} has no texture coordinates", sub_mesh)
+            }
+            ValidationIssue::GeneratedTangents { sub_mesh, .. } => {
+                write!(
+                    f,
+                    "sub mesh {} had it's tangents generated on load",
+                    sub_mesh
+                )
+            }
+            ValidationIssue::MissingImage { image, .. } => {
+                write!(f, "uses {} which isn't loaded", image)
+            }
+        }
+    }
+}
+
+/// Totals for everything the asset manager has loaded.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AssetStats {
+    pub images: usize,
+    /// Bytes of GPU memory used by textures, shared textures are counted once.
+    pub texture_memory: usize,
+    pub meshes: usize,
+    pub sub_meshes: usize,
+    pub vertices: usize,
+    pub triangles: usize,
+    pub materials: usize,
+}
+
+/// The result of `AssetManager::validate`, every problem found in one place.
+/// Print it to get a summary followed by one line per issue, worst first.
+#[derive(Debug, Default, Clone)]
+pub struct ValidationReport {
+    pub stats: AssetStats,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity() == severity)
+            .count()
+    }
+
+    /// Issues at or above `severity`.
+    pub fn issues_at_least(&self, severity: Severity) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues
+            .iter()
+            .filter(move |issue| issue.severity() >= severity)
+    }
+
+    // Worst first, then by asset so the same asset's issues are together.
+    pub(crate) fn sort(&mut self) {
+        self.issues.sort_by(|a, b| {
+            b.severity()
+                .cmp(&a.severity())
+                .then_with(|| a.asset().cmp(&b.asset()))
+        });
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stats = &self.stats;
+        writeln!(
+            f,
+            "{} images ({:.1} MB), {} meshes ({} sub meshes, {} vertices, {} triangles), \
+             {} materials",
+            stats.images,
+            stats.texture_memory as f32 / (1024.0 * 1024.0),
+            stats.meshes,
+            stats.sub_meshes,
+            stats.vertices,
+            stats.triangles,
+            stats.materials,
+        )?;
+        writeln!(
+            f,
+            "{} errors, {} warnings, {} info",
+            self.count(Severity::Error),
+            self.count(Severity::Warning),
+            self.count(Severity::Info),
+        )?;
+        for issue in self.issues.iter() {
+            writeln!(f, "  {:?} {}: {}", issue.severity(), issue.asset(), issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// The texture issues for an image of this size.
+pub(crate) fn check_texture(
+    image: &str,
+    width: u32,
+    height: u32,
+    rules: &ValidationRules,
+) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    if rules.require_power_of_two && !(width.is_power_of_two() && height.is_power_of_two()) {
+        issues.push(ValidationIssue::NonPowerOfTwoTexture {
+            image: image.to_string(),
+            width,
+            height,
+        });
+    }
+    if width.max(height) > rules.max_texture_size {
+        issues.push(ValidationIssue::OversizedTexture {
+            image: image.to_string(),
+            width,
+            height,
+        });
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation_report() {
+        let rules = ValidationRules::default();
+        assert!(check_texture("brick.png", 1024, 512, &rules).is_empty());
+        let issues = check_texture("sky.hdr", 8192, 3000, &rules);
+        assert_eq!(issues.len(), 2);
+
+        let mut report = ValidationReport::default();
+        report.issues.extend(issues);
+        report.issues.push(ValidationIssue::MissingImage {
+            material: 3,
+            image: "rust.png".to_string(),
+        });
+        report.sort();
+        assert_eq!(report.issues[0].severity(), Severity::Error);
+        assert_eq!(report.count(Severity::Warning), 2);
+        assert_eq!(report.issues_at_least(Severity::Error).count(), 1);
+        assert!(report.to_string().contains("1 errors, 2 warnings, 0 info"));
+    }
+}
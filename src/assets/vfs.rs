@@ -0,0 +1,132 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use walkdir::WalkDir;
+
+use super::{AssetError, AssetPack};
+
+enum Mount {
+    Directory(PathBuf),
+    Pack(AssetPack),
+}
+
+/// Where the asset manager reads it's files from, a stack of folders and asset packs.
+/// Paths are relative to the mount and always use `/`, like `core/shaders/pbr.shader`.
+/// Later mounts win, so mounting a pack over the asset folder lets it override single files.
+/// It's cheap to clone so loading threads can take their own copy.
+#[derive(Clone, Default)]
+pub struct VirtualFileSystem {
+    mounts: Vec<Arc<Mount>>,
+}
+
+impl VirtualFileSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mount_directory<T: Into<PathBuf>>(&mut self, path: T) {
+        self.mounts.push(Arc::new(Mount::Directory(path.into())));
+    }
+
+    /// Opens a pack made with `build_pack` and mounts it.
+    pub fn mount_pack<T: AsRef<Path>>(&mut self, path: T) -> Result<(), AssetError> {
+        let pack = AssetPack::open(path)?;
+        self.mounts.push(Arc::new(Mount::Pack(pack)));
+        Ok(())
+    }
+
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, AssetError> {
+        let path = normalize(path);
+        for mount in self.mounts.iter().rev() {
+            match mount.as_ref() {
+                Mount::Directory(root) => {
+                    let full_path = root.join(&path);
+                    if full_path.is_file() {
+                        return fs::read(&full_path).map_err(|error| AssetError::io(path, error));
+                    }
+                }
+                Mount::Pack(pack) => {
+                    if pack.contains(&path) {
+                        return pack.read(&path);
+                    }
+                }
+            }
+        }
+        Err(AssetError::io(
+            path,
+            std::io::Error::from(std::io::ErrorKind::NotFound),
+        ))
+    }
+
+    pub fn read_to_string(&self, path: &str) -> Result<String, AssetError> {
+        let bytes = self.read(path)?;
+        String::from_utf8(bytes).map_err(|error| AssetError::decode(path, error))
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        let path = normalize(path);
+        self.mounts.iter().any(|mount| match mount.as_ref() {
+            Mount::Directory(root) => root.join(&path).is_file(),
+            Mount::Pack(pack) => pack.contains(&path),
+        })
+    }
+
+    /// Every file in every mount, sorted and without duplicates.
+    pub fn files(&self) -> Vec<String> {
+        let mut files = Vec::new();
+        for mount in self.mounts.iter() {
+            match mount.as_ref() {
+                Mount::Directory(root) => files.extend(directory_files(root)),
+                Mount::Pack(pack) => files.extend(pack.files().map(str::to_string)),
+            }
+        }
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    /// The file on disk that `read` would use, None if it comes from a pack or doesn't exist.
+    /// Used for things that need a real file like watching for changes.
+    pub fn real_path(&self, path: &str) -> Option<PathBuf> {
+        let path = normalize(path);
+        for mount in self.mounts.iter().rev() {
+            match mount.as_ref() {
+                Mount::Directory(root) => {
+                    let full_path = root.join(&path);
+                    if full_path.is_file() {
+                        return Some(full_path);
+                    }
+                }
+                Mount::Pack(pack) => {
+                    if pack.contains(&path) {
+                        return None;
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The files in a folder relative to it, with `/` separators.
+pub(crate) fn directory_files(root: &Path) -> Vec<String> {
+    WalkDir::new(root)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(root).ok()?;
+            Some(normalize(relative.to_str()?))
+        })
+        .collect()
+}
+
+// Windows separators and leading `./` or `/` would otherwise miss pack entries.
+fn normalize(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut path = path.as_str();
+    while let Some(stripped) = path.strip_prefix("./").or_else(|| path.strip_prefix('/')) {
+        path = stripped;
+    }
+    path.to_string()
+}
@@ -1,20 +1,21 @@
 use std::fs::File;
 use std::io::prelude::*;
 
+use crate::AssetError;
+
 pub struct Font {
     pub data: Vec<u8>,
 }
 
 impl Font {
-    pub fn new(_device: &wgpu::Device, font_path: String) -> Self {
-        let mut file = File::open(&font_path).expect("Font: Unable to open the file");
+    pub fn new(_device: &wgpu::Device, font_path: String) -> Result<Self, AssetError> {
+        let mut file = File::open(&font_path).map_err(|err| AssetError::io(&font_path, err))?;
         let mut font_contents: Vec<u8> = Vec::new();
-        file.read_to_end(&mut font_contents).unwrap_or_else(|err| {
-            panic!("Unable to read the file: {} with error: {}", font_path, err)
-        });
+        file.read_to_end(&mut font_contents)
+            .map_err(|err| AssetError::io(&font_path, err))?;
 
-        Self {
+        Ok(Self {
             data: font_contents,
-        }
+        })
     }
 }
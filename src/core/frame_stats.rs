@@ -0,0 +1,206 @@
+use nalgebra_glm::{Vec2, Vec4};
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use crate::graphics::resources::{TextRenderer, TextSection};
+
+type Timings = Arc<Mutex<Vec<(String, f32)>>>;
+
+/// Time spent in a `ProfileScope` during a frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopeTime {
+    pub name: String,
+    /// Milliseconds, scopes with the same name are added up.
+    pub time: f32,
+    pub calls: u32,
+}
+
+/// Where the last frame's time went, a resource.
+/// Nothing is gathered until it's enabled, after that the engine times it's own update, probe,
+/// UI and render work and the main render systems while they record their passes. Time your
+/// own code with `scope`, it only needs read access:
+/// ```ignore
+/// let _scope = frame_stats.scope("pathfinding");
+/// ```
+/// wgpu doesn't have timestamp queries yet so render passes are timed on the CPU, a GPU bound
+/// frame shows up as time spent in "swap_chain" waiting for the next image.
+pub struct FrameStats {
+    pub enabled: bool,
+    /// Draws the stats in the top left corner of the screen with the text renderer.
+    pub show_overlay: bool,
+    /// Milliseconds the last frame took.
+    pub frame_time: f32,
+    /// Draw calls made for meshes, shadows, particles, sprites and text.
+    pub draw_calls: u32,
+    pub triangles: u32,
+    /// GPU memory used by the asset manager's images in bytes, shared textures are only
+    /// counted once.
+    pub texture_memory: usize,
+    /// The scopes timed last frame, in the order they first finished.
+    pub scopes: Vec<ScopeTime>,
+    timings: Timings,
+    draw_counter: AtomicU32,
+    triangle_counter: AtomicU32,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            show_overlay: false,
+            frame_time: 0.0,
+            draw_calls: 0,
+            triangles: 0,
+            texture_memory: 0,
+            scopes: Vec::new(),
+            timings: Arc::new(Mutex::new(Vec::new())),
+            draw_counter: AtomicU32::new(0),
+            triangle_counter: AtomicU32::new(0),
+        }
+    }
+}
+
+impl FrameStats {
+    /// Times everything until the returned scope is dropped. Does nothing while disabled.
+    pub fn scope<T: Into<String>>(&self, name: T) -> ProfileScope {
+        ProfileScope {
+            name: name.into(),
+            start: Instant::now(),
+            timings: if self.enabled {
+                Some(self.timings.clone())
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Counts a draw call, render systems call this for every draw.
+    pub fn record_draw(&self, triangles: u32) {
+        if self.enabled {
+            self.draw_counter.fetch_add(1, Ordering::Relaxed);
+            self.triangle_counter
+                .fetch_add(triangles, Ordering::Relaxed);
+        }
+    }
+
+    /// The time spent in a scope last frame in milliseconds, 0.0 if it didn't run.
+    pub fn scope_time(&self, name: &str) -> f32 {
+        self.scopes
+            .iter()
+            .find(|scope| scope.name == name)
+            .map_or(0.0, |scope| scope.time)
+    }
+
+    /// Publishes everything gathered since the last call and starts counting again.
+    pub(crate) fn finish_frame(&mut self, frame_time: f32, texture_memory: usize) {
+        self.frame_time = frame_time;
+        self.texture_memory = texture_memory;
+        self.draw_calls = self.draw_counter.swap(0, Ordering::Relaxed);
+        self.triangles = self.triangle_counter.swap(0, Ordering::Relaxed);
+        let timings = std::mem::take(&mut *self.timings.lock().unwrap());
+        self.scopes = collect_scopes(timings);
+    }
+
+    /// Queues the overlay's text, it's drawn with the rest of the text this frame.
+    pub(crate) fn queue_overlay(&self, text_renderer: &mut TextRenderer) {
+        let mut text = format!(
+            "{:.2} ms ({:.0} fps)\nDraw calls: {}\nTriangles: {}\nTextures: {:.1} MB",
+            self.frame_time,
+            1000.0 / self.frame_time.max(0.001),
+            self.draw_calls,
+            self.triangles,
+            self.texture_memory as f32 / (1024.0 * 1024.0),
+        );
+        for scope in self.scopes.iter() {
+            text.push_str(&format!("\n  {}: {:.2} ms", scope.name, scope.time));
+        }
+        text_renderer.queue(&TextSection {
+            text,
+            position: Vec2::new(8.0, 8.0),
+            size: 14.0,
+            color: Vec4::new(1.0, 1.0, 0.4, 1.0),
+            wrap: false,
+            ..TextSection::default()
+        });
+    }
+}
+
+fn collect_scopes(timings: Vec<(String, f32)>) -> Vec<ScopeTime> {
+    let mut scopes: Vec<ScopeTime> = Vec::new();
+    for (name, time) in timings {
+        match scopes.iter_mut().find(|scope| scope.name == name) {
+            Some(scope) => {
+                scope.time += time;
+                scope.calls += 1;
+            }
+            None => scopes.push(ScopeTime {
+                name,
+                time,
+                calls: 1,
+            }),
+        }
+    }
+    scopes
+}
+
+/// Made by `FrameStats::scope`, the time is recorded when it's dropped.
+pub struct ProfileScope {
+    name: String,
+    start: Instant,
+    timings: Option<Timings>,
+}
+
+impl Drop for ProfileScope {
+    fn drop(&mut self) {
+        if let Some(timings) = self.timings.as_ref() {
+            let time = self.start.elapsed().as_secs_f32() * 1000.0;
+            timings
+                .lock()
+                .unwrap()
+                .push((std::mem::take(&mut self.name), time));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_stats() {
+        let mut stats = FrameStats::default();
+        drop(stats.scope("ignored"));
+        stats.record_draw(12);
+        stats.finish_frame(16.0, 0);
+        assert!(stats.scopes.is_empty());
+        assert_eq!(stats.draw_calls, 0);
+
+        stats.enabled = true;
+        drop(stats.scope("update"));
+        drop(stats.scope("render"));
+        drop(stats.scope("update"));
+        stats.record_draw(12);
+        stats.record_draw(2);
+        stats.finish_frame(16.0, 1024);
+        let names: Vec<_> = stats
+            .scopes
+            .iter()
+            .map(|scope| scope.name.as_str())
+            .collect();
+        assert_eq!(names, ["update", "render"]);
+        assert_eq!(stats.scopes[0].calls, 2);
+        assert_eq!(stats.draw_calls, 2);
+        assert_eq!(stats.triangles, 14);
+        assert_eq!(stats.texture_memory, 1024);
+
+        // Counting starts over every frame.
+        stats.finish_frame(16.0, 1024);
+        assert!(stats.scopes.is_empty());
+        assert_eq!(stats.draw_calls, 0);
+    }
+}
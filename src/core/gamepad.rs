@@ -0,0 +1,80 @@
+use gilrs::{Axis, Button, Gilrs};
+use imgui::NavInput;
+
+/// Feeds the first connected gamepad into imgui's navigation. The d-pad and left stick move
+/// focus, south and east activate and cancel and the bumpers switch between windows.
+pub(crate) struct GamepadNav {
+    gilrs: Gilrs,
+}
+
+impl GamepadNav {
+    // Stick values below the deadzone are ignored, they reach full speed at `STICK_MAX`.
+    const STICK_DEADZONE: f32 = 0.3;
+    const STICK_MAX: f32 = 0.9;
+
+    const BUTTONS: [(NavInput, Button); 12] = [
+        (NavInput::Activate, Button::South),
+        (NavInput::Cancel, Button::East),
+        (NavInput::Input, Button::North),
+        (NavInput::Menu, Button::West),
+        (NavInput::DpadLeft, Button::DPadLeft),
+        (NavInput::DpadRight, Button::DPadRight),
+        (NavInput::DpadUp, Button::DPadUp),
+        (NavInput::DpadDown, Button::DPadDown),
+        (NavInput::FocusPrev, Button::LeftTrigger),
+        (NavInput::FocusNext, Button::RightTrigger),
+        (NavInput::TweakSlow, Button::LeftTrigger),
+        (NavInput::TweakFast, Button::RightTrigger),
+    ];
+
+    /// Turns on imgui's gamepad navigation, returns None if the platform has no gamepad support.
+    pub fn new(io: &mut imgui::Io) -> Option<Self> {
+        match Gilrs::new() {
+            Ok(gilrs) => {
+                io.config_flags |= imgui::ConfigFlags::NAV_ENABLE_GAMEPAD;
+                Some(Self { gilrs })
+            }
+            Err(error) => {
+                log::warn!("Gamepads can't navigate the UI: {}", error);
+                None
+            }
+        }
+    }
+
+    /// Sets imgui's nav inputs, call it before starting the imgui frame.
+    pub fn update(&mut self, io: &mut imgui::Io) {
+        // gilrs only updates the gamepad state while handing out events.
+        while self.gilrs.next_event().is_some() {}
+
+        for input in io.nav_inputs.iter_mut() {
+            *input = 0.0;
+        }
+        let gamepad = match self.gilrs.gamepads().next() {
+            Some((_, gamepad)) => gamepad,
+            None => {
+                io.backend_flags.remove(imgui::BackendFlags::HAS_GAMEPAD);
+                return;
+            }
+        };
+        io.backend_flags |= imgui::BackendFlags::HAS_GAMEPAD;
+
+        for (input, button) in Self::BUTTONS.iter() {
+            if gamepad.is_pressed(*button) {
+                io[*input] = 1.0;
+            }
+        }
+        let stick = |value: f32| {
+            ((value - Self::STICK_DEADZONE) / (Self::STICK_MAX - Self::STICK_DEADZONE))
+                .max(0.0)
+                .min(1.0)
+        };
+        let (x, y) = (
+            gamepad.value(Axis::LeftStickX),
+            gamepad.value(Axis::LeftStickY),
+        );
+        io[NavInput::LStickLeft] = stick(-x);
+        io[NavInput::LStickRight] = stick(x);
+        io[NavInput::LStickUp] = stick(y);
+        io[NavInput::LStickDown] = stick(-y);
+    }
+}
@@ -0,0 +1,214 @@
+use std::{collections::VecDeque, fmt, sync::Mutex};
+
+use super::ScopeTime;
+
+/// Something that happened around a slow frame, see `HitchDetector`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiagnosticEvent {
+    /// An image or mesh finished loading and was uploaded to the GPU.
+    AssetUpload(String),
+    /// A shader or one of it's variants was compiled.
+    ShaderCompiled(String),
+    /// A render pipeline was created or rebuilt.
+    PipelineCreated(String),
+    /// Anything your own code records with `HitchDetector::record`.
+    Custom(String),
+}
+
+impl fmt::Display for DiagnosticEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosticEvent::AssetUpload(name) => write!(f, "uploaded {}", name),
+            DiagnosticEvent::ShaderCompiled(name) => write!(f, "compiled shader {}", name),
+            DiagnosticEvent::PipelineCreated(name) => write!(f, "created pipeline {}", name),
+            DiagnosticEvent::Custom(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// A frame that took longer than `HitchDetector::threshold`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hitch {
+    pub frame: u64,
+    /// Milliseconds the frame took.
+    pub frame_time: f32,
+    /// Where the frame's time went, slowest first.
+    pub scopes: Vec<ScopeTime>,
+    /// Events from this frame and the few before it, with the frame they happened in.
+    pub events: Vec<(u64, DiagnosticEvent)>,
+}
+
+/// Records frames that take too long along with where their time went and what happened just
+/// before them, a resource. It helps track down hitches that only show up now and then.
+/// The timings come from `FrameStats` so enabling this turns it on too. Asset uploads, shader
+/// compiles and pipeline creations are recorded by the engine, add your own with `record`.
+/// The first frame is skipped since it includes loading.
+/// ```ignore
+/// let mut hitch_detector = resources.get_mut::<HitchDetector>().unwrap();
+/// hitch_detector.enabled = true;
+/// // Later, when the player reports stutter.
+/// std::fs::write("hitches.txt", hitch_detector.report())?;
+/// ```
+pub struct HitchDetector {
+    pub enabled: bool,
+    /// Frames slower than this many milliseconds are recorded.
+    pub threshold: f32,
+    /// How many hitches are kept, the oldest are dropped first.
+    pub max_hitches: usize,
+    /// How many frames before a hitch it's events reach back.
+    pub event_frames: u64,
+    frame: u64,
+    events: VecDeque<(u64, DiagnosticEvent)>,
+    recorded: Mutex<Vec<DiagnosticEvent>>,
+    hitches: VecDeque<Hitch>,
+}
+
+impl Default for HitchDetector {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: 50.0,
+            max_hitches: 64,
+            event_frames: 30,
+            frame: 0,
+            events: VecDeque::new(),
+            recorded: Mutex::new(Vec::new()),
+            hitches: VecDeque::new(),
+        }
+    }
+}
+
+impl HitchDetector {
+    /// Adds an event to the current frame, it only needs read access. Does nothing while
+    /// disabled.
+    pub fn record(&self, event: DiagnosticEvent) {
+        if self.enabled {
+            self.recorded.lock().unwrap().push(event);
+        }
+    }
+
+    /// The slow frames recorded so far, oldest first.
+    pub fn hitches(&self) -> impl Iterator<Item = &Hitch> {
+        self.hitches.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.hitches.clear();
+    }
+
+    /// Every recorded hitch as text, with it's timings and events.
+    pub fn report(&self) -> String {
+        let mut report = format!(
+            "{} frames over {:.1} ms\n",
+            self.hitches.len(),
+            self.threshold
+        );
+        for hitch in self.hitches.iter() {
+            report.push_str(&format!(
+                "\nFrame {}: {:.2} ms\n",
+                hitch.frame, hitch.frame_time
+            ));
+            for scope in hitch.scopes.iter() {
+                report.push_str(&format!(
+                    "  {}: {:.2} ms ({} calls)\n",
+                    scope.name, scope.time, scope.calls
+                ));
+            }
+            for (frame, event) in hitch.events.iter() {
+                report.push_str(&format!("  frame {}: {}\n", frame, event));
+            }
+        }
+        report
+    }
+
+    /// Ends a frame, it's recorded as a hitch if it took too long.
+    pub(crate) fn finish_frame<I>(&mut self, frame_time: f32, scopes: &[ScopeTime], events: I)
+    where
+        I: IntoIterator<Item = DiagnosticEvent>,
+    {
+        if !self.enabled {
+            self.events.clear();
+            self.recorded.lock().unwrap().clear();
+            return;
+        }
+        self.frame += 1;
+        let frame = self.frame;
+        let recorded = std::mem::take(&mut *self.recorded.lock().unwrap());
+        self.events.extend(
+            events
+                .into_iter()
+                .chain(recorded)
+                .map(|event| (frame, event)),
+        );
+        while self.events.front().map_or(false, |(event_frame, _)| {
+            event_frame + self.event_frames < frame
+        }) {
+            self.events.pop_front();
+        }
+
+        if frame == 1 || frame_time <= self.threshold {
+            return;
+        }
+        let mut scopes = scopes.to_vec();
+        scopes.sort_by(|a, b| {
+            b.time
+                .partial_cmp(&a.time)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        self.hitches.push_back(Hitch {
+            frame,
+            frame_time,
+            scopes,
+            events: self.events.iter().cloned().collect(),
+        });
+        while self.hitches.len() > self.max_hitches {
+            self.hitches.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hitch_detector() {
+        let mut detector = HitchDetector::default();
+        detector.finish_frame(100.0, &[], None);
+        assert_eq!(detector.hitches().count(), 0);
+
+        detector.enabled = true;
+        detector.event_frames = 1;
+        detector.max_hitches = 1;
+        let scope = |name: &str, time| ScopeTime {
+            name: name.to_string(),
+            time,
+            calls: 1,
+        };
+        // The first frame is loading.
+        detector.finish_frame(500.0, &[], None);
+        detector.finish_frame(10.0, &[], Some(DiagnosticEvent::AssetUpload("old".into())));
+        detector.finish_frame(
+            10.0,
+            &[],
+            Some(DiagnosticEvent::AssetUpload("a.png".into())),
+        );
+        detector.record(DiagnosticEvent::Custom("spawned".into()));
+        detector.finish_frame(80.0, &[scope("update", 5.0), scope("render", 70.0)], None);
+
+        let hitch = detector.hitches().next().unwrap();
+        assert_eq!((hitch.frame, hitch.frame_time), (4, 80.0));
+        assert_eq!(hitch.scopes[0].name, "render");
+        assert_eq!(
+            hitch.events,
+            vec![
+                (3, DiagnosticEvent::AssetUpload("a.png".into())),
+                (4, DiagnosticEvent::Custom("spawned".into())),
+            ]
+        );
+        assert!(detector.report().contains("frame 3: uploaded a.png"));
+
+        detector.finish_frame(90.0, &[], None);
+        assert_eq!(detector.hitches().next().unwrap().frame, 5);
+    }
+}
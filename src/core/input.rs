@@ -93,6 +93,11 @@ impl Input {
         self.mouse_buttons_released.contains(&button)
     }
 
+    /// How far the mouse wheel turned this frame in lines, positive y is away from the user.
+    pub fn mouse_wheel(&self) -> Vec2 {
+        self.mouse_wheel_movement
+    }
+
     pub(crate) fn update_events(&mut self, winit_event: &winit::event::Event<'_, ()>) {
         match winit_event {
             winit::event::Event::WindowEvent { event, .. } => match event {
@@ -127,6 +132,15 @@ impl Input {
                 } => {
                     self.mouse_position = Vec2::new(position.x as f32, position.y as f32);
                 }
+                winit::event::WindowEvent::MouseWheel { delta, .. } => {
+                    self.mouse_wheel_movement += match delta {
+                        winit::event::MouseScrollDelta::LineDelta(x, y) => Vec2::new(*x, *y),
+                        // Touchpads scroll in pixels, roughly 20 of them to a line.
+                        winit::event::MouseScrollDelta::PixelDelta(position) => {
+                            Vec2::new(position.x as f32, position.y as f32) / 20.0
+                        }
+                    };
+                }
                 _ => (),
             },
             winit::event::Event::DeviceEvent { event, .. } => match event {
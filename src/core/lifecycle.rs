@@ -0,0 +1,116 @@
+use winit::event::{Event, WindowEvent};
+
+/// Window and application lifecycle events.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AppEvent {
+    FocusGained,
+    FocusLost,
+    /// The window was minimized, rendering pauses until it's restored.
+    Minimized,
+    Restored,
+    /// The OS suspended the app (mostly mobile), rendering pauses until it resumes.
+    Suspended,
+    Resumed,
+    /// Someone asked the application to quit, see `AppEvents::exit`.
+    AppExit,
+}
+
+/// A resource that collects lifecycle events and the current window state.
+/// Events only last for one update, use the `is_*` functions for the current state.
+#[derive(Debug)]
+pub struct AppEvents {
+    events: Vec<AppEvent>,
+    focused: bool,
+    minimized: bool,
+    suspended: bool,
+    exit_requested: bool,
+}
+
+impl Default for AppEvents {
+    fn default() -> Self {
+        Self {
+            events: Vec::new(),
+            focused: true,
+            minimized: false,
+            suspended: false,
+            exit_requested: false,
+        }
+    }
+}
+
+impl AppEvents {
+    /// The events from this update.
+    pub fn iter(&self) -> impl Iterator<Item = &AppEvent> {
+        self.events.iter()
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    /// Returns true when nothing should be rendered.
+    pub fn is_paused(&self) -> bool {
+        self.minimized || self.suspended
+    }
+
+    /// Quits the application at the end of the frame, shutdown hooks still run.
+    pub fn exit(&mut self) {
+        if !self.exit_requested {
+            self.exit_requested = true;
+            self.events.push(AppEvent::AppExit);
+        }
+    }
+
+    pub fn exit_requested(&self) -> bool {
+        self.exit_requested
+    }
+
+    pub(crate) fn update_events(&mut self, event: &Event<'_, ()>) {
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::Focused(focused) if *focused != self.focused => {
+                    self.focused = *focused;
+                    self.events.push(if *focused {
+                        AppEvent::FocusGained
+                    } else {
+                        AppEvent::FocusLost
+                    });
+                }
+                // Most platforms resize the window to nothing when it's minimized.
+                WindowEvent::Resized(size) => {
+                    let minimized = size.width == 0 || size.height == 0;
+                    if minimized != self.minimized {
+                        self.minimized = minimized;
+                        self.events.push(if minimized {
+                            AppEvent::Minimized
+                        } else {
+                            AppEvent::Restored
+                        });
+                    }
+                }
+                _ => (),
+            },
+            Event::Suspended if !self.suspended => {
+                self.suspended = true;
+                self.events.push(AppEvent::Suspended);
+            }
+            Event::Resumed if self.suspended => {
+                self.suspended = false;
+                self.events.push(AppEvent::Resumed);
+            }
+            _ => (),
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.events.clear();
+    }
+}
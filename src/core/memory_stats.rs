@@ -0,0 +1,215 @@
+use imgui::{im_str, Condition};
+use legion::prelude::*;
+use std::any::TypeId;
+
+use crate::{graphics::resources::TextRenderer, scene::components, AssetManager};
+
+type CountFn = Box<dyn Fn(&World) -> usize + Send + Sync>;
+
+/// CPU memory held on to by assets, in bytes.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct AssetMemory {
+    /// Decoded images that haven't been uploaded yet, plus images imported with
+    /// `ImageInfo::keep_cpu_data`. Other uploaded images only live on the GPU.
+    pub images: usize,
+    /// Vertices, indices, skeletons and animations that meshes keep after upload.
+    pub meshes: usize,
+    /// Font files, plus the copies the text renderer parsed it's glyphs from.
+    pub fonts: usize,
+}
+
+impl AssetMemory {
+    pub fn total(&self) -> usize {
+        self.images + self.meshes + self.fonts
+    }
+}
+
+/// Images that didn't get their own texture because an identical one was already loaded.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SharedTextures {
+    pub images: usize,
+    /// GPU memory those images would've used if they were uploaded separately.
+    pub saved_bytes: usize,
+}
+
+/// How much storage a single component type takes up in the world.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentMemory {
+    pub name: &'static str,
+    pub count: usize,
+    /// Only the size of the component itself, anything it allocates isn't counted.
+    pub bytes: usize,
+}
+
+/// Diagnostics for tracking down CPU memory leaks, a resource.
+/// Nothing is gathered until it's enabled, after that it's updated every frame and shown in a
+/// "Memory" window on top of the UI.
+/// Engine components are counted by default, add your own with `register`.
+/// ```ignore
+/// let mut memory_stats = app.resources.get_mut::<MemoryStats>().unwrap();
+/// memory_stats.enabled = true;
+/// memory_stats.register::<Health>();
+/// ```
+pub struct MemoryStats {
+    pub enabled: bool,
+    pub assets: AssetMemory,
+    pub shared_textures: SharedTextures,
+    /// Registered components, biggest first.
+    pub components: Vec<ComponentMemory>,
+    types: Vec<(TypeId, &'static str, usize, CountFn)>,
+}
+
+impl Default for MemoryStats {
+    fn default() -> Self {
+        let mut stats = Self {
+            enabled: false,
+            assets: AssetMemory::default(),
+            shared_textures: SharedTextures::default(),
+            components: Vec::new(),
+            types: Vec::new(),
+        };
+        stats.register::<components::Transform>();
+        stats.register::<components::Mesh>();
+        stats.register::<components::Material>();
+        stats.register::<components::MaterialInstance>();
+        stats.register::<components::PaintTexture>();
+        stats.register::<components::Animator>();
+        stats.register::<components::CameraData>();
+        stats.register::<components::FlyCamera>();
+        stats.register::<components::OrbitCamera>();
+        stats.register::<components::Parent>();
+        stats.register::<components::Name>();
+        stats.register::<components::EntityId>();
+        stats.register::<components::Tags>();
+        stats.register::<components::WorldText>();
+        stats.register::<components::Visible>();
+        stats.register::<components::Static>();
+        stats.register::<components::ShadowFlags>();
+        stats.register::<components::WaterVolume>();
+        stats.register::<components::DeformableSurface>();
+        stats.register::<components::Deformer>();
+        stats.register::<components::Sprite>();
+        stats.register::<components::ParticleEmitter>();
+        stats.register::<components::DirectionalLightData>();
+        stats.register::<components::PointLightData>();
+        stats.register::<components::SpotLightData>();
+        stats.register::<crate::ai::Agent>();
+        stats
+    }
+}
+
+impl MemoryStats {
+    /// Adds a component type to the stats, registering it twice does nothing.
+    pub fn register<T: Component>(&mut self) {
+        let type_id = TypeId::of::<T>();
+        if self.types.iter().any(|(id, ..)| *id == type_id) {
+            return;
+        }
+        self.types.push((
+            type_id,
+            std::any::type_name::<T>(),
+            std::mem::size_of::<T>(),
+            Box::new(|world| <Read<T>>::query().iter(world).count()),
+        ));
+    }
+
+    /// Bytes used by every registered component.
+    pub fn component_total(&self) -> usize {
+        self.components
+            .iter()
+            .map(|component| component.bytes)
+            .sum()
+    }
+
+    pub(crate) fn update(&mut self, world: &World, resources: &Resources) {
+        let asset_manager = resources.get::<AssetManager>().unwrap();
+        self.assets = asset_manager.cpu_memory();
+        self.shared_textures = asset_manager.shared_textures();
+        if let Some(text_renderer) = resources.get::<TextRenderer>() {
+            self.assets.fonts += text_renderer.font_bytes();
+        }
+
+        self.update_components(world);
+    }
+
+    fn update_components(&mut self, world: &World) {
+        self.components = self
+            .types
+            .iter()
+            .map(|(_, name, size, count)| {
+                let count = count(world);
+                ComponentMemory {
+                    name,
+                    count,
+                    bytes: count * size,
+                }
+            })
+            .collect();
+        self.components.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+    }
+
+    pub(crate) fn draw_ui(&self, ui: &imgui::Ui<'_>) {
+        imgui::Window::new(im_str!("Memory"))
+            .size([400.0, 300.0], Condition::FirstUseEver)
+            .build(ui, || {
+                ui.text(im_str!("Assets: {}", format_bytes(self.assets.total())));
+                ui.text(im_str!("  Images: {}", format_bytes(self.assets.images)));
+                ui.text(im_str!("  Meshes: {}", format_bytes(self.assets.meshes)));
+                ui.text(im_str!("  Fonts: {}", format_bytes(self.assets.fonts)));
+                ui.text(im_str!(
+                    "Shared textures: {} (saved {} of GPU memory)",
+                    self.shared_textures.images,
+                    format_bytes(self.shared_textures.saved_bytes)
+                ));
+                ui.separator();
+                ui.text(im_str!(
+                    "Components: {}",
+                    format_bytes(self.component_total())
+                ));
+                for component in self.components.iter().filter(|c| c.count > 0) {
+                    // Drop the module path, the type name is enough to tell them apart.
+                    let name = component.name.rsplit("::").next().unwrap_or(component.name);
+                    ui.text(im_str!(
+                        "  {} x{}: {}",
+                        name,
+                        component.count,
+                        format_bytes(component.bytes)
+                    ));
+                }
+            });
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f32 / (1024.0 * 1024.0))
+    } else if bytes >= 1024 {
+        format!("{:.1} KB", bytes as f32 / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_component_memory() {
+        let mut world = Universe::new().create_world();
+        world.insert((), (0..3).map(|i| (components::Name(i.to_string()),)));
+
+        let mut stats = MemoryStats::default();
+        stats.register::<components::Name>();
+        stats.update_components(&world);
+        let names = stats
+            .components
+            .iter()
+            .find(|component| component.name.ends_with("Name"))
+            .unwrap();
+        assert_eq!(names.count, 3);
+        assert_eq!(names.bytes, 3 * std::mem::size_of::<components::Name>());
+        assert_eq!(stats.component_total(), names.bytes);
+        assert_eq!(format_bytes(2048), "2.0 KB");
+    }
+}
@@ -1,7 +1,35 @@
 pub mod input;
 
+mod lifecycle;
+pub use lifecycle::{AppEvent, AppEvents};
+
 mod font;
 pub use font::Font;
 
 mod theme;
 pub use theme::Theme;
+
+mod memory_stats;
+pub use memory_stats::{AssetMemory, ComponentMemory, MemoryStats, SharedTextures};
+
+mod frame_stats;
+pub use frame_stats::{FrameStats, ProfileScope, ScopeTime};
+
+mod hitch_detector;
+pub use hitch_detector::{DiagnosticEvent, Hitch, HitchDetector};
+
+mod random;
+pub use random::{Random, Rng};
+
+mod uuid;
+pub use uuid::Uuid;
+
+#[cfg(feature = "remote_debug")]
+mod remote_debug;
+#[cfg(feature = "remote_debug")]
+pub use remote_debug::RemoteDebugServer;
+
+#[cfg(feature = "gamepad")]
+mod gamepad;
+#[cfg(feature = "gamepad")]
+pub(crate) use gamepad::GamepadNav;
@@ -0,0 +1,189 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A small seeded random number generator (SplitMix64). The same seed always gives the same
+/// numbers on every platform, it's fast but not meant for anything cryptographic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Seeded from the clock and the process' hash keys, different every time.
+    pub fn from_entropy() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(time);
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        Self::new(hasher.finish())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        mix(self.state)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// From 0 up to but not including 1.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// From `min` up to but not including `max`.
+    pub fn range_f32(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    /// From `min` up to but not including `max`, `min` if the range is empty.
+    pub fn range_i32(&mut self, min: i32, max: i32) -> i32 {
+        if max <= min {
+            return min;
+        }
+        let span = (max as i64 - min as i64) as u64;
+        (min as i64 + (self.next_u64() % span) as i64) as i32
+    }
+
+    /// An index into something `len` long, 0 if it's empty.
+    pub fn index(&mut self, len: usize) -> usize {
+        if len == 0 {
+            return 0;
+        }
+        (self.next_u64() % len as u64) as usize
+    }
+
+    /// True `probability` of the time, from 0 to 1.
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.next_f32() < probability
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        items.get(self.index(items.len()))
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.index(i + 1);
+            items.swap(i, j);
+        }
+    }
+}
+
+// SplitMix64's output function, also used to spread out stream seeds.
+fn mix(value: u64) -> u64 {
+    let mut z = value;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// FNV-1a, unlike std's hashers it's guaranteed to stay the same between Rust versions.
+pub(crate) fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Random numbers for gameplay, a resource. `world` is shared by everything that doesn't care
+/// about reproducing a run, `stream` gives a system it's own generator. A stream only depends on
+/// the seed and it's name, so adding a system that uses randomness doesn't change what the
+/// others get. Reseed it with a stored seed to replay a session or in tests.
+/// ```ignore
+/// let mut random = resources.get_mut::<Random>().unwrap();
+/// random.reseed(replay.seed);
+/// let loot = random.stream("loot").pick(&drops);
+/// ```
+pub struct Random {
+    seed: u64,
+    world: Rng,
+    streams: HashMap<String, Rng>,
+}
+
+impl Default for Random {
+    /// Seeded from entropy, see `seed` to store it.
+    fn default() -> Self {
+        Self::new(Rng::from_entropy().next_u64())
+    }
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            world: Rng::new(seed),
+            streams: HashMap::new(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Starts over with a new seed, every stream starts over too.
+    pub fn reseed(&mut self, seed: u64) {
+        *self = Self::new(seed);
+    }
+
+    pub fn world(&mut self) -> &mut Rng {
+        &mut self.world
+    }
+
+    /// The generator named `name`, created the first time it's asked for.
+    pub fn stream(&mut self, name: &str) -> &mut Rng {
+        let seed = self.seed;
+        self.streams
+            .entry(name.to_string())
+            .or_insert_with(|| Rng::new(mix(seed ^ stable_hash(name.as_bytes()))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rng() {
+        let mut rng = Rng::new(7);
+        let numbers: Vec<u64> = (0..4).map(|_| rng.next_u64()).collect();
+        let mut again = Rng::new(7);
+        assert!(numbers.iter().all(|number| *number == again.next_u64()));
+
+        for _ in 0..1000 {
+            assert!((0.0..1.0).contains(&rng.next_f32()));
+            assert!((-3..3).contains(&rng.range_i32(-3, 3)));
+        }
+        assert_eq!(rng.range_i32(5, 5), 5);
+        assert_eq!(rng.pick::<u32>(&[]), None);
+
+        let mut items = [1, 2, 3, 4, 5];
+        rng.shuffle(&mut items);
+        items.sort();
+        assert_eq!(items, [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_streams_are_independent() {
+        let mut random = Random::new(42);
+        random.world().next_u64();
+        let loot = random.stream("loot").next_u64();
+        assert_ne!(loot, random.stream("weather").next_u64());
+
+        let mut other = Random::new(42);
+        assert_eq!(other.stream("loot").next_u64(), loot);
+        other.reseed(43);
+        assert_ne!(other.stream("loot").next_u64(), loot);
+    }
+}
@@ -0,0 +1,271 @@
+use legion::prelude::*;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    io,
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+use tungstenite::{Message, WebSocket};
+
+use crate::{
+    scene::{
+        components::{CameraData, Name, Tags, Transform},
+        ComponentRegistry, EntityDesc,
+    },
+    Application, AssetManager, TransformCount,
+};
+
+type CommandFn = Box<dyn FnMut(&mut Application, &[&str]) -> String>;
+
+struct Command {
+    name: String,
+    help: String,
+    run: CommandFn,
+}
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Lets external tools inspect a running game over WebSocket, only built with the
+/// "remote_debug" feature. Call `poll` once per update, it never blocks for long.
+///
+/// Every message is a JSON request, the reply has the same id and either a `result` or an
+/// `error`.
+/// ```json
+/// {"id": 1, "method": "entities"}
+/// {"id": 2, "method": "entity", "params": {"id": "4#0"}}
+/// {"id": 3, "method": "set_component", "params": {"id": "4#0", "name": "hp", "value": "(5.0)"}}
+/// {"id": 4, "method": "stats"}
+/// {"id": 5, "method": "command", "params": {"line": "help"}}
+/// ```
+/// Entities are the ones with a transform, camera or name, see `EntityDesc` for the
+/// components that are described. Components in the `ComponentRegistry` can be edited with
+/// "set_component", the value is RON.
+pub struct RemoteDebugServer {
+    listener: TcpListener,
+    clients: Vec<WebSocket<TcpStream>>,
+    commands: Vec<Command>,
+}
+
+impl RemoteDebugServer {
+    pub fn bind<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            clients: Vec::new(),
+            commands: Vec::new(),
+        })
+    }
+
+    /// Adds a console command, it's called with the words after it's name and the string it
+    /// returns is sent back. "help" lists the commands.
+    pub fn add_command<T, F>(&mut self, name: T, help: T, run: F)
+    where
+        T: Into<String>,
+        F: FnMut(&mut Application, &[&str]) -> String + 'static,
+    {
+        self.commands.push(Command {
+            name: name.into(),
+            help: help.into(),
+            run: Box::new(run),
+        });
+    }
+
+    /// Accepts new connections and answers any requests that came in.
+    pub fn poll(&mut self, app: &mut Application) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, address)) => match Self::handshake(stream) {
+                    Ok(client) => {
+                        log::info!("Remote Debug: {} connected.", address);
+                        self.clients.push(client);
+                    }
+                    Err(error) => log::warn!("Remote Debug: {} failed: {}", address, error),
+                },
+                Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    log::warn!("Remote Debug: accept failed: {}", error);
+                    break;
+                }
+            }
+        }
+
+        let mut clients = Vec::with_capacity(self.clients.len());
+        // Taken out of self so commands can run while they're being read.
+        for mut client in std::mem::replace(&mut self.clients, Vec::new()) {
+            let connected = loop {
+                match client.read_message() {
+                    Ok(Message::Text(text)) => {
+                        let reply = self.reply(app, &text);
+                        if let Err(error) = client.write_message(Message::Text(reply)) {
+                            if !is_would_block(&error) {
+                                break false;
+                            }
+                        }
+                    }
+                    Ok(Message::Close(_)) => break false,
+                    Ok(_) => (),
+                    Err(ref error) if is_would_block(error) => break true,
+                    Err(_) => break false,
+                }
+            };
+            // Replies that didn't fit in the socket's buffer are sent on later polls.
+            if connected
+                && client
+                    .write_pending()
+                    .map_or_else(|e| is_would_block(&e), |_| true)
+            {
+                clients.push(client);
+            }
+        }
+        self.clients = clients;
+    }
+
+    // The handshake is only a couple of small messages so it's done blocking.
+    fn handshake(stream: TcpStream) -> Result<WebSocket<TcpStream>, String> {
+        stream
+            .set_nonblocking(false)
+            .and_then(|_| stream.set_read_timeout(Some(Duration::from_secs(1))))
+            .map_err(|error| error.to_string())?;
+        let client = tungstenite::accept(stream).map_err(|error| error.to_string())?;
+        client
+            .get_ref()
+            .set_nonblocking(true)
+            .map_err(|error| error.to_string())?;
+        Ok(client)
+    }
+
+    fn reply(&mut self, app: &mut Application, text: &str) -> String {
+        let reply = match serde_json::from_str::<Request>(text) {
+            Ok(request) => match self.handle(app, &request) {
+                Ok(result) => json!({ "id": request.id, "result": result }),
+                Err(error) => json!({ "id": request.id, "error": error }),
+            },
+            Err(error) => json!({ "id": Value::Null, "error": error.to_string() }),
+        };
+        reply.to_string()
+    }
+
+    fn handle(&mut self, app: &mut Application, request: &Request) -> Result<Value, String> {
+        match request.method.as_str() {
+            "entities" => {
+                let world = &app.current_scene.world;
+                let entities: Vec<Value> = inspected_entities(world)
+                    .into_iter()
+                    .map(|entity| {
+                        json!({
+                            "id": entity.to_string(),
+                            "name": world
+                                .get_component::<Name>(entity)
+                                .map(|name| name.0.clone()),
+                        })
+                    })
+                    .collect();
+                Ok(Value::Array(entities))
+            }
+            "entity" => {
+                let id = request.params["id"]
+                    .as_str()
+                    .ok_or_else(|| "Missing the entity id.".to_string())?;
+                let world = &app.current_scene.world;
+                let entity = inspected_entities(world)
+                    .into_iter()
+                    .find(|entity| entity.to_string() == id)
+                    .ok_or_else(|| format!("There's no entity {}.", id))?;
+                let asset_manager = app.resources.get::<AssetManager>().unwrap();
+                let registry = app.resources.get::<ComponentRegistry>().unwrap();
+                let desc = EntityDesc::from_entity(world, &asset_manager, &registry, entity);
+                let mut result = serde_json::to_value(desc).map_err(|error| error.to_string())?;
+                result["id"] = json!(id);
+                let tags = world
+                    .get_component::<Tags>(entity)
+                    .map(|tags| tags.0.clone());
+                result["tags"] = json!(tags);
+                Ok(result)
+            }
+            "set_component" => {
+                let param = |name: &str| {
+                    request.params[name]
+                        .as_str()
+                        .ok_or_else(|| format!("Missing the {}.", name))
+                };
+                let (id, name, value) = (param("id")?, param("name")?, param("value")?);
+                let world = &mut app.current_scene.world;
+                let entity = inspected_entities(world)
+                    .into_iter()
+                    .find(|entity| entity.to_string() == id)
+                    .ok_or_else(|| format!("There's no entity {}.", id))?;
+                let registry = app.resources.get::<ComponentRegistry>().unwrap();
+                registry
+                    .set_ron(world, entity, name, value)
+                    .map_err(|error| error.to_string())?;
+                Ok(Value::Null)
+            }
+            "stats" => {
+                let transforms = app.resources.get::<TransformCount>().unwrap().0;
+                Ok(json!({
+                    "frame_time": app.frame_time,
+                    "delta_time": app.delta_time,
+                    "fps": if app.frame_time > 0.0 { 1.0 / app.frame_time } else { 0.0 },
+                    "entities": inspected_entities(&app.current_scene.world).len(),
+                    "transforms": transforms,
+                }))
+            }
+            "command" => {
+                let line = request.params["line"]
+                    .as_str()
+                    .ok_or_else(|| "Missing the command line.".to_string())?;
+                let words: Vec<&str> = line.split_whitespace().collect();
+                match words.split_first() {
+                    Some((&"help", _)) | None => Ok(json!(self
+                        .commands
+                        .iter()
+                        .map(|command| format!("{} - {}", command.name, command.help))
+                        .collect::<Vec<_>>()
+                        .join("\n"))),
+                    Some((name, arguments)) => {
+                        let command = self
+                            .commands
+                            .iter_mut()
+                            .find(|command| command.name == *name)
+                            .ok_or_else(|| format!("Unknown command {}, try help.", name))?;
+                        Ok(json!((command.run)(app, arguments)))
+                    }
+                }
+            }
+            method => Err(format!("Unknown method {}.", method)),
+        }
+    }
+}
+
+fn is_would_block(error: &tungstenite::Error) -> bool {
+    match error {
+        tungstenite::Error::Io(error) => error.kind() == io::ErrorKind::WouldBlock,
+        _ => false,
+    }
+}
+
+// Everything with a transform, camera or name, the same order every time.
+fn inspected_entities(world: &World) -> Vec<Entity> {
+    let mut entities: Vec<Entity> = <Read<Transform>>::query()
+        .iter_entities(world)
+        .map(|(entity, _)| entity)
+        .collect();
+    for (entity, _) in <Read<CameraData>>::query().iter_entities(world) {
+        entities.push(entity);
+    }
+    for (entity, _) in <Read<Name>>::query().iter_entities(world) {
+        entities.push(entity);
+    }
+    entities.sort();
+    entities.dedup();
+    entities
+}
@@ -0,0 +1,134 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+use super::random::{stable_hash, Rng};
+
+/// A 128 bit id that stays the same across renames and sessions, stored as the usual
+/// "8-4-4-4-12" hex text. Used to refer to entities and assets in saved files and over the
+/// network, see `EntityId` and `AssetManager::asset_id`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Uuid(u128);
+
+impl Uuid {
+    pub const NIL: Uuid = Uuid(0);
+
+    /// A random (version 4) id.
+    pub fn new() -> Self {
+        Self::from_rng(&mut Rng::from_entropy())
+    }
+
+    /// A random (version 4) id from a seeded generator, handy for reproducible tests.
+    pub fn from_rng(rng: &mut Rng) -> Self {
+        let value = ((rng.next_u64() as u128) << 64) | rng.next_u64() as u128;
+        Self::with_version(value, 4)
+    }
+
+    /// The same name always gives the same id. It's a hash of the name marked as a custom
+    /// (version 8) id, not a standard version 5 one.
+    pub fn from_name(name: &str) -> Self {
+        let high = stable_hash(name.as_bytes());
+        let low = Rng::new(high ^ name.len() as u64).next_u64();
+        Self::with_version(((high as u128) << 64) | low as u128, 8)
+    }
+
+    pub fn from_u128(value: u128) -> Self {
+        Self(value)
+    }
+
+    pub fn as_u128(&self) -> u128 {
+        self.0
+    }
+
+    pub fn is_nil(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// The version number stored in the id, 4 for random ones.
+    pub fn version(&self) -> u8 {
+        ((self.0 >> 76) & 0xf) as u8
+    }
+
+    // Sets the version nibble and the RFC 4122 variant bits.
+    fn with_version(value: u128, version: u8) -> Self {
+        let value = (value & !(0xf << 76)) | ((version as u128) << 76);
+        let value = (value & !(0x3 << 62)) | (0x2 << 62);
+        Self(value)
+    }
+}
+
+impl Default for Uuid {
+    fn default() -> Self {
+        Self::NIL
+    }
+}
+
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.0;
+        write!(
+            f,
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            value >> 96,
+            (value >> 80) & 0xffff,
+            (value >> 64) & 0xffff,
+            (value >> 48) & 0xffff,
+            value & 0xffff_ffff_ffff
+        )
+    }
+}
+
+impl FromStr for Uuid {
+    type Err = String;
+
+    /// Reads "8-4-4-4-12" hex text, dashes are optional.
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let digits: String = source.chars().filter(|c| *c != '-').collect();
+        if digits.len() != 32 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("{} isn't a uuid, it needs 32 hex digits", source));
+        }
+        u128::from_str_radix(&digits, 16)
+            .map(Uuid)
+            .map_err(|error| error.to_string())
+    }
+}
+
+impl Serialize for Uuid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Uuid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        source.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uuid_text() {
+        let id = Uuid::from_rng(&mut Rng::new(1));
+        assert_eq!(id.version(), 4);
+        let text = id.to_string();
+        assert_eq!(text.len(), 36);
+        assert_eq!(text.as_bytes()[14], b'4');
+        assert_eq!(text.parse::<Uuid>(), Ok(id));
+        assert_eq!(text.replace('-', "").parse::<Uuid>(), Ok(id));
+        assert!("not-a-uuid".parse::<Uuid>().is_err());
+
+        let ron = ron::ser::to_string(&id).unwrap();
+        assert_eq!(ron::de::from_str::<Uuid>(&ron).unwrap(), id);
+    }
+
+    #[test]
+    fn test_uuid_from_name() {
+        assert_eq!(Uuid::from_name("cube.gltf"), Uuid::from_name("cube.gltf"));
+        assert_ne!(Uuid::from_name("cube.gltf"), Uuid::from_name("cube.glb"));
+        assert_eq!(Uuid::from_name("cube.gltf").version(), 8);
+        assert_ne!(Uuid::new(), Uuid::new());
+    }
+}
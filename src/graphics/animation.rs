@@ -0,0 +1,308 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra_glm::{Mat4, Quat, Vec3};
+
+/// The most joints a single skeleton can have, this is the size of the joint uniform.
+pub const MAX_JOINTS: usize = 128;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct JointUniform {
+    pub matrix: Mat4,
+}
+
+unsafe impl Zeroable for JointUniform {}
+unsafe impl Pod for JointUniform {}
+
+/// A single bone in a skeleton.
+#[derive(Debug, Clone)]
+pub struct Joint {
+    pub name: Option<String>,
+    /// Index of the parent joint, roots don't have one.
+    pub parent: Option<usize>,
+    /// Rest pose, relative to the parent.
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+    /// Moves a vertex from mesh space into the joint's space.
+    pub inverse_bind: Mat4,
+}
+
+/// The local translation, rotation and scale of each joint.
+pub type Pose = Vec<(Vec3, Quat, Vec3)>;
+
+/// A hierarchy of joints used to deform a skinned mesh.
+#[derive(Debug, Clone, Default)]
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+}
+
+impl Skeleton {
+    /// The pose the skeleton was authored in.
+    pub fn rest_pose(&self) -> Pose {
+        self.joints
+            .iter()
+            .map(|joint| (joint.translation, joint.rotation, joint.scale))
+            .collect()
+    }
+
+    /// Turns a pose into the matrices the vertex shader uses to skin vertices.
+    pub fn joint_matrices(&self, pose: &Pose) -> Vec<Mat4> {
+        let mut globals: Vec<Option<Mat4>> = vec![None; self.joints.len()];
+        for index in 0..self.joints.len() {
+            self.global_matrix(index, pose, &mut globals, 0);
+        }
+        globals
+            .iter()
+            .zip(self.joints.iter())
+            .map(|(global, joint)| global.unwrap_or_else(Mat4::identity) * joint.inverse_bind)
+            .collect()
+    }
+
+    /// Puts `rotation` and a uniform `scale` above the root joints. The inverse bind matrices
+    /// change to match, so vertices moved the same way are still skinned correctly.
+    pub(crate) fn transform_roots(&mut self, rotation: &Quat, scale: f32) {
+        let inverse = nalgebra_glm::scaling(&Vec3::new(1.0 / scale, 1.0 / scale, 1.0 / scale))
+            * nalgebra_glm::quat_to_mat4(&nalgebra_glm::quat_inverse(rotation));
+        for joint in self.joints.iter_mut() {
+            joint.inverse_bind *= inverse;
+            if joint.parent.is_none() {
+                joint.translation =
+                    nalgebra_glm::quat_rotate_vec3(rotation, &joint.translation) * scale;
+                joint.rotation = rotation * joint.rotation;
+                joint.scale *= scale;
+            }
+        }
+    }
+
+    fn global_matrix(
+        &self,
+        index: usize,
+        pose: &Pose,
+        globals: &mut Vec<Option<Mat4>>,
+        depth: usize,
+    ) -> Mat4 {
+        if let Some(global) = globals[index] {
+            return global;
+        }
+
+        let (translation, rotation, scale) = pose[index];
+        let local = nalgebra_glm::translation(&translation)
+            * nalgebra_glm::quat_to_mat4(&rotation)
+            * nalgebra_glm::scaling(&scale);
+        // Bad files can have parent cycles, so stop walking up at some point.
+        let global = match self.joints[index].parent {
+            Some(parent) if depth < self.joints.len() => {
+                self.global_matrix(parent, pose, globals, depth + 1) * local
+            }
+            _ => local,
+        };
+        globals[index] = Some(global);
+        global
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    /// Each key stores an in tangent, the value and an out tangent.
+    CubicSpline,
+}
+
+#[derive(Debug, Clone)]
+pub enum ChannelValues {
+    Translation(Vec<Vec3>),
+    Rotation(Vec<Quat>),
+    Scale(Vec<Vec3>),
+}
+
+/// Animates one property of a single joint.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub joint: usize,
+    pub times: Vec<f32>,
+    pub values: ChannelValues,
+    pub interpolation: Interpolation,
+}
+
+/// A named animation, for example "walk" or "idle".
+#[derive(Debug, Clone)]
+pub struct AnimationClip {
+    pub name: String,
+    /// Length of the clip in seconds.
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+}
+
+impl AnimationClip {
+    /// Bytes used by the keyframes.
+    pub(crate) fn cpu_size(&self) -> usize {
+        use std::mem::size_of;
+        self.channels
+            .iter()
+            .map(|channel| {
+                let values = match &channel.values {
+                    ChannelValues::Translation(values) | ChannelValues::Scale(values) => {
+                        values.capacity() * size_of::<Vec3>()
+                    }
+                    ChannelValues::Rotation(values) => values.capacity() * size_of::<Quat>(),
+                };
+                channel.times.capacity() * size_of::<f32>() + values
+            })
+            .sum()
+    }
+
+    /// Changes the keys of `skeleton`'s root joints the same way as `Skeleton::transform_roots`.
+    pub(crate) fn transform_roots(&mut self, skeleton: &Skeleton, rotation: &Quat, scale: f32) {
+        for channel in self.channels.iter_mut() {
+            let is_root = skeleton
+                .joints
+                .get(channel.joint)
+                .map_or(false, |joint| joint.parent.is_none());
+            if !is_root {
+                continue;
+            }
+            // Cubic spline tangents are changed the same way, it's all linear.
+            match &mut channel.values {
+                ChannelValues::Translation(values) => {
+                    for value in values.iter_mut() {
+                        *value = nalgebra_glm::quat_rotate_vec3(rotation, value) * scale;
+                    }
+                }
+                ChannelValues::Rotation(values) => {
+                    for value in values.iter_mut() {
+                        *value = rotation * *value;
+                    }
+                }
+                ChannelValues::Scale(values) => {
+                    for value in values.iter_mut() {
+                        *value *= scale;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Samples the clip at `time` writing the result into `pose`.
+    /// Joints the clip doesn't animate are left alone.
+    pub fn sample(&self, time: f32, pose: &mut Pose) {
+        for channel in self.channels.iter() {
+            if channel.joint >= pose.len() || channel.times.is_empty() {
+                continue;
+            }
+            let (key, t, dt) = find_key(&channel.times, time);
+            let joint_pose = &mut pose[channel.joint];
+            match &channel.values {
+                ChannelValues::Translation(values) => {
+                    joint_pose.0 = sample_vec3(values, channel.interpolation, key, t, dt);
+                }
+                ChannelValues::Scale(values) => {
+                    joint_pose.2 = sample_vec3(values, channel.interpolation, key, t, dt);
+                }
+                ChannelValues::Rotation(values) => {
+                    joint_pose.1 = sample_quat(values, channel.interpolation, key, t, dt);
+                }
+            }
+        }
+    }
+}
+
+// Returns the key before `time`, how far we are to the next key (0 - 1) and the time between them.
+fn find_key(times: &[f32], time: f32) -> (usize, f32, f32) {
+    if time <= times[0] {
+        return (0, 0.0, 0.0);
+    }
+    let last = times.len() - 1;
+    if time >= times[last] {
+        return (last, 0.0, 0.0);
+    }
+    let next = times.iter().position(|key_time| *key_time > time).unwrap();
+    let key = next - 1;
+    let dt = times[next] - times[key];
+    (key, (time - times[key]) / dt, dt)
+}
+
+fn hermite<T>(v0: T, b0: T, v1: T, a1: T, t: f32, dt: f32) -> T
+where
+    T: std::ops::Mul<f32, Output = T> + std::ops::Add<Output = T>,
+{
+    let t2 = t * t;
+    let t3 = t2 * t;
+    v0 * (2.0 * t3 - 3.0 * t2 + 1.0)
+        + b0 * ((t3 - 2.0 * t2 + t) * dt)
+        + v1 * (-2.0 * t3 + 3.0 * t2)
+        + a1 * ((t3 - t2) * dt)
+}
+
+fn sample_vec3(values: &[Vec3], interpolation: Interpolation, key: usize, t: f32, dt: f32) -> Vec3 {
+    match interpolation {
+        Interpolation::Step => values[key],
+        Interpolation::Linear => {
+            let next = (key + 1).min(values.len() - 1);
+            nalgebra_glm::lerp(&values[key], &values[next], t)
+        }
+        Interpolation::CubicSpline => {
+            let next = (key + 1).min(values.len() / 3 - 1);
+            hermite(
+                values[key * 3 + 1],
+                values[key * 3 + 2],
+                values[next * 3 + 1],
+                values[next * 3],
+                t,
+                dt,
+            )
+        }
+    }
+}
+
+fn sample_quat(values: &[Quat], interpolation: Interpolation, key: usize, t: f32, dt: f32) -> Quat {
+    match interpolation {
+        Interpolation::Step => values[key],
+        Interpolation::Linear => {
+            let next = (key + 1).min(values.len() - 1);
+            nalgebra_glm::quat_slerp(&values[key], &values[next], t)
+        }
+        Interpolation::CubicSpline => {
+            let next = (key + 1).min(values.len() / 3 - 1);
+            let coords = hermite(
+                values[key * 3 + 1].coords,
+                values[key * 3 + 2].coords,
+                values[next * 3 + 1].coords,
+                values[next * 3].coords,
+                t,
+                dt,
+            );
+            nalgebra_glm::quat_normalize(&Quat { coords })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_linear_translation() {
+        let clip = AnimationClip {
+            name: "move".to_string(),
+            duration: 2.0,
+            channels: vec![Channel {
+                joint: 0,
+                times: vec![0.0, 2.0],
+                values: ChannelValues::Translation(vec![
+                    Vec3::new(0.0, 0.0, 0.0),
+                    Vec3::new(4.0, 0.0, 0.0),
+                ]),
+                interpolation: Interpolation::Linear,
+            }],
+        };
+        let mut pose = vec![(Vec3::zeros(), Quat::identity(), Vec3::new(1.0, 1.0, 1.0))];
+
+        clip.sample(0.5, &mut pose);
+        assert!((pose[0].0.x - 1.0).abs() < 0.0001);
+
+        // Past the end we hold the last key.
+        clip.sample(10.0, &mut pose);
+        assert!((pose[0].0.x - 4.0).abs() < 0.0001);
+    }
+}
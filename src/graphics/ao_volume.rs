@@ -0,0 +1,391 @@
+use bytemuck::{Pod, Zeroable};
+use legion::prelude::*;
+use nalgebra_glm::{Vec3, Vec4};
+use rayon::prelude::*;
+
+use crate::{
+    graphics::{queries::transform_point, resources::GPUResourceManager, FramesInFlight},
+    scene::components::{Mesh, Static, Transform},
+    AssetManager,
+};
+
+// This needs to match library/ao_volume.glsl.
+/// The most voxels the AO volume can have along each axis, bigger resolutions are clamped.
+pub const MAX_AO_VOXELS: u32 = 64;
+/// The format of the AO volume's texture, one visibility value per voxel.
+pub(crate) const AO_VOLUME_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+// Buffer to texture copies need rows of 256 bytes.
+const PADDED_ROW: u32 = 256;
+
+/// Baked world space ambient occlusion for static geometry, a resource.
+/// The triangles of every entity with a `Mesh`, `Transform` and `Static` are voxelized into a
+/// grid over the box at `origin` with `size`, then each voxel looks for solid voxels within
+/// `radius` along a fixed set of directions. The PBR shader darkens it's ambient light with
+/// the voxel a little way out from the surface, which catches the large scale occlusion of
+/// rooms, corners and caves that SSAO can't see from the screen.
+/// Nothing is baked by default, call `bake` after the scene is loaded or store `voxels` from an
+/// offline bake and hand them back with `set_voxels`.
+/// Note: The occlusion is relative to a voxel right above an open floor, so flat ground isn't
+/// darkened.
+#[derive(Debug, Clone)]
+pub struct AoVolume {
+    /// The corner of the volume with the smallest coordinates.
+    pub origin: Vec3,
+    /// Size of the volume along each axis.
+    pub size: Vec3,
+    /// Voxels along each axis, clamped to `MAX_AO_VOXELS`.
+    pub resolution: [u32; 3],
+    /// How far away geometry still occludes a voxel.
+    pub radius: f32,
+    /// Scales the occlusion, 0.0 turns it off.
+    pub intensity: f32,
+    // The visibility of each voxel ordered along x first, then y and then z.
+    voxels: Option<Vec<u8>>,
+    voxels_changed: bool,
+    // What the uniform buffer holds, it starts out zeroed which turns the volume off.
+    uploaded: AoVolumeUniform,
+}
+
+impl Default for AoVolume {
+    fn default() -> Self {
+        Self {
+            origin: Vec3::new(-16.0, -1.0, -16.0),
+            size: Vec3::new(32.0, 16.0, 32.0),
+            resolution: [32, 16, 32],
+            radius: 4.0,
+            intensity: 1.0,
+            voxels: None,
+            voxels_changed: false,
+            uploaded: AoVolumeUniform::default(),
+        }
+    }
+}
+
+impl AoVolume {
+    /// The voxels along each axis.
+    pub fn voxel_counts(&self) -> [u32; 3] {
+        let axis_count = |count: u32| count.max(1).min(MAX_AO_VOXELS);
+        [
+            axis_count(self.resolution[0]),
+            axis_count(self.resolution[1]),
+            axis_count(self.resolution[2]),
+        ]
+    }
+
+    /// Size of a single voxel.
+    pub fn voxel_size(&self) -> Vec3 {
+        let counts = self.voxel_counts();
+        Vec3::new(
+            self.size.x.max(0.01) / counts[0] as f32,
+            self.size.y.max(0.01) / counts[1] as f32,
+            self.size.z.max(0.01) / counts[2] as f32,
+        )
+    }
+
+    /// The baked visibility of each voxel, 255 is unoccluded. They're ordered along x first,
+    /// then y and then z. None until it's baked.
+    pub fn voxels(&self) -> Option<&[u8]> {
+        self.voxels.as_deref()
+    }
+
+    /// Uses voxels baked earlier, for example by an offline tool with the same `origin`,
+    /// `size` and `resolution`.
+    /// Panics if there's not one value for each voxel of `voxel_counts`.
+    pub fn set_voxels(&mut self, voxels: Vec<u8>) {
+        let count = self.voxel_counts().iter().product::<u32>() as usize;
+        assert_eq!(voxels.len(), count, "expected one value per voxel");
+        self.voxels = Some(voxels);
+        self.voxels_changed = true;
+    }
+
+    /// Throws away the baked voxels, which turns the volume off.
+    pub fn clear(&mut self) {
+        self.voxels = None;
+        self.voxels_changed = true;
+    }
+
+    /// Bakes the volume from the static meshes in the world. Meshes that aren't loaded yet are
+    /// skipped.
+    pub fn bake(&mut self, world: &World, asset_manager: &AssetManager) {
+        let query = <(Read<Mesh>, Read<Transform>, Read<Static>)>::query();
+        let mut triangles = Vec::new();
+        for (mesh, transform, _) in query.iter(world) {
+            let mesh = match asset_manager.try_get_mesh(mesh.mesh_name.clone()) {
+                Ok(mesh) => mesh,
+                Err(_) => continue,
+            };
+            let sub_mesh_triangles = mesh
+                .sub_meshes
+                .iter()
+                .flat_map(|sub_mesh| sub_mesh.triangles());
+            triangles.extend(sub_mesh_triangles.map(|triangle| {
+                [
+                    transform_point(&transform.matrix, &triangle[0]),
+                    transform_point(&transform.matrix, &triangle[1]),
+                    transform_point(&transform.matrix, &triangle[2]),
+                ]
+            }));
+        }
+        self.bake_triangles(&triangles);
+    }
+
+    /// Bakes the volume from world space triangles.
+    pub fn bake_triangles(&mut self, triangles: &[[Vec3; 3]]) {
+        let solid = self.voxelize(triangles);
+        let counts = self.voxel_counts();
+        let voxel_size = self.voxel_size();
+        let first = voxel_size.min();
+        let step = first * 0.5;
+        let radius = self.radius.max(first);
+        let directions = directions();
+        // Right above an open floor the 9 downward directions hit it in their first step.
+        let floor = 1.0 - 9.0 / directions.len() as f32 * (1.0 - first / radius);
+
+        let voxel_count = counts.iter().product::<u32>() as usize;
+        let voxels = (0..voxel_count)
+            .into_par_iter()
+            .map(|index| {
+                let (x, y) = (counts[0] as usize, counts[1] as usize);
+                let cell = Vec3::new(
+                    (index % x) as f32 + 0.5,
+                    (index / x % y) as f32 + 0.5,
+                    (index / (x * y)) as f32 + 0.5,
+                );
+                let center = self.origin + voxel_size.component_mul(&cell);
+                let occlusion = directions
+                    .iter()
+                    .map(|direction| {
+                        // The voxel itself is skipped, surfaces are sampled a voxel out.
+                        let mut distance = first;
+                        while distance < radius {
+                            let point = center + direction * distance;
+                            match self.voxel_index(&point) {
+                                Some(hit) if solid[hit] => return 1.0 - distance / radius,
+                                Some(_) => {}
+                                None => break,
+                            }
+                            distance += step;
+                        }
+                        0.0
+                    })
+                    .sum::<f32>()
+                    / directions.len() as f32;
+                let visibility = ((1.0 - occlusion) / floor).min(1.0);
+                (visibility * 255.0).round() as u8
+            })
+            .collect();
+        self.voxels = Some(voxels);
+        self.voxels_changed = true;
+    }
+
+    // Marks every voxel a triangle passes through, by sampling it at half a voxel apart.
+    fn voxelize(&self, triangles: &[[Vec3; 3]]) -> Vec<bool> {
+        let counts = self.voxel_counts();
+        let mut solid = vec![false; counts.iter().product::<u32>() as usize];
+        let step = self.voxel_size().min() * 0.5;
+        let max = self.origin + self.size;
+        for [a, b, c] in triangles {
+            let low = a.inf(b).inf(c);
+            let high = a.sup(b).sup(c);
+            if (0..3).any(|axis| high[axis] < self.origin[axis] || low[axis] > max[axis]) {
+                continue;
+            }
+            let longest = (b - a).norm().max((c - b).norm()).max((a - c).norm());
+            let samples = (longest / step).ceil().max(1.0) as u32;
+            for i in 0..=samples {
+                for j in 0..=samples - i {
+                    let u = i as f32 / samples as f32;
+                    let v = j as f32 / samples as f32;
+                    let point = a + (b - a) * u + (c - a) * v;
+                    if let Some(index) = self.voxel_index(&point) {
+                        solid[index] = true;
+                    }
+                }
+            }
+        }
+        solid
+    }
+
+    // The voxel a point is in, None outside of the volume.
+    fn voxel_index(&self, point: &Vec3) -> Option<usize> {
+        let counts = self.voxel_counts();
+        let cell = (point - self.origin).component_div(&self.voxel_size());
+        if (0..3).any(|axis| cell[axis] < 0.0 || cell[axis] >= counts[axis] as f32) {
+            return None;
+        }
+        let (x, y, z) = (cell.x as usize, cell.y as usize, cell.z as usize);
+        Some(x + counts[0] as usize * (y + counts[1] as usize * z))
+    }
+
+    // A zeroed uniform while nothing is baked.
+    pub(crate) fn uniform(&self) -> AoVolumeUniform {
+        if self.voxels.is_none() {
+            return AoVolumeUniform::default();
+        }
+        let counts = self.voxel_counts();
+        AoVolumeUniform {
+            origin: Vec4::new(
+                self.origin.x,
+                self.origin.y,
+                self.origin.z,
+                self.intensity.max(0.0),
+            ),
+            size: Vec4::new(
+                self.size.x.max(0.01),
+                self.size.y.max(0.01),
+                self.size.z.max(0.01),
+                0.0,
+            ),
+            counts: Vec4::new(
+                counts[0] as f32,
+                counts[1] as f32,
+                counts[2] as f32,
+                MAX_AO_VOXELS as f32,
+            ),
+        }
+    }
+}
+
+// The 26 directions to the neighbours of a voxel.
+fn directions() -> Vec<Vec3> {
+    let mut directions = Vec::with_capacity(26);
+    for x in -1..=1 {
+        for y in -1..=1 {
+            for z in -1..=1 {
+                if (x, y, z) != (0, 0, 0) {
+                    directions.push(Vec3::new(x as f32, y as f32, z as f32).normalize());
+                }
+            }
+        }
+    }
+    directions
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct AoVolumeUniform {
+    // (origin, intensity), the intensity is 0.0 when it's off.
+    origin: Vec4,
+    // (size, unused)
+    size: Vec4,
+    // (voxels along x, y and z, MAX_AO_VOXELS)
+    counts: Vec4,
+}
+
+unsafe impl Zeroable for AoVolumeUniform {}
+unsafe impl Pod for AoVolumeUniform {}
+
+impl Default for AoVolumeUniform {
+    fn default() -> Self {
+        Self::zeroed()
+    }
+}
+
+/// Uploads the volume once it's baked or changed, runs once a frame.
+pub(crate) fn update(resources: &mut Resources) {
+    let mut ao_volume = resources.get_mut::<AoVolume>().unwrap();
+    let uniform = ao_volume.uniform();
+    if uniform == ao_volume.uploaded && !ao_volume.voxels_changed {
+        return;
+    }
+
+    let resource_manager = resources.get::<GPUResourceManager>().unwrap();
+    let device = resources.get::<wgpu::Device>().unwrap();
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("ao_volume"),
+    });
+    if let (true, Some(voxels)) = (ao_volume.voxels_changed, ao_volume.voxels.as_ref()) {
+        let counts = ao_volume.voxel_counts();
+        let mut padded = vec![0u8; (PADDED_ROW * counts[1] * counts[2]) as usize];
+        for (row, voxels) in voxels.chunks_exact(counts[0] as usize).enumerate() {
+            let start = row * PADDED_ROW as usize;
+            padded[start..start + voxels.len()].copy_from_slice(voxels);
+        }
+        let buffer = device.create_buffer_with_data(&padded, wgpu::BufferUsage::COPY_SRC);
+        encoder.copy_buffer_to_texture(
+            wgpu::BufferCopyView {
+                buffer: &buffer,
+                layout: wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: PADDED_ROW,
+                    rows_per_image: counts[1],
+                },
+            },
+            wgpu::TextureCopyView {
+                texture: &resource_manager.ao_volume_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: 0, y: 0, z: 0 },
+            },
+            wgpu::Extent3d {
+                width: counts[0],
+                height: counts[1],
+                depth: counts[2],
+            },
+        );
+    }
+    let uniform_buffer =
+        device.create_buffer_with_data(bytemuck::bytes_of(&uniform), wgpu::BufferUsage::COPY_SRC);
+    encoder.copy_buffer_to_buffer(
+        &uniform_buffer,
+        0,
+        &resource_manager.ao_volume_uniform_buffer,
+        0,
+        std::mem::size_of::<AoVolumeUniform>() as u64,
+    );
+    resources
+        .get::<FramesInFlight>()
+        .unwrap()
+        .submit(Some(encoder.finish()));
+    ao_volume.uploaded = uniform;
+    ao_volume.voxels_changed = false;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad(a: Vec3, b: Vec3, c: Vec3, d: Vec3) -> Vec<[Vec3; 3]> {
+        vec![[a, b, c], [a, c, d]]
+    }
+
+    #[test]
+    fn test_bake() {
+        let mut ao_volume = AoVolume {
+            origin: Vec3::new(0.0, 0.0, 0.0),
+            size: Vec3::new(8.0, 8.0, 8.0),
+            resolution: [8, 8, 8],
+            radius: 3.0,
+            ..Default::default()
+        };
+        assert_eq!(ao_volume.uniform(), AoVolumeUniform::default());
+
+        // A floor with a wall along x = 0.
+        let mut triangles = quad(
+            Vec3::new(0.0, 0.5, 0.0),
+            Vec3::new(8.0, 0.5, 0.0),
+            Vec3::new(8.0, 0.5, 8.0),
+            Vec3::new(0.0, 0.5, 8.0),
+        );
+        triangles.extend(quad(
+            Vec3::new(0.5, 0.0, 0.0),
+            Vec3::new(0.5, 8.0, 0.0),
+            Vec3::new(0.5, 8.0, 8.0),
+            Vec3::new(0.5, 0.0, 8.0),
+        ));
+        ao_volume.bake_triangles(&triangles);
+
+        let voxels = ao_volume.voxels().unwrap();
+        assert_eq!(voxels.len(), 512);
+        let voxel = |x: usize, y: usize, z: usize| voxels[x + 8 * (y + 8 * z)];
+        // Open space far from everything.
+        assert_eq!(voxel(6, 6, 4), 255);
+        // Above the floor.
+        assert_eq!(voxel(6, 1, 4), 255);
+        // In the corner between the floor and the wall.
+        assert!(voxel(1, 1, 4) < 220);
+        assert!(ao_volume.uniform().origin.w > 0.0);
+
+        ao_volume.clear();
+        assert!(ao_volume.voxels().is_none());
+    }
+}
@@ -0,0 +1,283 @@
+use image::{imageops, RgbaImage};
+use nalgebra_glm::{Vec2, Vec4};
+use std::{collections::HashMap, path::Path};
+
+use super::{
+    material::{image::DecodedImage, Image, SamplerInfo, TextureTransform},
+    resources::GPUResourceManager,
+};
+use crate::{AssetError, AssetManager, Handle};
+
+// The page and corner of every image, and how much of each page was used.
+type Packing = (Vec<(usize, u32, u32)>, Vec<(u32, u32)>);
+
+/// Where an image ended up inside a `TextureAtlas`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtlasRegion {
+    /// The page the image was packed into, it's a regular image in the asset manager so
+    /// everything drawn from the same page can share it's binding.
+    pub page: Handle<Image>,
+    /// (offset x, offset y, scale x, scale y) in UV space.
+    pub uv_rect: Vec4,
+    /// Size of the original image in pixels.
+    pub width: u32,
+    pub height: u32,
+}
+
+impl AtlasRegion {
+    /// Top left UV, together with `uv_max` it's what `imgui::Image` expects.
+    pub fn uv_min(&self) -> [f32; 2] {
+        [self.uv_rect.x, self.uv_rect.y]
+    }
+
+    pub fn uv_max(&self) -> [f32; 2] {
+        [
+            self.uv_rect.x + self.uv_rect.z,
+            self.uv_rect.y + self.uv_rect.w,
+        ]
+    }
+
+    /// Maps a material's UVs onto this region, use it with the page as the main texture.
+    pub fn texture_transform(&self) -> TextureTransform {
+        TextureTransform {
+            offset: Vec2::new(self.uv_rect.x, self.uv_rect.y),
+            rotation: 0.0,
+            scale: Vec2::new(self.uv_rect.z, self.uv_rect.w),
+        }
+    }
+}
+
+/// Lots of small images packed into a few shared textures, built with `AtlasBuilder`.
+#[derive(Debug, Clone)]
+pub struct TextureAtlas {
+    pages: Vec<Handle<Image>>,
+    regions: HashMap<String, AtlasRegion>,
+}
+
+impl TextureAtlas {
+    /// Looks up an image by the name it was added to the builder with.
+    pub fn get(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.get(name)
+    }
+
+    pub fn pages(&self) -> &[Handle<Image>] {
+        &self.pages
+    }
+
+    pub fn regions(&self) -> impl Iterator<Item = (&String, &AtlasRegion)> {
+        self.regions.iter()
+    }
+}
+
+/// Packs images with the same format into as few textures as possible.
+/// Each page is added to the asset manager as "{name}_page{index}".
+/// ```ignore
+/// let mut builder = AtlasBuilder::new("icons").with_padding(2);
+/// builder.add_file(&asset_manager, "icons/heart.png")?;
+/// builder.add_file(&asset_manager, "icons/star.png")?;
+/// let atlas = builder.build(&device, &mut queue, &mut asset_manager, &resource_manager)?;
+/// let heart = atlas.get("heart.png").unwrap();
+/// ```
+pub struct AtlasBuilder {
+    name: String,
+    page_size: u32,
+    padding: u32,
+    format: wgpu::TextureFormat,
+    images: Vec<(String, RgbaImage)>,
+}
+
+impl AtlasBuilder {
+    pub fn new<T: Into<String>>(name: T) -> Self {
+        Self {
+            name: name.into(),
+            page_size: 2048,
+            padding: 1,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            images: Vec::new(),
+        }
+    }
+
+    /// The biggest a page can get, images that don't fit on one page go on the next.
+    pub fn with_page_size(mut self, page_size: u32) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    /// Empty pixels around every image so filtering doesn't bleed neighbours into each other.
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Rgba8UnormSrgb by default, use Rgba8Unorm when the images aren't colors.
+    pub fn with_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn add_image<T: Into<String>>(&mut self, name: T, image: RgbaImage) {
+        self.images.push((name.into(), image));
+    }
+
+    /// Reads an image from the asset folder or a mounted pack, it's looked up by it's file name
+    /// afterwards.
+    pub fn add_file<T: AsRef<Path>>(
+        &mut self,
+        asset_manager: &AssetManager,
+        path: T,
+    ) -> Result<(), AssetError> {
+        let path = path.as_ref();
+        let path_name = path.to_string_lossy().to_string();
+        let bytes = asset_manager.vfs().read(&path_name)?;
+        let image = image::load_from_memory(&bytes)
+            .map_err(|error| AssetError::from_image(&path_name, error))?
+            .to_rgba();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or(path_name);
+        self.add_image(name, image);
+        Ok(())
+    }
+
+    pub fn build(
+        self,
+        device: &wgpu::Device,
+        queue: &mut wgpu::Queue,
+        asset_manager: &mut AssetManager,
+        resource_manager: &GPUResourceManager,
+    ) -> Result<TextureAtlas, AssetError> {
+        let padding = self.padding;
+        let sizes: Vec<(u32, u32)> = self
+            .images
+            .iter()
+            .map(|(_, image)| (image.width() + padding * 2, image.height() + padding * 2))
+            .collect();
+        let (placements, page_sizes) = pack(&sizes, self.page_size).map_err(|index| {
+            AssetError::unsupported(
+                &self.images[index].0,
+                format!("it doesn't fit on a {0}x{0} atlas page", self.page_size),
+            )
+        })?;
+
+        let mut pages: Vec<RgbaImage> = page_sizes
+            .iter()
+            .map(|(width, height)| {
+                RgbaImage::new(
+                    width.next_power_of_two().min(self.page_size),
+                    height.next_power_of_two().min(self.page_size),
+                )
+            })
+            .collect();
+        let page_handles: Vec<Handle<Image>> = (0..pages.len())
+            .map(|index| Handle::new(format!("{}_page{}", self.name, index)))
+            .collect();
+
+        let mut regions = HashMap::new();
+        for ((name, image), (page, x, y)) in self.images.into_iter().zip(placements) {
+            let (x, y) = (x + padding, y + padding);
+            imageops::replace(&mut pages[page], &image, x, y);
+            let (page_width, page_height) = pages[page].dimensions();
+            let uv_rect = Vec4::new(
+                x as f32 / page_width as f32,
+                y as f32 / page_height as f32,
+                image.width() as f32 / page_width as f32,
+                image.height() as f32 / page_height as f32,
+            );
+            let region = AtlasRegion {
+                page: page_handles[page].clone(),
+                uv_rect,
+                width: image.width(),
+                height: image.height(),
+            };
+            regions.insert(name, region);
+        }
+
+        // No mips, they'd blur neighbouring images into each other.
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("texture atlas"),
+        });
+        let sampler_info = SamplerInfo::clamped();
+        for (page, handle) in pages.into_iter().zip(page_handles.iter()) {
+            let (width, height) = page.dimensions();
+            let decoded = DecodedImage {
+                levels: vec![page.into_raw()],
+                extent: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth: 1,
+                },
+                format: self.format,
+                keep_cpu_data: false,
+            };
+            asset_manager.insert_image(Image::from_decoded(
+                device,
+                &mut encoder,
+                handle.name().to_string(),
+                decoded,
+                Image::DEFAULT_USAGE,
+                sampler_info,
+                resource_manager.get_sampler(device, &sampler_info),
+            ));
+        }
+        queue.submit(Some(encoder.finish()));
+
+        Ok(TextureAtlas {
+            pages: page_handles,
+            regions,
+        })
+    }
+}
+
+// Shelf packing, tallest first. Rows are filled left to right and a new page is started once
+// one's full. Returns the page and top left corner for every size along with the extent used
+// on each page, or the index of a size that doesn't fit on a page at all.
+fn pack(sizes: &[(u32, u32)], page_size: u32) -> Result<Packing, usize> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse(sizes[index].1));
+
+    let mut placements = vec![(0, 0, 0); sizes.len()];
+    let mut pages: Vec<(u32, u32)> = Vec::new();
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+    for index in order {
+        let (width, height) = sizes[index];
+        if width > page_size || height > page_size {
+            return Err(index);
+        }
+        if x + width > page_size {
+            x = 0;
+            y += shelf_height;
+            shelf_height = 0;
+        }
+        if pages.is_empty() || y + height > page_size {
+            pages.push((0, 0));
+            x = 0;
+            y = 0;
+            shelf_height = 0;
+        }
+        let page = pages.len() - 1;
+        placements[index] = (page, x, y);
+        x += width;
+        shelf_height = shelf_height.max(height);
+        pages[page] = (pages[page].0.max(x), pages[page].1.max(y + height));
+    }
+    Ok((placements, pages))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack() {
+        let sizes = [(60, 20), (60, 40), (60, 40), (100, 100)];
+        let (placements, pages) = pack(&sizes, 128).unwrap();
+        assert_eq!(placements[3], (0, 0, 0));
+        // Doesn't fit next to or under the big one, so it starts a second page.
+        assert_eq!(placements[1], (1, 0, 0));
+        assert_eq!(placements[2], (1, 60, 0));
+        assert_eq!(placements[0], (1, 0, 40));
+        assert_eq!(pages, vec![(100, 100), (120, 60)]);
+        assert_eq!(pack(&[(10, 10), (200, 10)], 128), Err(1));
+    }
+}
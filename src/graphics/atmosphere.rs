@@ -0,0 +1,475 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra_glm::{Vec3, Vec4};
+use std::f32::consts::PI;
+
+pub(crate) const TRANSMITTANCE_LUT_SIZE: (u32, u32) = (256, 64);
+pub(crate) const MULTI_SCATTERING_LUT_SIZE: u32 = 32;
+
+const TRANSMITTANCE_STEPS: usize = 40;
+const MULTI_SCATTERING_STEPS: usize = 20;
+// The multiple scattering LUT integrates over this many by this many directions.
+const MULTI_SCATTERING_DIRECTIONS: usize = 8;
+
+/// Physically based sky for outdoor scenes, a resource. Use it with `Skybox::create_atmosphere`.
+/// Distances are in kilometers, the defaults are earth's atmosphere.
+/// Changing anything but the sun, mie g or aerial perspective rebakes the LUTs on the CPU,
+/// so avoid animating them.
+/// The sky is lit by the first directional light and distant geometry fades into the sky
+/// with aerial perspective.
+/// Note: The camera is kept inside the atmosphere, views from space aren't supported yet.
+#[derive(Debug, Clone)]
+pub struct Atmosphere {
+    /// Radius of the planet's surface, world y = 0 is on the surface.
+    pub bottom_radius: f32,
+    /// Radius where the atmosphere ends.
+    pub top_radius: f32,
+    /// Rayleigh scattering at the surface per kilometer, it's what makes the sky blue.
+    pub rayleigh_scattering: Vec3,
+    /// Height where the rayleigh density falls off to 1/e.
+    pub rayleigh_scale_height: f32,
+    /// Mie scattering at the surface per kilometer, the haze around the sun.
+    pub mie_scattering: f32,
+    pub mie_absorption: f32,
+    pub mie_scale_height: f32,
+    /// How much mie scattering goes forwards, 0 is evenly in every direction.
+    pub mie_g: f32,
+    /// Absorption by the ozone layer around 25km up, it keeps sunsets from turning green.
+    pub ozone_absorption: Vec3,
+    /// How much light the ground bounces back into the atmosphere.
+    pub ground_albedo: Vec3,
+    /// Scales the directional light's color when lighting the sky.
+    pub sun_intensity: f32,
+    /// Angular radius of the sun disc in radians, 0 hides it.
+    pub sun_angular_radius: f32,
+    /// How much brighter the sun disc is than the sky light.
+    pub sun_disc_intensity: f32,
+    /// Fades geometry into the sky with distance.
+    pub aerial_perspective: bool,
+    /// Multiplies the distance to geometry for aerial perspective, raise it for small scenes.
+    pub aerial_perspective_scale: f32,
+    /// How many world units make a kilometer.
+    pub units_per_kilometer: f32,
+    // The medium the LUTs were last baked with.
+    pub(crate) baked: Option<[f32; 15]>,
+}
+
+impl Default for Atmosphere {
+    fn default() -> Self {
+        Self {
+            bottom_radius: 6360.0,
+            top_radius: 6460.0,
+            rayleigh_scattering: Vec3::new(5.802e-3, 13.558e-3, 33.1e-3),
+            rayleigh_scale_height: 8.0,
+            mie_scattering: 3.996e-3,
+            mie_absorption: 0.444e-3,
+            mie_scale_height: 1.2,
+            mie_g: 0.8,
+            ozone_absorption: Vec3::new(0.650e-3, 1.881e-3, 0.085e-3),
+            ground_albedo: Vec3::new(0.3, 0.3, 0.3),
+            sun_intensity: 10.0,
+            sun_angular_radius: 0.004675,
+            sun_disc_intensity: 20.0,
+            aerial_perspective: true,
+            aerial_perspective_scale: 1.0,
+            units_per_kilometer: 1000.0,
+            baked: None,
+        }
+    }
+}
+
+// Scattering and extinction at one point in the atmosphere.
+struct Medium {
+    rayleigh_scattering: Vec3,
+    mie_scattering: f32,
+    extinction: Vec3,
+}
+
+// Bilinear lookups into the CPU copy of the transmittance LUT.
+struct TransmittanceLut<'a> {
+    atmosphere: &'a Atmosphere,
+    data: &'a [f32],
+}
+
+impl<'a> TransmittanceLut<'a> {
+    fn sample(&self, height: f32, cos_zenith: f32) -> Vec3 {
+        let (u, v) = self.atmosphere.transmittance_uv(height, cos_zenith);
+        let (width, height) = TRANSMITTANCE_LUT_SIZE;
+        let x = (u * width as f32 - 0.5).max(0.0).min(width as f32 - 1.0);
+        let y = (v * height as f32 - 0.5).max(0.0).min(height as f32 - 1.0);
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = (
+            (x0 + 1).min(width as usize - 1),
+            (y0 + 1).min(height as usize - 1),
+        );
+        let texel = |x: usize, y: usize| {
+            let index = (y * width as usize + x) * 4;
+            Vec3::new(self.data[index], self.data[index + 1], self.data[index + 2])
+        };
+        let (fx, fy) = (x.fract(), y.fract());
+        let top = texel(x0, y0) * (1.0 - fx) + texel(x1, y0) * fx;
+        let bottom = texel(x0, y1) * (1.0 - fx) + texel(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+// Distance along a ray starting `height` from the planet center to a sphere around the center.
+// Returns the nearest hit in front of the ray.
+fn ray_sphere(height: f32, cos_zenith: f32, radius: f32) -> Option<f32> {
+    let discriminant = height * height * (cos_zenith * cos_zenith - 1.0) + radius * radius;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let near = -height * cos_zenith - root;
+    let far = -height * cos_zenith + root;
+    if near > 0.0 {
+        Some(near)
+    } else if far > 0.0 {
+        Some(far)
+    } else {
+        None
+    }
+}
+
+impl Atmosphere {
+    /// How much sunlight reaches the ground when the sun is in `sun_direction`.
+    /// Multiply a directional light's color by it to redden the sun at sunset.
+    pub fn sun_transmittance(&self, sun_direction: &Vec3) -> Vec3 {
+        if sun_direction.magnitude() == 0.0 {
+            return Vec3::new(1.0, 1.0, 1.0);
+        }
+        let cos_zenith = sun_direction.normalize().y;
+        let height = self.bottom_radius + 0.001;
+        if ray_sphere(height, cos_zenith, self.bottom_radius).is_some() {
+            return Vec3::zeros();
+        }
+        self.transmittance(height, cos_zenith)
+    }
+
+    pub(crate) fn medium_key(&self) -> [f32; 15] {
+        [
+            self.bottom_radius,
+            self.top_radius,
+            self.rayleigh_scattering.x,
+            self.rayleigh_scattering.y,
+            self.rayleigh_scattering.z,
+            self.rayleigh_scale_height,
+            self.mie_scattering,
+            self.mie_absorption,
+            self.mie_scale_height,
+            self.ozone_absorption.x,
+            self.ozone_absorption.y,
+            self.ozone_absorption.z,
+            self.ground_albedo.x,
+            self.ground_albedo.y,
+            self.ground_albedo.z,
+        ]
+    }
+
+    fn top(&self) -> f32 {
+        self.top_radius.max(self.bottom_radius + 1.0)
+    }
+
+    fn medium(&self, altitude: f32) -> Medium {
+        let rayleigh_density = (-altitude / self.rayleigh_scale_height.max(0.001)).exp();
+        let mie_density = (-altitude / self.mie_scale_height.max(0.001)).exp();
+        let ozone_density = (1.0 - (altitude - 25.0).abs() / 15.0).max(0.0);
+        let rayleigh_scattering = self.rayleigh_scattering * rayleigh_density;
+        let mie_scattering = self.mie_scattering * mie_density;
+        let mie_extinction = (self.mie_scattering + self.mie_absorption) * mie_density;
+        Medium {
+            rayleigh_scattering,
+            mie_scattering,
+            extinction: rayleigh_scattering
+                + Vec3::new(mie_extinction, mie_extinction, mie_extinction)
+                + self.ozone_absorption * ozone_density,
+        }
+    }
+
+    // Transmittance from a point to the top of the atmosphere, ignoring the ground.
+    fn transmittance(&self, height: f32, cos_zenith: f32) -> Vec3 {
+        let distance = ray_sphere(height, cos_zenith, self.top()).unwrap_or(0.0);
+        let step = distance / TRANSMITTANCE_STEPS as f32;
+        let mut optical_depth = Vec3::zeros();
+        for i in 0..TRANSMITTANCE_STEPS {
+            let t = (i as f32 + 0.5) * step;
+            let sample_height = (height * height + t * t + 2.0 * height * cos_zenith * t).sqrt();
+            optical_depth += self.medium(sample_height - self.bottom_radius).extinction * step;
+        }
+        optical_depth.map(|depth| (-depth).exp())
+    }
+
+    // The parameterization from Bruneton's precomputed atmospheric scattering, it keeps
+    // more texels near the horizon.
+    fn transmittance_uv(&self, height: f32, cos_zenith: f32) -> (f32, f32) {
+        let (bottom, top) = (self.bottom_radius, self.top());
+        let horizon = (top * top - bottom * bottom).sqrt();
+        let rho = (height * height - bottom * bottom).max(0.0).sqrt();
+        let discriminant = height * height * (cos_zenith * cos_zenith - 1.0) + top * top;
+        let distance = (-height * cos_zenith + discriminant.max(0.0).sqrt()).max(0.0);
+        let (distance_min, distance_max) = (top - height, rho + horizon);
+        (
+            (distance - distance_min) / (distance_max - distance_min),
+            rho / horizon,
+        )
+    }
+
+    fn transmittance_params(&self, u: f32, v: f32) -> (f32, f32) {
+        let (bottom, top) = (self.bottom_radius, self.top());
+        let horizon = (top * top - bottom * bottom).sqrt();
+        let rho = horizon * v;
+        let height = (rho * rho + bottom * bottom).sqrt();
+        let (distance_min, distance_max) = (top - height, rho + horizon);
+        let distance = distance_min + u * (distance_max - distance_min);
+        let cos_zenith = if distance == 0.0 {
+            1.0
+        } else {
+            (horizon * horizon - rho * rho - distance * distance) / (2.0 * height * distance)
+        };
+        (height, cos_zenith.max(-1.0).min(1.0))
+    }
+
+    /// RGBA floats, u is the view angle and v the height.
+    pub(crate) fn transmittance_lut(&self) -> Vec<f32> {
+        let (width, height) = TRANSMITTANCE_LUT_SIZE;
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let (view_height, cos_zenith) = self.transmittance_params(
+                    (x as f32 + 0.5) / width as f32,
+                    (y as f32 + 0.5) / height as f32,
+                );
+                let transmittance = self.transmittance(view_height, cos_zenith);
+                data.extend_from_slice(&[transmittance.x, transmittance.y, transmittance.z, 1.0]);
+            }
+        }
+        data
+    }
+
+    /// RGBA floats, u is the cosine of the sun's zenith angle and v the height.
+    /// Each texel is the light scattered more than once towards a point, as in Hillaire's
+    /// "A Scalable and Production Ready Sky and Atmosphere Rendering Technique".
+    pub(crate) fn multi_scattering_lut(&self, transmittance_lut: &[f32]) -> Vec<f32> {
+        let transmittance_lut = TransmittanceLut {
+            atmosphere: self,
+            data: transmittance_lut,
+        };
+        let (bottom, top) = (self.bottom_radius, self.top());
+        let size = MULTI_SCATTERING_LUT_SIZE;
+        let mut data = Vec::with_capacity((size * size * 4) as usize);
+        for y in 0..size {
+            for x in 0..size {
+                let cos_sun = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let height = bottom + (y as f32 + 0.5) / size as f32 * (top - bottom);
+                let sun = Vec3::new((1.0 - cos_sun * cos_sun).max(0.0).sqrt(), cos_sun, 0.0);
+                let origin = Vec3::new(0.0, height, 0.0);
+
+                let mut luminance = Vec3::zeros();
+                let mut transfer = Vec3::zeros();
+                for i in 0..MULTI_SCATTERING_DIRECTIONS {
+                    for j in 0..MULTI_SCATTERING_DIRECTIONS {
+                        let theta =
+                            2.0 * PI * (i as f32 + 0.5) / MULTI_SCATTERING_DIRECTIONS as f32;
+                        let cos_phi =
+                            1.0 - 2.0 * (j as f32 + 0.5) / MULTI_SCATTERING_DIRECTIONS as f32;
+                        let sin_phi = (1.0 - cos_phi * cos_phi).sqrt();
+                        let direction =
+                            Vec3::new(theta.cos() * sin_phi, cos_phi, theta.sin() * sin_phi);
+                        let (l, f) =
+                            self.integrate_isotropic(&transmittance_lut, origin, direction, sun);
+                        luminance += l;
+                        transfer += f;
+                    }
+                }
+                let samples = (MULTI_SCATTERING_DIRECTIONS * MULTI_SCATTERING_DIRECTIONS) as f32;
+                luminance /= samples;
+                transfer /= samples;
+                // The geometric series of every further scattering order.
+                let psi = luminance.component_div(&transfer.map(|f| (1.0 - f).max(0.001)));
+                data.extend_from_slice(&[psi.x, psi.y, psi.z, 1.0]);
+            }
+        }
+        data
+    }
+
+    // Second order scattering along one ray with a unit sun, light scatters evenly in every
+    // direction. Returns the scattered light and how much of the light is scattered again.
+    fn integrate_isotropic(
+        &self,
+        transmittance_lut: &TransmittanceLut<'_>,
+        origin: Vec3,
+        direction: Vec3,
+        sun: Vec3,
+    ) -> (Vec3, Vec3) {
+        let isotropic_phase = 1.0 / (4.0 * PI);
+        let height = origin.magnitude();
+        let cos_zenith = direction.dot(&origin) / height;
+        let ground = ray_sphere(height, cos_zenith, self.bottom_radius);
+        let distance = ground
+            .or_else(|| ray_sphere(height, cos_zenith, self.top()))
+            .unwrap_or(0.0);
+        let step = distance / MULTI_SCATTERING_STEPS as f32;
+
+        let mut luminance = Vec3::zeros();
+        let mut transfer = Vec3::zeros();
+        let mut throughput = Vec3::new(1.0, 1.0, 1.0);
+        for i in 0..MULTI_SCATTERING_STEPS {
+            let position = origin + direction * ((i as f32 + 0.5) * step);
+            let sample_height = position.magnitude();
+            let cos_sun = position.dot(&sun) / sample_height;
+            let medium = self.medium(sample_height - self.bottom_radius);
+            let scattering = medium.rayleigh_scattering
+                + Vec3::new(
+                    medium.mie_scattering,
+                    medium.mie_scattering,
+                    medium.mie_scattering,
+                );
+            let shadow = if ray_sphere(sample_height, cos_sun, self.bottom_radius).is_some() {
+                0.0
+            } else {
+                1.0
+            };
+            let in_scattered = transmittance_lut
+                .sample(sample_height, cos_sun)
+                .component_mul(&scattering)
+                * (shadow * isotropic_phase);
+            let step_transmittance = (medium.extinction * step).map(|depth| (-depth).exp());
+            // Integrated analytically over the step, see Hillaire's "Physically Based Sky,
+            // Atmosphere and Cloud Rendering in Frostbite".
+            let integrate = |value: Vec3| {
+                (value - value.component_mul(&step_transmittance))
+                    .component_div(&medium.extinction.map(|e| e.max(1e-6)))
+            };
+            luminance += throughput.component_mul(&integrate(in_scattered));
+            transfer += throughput.component_mul(&integrate(scattering));
+            throughput = throughput.component_mul(&step_transmittance);
+        }
+
+        // Sunlight bouncing off the ground.
+        if ground.is_some() {
+            let position = origin + direction * distance;
+            let up = position.normalize();
+            let cos_sun = up.dot(&sun);
+            let bounced = transmittance_lut
+                .sample(self.bottom_radius, cos_sun)
+                .component_mul(&throughput)
+                .component_mul(&self.ground_albedo)
+                * (cos_sun.max(0.0) / PI);
+            luminance += bounced;
+        }
+        (luminance, transfer)
+    }
+
+    pub(crate) fn uniform(
+        &self,
+        camera_position: Vec3,
+        sun_direction: Vec3,
+        sun_color: Vec3,
+    ) -> AtmosphereUniform {
+        let (bottom, top) = (self.bottom_radius, self.top());
+        let units = self.units_per_kilometer.max(0.001);
+        // Keep the camera just inside the atmosphere so the LUTs stay valid.
+        let altitude = (camera_position.y / units)
+            .max(0.001)
+            .min(top - bottom - 0.001);
+        let sun_direction = if sun_direction.magnitude() > 0.0 {
+            sun_direction.normalize()
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        };
+        let sun_color = sun_color * self.sun_intensity;
+        let aerial_perspective_scale = if self.aerial_perspective {
+            self.aerial_perspective_scale.max(0.0)
+        } else {
+            0.0
+        };
+        AtmosphereUniform {
+            sun_direction: Vec4::new(
+                sun_direction.x,
+                sun_direction.y,
+                sun_direction.z,
+                self.sun_angular_radius.cos(),
+            ),
+            sun_color: Vec4::new(
+                sun_color.x,
+                sun_color.y,
+                sun_color.z,
+                self.sun_disc_intensity,
+            ),
+            camera_position: Vec4::new(
+                camera_position.x / units,
+                bottom + altitude,
+                camera_position.z / units,
+                units,
+            ),
+            radii: Vec4::new(bottom, top, self.mie_g, aerial_perspective_scale),
+            rayleigh: Vec4::new(
+                self.rayleigh_scattering.x,
+                self.rayleigh_scattering.y,
+                self.rayleigh_scattering.z,
+                self.rayleigh_scale_height.max(0.001),
+            ),
+            mie: Vec4::new(
+                self.mie_scattering,
+                self.mie_absorption,
+                self.mie_scale_height.max(0.001),
+                0.0,
+            ),
+            ozone: Vec4::new(
+                self.ozone_absorption.x,
+                self.ozone_absorption.y,
+                self.ozone_absorption.z,
+                0.0,
+            ),
+            ground_albedo: Vec4::new(
+                self.ground_albedo.x,
+                self.ground_albedo.y,
+                self.ground_albedo.z,
+                0.0,
+            ),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct AtmosphereUniform {
+    // (direction, cosine of the sun's angular radius)
+    pub sun_direction: Vec4,
+    // (color * intensity, sun disc intensity)
+    pub sun_color: Vec4,
+    // (camera position from the planet center in km, units per km)
+    pub camera_position: Vec4,
+    // (bottom radius, top radius, mie g, aerial perspective scale)
+    pub radii: Vec4,
+    // (scattering, scale height)
+    pub rayleigh: Vec4,
+    // (scattering, absorption, scale height, unused)
+    pub mie: Vec4,
+    pub ozone: Vec4,
+    pub ground_albedo: Vec4,
+}
+
+unsafe impl Zeroable for AtmosphereUniform {}
+unsafe impl Pod for AtmosphereUniform {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sun_transmittance() {
+        let atmosphere = Atmosphere::default();
+        let noon = atmosphere.sun_transmittance(&Vec3::new(0.0, 1.0, 0.0));
+        let sunset = atmosphere.sun_transmittance(&Vec3::new(1.0, 0.05, 0.0));
+        // Blue is scattered away first so the sun turns red near the horizon.
+        assert!(noon.x > 0.8 && noon.x <= 1.0);
+        assert!(noon.x > noon.z);
+        assert!(sunset.x < noon.x && sunset.z < sunset.x * 0.5);
+        // Below the horizon the planet is in the way.
+        assert_eq!(
+            atmosphere.sun_transmittance(&Vec3::new(0.0, -1.0, 0.0)),
+            Vec3::zeros()
+        );
+    }
+}
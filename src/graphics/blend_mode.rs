@@ -0,0 +1,67 @@
+/// How a pipeline's output is combined with what's already in the frame.
+/// Set it on a `PipelineDesc` with `set_blend_mode`, blended PBR materials pick one with
+/// `PBRMaterial::blend_mode`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// Overwrites the frame.
+    Opaque,
+    /// Regular transparency, mixes by the output's alpha.
+    Alpha,
+    /// Like `Alpha` but the color has already been multiplied by it's alpha.
+    Premultiplied,
+    /// Adds the color scaled by it's alpha, for glows, lasers and fire.
+    Additive,
+    /// Multiplies the frame with the color, for shadow blobs and tinted glass.
+    Multiply,
+    Custom {
+        color: wgpu::BlendDescriptor,
+        alpha: wgpu::BlendDescriptor,
+    },
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        BlendMode::Alpha
+    }
+}
+
+impl BlendMode {
+    pub fn color_blend(&self) -> wgpu::BlendDescriptor {
+        let blend = |src_factor, dst_factor| wgpu::BlendDescriptor {
+            src_factor,
+            dst_factor,
+            operation: wgpu::BlendOperation::Add,
+        };
+        match self {
+            BlendMode::Opaque => wgpu::BlendDescriptor::REPLACE,
+            BlendMode::Alpha => blend(
+                wgpu::BlendFactor::SrcAlpha,
+                wgpu::BlendFactor::OneMinusSrcAlpha,
+            ),
+            BlendMode::Premultiplied => {
+                blend(wgpu::BlendFactor::One, wgpu::BlendFactor::OneMinusSrcAlpha)
+            }
+            BlendMode::Additive => blend(wgpu::BlendFactor::SrcAlpha, wgpu::BlendFactor::One),
+            BlendMode::Multiply => blend(wgpu::BlendFactor::DstColor, wgpu::BlendFactor::Zero),
+            BlendMode::Custom { color, .. } => color.clone(),
+        }
+    }
+
+    pub fn alpha_blend(&self) -> wgpu::BlendDescriptor {
+        match self {
+            BlendMode::Opaque => wgpu::BlendDescriptor::REPLACE,
+            BlendMode::Alpha | BlendMode::Premultiplied => wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+            // These only change the color, the frame keeps it's alpha.
+            BlendMode::Additive | BlendMode::Multiply => wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            BlendMode::Custom { alpha, .. } => alpha.clone(),
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+
+/// An axis aligned bounding box.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self {
+            min: Vec3::zeros(),
+            max: Vec3::zeros(),
+        }
+    }
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Creates the smallest box around the points, an empty iterator gives an empty box at the origin.
+    pub fn from_points<'a, I: IntoIterator<Item = &'a Vec3>>(points: I) -> Self {
+        let mut points = points.into_iter();
+        let first = match points.next() {
+            Some(first) => *first,
+            None => return Self::default(),
+        };
+        points.fold(Self::new(first, first), |aabb, point| Self {
+            min: nalgebra_glm::min2(&aabb.min, point),
+            max: nalgebra_glm::max2(&aabb.max, point),
+        })
+    }
+
+    /// Returns a box that contains both boxes.
+    pub fn merge(&self, other: &Aabb) -> Self {
+        Self {
+            min: nalgebra_glm::min2(&self.min, &other.min),
+            max: nalgebra_glm::max2(&self.max, &other.max),
+        }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Half the size of the box.
+    pub fn extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Vec3::new(min.x, min.y, min.z),
+            Vec3::new(max.x, min.y, min.z),
+            Vec3::new(min.x, max.y, min.z),
+            Vec3::new(max.x, max.y, min.z),
+            Vec3::new(min.x, min.y, max.z),
+            Vec3::new(max.x, min.y, max.z),
+            Vec3::new(min.x, max.y, max.z),
+            Vec3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    /// Moves the box into another space, the result still lines up with the axes so it grows
+    /// when rotated.
+    pub fn transform(&self, matrix: &Mat4) -> Self {
+        let center = matrix * Vec4::new(self.center().x, self.center().y, self.center().z, 1.0);
+        let extents = self.extents();
+        let mut new_extents = Vec3::zeros();
+        for row in 0..3 {
+            for column in 0..3 {
+                new_extents[row] += matrix[(row, column)].abs() * extents[column];
+            }
+        }
+        Self {
+            min: center.xyz() - new_extents,
+            max: center.xyz() + new_extents,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// A sphere that fits around the box.
+    pub fn from_aabb(aabb: &Aabb) -> Self {
+        Self {
+            center: aabb.center(),
+            radius: nalgebra_glm::length(&aabb.extents()),
+        }
+    }
+
+    /// How much of the screen's height the sphere covers when seen from `eye`, 1.0 fills it.
+    /// Used to pick mesh LODs, it's only an estimate for spheres near the edge of the view.
+    pub fn screen_size(&self, eye: &Vec3, projection: &Mat4) -> f32 {
+        let scale = self.radius * projection[(1, 1)];
+        // Orthographic projections don't shrink things with distance.
+        if projection[(3, 3)] == 1.0 {
+            return scale;
+        }
+        let distance = nalgebra_glm::distance(eye, &self.center);
+        if distance <= self.radius {
+            return f32::INFINITY;
+        }
+        scale / distance
+    }
+}
+
+/// The six planes of a camera's view, normals point inwards.
+#[derive(Debug, Copy, Clone)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Pulls the planes out of a view projection matrix with a -1 to 1 depth range
+    /// like the one `CameraData::get_matrix` returns.
+    pub fn from_matrix(view_projection: &Mat4) -> Self {
+        let row = |index: usize| -> Vec4 { view_projection.row(index).transpose() };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let mut planes = [w + x, w - x, w + y, w - y, w + z, w - z];
+        for plane in planes.iter_mut() {
+            let length = nalgebra_glm::length(&plane.xyz());
+            if length > 0.0 {
+                *plane /= length;
+            }
+        }
+        Self { planes }
+    }
+
+    fn distance(plane: &Vec4, point: &Vec3) -> f32 {
+        plane.x * point.x + plane.y * point.y + plane.z * point.z + plane.w
+    }
+
+    /// Returns false only if the box is completely outside, boxes near the corners can
+    /// still pass.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the plane's normal.
+            let positive = Vec3::new(
+                if plane.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+            Self::distance(plane, &positive) >= 0.0
+        })
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| Self::distance(plane, &sphere.center) >= -sphere.radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frustum_culling() {
+        let projection =
+            nalgebra_glm::perspective_fov_rh_no(70.0f32.to_radians(), 800.0, 600.0, 0.1, 100.0);
+        let view = nalgebra_glm::look_at_rh(
+            &Vec3::new(0.0, 0.0, 5.0),
+            &Vec3::zeros(),
+            &Vec3::new(0.0, 1.0, 0.0),
+        );
+        let frustum = Frustum::from_matrix(&(projection * view));
+
+        let in_front = Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+        assert!(frustum.intersects_aabb(&in_front));
+        assert!(frustum.intersects_sphere(&BoundingSphere::from_aabb(&in_front)));
+
+        let moved = in_front.transform(&nalgebra_glm::translation(&Vec3::new(0.0, 0.0, 20.0)));
+        assert!(!frustum.intersects_aabb(&moved));
+        assert!(!frustum.intersects_sphere(&BoundingSphere::from_aabb(&moved)));
+    }
+
+    #[test]
+    fn test_sphere_screen_size() {
+        let projection = nalgebra_glm::perspective_rh_no(1.0, 90.0f32.to_radians(), 0.1, 100.0);
+        let sphere = BoundingSphere {
+            center: Vec3::new(0.0, 0.0, -10.0),
+            radius: 1.0,
+        };
+        let size = sphere.screen_size(&Vec3::zeros(), &projection);
+        assert!((size - 0.1).abs() < 0.001);
+        let far = sphere.screen_size(&Vec3::new(0.0, 0.0, 10.0), &projection);
+        assert!((far - 0.05).abs() < 0.001);
+        assert!(sphere
+            .screen_size(&sphere.center, &projection)
+            .is_infinite());
+    }
+}
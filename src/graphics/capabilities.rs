@@ -0,0 +1,77 @@
+use super::RenderSettings;
+
+/// What the GPU the renderer picked can do, a resource filled in when the app starts.
+/// Check it before creating pipelines or textures that might not be supported instead of
+/// finding out from a panic:
+/// ```ignore
+/// let capabilities = resources.get::<GpuCapabilities>().unwrap();
+/// if capabilities.compute {
+///     // Create the compute pipeline.
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct GpuCapabilities {
+    pub adapter_name: String,
+    pub backend: wgpu::Backend,
+    pub device_type: wgpu::DeviceType,
+    /// Largest width or height a 2D texture can have.
+    pub max_texture_size: u32,
+    pub max_bind_groups: u32,
+    /// MSAA sample counts that can be used for `RenderSettings::sample_count`.
+    pub sample_counts: &'static [u32],
+    /// Block compressed formats that can be uploaded as they are. Empty until wgpu exposes
+    /// texture compression, until then compressed files are decompressed on load.
+    pub compressed_formats: Vec<wgpu::TextureFormat>,
+    /// Compute shaders, light culling is skipped without them.
+    pub compute: bool,
+    /// Linear filtering of 32 bit float textures like `Rgba32Float`.
+    pub float32_filterable: bool,
+    /// Bytes of push constants a pipeline can use, 0 when they aren't supported.
+    pub max_push_constant_size: u32,
+    pub ray_tracing: bool,
+}
+
+impl GpuCapabilities {
+    // The size every backend wgpu runs on guarantees.
+    const MAX_TEXTURE_SIZE: u32 = 8192;
+
+    pub(crate) fn new(info: &wgpu::AdapterInfo, limits: &wgpu::Limits) -> Self {
+        Self {
+            adapter_name: info.name.clone(),
+            backend: info.backend,
+            device_type: info.device_type.clone(),
+            max_texture_size: Self::MAX_TEXTURE_SIZE,
+            max_bind_groups: limits.max_bind_groups,
+            sample_counts: RenderSettings::supported_sample_counts(info.backend),
+            compressed_formats: Vec::new(),
+            compute: info.backend != wgpu::Backend::Gl,
+            // Neither GLES nor WebGPU guarantee it.
+            float32_filterable: info.backend != wgpu::Backend::Gl
+                && info.backend != wgpu::Backend::BrowserWebGpu,
+            max_push_constant_size: 0,
+            ray_tracing: false,
+        }
+    }
+
+    /// GL, the web, software rasterizers and anything without compute, these get
+    /// `RenderPath::Reduced` unless the app picks a path itself.
+    pub fn is_low_end(&self) -> bool {
+        self.backend == wgpu::Backend::Gl
+            || self.backend == wgpu::Backend::BrowserWebGpu
+            || matches!(self.device_type, wgpu::DeviceType::Cpu)
+            || !self.compute
+    }
+
+    pub fn supports_sample_count(&self, sample_count: u32) -> bool {
+        self.sample_counts.contains(&sample_count)
+    }
+
+    pub fn supports_compressed_format(&self, format: wgpu::TextureFormat) -> bool {
+        self.compressed_formats.contains(&format)
+    }
+
+    /// True if a 2D texture of this size can be created.
+    pub fn supports_texture_size(&self, width: u32, height: u32) -> bool {
+        width.max(height) <= self.max_texture_size
+    }
+}
@@ -0,0 +1,169 @@
+use futures::{channel::oneshot, FutureExt};
+use std::{
+    future::Future,
+    path::PathBuf,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use super::renderer::FRAME_FORMAT;
+
+type MapFuture = Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+/// Set by the renderer on frames that are being captured, post processing draws the final
+/// image into it as well as the screen.
+pub(crate) struct CaptureTarget(pub Option<wgpu::TextureView>);
+
+// Where a finished capture goes.
+pub(crate) enum CaptureDestination {
+    Image(oneshot::Sender<image::RgbaImage>),
+    Png(PathBuf),
+}
+
+// A frame that was copied into a buffer and is waiting to be mapped.
+pub(crate) struct PendingCapture {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    mapping: MapFuture,
+    destination: Option<CaptureDestination>,
+}
+
+impl PendingCapture {
+    /// Copies `texture` into a mappable buffer and starts mapping it.
+    /// The copy has to be submitted before the mapping can finish.
+    pub fn new(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        width: u32,
+        height: u32,
+        destination: CaptureDestination,
+    ) -> Self {
+        // Rows have to be aligned to 256 bytes when copying into a buffer.
+        let padded_bytes_per_row = (width * 4 + 255) & !255;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("frame capture"),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::TextureCopyView {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::BufferCopyView {
+                buffer: &buffer,
+                layout: wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: padded_bytes_per_row,
+                    rows_per_image: 0,
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+        );
+        let mapping = Box::pin(buffer.slice(..).map_async(wgpu::MapMode::Read));
+        Self {
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            mapping,
+            destination: Some(destination),
+        }
+    }
+
+    /// Sends the image on once the buffer is mapped, returns false while it's still waiting.
+    /// The device has to be polled for the mapping to make progress.
+    pub fn finish(&mut self) -> bool {
+        let waker = futures::task::noop_waker();
+        let mapped = match self.mapping.poll_unpin(&mut Context::from_waker(&waker)) {
+            Poll::Pending => return false,
+            Poll::Ready(mapped) => mapped,
+        };
+        if mapped.is_err() {
+            log::warn!("Frame Capture: couldn't read the frame back.");
+            return true;
+        }
+
+        let image = {
+            let bytes = self.buffer.slice(..).get_mapped_range();
+            unpad_bgra(&bytes, self.width, self.height, self.padded_bytes_per_row)
+        };
+        self.buffer.unmap();
+
+        match self.destination.take() {
+            Some(CaptureDestination::Image(sender)) => {
+                // The receiver was dropped so nobody wants the image anymore.
+                sender.send(image).ok();
+            }
+            Some(CaptureDestination::Png(path)) => {
+                if let Err(error) = image.save(&path) {
+                    log::warn!("Frame Capture: couldn't save {}: {}", path.display(), error);
+                }
+            }
+            None => (),
+        }
+        true
+    }
+}
+
+/// Creates a texture the final image can be drawn into and copied out of.
+pub(crate) fn create_capture_texture(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("frame capture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: FRAME_FORMAT,
+        usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+    })
+}
+
+// Drops the row padding and swaps the frame's BGRA pixels into RGBA.
+fn unpad_bgra(
+    bytes: &[u8],
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+) -> image::RgbaImage {
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for row in bytes
+        .chunks(padded_bytes_per_row as usize)
+        .take(height as usize)
+    {
+        for pixel in row[..(width * 4) as usize].chunks_exact(4) {
+            pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+        }
+    }
+    image::RgbaImage::from_raw(width, height, pixels).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unpad_bgra() {
+        // Two rows of a single pixel, each padded out to 8 bytes.
+        let bytes = [1, 2, 3, 4, 0, 0, 0, 0, 5, 6, 7, 8, 0, 0, 0, 0];
+        let image = unpad_bgra(&bytes, 1, 2, 8);
+        assert_eq!(image.get_pixel(0, 0).0, [3, 2, 1, 4]);
+        assert_eq!(image.get_pixel(0, 1).0, [7, 6, 5, 8]);
+    }
+}
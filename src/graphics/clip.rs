@@ -0,0 +1,116 @@
+use super::Viewport;
+
+/// A rectangle in pixels with the origin in the top left, anything 2D drawn outside of it is
+/// cut off. Used for scrollable panels and other UI that shouldn't spill out of it's box.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ClipRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl ClipRect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The part of both rectangles that overlaps, empty if they don't.
+    pub fn intersect(&self, other: &ClipRect) -> ClipRect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+        ClipRect::new(x, y, (right - x).max(0.0), (bottom - y).max(0.0))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0.0 || self.height <= 0.0
+    }
+
+    /// The rectangle as a scissor rect inside a target of this size, None when nothing of it
+    /// is left.
+    pub(crate) fn to_scissor(&self, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+        let rect = self.intersect(&ClipRect::new(0.0, 0.0, width as f32, height as f32));
+        let x = rect.x.floor() as u32;
+        let y = rect.y.floor() as u32;
+        let right = (rect.x + rect.width).ceil() as u32;
+        let bottom = (rect.y + rect.height).ceil() as u32;
+        if rect.is_empty() || right <= x || bottom <= y {
+            return None;
+        }
+        Some((x, y, right - x, bottom - y))
+    }
+}
+
+impl From<Viewport> for ClipRect {
+    fn from(viewport: Viewport) -> Self {
+        Self::new(viewport.x, viewport.y, viewport.width, viewport.height)
+    }
+}
+
+/// Nested clip rects, each one pushed is cut down to the ones around it.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ClipStack {
+    rects: Vec<ClipRect>,
+}
+
+impl ClipStack {
+    pub fn push(&mut self, rect: ClipRect) {
+        let rect = match self.rects.last() {
+            Some(parent) => parent.intersect(&rect),
+            None => rect,
+        };
+        self.rects.push(rect);
+    }
+
+    pub fn pop(&mut self) -> Option<ClipRect> {
+        self.rects.pop()
+    }
+
+    pub fn current(&self) -> Option<ClipRect> {
+        self.rects.last().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.rects.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clip_stack() {
+        let mut stack = ClipStack::default();
+        assert_eq!(stack.current(), None);
+        stack.push(ClipRect::new(0.0, 0.0, 100.0, 100.0));
+        stack.push(ClipRect::new(50.0, 80.0, 100.0, 100.0));
+        assert_eq!(stack.current(), Some(ClipRect::new(50.0, 80.0, 50.0, 20.0)));
+        stack.push(ClipRect::new(200.0, 0.0, 10.0, 10.0));
+        assert!(stack.current().unwrap().is_empty());
+        stack.pop();
+        stack.pop();
+        assert_eq!(stack.current(), Some(ClipRect::new(0.0, 0.0, 100.0, 100.0)));
+    }
+
+    #[test]
+    fn test_clip_rect_to_scissor() {
+        let rect = ClipRect::new(-10.0, 10.5, 50.0, 500.0);
+        assert_eq!(rect.to_scissor(100, 100), Some((0, 10, 40, 90)));
+        assert_eq!(
+            ClipRect::new(120.0, 0.0, 10.0, 10.0).to_scissor(100, 100),
+            None
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra_glm::{Mat4, Vec2, Vec3, Vec4};
+
+/// A raymarched layer of volumetric clouds between two heights, a resource.
+/// The clouds are lit by the first directional light and drawn behind everything else like a sky.
+/// Note: Clouds are only drawn for the first split-screen view.
+#[derive(Debug, Clone)]
+pub struct Clouds {
+    pub enabled: bool,
+    /// World height of the bottom and top of the layer.
+    pub bottom: f32,
+    pub top: f32,
+    /// How much of the sky is covered, 0 is a clear sky and 1 is overcast.
+    pub coverage: f32,
+    /// How thick the clouds are, higher values block more light.
+    pub density: f32,
+    /// How fast the clouds move in world units per second along x and z.
+    pub wind: Vec2,
+    /// How many world units one repeat of the cloud noise covers.
+    pub scale: f32,
+    /// Scales the sky light that fills in the shadowed sides of clouds.
+    pub ambient: f32,
+    /// Raymarching steps through the layer, more looks smoother but costs more.
+    pub steps: u32,
+    /// Steps towards the sun for each step through the layer.
+    pub light_steps: u32,
+    /// Blends each frame with the last one so fewer steps can be used.
+    /// This hides the noise from the jittered steps but can smear fast camera cuts.
+    pub temporal_reprojection: bool,
+    pub(crate) time: f32,
+    pub(crate) frame: u32,
+    pub(crate) previous_view_projection: Option<Mat4>,
+}
+
+impl Default for Clouds {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bottom: 150.0,
+            top: 350.0,
+            coverage: 0.5,
+            density: 0.05,
+            wind: Vec2::new(5.0, 2.0),
+            scale: 2000.0,
+            ambient: 0.4,
+            steps: 32,
+            light_steps: 6,
+            temporal_reprojection: true,
+            time: 0.0,
+            frame: 0,
+            previous_view_projection: None,
+        }
+    }
+}
+
+impl Clouds {
+    /// Call whenever the camera jumps somewhere new so last frame's clouds aren't blended in.
+    pub fn reset_history(&mut self) {
+        self.previous_view_projection = None;
+    }
+
+    pub(crate) fn uniform(
+        &self,
+        view_projection: Mat4,
+        camera_position: Vec3,
+        sun_direction: Vec3,
+        sun_color: Vec3,
+    ) -> CloudUniform {
+        // The first frame doesn't have anything to blend with.
+        let history_weight = match self.previous_view_projection {
+            Some(_) if self.temporal_reprojection => 0.9,
+            _ => 0.0,
+        };
+        let sun_direction = if sun_direction.magnitude() > 0.0 {
+            sun_direction.normalize()
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        };
+        CloudUniform {
+            inverse_view_projection: nalgebra_glm::inverse(&view_projection),
+            previous_view_projection: self.previous_view_projection.unwrap_or(view_projection),
+            camera_position: Vec4::new(
+                camera_position.x,
+                camera_position.y,
+                camera_position.z,
+                self.time,
+            ),
+            sun_direction: Vec4::new(sun_direction.x, sun_direction.y, sun_direction.z, 0.0),
+            sun_color: Vec4::new(sun_color.x, sun_color.y, sun_color.z, self.ambient),
+            layer: Vec4::new(
+                self.bottom,
+                self.top.max(self.bottom + 1.0),
+                self.coverage.max(0.0).min(1.0),
+                self.density.max(0.0),
+            ),
+            wind: Vec4::new(self.wind.x, self.wind.y, 1.0 / self.scale.max(1.0), 0.0),
+            info: Vec4::new(
+                self.steps.max(1) as f32,
+                self.light_steps as f32,
+                (self.frame % 16) as f32,
+                history_weight,
+            ),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CloudUniform {
+    pub inverse_view_projection: Mat4,
+    pub previous_view_projection: Mat4,
+    // (camera position, time)
+    pub camera_position: Vec4,
+    pub sun_direction: Vec4,
+    // (sun color, ambient)
+    pub sun_color: Vec4,
+    // (bottom, top, coverage, density)
+    pub layer: Vec4,
+    // (wind x, wind z, 1 / scale, unused)
+    pub wind: Vec4,
+    // (steps, light steps, frame, history weight)
+    pub info: Vec4,
+}
+
+unsafe impl Zeroable for CloudUniform {}
+unsafe impl Pod for CloudUniform {}
@@ -0,0 +1,116 @@
+use crate::graphics::material::Image;
+
+/// Builds the bind group layout and bind group for a compute pass, bindings are numbered in
+/// the order they're added starting at 0.
+/// ```ignore
+/// let bindings = ComputeBindings::new()
+///     .with_uniform_buffer(&settings_buffer)
+///     .with_storage_buffer(&particle_buffer, false)
+///     .with_storage_texture(&asset_manager.get_image("heightmap"), true);
+/// let layout = bindings.create_layout(&device, "particles");
+/// resource_manager.add_compute_pipeline(
+///     "particles", "particles.shader", &[&layout], &device, &asset_manager,
+/// )?;
+/// let bind_group = bindings.create_bind_group(&device, &layout);
+/// resource_manager.dispatch(&mut encoder, "particles", &[&bind_group], [count / 64, 1, 1])?;
+/// ```
+#[derive(Default)]
+pub struct ComputeBindings<'a> {
+    entries: Vec<wgpu::BindGroupLayoutEntry>,
+    resources: Vec<wgpu::BindingResource<'a>>,
+}
+
+impl<'a> ComputeBindings<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_uniform_buffer(self, buffer: &'a wgpu::Buffer) -> Self {
+        self.with(
+            wgpu::BindingType::UniformBuffer { dynamic: false },
+            wgpu::BindingResource::Buffer(buffer.slice(..)),
+        )
+    }
+
+    /// The buffer needs STORAGE usage.
+    pub fn with_storage_buffer(self, buffer: &'a wgpu::Buffer, readonly: bool) -> Self {
+        self.with(
+            wgpu::BindingType::StorageBuffer {
+                dynamic: false,
+                readonly,
+            },
+            wgpu::BindingResource::Buffer(buffer.slice(..)),
+        )
+    }
+
+    /// Read or write the image with `imageLoad` and `imageStore`, it needs STORAGE usage.
+    /// Use `AssetManager::create_storage_texture` to make one.
+    pub fn with_storage_texture(self, image: &'a Image, readonly: bool) -> Self {
+        self.with(
+            wgpu::BindingType::StorageTexture {
+                dimension: wgpu::TextureViewDimension::D2,
+                component_type: wgpu::TextureComponentType::Float,
+                format: image.format,
+                readonly,
+            },
+            wgpu::BindingResource::TextureView(&image.view),
+        )
+    }
+
+    /// Adds the image's texture and then it's sampler.
+    pub fn with_texture(self, image: &'a Image) -> Self {
+        self.with(
+            wgpu::BindingType::SampledTexture {
+                multisampled: false,
+                component_type: wgpu::TextureComponentType::Float,
+                dimension: wgpu::TextureViewDimension::D2,
+            },
+            wgpu::BindingResource::TextureView(&image.view),
+        )
+        .with(
+            wgpu::BindingType::Sampler { comparison: false },
+            wgpu::BindingResource::Sampler(&image.sampler),
+        )
+    }
+
+    /// Adds any other kind of binding.
+    pub fn with(mut self, ty: wgpu::BindingType, resource: wgpu::BindingResource<'a>) -> Self {
+        self.entries.push(wgpu::BindGroupLayoutEntry {
+            binding: self.entries.len() as u32,
+            visibility: wgpu::ShaderStage::COMPUTE,
+            ty,
+        });
+        self.resources.push(resource);
+        self
+    }
+
+    /// The layout is the same for any resources of the same kind, so it only has to be made
+    /// once per compute pipeline.
+    pub fn create_layout(&self, device: &wgpu::Device, label: &str) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &self.entries,
+            label: Some(label),
+        })
+    }
+
+    pub fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::BindGroup {
+        let bindings = self
+            .resources
+            .iter()
+            .enumerate()
+            .map(|(binding, resource)| wgpu::Binding {
+                binding: binding as u32,
+                resource: resource.clone(),
+            })
+            .collect::<Vec<_>>();
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            bindings: &bindings,
+        })
+    }
+}
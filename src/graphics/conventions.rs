@@ -0,0 +1,148 @@
+use nalgebra_glm::{Mat4, Quat, Vec2, Vec3, Vec4};
+
+/// Where uv (0, 0) is on an image. The engine uses the top left like glTF and wgpu, OpenGL
+/// and OBJ files start at the bottom left.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum UvOrigin {
+    TopLeft,
+    BottomLeft,
+}
+
+impl Default for UvOrigin {
+    fn default() -> Self {
+        UvOrigin::TopLeft
+    }
+}
+
+impl UvOrigin {
+    /// Moves a uv from this convention into the engine's.
+    pub fn to_top_left(&self, uv: Vec2) -> Vec2 {
+        match self {
+            UvOrigin::TopLeft => uv,
+            UvOrigin::BottomLeft => Vec2::new(uv.x, 1.0 - uv.y),
+        }
+    }
+
+    /// Same as `to_top_left` for a (offset x, offset y, scale x, scale y) rect like
+    /// `Sprite::uv_rect`, the offset stays at the rect's top left corner.
+    pub fn rect_to_top_left(&self, rect: Vec4) -> Vec4 {
+        match self {
+            UvOrigin::TopLeft => rect,
+            UvOrigin::BottomLeft => Vec4::new(rect.x, 1.0 - rect.y - rect.w, rect.z, rect.w),
+        }
+    }
+}
+
+/// Which axis points up in a mesh file. The engine is Y up, like glTF.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum UpAxis {
+    Y,
+    /// Blender, 3ds Max and most CAD tools, -Y is forward.
+    Z,
+}
+
+impl Default for UpAxis {
+    fn default() -> Self {
+        UpAxis::Y
+    }
+}
+
+impl UpAxis {
+    /// Rotates this axis onto the engine's Y up.
+    pub fn rotation(&self) -> Quat {
+        match self {
+            UpAxis::Y => Quat::identity(),
+            UpAxis::Z => nalgebra_glm::quat_angle_axis(
+                -std::f32::consts::FRAC_PI_2,
+                &Vec3::new(1.0, 0.0, 0.0),
+            ),
+        }
+    }
+}
+
+/// How a mesh file's conventions are turned into the engine's while it's imported, see
+/// `AssetManager::set_mesh_import`. The defaults leave the file alone.
+/// ```ignore
+/// // A Z up OBJ modeled in centimeters.
+/// asset_manager.set_mesh_import(
+///     "house.obj",
+///     MeshImport::default().with_up_axis(UpAxis::Z).with_scale(0.01),
+/// );
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MeshImport {
+    pub up_axis: UpAxis,
+    /// Multiplied with the positions, for files that aren't in meters. Has to be positive.
+    pub scale: f32,
+    /// Where the file's uvs start, None uses the format's own: the top left for glTF and the
+    /// bottom left for OBJ. Some exporters get this wrong, which shows up as upside down
+    /// textures.
+    pub uv_origin: Option<UvOrigin>,
+}
+
+impl Default for MeshImport {
+    fn default() -> Self {
+        Self {
+            up_axis: UpAxis::Y,
+            scale: 1.0,
+            uv_origin: None,
+        }
+    }
+}
+
+impl MeshImport {
+    pub fn with_up_axis(mut self, up_axis: UpAxis) -> Self {
+        self.up_axis = up_axis;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_uv_origin(mut self, uv_origin: UvOrigin) -> Self {
+        self.uv_origin = Some(uv_origin);
+        self
+    }
+
+    /// Moves points from the file's space into the engine's.
+    pub fn matrix(&self) -> Mat4 {
+        nalgebra_glm::quat_to_mat4(&self.up_axis.rotation())
+            * nalgebra_glm::scaling(&Vec3::new(self.scale, self.scale, self.scale))
+    }
+
+    /// True if the file's uvs have to be flipped for a format that starts at `format_origin`.
+    pub(crate) fn flips_uvs(&self, format_origin: UvOrigin) -> bool {
+        self.uv_origin
+            .map_or(false, |origin| origin != format_origin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uv_origin() {
+        let uv = UvOrigin::BottomLeft.to_top_left(Vec2::new(0.25, 0.0));
+        assert_eq!(uv, Vec2::new(0.25, 1.0));
+        let rect = UvOrigin::BottomLeft.rect_to_top_left(Vec4::new(0.0, 0.0, 0.5, 0.25));
+        assert_eq!(rect, Vec4::new(0.0, 0.75, 0.5, 0.25));
+    }
+
+    #[test]
+    fn test_mesh_import_matrix() {
+        let import = MeshImport::default()
+            .with_up_axis(UpAxis::Z)
+            .with_scale(0.01);
+        let up = import.matrix() * Vec4::new(0.0, 0.0, 100.0, 1.0);
+        assert!((up.xyz() - Vec3::new(0.0, 1.0, 0.0)).norm() < 1e-5);
+        let forward = import.matrix() * Vec4::new(0.0, -100.0, 0.0, 1.0);
+        assert!((forward.xyz() - Vec3::new(0.0, 0.0, 1.0)).norm() < 1e-5);
+        assert!(!import.flips_uvs(UvOrigin::TopLeft));
+        assert!(import
+            .with_uv_origin(UvOrigin::TopLeft)
+            .flips_uvs(UvOrigin::BottomLeft));
+    }
+}
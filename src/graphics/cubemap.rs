@@ -0,0 +1,256 @@
+use futures::channel::oneshot;
+use nalgebra_glm::Vec3;
+use std::{fs::File, io::BufWriter, path::Path, path::PathBuf};
+
+use super::GpuReadback;
+
+// The cube is rendered in the frame's HDR format, four 16 bit floats per pixel.
+const BYTES_PER_PIXEL: u32 = 8;
+// File name endings of the faces, in the order they're rendered.
+const FACE_NAMES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+/// The scene around a point as six HDR images, from `ProbeManager::capture_cubemap`.
+/// Faces are in the order X+ X- Y+ Y- Z+ Z-, every face is `resolution` pixels square and laid
+/// out row by row, colors are linear and not tonemapped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HdrCubemap {
+    pub resolution: u32,
+    pub faces: Vec<Vec<Vec3>>,
+}
+
+impl HdrCubemap {
+    /// The color seen when looking along `direction`.
+    pub fn sample(&self, direction: Vec3) -> Vec3 {
+        let (face, u, v) = face_coordinates(direction);
+        let pixel = |t: f32| ((t * self.resolution as f32) as u32).min(self.resolution - 1);
+        self.faces[face][(pixel(v) * self.resolution + pixel(u)) as usize]
+    }
+
+    /// Unwraps the cube into an equirectangular image that's half as tall as it's wide, the
+    /// layout `ProbeManager::create_environment` and `Skybox::new_hdr` load.
+    pub fn to_equirectangular(&self, width: u32) -> image::ImageBuffer<image::Rgb<f32>, Vec<f32>> {
+        let height = (width / 2).max(1);
+        image::ImageBuffer::from_fn(width, height, |x, y| {
+            let u = (x as f32 + 0.5) / width as f32;
+            let v = (y as f32 + 0.5) / height as f32;
+            let color = self.sample(equirectangular_direction(u, v));
+            image::Rgb([color.x, color.y, color.z])
+        })
+    }
+
+    /// Writes the cube as an equirectangular Radiance HDR file, `width` pixels wide.
+    pub fn save_hdr<T: AsRef<Path>>(&self, path: T, width: u32) -> image::ImageResult<()> {
+        let image = self.to_equirectangular(width);
+        let pixels: Vec<image::Rgb<f32>> = image.pixels().copied().collect();
+        write_hdr(path.as_ref(), &pixels, image.width(), image.height())
+    }
+
+    /// Writes every face as a Radiance HDR file next to `path`, `sky.hdr` becomes
+    /// `sky_px.hdr`, `sky_nx.hdr` and so on.
+    pub fn save_faces<T: AsRef<Path>>(&self, path: T) -> image::ImageResult<()> {
+        let path = path.as_ref();
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("cubemap");
+        for (face, name) in self.faces.iter().zip(FACE_NAMES.iter()) {
+            let pixels: Vec<image::Rgb<f32>> = face
+                .iter()
+                .map(|color| image::Rgb([color.x, color.y, color.z]))
+                .collect();
+            let face_path = path.with_file_name(format!("{}_{}.hdr", stem, name));
+            write_hdr(&face_path, &pixels, self.resolution, self.resolution)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_hdr(
+    path: &Path,
+    pixels: &[image::Rgb<f32>],
+    width: u32,
+    height: u32,
+) -> image::ImageResult<()> {
+    let file = BufWriter::new(File::create(path)?);
+    image::hdr::HdrEncoder::new(file).encode(pixels, width as usize, height as usize)
+}
+
+/// The face a direction points at and where on it, u and v go from 0 to 1 with v down.
+/// These follow the usual cube map layout, the same one the shaders sample with.
+fn face_coordinates(direction: Vec3) -> (usize, f32, f32) {
+    let (x, y, z) = (direction.x, direction.y, direction.z);
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+    let (face, s, t, major) = if ax >= ay && ax >= az {
+        if x > 0.0 {
+            (0, -z, -y, ax)
+        } else {
+            (1, z, -y, ax)
+        }
+    } else if ay >= az {
+        if y > 0.0 {
+            (2, x, z, ay)
+        } else {
+            (3, x, -z, ay)
+        }
+    } else if z > 0.0 {
+        (4, x, -y, az)
+    } else {
+        (5, -x, -y, az)
+    };
+    let major = major.max(0.000_001);
+    (face, (s / major + 1.0) * 0.5, (t / major + 1.0) * 0.5)
+}
+
+/// The direction at a point of an equirectangular image, it matches the projection in
+/// `equirectangular_to_cube_faces_frag.glsl`.
+fn equirectangular_direction(u: f32, v: f32) -> Vec3 {
+    let longitude = (u - 0.5) * std::f32::consts::PI * 2.0;
+    let latitude = (v - 0.5) * std::f32::consts::PI;
+    Vec3::new(
+        latitude.cos() * longitude.sin(),
+        -latitude.sin(),
+        latitude.cos() * longitude.cos(),
+    )
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        31 if bits & 0x3ff == 0 => sign * std::f32::INFINITY,
+        31 => std::f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+// Where a finished cube map goes.
+pub(crate) enum CubemapDestination {
+    Cubemap(oneshot::Sender<HdrCubemap>),
+    Hdr(PathBuf, u32),
+}
+
+// A cube map that's waiting to be rendered at the next probe update.
+pub(crate) struct CubemapRequest {
+    pub position: Vec3,
+    pub resolution: u32,
+    pub destination: CubemapDestination,
+}
+
+// A rendered cube map that was copied into a buffer and is waiting to be read back.
+pub(crate) struct PendingCubemap {
+    readback: GpuReadback,
+    resolution: u32,
+    destination: Option<CubemapDestination>,
+}
+
+impl PendingCubemap {
+    /// Copies all six layers of `texture`, the copy has to be submitted to arrive.
+    pub fn new(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        resolution: u32,
+        destination: CubemapDestination,
+    ) -> Self {
+        let readback = GpuReadback::from_texture(
+            device,
+            encoder,
+            texture,
+            0,
+            wgpu::Origin3d::ZERO,
+            wgpu::Extent3d {
+                width: resolution,
+                height: resolution,
+                depth: 6,
+            },
+            BYTES_PER_PIXEL,
+        );
+        Self {
+            readback,
+            resolution,
+            destination: Some(destination),
+        }
+    }
+
+    /// Sends or saves the cube map once it's arrived, returns false while it's still waiting.
+    pub fn finish(&mut self) -> bool {
+        let bytes = match self.readback.try_take() {
+            None => return false,
+            Some(Ok(bytes)) => bytes,
+            Some(Err(_)) => {
+                log::warn!("Cube Map Capture: couldn't read the cube map back.");
+                return true;
+            }
+        };
+        let pixels: Vec<Vec3> = bytes
+            .chunks_exact(BYTES_PER_PIXEL as usize)
+            .map(|pixel| {
+                let channel = |i: usize| f16_to_f32(u16::from_le_bytes([pixel[i], pixel[i + 1]]));
+                Vec3::new(channel(0), channel(2), channel(4))
+            })
+            .collect();
+        let face_size = (self.resolution * self.resolution) as usize;
+        let cubemap = HdrCubemap {
+            resolution: self.resolution,
+            faces: pixels.chunks(face_size).map(|face| face.to_vec()).collect(),
+        };
+
+        match self.destination.take() {
+            Some(CubemapDestination::Cubemap(sender)) => {
+                // The receiver was dropped so nobody wants the cube map anymore.
+                sender.send(cubemap).ok();
+            }
+            Some(CubemapDestination::Hdr(path, width)) => {
+                if let Err(error) = cubemap.save_hdr(&path, width) {
+                    log::warn!(
+                        "Cube Map Capture: couldn't save {}: {}",
+                        path.display(),
+                        error
+                    );
+                }
+            }
+            None => (),
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cubemap() -> HdrCubemap {
+        // Every face is filled with it's index.
+        HdrCubemap {
+            resolution: 2,
+            faces: (0..6)
+                .map(|face| vec![Vec3::new(face as f32, 0.0, 0.0); 4])
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_cubemap_direction_to_face() {
+        let cubemap = cubemap();
+        let face = |direction: Vec3| cubemap.sample(direction).x;
+        assert_eq!(face(Vec3::new(1.0, 0.2, 0.1)), 0.0);
+        assert_eq!(face(Vec3::new(-1.0, 0.2, 0.1)), 1.0);
+        assert_eq!(face(Vec3::new(0.0, 1.0, 0.5)), 2.0);
+        assert_eq!(face(Vec3::new(0.0, -1.0, 0.5)), 3.0);
+        assert_eq!(face(Vec3::new(0.3, 0.0, 1.0)), 4.0);
+        assert_eq!(face(Vec3::new(0.3, 0.0, -1.0)), 5.0);
+    }
+
+    #[test]
+    fn test_equirectangular_center_looks_forward() {
+        let image = cubemap().to_equirectangular(8);
+        assert_eq!((image.width(), image.height()), (8, 4));
+        assert_eq!(image.get_pixel(4, 2)[0], 4.0);
+        assert_eq!(image.get_pixel(4, 0)[0], 2.0);
+        assert_eq!(image.get_pixel(0, 2)[0], 5.0);
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+    }
+}
@@ -0,0 +1,54 @@
+use nalgebra_glm::Vec3;
+
+use super::{bounds::Aabb, mesh::MeshTangentLine};
+
+/// Controls frustum culling of meshes, a resource.
+/// Meshes whose bounds are completely outside of the active camera's view aren't drawn.
+/// Skinned meshes are never culled since their bounds are for the bind pose.
+pub struct FrustumCulling {
+    pub enabled: bool,
+    /// Draws the world space bounds of every mesh, green when drawn and red when culled.
+    pub draw_bounds: bool,
+    pub(crate) culled_count: usize,
+}
+
+impl Default for FrustumCulling {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            draw_bounds: false,
+            culled_count: 0,
+        }
+    }
+}
+
+impl FrustumCulling {
+    /// How many meshes were culled last frame.
+    pub fn culled_count(&self) -> usize {
+        self.culled_count
+    }
+}
+
+// The 12 edges of the box as a line list for the line pipeline.
+pub(crate) fn aabb_lines(aabb: &Aabb, color: Vec3) -> Vec<MeshTangentLine> {
+    const EDGES: [(usize, usize); 12] = [
+        (0, 1),
+        (2, 3),
+        (4, 5),
+        (6, 7),
+        (0, 2),
+        (1, 3),
+        (4, 6),
+        (5, 7),
+        (0, 4),
+        (1, 5),
+        (2, 6),
+        (3, 7),
+    ];
+    let corners = aabb.corners();
+    EDGES
+        .iter()
+        .flat_map(|(start, end)| vec![corners[*start], corners[*end]])
+        .map(|pos| MeshTangentLine { pos, color })
+        .collect()
+}
@@ -0,0 +1,175 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+
+use super::bounds::Aabb;
+
+/// Segments used for each of a sphere's circles.
+const SPHERE_SEGMENTS: usize = 24;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct DebugVertex {
+    pub position: Vec3,
+    pub color: Vec3,
+}
+
+unsafe impl Zeroable for DebugVertex {}
+unsafe impl Pod for DebugVertex {}
+
+/// Immediate mode lines for debugging physics, culling, light volumes and so on, a resource.
+/// Anything drawn is shown for a single frame on top of the scene, so draw it again every update.
+/// ```ignore
+/// let mut debug = resources.get_mut::<DebugDraw>().unwrap();
+/// debug.draw_aabb(&bounds, Vec3::new(0.0, 1.0, 0.0));
+/// debug.set_depth_test(false);
+/// debug.draw_axes(&transform.matrix, 1.0);
+/// ```
+pub struct DebugDraw {
+    depth_test: bool,
+    pub(crate) lines: Vec<DebugVertex>,
+    /// Lines drawn with the depth test off, they show through everything.
+    pub(crate) overlay_lines: Vec<DebugVertex>,
+    /// The vertex buffer both lists are copied into, it grows as needed.
+    pub(crate) buffer: Option<(wgpu::Buffer, u64)>,
+}
+
+impl Default for DebugDraw {
+    fn default() -> Self {
+        Self {
+            depth_test: true,
+            lines: Vec::new(),
+            overlay_lines: Vec::new(),
+            buffer: None,
+        }
+    }
+}
+
+impl DebugDraw {
+    /// Whether the following shapes are hidden behind the scene, on by default.
+    pub fn set_depth_test(&mut self, depth_test: bool) {
+        self.depth_test = depth_test;
+    }
+
+    pub fn draw_line(&mut self, start: Vec3, end: Vec3, color: Vec3) {
+        let lines = if self.depth_test {
+            &mut self.lines
+        } else {
+            &mut self.overlay_lines
+        };
+        lines.push(DebugVertex {
+            position: start,
+            color,
+        });
+        lines.push(DebugVertex {
+            position: end,
+            color,
+        });
+    }
+
+    /// Draws the 12 edges of the box.
+    pub fn draw_aabb(&mut self, aabb: &Aabb, color: Vec3) {
+        let corner = |index: usize| {
+            Vec3::new(
+                if index & 1 == 0 {
+                    aabb.min.x
+                } else {
+                    aabb.max.x
+                },
+                if index & 2 == 0 {
+                    aabb.min.y
+                } else {
+                    aabb.max.y
+                },
+                if index & 4 == 0 {
+                    aabb.min.z
+                } else {
+                    aabb.max.z
+                },
+            )
+        };
+        // Corners that differ by a single bit share an edge.
+        for index in 0..8 {
+            for bit in [1, 2, 4].iter() {
+                if index & bit == 0 {
+                    self.draw_line(corner(index), corner(index | bit), color);
+                }
+            }
+        }
+    }
+
+    /// Draws a circle around each axis.
+    pub fn draw_sphere(&mut self, center: Vec3, radius: f32, color: Vec3) {
+        let point = |segment: usize| {
+            let angle = segment as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::PI * 2.0;
+            (angle.cos() * radius, angle.sin() * radius)
+        };
+        for segment in 0..SPHERE_SEGMENTS {
+            let (x0, y0) = point(segment);
+            let (x1, y1) = point(segment + 1);
+            self.draw_line(
+                center + Vec3::new(x0, y0, 0.0),
+                center + Vec3::new(x1, y1, 0.0),
+                color,
+            );
+            self.draw_line(
+                center + Vec3::new(x0, 0.0, y0),
+                center + Vec3::new(x1, 0.0, y1),
+                color,
+            );
+            self.draw_line(
+                center + Vec3::new(0.0, x0, y0),
+                center + Vec3::new(0.0, x1, y1),
+                color,
+            );
+        }
+    }
+
+    /// Draws the x, y and z axes of a transform in red, green and blue.
+    pub fn draw_axes(&mut self, matrix: &Mat4, size: f32) {
+        let origin = (matrix * Vec4::new(0.0, 0.0, 0.0, 1.0)).xyz();
+        for axis in 0..3 {
+            let mut direction = Vec4::zeros();
+            direction[axis] = size;
+            let mut color = Vec3::zeros();
+            color[axis] = 1.0;
+            self.draw_line(origin, origin + (matrix * direction).xyz(), color);
+        }
+    }
+
+    /// Drops everything drawn so far, the renderer does this after each frame.
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.overlay_lines.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_shapes() {
+        let mut debug = DebugDraw::default();
+        debug.draw_aabb(
+            &Aabb::new(Vec3::zeros(), Vec3::new(1.0, 2.0, 3.0)),
+            Vec3::zeros(),
+        );
+        assert_eq!(debug.lines.len(), 24);
+        // Every edge runs along a single axis.
+        assert!(debug.lines.chunks(2).all(|edge| {
+            let offset = edge[1].position - edge[0].position;
+            offset.iter().filter(|axis| **axis != 0.0).count() == 1
+        }));
+
+        debug.set_depth_test(false);
+        debug.draw_sphere(Vec3::zeros(), 2.0, Vec3::zeros());
+        assert_eq!(debug.overlay_lines.len(), SPHERE_SEGMENTS * 6);
+        assert!(debug
+            .overlay_lines
+            .iter()
+            .all(|vertex| (nalgebra_glm::length(&vertex.position) - 2.0).abs() < 0.0001));
+
+        debug.clear();
+        assert!(debug.lines.is_empty() && debug.overlay_lines.is_empty());
+    }
+}
@@ -0,0 +1,187 @@
+use bytemuck::{Pod, Zeroable};
+use legion::prelude::Entity;
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+use std::collections::HashMap;
+
+use crate::{
+    assets::Handle,
+    graphics::material::Image,
+    scene::components::{DeformableSurface, Deformer},
+};
+
+// These need to match deformation_comp.glsl and deformation_displace_comp.glsl.
+/// The most deformers that can press into one surface each frame, the rest are ignored.
+pub const MAX_DEFORMERS: usize = 16;
+/// Heightfields store how deep the surface is pressed in, in world units.
+pub const DEFORMATION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct DeformerUniform {
+    // (position, radius)
+    position: Vec4,
+    // (depth, unused..)
+    info: Vec4,
+}
+
+/// Settings for the compute pass that presses deformers into a heightfield.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct DeformationUniform {
+    // (center x, center z, half width, half depth)
+    surface: Vec4,
+    // (deformer count, max depth, depth recovered this frame, unused)
+    info: Vec4,
+    deformers: [DeformerUniform; MAX_DEFORMERS],
+}
+
+/// Settings for the compute pass that pushes one sub mesh's vertices down.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct DisplaceUniform {
+    world: Mat4,
+    inverse_world: Mat4,
+    // (center x, center z, half width, half depth)
+    surface: Vec4,
+    trampled_color: Vec4,
+    // (max depth, vertex count, unused..)
+    info: Vec4,
+}
+
+unsafe impl Zeroable for DeformerUniform {}
+unsafe impl Pod for DeformerUniform {}
+unsafe impl Zeroable for DeformationUniform {}
+unsafe impl Pod for DeformationUniform {}
+unsafe impl Zeroable for DisplaceUniform {}
+unsafe impl Pod for DisplaceUniform {}
+
+fn surface_rect(surface: &DeformableSurface, position: &Vec3) -> Vec4 {
+    Vec4::new(
+        position.x,
+        position.z,
+        surface.size.x * 0.5,
+        surface.size.y * 0.5,
+    )
+}
+
+impl DeformationUniform {
+    /// Deformers that aren't over the surface or are too far above it are skipped.
+    pub(crate) fn new<'a>(
+        surface: &DeformableSurface,
+        position: Vec3,
+        deformers: impl Iterator<Item = (Vec3, &'a Deformer)>,
+        delta_time: f32,
+    ) -> Self {
+        let mut uniform = Self::zeroed();
+        uniform.surface = surface_rect(surface, &position);
+        let half_size = surface.size * 0.5;
+        let touching = deformers.filter(|(deformer_position, deformer)| {
+            let offset = deformer_position - position;
+            offset.x.abs() <= half_size.x + deformer.radius
+                && offset.z.abs() <= half_size.y + deformer.radius
+                && offset.y <= deformer.radius
+        });
+        let mut count = 0;
+        for (deformer_position, deformer) in touching.take(MAX_DEFORMERS) {
+            uniform.deformers[count] = DeformerUniform {
+                position: Vec4::new(
+                    deformer_position.x,
+                    deformer_position.y,
+                    deformer_position.z,
+                    deformer.radius.max(0.0001),
+                ),
+                info: Vec4::new(deformer.depth, 0.0, 0.0, 0.0),
+            };
+            count += 1;
+        }
+        uniform.info = Vec4::new(
+            count as f32,
+            surface.max_depth,
+            surface.recovery * delta_time,
+            0.0,
+        );
+        uniform
+    }
+}
+
+impl DisplaceUniform {
+    pub(crate) fn new(
+        surface: &DeformableSurface,
+        position: Vec3,
+        world: Mat4,
+        vertex_count: usize,
+    ) -> Self {
+        Self {
+            world,
+            inverse_world: nalgebra_glm::inverse(&world),
+            surface: surface_rect(surface, &position),
+            trampled_color: surface.trampled_color,
+            info: Vec4::new(surface.max_depth, vertex_count as f32, 0.0, 0.0),
+        }
+    }
+}
+
+/// GPU state for one sub mesh of a deformed mesh.
+pub(crate) struct DisplaceState {
+    pub uniform: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    pub vertex_count: usize,
+    // The undeformed vertices the displaced ones are made from every frame.
+    #[allow(dead_code)]
+    pub rest_vertices: wgpu::Buffer,
+    #[allow(dead_code)]
+    pub rest_attributes: wgpu::Buffer,
+}
+
+/// GPU state for one `DeformableSurface`.
+pub(crate) struct SurfaceState {
+    /// The mesh the entity had before, it gets it back when the component is removed.
+    pub source_mesh: String,
+    /// The entity's own copy of it's mesh.
+    pub mesh_name: String,
+    pub heightfield: Handle<Image>,
+    pub uniform: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    pub sub_meshes: Vec<DisplaceState>,
+}
+
+/// Keeps the heightfields and mesh copies of every `DeformableSurface`, a resource.
+/// They're removed along with the entity or the component.
+#[derive(Default)]
+pub struct SurfaceDeformation {
+    pub(crate) surfaces: HashMap<Entity, SurfaceState>,
+}
+
+impl SurfaceDeformation {
+    /// The heightfield of a surface, None until the first frame it's deformed.
+    /// It's in the `DEFORMATION_FORMAT` and can be read by custom shaders, e.g. to put
+    /// footprints in a material.
+    pub fn heightfield(&self, entity: Entity) -> Option<&Handle<Image>> {
+        self.surfaces
+            .get(&entity)
+            .map(|surface| &surface.heightfield)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra_glm::Vec2;
+
+    #[test]
+    fn test_deformers_touching_the_surface() {
+        let surface = DeformableSurface::new(Vec2::new(10.0, 10.0)).with_recovery(0.5);
+        let foot = Deformer::new(0.5, 0.2);
+        let deformers = vec![
+            (Vec3::new(1.0, 0.2, 1.0), &foot),
+            // Too high above the surface.
+            (Vec3::new(1.0, 3.0, 1.0), &foot),
+            // Outside of it.
+            (Vec3::new(20.0, 0.0, 1.0), &foot),
+        ];
+        let uniform = DeformationUniform::new(&surface, Vec3::zeros(), deformers.into_iter(), 2.0);
+        assert_eq!(uniform.info.x, 1.0);
+        assert_eq!(uniform.info.z, 1.0);
+        assert_eq!(uniform.surface, Vec4::new(0.0, 0.0, 5.0, 5.0));
+    }
+}
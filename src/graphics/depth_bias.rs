@@ -0,0 +1,53 @@
+use std::hash::{Hash, Hasher};
+
+/// Pushes a pipeline's depth towards or away from the camera, so coplanar geometry like
+/// decals, road markings and detail meshes doesn't z-fight with what it sits on.
+/// Negative values pull it towards the camera.
+#[derive(Debug, Copy, Clone)]
+pub struct DepthBias {
+    /// In the smallest steps the depth buffer can store.
+    pub constant: i32,
+    /// Scaled by the slope of the polygon, surfaces seen at a steep angle need more bias.
+    pub slope_scale: f32,
+    /// The most the bias can move the depth by, 0 doesn't clamp.
+    pub clamp: f32,
+}
+
+impl DepthBias {
+    /// A good start for decals and overlays drawn on top of other geometry.
+    pub const DECAL: DepthBias = DepthBias {
+        constant: -2,
+        slope_scale: -1.0,
+        clamp: 0.0,
+    };
+
+    pub fn new(constant: i32, slope_scale: f32) -> Self {
+        Self {
+            constant,
+            slope_scale,
+            clamp: 0.0,
+        }
+    }
+
+    fn bits(&self) -> (i32, u32, u32) {
+        (
+            self.constant,
+            self.slope_scale.to_bits(),
+            self.clamp.to_bits(),
+        )
+    }
+}
+
+impl PartialEq for DepthBias {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits() == other.bits()
+    }
+}
+
+impl Eq for DepthBias {}
+
+impl Hash for DepthBias {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bits().hash(state);
+    }
+}
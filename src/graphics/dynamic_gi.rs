@@ -0,0 +1,350 @@
+use bytemuck::{Pod, Zeroable};
+use legion::prelude::*;
+use nalgebra_glm::{Vec3, Vec4};
+
+use crate::{
+    graphics::{
+        pipelines::dynamic_gi::DynamicGIResources,
+        renderer::HDR_FORMAT,
+        resources::{render_cube, GPUResourceManager},
+        FramesInFlight, RenderSettings,
+    },
+    scene::Scene,
+};
+
+// This needs to match library/dynamic_gi.glsl.
+/// The most probes the dynamic GI grid can have, bigger grids are shrunk to fit.
+pub const MAX_GI_PROBES: usize = 512;
+/// Width and height of the cube faces each probe renders.
+pub(crate) const GI_PROBE_RESOLUTION: u32 = 16;
+
+/// Dynamic global illumination from a grid of light probes, a resource.
+/// Every frame a few probes render the scene around them into a small cube map, a compute
+/// pass turns it into irradiance and the PBR shader blends the nearest probes for it's
+/// indirect diffuse light instead of using the environment probe. Probes see the light of the
+/// last updates, so light bounces a little further each time the grid is updated.
+/// It's turned on and budgeted with `RenderSettings::dynamic_gi` and
+/// `RenderSettings::gi_probe_updates`, and needs compute shaders.
+/// Note: There's no visibility test between probes and surfaces, light leaks through walls
+/// thinner than the probe spacing.
+#[derive(Debug, Clone)]
+pub struct DynamicGI {
+    /// The probe with the smallest coordinates.
+    pub origin: Vec3,
+    /// Distance between probes along each axis.
+    pub spacing: Vec3,
+    /// Probes along each axis.
+    pub counts: [u32; 3],
+    /// Scales the probe light.
+    pub intensity: f32,
+    /// How much of it's old light a probe keeps when it's updated. Higher hides flickering
+    /// but reacts slower to changes.
+    pub hysteresis: f32,
+    next_probe: usize,
+    // The grid the probes were updated for, they're cleared when it changes.
+    grid: Option<(Vec3, Vec3, [u32; 3])>,
+    // What the uniform buffer holds, it starts out zeroed which turns dynamic GI off.
+    uploaded: DynamicGIUniform,
+}
+
+impl Default for DynamicGI {
+    fn default() -> Self {
+        Self {
+            origin: Vec3::new(-14.0, 0.0, -14.0),
+            spacing: Vec3::new(4.0, 4.0, 4.0),
+            counts: [8, 4, 8],
+            intensity: 1.0,
+            hysteresis: 0.8,
+            next_probe: 0,
+            grid: None,
+            uploaded: DynamicGIUniform::default(),
+        }
+    }
+}
+
+impl DynamicGI {
+    /// Clears the probes so they're lit from scratch, call it after loading a new scene.
+    pub fn reset(&mut self) {
+        self.grid = None;
+    }
+
+    /// The probes along each axis, the longest axis is shortened until they fit in
+    /// `MAX_GI_PROBES`.
+    pub fn probe_counts(&self) -> [u32; 3] {
+        let axis_count = |count: u32| count.max(1).min(MAX_GI_PROBES as u32);
+        let mut counts = [
+            axis_count(self.counts[0]),
+            axis_count(self.counts[1]),
+            axis_count(self.counts[2]),
+        ];
+        while counts.iter().product::<u32>() as usize > MAX_GI_PROBES {
+            let longest = (0..3).max_by_key(|axis| counts[*axis]).unwrap();
+            counts[longest] -= 1;
+        }
+        counts
+    }
+
+    pub fn probe_count(&self) -> usize {
+        self.probe_counts().iter().product::<u32>() as usize
+    }
+
+    /// Where a probe is, they're ordered along x first, then y and then z.
+    pub fn probe_position(&self, index: usize) -> Vec3 {
+        let counts = self.probe_counts();
+        let (x, y) = (counts[0] as usize, counts[1] as usize);
+        let cell = Vec3::new(
+            (index % x) as f32,
+            (index / x % y) as f32,
+            (index / (x * y)) as f32,
+        );
+        self.origin + self.spacing.component_mul(&cell)
+    }
+
+    // The next probes to update, going through the grid in order.
+    fn next_probes(&mut self, budget: u32) -> Vec<usize> {
+        let count = self.probe_count();
+        (0..(budget as usize).min(count))
+            .map(|_| {
+                let probe = self.next_probe % count;
+                self.next_probe = (probe + 1) % count;
+                probe
+            })
+            .collect()
+    }
+
+    // True once when the grid moves or changes size, the probes need to be cleared then.
+    fn grid_changed(&mut self) -> bool {
+        let grid = Some((self.origin, self.spacing, self.probe_counts()));
+        if self.grid == grid {
+            return false;
+        }
+        self.grid = grid;
+        self.next_probe = 0;
+        true
+    }
+
+    // A zeroed uniform when it's off.
+    pub(crate) fn uniform(&self, enabled: bool) -> DynamicGIUniform {
+        if !enabled {
+            return DynamicGIUniform::default();
+        }
+        let counts = self.probe_counts();
+        DynamicGIUniform {
+            origin: Vec4::new(
+                self.origin.x,
+                self.origin.y,
+                self.origin.z,
+                self.intensity.max(0.0),
+            ),
+            spacing: Vec4::new(
+                self.spacing.x.max(0.01),
+                self.spacing.y.max(0.01),
+                self.spacing.z.max(0.01),
+                0.0,
+            ),
+            counts: Vec4::new(counts[0] as f32, counts[1] as f32, counts[2] as f32, 0.0),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct DynamicGIUniform {
+    // (origin, intensity), the intensity is 0.0 when it's off.
+    origin: Vec4,
+    // (spacing, unused)
+    spacing: Vec4,
+    // (probes along x, y and z, unused)
+    counts: Vec4,
+}
+
+unsafe impl Zeroable for DynamicGIUniform {}
+unsafe impl Pod for DynamicGIUniform {}
+
+impl Default for DynamicGIUniform {
+    fn default() -> Self {
+        Self::zeroed()
+    }
+}
+
+// Which probe the compute pass updates.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct ProbeUpdateUniform {
+    // (probe index, hysteresis, unused, unused)
+    info: Vec4,
+}
+
+unsafe impl Zeroable for ProbeUpdateUniform {}
+unsafe impl Pod for ProbeUpdateUniform {}
+
+/// Bytes in the probe buffer, every probe has six colors for it's ambient cube.
+pub(crate) fn gi_probe_buffer_size() -> u64 {
+    (MAX_GI_PROBES * 6 * std::mem::size_of::<Vec4>()) as u64
+}
+
+/// Uploads the grid and re-renders the next probes, runs once a frame after the reflection
+/// probes are rendered. Everything goes into the frame's `FramesInFlight` batch, so each
+/// probe's compute pass runs right after it's cube faces are drawn and before the next probe
+/// draws over them.
+pub(crate) fn update(resources: &mut Resources, scene: &mut Scene) {
+    let (enabled, budget) = {
+        let render_settings = resources.get::<RenderSettings>().unwrap();
+        let resource_manager = resources.get::<GPUResourceManager>().unwrap();
+        (
+            render_settings.dynamic_gi
+                && !render_settings.reduced()
+                && resource_manager
+                    .get_compute_pipeline("dynamic_gi")
+                    .is_some(),
+            render_settings.gi_probe_updates,
+        )
+    };
+
+    let probes = {
+        let mut dynamic_gi = resources.get_mut::<DynamicGI>().unwrap();
+        if !enabled {
+            dynamic_gi.reset();
+        }
+        let uniform = dynamic_gi.uniform(enabled);
+        let grid_changed = enabled && dynamic_gi.grid_changed();
+        // Nothing to do while it's off and the shaders already know.
+        if uniform != dynamic_gi.uploaded || grid_changed {
+            let resource_manager = resources.get::<GPUResourceManager>().unwrap();
+            let device = resources.get::<wgpu::Device>().unwrap();
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("dynamic_gi"),
+            });
+            if grid_changed {
+                let zeros = device.create_buffer_with_data(
+                    &vec![0u8; gi_probe_buffer_size() as usize],
+                    wgpu::BufferUsage::COPY_SRC,
+                );
+                encoder.copy_buffer_to_buffer(
+                    &zeros,
+                    0,
+                    &resource_manager.gi_probe_buffer,
+                    0,
+                    gi_probe_buffer_size(),
+                );
+            }
+            let uniform_buffer = device
+                .create_buffer_with_data(bytemuck::bytes_of(&uniform), wgpu::BufferUsage::COPY_SRC);
+            encoder.copy_buffer_to_buffer(
+                &uniform_buffer,
+                0,
+                &resource_manager.gi_uniform_buffer,
+                0,
+                std::mem::size_of::<DynamicGIUniform>() as u64,
+            );
+            resources
+                .get::<FramesInFlight>()
+                .unwrap()
+                .submit(Some(encoder.finish()));
+            dynamic_gi.uploaded = uniform;
+        }
+
+        if !enabled {
+            return;
+        }
+        let hysteresis = dynamic_gi.hysteresis.max(0.0).min(1.0);
+        dynamic_gi
+            .next_probes(budget)
+            .into_iter()
+            .map(|probe| (probe, dynamic_gi.probe_position(probe), hysteresis))
+            .collect::<Vec<_>>()
+    };
+
+    let cube = resources.get::<DynamicGIResources>().unwrap().cube.clone();
+    for (probe, position, hysteresis) in probes {
+        render_cube(
+            resources,
+            scene,
+            position,
+            &cube,
+            HDR_FORMAT,
+            GI_PROBE_RESOLUTION,
+            true,
+        );
+
+        let resource_manager = resources.get::<GPUResourceManager>().unwrap();
+        let device = resources.get::<wgpu::Device>().unwrap();
+        let uniform_buffer = device.create_buffer_with_data(
+            bytemuck::bytes_of(&ProbeUpdateUniform {
+                info: Vec4::new(probe as f32, hysteresis, 0.0, 0.0),
+            }),
+            wgpu::BufferUsage::UNIFORM,
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("dynamic_gi"),
+            layout: resource_manager
+                .get_bind_group_layout("dynamic_gi")
+                .unwrap(),
+            bindings: &[
+                wgpu::Binding {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(uniform_buffer.slice(..)),
+                },
+                wgpu::Binding {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&cube.texture_view),
+                },
+                wgpu::Binding {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&cube.sampler),
+                },
+                wgpu::Binding {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Buffer(
+                        resource_manager.gi_probe_buffer.slice(..),
+                    ),
+                },
+            ],
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("dynamic_gi"),
+        });
+        // One work group per probe, the six invocations each fill one side of it's ambient cube.
+        if let Err(error) =
+            resource_manager.dispatch(&mut encoder, "dynamic_gi", &[&bind_group], [1, 1, 1])
+        {
+            log::error!("{}", error);
+            return;
+        }
+        resources
+            .get::<FramesInFlight>()
+            .unwrap()
+            .submit(Some(encoder.finish()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_grid() {
+        let mut dynamic_gi = DynamicGI {
+            origin: Vec3::new(1.0, 0.0, 0.0),
+            spacing: Vec3::new(2.0, 3.0, 4.0),
+            counts: [2, 3, 4],
+            ..Default::default()
+        };
+        assert_eq!(dynamic_gi.probe_count(), 24);
+        assert_eq!(dynamic_gi.probe_position(0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(dynamic_gi.probe_position(1), Vec3::new(3.0, 0.0, 0.0));
+        assert_eq!(dynamic_gi.probe_position(2), Vec3::new(1.0, 3.0, 0.0));
+        assert_eq!(dynamic_gi.probe_position(23), Vec3::new(3.0, 6.0, 12.0));
+
+        // The updates wrap around the grid.
+        assert_eq!(dynamic_gi.next_probes(20).len(), 20);
+        assert_eq!(dynamic_gi.next_probes(6), vec![20, 21, 22, 23, 0, 1]);
+        assert!(dynamic_gi.grid_changed());
+        assert_eq!(dynamic_gi.next_probes(1), vec![0]);
+        assert!(!dynamic_gi.grid_changed());
+
+        dynamic_gi.counts = [64, 0, 64];
+        assert_eq!(dynamic_gi.probe_counts(), [23, 1, 22]);
+        assert!(dynamic_gi.probe_count() <= MAX_GI_PROBES);
+    }
+}
@@ -0,0 +1,334 @@
+use futures::channel::oneshot;
+use legion::systems::resource::Resources;
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+};
+
+use super::Renderer;
+
+/// Where exported frames end up.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportTarget {
+    /// Numbered PNG files in a folder, `frame_000000.png`, `frame_000001.png` and so on.
+    Png(PathBuf),
+    /// Numbered 32 bit float EXR files in a folder. They hold the final tonemapped frame turned
+    /// back into linear color, not the scene's HDR buffer.
+    Exr(PathBuf),
+    /// Raw frames piped into ffmpeg, which has to be on the path. `args` go between the input
+    /// and the output file, they pick the codec.
+    Ffmpeg { output: PathBuf, args: Vec<String> },
+}
+
+/// Renders a scene offline, see `Application::start_frame_export`. While it's running the
+/// simulation steps exactly `1 / frame_rate` seconds every frame no matter how long the frame
+/// took to draw, and every frame is written out without the UI. Good for trailers and
+/// cinematics that can't be drawn in real time.
+/// ```ignore
+/// app.start_frame_export(
+///     FrameExport::ffmpeg("trailer.mp4")
+///         .with_frame_rate(60.0)
+///         .with_frame_count(600),
+/// )?;
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameExport {
+    pub target: ExportTarget,
+    pub frame_rate: f32,
+    /// How many frames to write before it stops by itself, None keeps going until
+    /// `Application::stop_frame_export`.
+    pub frame_count: Option<u32>,
+}
+
+impl FrameExport {
+    pub fn png<T: Into<PathBuf>>(directory: T) -> Self {
+        Self::new(ExportTarget::Png(directory.into()))
+    }
+
+    pub fn exr<T: Into<PathBuf>>(directory: T) -> Self {
+        Self::new(ExportTarget::Exr(directory.into()))
+    }
+
+    /// Encodes H.264 by default, which needs a width and height that are even.
+    pub fn ffmpeg<T: Into<PathBuf>>(output: T) -> Self {
+        let args = ["-c:v", "libx264", "-pix_fmt", "yuv420p", "-crf", "18"];
+        Self::new(ExportTarget::Ffmpeg {
+            output: output.into(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        })
+    }
+
+    fn new(target: ExportTarget) -> Self {
+        Self {
+            target,
+            frame_rate: 30.0,
+            frame_count: None,
+        }
+    }
+
+    pub fn with_frame_rate(mut self, frame_rate: f32) -> Self {
+        self.frame_rate = frame_rate;
+        self
+    }
+
+    pub fn with_frame_count(mut self, frame_count: u32) -> Self {
+        self.frame_count = Some(frame_count);
+        self
+    }
+
+    /// Replaces the ffmpeg encoding arguments, does nothing for image sequences.
+    pub fn with_ffmpeg_args(mut self, new_args: &[&str]) -> Self {
+        if let ExportTarget::Ffmpeg { args, .. } = &mut self.target {
+            *args = new_args.iter().map(|arg| arg.to_string()).collect();
+        }
+        self
+    }
+}
+
+// A running export, owned by the application.
+pub(crate) struct FrameExporter {
+    export: FrameExport,
+    width: u32,
+    height: u32,
+    requested: u32,
+    // Captures that haven't arrived yet, in frame order.
+    pending: VecDeque<(u32, oneshot::Receiver<image::RgbaImage>)>,
+    ffmpeg: Option<(Child, BufWriter<ChildStdin>)>,
+    failed: bool,
+}
+
+impl FrameExporter {
+    pub fn start(export: FrameExport, width: u32, height: u32) -> io::Result<Self> {
+        if export.frame_rate.is_nan() || export.frame_rate <= 0.0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the frame rate has to be positive",
+            ));
+        }
+        let ffmpeg = match &export.target {
+            ExportTarget::Png(directory) | ExportTarget::Exr(directory) => {
+                fs::create_dir_all(directory)?;
+                None
+            }
+            ExportTarget::Ffmpeg { output, args } => {
+                let mut child = Command::new("ffmpeg")
+                    .args(&["-y", "-f", "rawvideo", "-pix_fmt", "rgba"])
+                    .args(&["-s", &format!("{}x{}", width, height)])
+                    .args(&["-framerate", &export.frame_rate.to_string()])
+                    .args(&["-i", "-"])
+                    .args(args)
+                    .arg(output)
+                    .stdin(Stdio::piped())
+                    .spawn()?;
+                let stdin = child.stdin.take().unwrap();
+                Some((child, BufWriter::new(stdin)))
+            }
+        };
+        Ok(Self {
+            export,
+            width,
+            height,
+            requested: 0,
+            pending: VecDeque::new(),
+            ffmpeg,
+            failed: false,
+        })
+    }
+
+    /// How far the simulation steps each frame.
+    pub fn frame_duration(&self) -> f32 {
+        1.0 / self.export.frame_rate
+    }
+
+    /// Asks the renderer for the frame that's about to be drawn.
+    pub fn request_frame(&mut self, renderer: &mut Renderer) {
+        if self.wants_frames() {
+            self.pending
+                .push_back((self.requested, renderer.capture_frame()));
+            self.requested += 1;
+        }
+    }
+
+    /// Writes the frames that were read back, stops at the first one that's still on it's way
+    /// so they're written in order.
+    pub fn write_ready(&mut self) {
+        while let Some((index, receiver)) = self.pending.front_mut() {
+            let index = *index;
+            let image = match receiver.try_recv() {
+                Ok(Some(image)) => Some(image),
+                Ok(None) => return,
+                // The capture was dropped, there's nothing to wait for.
+                Err(_) => None,
+            };
+            self.pending.pop_front();
+            match image {
+                Some(image) if !self.failed => {
+                    if let Err(error) = self.write(index, &image) {
+                        log::warn!("Frame Export: couldn't write frame {}: {}", index, error);
+                        self.failed = true;
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// True once every frame was written, or when writing failed.
+    pub fn is_finished(&self) -> bool {
+        self.failed || (!self.wants_frames() && self.pending.is_empty())
+    }
+
+    /// Writes whatever is left and waits for ffmpeg to finish the video.
+    pub fn finish(mut self, renderer: &mut Renderer, resources: &Resources) {
+        renderer.wait_for_captures(resources);
+        self.write_ready();
+        if let Some((mut child, mut stdin)) = self.ffmpeg.take() {
+            // Dropping stdin closes the pipe, that's how ffmpeg knows the video is done.
+            let flushed = stdin.flush();
+            drop(stdin);
+            match (flushed, child.wait()) {
+                (Ok(()), Ok(status)) if status.success() => (),
+                (_, status) => log::warn!("Frame Export: ffmpeg failed: {:?}", status),
+            }
+        }
+        log::info!("Frame Export: wrote {} frames.", self.requested);
+    }
+
+    fn wants_frames(&self) -> bool {
+        self.export
+            .frame_count
+            .map_or(true, |frame_count| self.requested < frame_count)
+    }
+
+    fn write(&mut self, index: u32, image: &image::RgbaImage) -> io::Result<()> {
+        match (&self.export.target, self.ffmpeg.as_mut()) {
+            (ExportTarget::Png(directory), _) => image
+                .save(frame_path(directory, index, "png"))
+                .map_err(|error| io::Error::new(io::ErrorKind::Other, error.to_string())),
+            (ExportTarget::Exr(directory), _) => {
+                let file = File::create(frame_path(directory, index, "exr"))?;
+                write_exr(&mut BufWriter::new(file), image)
+            }
+            (ExportTarget::Ffmpeg { .. }, Some((_, stdin))) => {
+                // ffmpeg was told the size up front, it can't take anything else.
+                if image.dimensions() != (self.width, self.height) {
+                    log::warn!("Frame Export: skipped frame {}, the window resized.", index);
+                    return Ok(());
+                }
+                stdin.write_all(image.as_raw())
+            }
+            (ExportTarget::Ffmpeg { .. }, None) => Ok(()),
+        }
+    }
+}
+
+fn frame_path(directory: &Path, index: u32, extension: &str) -> PathBuf {
+    directory.join(format!("frame_{:06}.{}", index, extension))
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// An uncompressed scanline EXR with 32 bit float A, B, G and R channels. The channels have to
+// be listed alphabetically and each line's values are stored channel by channel.
+fn write_exr<W: Write>(writer: &mut W, image: &image::RgbaImage) -> io::Result<()> {
+    let (width, height) = image.dimensions();
+    let mut header = Vec::new();
+    header.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+
+    let mut channels = Vec::new();
+    for name in &[b'A', b'B', b'G', b'R'] {
+        channels.extend_from_slice(&[*name, 0]);
+        // FLOAT pixels, not linear, 3 reserved bytes, no subsampling.
+        channels.extend_from_slice(&2i32.to_le_bytes());
+        channels.extend_from_slice(&[0, 0, 0, 0]);
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+    let mut window = Vec::new();
+    for value in &[0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&value.to_le_bytes());
+    }
+    let attributes: [(&str, &str, Vec<u8>); 8] = [
+        ("channels", "chlist", channels),
+        ("compression", "compression", vec![0]),
+        ("dataWindow", "box2i", window.clone()),
+        ("displayWindow", "box2i", window),
+        ("lineOrder", "lineOrder", vec![0]),
+        ("pixelAspectRatio", "float", 1.0f32.to_le_bytes().to_vec()),
+        ("screenWindowCenter", "v2f", vec![0; 8]),
+        ("screenWindowWidth", "float", 1.0f32.to_le_bytes().to_vec()),
+    ];
+    for (name, kind, value) in attributes.iter() {
+        header.extend_from_slice(name.as_bytes());
+        header.push(0);
+        header.extend_from_slice(kind.as_bytes());
+        header.push(0);
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(value);
+    }
+    header.push(0);
+    writer.write_all(&header)?;
+
+    // Every line is it's own block, the offset table points at each of them.
+    let line_bytes = width as u64 * 4 * 4;
+    let first_line = header.len() as u64 + height as u64 * 8;
+    for y in 0..height as u64 {
+        writer.write_all(&(first_line + y * (8 + line_bytes)).to_le_bytes())?;
+    }
+    for y in 0..height {
+        writer.write_all(&(y as i32).to_le_bytes())?;
+        writer.write_all(&(line_bytes as i32).to_le_bytes())?;
+        for channel in &[3, 2, 1, 0] {
+            for x in 0..width {
+                let value = image.get_pixel(x, y).0[*channel];
+                let value = if *channel == 3 {
+                    value as f32 / 255.0
+                } else {
+                    srgb_to_linear(value)
+                };
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_exr() {
+        let image = image::RgbaImage::from_raw(2, 1, vec![255, 0, 0, 255, 0, 0, 255, 0]).unwrap();
+        let mut bytes = Vec::new();
+        write_exr(&mut bytes, &image).unwrap();
+        assert_eq!(&bytes[..4], &[0x76, 0x2f, 0x31, 0x01]);
+
+        // One line of 2 pixels with 4 float channels after the y and size.
+        let line = &bytes[bytes.len() - (8 + 2 * 4 * 4)..];
+        assert_eq!(line[..4], 0i32.to_le_bytes());
+        let values: Vec<f32> = line[8..]
+            .chunks_exact(4)
+            .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+            .collect();
+        // A, B, G then R for both pixels.
+        assert_eq!(values, vec![1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_frame_path() {
+        let path = frame_path(Path::new("renders"), 42, "png");
+        assert_eq!(path, Path::new("renders").join("frame_000042.png"));
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+
+/// How big a transient attachment is.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AttachmentSize {
+    /// The same size as the window.
+    Frame,
+    /// The window size times the scale, for example 0.5 for a half resolution bloom buffer.
+    Scaled(f32),
+    Fixed(u32, u32),
+}
+
+/// A texture the engine creates for passes to render into, it's resized with the window.
+#[derive(Debug, Clone)]
+pub struct AttachmentDesc {
+    pub format: wgpu::TextureFormat,
+    pub size: AttachmentSize,
+    /// OUTPUT_ATTACHMENT and SAMPLED are always added.
+    pub usage: wgpu::TextureUsage,
+    /// More than 1 makes a multisampled attachment, it has to be resolved before it's sampled.
+    pub sample_count: u32,
+    /// The contents are only needed between the first and last pass that list it in their reads
+    /// or writes, so it can share memory with other transient attachments used at other times.
+    /// Don't use it for anything kept between frames like history buffers.
+    pub transient: bool,
+}
+
+impl AttachmentDesc {
+    pub fn new(format: wgpu::TextureFormat, size: AttachmentSize) -> Self {
+        Self {
+            format,
+            size,
+            usage: wgpu::TextureUsage::empty(),
+            sample_count: 1,
+            transient: false,
+        }
+    }
+
+    pub fn transient(mut self) -> Self {
+        self.transient = true;
+        self
+    }
+
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    pub fn with_usage(mut self, usage: wgpu::TextureUsage) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    fn extent(&self, frame_width: u32, frame_height: u32) -> (u32, u32) {
+        let (width, height) = match self.size {
+            AttachmentSize::Frame => (frame_width, frame_height),
+            AttachmentSize::Scaled(scale) => (
+                (frame_width as f32 * scale) as u32,
+                (frame_height as f32 * scale) as u32,
+            ),
+            AttachmentSize::Fixed(width, height) => (width, height),
+        };
+        (width.max(1), height.max(1))
+    }
+}
+
+struct Attachment {
+    desc: AttachmentDesc,
+    extent: (u32, u32),
+    // Index into `FrameGraph::textures`.
+    texture: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Pass {
+    name: String,
+    reads: Vec<String>,
+    writes: Vec<String>,
+}
+
+/// Keeps track of which attachments each pass reads and writes, the pipeline manager uses it
+/// to work out the order command buffers are submitted in.
+/// wgpu inserts the barriers between passes for us.
+#[derive(Default)]
+pub(crate) struct FrameGraph {
+    passes: Vec<Pass>,
+    attachments: HashMap<String, Attachment>,
+    // Aliased attachments share one of these.
+    textures: Vec<(wgpu::Texture, wgpu::TextureView)>,
+    // The passes or attachments changed since the textures were made.
+    dirty: bool,
+}
+
+impl FrameGraph {
+    pub fn add_pass(&mut self, name: String, reads: Vec<String>, writes: Vec<String>) {
+        let pass = Pass {
+            name: name.clone(),
+            reads,
+            writes,
+        };
+        match self.passes.iter_mut().find(|pass| pass.name == name) {
+            Some(existing) => *existing = pass,
+            None => self.passes.push(pass),
+        }
+        self.dirty = true;
+    }
+
+    /// Each pass depends on the passes declared before it that write one of the attachments
+    /// it reads or writes.
+    pub fn dependencies(&self) -> Vec<(String, Vec<String>)> {
+        self.passes
+            .iter()
+            .enumerate()
+            .map(|(index, pass)| {
+                let dependencies = self.passes[..index]
+                    .iter()
+                    .filter(|earlier| {
+                        earlier.writes.iter().any(|attachment| {
+                            pass.reads.contains(attachment) || pass.writes.contains(attachment)
+                        })
+                    })
+                    .map(|earlier| earlier.name.clone())
+                    .collect();
+                (pass.name.clone(), dependencies)
+            })
+            .collect()
+    }
+
+    pub fn add_attachment(&mut self, name: String, desc: AttachmentDesc) {
+        self.attachments.insert(
+            name,
+            Attachment {
+                desc,
+                extent: (0, 0),
+                texture: None,
+            },
+        );
+        self.dirty = true;
+    }
+
+    /// Which texture each attachment uses. Transient attachments share a texture when their
+    /// size, format and sample count match and the passes using them, in submission `order`,
+    /// don't overlap. The shared texture gets the usages of all of them. Transient attachments
+    /// no pass lists get their own.
+    pub(crate) fn alias(
+        &self,
+        order: &[String],
+        frame_width: u32,
+        frame_height: u32,
+    ) -> HashMap<String, usize> {
+        let lifetime = |name: &str| {
+            let uses: Vec<usize> = order
+                .iter()
+                .enumerate()
+                .filter(|(_, pass)| {
+                    self.passes.iter().any(|known| {
+                        &known.name == *pass
+                            && (known.reads.iter().any(|read| read == name)
+                                || known.writes.iter().any(|write| write == name))
+                    })
+                })
+                .map(|(index, _)| index)
+                .collect();
+            Some((*uses.first()?, *uses.last()?))
+        };
+
+        let mut names: Vec<&String> = self.attachments.keys().collect();
+        names.sort();
+        let mut transient: Vec<(&String, (usize, usize))> = Vec::new();
+        let mut slots = HashMap::new();
+        let mut count = 0;
+        for name in names {
+            match lifetime(name) {
+                Some(lifetime) if self.attachments[name].desc.transient => {
+                    transient.push((name, lifetime))
+                }
+                _ => {
+                    slots.insert(name.clone(), count);
+                    count += 1;
+                }
+            }
+        }
+
+        // Greedily reuse the first matching texture that's free by the time each one starts.
+        transient.sort_by_key(|(_, (first, _))| *first);
+        let mut shared: Vec<(usize, &AttachmentDesc, (u32, u32), usize)> = Vec::new();
+        for (name, (first, last)) in transient {
+            let desc = &self.attachments[name].desc;
+            let extent = desc.extent(frame_width, frame_height);
+            let free = shared
+                .iter_mut()
+                .find(|(_, other, other_extent, free_after)| {
+                    *free_after < first
+                        && *other_extent == extent
+                        && other.format == desc.format
+                        && other.sample_count == desc.sample_count
+                });
+            match free {
+                Some((slot, _, _, free_after)) => {
+                    *free_after = last;
+                    slots.insert(name.clone(), *slot);
+                }
+                None => {
+                    shared.push((count, desc, extent, last));
+                    slots.insert(name.clone(), count);
+                    count += 1;
+                }
+            }
+        }
+        slots
+    }
+
+    /// Creates any missing attachments and recreates them when a size or the passes changed.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        frame_width: u32,
+        frame_height: u32,
+        order: &[String],
+    ) {
+        let resized = self.attachments.values().any(|attachment| {
+            attachment.texture.is_none()
+                || attachment.extent != attachment.desc.extent(frame_width, frame_height)
+        });
+        if !resized && !self.dirty {
+            return;
+        }
+
+        let slots = self.alias(order, frame_width, frame_height);
+        let count = slots.values().max().map_or(0, |last| last + 1);
+        let mut usages = vec![wgpu::TextureUsage::empty(); count];
+        for (name, attachment) in self.attachments.iter() {
+            usages[slots[name]] |= attachment.desc.usage;
+        }
+        let mut textures: Vec<Option<(wgpu::Texture, wgpu::TextureView)>> =
+            (0..count).map(|_| None).collect();
+        for (name, attachment) in self.attachments.iter_mut() {
+            let slot = slots[name];
+            let extent = attachment.desc.extent(frame_width, frame_height);
+            attachment.texture = Some(slot);
+            attachment.extent = extent;
+            if textures[slot].is_some() {
+                continue;
+            }
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                size: wgpu::Extent3d {
+                    width: extent.0,
+                    height: extent.1,
+                    depth: 1,
+                },
+                mip_level_count: 1,
+                sample_count: attachment.desc.sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: attachment.desc.format,
+                usage: usages[slot]
+                    | wgpu::TextureUsage::OUTPUT_ATTACHMENT
+                    | wgpu::TextureUsage::SAMPLED,
+                label: None,
+            });
+            let view = texture.create_default_view();
+            textures[slot] = Some((texture, view));
+        }
+        self.textures = textures.into_iter().flatten().collect();
+        self.dirty = false;
+    }
+
+    pub fn get_view(&self, name: &str) -> Option<&wgpu::TextureView> {
+        self.attachments
+            .get(name)
+            .and_then(|attachment| attachment.texture)
+            .and_then(|slot| self.textures.get(slot))
+            .map(|(_, view)| view)
+    }
+
+    pub fn get_texture(&self, name: &str) -> Option<&wgpu::Texture> {
+        self.attachments
+            .get(name)
+            .and_then(|attachment| attachment.texture)
+            .and_then(|slot| self.textures.get(slot))
+            .map(|(texture, _)| texture)
+    }
+
+    pub fn get_extent(&self, name: &str) -> Option<(u32, u32)> {
+        self.attachments
+            .get(name)
+            .map(|attachment| attachment.extent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_pass_dependencies() {
+        let mut graph = FrameGraph::default();
+        graph.add_pass("shadow".to_string(), vec![], names(&["shadow_map"]));
+        graph.add_pass("skybox".to_string(), vec![], names(&["frame"]));
+        graph.add_pass("pbr".to_string(), names(&["shadow_map"]), names(&["frame"]));
+        graph.add_pass("post".to_string(), names(&["frame"]), names(&["frame"]));
+
+        let dependencies: HashMap<_, _> = graph.dependencies().into_iter().collect();
+        assert!(dependencies["shadow"].is_empty());
+        assert!(dependencies["skybox"].is_empty());
+        assert_eq!(dependencies["pbr"], names(&["shadow", "skybox"]));
+        assert_eq!(dependencies["post"], names(&["skybox", "pbr"]));
+    }
+
+    #[test]
+    fn test_transient_aliasing() {
+        let mut graph = FrameGraph::default();
+        let half = AttachmentDesc::new(
+            wgpu::TextureFormat::Rgba16Float,
+            AttachmentSize::Scaled(0.5),
+        );
+        graph.add_attachment("ssao".to_string(), half.clone().transient());
+        graph.add_attachment("ssao_blur".to_string(), half.clone().transient());
+        graph.add_attachment("bloom".to_string(), half.clone().transient());
+        graph.add_attachment("history".to_string(), half);
+        graph.add_pass("ssao".to_string(), vec![], names(&["ssao"]));
+        graph.add_pass("blur".to_string(), names(&["ssao"]), names(&["ssao_blur"]));
+        graph.add_pass("pbr".to_string(), names(&["ssao_blur"]), names(&["frame"]));
+        graph.add_pass(
+            "post".to_string(),
+            names(&["frame", "history"]),
+            names(&["bloom"]),
+        );
+
+        let order = names(&["ssao", "blur", "pbr", "post"]);
+        let slots = graph.alias(&order, 800, 600);
+        assert_ne!(slots["ssao"], slots["ssao_blur"]);
+        assert_eq!(slots["ssao"], slots["bloom"]);
+        assert_ne!(slots["history"], slots["bloom"]);
+        assert_ne!(slots["history"], slots["ssao_blur"]);
+        assert_eq!(slots.values().max(), Some(&2));
+    }
+}
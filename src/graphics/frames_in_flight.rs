@@ -0,0 +1,101 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use crate::graphics::{GpuReadback, UniformArena};
+
+/// Keeps the CPU from getting too far ahead of the GPU and batches the frame's work, a
+/// resource. Up to `RenderSettings::frames_in_flight` frames can be submitted before the next
+/// one waits for the oldest to finish, and everything a frame submits goes to the queue in a
+/// single `submit` at the end of it.
+pub struct FramesInFlight {
+    max: usize,
+    frame: u64,
+    // One fence per frame the GPU hasn't finished yet, oldest first.
+    fences: Mutex<VecDeque<GpuReadback>>,
+    // A few bytes every fence copies, the copy finishes when the frame before it has.
+    fence_source: wgpu::Buffer,
+    batch: Mutex<Vec<wgpu::CommandBuffer>>,
+}
+
+impl FramesInFlight {
+    pub(crate) fn new(device: &wgpu::Device, max: u32) -> Self {
+        Self {
+            max: max.max(1) as usize,
+            frame: 0,
+            fences: Mutex::new(VecDeque::new()),
+            fence_source: device.create_buffer_with_data(&[0; 4], wgpu::BufferUsage::COPY_SRC),
+            batch: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Counts up by one every frame.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Which copy of buffered per frame data this frame writes, from 0 up to
+    /// `max_frames_in_flight`. The GPU is done with the copy by the time it comes around again.
+    pub fn slot(&self) -> usize {
+        (self.frame % self.max as u64) as usize
+    }
+
+    pub fn max_frames_in_flight(&self) -> usize {
+        self.max
+    }
+
+    /// Frames that were submitted and the GPU hasn't finished yet.
+    pub fn in_flight(&self) -> usize {
+        self.fences.lock().unwrap().len()
+    }
+
+    /// Adds command buffers to this frame's submission, they run in the order they're added.
+    /// The frame graph's passes are added when the render schedule finishes.
+    pub fn submit<I: IntoIterator<Item = wgpu::CommandBuffer>>(&self, buffers: I) {
+        self.batch.lock().unwrap().extend(buffers);
+    }
+
+    // Waits until there's a free slot for the next frame.
+    pub(crate) fn begin_frame(&self, device: &wgpu::Device) {
+        let mut fences = self.fences.lock().unwrap();
+        loop {
+            while fences
+                .front_mut()
+                .map_or(false, |fence| fence.try_take().is_some())
+            {
+                fences.pop_front();
+            }
+            if fences.len() < self.max {
+                return;
+            }
+            device.poll(wgpu::Maintain::Poll);
+            std::thread::yield_now();
+        }
+    }
+
+    // Uploads the frame's uniforms and submits everything it recorded followed by it's fence.
+    pub(crate) fn end_frame(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        uniform_arena: &mut UniformArena,
+    ) {
+        let mut upload = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("frame_uniforms"),
+        });
+        uniform_arena.flush(device, &mut upload);
+        let mut fence = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("frame_fence"),
+        });
+        let readback = GpuReadback::from_buffer(device, &mut fence, &self.fence_source, 0, 4);
+
+        let batch = std::mem::replace(&mut *self.batch.lock().unwrap(), Vec::new());
+        queue.submit(
+            std::iter::once(upload.finish())
+                .chain(batch)
+                .chain(std::iter::once(fence.finish())),
+        );
+        self.fences.lock().unwrap().push_back(readback);
+
+        self.frame += 1;
+        uniform_arena.begin_frame(device, self.slot());
+    }
+}
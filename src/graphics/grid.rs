@@ -0,0 +1,77 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra_glm::Vec4;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct GridUniform {
+    pub minor_color: Vec4,
+    pub major_color: Vec4,
+    /// x is the height, y the cell size, z the cells per major line and w the fade distance.
+    pub params: Vec4,
+    /// 1.0 in x to color the x and z axes.
+    pub flags: Vec4,
+}
+
+unsafe impl Zeroable for GridUniform {}
+unsafe impl Pod for GridUniform {}
+
+/// An infinite grid on a horizontal plane for editors and tools, a resource.
+/// It's drawn over the scene after the meshes, hidden behind anything that's in front of it and
+/// fades out with distance. Lines are anti aliased and thin lines that would shimmer in the
+/// distance fade out before the major ones do.
+/// ```ignore
+/// resources.get_mut::<EditorGrid>().unwrap().enabled = true;
+/// ```
+pub struct EditorGrid {
+    /// Off by default, turn it on in editor or tool modes.
+    pub enabled: bool,
+    /// Height of the plane the grid lies on.
+    pub height: f32,
+    /// World units between minor lines.
+    pub cell_size: f32,
+    /// Every this many minor lines there's a major line.
+    pub major_every: u32,
+    /// Distance from the camera where the grid is fully faded out.
+    pub fade_distance: f32,
+    pub minor_color: Vec4,
+    pub major_color: Vec4,
+    /// Draws the x axis red and the z axis blue.
+    pub show_axes: bool,
+    pub(crate) uniform: Option<(wgpu::Buffer, wgpu::BindGroup)>,
+}
+
+impl Default for EditorGrid {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            height: 0.0,
+            cell_size: 1.0,
+            major_every: 10,
+            fade_distance: 150.0,
+            minor_color: Vec4::new(0.5, 0.5, 0.5, 0.4),
+            major_color: Vec4::new(0.7, 0.7, 0.7, 0.7),
+            show_axes: true,
+            uniform: None,
+        }
+    }
+}
+
+impl EditorGrid {
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+
+    pub(crate) fn uniform_data(&self) -> GridUniform {
+        GridUniform {
+            minor_color: self.minor_color,
+            major_color: self.major_color,
+            params: Vec4::new(
+                self.height,
+                self.cell_size.max(0.0001),
+                self.major_every.max(1) as f32,
+                self.fade_distance,
+            ),
+            flags: Vec4::new(if self.show_axes { 1.0 } else { 0.0 }, 0.0, 0.0, 0.0),
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use bytemuck::{Pod, Zeroable};
+use legion::prelude::Entity;
+use nalgebra_glm::{Mat4, Vec4};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
+/// Per instance vertex data, read from the second vertex buffer by the instanced pipelines.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct InstanceData {
+    pub world: Mat4,
+    pub color: Vec4,
+}
+
+unsafe impl Zeroable for InstanceData {}
+unsafe impl Pod for InstanceData {}
+
+/// A group of entities that share a mesh, LOD and material and can be drawn in one call.
+#[derive(Debug, Clone)]
+pub(crate) struct InstanceBatch {
+    pub mesh_name: String,
+    pub lod: usize,
+    pub material_index: u32,
+    /// Used to fill bind group 0, the instanced shader doesn't read it.
+    pub first_transform: u32,
+    pub entities: Vec<Entity>,
+    /// The instances in the instance buffer.
+    pub range: Range<u32>,
+}
+
+/// Collects entities that share a mesh, LOD and material, returns the batches and the instance data
+/// in the order the batches point into it.
+pub(crate) fn build_batches<I>(items: I) -> (Vec<InstanceBatch>, Vec<InstanceData>)
+where
+    I: IntoIterator<Item = (Entity, String, usize, u32, u32, InstanceData)>,
+{
+    let mut groups: HashMap<(String, usize, u32), Vec<(Entity, u32, InstanceData)>> =
+        HashMap::new();
+    for (entity, mesh_name, lod, material_index, transform_index, data) in items {
+        groups
+            .entry((mesh_name, lod, material_index))
+            .or_insert_with(Vec::new)
+            .push((entity, transform_index, data));
+    }
+
+    // Sort so batches come out in the same order every frame.
+    let mut groups: Vec<_> = groups.into_iter().collect();
+    groups.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut batches = Vec::with_capacity(groups.len());
+    let mut instance_data = Vec::new();
+    for ((mesh_name, lod, material_index), instances) in groups {
+        let start = instance_data.len() as u32;
+        instance_data.extend(instances.iter().map(|(_, _, data)| *data));
+        batches.push(InstanceBatch {
+            mesh_name,
+            lod,
+            material_index,
+            first_transform: instances[0].1,
+            entities: instances.iter().map(|(entity, _, _)| *entity).collect(),
+            range: start..instance_data.len() as u32,
+        });
+    }
+
+    (batches, instance_data)
+}
+
+/// What the renderer keeps around for entities marked `Static`, a resource.
+#[derive(Default)]
+pub struct StaticCache {
+    /// Transforms whose world matrix is already on the GPU.
+    pub(crate) uploaded: HashSet<u32>,
+    // What the static batches were built from, they're rebuilt when it changes.
+    key: Vec<(Entity, String, usize, u32, u32, Vec4)>,
+    pub(crate) batches: Vec<InstanceBatch>,
+    pub(crate) buffer: Option<wgpu::Buffer>,
+}
+
+impl StaticCache {
+    /// Rebuilds the static batches if the entities, meshes, LODs, materials or colors going
+    /// into them changed, returning the instance data the new buffer needs.
+    pub(crate) fn update_batches<I>(&mut self, items: I) -> Option<Vec<InstanceData>>
+    where
+        I: IntoIterator<Item = (Entity, String, usize, u32, u32, InstanceData)>,
+    {
+        let items: Vec<_> = items.into_iter().collect();
+        let key: Vec<_> = items
+            .iter()
+            .map(
+                |(entity, mesh_name, lod, material_index, transform_index, data)| {
+                    let (material_index, transform_index) = (*material_index, *transform_index);
+                    (
+                        *entity,
+                        mesh_name.clone(),
+                        *lod,
+                        material_index,
+                        transform_index,
+                        data.color,
+                    )
+                },
+            )
+            .collect();
+        if key == self.key {
+            return None;
+        }
+        self.key = key;
+        let (batches, instance_data) = build_batches(items);
+        self.batches = batches;
+        self.buffer = None;
+        Some(instance_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use legion::prelude::*;
+
+    #[test]
+    fn test_build_batches() {
+        let mut world = Universe::new().create_world();
+        let entities = world.insert((), vec![(0u32,), (1u32,), (2u32,)]).to_vec();
+        let data = InstanceData {
+            world: Mat4::identity(),
+            color: Vec4::new(1.0, 1.0, 1.0, 1.0),
+        };
+
+        let (batches, instance_data) = build_batches(vec![
+            (entities[0], "cube.gltf".to_string(), 0, 0, 0, data),
+            (entities[1], "sphere.gltf".to_string(), 0, 0, 1, data),
+            (entities[2], "cube.gltf".to_string(), 0, 0, 2, data),
+        ]);
+
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].mesh_name, "cube.gltf");
+        assert_eq!(batches[0].entities, vec![entities[0], entities[2]]);
+        assert_eq!(batches[0].range, 0..2);
+        assert_eq!(batches[1].range, 2..3);
+        assert_eq!(instance_data.len(), 3);
+    }
+
+    #[test]
+    fn test_build_batches_splits_lods() {
+        let mut world = Universe::new().create_world();
+        let entities = world.insert((), vec![(0u32,), (1u32,)]).to_vec();
+        let data = InstanceData {
+            world: Mat4::identity(),
+            color: Vec4::new(1.0, 1.0, 1.0, 1.0),
+        };
+
+        let (batches, _) = build_batches(vec![
+            (entities[0], "cube.gltf".to_string(), 0, 0, 0, data),
+            (entities[1], "cube.gltf".to_string(), 1, 0, 1, data),
+        ]);
+
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].lod, 0);
+        assert_eq!(batches[1].lod, 1);
+    }
+
+    #[test]
+    fn test_static_batches_rebuild_on_change() {
+        let mut world = Universe::new().create_world();
+        let entities = world.insert((), vec![(0u32,), (1u32,)]).to_vec();
+        let data = InstanceData {
+            world: Mat4::identity(),
+            color: Vec4::new(1.0, 1.0, 1.0, 1.0),
+        };
+        let items = |count: usize| {
+            entities
+                .iter()
+                .take(count)
+                .enumerate()
+                .map(|(i, entity)| (*entity, "cube.gltf".to_string(), 0, 0, i as u32, data))
+                .collect::<Vec<_>>()
+        };
+
+        let mut cache = StaticCache::default();
+        assert_eq!(
+            cache.update_batches(items(2)).map(|data| data.len()),
+            Some(2)
+        );
+        assert!(cache.update_batches(items(2)).is_none());
+        assert_eq!(
+            cache.update_batches(items(1)).map(|data| data.len()),
+            Some(1)
+        );
+        assert_eq!(cache.batches[0].range, 0..1);
+    }
+}
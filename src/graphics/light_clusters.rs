@@ -0,0 +1,121 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra_glm::{Mat4, Quat, Vec3, Vec4};
+
+// These need to match library/clusters.glsl.
+pub(crate) const CLUSTER_X: u32 = 16;
+pub(crate) const CLUSTER_Y: u32 = 9;
+pub(crate) const CLUSTER_Z: u32 = 24;
+pub(crate) const CLUSTER_COUNT: u32 = CLUSTER_X * CLUSTER_Y * CLUSTER_Z;
+/// Lights past this in a single cluster are ignored.
+pub(crate) const MAX_LIGHTS_PER_CLUSTER: u32 = 64;
+/// The most point and spot lights that can be on screen, the closest ones to the camera win.
+pub const MAX_CLUSTERED_LIGHTS: usize = 512;
+
+/// A point or spot light as the shaders see it.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GpuLight {
+    // (position, range)
+    pub position: Vec4,
+    // (color * intensity, 0 for point lights and 1 for spot lights)
+    pub color: Vec4,
+    // (direction, cos of the outer angle)
+    pub direction: Vec4,
+    // (cos of the inner angle, contact shadow length or 0.0, shadow tile or -1.0, unused)
+    pub spot: Vec4,
+    // (the light's right axis, first cookie layer or -1.0)
+    pub cookie: Vec4,
+}
+
+unsafe impl Zeroable for GpuLight {}
+unsafe impl Pod for GpuLight {}
+
+impl GpuLight {
+    pub fn point(position: Vec3, color: Vec3, intensity: f32, range: f32) -> Self {
+        Self {
+            position: Vec4::new(position.x, position.y, position.z, range),
+            color: Vec4::new(
+                color.x * intensity,
+                color.y * intensity,
+                color.z * intensity,
+                0.0,
+            ),
+            direction: Vec4::zeros(),
+            spot: Vec4::new(0.0, 0.0, -1.0, 0.0),
+            cookie: Vec4::new(0.0, 0.0, 0.0, -1.0),
+        }
+    }
+
+    /// Angles are in degrees.
+    pub fn spot(
+        position: Vec3,
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Self {
+        let direction = nalgebra_glm::normalize(&direction);
+        let outer = outer_angle.to_radians().cos();
+        // Keep the inner cone inside the outer one so the falloff never divides by zero.
+        let inner = inner_angle.to_radians().cos().max(outer + 0.0001);
+        let mut light = Self::point(position, color, intensity, range);
+        light.color.w = 1.0;
+        light.direction = Vec4::new(direction.x, direction.y, direction.z, outer);
+        light.spot = Vec4::new(inner, 0.0, -1.0, 0.0);
+        light
+    }
+
+    /// A length of 0.0 turns contact shadows off.
+    pub fn with_contact_shadows(mut self, length: f32) -> Self {
+        self.spot.y = length;
+        self
+    }
+
+    /// Spot lights only, the tile of the shadow atlas the shadow system rendered it into.
+    pub fn with_shadow(mut self, tile: usize) -> Self {
+        self.spot.z = tile as f32;
+        self
+    }
+
+    /// Cookies are projected along the light's -Z, point lights get their direction from the
+    /// rotation here too.
+    pub fn with_cookie(mut self, rotation: &Quat, layer: u32) -> Self {
+        let forward = nalgebra_glm::quat_rotate_vec3(rotation, &Vec3::new(0.0, 0.0, -1.0));
+        let right = nalgebra_glm::quat_rotate_vec3(rotation, &Vec3::new(1.0, 0.0, 0.0));
+        self.direction = Vec4::new(forward.x, forward.y, forward.z, self.direction.w);
+        self.cookie = Vec4::new(right.x, right.y, right.z, layer as f32);
+        self
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ClusterUniform {
+    pub inverse_projection: Mat4,
+    pub view: Mat4,
+    // (viewport width, viewport height, near, far)
+    pub screen: Vec4,
+    // (light count, viewport x, viewport y, unused)
+    pub counts: Vec4,
+}
+
+unsafe impl Zeroable for ClusterUniform {}
+unsafe impl Pod for ClusterUniform {}
+
+impl Default for ClusterUniform {
+    fn default() -> Self {
+        Self {
+            inverse_projection: Mat4::identity(),
+            view: Mat4::identity(),
+            screen: Vec4::new(1.0, 1.0, 0.1, 100.0),
+            counts: Vec4::zeros(),
+        }
+    }
+}
+
+/// Size in bytes of the cluster buffer, each cluster stores it's light count then it's light indices.
+pub(crate) fn cluster_buffer_size() -> u64 {
+    (CLUSTER_COUNT * (MAX_LIGHTS_PER_CLUSTER + 1)) as u64 * std::mem::size_of::<u32>() as u64
+}
@@ -0,0 +1,157 @@
+use image::{imageops, RgbaImage};
+use std::collections::HashMap;
+
+use crate::{AssetError, AssetManager};
+
+/// Width and height every cookie is resized to.
+pub(crate) const COOKIE_SIZE: u32 = 256;
+/// Layers in the cookie texture, spot light cookies take one and point light cookies six.
+pub(crate) const COOKIE_LAYERS: u32 = 24;
+pub(crate) const COOKIE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// Which layers of the cookie texture each cookie was loaded into. Cookies are loaded the
+/// first time a light uses them and stay until the app closes.
+#[derive(Default)]
+pub(crate) struct LightCookies {
+    // Keyed by the file and if it's a cube cookie. None when the cookie couldn't be loaded,
+    // so it's only reported once.
+    layers: HashMap<(String, bool), Option<u32>>,
+    next_layer: u32,
+}
+
+impl LightCookies {
+    /// Returns the first layer of the cookie, it's loaded into `texture` the first time.
+    /// Cube cookies take six layers, one per face.
+    pub fn layer(
+        &mut self,
+        name: &str,
+        cube: bool,
+        asset_manager: &AssetManager,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) -> Option<u32> {
+        let key = (name.to_string(), cube);
+        if let Some(layer) = self.layers.get(&key) {
+            return *layer;
+        }
+        let layer = match self.load(name, cube, asset_manager, device, encoder, texture) {
+            Ok(layer) => Some(layer),
+            Err(error) => {
+                log::error!("Could not load the light cookie: {}", error);
+                None
+            }
+        };
+        self.layers.insert(key, layer);
+        layer
+    }
+
+    fn load(
+        &mut self,
+        name: &str,
+        cube: bool,
+        asset_manager: &AssetManager,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) -> Result<u32, AssetError> {
+        let bytes = asset_manager.vfs().read(name)?;
+        let image = image::load_from_memory(&bytes)
+            .map_err(|error| AssetError::from_image(name, error))?
+            .to_rgba();
+        let faces =
+            cookie_faces(&image, cube).map_err(|message| AssetError::unsupported(name, message))?;
+        if self.next_layer + faces.len() as u32 > COOKIE_LAYERS {
+            return Err(AssetError::unsupported(
+                name,
+                format!("all {} cookie layers are used", COOKIE_LAYERS),
+            ));
+        }
+
+        let first_layer = self.next_layer;
+        for (index, face) in faces.into_iter().enumerate() {
+            // Rows are 1024 bytes, already aligned for buffer copies.
+            let buffer =
+                device.create_buffer_with_data(&face.into_raw(), wgpu::BufferUsage::COPY_SRC);
+            encoder.copy_buffer_to_texture(
+                wgpu::BufferCopyView {
+                    buffer: &buffer,
+                    layout: wgpu::TextureDataLayout {
+                        offset: 0,
+                        bytes_per_row: COOKIE_SIZE * 4,
+                        rows_per_image: 0,
+                    },
+                },
+                wgpu::TextureCopyView {
+                    texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: first_layer + index as u32,
+                    },
+                },
+                wgpu::Extent3d {
+                    width: COOKIE_SIZE,
+                    height: COOKIE_SIZE,
+                    depth: 1,
+                },
+            );
+        }
+        self.next_layer += if cube { 6 } else { 1 };
+        Ok(first_layer)
+    }
+}
+
+// Cube cookies are six square faces side by side, in the order X+ X- Y+ Y- Z+ Z- like
+// `HdrCubemap`. Every face is resized to `COOKIE_SIZE`.
+fn cookie_faces(image: &RgbaImage, cube: bool) -> Result<Vec<RgbaImage>, String> {
+    let resize = |image: &RgbaImage| {
+        imageops::resize(
+            image,
+            COOKIE_SIZE,
+            COOKIE_SIZE,
+            imageops::FilterType::Triangle,
+        )
+    };
+    if !cube {
+        return Ok(vec![resize(image)]);
+    }
+    let (width, height) = image.dimensions();
+    if width != height * 6 {
+        return Err("cube cookies need six square faces side by side".to_string());
+    }
+    Ok((0..6)
+        .map(|face| {
+            let face = imageops::crop_imm(image, face * height, 0, height, height).to_image();
+            resize(&face)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cube_cookie_faces() {
+        let mut strip = RgbaImage::new(60, 10);
+        // Paints the Y+ face so the order can be checked.
+        for y in 0..10 {
+            for x in 20..30 {
+                strip.put_pixel(x, y, image::Rgba([255, 0, 0, 255]));
+            }
+        }
+        let faces = cookie_faces(&strip, true).unwrap();
+        assert_eq!(faces.len(), 6);
+        assert_eq!(faces[2].dimensions(), (COOKIE_SIZE, COOKIE_SIZE));
+        assert_eq!(faces[2].get_pixel(COOKIE_SIZE / 2, COOKIE_SIZE / 2)[0], 255);
+        assert_eq!(faces[1].get_pixel(COOKIE_SIZE / 2, COOKIE_SIZE / 2)[0], 0);
+
+        assert!(cookie_faces(&RgbaImage::new(40, 10), true).is_err());
+        assert_eq!(
+            cookie_faces(&RgbaImage::new(40, 10), false).unwrap().len(),
+            1
+        );
+    }
+}
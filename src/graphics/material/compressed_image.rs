@@ -0,0 +1,340 @@
+use image::RgbaImage;
+
+use crate::AssetError;
+
+/// Block compressed formats we know how to read.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum BlockFormat {
+    BC1,
+    BC3,
+    BC4,
+    BC5,
+}
+
+impl BlockFormat {
+    fn block_size(&self) -> usize {
+        match self {
+            BlockFormat::BC1 | BlockFormat::BC4 => 8,
+            BlockFormat::BC3 | BlockFormat::BC5 => 16,
+        }
+    }
+}
+
+/// A block compressed image read from a DDS or KTX2 file.
+pub(crate) struct CompressedImage {
+    pub format: BlockFormat,
+    /// None if the file doesn't say, in that case we guess from the file name like other images.
+    pub srgb: Option<bool>,
+    pub width: u32,
+    pub height: u32,
+    /// The compressed data for each mip level, starting with the full size image.
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl CompressedImage {
+    pub fn load(path: &str) -> Result<Self, AssetError> {
+        let bytes = std::fs::read(path).map_err(|error| AssetError::io(path, error))?;
+        Self::from_bytes(path, &bytes)
+    }
+
+    /// Parses a file that was already read, `path` is only used to pick the format.
+    pub fn from_bytes(path: &str, bytes: &[u8]) -> Result<Self, AssetError> {
+        let result = if path.ends_with(".dds") {
+            Self::from_dds(bytes)
+        } else if path.ends_with(".ktx2") {
+            Self::from_ktx2(bytes)
+        } else {
+            Err("Unknown compressed image type".to_string())
+        };
+        result.map_err(|message| AssetError::unsupported(path, message))
+    }
+
+    fn from_dds(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < 128 || &bytes[0..4] != b"DDS " {
+            return Err("Not a DDS file".to_string());
+        }
+        let height = read_u32(bytes, 12);
+        let width = read_u32(bytes, 16);
+        let mip_count = read_u32(bytes, 28).max(1);
+        let four_cc = &bytes[84..88];
+
+        let (format, srgb, data_start) = match four_cc {
+            b"DXT1" => (BlockFormat::BC1, None, 128),
+            b"DXT5" => (BlockFormat::BC3, None, 128),
+            b"ATI1" | b"BC4U" => (BlockFormat::BC4, Some(false), 128),
+            b"ATI2" | b"BC5U" => (BlockFormat::BC5, Some(false), 128),
+            b"DX10" => {
+                if bytes.len() < 148 {
+                    return Err("DDS file is too small".to_string());
+                }
+                let (format, srgb) = match read_u32(bytes, 128) {
+                    71 => (BlockFormat::BC1, false),
+                    72 => (BlockFormat::BC1, true),
+                    77 => (BlockFormat::BC3, false),
+                    78 => (BlockFormat::BC3, true),
+                    80 => (BlockFormat::BC4, false),
+                    83 => (BlockFormat::BC5, false),
+                    dxgi_format => {
+                        return Err(format!("Unsupported DXGI format {}", dxgi_format));
+                    }
+                };
+                (format, Some(srgb), 148)
+            }
+            _ => {
+                return Err(format!(
+                    "Unsupported DDS format {}",
+                    String::from_utf8_lossy(four_cc)
+                ))
+            }
+        };
+
+        let mut levels = Vec::new();
+        let mut offset = data_start;
+        for level in 0..mip_count {
+            let size = level_size(format, width >> level, height >> level);
+            if offset + size > bytes.len() {
+                break;
+            }
+            levels.push(bytes[offset..offset + size].to_vec());
+            offset += size;
+        }
+        if levels.is_empty() {
+            return Err("DDS file has no image data".to_string());
+        }
+
+        Ok(Self {
+            format,
+            srgb,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    fn from_ktx2(bytes: &[u8]) -> Result<Self, String> {
+        const IDENTIFIER: [u8; 12] = [
+            0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+        ];
+        if bytes.len() < 80 || bytes[0..12] != IDENTIFIER {
+            return Err("Not a KTX2 file".to_string());
+        }
+        let (format, srgb) = match read_u32(bytes, 12) {
+            131 | 133 => (BlockFormat::BC1, false),
+            132 | 134 => (BlockFormat::BC1, true),
+            137 => (BlockFormat::BC3, false),
+            138 => (BlockFormat::BC3, true),
+            139 => (BlockFormat::BC4, false),
+            141 => (BlockFormat::BC5, false),
+            vk_format => return Err(format!("Unsupported Vulkan format {}", vk_format)),
+        };
+        let width = read_u32(bytes, 20);
+        let height = read_u32(bytes, 24);
+        let level_count = read_u32(bytes, 40).max(1) as usize;
+        if read_u32(bytes, 44) != 0 {
+            return Err("Supercompressed KTX2 files aren't supported".to_string());
+        }
+
+        let mut levels = Vec::new();
+        for level in 0..level_count {
+            let index = 80 + level * 24;
+            if index + 16 > bytes.len() {
+                return Err("KTX2 level index is truncated".to_string());
+            }
+            let offset = read_u64(bytes, index) as usize;
+            let length = read_u64(bytes, index + 8) as usize;
+            if offset + length > bytes.len() {
+                return Err("KTX2 level data is truncated".to_string());
+            }
+            levels.push(bytes[offset..offset + length].to_vec());
+        }
+
+        Ok(Self {
+            format,
+            srgb: Some(srgb),
+            width,
+            height,
+            levels,
+        })
+    }
+
+    /// Decompresses every level to RGBA8.
+    /// The engine's wgpu version can't sample block compressed textures yet so we always take this path.
+    pub fn decompress(&self) -> Vec<RgbaImage> {
+        self.levels
+            .iter()
+            .enumerate()
+            .map(|(level, data)| {
+                let width = (self.width >> level).max(1);
+                let height = (self.height >> level).max(1);
+                decompress_level(self.format, data, width, height)
+            })
+            .collect()
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut value = [0u8; 4];
+    value.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(value)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(value)
+}
+
+fn level_size(format: BlockFormat, width: u32, height: u32) -> usize {
+    let blocks_wide = ((width.max(1) + 3) / 4) as usize;
+    let blocks_high = ((height.max(1) + 3) / 4) as usize;
+    blocks_wide * blocks_high * format.block_size()
+}
+
+fn decompress_level(format: BlockFormat, data: &[u8], width: u32, height: u32) -> RgbaImage {
+    let mut image = RgbaImage::new(width, height);
+    let blocks_wide = (width + 3) / 4;
+    let blocks_high = (height + 3) / 4;
+    let block_size = format.block_size();
+
+    for block_y in 0..blocks_high {
+        for block_x in 0..blocks_wide {
+            let start = ((block_y * blocks_wide + block_x) as usize) * block_size;
+            if start + block_size > data.len() {
+                return image;
+            }
+            let block = &data[start..start + block_size];
+            let pixels = match format {
+                BlockFormat::BC1 => decode_bc1(block, true),
+                BlockFormat::BC3 => {
+                    let mut pixels = decode_bc1(&block[8..16], false);
+                    let alpha = decode_bc4(&block[0..8]);
+                    for (pixel, alpha) in pixels.iter_mut().zip(alpha.iter()) {
+                        pixel[3] = *alpha;
+                    }
+                    pixels
+                }
+                BlockFormat::BC4 => {
+                    let red = decode_bc4(block);
+                    let mut pixels = [[0, 0, 0, 255]; 16];
+                    for (pixel, red) in pixels.iter_mut().zip(red.iter()) {
+                        pixel[0] = *red;
+                        pixel[1] = *red;
+                        pixel[2] = *red;
+                    }
+                    pixels
+                }
+                BlockFormat::BC5 => {
+                    let red = decode_bc4(&block[0..8]);
+                    let green = decode_bc4(&block[8..16]);
+                    let mut pixels = [[0, 0, 0, 255]; 16];
+                    for (i, pixel) in pixels.iter_mut().enumerate() {
+                        pixel[0] = red[i];
+                        pixel[1] = green[i];
+                    }
+                    pixels
+                }
+            };
+
+            for (i, pixel) in pixels.iter().enumerate() {
+                let x = block_x * 4 + (i % 4) as u32;
+                let y = block_y * 4 + (i / 4) as u32;
+                if x < width && y < height {
+                    image.put_pixel(x, y, image::Rgba(*pixel));
+                }
+            }
+        }
+    }
+
+    image
+}
+
+fn rgb565(color: u16) -> [u32; 3] {
+    let r = ((color >> 11) & 0x1F) as u32;
+    let g = ((color >> 5) & 0x3F) as u32;
+    let b = (color & 0x1F) as u32;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+/// Decodes a BC1 color block, BC3 blocks always use the 4 color mode.
+fn decode_bc1(block: &[u8], allow_alpha: bool) -> [[u8; 4]; 16] {
+    let color0 = u16::from_le_bytes([block[0], block[1]]);
+    let color1 = u16::from_le_bytes([block[2], block[3]]);
+    let c0 = rgb565(color0);
+    let c1 = rgb565(color1);
+
+    let mut palette = [[0u8; 4]; 4];
+    palette[0] = [c0[0] as u8, c0[1] as u8, c0[2] as u8, 255];
+    palette[1] = [c1[0] as u8, c1[1] as u8, c1[2] as u8, 255];
+    if color0 > color1 || !allow_alpha {
+        for channel in 0..3 {
+            palette[2][channel] = ((2 * c0[channel] + c1[channel]) / 3) as u8;
+            palette[3][channel] = ((c0[channel] + 2 * c1[channel]) / 3) as u8;
+        }
+        palette[2][3] = 255;
+        palette[3][3] = 255;
+    } else {
+        for channel in 0..3 {
+            palette[2][channel] = ((c0[channel] + c1[channel]) / 2) as u8;
+        }
+        palette[2][3] = 255;
+        palette[3] = [0, 0, 0, 0];
+    }
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    let mut pixels = [[0u8; 4]; 16];
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        *pixel = palette[((indices >> (i * 2)) & 0x3) as usize];
+    }
+    pixels
+}
+
+/// Decodes a single channel BC4 block, also used for BC3 alpha and each BC5 channel.
+fn decode_bc4(block: &[u8]) -> [u8; 16] {
+    let a0 = block[0] as u32;
+    let a1 = block[1] as u32;
+    let mut palette = [0u8; 8];
+    palette[0] = a0 as u8;
+    palette[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u32) * a0 + i as u32 * a1) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u32) * a0 + i as u32 * a1) / 5) as u8;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    let mut bits = 0u64;
+    for (i, byte) in block[2..8].iter().enumerate() {
+        bits |= (*byte as u64) << (i * 8);
+    }
+    let mut values = [0u8; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = palette[((bits >> (i * 3)) & 0x7) as usize];
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_bc1_block() {
+        // Pure red and pure blue end points, first row uses each palette entry once.
+        let block = [0x00, 0xF8, 0x1F, 0x00, 0b11_10_01_00, 0, 0, 0];
+        let pixels = decode_bc1(&block, true);
+        assert_eq!(pixels[0], [255, 0, 0, 255]);
+        assert_eq!(pixels[1], [0, 0, 255, 255]);
+        assert_eq!(pixels[2], [170, 0, 85, 255]);
+        assert_eq!(pixels[3], [85, 0, 170, 255]);
+        assert_eq!(pixels[4], [255, 0, 0, 255]);
+    }
+}
@@ -0,0 +1,104 @@
+use std::{collections::HashMap, sync::Arc};
+
+use super::{image::DecodedImage, Image, SamplerInfo, TextureSlot};
+
+/// Built in 1x1 images the asset manager creates at startup, materials use them for textures
+/// they don't have or that failed to load.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FallbackTexture {
+    White,
+    Black,
+    /// A flat tangent space normal.
+    Normal,
+    /// Mid gray roughness and metallic.
+    MetallicRoughness,
+}
+
+impl FallbackTexture {
+    pub const ALL: [FallbackTexture; 4] = [
+        FallbackTexture::White,
+        FallbackTexture::Black,
+        FallbackTexture::Normal,
+        FallbackTexture::MetallicRoughness,
+    ];
+
+    /// The name the image is registered under in the asset manager.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FallbackTexture::White => "fallback_white",
+            FallbackTexture::Black => "fallback_black",
+            FallbackTexture::Normal => "fallback_normal",
+            FallbackTexture::MetallicRoughness => "fallback_metallic_roughness",
+        }
+    }
+
+    /// The fallback used when a PBR material's texture is missing.
+    pub fn for_slot(slot: TextureSlot) -> Self {
+        match slot {
+            TextureSlot::Main | TextureSlot::Occlusion => FallbackTexture::White,
+            TextureSlot::Normal => FallbackTexture::Normal,
+            TextureSlot::Roughness => FallbackTexture::MetallicRoughness,
+            // A missing emissive texture shouldn't make the material glow.
+            TextureSlot::Emissive => FallbackTexture::Black,
+        }
+    }
+
+    fn pixel(&self) -> [u8; 4] {
+        match self {
+            FallbackTexture::White => [255, 255, 255, 255],
+            FallbackTexture::Black => [0, 0, 0, 255],
+            FallbackTexture::Normal => [128, 128, 255, 255],
+            FallbackTexture::MetallicRoughness => [128, 128, 128, 255],
+        }
+    }
+
+    pub(crate) fn create_image(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        sampler: Arc<wgpu::Sampler>,
+    ) -> Image {
+        // Only colors are stored as sRGB, normals and roughness are linear data.
+        let format = match self {
+            FallbackTexture::White | FallbackTexture::Black => wgpu::TextureFormat::Rgba8UnormSrgb,
+            _ => wgpu::TextureFormat::Rgba8Unorm,
+        };
+        Image::from_decoded(
+            device,
+            encoder,
+            self.name().to_string(),
+            DecodedImage {
+                levels: vec![self.pixel().to_vec()],
+                extent: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth: 1,
+                },
+                format,
+                keep_cpu_data: false,
+            },
+            Image::DEFAULT_USAGE,
+            SamplerInfo::default(),
+            sampler,
+        )
+    }
+
+    /// Looks up an image, using this fallback if it's not there.
+    pub(crate) fn get_or_fallback<'a>(
+        &self,
+        images: &'a HashMap<String, Image>,
+        name: &str,
+    ) -> &'a Image {
+        images
+            .get(name)
+            .or_else(|| images.get(self.name()))
+            .unwrap_or_else(|| {
+                panic!(
+                    "Material Error: Couldn't find {} or the {} fallback, the asset manager \
+                     creates fallbacks when it loads.",
+                    name,
+                    self.name()
+                )
+            })
+    }
+}
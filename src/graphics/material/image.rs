@@ -1,100 +1,406 @@
-use std::{fs, io};
+use image::{imageops, ImageBuffer, Pixel};
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    sync::Arc,
+};
+
+use super::{compressed_image::CompressedImage, ImageInfo, SamplerInfo};
+use crate::AssetError;
+
+type DecodeResult = Result<(Vec<Vec<u8>>, wgpu::Extent3d, wgpu::TextureFormat), AssetError>;
+
+/// Image data that has been decoded but not uploaded to the GPU yet.
+pub(crate) struct DecodedImage {
+    /// The bytes for each mip level, starting with the full size image.
+    pub levels: Vec<Vec<u8>>,
+    pub extent: wgpu::Extent3d,
+    pub format: wgpu::TextureFormat,
+    /// Keeps the bytes around on the image after upload, see `ImageInfo::keep_cpu_data`.
+    pub keep_cpu_data: bool,
+}
+
+impl DecodedImage {
+    /// Bytes used by every mip level.
+    pub fn size(&self) -> usize {
+        self.levels.iter().map(Vec::len).sum()
+    }
+
+    /// Hashes the pixels along with everything else that ends up in the texture, images with
+    /// the same hash can share one.
+    pub fn content_hash(&self, usage: wgpu::TextureUsage) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.levels.hash(&mut hasher);
+        (self.extent.width, self.extent.height, self.extent.depth).hash(&mut hasher);
+        self.format.hash(&mut hasher);
+        usage.bits().hash(&mut hasher);
+        hasher.finish()
+    }
+}
 
 pub struct Image {
     pub name: String,
-    pub texture: wgpu::Texture,
+    /// Shared with any other image that has the exact same contents.
+    pub texture: Arc<wgpu::Texture>,
     pub extent: wgpu::Extent3d,
-    pub sampler: wgpu::Sampler,
-    pub view: wgpu::TextureView,
+    /// Shared with every other image using the same `sampler_info`.
+    pub sampler: Arc<wgpu::Sampler>,
+    pub sampler_info: SamplerInfo,
+    pub view: Arc<wgpu::TextureView>,
     pub format: wgpu::TextureFormat,
+    pub usage: wgpu::TextureUsage,
+    // Only kept when it was asked for, otherwise the bytes are dropped once they're uploaded.
+    cpu_data: Option<Vec<Vec<u8>>>,
+    gpu_size: usize,
 }
 
 impl Image {
+    /// The usage images get when nothing else was requested.
+    pub const DEFAULT_USAGE: wgpu::TextureUsage = wgpu::TextureUsage::from_bits_truncate(
+        wgpu::TextureUsage::SAMPLED.bits() | wgpu::TextureUsage::COPY_DST.bits(),
+    );
+
     pub fn new<T>(
         device: &wgpu::Device,
         encoder: &mut wgpu::CommandEncoder,
         path: T,
         file_name: T,
-    ) -> Self
+    ) -> Result<Self, AssetError>
     where
         T: Into<String>,
     {
-        let path = path.into();
+        Self::new_with_usage(device, encoder, path, file_name, Self::DEFAULT_USAGE)
+    }
+
+    /// Same as `new` but lets you pick the texture usage, for example STORAGE for compute passes
+    /// or COPY_SRC for reading the image back.
+    /// Note: COPY_DST is always added since we need it to upload the image.
+    pub fn new_with_usage<T>(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        path: T,
+        file_name: T,
+        usage: wgpu::TextureUsage,
+    ) -> Result<Self, AssetError>
+    where
+        T: Into<String>,
+    {
+        let info = ImageInfo {
+            usage,
+            ..ImageInfo::default()
+        };
+        Self::new_with_info(device, encoder, path, file_name, &info)
+    }
+
+    /// Creates an image using the usage and import operations described by `info`.
+    pub fn new_with_info<T>(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        path: T,
+        file_name: T,
+        info: &ImageInfo,
+    ) -> Result<Self, AssetError>
+    where
+        T: Into<String>,
+    {
+        let decoded = Self::decode(&path.into(), info)?;
+        Ok(Self::from_decoded(
+            device,
+            encoder,
+            file_name.into(),
+            decoded,
+            info.usage,
+            info.sampler,
+            Arc::new(info.sampler.create_sampler(device)),
+        ))
+    }
 
-        let (image_bytes, texture_extent, format) = if path.ends_with(".hdr") {
-            Self::create_hdr_image(path)
+    /// Reads and decodes the image on the CPU.
+    /// This doesn't touch the GPU so it's safe to call from a loading thread.
+    pub(crate) fn decode(path: &str, info: &ImageInfo) -> Result<DecodedImage, AssetError> {
+        let bytes = fs::read(path).map_err(|error| AssetError::io(path, error))?;
+        Self::decode_bytes(path, &bytes, info)
+    }
+
+    /// Decodes an image file that was already read, for example from an asset pack.
+    /// The format is picked from `path`'s extension like `decode`.
+    pub(crate) fn decode_bytes(
+        path: &str,
+        bytes: &[u8],
+        info: &ImageInfo,
+    ) -> Result<DecodedImage, AssetError> {
+        let path = path.to_string();
+        let (levels, extent, format) = if path.ends_with(".hdr") {
+            Self::create_hdr_image(path, bytes, info)
+        } else if path.ends_with(".dds") || path.ends_with(".ktx2") {
+            Self::create_compressed_image(path, bytes, info)
         } else if path.to_lowercase().contains("_normal")
             || path.to_lowercase().contains("metallic")
         {
-            Self::create_normal_image(path)
+            Self::create_normal_image(path, bytes, info)
+        } else {
+            Self::create_color_image(path, bytes, info)
+        }?;
+
+        Ok(DecodedImage {
+            levels,
+            extent,
+            format,
+            keep_cpu_data: info.keep_cpu_data,
+        })
+    }
+
+    /// Builds a decoded image out of pixels that are already in memory, for example the images
+    /// embedded in a .glb file.
+    pub(crate) fn decode_rgba8(
+        image: ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+        srgb: bool,
+        info: &ImageInfo,
+    ) -> DecodedImage {
+        let image = info.apply_rgba8(image);
+        let (width, height) = image.dimensions();
+        let format = if srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
         } else {
-            Self::create_color_image(path)
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+        DecodedImage {
+            levels: Self::mip_chain(image, info.generate_mips, ImageBuffer::into_raw),
+            extent: wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+            format,
+            keep_cpu_data: info.keep_cpu_data,
+        }
+    }
+
+    /// The bytes of each mip level, only there if the image was imported with
+    /// `ImageInfo::keep_cpu_data` set.
+    pub fn cpu_data(&self) -> Option<&[Vec<u8>]> {
+        self.cpu_data.as_deref()
+    }
+
+    /// Bytes used by the texture on the GPU, including it's mips. Images sharing a texture
+    /// report the same size.
+    pub fn gpu_size(&self) -> usize {
+        self.gpu_size
+    }
+
+    /// Creates an empty image that can be rendered to and used as a material texture,
+    /// for example a security camera monitor. Render to it using it's `view`.
+    pub fn new_render_texture<T>(
+        device: &wgpu::Device,
+        name: T,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Self
+    where
+        T: Into<String>,
+    {
+        let usage = wgpu::TextureUsage::OUTPUT_ATTACHMENT
+            | wgpu::TextureUsage::SAMPLED
+            | wgpu::TextureUsage::COPY_DST
+            | wgpu::TextureUsage::COPY_SRC;
+        Self::new_empty(device, name.into(), width, height, format, usage)
+    }
+
+    /// Creates an empty image compute shaders can write to and materials can sample from,
+    /// for example with `NoiseGenerator`.
+    pub fn new_storage_texture<T>(
+        device: &wgpu::Device,
+        name: T,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Self
+    where
+        T: Into<String>,
+    {
+        let usage = wgpu::TextureUsage::STORAGE
+            | wgpu::TextureUsage::SAMPLED
+            | wgpu::TextureUsage::COPY_DST
+            | wgpu::TextureUsage::COPY_SRC;
+        Self::new_empty(device, name.into(), width, height, format, usage)
+    }
+
+    fn new_empty(
+        device: &wgpu::Device,
+        name: String,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsage,
+    ) -> Self {
+        let extent = wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth: 1,
         };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            label: None,
+        });
+        let sampler_info = SamplerInfo::clamped();
+        let sampler = Arc::new(sampler_info.create_sampler(device));
+        let view = Arc::new(texture.create_default_view());
+
+        Self {
+            name,
+            texture: Arc::new(texture),
+            extent,
+            sampler,
+            sampler_info,
+            view,
+            format,
+            usage,
+            cpu_data: None,
+            gpu_size: (extent.width * extent.height) as usize * texel_size(format),
+        }
+    }
+
+    /// Uploads an image that was already decoded with `decode`.
+    /// The sampler should've been created from `sampler_info`, usually it comes from
+    /// `GPUResourceManager::get_sampler`.
+    pub(crate) fn from_decoded(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        file_name: String,
+        decoded: DecodedImage,
+        usage: wgpu::TextureUsage,
+        sampler_info: SamplerInfo,
+        sampler: Arc<wgpu::Sampler>,
+    ) -> Self {
+        let usage = usage | wgpu::TextureUsage::COPY_DST;
+        let gpu_size = decoded.size();
+        let DecodedImage {
+            levels,
+            extent: texture_extent,
+            format,
+            keep_cpu_data,
+        } = decoded;
 
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             size: texture_extent,
-            mip_level_count: 1,
+            mip_level_count: levels.len() as u32,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format,
-            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+            usage,
             label: None,
         });
 
-        let temp_buf = device.create_buffer_with_data(&image_bytes, wgpu::BufferUsage::COPY_SRC);
-
-        encoder.copy_buffer_to_texture(
-            wgpu::BufferCopyView {
-                buffer: &temp_buf,
-                layout: wgpu::TextureDataLayout {
-                    offset: 0,
-                    bytes_per_row: if format == wgpu::TextureFormat::Rgba8UnormSrgb
-                        || format == wgpu::TextureFormat::Rgba8Unorm
-                    {
-                        4 * texture_extent.width
-                    } else {
-                        (4 * 4) * texture_extent.width
+        let bytes_per_pixel = if format == wgpu::TextureFormat::Rgba8UnormSrgb
+            || format == wgpu::TextureFormat::Rgba8Unorm
+        {
+            4
+        } else {
+            4 * 4
+        };
+
+        let mut cpu_data = Vec::new();
+        for (mip_level, level_bytes) in levels.into_iter().enumerate() {
+            let width = (texture_extent.width >> mip_level).max(1);
+            let height = (texture_extent.height >> mip_level).max(1);
+
+            // Rows have to be aligned to 256 bytes when copying from a buffer, small mips usually aren't.
+            let bytes_per_row = bytes_per_pixel * width;
+            let padded_bytes_per_row = (bytes_per_row + 255) & !255;
+            let temp_buf = if padded_bytes_per_row == bytes_per_row {
+                device.create_buffer_with_data(&level_bytes, wgpu::BufferUsage::COPY_SRC)
+            } else {
+                let mut padded = vec![0u8; (padded_bytes_per_row * height) as usize];
+                for (row, data) in level_bytes.chunks_exact(bytes_per_row as usize).enumerate() {
+                    let start = row * padded_bytes_per_row as usize;
+                    padded[start..start + bytes_per_row as usize].copy_from_slice(data);
+                }
+                device.create_buffer_with_data(&padded, wgpu::BufferUsage::COPY_SRC)
+            };
+
+            encoder.copy_buffer_to_texture(
+                wgpu::BufferCopyView {
+                    buffer: &temp_buf,
+                    layout: wgpu::TextureDataLayout {
+                        offset: 0,
+                        bytes_per_row: padded_bytes_per_row,
+                        rows_per_image: 0,
                     },
-                    rows_per_image: 0,
                 },
-            },
-            wgpu::TextureCopyView {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            texture_extent,
-        );
+                wgpu::TextureCopyView {
+                    texture: &texture,
+                    mip_level: mip_level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth: 1,
+                },
+            );
 
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: None,
-            address_mode_u: wgpu::AddressMode::Repeat,
-            address_mode_v: wgpu::AddressMode::Repeat,
-            address_mode_w: wgpu::AddressMode::Repeat,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Linear,
-            lod_min_clamp: -100.0,
-            lod_max_clamp: 100.0,
-            compare: wgpu::CompareFunction::Undefined,
-        });
+            // The staging buffer has it's own copy, so each level can go as soon as it's encoded.
+            if keep_cpu_data {
+                cpu_data.push(level_bytes);
+            }
+        }
 
-        let view = texture.create_default_view();
+        let view = Arc::new(texture.create_default_view());
 
         Self {
-            name: file_name.into().clone(),
-            texture,
+            name: file_name,
+            texture: Arc::new(texture),
             extent: texture_extent,
             sampler,
+            sampler_info,
             view,
             format,
+            usage,
+            cpu_data: if keep_cpu_data { Some(cpu_data) } else { None },
+            gpu_size,
+        }
+    }
+
+    /// Creates another image on top of this one's texture, `decoded` should have the same
+    /// contents. Only it's CPU data is kept, nothing is uploaded.
+    pub(crate) fn share(
+        &self,
+        file_name: String,
+        decoded: DecodedImage,
+        sampler_info: SamplerInfo,
+        sampler: Arc<wgpu::Sampler>,
+    ) -> Self {
+        Self {
+            name: file_name,
+            texture: self.texture.clone(),
+            extent: self.extent,
+            sampler,
+            sampler_info,
+            view: self.view.clone(),
+            format: self.format,
+            usage: self.usage,
+            cpu_data: if decoded.keep_cpu_data {
+                Some(decoded.levels)
+            } else {
+                None
+            },
+            gpu_size: self.gpu_size,
         }
     }
 
-    fn create_normal_image(path: String) -> (Vec<u8>, wgpu::Extent3d, wgpu::TextureFormat) {
-        let img = image::open(&path)
-            .unwrap_or_else(|_| panic!("Image: Unable to open the file: {}", path))
+    fn create_normal_image(path: String, bytes: &[u8], info: &ImageInfo) -> DecodeResult {
+        let img = image::load_from_memory(bytes)
+            .map_err(|error| AssetError::from_image(&path, error))?
             .to_rgba();
+        let img = info.apply_rgba8(img);
         let (width, height) = img.dimensions();
         let texture_extent = wgpu::Extent3d {
             width,
@@ -102,15 +408,16 @@ impl Image {
             depth: 1,
         };
 
-        let image_bytes: Vec<u8> = img.into_raw();
+        let levels = Self::mip_chain(img, info.generate_mips, ImageBuffer::into_raw);
 
-        (image_bytes, texture_extent, wgpu::TextureFormat::Rgba8Unorm)
+        Ok((levels, texture_extent, wgpu::TextureFormat::Rgba8Unorm))
     }
 
-    fn create_color_image(path: String) -> (Vec<u8>, wgpu::Extent3d, wgpu::TextureFormat) {
-        let img = image::open(&path)
-            .unwrap_or_else(|_| panic!("Image: Unable to open the file: {}", path))
+    fn create_color_image(path: String, bytes: &[u8], info: &ImageInfo) -> DecodeResult {
+        let img = image::load_from_memory(bytes)
+            .map_err(|error| AssetError::from_image(&path, error))?
             .to_rgba();
+        let img = info.apply_rgba8(img);
         let (width, height) = img.dimensions();
         let texture_extent = wgpu::Extent3d {
             width,
@@ -118,26 +425,72 @@ impl Image {
             depth: 1,
         };
 
-        let image_bytes: Vec<u8> = img.into_raw();
+        let levels = Self::mip_chain(img, info.generate_mips, ImageBuffer::into_raw);
 
         // TODO: Fix loading of images. We should use SRGB for textures and Unorm for roughness/normal maps/etc.
         // Should be done with a material loader perhaps?
-        (
-            image_bytes,
-            texture_extent,
-            wgpu::TextureFormat::Rgba8UnormSrgb,
-        )
+        Ok((levels, texture_extent, wgpu::TextureFormat::Rgba8UnormSrgb))
+    }
+
+    /// Reads a DDS or KTX2 file and decompresses it to RGBA8.
+    /// Mips stored in the file are used as is unless `info` asks for import operations,
+    /// then they're regenerated from the top level.
+    fn create_compressed_image(path: String, bytes: &[u8], info: &ImageInfo) -> DecodeResult {
+        let compressed = CompressedImage::from_bytes(&path, bytes)?;
+        let srgb = compressed.srgb.unwrap_or_else(|| {
+            !(path.to_lowercase().contains("_normal") || path.to_lowercase().contains("metallic"))
+        });
+        let format = if srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+
+        let mut images = compressed.decompress();
+        let (levels, (width, height)) =
+            if info.has_operations() || (images.len() == 1 && info.generate_mips) {
+                let img = info.apply_rgba8(images.swap_remove(0));
+                let size = img.dimensions();
+                (Self::mip_chain(img, info.generate_mips, ImageBuffer::into_raw), size)
+            } else {
+                if !info.generate_mips {
+                    images.truncate(1);
+                }
+                let size = images[0].dimensions();
+                (images.into_iter().map(ImageBuffer::into_raw).collect(), size)
+            };
+
+        let texture_extent = wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        };
+
+        Ok((levels, texture_extent, format))
     }
 
-    fn create_hdr_image(path: String) -> (Vec<u8>, wgpu::Extent3d, wgpu::TextureFormat) {
-        // Load the image
-        let decoder =
-            image::hdr::HdrDecoder::new(io::BufReader::new(fs::File::open(&path).unwrap()))
-                .unwrap();
+    fn create_hdr_image(path: String, bytes: &[u8], info: &ImageInfo) -> DecodeResult {
+        let decoder = image::hdr::HdrDecoder::new(io::Cursor::new(bytes))
+            .map_err(|error| AssetError::from_image(&path, error))?;
         let metadata = decoder.metadata();
-        let decoded = decoder.read_image_hdr().unwrap();
+        let decoded = decoder
+            .read_image_hdr()
+            .map_err(|error| AssetError::from_image(&path, error))?;
+
+        // Widen to RGBA in a single allocation, the RGB pixels are dropped as soon as it's done.
+        let mut image_data = Vec::with_capacity(decoded.len() * 4);
+        for pixel in decoded {
+            image_data.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 1.0]);
+        }
+        let img = image::ImageBuffer::<image::Rgba<f32>, Vec<f32>>::from_raw(
+            metadata.width,
+            metadata.height,
+            image_data,
+        )
+        .ok_or_else(|| AssetError::decode(&path, "HDR data doesn't match it's size"))?;
+        let img = info.apply_rgba32f(img);
 
-        let (w, h) = (metadata.width, metadata.height);
+        let (w, h) = img.dimensions();
 
         let texture_extent = wgpu::Extent3d {
             width: w,
@@ -145,20 +498,51 @@ impl Image {
             depth: 1,
         };
 
-        let image_data = decoded
-            .iter()
-            .flat_map(|pixel| vec![pixel[0], pixel[1], pixel[2], 1.0])
-            .collect::<Vec<_>>();
+        let levels = Self::mip_chain(img, info.generate_mips, |level| {
+            bytemuck::cast_slice::<f32, u8>(&level.into_raw()).to_vec()
+        });
+
+        Ok((levels, texture_extent, wgpu::TextureFormat::Rgba32Float))
+    }
 
-        let image_bytes = unsafe {
-            std::slice::from_raw_parts(image_data.as_ptr() as *const u8, image_data.len() * 4)
+    /// Builds the mip chain for an image by repeatedly halving it down to 1x1.
+    /// Each level is turned into bytes with `to_bytes` as soon as the next one is made, so at most
+    /// two levels are held as pixels. The first level is the image itself, if `generate_mips` is
+    /// false that's the only level.
+    fn mip_chain<P, F>(
+        image: ImageBuffer<P, Vec<P::Subpixel>>,
+        generate_mips: bool,
+        mut to_bytes: F,
+    ) -> Vec<Vec<u8>>
+    where
+        P: Pixel + 'static,
+        P::Subpixel: 'static,
+        F: FnMut(ImageBuffer<P, Vec<P::Subpixel>>) -> Vec<u8>,
+    {
+        let mut levels = Vec::new();
+        let mut level = image;
+        while generate_mips && level.dimensions() != (1, 1) {
+            let (width, height) = level.dimensions();
+            let next = imageops::resize(
+                &level,
+                (width / 2).max(1),
+                (height / 2).max(1),
+                imageops::FilterType::Triangle,
+            );
+            levels.push(to_bytes(std::mem::replace(&mut level, next)));
         }
-        .to_vec();
+        levels.push(to_bytes(level));
+        levels
+    }
+}
 
-        (
-            image_bytes,
-            texture_extent,
-            wgpu::TextureFormat::Rgba32Float,
-        )
+// Bytes per pixel for the formats empty images are usually made with.
+fn texel_size(format: wgpu::TextureFormat) -> usize {
+    match format {
+        wgpu::TextureFormat::R8Unorm => 1,
+        wgpu::TextureFormat::Rg8Unorm | wgpu::TextureFormat::R16Float => 2,
+        wgpu::TextureFormat::Rgba16Float | wgpu::TextureFormat::Rg32Float => 8,
+        wgpu::TextureFormat::Rgba32Float => 16,
+        _ => 4,
     }
 }
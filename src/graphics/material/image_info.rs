@@ -0,0 +1,287 @@
+use image::{imageops, ImageBuffer, Pixel, Rgba};
+use serde::Deserialize;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use super::Image;
+
+/// A single color channel, used to swizzle images on import.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Channel {
+    R,
+    G,
+    B,
+    A,
+}
+
+impl Channel {
+    fn index(&self) -> usize {
+        match self {
+            Channel::R => 0,
+            Channel::G => 1,
+            Channel::B => 2,
+            Channel::A => 3,
+        }
+    }
+}
+
+/// How texture coordinates outside of 0 to 1 are handled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize)]
+pub enum AddressMode {
+    Repeat,
+    MirrorRepeat,
+    ClampToEdge,
+}
+
+impl AddressMode {
+    fn to_wgpu(&self) -> wgpu::AddressMode {
+        match self {
+            AddressMode::Repeat => wgpu::AddressMode::Repeat,
+            AddressMode::MirrorRepeat => wgpu::AddressMode::MirrorRepeat,
+            AddressMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize)]
+pub enum FilterMode {
+    /// Blocky, good for pixel art.
+    Nearest,
+    Linear,
+}
+
+impl FilterMode {
+    fn to_wgpu(&self) -> wgpu::FilterMode {
+        match self {
+            FilterMode::Nearest => wgpu::FilterMode::Nearest,
+            FilterMode::Linear => wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+/// How an image is sampled. Images with the same settings share a sampler.
+/// Can be read from RON, any missing fields use the defaults:
+/// `(address_mode_u: ClampToEdge, mag_filter: Nearest)`
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SamplerInfo {
+    pub address_mode_u: AddressMode,
+    pub address_mode_v: AddressMode,
+    pub address_mode_w: AddressMode,
+    pub mag_filter: FilterMode,
+    pub min_filter: FilterMode,
+    pub mipmap_filter: FilterMode,
+    /// Anisotropic filtering level, 1 turns it off.
+    /// Note: The wgpu version we're on can't create anisotropic samplers yet so this is only
+    /// stored for now, samplers with different levels are still kept apart.
+    pub anisotropy: u8,
+    pub lod_min_clamp: f32,
+    pub lod_max_clamp: f32,
+}
+
+impl Default for SamplerInfo {
+    fn default() -> Self {
+        Self {
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            address_mode_w: AddressMode::Repeat,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            anisotropy: 1,
+            lod_min_clamp: -100.0,
+            lod_max_clamp: 100.0,
+        }
+    }
+}
+
+// The LOD clamps are compared by their bits so the info can be used as a cache key.
+impl Eq for SamplerInfo {}
+
+impl Hash for SamplerInfo {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.address_mode_u.hash(state);
+        self.address_mode_v.hash(state);
+        self.address_mode_w.hash(state);
+        self.mag_filter.hash(state);
+        self.min_filter.hash(state);
+        self.mipmap_filter.hash(state);
+        self.anisotropy.hash(state);
+        self.lod_min_clamp.to_bits().hash(state);
+        self.lod_max_clamp.to_bits().hash(state);
+    }
+}
+
+impl SamplerInfo {
+    /// Clamps on every axis, used for render and storage textures.
+    pub fn clamped() -> Self {
+        Self {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            ..Self::default()
+        }
+    }
+
+    pub fn from_ron(source: &str) -> Result<Self, ron::de::Error> {
+        ron::de::from_str(source)
+    }
+
+    /// Creates a new sampler, prefer `GPUResourceManager::get_sampler` which reuses them.
+    pub fn create_sampler(&self, device: &wgpu::Device) -> wgpu::Sampler {
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: self.address_mode_u.to_wgpu(),
+            address_mode_v: self.address_mode_v.to_wgpu(),
+            address_mode_w: self.address_mode_w.to_wgpu(),
+            mag_filter: self.mag_filter.to_wgpu(),
+            min_filter: self.min_filter.to_wgpu(),
+            mipmap_filter: self.mipmap_filter.to_wgpu(),
+            lod_min_clamp: self.lod_min_clamp,
+            lod_max_clamp: self.lod_max_clamp,
+            compare: wgpu::CompareFunction::Undefined,
+        })
+    }
+}
+
+/// Describes how an image should be imported.
+/// Operations run in this order: resize, flip, swizzle, premultiply alpha.
+/// The processing is redone on every load unless `AssetManager::set_import_cache` is used.
+#[derive(Debug, Clone)]
+pub struct ImageInfo {
+    pub usage: wgpu::TextureUsage,
+    /// Images larger than this (in either dimension) are scaled down keeping their aspect ratio.
+    pub max_size: Option<u32>,
+    pub flip_vertical: bool,
+    /// Which source channel ends up in R, G, B and A.
+    pub swizzle: Option<[Channel; 4]>,
+    pub premultiply_alpha: bool,
+    /// Generates the full mip chain on import, this stops textures shimmering in the distance.
+    pub generate_mips: bool,
+    pub sampler: SamplerInfo,
+    /// Keeps the decoded bytes on the image after upload so they can be read back with
+    /// `Image::cpu_data`. Off by default, the bytes are dropped as soon as they're uploaded.
+    pub keep_cpu_data: bool,
+}
+
+impl Default for ImageInfo {
+    fn default() -> Self {
+        Self {
+            usage: Image::DEFAULT_USAGE,
+            max_size: None,
+            flip_vertical: false,
+            swizzle: None,
+            premultiply_alpha: false,
+            generate_mips: true,
+            sampler: SamplerInfo::default(),
+            keep_cpu_data: false,
+        }
+    }
+}
+
+impl ImageInfo {
+    /// The default import settings for a file.
+    /// HDR images are used as environment maps and don't get mips by default,
+    /// sampling them with mips shows a seam where the equirectangular image wraps.
+    pub fn for_file(file_name: &str) -> Self {
+        Self {
+            generate_mips: !file_name.ends_with(".hdr"),
+            ..Self::default()
+        }
+    }
+
+    /// True if any of the import operations (resize, flip, etc) are enabled.
+    pub(crate) fn has_operations(&self) -> bool {
+        self.max_size.is_some()
+            || self.flip_vertical
+            || self.swizzle.is_some()
+            || self.premultiply_alpha
+    }
+
+    /// Identifies the result of importing `bytes` from `path` with these settings, used as the
+    /// `ImportCache` key. Only the settings that change the pixels are hashed.
+    pub(crate) fn import_hash(&self, path: &str, bytes: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        path.hash(&mut hasher);
+        bytes.hash(&mut hasher);
+        self.max_size.hash(&mut hasher);
+        self.flip_vertical.hash(&mut hasher);
+        self.swizzle.hash(&mut hasher);
+        self.premultiply_alpha.hash(&mut hasher);
+        self.generate_mips.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub(crate) fn apply_rgba8(
+        &self,
+        image: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    ) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+        let mut image = self.apply_common(image);
+        if self.premultiply_alpha {
+            for pixel in image.pixels_mut() {
+                let alpha = pixel[3] as f32 / 255.0;
+                for channel in 0..3 {
+                    pixel[channel] = (pixel[channel] as f32 * alpha).round() as u8;
+                }
+            }
+        }
+        image
+    }
+
+    pub(crate) fn apply_rgba32f(
+        &self,
+        image: ImageBuffer<Rgba<f32>, Vec<f32>>,
+    ) -> ImageBuffer<Rgba<f32>, Vec<f32>> {
+        let mut image = self.apply_common(image);
+        if self.premultiply_alpha {
+            for pixel in image.pixels_mut() {
+                let alpha = pixel[3];
+                for channel in 0..3 {
+                    pixel[channel] *= alpha;
+                }
+            }
+        }
+        image
+    }
+
+    fn apply_common<S>(
+        &self,
+        mut image: ImageBuffer<Rgba<S>, Vec<S>>,
+    ) -> ImageBuffer<Rgba<S>, Vec<S>>
+    where
+        S: image::Primitive + 'static,
+    {
+        if let Some(max_size) = self.max_size {
+            let (width, height) = image.dimensions();
+            if width > max_size || height > max_size {
+                let scale = max_size as f32 / width.max(height) as f32;
+                let new_width = ((width as f32 * scale).round() as u32).max(1);
+                let new_height = ((height as f32 * scale).round() as u32).max(1);
+                image = imageops::resize(
+                    &image,
+                    new_width,
+                    new_height,
+                    imageops::FilterType::Triangle,
+                );
+            }
+        }
+
+        if self.flip_vertical {
+            image = imageops::flip_vertical(&image);
+        }
+
+        if let Some(swizzle) = self.swizzle {
+            for pixel in image.pixels_mut() {
+                let source = *pixel;
+                let channels = pixel.channels_mut();
+                for (target, channel) in swizzle.iter().enumerate() {
+                    channels[target] = source[channel.index()];
+                }
+            }
+        }
+
+        image
+    }
+}
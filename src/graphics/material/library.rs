@@ -0,0 +1,98 @@
+use nalgebra_glm::{Vec2, Vec3, Vec4};
+
+use super::{AlphaMode, FallbackTexture, Material, PBRMaterial, ShaderDefines, UnlitMaterial};
+use crate::graphics::RenderQueue;
+
+/// Ready made materials the asset manager registers at startup so you can block out a level
+/// before there's any art. They're always the first materials, use `index` with
+/// `components::Material::new` and a `MaterialInstance` to tweak the PBR ones per entity.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BuiltinMaterial {
+    /// Plain mid gray PBR.
+    Default,
+    /// Glows white, bright enough to bloom.
+    Emissive,
+    /// Clear and smooth, drawn with the transparent meshes.
+    Glass,
+    /// Flat white that ignores lighting.
+    Unlit,
+    /// A gray checker laid out in world space, one cell per unit. The texture transform's
+    /// x scale sets the cell size, so the pattern lines up across meshes of any size.
+    Grid,
+}
+
+impl BuiltinMaterial {
+    pub const ALL: [BuiltinMaterial; 5] = [
+        BuiltinMaterial::Default,
+        BuiltinMaterial::Emissive,
+        BuiltinMaterial::Glass,
+        BuiltinMaterial::Unlit,
+        BuiltinMaterial::Grid,
+    ];
+
+    /// The index the material is registered under in the asset manager.
+    pub fn index(&self) -> u32 {
+        Self::ALL
+            .iter()
+            .position(|material| material == self)
+            .unwrap() as u32
+    }
+
+    pub(crate) fn create(&self) -> Material {
+        let index = self.index();
+        let pbr = |color: Vec4, roughness: f32| {
+            let mut material = PBRMaterial::new(
+                FallbackTexture::White.name(),
+                FallbackTexture::Normal.name(),
+                FallbackTexture::White.name(),
+                color,
+                index,
+            );
+            material.roughness = roughness;
+            material
+        };
+        let gray = Vec4::new(0.5, 0.5, 0.5, 1.0);
+        match self {
+            BuiltinMaterial::Default => Material::PBR(pbr(gray, 0.6)),
+            BuiltinMaterial::Emissive => {
+                let mut material = pbr(Vec4::new(1.0, 1.0, 1.0, 1.0), 0.6);
+                material.emissive = Vec3::new(4.0, 4.0, 4.0);
+                Material::PBR(material)
+            }
+            BuiltinMaterial::Glass => {
+                let mut material = pbr(Vec4::new(0.9, 0.95, 1.0, 0.15), 0.05);
+                material.alpha_mode = AlphaMode::Blend;
+                material.render_queue = RenderQueue::Transparent;
+                Material::PBR(material)
+            }
+            BuiltinMaterial::Unlit => Material::Unlit(UnlitMaterial::new(
+                FallbackTexture::White.name(),
+                Vec4::new(1.0, 1.0, 1.0, 1.0),
+                index,
+            )),
+            BuiltinMaterial::Grid => {
+                let mut material = pbr(gray, 0.8);
+                material.texture_transform.scale = Vec2::new(1.0, 1.0);
+                material.shader_defines = ShaderDefines::default().with_flag("WORLD_GRID");
+                Material::PBR(material)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_indices() {
+        for (index, builtin) in BuiltinMaterial::ALL.iter().enumerate() {
+            assert_eq!(builtin.index(), index as u32);
+            let material_index = match builtin.create() {
+                Material::PBR(material) => material.index,
+                Material::Unlit(material) => material.index,
+            };
+            assert_eq!(material_index, index as u32);
+        }
+    }
+}
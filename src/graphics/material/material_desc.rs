@@ -0,0 +1,99 @@
+use nalgebra_glm::{Vec3, Vec4};
+use serde::Deserialize;
+
+use super::{FallbackTexture, NormalEncoding, PBRMaterial};
+
+/// A PBR material written in RON. Files ending in `.material.ron` are loaded with the rest of
+/// the assets, use `AssetManager::material_index` to find them. Missing fields use the defaults,
+/// textures left out use a `FallbackTexture`:
+/// ```ron
+/// (
+///     main_texture: Some("bricks.png"),
+///     normal_texture: Some("bricks_normal.png"),
+///     normal_encoding: RG,
+///     roughness: 0.8,
+/// )
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct MaterialDesc {
+    pub main_texture: Option<String>,
+    pub normal_texture: Option<String>,
+    pub roughness_texture: Option<String>,
+    pub emissive_texture: Option<String>,
+    pub occlusion_texture: Option<String>,
+    pub color: (f32, f32, f32, f32),
+    pub roughness: f32,
+    pub metallic: f32,
+    pub emissive: (f32, f32, f32),
+    /// Set this to `RG` for two channel normal maps.
+    pub normal_encoding: NormalEncoding,
+    pub normal_scale: f32,
+    pub double_sided: bool,
+}
+
+impl Default for MaterialDesc {
+    fn default() -> Self {
+        Self {
+            main_texture: None,
+            normal_texture: None,
+            roughness_texture: None,
+            emissive_texture: None,
+            occlusion_texture: None,
+            color: (1.0, 1.0, 1.0, 1.0),
+            roughness: 1.0,
+            metallic: 0.0,
+            emissive: (0.0, 0.0, 0.0),
+            normal_encoding: NormalEncoding::default(),
+            normal_scale: 1.0,
+            double_sided: false,
+        }
+    }
+}
+
+impl MaterialDesc {
+    pub fn from_ron(source: &str) -> Result<Self, ron::de::Error> {
+        ron::de::from_str(source)
+    }
+
+    /// Creates the material, it still needs it's bind group before it can be drawn.
+    pub fn create(&self, index: u32) -> PBRMaterial {
+        let texture = |name: &Option<String>, fallback: FallbackTexture| {
+            name.clone().unwrap_or_else(|| fallback.name().to_string())
+        };
+        let (r, g, b, a) = self.color;
+        let mut material = PBRMaterial::new(
+            texture(&self.main_texture, FallbackTexture::White),
+            texture(&self.normal_texture, FallbackTexture::Normal),
+            texture(&self.roughness_texture, FallbackTexture::White),
+            Vec4::new(r, g, b, a),
+            index,
+        );
+        material.emissive_texture = texture(&self.emissive_texture, FallbackTexture::White);
+        material.occlusion_texture = texture(&self.occlusion_texture, FallbackTexture::White);
+        material.roughness = self.roughness;
+        material.metallic = self.metallic;
+        let (r, g, b) = self.emissive;
+        material.emissive = Vec3::new(r, g, b);
+        material.normal_encoding = self.normal_encoding;
+        material.normal_scale = self.normal_scale;
+        material.double_sided = self.double_sided;
+        material
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_material_desc_normal_encoding() {
+        let desc = MaterialDesc::from_ron("(normal_texture: Some(\"n.png\"), normal_encoding: RG)")
+            .unwrap();
+        let material = desc.create(3);
+        assert_eq!(material.normal_encoding, NormalEncoding::RG);
+        assert_eq!(material.normal_texture, "n.png");
+        assert_eq!(material.main_texture, FallbackTexture::White.name());
+        assert_eq!(material.index, 3);
+    }
+}
@@ -1,9 +1,17 @@
 pub(crate) mod shader;
-pub use shader::Shader;
+pub use shader::{Shader, ShaderDefines};
 
 pub(crate) mod image;
 pub use self::image::Image;
 
+pub(crate) mod compressed_image;
+
+pub(crate) mod image_info;
+pub use self::image_info::{AddressMode, Channel, FilterMode, ImageInfo, SamplerInfo};
+
+pub(crate) mod fallback;
+pub use self::fallback::FallbackTexture;
+
 pub(crate) mod skybox;
 pub use self::skybox::Skybox;
 
@@ -13,6 +21,12 @@ pub use self::unlit_material::*;
 pub(crate) mod pbr_material;
 pub use self::pbr_material::*;
 
+pub(crate) mod material_desc;
+pub use self::material_desc::MaterialDesc;
+
+pub(crate) mod library;
+pub use self::library::BuiltinMaterial;
+
 pub enum Material {
     Unlit(UnlitMaterial),
     PBR(PBRMaterial),
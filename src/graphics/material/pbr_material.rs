@@ -1,27 +1,163 @@
-use super::Image;
-use crate::graphics::resources::BindGroup;
+use super::{FallbackTexture, Image, ShaderDefines};
+use crate::graphics::{
+    pipeline_manager::PipelineVariant, resources::BindGroup, BlendMode, DepthBias, RenderQueue,
+};
 use bytemuck::{Pod, Zeroable};
-use nalgebra_glm::Vec4;
-use std::{collections::HashMap, mem};
+use nalgebra_glm::{Vec2, Vec3, Vec4};
+use serde::Deserialize;
+use std::collections::HashMap;
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct PBRMaterialUniform {
     pub color: Vec4,
     pub info: Vec4,
+    // (normal encoding, normal scale, occlusion strength, receives shadows)
+    pub normal_info: Vec4,
+    // (alpha cutoff, is masked, alpha to coverage, is blended)
+    pub alpha_info: Vec4,
+    // (r, g, b, wetness)
+    pub emissive: Vec4,
+    // (offset x, offset y, scale x, scale y)
+    pub uv_transform: Vec4,
+    // (rotation, unused..)
+    pub uv_rotation: Vec4,
 }
 
 unsafe impl Zeroable for PBRMaterialUniform {}
 unsafe impl Pod for PBRMaterialUniform {}
 
+/// How the normal map stores it's data, see `MaterialDesc::normal_encoding`.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+pub enum NormalEncoding {
+    /// Regular RGB normal maps.
+    RGB,
+    /// Two channel normal maps (BC5/RG), Z is reconstructed in the shader.
+    RG,
+}
+
+impl Default for NormalEncoding {
+    fn default() -> Self {
+        NormalEncoding::RGB
+    }
+}
+
+impl NormalEncoding {
+    fn to_shader_value(&self) -> f32 {
+        match self {
+            NormalEncoding::RGB => 0.0,
+            NormalEncoding::RG => 1.0,
+        }
+    }
+}
+
+/// How a material uses it's alpha.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AlphaMode {
+    /// Alpha is ignored.
+    Opaque,
+    /// Pixels with an alpha below the cutoff are not drawn.
+    Mask(f32),
+    /// Blended over whatever is behind it with the material's `blend_mode`. Blended meshes
+    /// are sorted back to front every frame and don't write depth, skinned meshes are always
+    /// drawn opaque.
+    Blend,
+}
+
+impl Default for AlphaMode {
+    fn default() -> Self {
+        AlphaMode::Opaque
+    }
+}
+
+impl AlphaMode {
+    pub fn is_masked(&self) -> bool {
+        match self {
+            AlphaMode::Mask(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_blended(&self) -> bool {
+        *self == AlphaMode::Blend
+    }
+}
+
+/// The texture slots of a PBR material.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TextureSlot {
+    Main,
+    Normal,
+    Roughness,
+    Emissive,
+    Occlusion,
+}
+
+/// Offsets, rotates and scales the UVs before any of the material's textures are sampled,
+/// like glTF's `KHR_texture_transform`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextureTransform {
+    pub offset: Vec2,
+    /// Rotation in radians.
+    pub rotation: f32,
+    pub scale: Vec2,
+}
+
+impl Default for TextureTransform {
+    fn default() -> Self {
+        Self {
+            offset: Vec2::zeros(),
+            rotation: 0.0,
+            scale: Vec2::new(1.0, 1.0),
+        }
+    }
+}
+
 pub struct PBRMaterial {
     pub index: u32,
     pub main_texture: String,
     pub roughness_texture: String,
     pub normal_texture: String,
+    /// Multiplied with `emissive`, white by default.
+    pub emissive_texture: String,
+    /// Ambient occlusion is read from the red channel like glTF.
+    pub occlusion_texture: String,
     pub roughness: f32,
     pub metallic: f32,
     pub color: Vec4,
+    /// Light given off by the material, added on top of the lit color.
+    pub emissive: Vec3,
+    pub normal_encoding: NormalEncoding,
+    /// Scales the X and Y of the normal map, 0 flattens it out.
+    pub normal_scale: f32,
+    /// How much the occlusion texture darkens ambient light, from 0 to 1.
+    pub occlusion_strength: f32,
+    /// Reads the occlusion texture with the mesh's second uv set, for baked AO and lightmaps.
+    /// Meshes without one use the first. Materials with it aren't drawn instanced.
+    pub occlusion_uv1: bool,
+    /// Double sided materials don't cull back faces and light them with a flipped normal.
+    pub double_sided: bool,
+    /// Meshes using the material are drawn into the shadow maps, see `ShadowFlags` for
+    /// turning it off for a single entity.
+    pub cast_shadows: bool,
+    pub receive_shadows: bool,
+    /// How wet the material gets under and near a `WaterVolume`, from 0 to 1. Wet surfaces
+    /// are darker and smoother, turn it off for things that don't soak up water like metal.
+    pub wetness: f32,
+    pub texture_transform: TextureTransform,
+    pub alpha_mode: AlphaMode,
+    /// How blended materials are combined with what's behind them, regular alpha blending by
+    /// default. Only used with `AlphaMode::Blend`.
+    pub blend_mode: BlendMode,
+    /// Keeps decals and other coplanar meshes from z-fighting, see `DepthBias::DECAL`.
+    /// Materials with a depth bias aren't drawn instanced.
+    pub depth_bias: Option<DepthBias>,
+    pub(crate) alpha_to_coverage: bool,
+    pub render_queue: RenderQueue,
+    /// Draws the material with a variant of the PBR shader compiled with these defines.
+    /// The built in shader understands `NO_NORMAL_MAP`, custom includes can check their own.
+    /// Materials with defines aren't drawn instanced.
+    pub shader_defines: ShaderDefines,
     pub uniform_buf: Option<wgpu::Buffer>,
 }
 
@@ -41,57 +177,181 @@ impl PBRMaterial {
             main_texture: main_texture.into(),
             roughness_texture: roughness_texture.into(),
             normal_texture: normal_texture.into(),
+            emissive_texture: FallbackTexture::White.name().to_string(),
+            occlusion_texture: FallbackTexture::White.name().to_string(),
             color,
             roughness: 0.0,
             metallic: 0.0,
+            emissive: Vec3::zeros(),
+            normal_encoding: NormalEncoding::default(),
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            occlusion_uv1: false,
+            double_sided: false,
+            cast_shadows: true,
+            receive_shadows: true,
+            wetness: 1.0,
+            texture_transform: TextureTransform::default(),
+            alpha_mode: AlphaMode::default(),
+            blend_mode: BlendMode::default(),
+            depth_bias: None,
+            alpha_to_coverage: false,
+            render_queue: RenderQueue::default(),
+            shader_defines: ShaderDefines::default(),
             uniform_buf: None,
         }
     }
 
+    /// Points a texture slot at an image, the material needs a new bind group afterwards.
+    /// Use `AssetManager::set_material_texture` to have that happen automatically.
+    pub fn set_texture<T: Into<String>>(&mut self, slot: TextureSlot, image_name: T) {
+        let image_name = image_name.into();
+        match slot {
+            TextureSlot::Main => self.main_texture = image_name,
+            TextureSlot::Normal => self.normal_texture = image_name,
+            TextureSlot::Roughness => self.roughness_texture = image_name,
+            TextureSlot::Emissive => self.emissive_texture = image_name,
+            TextureSlot::Occlusion => self.occlusion_texture = image_name,
+        }
+    }
+
+    /// The pipelines the material can be drawn with.
+    pub(crate) fn pipeline_names(&self) -> &'static [&'static str] {
+        match (self.alpha_mode, self.double_sided) {
+            (AlphaMode::Blend, false) => &["pbr_blended", "pbr_skinned"],
+            (AlphaMode::Blend, true) => &["pbr_blended_double_sided", "pbr_skinned"],
+            (AlphaMode::Mask(_), false) => &["pbr_masked", "pbr_skinned"],
+            (AlphaMode::Mask(_), true) => &["pbr_masked_double_sided", "pbr_skinned"],
+            (AlphaMode::Opaque, false) => &["pbr", "pbr_skinned"],
+            (AlphaMode::Opaque, true) => &["pbr_double_sided", "pbr_skinned"],
+        }
+    }
+
+    /// What the material changes about the `pipeline_name` pipeline.
+    pub(crate) fn pipeline_variant(&self, pipeline_name: &str) -> PipelineVariant {
+        // The blended pipelines already use regular alpha blending.
+        let blended = self.alpha_mode.is_blended() && pipeline_name != "pbr_skinned";
+        let mut shader_defines = self.shader_defines.clone();
+        if self.occlusion_uv1 {
+            shader_defines = shader_defines.with_flag("OCCLUSION_UV1");
+        }
+        PipelineVariant {
+            shader_defines,
+            blend_mode: Some(self.blend_mode.clone())
+                .filter(|blend_mode| blended && *blend_mode != BlendMode::Alpha),
+            depth_bias: self.depth_bias,
+            ..Default::default()
+        }
+    }
+
+    /// Whether it can be drawn with the regular opaque and instanced pipelines.
+    pub(crate) fn uses_default_pipelines(&self) -> bool {
+        self.shader_defines.is_empty() && self.depth_bias.is_none() && !self.occlusion_uv1
+    }
+
+    /// Every image the material samples from.
+    pub(crate) fn textures(&self) -> [&String; 5] {
+        [
+            &self.main_texture,
+            &self.normal_texture,
+            &self.roughness_texture,
+            &self.emissive_texture,
+            &self.occlusion_texture,
+        ]
+    }
+
+    pub(crate) fn uniform(&self) -> PBRMaterialUniform {
+        // Without a metallic roughness texture the material's own values are used.
+        let amount = if FallbackTexture::ALL
+            .iter()
+            .any(|fallback| fallback.name() == self.roughness_texture)
+        {
+            1.0
+        } else {
+            0.0
+        };
+        PBRMaterialUniform {
+            color: self.color,
+            info: Vec4::new(self.metallic, self.roughness, amount, amount),
+            normal_info: Vec4::new(
+                self.normal_encoding.to_shader_value(),
+                self.normal_scale,
+                self.occlusion_strength,
+                if self.receive_shadows { 1.0 } else { 0.0 },
+            ),
+            alpha_info: Vec4::new(
+                match self.alpha_mode {
+                    AlphaMode::Mask(cutoff) => cutoff,
+                    _ => 0.0,
+                },
+                if self.alpha_mode.is_masked() { 1.0 } else { 0.0 },
+                if self.alpha_to_coverage { 1.0 } else { 0.0 },
+                if self.alpha_mode.is_blended() { 1.0 } else { 0.0 },
+            ),
+            emissive: Vec4::new(
+                self.emissive.x,
+                self.emissive.y,
+                self.emissive.z,
+                self.wetness,
+            ),
+            uv_transform: Vec4::new(
+                self.texture_transform.offset.x,
+                self.texture_transform.offset.y,
+                self.texture_transform.scale.x,
+                self.texture_transform.scale.y,
+            ),
+            uv_rotation: Vec4::new(self.texture_transform.rotation, 0.0, 0.0, 0.0),
+        }
+    }
+
     pub(crate) fn create_bind_group<'a>(
         &mut self,
         images: &HashMap<String, Image>,
         device: &wgpu::Device,
         pipeline_layout: &'a wgpu::BindGroupLayout,
     ) -> BindGroup {
-        let uniform = PBRMaterialUniform {
-            color: self.color,
-            info: Vec4::new(self.metallic, self.roughness, 0.0, 0.0),
-        };
-
-        let material_uniform_size = mem::size_of::<PBRMaterialUniform>() as wgpu::BufferAddress;
         let uniform_buf = device.create_buffer_with_data(
-            bytemuck::bytes_of(&uniform),
+            bytemuck::bytes_of(&self.uniform()),
             wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
         );
+        let bind_group = self.create_bind_group_with(
+            images,
+            device,
+            pipeline_layout,
+            &uniform_buf,
+            &self.main_texture,
+        );
         self.uniform_buf = Some(uniform_buf);
+        bind_group
+    }
+
+    /// Creates a bind group using the material's textures and the given uniform buffer.
+    /// Used by material instances to share textures with the material they override,
+    /// `main_texture` lets them swap out the main texture.
+    pub(crate) fn create_bind_group_with<'a>(
+        &self,
+        images: &HashMap<String, Image>,
+        device: &wgpu::Device,
+        pipeline_layout: &'a wgpu::BindGroupLayout,
+        uniform_buf: &wgpu::Buffer,
+        main_texture: &str,
+    ) -> BindGroup {
 
-        // Asset manager will panic if image doesn't exist, but we don't want that.
-        // So use get_image_option instead.
-        let main_image = images.get(&self.main_texture)
-            .unwrap_or(
-                images.get("white.png")
-                    .unwrap_or_else(|| panic!("PBRMaterial Error: Couldn't find default white texture. Please make sure it exists in the asset folder or make sure your material's image can be found."))
-            );
-
-        let normal_image = images.get(&self.normal_texture)
-            .unwrap_or(
-                images.get("white.png")
-                    .unwrap_or_else(|| panic!("PBRMaterial Error: Couldn't find default white texture. Please make sure it exists in the asset folder or make sure your material's image can be found."))
-            );
-
-        let roughness_image = images.get(&self.roughness_texture)
-            .unwrap_or(
-                images.get("white.png")
-                    .unwrap_or_else(|| panic!("PBRMaterial Error: Couldn't find default white texture. Please make sure it exists in the asset folder or make sure your material's image can be found."))
-            );
+        let image = |slot: TextureSlot, name: &str| {
+            FallbackTexture::for_slot(slot).get_or_fallback(images, name)
+        };
+        let main_image = image(TextureSlot::Main, main_texture);
+        let normal_image = image(TextureSlot::Normal, &self.normal_texture);
+        let roughness_image = image(TextureSlot::Roughness, &self.roughness_texture);
+        let emissive_image = image(TextureSlot::Emissive, &self.emissive_texture);
+        let occlusion_image = image(TextureSlot::Occlusion, &self.occlusion_texture);
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &pipeline_layout,
             bindings: &[
                 wgpu::Binding {
                     binding: 0,
-                    resource: wgpu::BindingResource::Buffer(self.uniform_buf.as_ref().unwrap().slice(..)),
+                    resource: wgpu::BindingResource::Buffer(uniform_buf.slice(..)),
                 },
                 wgpu::Binding {
                     binding: 1,
@@ -109,6 +369,14 @@ impl PBRMaterial {
                     binding: 4,
                     resource: wgpu::BindingResource::TextureView(&roughness_image.view),
                 },
+                wgpu::Binding {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&emissive_image.view),
+                },
+                wgpu::Binding {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(&occlusion_image.view),
+                },
             ],
             label: None,
         });
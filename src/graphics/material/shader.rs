@@ -1,102 +1,383 @@
 use shaderc;
 
-use std::fs::File;
-use std::io::prelude::*;
-use std::io::BufRead;
-use std::io::BufReader;
+use std::collections::BTreeMap;
 
+use crate::{AssetError, VirtualFileSystem};
+
+// Used in place of shaders that don't compile, it draws mesh pipelines in bright magenta.
+// Only the transforms are read so it fits any layout that starts with locals and globals.
+const ERROR_VERTEX: &str = "
+#version 450
+layout(location = 0) in vec3 i_Pos;
+layout(set = 0, binding = 0) uniform Locals {
+    mat4 world;
+};
+layout(set = 1, binding = 0) uniform Globals {
+    mat4 view_projection;
+};
+void main() {
+    gl_Position = view_projection * world * vec4(i_Pos, 1.0);
+}
+";
+
+const ERROR_FRAGMENT: &str = "
+#version 450
+layout(location = 0) out vec4 outColor;
+void main() {
+    outColor = vec4(1.0, 0.0, 1.0, 1.0);
+}
+";
+
+/// Values baked into a shader variant as `#define`s, so one shader source can be specialized
+/// per material instead of branching on uniforms. Each set of defines gets it's own compiled
+/// shader and pipeline, see `PipelineManager::add_variant`.
+/// wgpu doesn't expose SPIR-V specialization constants yet, so the preprocessor does the job.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ShaderDefines(BTreeMap<String, String>);
+
+impl ShaderDefines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `#define name value`.
+    pub fn with<T: Into<String>, V: ToString>(mut self, name: T, value: V) -> Self {
+        self.0.insert(name.into(), value.to_string());
+        self
+    }
+
+    /// Adds `#define name 1`, for toggles checked with `#ifdef`.
+    pub fn with_flag<T: Into<String>>(self, name: T) -> Self {
+        self.with(name, 1)
+    }
+
+    /// Adds every define in `other`, replacing the values of ones both have.
+    pub fn merged(mut self, other: &ShaderDefines) -> Self {
+        self.0
+            .extend(other.iter().map(|(name, value)| (name.clone(), value.clone())));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.0.iter()
+    }
+
+    /// The name the variant of `shader` is stored under in the asset manager,
+    /// just the shader's name without any defines.
+    pub fn variant_name(&self, shader: &str) -> String {
+        if self.is_empty() {
+            return shader.to_string();
+        }
+        let defines = self
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join(",");
+        format!("{}[{}]", shader, defines)
+    }
+}
+
+/// A compiled `.shader` asset, the file lists the glsl files for each stage, one per line.
+/// Files with "frag" in their name are the fragment stage, names ending in "comp" like
+/// `particles_comp.glsl` the compute stage and anything else the vertex stage.
+/// Render shaders have a vertex and fragment stage, compute shaders only have a compute
+/// stage, see `GPUResourceManager::add_compute_pipeline`.
 pub struct Shader {
-    pub fragment: wgpu::ShaderModule,
-    pub vertex: wgpu::ShaderModule,
+    pub fragment: Option<wgpu::ShaderModule>,
+    pub vertex: Option<wgpu::ShaderModule>,
+    pub compute: Option<wgpu::ShaderModule>,
 }
 
 impl Shader {
-    pub fn new(device: &wgpu::Device, path: String, file_name: String) -> Self {
+    /// Compiles `file_name` from the `path` folder of `vfs`, `path` ends with a `/`.
+    pub fn new(
+        device: &wgpu::Device,
+        vfs: &VirtualFileSystem,
+        path: String,
+        file_name: String,
+    ) -> Result<Self, AssetError> {
+        Self::with_defines(device, vfs, path, file_name, &ShaderDefines::default())
+    }
+
+    /// Compiles the shader with extra `#define`s, every stage sees them.
+    pub fn with_defines(
+        device: &wgpu::Device,
+        vfs: &VirtualFileSystem,
+        path: String,
+        file_name: String,
+        defines: &ShaderDefines,
+    ) -> Result<Self, AssetError> {
         // Compiler
         let mut compiler = shaderc::Compiler::new().unwrap();
-        let mut options = shaderc::CompileOptions::new().unwrap();
-
-        #[cfg(not(debug_assertions))]
-        {
-            options.set_optimization_level(shaderc::OptimizationLevel::Performance);
-        }
-        #[cfg(debug_assertions)]
-        {
-            options.set_optimization_level(shaderc::OptimizationLevel::Zero);
-        }
-
-        options.add_macro_definition("EP", Some("main"));
-        options.set_include_callback(|file_path, _include_type, _, _| {
-            let shader_path = format!("{}{}", path, file_path);
-            let mut file = File::open(&shader_path).expect("Shader: Unable to open the file");
-            let mut contents = String::new();
-            file.read_to_string(&mut contents)
-                .unwrap_or_else(|_| panic!("Unable to read the file: {}", shader_path));
-            Result::Ok(shaderc::ResolvedInclude {
-                resolved_name: file_path.to_string(),
-                content: contents,
-            })
-        });
+        let options = compile_options(vfs, &path, defines);
 
         let shader_path = format!("{}{}", path, file_name);
-        let file = File::open(&shader_path).expect("Shader: Unable to open the file");
-
-        let shader_file = BufReader::new(&file);
-        let mut vert_file_name = String::new();
-        let mut frag_file_name = String::new();
-        for (_num, line) in shader_file.lines().enumerate() {
-            let current_line = line.unwrap();
-            if current_line.contains("frag") {
-                frag_file_name = current_line;
-            } else {
-                vert_file_name = current_line;
-            }
+        let lines: Vec<String> = vfs
+            .read_to_string(&shader_path)?
+            .lines()
+            .map(str::to_string)
+            .collect();
+        let stages = StageFiles::parse(&lines);
+        if stages.compute.is_none() && (stages.vertex.is_none() || stages.fragment.is_none()) {
+            return Err(AssetError::decode(
+                shader_path,
+                "needs a vertex and fragment stage or a compute stage",
+            ));
         }
 
-        // Pixel
-        let shader_path = format!("{}{}", path, frag_file_name);
-        let mut file = File::open(&shader_path)
-            .unwrap_or_else(|_| panic!("Unable to open the file: {}", shader_path));
-        let mut frag_contents = String::new();
-        file.read_to_string(&mut frag_contents)
-            .unwrap_or_else(|_| panic!("Unable to read the file: {}", shader_path));
-
-        // Vertex
-        let shader_path = format!("{}/{}", path, vert_file_name);
-        file = File::open(&shader_path)
-            .unwrap_or_else(|_| panic!("Unable to read the file: {}", shader_path));
-        let mut vert_contents = String::new();
-        file.read_to_string(&mut vert_contents)
-            .unwrap_or_else(|_| panic!("Unable to read the file: {}", shader_path));
-
-        options.add_macro_definition("EP", Some("main"));
-
-        let vertex = {
+        let mut compile = |file_name: Option<&String>,
+                           kind: shaderc::ShaderKind|
+         -> Result<Option<wgpu::ShaderModule>, AssetError> {
+            let file_name = match file_name {
+                Some(file_name) => file_name,
+                None => return Ok(None),
+            };
+            let contents = vfs.read_to_string(&format!("{}{}", path, file_name))?;
             let spirv = compiler
-                .compile_into_spirv(
-                    &vert_contents,
-                    shaderc::ShaderKind::Vertex,
-                    "vertex.glsl",
-                    "main",
-                    Some(&options),
-                )
-                .unwrap();
-            device.create_shader_module(&spirv.as_binary())
+                .compile_into_spirv(&contents, kind, file_name, "main", Some(&options))
+                .map_err(|err| compile_error(vfs, &path, file_name, &contents, err))?;
+            Ok(Some(device.create_shader_module(spirv.as_binary())))
         };
 
-        let fragment = {
+        Ok(Shader {
+            vertex: compile(stages.vertex.as_ref(), shaderc::ShaderKind::Vertex)?,
+            fragment: compile(stages.fragment.as_ref(), shaderc::ShaderKind::Fragment)?,
+            compute: compile(stages.compute.as_ref(), shaderc::ShaderKind::Compute)?,
+        })
+    }
+
+    /// The vertex stage, render pipelines can't be built from compute shaders.
+    pub(crate) fn vertex_module(&self) -> &wgpu::ShaderModule {
+        self.vertex
+            .as_ref()
+            .expect("Shader Error: Compute shaders can't be used for render pipelines.")
+    }
+
+    pub(crate) fn fragment_module(&self) -> &wgpu::ShaderModule {
+        self.fragment
+            .as_ref()
+            .expect("Shader Error: Compute shaders can't be used for render pipelines.")
+    }
+
+    /// The magenta shader the asset manager swaps in for shaders that fail to compile, so
+    /// whatever uses them stands out instead of crashing.
+    pub(crate) fn error(device: &wgpu::Device) -> Self {
+        let mut compiler = shaderc::Compiler::new().unwrap();
+        let mut compile = |source: &str, kind: shaderc::ShaderKind| {
             let spirv = compiler
-                .compile_into_spirv(
-                    &frag_contents,
-                    shaderc::ShaderKind::Fragment,
-                    "pixel.glsl",
-                    "main",
-                    Some(&options),
-                )
-                .unwrap();
+                .compile_into_spirv(source, kind, "error.glsl", "main", None)
+                .expect("Shader Error: The built in error shader doesn't compile.");
             device.create_shader_module(spirv.as_binary())
         };
+        Shader {
+            vertex: Some(compile(ERROR_VERTEX, shaderc::ShaderKind::Vertex)),
+            fragment: Some(compile(ERROR_FRAGMENT, shaderc::ShaderKind::Fragment)),
+            compute: None,
+        }
+    }
+}
+
+// The glsl file used for each stage of a `.shader`.
+#[derive(Debug, Default, PartialEq)]
+struct StageFiles {
+    vertex: Option<String>,
+    fragment: Option<String>,
+    compute: Option<String>,
+}
+
+impl StageFiles {
+    fn parse(lines: &[String]) -> Self {
+        let mut stages = Self::default();
+        for line in lines.iter().map(|line| line.trim()).filter(|line| !line.is_empty()) {
+            let stem = line.rsplitn(2, '.').last().unwrap_or(line);
+            let stage = if line.contains("frag") {
+                &mut stages.fragment
+            } else if stem.ends_with("comp") {
+                &mut stages.compute
+            } else {
+                &mut stages.vertex
+            };
+            *stage = Some(line.to_string());
+        }
+        stages
+    }
+}
+
+// Points a compile error at the offending line so it's easy to find, see `locate_error`.
+fn compile_error(
+    vfs: &VirtualFileSystem,
+    path: &str,
+    file_name: &str,
+    source: &str,
+    error: shaderc::Error,
+) -> AssetError {
+    locate_error(vfs, path, file_name, source, &error.to_string())
+}
+
+// glslang reports errors like "pbr_fragment.glsl:12: error: 'colour' : undeclared identifier".
+// Only the first error is kept, it doesn't give columns so the quoted token is looked up in
+// the line instead. Errors in includes are reported against the included file.
+fn locate_error(
+    vfs: &VirtualFileSystem,
+    path: &str,
+    file_name: &str,
+    source: &str,
+    message: &str,
+) -> AssetError {
+    let located = message.lines().filter(|line| line.contains("error")).find_map(|line| {
+        let mut parts = line.splitn(3, ':');
+        let file = parts.next()?.trim();
+        let line_number: usize = parts.next()?.trim().parse().ok()?;
+        let message = parts.next()?.trim();
+        Some((file, line_number.max(1), message))
+    });
+    let (file, line, message) = match located {
+        Some(located) => located,
+        None => return AssetError::decode(file_name, message.trim()),
+    };
+
+    let included_source;
+    let source = if file == file_name {
+        source
+    } else {
+        included_source = vfs
+            .read_to_string(&format!("{}{}", path, file))
+            .unwrap_or_default();
+        &included_source
+    };
+    let text = source.lines().nth(line - 1).unwrap_or("");
+    let column = message
+        .split('\'')
+        .nth(1)
+        .filter(|token| !token.is_empty())
+        .and_then(|token| text.find(token))
+        .map_or(1, |index| index + 1);
+
+    let gutter = line.to_string();
+    let excerpt = format!(
+        "{} | {}\n{} | {}^",
+        gutter,
+        text,
+        " ".repeat(gutter.len()),
+        " ".repeat(column - 1)
+    );
+    AssetError::Shader {
+        path: file.to_string(),
+        line,
+        column,
+        message: message.trim_start_matches("error:").trim().to_string(),
+        excerpt,
+    }
+}
+
+fn compile_options(
+    vfs: &VirtualFileSystem,
+    path: &str,
+    defines: &ShaderDefines,
+) -> shaderc::CompileOptions<'static> {
+    let mut options = shaderc::CompileOptions::new().unwrap();
+
+    #[cfg(not(debug_assertions))]
+    {
+        options.set_optimization_level(shaderc::OptimizationLevel::Performance);
+    }
+    #[cfg(debug_assertions)]
+    {
+        options.set_optimization_level(shaderc::OptimizationLevel::Zero);
+    }
+
+    options.add_macro_definition("EP", Some("main"));
+    for (name, value) in defines.iter() {
+        options.add_macro_definition(name, Some(value));
+    }
+    // Includes are read through the virtual file system too so they work from a pack.
+    let (vfs, path) = (vfs.clone(), path.to_string());
+    options.set_include_callback(move |file_path, _include_type, _, _| {
+        let shader_path = format!("{}{}", path, file_path);
+        let contents = vfs
+            .read_to_string(&shader_path)
+            .map_err(|err| format!("Unable to read the file: {}", err))?;
+        Result::Ok(shaderc::ResolvedInclude {
+            resolved_name: file_path.to_string(),
+            content: contents,
+        })
+    });
+    options
+}
+
+/// Compiles a single glsl compute shader, includes are relative to `path`.
+pub(crate) fn compile_compute(
+    device: &wgpu::Device,
+    vfs: &VirtualFileSystem,
+    path: &str,
+    file_name: &str,
+) -> Result<wgpu::ShaderModule, AssetError> {
+    let mut compiler = shaderc::Compiler::new().unwrap();
+    let options = compile_options(vfs, path, &ShaderDefines::default());
+    let contents = vfs.read_to_string(&format!("{}{}", path, file_name))?;
+
+    let spirv = compiler
+        .compile_into_spirv(
+            &contents,
+            shaderc::ShaderKind::Compute,
+            file_name,
+            "main",
+            Some(&options),
+        )
+        .map_err(|err| compile_error(vfs, path, file_name, &contents, err))?;
+    Ok(device.create_shader_module(spirv.as_binary()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate_error() {
+        let source = "#version 450\nvoid main() {\n    vec4 color = colour;\n}\n";
+        let message = "sky.glsl:3: error: 'colour' : undeclared identifier\n\
+                       sky.glsl:3: error: '' : compilation terminated\n2 errors generated.\n";
+        match locate_error(&VirtualFileSystem::new(), "", "sky.glsl", source, message) {
+            AssetError::Shader {
+                path,
+                line,
+                column,
+                message,
+                excerpt,
+            } => {
+                assert_eq!(path, "sky.glsl");
+                assert_eq!((line, column), (3, 18));
+                assert_eq!(message, "'colour' : undeclared identifier");
+                assert_eq!(excerpt, "3 |     vec4 color = colour;\n  |                  ^");
+            }
+            error => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[test]
+    fn test_stage_files() {
+        let lines = vec!["pbr_fragment.glsl".to_string(), "pbr_vertex.glsl".to_string()];
+        let stages = StageFiles::parse(&lines);
+        assert_eq!(stages.vertex.as_deref(), Some("pbr_vertex.glsl"));
+        assert_eq!(stages.fragment.as_deref(), Some("pbr_fragment.glsl"));
+        assert_eq!(stages.compute, None);
+
+        let stages = StageFiles::parse(&["clouds_composite_vert.glsl".to_string()]);
+        assert_eq!(stages.vertex.as_deref(), Some("clouds_composite_vert.glsl"));
+
+        let stages = StageFiles::parse(&["particles_comp.glsl".to_string(), "".to_string()]);
+        assert_eq!(stages.compute.as_deref(), Some("particles_comp.glsl"));
+        assert_eq!(stages.vertex, None);
+    }
 
-        Shader { fragment, vertex }
+    #[test]
+    fn test_variant_name() {
+        assert_eq!(ShaderDefines::new().variant_name("pbr.shader"), "pbr.shader");
+        let defines = ShaderDefines::new().with("LAYERS", 4).with_flag("NO_NORMAL_MAP");
+        assert_eq!(defines.variant_name("pbr.shader"), "pbr.shader[LAYERS=4,NO_NORMAL_MAP=1]");
     }
 }
@@ -15,6 +15,8 @@ pub enum SkyboxType {
     ClearColor,
     HdrCubemap,
     RealTime,
+    /// Physically based scattering, set it up with the `Atmosphere` resource.
+    Atmosphere,
 }
 
 pub struct Skybox {
@@ -156,6 +158,20 @@ impl Skybox {
         }
     }
 
+    /// A sky drawn from the `Atmosphere` resource, the sun follows the first directional light.
+    pub fn create_atmosphere() -> Self {
+        Self {
+            size: 0.0,
+            color_texture: None,
+            color_view: None,
+            cubemap_sampler: None,
+            cubemap_bind_group: None,
+            pbr_bind_group: None,
+            clear_color: Vec3::new(0.0, 0.0, 0.0),
+            skybox_type: SkyboxType::Atmosphere,
+        }
+    }
+
     pub(crate) fn create_realtime_bind_group(
         &mut self,
         device: &wgpu::Device,
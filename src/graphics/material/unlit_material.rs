@@ -1,8 +1,8 @@
-use super::Image;
-use crate::graphics::pipeline::BindGroupWithData;
+use super::{FallbackTexture, Image};
+use crate::graphics::{pipeline::BindGroupWithData, RenderQueue};
 use bytemuck::{Pod, Zeroable};
 use nalgebra_glm::Vec4;
-use std::{collections::HashMap, mem};
+use std::collections::HashMap;
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
@@ -17,6 +17,7 @@ pub struct UnlitMaterial {
     pub index: u32,
     pub main_texture: String,
     pub color: Vec4,
+    pub render_queue: RenderQueue,
     pub(crate) bind_group_data: Option<BindGroupWithData>,
 }
 
@@ -30,6 +31,7 @@ impl UnlitMaterial {
             index: material_index,
             main_texture: main_texture.clone(),
             color,
+            render_queue: RenderQueue::default(),
             bind_group_data: None,
         }
     }
@@ -42,21 +44,12 @@ impl UnlitMaterial {
         device: &wgpu::Device,
         local_bind_group_layout: &wgpu::BindGroupLayout,
     ) {
-        let material_uniform_size = mem::size_of::<UnlitUniform>() as wgpu::BufferAddress;
-        let uniform_buf = device.create_buffer(&wgpu::BufferDescriptor {
-            size: material_uniform_size,
-            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
-            label: None,
-        });
+        let uniform_buf = device.create_buffer_with_data(
+            bytemuck::bytes_of(&UnlitUniform { color: self.color }),
+            wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        );
 
-        // Asset manager will panic if image doesn't exist, but we don't want that.
-        // So use get_image_option instead.
-        let image = images
-            .get(&self.main_texture)
-            .unwrap_or(
-                images.get("white.png")
-                    .unwrap_or_else(|| panic!("UnlitMaterial Error: Couldn't find default white texture. Please make sure it exists in the asset folder or make sure your material's image can be found."))
-            );
+        let image = FallbackTexture::White.get_or_fallback(images, &self.main_texture);
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &local_bind_group_layout,
@@ -1,7 +1,18 @@
-use super::material::PBRMaterial;
-use crate::graphics::material::Material;
+use super::animation::{
+    AnimationClip, Channel, ChannelValues, Interpolation, Joint, Skeleton, MAX_JOINTS,
+};
+use super::bounds::{Aabb, BoundingSphere};
+use super::material::{
+    image::DecodedImage, AlphaMode, FallbackTexture, Image, ImageInfo, PBRMaterial,
+    TextureTransform, UnlitMaterial,
+};
+use crate::graphics::{
+    material::Material, MeshImport, RenderQueue, UvOrigin, VertexStateBuilder,
+};
+use crate::AssetError;
 use bytemuck::{Pod, Zeroable};
-use nalgebra_glm::{Vec2, Vec3, Vec4};
+use nalgebra_glm::{Mat4, Quat, Vec2, Vec3, Vec4};
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsStr;
 use std::path::Path;
 
@@ -18,6 +29,42 @@ pub struct MeshVertexData {
 unsafe impl Zeroable for MeshVertexData {}
 unsafe impl Pod for MeshVertexData {}
 
+/// Joint indices and weights for skinned vertices.
+/// Stored in it's own vertex buffer so static meshes don't pay for it.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SkinVertexData {
+    pub joints: [u32; 4],
+    pub weights: Vec4,
+}
+
+unsafe impl Zeroable for SkinVertexData {}
+unsafe impl Pod for SkinVertexData {}
+
+/// Vertex colors and a second set of texture coordinates, glTF's COLOR_0 and TEXCOORD_1.
+/// Stored in it's own vertex buffer like `SkinVertexData`, meshes with it are drawn with a
+/// pipeline variant that reads it.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct AttributeVertexData {
+    pub color: Vec4,
+    pub uv1: Vec2,
+}
+
+unsafe impl Zeroable for AttributeVertexData {}
+unsafe impl Pod for AttributeVertexData {}
+
+impl AttributeVertexData {
+    // Adds the buffer after the pipeline's own ones, at locations that none of them use.
+    pub(crate) fn add_buffer_descriptor(vertex_state: &mut VertexStateBuilder) {
+        vertex_state.new_buffer_descriptor(
+            std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            wgpu::InputStepMode::Vertex,
+            wgpu::vertex_attr_array![9 => Float4, 10 => Float2].to_vec(),
+        );
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct MeshTangentLine {
@@ -42,18 +89,211 @@ impl Default for MeshVertexData {
 pub struct SubMesh {
     pub vertices: Vec<MeshVertexData>,
     pub tangent_lines: Vec<MeshTangentLine>,
+    /// Empty unless the mesh is skinned.
+    pub skin_vertices: Vec<SkinVertexData>,
+    /// Empty unless the file had vertex colors or a second uv set.
+    pub attribute_vertices: Vec<AttributeVertexData>,
+    /// Bounds of the vertices in mesh space, skinned meshes use their bind pose.
+    pub aabb: Aabb,
+    pub bounding_sphere: BoundingSphere,
     indices: Vec<u32>,
     pub(crate) index_count: usize,
     mode: wgpu::PrimitiveTopology,
     material_id: Option<usize>,
     pub(crate) vertex_buffer: Option<wgpu::Buffer>,
     pub(crate) tangent_line_buffer: Option<wgpu::Buffer>,
-    pub(crate) index_buffer: wgpu::Buffer,
+    pub(crate) index_buffer: Option<wgpu::Buffer>,
+    pub(crate) skin_buffer: Option<wgpu::Buffer>,
+    pub(crate) attribute_buffer: Option<wgpu::Buffer>,
+    has_uvs: bool,
+    generated_tangents: bool,
 
     // Material index is stored here.
     pub material_index: u32,
 }
 
+impl SubMesh {
+    /// The corners of each triangle in mesh space, empty if the sub mesh isn't a triangle list.
+    pub fn triangles(&self) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        let chunks = if self.mode == wgpu::PrimitiveTopology::TriangleList {
+            self.indices.chunks_exact(3)
+        } else {
+            [].chunks_exact(3)
+        };
+        chunks.map(move |triangle| {
+            [
+                self.vertices[triangle[0] as usize].position,
+                self.vertices[triangle[1] as usize].position,
+                self.vertices[triangle[2] as usize].position,
+            ]
+        })
+    }
+
+    /// False if the file had no texture coordinates, every vertex's uv is zero then.
+    pub fn has_uvs(&self) -> bool {
+        self.has_uvs
+    }
+
+    /// True if the file had no tangents and they were generated while importing.
+    pub fn generated_tangents(&self) -> bool {
+        self.generated_tangents
+    }
+
+    /// True if the sub mesh has vertex colors or a second uv set.
+    pub fn has_vertex_attributes(&self) -> bool {
+        !self.attribute_vertices.is_empty()
+    }
+
+    /// Bytes of vertex and index data kept on the CPU.
+    pub fn cpu_size(&self) -> usize {
+        use std::mem::size_of;
+        self.vertices.capacity() * size_of::<MeshVertexData>()
+            + self.tangent_lines.capacity() * size_of::<MeshTangentLine>()
+            + self.skin_vertices.capacity() * size_of::<SkinVertexData>()
+            + self.attribute_vertices.capacity() * size_of::<AttributeVertexData>()
+            + self.indices.capacity() * size_of::<u32>()
+    }
+}
+
+impl SubMesh {
+    // Generates tangents if the file didn't have them and builds the debug lines that show them.
+    fn prepare_tangents(&mut self, had_tangents: bool, path: &str) {
+        self.generated_tangents = !had_tangents;
+        if !had_tangents {
+            log::info!(
+                "No tangents found for: {} generating tangents instead!",
+                &path
+            );
+            mikktspace::generate_tangents(self);
+        }
+
+        let tangents: Vec<(Vec3, Vec3)> = self
+            .vertices
+            .iter()
+            .map(|data| (data.tangent.xyz() * data.tangent.w, data.position))
+            .collect();
+        let mut tangent_lines = Vec::new();
+        for (tangent, position) in tangents.iter() {
+            let position: Vec3 = position.clone(); // * 50.0;
+            let tangent: Vec3 = tangent.clone();
+            let scale: f32 = 0.1;
+            let vec3_tangent: Vec3 = Vec3::new(
+                position.x + (tangent.x * scale),
+                position.y + (tangent.y * scale),
+                position.z + (tangent.z * scale),
+            );
+            tangent_lines.push(MeshTangentLine {
+                pos: position.clone(),
+                color: 0.5 * (tangent + Vec3::new(1.0, 1.0, 1.0)),
+            });
+            tangent_lines.push(MeshTangentLine {
+                pos: vec3_tangent,
+                color: 0.5 * (tangent + Vec3::new(1.0, 1.0, 1.0)),
+            });
+        }
+        tangent_lines.push(MeshTangentLine {
+            pos: Vec3::new(0.0, 0.0, 0.0),
+            color: Vec3::new(0.0, 0.0, 1.0),
+        });
+        tangent_lines.push(MeshTangentLine {
+            pos: Vec3::new(0.0, 0.0, 5.0),
+            color: Vec3::new(0.0, 0.0, 1.0),
+        });
+        tangent_lines.push(MeshTangentLine {
+            pos: Vec3::new(0.0, 0.0, 0.0),
+            color: Vec3::new(0.0, 1.0, 0.0),
+        });
+        tangent_lines.push(MeshTangentLine {
+            pos: Vec3::new(0.0, 5.0, 0.0),
+            color: Vec3::new(0.0, 1.0, 0.0),
+        });
+        tangent_lines.push(MeshTangentLine {
+            pos: Vec3::new(0.0, 0.0, 0.0),
+            color: Vec3::new(1.0, 0.0, 0.0),
+        });
+        tangent_lines.push(MeshTangentLine {
+            pos: Vec3::new(5.0, 0.0, 0.0),
+            color: Vec3::new(1.0, 0.0, 0.0),
+        });
+        self.tangent_lines = tangent_lines;
+    }
+
+    // Merges the vertices that fall in the same cell of a grid over `bounds`, dropping the
+    // triangles that collapse. None for anything but triangle lists or if nothing's left.
+    fn decimate(&self, bounds: &Aabb, cells: f32) -> Option<SubMesh> {
+        if self.mode != wgpu::PrimitiveTopology::TriangleList {
+            return None;
+        }
+        let cell_size = (bounds.max - bounds.min).amax() / cells;
+        if cell_size <= 0.0 {
+            return None;
+        }
+
+        let mut lookup = HashMap::new();
+        let mut vertices = Vec::new();
+        let mut attribute_vertices = Vec::new();
+        let remap: Vec<u32> = self
+            .vertices
+            .iter()
+            .enumerate()
+            .map(|(index, vertex)| {
+                let cell = (vertex.position - bounds.min) / cell_size;
+                // Vertices facing different ways aren't merged so hard edges survive.
+                let facing = (vertex.normal.x > 0.0) as u8
+                    | ((vertex.normal.y > 0.0) as u8) << 1
+                    | ((vertex.normal.z > 0.0) as u8) << 2;
+                let key = (cell.x as i32, cell.y as i32, cell.z as i32, facing);
+                *lookup.entry(key).or_insert_with(|| {
+                    vertices.push(*vertex);
+                    attribute_vertices.extend(self.attribute_vertices.get(index).copied());
+                    vertices.len() as u32 - 1
+                })
+            })
+            .collect();
+        let indices: Vec<u32> = self
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                [
+                    remap[triangle[0] as usize],
+                    remap[triangle[1] as usize],
+                    remap[triangle[2] as usize],
+                ]
+            })
+            .filter(|[a, b, c]| a != b && b != c && a != c)
+            .flat_map(|triangle| triangle.to_vec())
+            .collect();
+        if indices.is_empty() {
+            return None;
+        }
+
+        let aabb = Aabb::from_points(vertices.iter().map(|vertex| &vertex.position));
+        let mut sub_mesh = SubMesh {
+            vertices,
+            tangent_lines: Vec::new(),
+            skin_vertices: Vec::new(),
+            attribute_vertices,
+            aabb,
+            bounding_sphere: BoundingSphere::from_aabb(&aabb),
+            index_count: indices.len(),
+            indices,
+            mode: self.mode,
+            material_id: self.material_id,
+            vertex_buffer: None,
+            tangent_line_buffer: None,
+            index_buffer: None,
+            skin_buffer: None,
+            attribute_buffer: None,
+            has_uvs: self.has_uvs,
+            generated_tangents: false,
+            material_index: self.material_index,
+        };
+        sub_mesh.prepare_tangents(true, "lod");
+        sub_mesh.generated_tangents = self.generated_tangents;
+        Some(sub_mesh)
+    }
+}
+
 fn vertex(sub_mesh: &SubMesh, face: usize, vert: usize) -> &MeshVertexData {
     &sub_mesh.vertices[sub_mesh.indices[face * 3 + vert] as usize]
 }
@@ -88,84 +328,245 @@ impl mikktspace::Geometry for SubMesh {
     }
 }
 
+/// A lower detail version of a mesh, using the same materials.
+pub struct MeshLod {
+    pub sub_meshes: Vec<SubMesh>,
+    /// The LOD is used once the mesh's bounding sphere covers less than this much of the
+    /// screen's height.
+    pub screen_size: f32,
+}
+
+impl MeshLod {
+    /// Each level is used at half the size of the one before it.
+    pub fn default_screen_size(level: usize) -> f32 {
+        0.5f32.powi(level as i32)
+    }
+}
+
 pub struct Mesh {
     pub sub_meshes: Vec<SubMesh>,
+    /// The first skin in the file, if it has one.
+    pub skeleton: Option<Skeleton>,
+    /// Animations that target the skeleton's joints.
+    pub animations: Vec<AnimationClip>,
+    /// Bounds of all of the sub meshes in mesh space.
+    pub aabb: Aabb,
+    /// Images packed inside a .glb file, the asset manager adds them with the mesh under
+    /// "<file name>#<image index>".
+    pub(crate) embedded_images: Vec<(String, DecodedImage)>,
+    /// Lower detail versions of `sub_meshes`, from most to least detailed.
+    pub lods: Vec<MeshLod>,
 }
 
 impl Mesh {
-    /// Imports glTF 2.0
+    /// True if any sub mesh has vertex colors or a second uv set.
+    pub fn has_vertex_attributes(&self) -> bool {
+        self.sub_meshes.iter().any(SubMesh::has_vertex_attributes)
+    }
+
+    /// Returns true if the mesh can be deformed by a skeleton.
+    pub fn is_skinned(&self) -> bool {
+        self.skeleton.is_some()
+            && self
+                .sub_meshes
+                .iter()
+                .all(|sub_mesh| !sub_mesh.skin_vertices.is_empty())
+    }
+
+    /// Bytes the mesh keeps on the CPU after it's buffers are created, including embedded
+    /// images that haven't been uploaded yet.
+    pub fn cpu_size(&self) -> usize {
+        let sub_meshes: usize = self
+            .sub_meshes
+            .iter()
+            .chain(self.lods.iter().flat_map(|lod| lod.sub_meshes.iter()))
+            .map(SubMesh::cpu_size)
+            .sum();
+        let joints = self.skeleton.as_ref().map_or(0, |skeleton| {
+            skeleton.joints.capacity() * std::mem::size_of::<Joint>()
+        });
+        let animations: usize = self.animations.iter().map(AnimationClip::cpu_size).sum();
+        let images: usize = self
+            .embedded_images
+            .iter()
+            .map(|(_, image)| image.size())
+            .sum();
+        sub_meshes + joints + animations + images
+    }
+
+    /// The sub meshes to draw for a level of detail, 0 is the full detail mesh.
+    /// Levels past the last LOD use the last one.
+    pub fn lod_sub_meshes(&self, level: usize) -> &[SubMesh] {
+        match level.min(self.lods.len()) {
+            0 => &self.sub_meshes,
+            level => &self.lods[level - 1].sub_meshes,
+        }
+    }
+
+    /// The level of detail to use when the mesh covers `screen_size` of the screen's height.
+    pub fn select_lod(&self, screen_size: f32) -> usize {
+        self.lods
+            .iter()
+            .take_while(|lod| screen_size < lod.screen_size)
+            .count()
+    }
+
+    /// Replaces the LODs with `levels` simplified versions of the mesh, each with about half
+    /// the detail of the one before it. Skinned meshes and anything that isn't a triangle
+    /// list are left alone. Call it before the mesh's buffers are created.
+    pub fn generate_lods(&mut self, levels: usize) {
+        if self.is_skinned() {
+            return;
+        }
+        self.lods = (1..=levels)
+            .filter_map(|level| {
+                // Vertices are merged on a grid that gets coarser with every level.
+                let cells = 64.0 / 2.0f32.powi(level as i32 - 1);
+                let sub_meshes = self
+                    .sub_meshes
+                    .iter()
+                    .map(|sub_mesh| sub_mesh.decimate(&self.aabb, cells))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(MeshLod {
+                    sub_meshes,
+                    screen_size: MeshLod::default_screen_size(level),
+                })
+            })
+            .collect();
+    }
+
+    /// Finds an animation by name.
+    pub fn get_animation(&self, name: &str) -> Option<&AnimationClip> {
+        self.animations.iter().find(|clip| clip.name == name)
+    }
+
+    /// Imports glTF 2.0 (.gltf or .glb) or OBJ files.
     pub fn new<T>(
         device: &wgpu::Device,
         path: T,
         material_start_index: u32,
-    ) -> (Mesh, Vec<Material>)
+    ) -> Result<(Mesh, Vec<Material>), AssetError>
+    where
+        T: Into<String>,
+    {
+        Self::new_with_usage(
+            device,
+            path,
+            material_start_index,
+            wgpu::BufferUsage::empty(),
+        )
+    }
+
+    /// Same as `new`, adding `usage` to the vertex and index buffers.
+    /// Useful when a compute pass or readback needs to access the mesh data (STORAGE, COPY_SRC, etc).
+    pub fn new_with_usage<T>(
+        device: &wgpu::Device,
+        path: T,
+        material_start_index: u32,
+        usage: wgpu::BufferUsage,
+    ) -> Result<(Mesh, Vec<Material>), AssetError>
+    where
+        T: Into<String>,
+    {
+        let (mut mesh, materials) = Self::import(path, material_start_index)?;
+        mesh.create_buffers(device, usage);
+        Ok((mesh, materials))
+    }
+
+    /// Reads the mesh file into CPU side data without creating any GPU buffers.
+    /// This doesn't touch the GPU so it's safe to call from a loading thread.
+    pub(crate) fn import<T>(
+        path: T,
+        material_start_index: u32,
+    ) -> Result<(Mesh, Vec<Material>), AssetError>
     where
         T: Into<String>,
     {
+        let read = |path: &str| std::fs::read(path).map_err(|error| AssetError::io(path, error));
+        Self::import_with(
+            &path.into(),
+            material_start_index,
+            &MeshImport::default(),
+            &read,
+        )
+    }
+
+    /// Same as `import` but every file, including .bin buffers and .mtl libraries next to the
+    /// mesh, is read with `read`, and the mesh is converted with `import`. Used to import meshes
+    /// from the virtual file system.
+    pub(crate) fn import_with(
+        path: &str,
+        material_start_index: u32,
+        import: &MeshImport,
+        read: &dyn Fn(&str) -> Result<Vec<u8>, AssetError>,
+    ) -> Result<(Mesh, Vec<Material>), AssetError> {
+        let is_obj = path.to_lowercase().ends_with(".obj");
+        let (mut mesh, materials) = if is_obj {
+            Self::import_obj(path, material_start_index, read)?
+        } else {
+            Self::import_gltf(path, material_start_index, read)?
+        };
+        let format_origin = if is_obj {
+            UvOrigin::BottomLeft
+        } else {
+            UvOrigin::TopLeft
+        };
+        if *import != MeshImport::default() {
+            mesh.convert(import, import.flips_uvs(format_origin));
+        }
+        Ok((mesh, materials))
+    }
+
+    fn import_gltf(
+        path: &str,
+        material_start_index: u32,
+        read: &dyn Fn(&str) -> Result<Vec<u8>, AssetError>,
+    ) -> Result<(Mesh, Vec<Material>), AssetError> {
         let mut materials = Vec::new();
-        let path = path.into();
-        let (document, data, _) =
-            gltf::import(path.clone()).expect("Loaded the gltf file successfully!");
-        let get_buffer_data = |buffer: gltf::Buffer<'_>| data.get(buffer.index()).map(|x| &*x.0);
+        let path = path.to_string();
+        let file_name = Self::file_name(&path);
+        // .glb files work the same, the binary chunk ends up in `blob`.
+        let gltf::Gltf { document, blob } = gltf::Gltf::from_slice(&read(&path)?)
+            .map_err(|error| AssetError::decode(&path, error))?;
+        let data = Self::import_buffers(&document, blob, &path, read)?;
+        let get_buffer_data =
+            |buffer: gltf::Buffer<'_>| data.get(buffer.index()).map(Vec::as_slice);
 
-        // let mut meshes = Vec::new();
         let meshes = document.meshes().collect::<Vec<gltf::Mesh<'_>>>();
-        if meshes.len() > 1 {
+        // Meshes or the nodes using them can be named like "rock_LOD1" for lower detail
+        // versions, LOD0 or the first mesh without a level is the full detail one.
+        let lod_level = |mesh: &gltf::Mesh<'_>| {
+            mesh.name().and_then(Self::lod_level).or_else(|| {
+                document
+                    .nodes()
+                    .filter(|node| {
+                        node.mesh().map(|node_mesh| node_mesh.index()) == Some(mesh.index())
+                    })
+                    .find_map(|node| node.name().and_then(Self::lod_level))
+            })
+        };
+        let gltf_mesh: &gltf::Mesh<'_> = meshes
+            .iter()
+            .find(|mesh| lod_level(*mesh) == Some(0))
+            .or_else(|| meshes.iter().find(|mesh| lod_level(*mesh).is_none()))
+            .or_else(|| meshes.first())
+            .ok_or_else(|| AssetError::decode(&path, "gltf file doesn't contain a mesh"))?;
+        let mut lod_meshes: Vec<(usize, &gltf::Mesh<'_>)> = meshes
+            .iter()
+            .filter(|mesh| mesh.index() != gltf_mesh.index())
+            .filter_map(|mesh| Some((lod_level(mesh)?, mesh)))
+            .filter(|(level, _)| *level > 0)
+            .collect();
+        lod_meshes.sort_by_key(|(level, _)| *level);
+        if meshes.len() > lod_meshes.len() + 1 {
             log::warn!("Currently we only support 1 mesh per gltf object. If you have more than one it will not be rendered.");
         }
-        // For now we only support 1 mesh.
-        let gltf_mesh: &gltf::Mesh<'_> = meshes.first().unwrap();
 
         let mut sub_meshes = Vec::new();
         let primitives = gltf_mesh.primitives();
 
-        let images: Vec<gltf::Image<'_>> = document.images().collect();
-
         for primitive in primitives {
-            let reader = primitive.reader(get_buffer_data);
-            let positions: Vec<_> = reader
-                .read_positions()
-                .map(|iter| iter.collect())
-                .ok_or(format!("mesh primitive is missing positions"))
-                .unwrap();
-
-            let mut vertices: Vec<MeshVertexData> = positions
-                .iter()
-                .map(|pos| MeshVertexData {
-                    position: Vec3::from(pos.clone()),
-                    ..MeshVertexData::default()
-                })
-                .collect();
-
-            if let Some(normals) = reader.read_normals() {
-                for (i, normal) in normals.enumerate() {
-                    vertices[i].normal = Vec3::from(normal.clone());
-                }
-            }
-            if let Some(uvs) = reader.read_tex_coords(0) {
-                for (i, uv) in uvs.into_f32().enumerate() {
-                    vertices[i].uv = Vec2::from(uv.clone());
-                }
-            }
-
-            let mut had_tangents = false;
-            // Load tangents if we have them.
-            if let Some(tangents) = reader.read_tangents() {
-                for (i, tangent) in tangents.enumerate() {
-                    vertices[i].tangent = Vec4::new(tangent[0], tangent[1], tangent[2], tangent[3]);
-                }
-                had_tangents = true;
-            } else {
-                // TODO: Calculate tangents if we don't have them.
-                //warn!("Don't have tangents for mesh.");
-            }
-
-            let indices: Vec<u32> = if let Some(index_enum) = reader.read_indices() {
-                index_enum.into_u32().collect()
-            } else {
-                panic!("model doesn't have indices");
-            };
-
+            let material_index = material_start_index + materials.len() as u32;
             let gltf_material: gltf::Material<'_> = primitive.material();
             let pbr = gltf_material.pbr_metallic_roughness();
 
@@ -178,176 +579,947 @@ impl Mesh {
             );
 
             let main_info = pbr.base_color_texture();
-            let mut normal_texture = None;
-            let normals_texture = gltf_material.normal_texture();
-            if normals_texture.is_some() {
-                let normal_source = normals_texture.unwrap().texture().source().source();
-                match normal_source {
-                    gltf::image::Source::Uri { uri, .. } => {
-                        let texture_file_name = Some(
-                            Path::new(&uri)
-                                .file_name()
-                                .and_then(OsStr::to_str)
-                                .unwrap()
-                                .to_string(),
-                        );
-                        if texture_file_name.is_some() {
-                            normal_texture = Some(texture_file_name.unwrap());
-                        }
+            let normal_info = gltf_material.normal_texture();
+            let normal_texture = normal_info
+                .as_ref()
+                .and_then(|info| Self::get_source_file_name(&info.texture(), &file_name));
+            let occlusion_info = gltf_material.occlusion_texture();
+            let occlusion_texture = occlusion_info
+                .as_ref()
+                .and_then(|info| Self::get_source_file_name(&info.texture(), &file_name));
+            let roughness_info = pbr.metallic_roughness_texture();
+            let emissive_info = gltf_material.emissive_texture();
+
+            let main_texture = Self::get_texture_url(&main_info, &file_name);
+            let roughness_texture = Self::get_texture_url(&roughness_info, &file_name);
+            let emissive_texture = Self::get_texture_url(&emissive_info, &file_name);
+
+            if gltf_material.unlit() {
+                let mut material = UnlitMaterial::new(
+                    main_texture.unwrap_or_else(|| FallbackTexture::White.name().to_string()),
+                    color,
+                    material_index,
+                );
+                if gltf_material.alpha_mode() == gltf::material::AlphaMode::Blend {
+                    material.render_queue = RenderQueue::Transparent;
+                }
+                materials.push(Material::Unlit(material));
+            } else {
+                let mut material = PBRMaterial::new(
+                    main_texture.unwrap_or_else(|| FallbackTexture::White.name().to_string()),
+                    normal_texture.unwrap_or_else(|| FallbackTexture::Normal.name().to_string()),
+                    roughness_texture.unwrap_or_else(|| FallbackTexture::White.name().to_string()),
+                    color,
+                    material_index,
+                );
+                material.metallic = pbr.metallic_factor();
+                material.roughness = pbr.roughness_factor();
+                let emissive = gltf_material.emissive_factor();
+                material.emissive = Vec3::new(emissive[0], emissive[1], emissive[2]);
+                if let Some(emissive_texture) = emissive_texture {
+                    material.emissive_texture = emissive_texture;
+                }
+                if let Some(occlusion_texture) = occlusion_texture {
+                    material.occlusion_texture = occlusion_texture;
+                }
+                if let Some(info) = normal_info.as_ref() {
+                    material.normal_scale = info.scale();
+                }
+                if let Some(info) = occlusion_info.as_ref() {
+                    material.occlusion_strength = info.strength();
+                    material.occlusion_uv1 = info.tex_coord() == 1;
+                }
+                // Every texture shares the base color's transform, it's the one that's
+                // almost always set.
+                let transform = main_info.as_ref().and_then(|info| info.texture_transform());
+                if let Some(transform) = transform {
+                    material.texture_transform = TextureTransform {
+                        offset: Vec2::new(transform.offset()[0], transform.offset()[1]),
+                        rotation: transform.rotation(),
+                        scale: Vec2::new(transform.scale()[0], transform.scale()[1]),
+                    };
+                }
+                material.double_sided = gltf_material.double_sided();
+                match gltf_material.alpha_mode() {
+                    gltf::material::AlphaMode::Mask => {
+                        material.alpha_mode = AlphaMode::Mask(gltf_material.alpha_cutoff());
                     }
-                    _ => (),
+                    gltf::material::AlphaMode::Blend => {
+                        material.alpha_mode = AlphaMode::Blend;
+                        material.render_queue = RenderQueue::Transparent;
+                    }
+                    gltf::material::AlphaMode::Opaque => (),
                 }
+                materials.push(Material::PBR(material));
             }
-            let roughness_info = pbr.metallic_roughness_texture();
 
-            let main_texture = Self::get_texture_url(&main_info, &images);
-            let roughness_texture = Self::get_texture_url(&roughness_info, &images);
-
-            let material_index = material_start_index + materials.len() as u32;
-            let material = PBRMaterial::new(
-                main_texture.unwrap_or("white.png".to_string()),
-                normal_texture.unwrap_or("empty_normal.png".to_string()),
-                roughness_texture.unwrap_or("white.png".to_string()),
-                color,
+            sub_meshes.push(Self::import_primitive(
+                &primitive,
+                &path,
+                get_buffer_data,
                 material_index,
-            );
-            materials.push(Material::PBR(material));
+            )?);
+        }
 
-            let primitive_topology = Self::get_primitive_mode(primitive.mode());
+        let mut lods = Vec::new();
+        for (level, lod_mesh) in lod_meshes {
+            let mut lod_sub_meshes = Vec::new();
+            for primitive in lod_mesh.primitives() {
+                // LODs share the materials of the full detail mesh.
+                let material_index = sub_meshes
+                    .iter()
+                    .find(|sub_mesh| sub_mesh.material_id == primitive.material().index())
+                    .or_else(|| sub_meshes.first())
+                    .map_or(material_start_index, |sub_mesh| sub_mesh.material_index);
+                lod_sub_meshes.push(Self::import_primitive(
+                    &primitive,
+                    &path,
+                    get_buffer_data,
+                    material_index,
+                )?);
+            }
+            lods.push(MeshLod {
+                sub_meshes: lod_sub_meshes,
+                screen_size: MeshLod::default_screen_size(level),
+            });
+        }
 
-            let index_buffer = device
-                .create_buffer_with_data(&bytemuck::cast_slice(&indices), wgpu::BufferUsage::INDEX);
-            let index_count = indices.len();
+        let (skeleton, animations) = Self::import_skeleton(&document, &path, get_buffer_data)?;
+        let aabb = Self::combined_aabb(&sub_meshes);
 
+        // Images stored inside the file itself, usually from a .glb. Color textures are sRGB.
+        let srgb_textures: HashSet<String> = materials
+            .iter()
+            .flat_map(|material| match material {
+                Material::PBR(data) => {
+                    vec![data.main_texture.clone(), data.emissive_texture.clone()]
+                }
+                Material::Unlit(data) => vec![data.main_texture.clone()],
+            })
+            .collect();
+        let mut embedded_images = Vec::new();
+        for image in document.images() {
+            if let gltf::image::Source::View { view, .. } = image.source() {
+                let name = Self::embedded_image_name(&file_name, image.index());
+                let bytes = data
+                    .get(view.buffer().index())
+                    .and_then(|buffer| buffer.get(view.offset()..view.offset() + view.length()));
+                match bytes.and_then(|bytes| image::load_from_memory(bytes).ok()) {
+                    Some(pixels) => {
+                        let srgb = srgb_textures.contains(&name);
+                        let decoded =
+                            Image::decode_rgba8(pixels.to_rgba(), srgb, &ImageInfo::default());
+                        embedded_images.push((name, decoded));
+                    }
+                    None => log::warn!(
+                        "Skipping embedded image {}, it's format isn't supported.",
+                        name
+                    ),
+                }
+            }
+        }
+
+        Ok((
+            Mesh {
+                sub_meshes,
+                skeleton,
+                animations,
+                aabb,
+                embedded_images,
+                lods,
+            },
+            materials,
+        ))
+    }
+
+    // Each object or group becomes a sub mesh with it's own material. Materials using
+    // illumination model 0 (color without any lighting) are imported as unlit.
+    fn import_obj(
+        path: &str,
+        material_start_index: u32,
+        read: &dyn Fn(&str) -> Result<Vec<u8>, AssetError>,
+    ) -> Result<(Mesh, Vec<Material>), AssetError> {
+        let obj = read(path)?;
+        // Material libraries are relative to the .obj file.
+        let load_mtl = |mtl_path: &Path| -> tobj::MTLLoadResult {
+            let mtl_path = Self::sibling_path(path, mtl_path.to_str().unwrap_or(""));
+            let mtl = read(&mtl_path).map_err(|_| tobj::LoadError::OpenFileFailed)?;
+            tobj::load_mtl_buf(&mut mtl.as_slice())
+        };
+        let (models, obj_materials) = tobj::load_obj_buf(&mut obj.as_slice(), true, load_mtl)
+            .map_err(|error| AssetError::decode(path, error))?;
+
+        let mut materials: Vec<Material> = obj_materials
+            .iter()
+            .enumerate()
+            .map(|(index, material)| {
+                Self::import_obj_material(material, material_start_index + index as u32)
+            })
+            .collect();
+        let mut default_material = None;
+
+        let mut sub_meshes = Vec::new();
+        for model in models.iter() {
+            let obj_mesh = &model.mesh;
+            if obj_mesh.positions.is_empty() || obj_mesh.indices.is_empty() {
+                continue;
+            }
+            let material_index = match obj_mesh.material_id {
+                Some(id) if id < obj_materials.len() => material_start_index + id as u32,
+                _ => *default_material.get_or_insert_with(|| {
+                    let index = material_start_index + materials.len() as u32;
+                    materials.push(Material::PBR(PBRMaterial::new(
+                        FallbackTexture::White.name(),
+                        FallbackTexture::Normal.name(),
+                        FallbackTexture::White.name(),
+                        Vec4::new(1.0, 1.0, 1.0, 1.0),
+                        index,
+                    )));
+                    index
+                }),
+            };
+
+            let mut vertices: Vec<MeshVertexData> = obj_mesh
+                .positions
+                .chunks_exact(3)
+                .map(|position| MeshVertexData {
+                    position: Vec3::new(position[0], position[1], position[2]),
+                    ..MeshVertexData::default()
+                })
+                .collect();
+            let has_normals = obj_mesh.normals.len() == obj_mesh.positions.len();
+            if has_normals {
+                for (vertex, normal) in vertices.iter_mut().zip(obj_mesh.normals.chunks_exact(3)) {
+                    vertex.normal = Vec3::new(normal[0], normal[1], normal[2]);
+                }
+            }
+            let has_uvs = obj_mesh.texcoords.len() / 2 == vertices.len();
+            if has_uvs {
+                // OBJ's V goes up, ours goes down like glTF.
+                for (vertex, uv) in vertices.iter_mut().zip(obj_mesh.texcoords.chunks_exact(2)) {
+                    vertex.uv = Vec2::new(uv[0], 1.0 - uv[1]);
+                }
+            }
+            let indices = obj_mesh.indices.clone();
+            if !has_normals {
+                Self::generate_normals(&mut vertices, &indices);
+            }
+
+            let index_count = indices.len();
+            let aabb = Aabb::from_points(vertices.iter().map(|vertex| &vertex.position));
             let mut sub_mesh = SubMesh {
                 vertices,
                 tangent_lines: Vec::new(),
+                skin_vertices: Vec::new(),
+                attribute_vertices: Vec::new(),
+                aabb,
+                bounding_sphere: BoundingSphere::from_aabb(&aabb),
                 indices,
                 index_count,
-                mode: primitive_topology,
-                material_id: primitive.material().index(),
+                mode: wgpu::PrimitiveTopology::TriangleList,
+                material_id: obj_mesh.material_id,
                 vertex_buffer: None,
                 tangent_line_buffer: None,
-                index_buffer,
+                index_buffer: None,
+                skin_buffer: None,
+                attribute_buffer: None,
+                has_uvs,
+                generated_tangents: false,
                 material_index,
             };
+            sub_mesh.prepare_tangents(false, path);
+            sub_meshes.push(sub_mesh);
+        }
 
-            if !had_tangents {
-                log::info!(
-                    "No tangents found for: {} generating tangents instead!",
-                    &path
-                );
-                mikktspace::generate_tangents(&mut sub_mesh);
+        if sub_meshes.is_empty() {
+            return Err(AssetError::decode(path, "obj file doesn't contain any faces"));
+        }
+
+        let aabb = Self::combined_aabb(&sub_meshes);
+        Ok((
+            Mesh {
+                sub_meshes,
+                skeleton: None,
+                animations: Vec::new(),
+                aabb,
+                embedded_images: Vec::new(),
+                lods: Vec::new(),
+            },
+            materials,
+        ))
+    }
+
+    // Rotates and scales the mesh into the engine's space and flips it's uvs if asked to.
+    // Skinned meshes get the same change at their root joints so they still line up.
+    fn convert(&mut self, import: &MeshImport, flip_uvs: bool) {
+        let rotation = import.up_axis.rotation();
+        let lod_sub_meshes = self.lods.iter_mut().flat_map(|lod| lod.sub_meshes.iter_mut());
+        for sub_mesh in self.sub_meshes.iter_mut().chain(lod_sub_meshes) {
+            for vertex in sub_mesh.vertices.iter_mut() {
+                vertex.position =
+                    nalgebra_glm::quat_rotate_vec3(&rotation, &vertex.position) * import.scale;
+                vertex.normal = nalgebra_glm::quat_rotate_vec3(&rotation, &vertex.normal);
+                let tangent = nalgebra_glm::quat_rotate_vec3(&rotation, &vertex.tangent.xyz());
+                // Flipping V mirrors the bitangent.
+                let sign = if flip_uvs {
+                    -vertex.tangent.w
+                } else {
+                    vertex.tangent.w
+                };
+                vertex.tangent = Vec4::new(tangent.x, tangent.y, tangent.z, sign);
+                if flip_uvs {
+                    vertex.uv = UvOrigin::BottomLeft.to_top_left(vertex.uv);
+                }
             }
+            if flip_uvs {
+                for attribute in sub_mesh.attribute_vertices.iter_mut() {
+                    attribute.uv1 = UvOrigin::BottomLeft.to_top_left(attribute.uv1);
+                }
+            }
+            let positions = sub_mesh.vertices.iter().map(|vertex| &vertex.position);
+            sub_mesh.aabb = Aabb::from_points(positions);
+            sub_mesh.bounding_sphere = BoundingSphere::from_aabb(&sub_mesh.aabb);
+            let generated_tangents = sub_mesh.generated_tangents;
+            sub_mesh.prepare_tangents(true, "");
+            sub_mesh.generated_tangents = generated_tangents;
+        }
+        self.aabb = Self::combined_aabb(&self.sub_meshes);
 
-            let tangents: Vec<(Vec3, Vec3)> = sub_mesh
-                .vertices
-                .iter()
-                .map(|data| (data.tangent.xyz() * data.tangent.w, data.position))
-                .collect();
-            let mut tangent_lines = Vec::new();
-            for (tangent, position) in tangents.iter() {
-                let position: Vec3 = position.clone(); // * 50.0;
-                let tangent: Vec3 = tangent.clone();
-                let scale: f32 = 0.1;
-                let vec3_tangent: Vec3 = Vec3::new(
-                    position.x + (tangent.x * scale),
-                    position.y + (tangent.y * scale),
-                    position.z + (tangent.z * scale),
-                );
-                tangent_lines.push(MeshTangentLine {
-                    pos: position.clone(),
-                    color: 0.5 * (tangent + Vec3::new(1.0, 1.0, 1.0)),
-                });
-                tangent_lines.push(MeshTangentLine {
-                    pos: vec3_tangent,
-                    color: 0.5 * (tangent + Vec3::new(1.0, 1.0, 1.0)),
+        if let Some(skeleton) = self.skeleton.as_mut() {
+            skeleton.transform_roots(&rotation, import.scale);
+            for clip in self.animations.iter_mut() {
+                clip.transform_roots(skeleton, &rotation, import.scale);
+            }
+        }
+    }
+
+    /// A flat square on the xz plane facing up, `size` units across and centered on the origin.
+    /// Call `create_buffers` or hand it to `AssetManager::insert_mesh` before drawing it.
+    pub fn plane(size: f32, material_index: u32) -> Mesh {
+        let half = size * 0.5;
+        let corners = [(-1.0, -1.0), (-1.0, 1.0), (1.0, 1.0), (1.0, -1.0)];
+        let vertices: Vec<MeshVertexData> = corners
+            .iter()
+            .map(|(x, z)| MeshVertexData {
+                position: Vec3::new(x * half, 0.0, z * half),
+                normal: Vec3::new(0.0, 1.0, 0.0),
+                uv: Vec2::new((x + 1.0) * 0.5, (z + 1.0) * 0.5),
+                tangent: Vec4::new(1.0, 0.0, 0.0, -1.0),
+            })
+            .collect();
+        let indices = vec![0, 1, 2, 0, 2, 3];
+        let aabb = Aabb::from_points(vertices.iter().map(|vertex| &vertex.position));
+        let mut sub_mesh = SubMesh {
+            vertices,
+            tangent_lines: Vec::new(),
+            skin_vertices: Vec::new(),
+            attribute_vertices: Vec::new(),
+            aabb,
+            bounding_sphere: BoundingSphere::from_aabb(&aabb),
+            index_count: indices.len(),
+            indices,
+            mode: wgpu::PrimitiveTopology::TriangleList,
+            material_id: None,
+            vertex_buffer: None,
+            tangent_line_buffer: None,
+            index_buffer: None,
+            skin_buffer: None,
+            attribute_buffer: None,
+            has_uvs: true,
+            generated_tangents: false,
+            material_index,
+        };
+        sub_mesh.prepare_tangents(true, "plane");
+        Mesh {
+            sub_meshes: vec![sub_mesh],
+            skeleton: None,
+            animations: Vec::new(),
+            aabb,
+            embedded_images: Vec::new(),
+            lods: Vec::new(),
+        }
+    }
+
+    /// Like `plane` but split into `subdivisions` squares along each side, for surfaces that
+    /// are bent on the GPU like a `DeformableSurface`.
+    pub fn grid(size: f32, subdivisions: u32, material_index: u32) -> Mesh {
+        let subdivisions = subdivisions.max(1);
+        let row = subdivisions + 1;
+        let mut vertices = Vec::with_capacity((row * row) as usize);
+        for z in 0..row {
+            for x in 0..row {
+                let uv = Vec2::new(x as f32, z as f32) / subdivisions as f32;
+                vertices.push(MeshVertexData {
+                    position: Vec3::new((uv.x - 0.5) * size, 0.0, (uv.y - 0.5) * size),
+                    normal: Vec3::new(0.0, 1.0, 0.0),
+                    uv,
+                    tangent: Vec4::new(1.0, 0.0, 0.0, -1.0),
                 });
             }
-            tangent_lines.push(MeshTangentLine {
-                pos: Vec3::new(0.0, 0.0, 0.0),
-                color: Vec3::new(0.0, 0.0, 1.0),
-            });
-            tangent_lines.push(MeshTangentLine {
-                pos: Vec3::new(0.0, 0.0, 5.0),
-                color: Vec3::new(0.0, 0.0, 1.0),
-            });
-            tangent_lines.push(MeshTangentLine {
-                pos: Vec3::new(0.0, 0.0, 0.0),
-                color: Vec3::new(0.0, 1.0, 0.0),
-            });
-            tangent_lines.push(MeshTangentLine {
-                pos: Vec3::new(0.0, 5.0, 0.0),
-                color: Vec3::new(0.0, 1.0, 0.0),
-            });
-            tangent_lines.push(MeshTangentLine {
-                pos: Vec3::new(0.0, 0.0, 0.0),
-                color: Vec3::new(1.0, 0.0, 0.0),
-            });
-            tangent_lines.push(MeshTangentLine {
-                pos: Vec3::new(5.0, 0.0, 0.0),
-                color: Vec3::new(1.0, 0.0, 0.0),
-            });
-            let tangent_line_buffer = device.create_buffer_with_data(
-                &bytemuck::cast_slice(&tangent_lines),
-                wgpu::BufferUsage::VERTEX,
-            );
+        }
+        // Same winding as `plane`.
+        let mut indices = Vec::with_capacity((subdivisions * subdivisions * 6) as usize);
+        for z in 0..subdivisions {
+            for x in 0..subdivisions {
+                let corner = z * row + x;
+                let (a, b, c, d) = (corner, corner + row, corner + row + 1, corner + 1);
+                indices.extend_from_slice(&[a, b, c, a, c, d]);
+            }
+        }
+        let mut mesh = Self::plane(size, material_index);
+        let sub_mesh = &mut mesh.sub_meshes[0];
+        sub_mesh.vertices = vertices;
+        sub_mesh.index_count = indices.len();
+        sub_mesh.indices = indices;
+        sub_mesh.prepare_tangents(true, "grid");
+        mesh
+    }
 
-            let vertex_buffer = device.create_buffer_with_data(
-                &bytemuck::cast_slice(&sub_mesh.vertices),
-                wgpu::BufferUsage::VERTEX,
+    /// A copy of the mesh's CPU side data without buffers, LODs or a skeleton, for meshes
+    /// that are changed on the GPU. Every sub mesh gets vertex attributes so their colors can
+    /// be written too, the bounds are grown `depth` units down to cover pushed in vertices.
+    pub(crate) fn deformable_copy(&self, depth: f32) -> Mesh {
+        let grow = |mut aabb: Aabb| {
+            aabb.min.y -= depth;
+            aabb
+        };
+        let sub_meshes = self
+            .sub_meshes
+            .iter()
+            .map(|sub_mesh| {
+                let mut attribute_vertices = sub_mesh.attribute_vertices.clone();
+                if attribute_vertices.is_empty() {
+                    attribute_vertices = sub_mesh
+                        .vertices
+                        .iter()
+                        .map(|vertex| AttributeVertexData {
+                            color: Vec4::new(1.0, 1.0, 1.0, 1.0),
+                            uv1: vertex.uv,
+                        })
+                        .collect();
+                }
+                let aabb = grow(sub_mesh.aabb);
+                SubMesh {
+                    vertices: sub_mesh.vertices.clone(),
+                    tangent_lines: sub_mesh.tangent_lines.clone(),
+                    skin_vertices: Vec::new(),
+                    attribute_vertices,
+                    aabb,
+                    bounding_sphere: BoundingSphere::from_aabb(&aabb),
+                    indices: sub_mesh.indices.clone(),
+                    index_count: sub_mesh.index_count,
+                    mode: sub_mesh.mode,
+                    material_id: sub_mesh.material_id,
+                    vertex_buffer: None,
+                    tangent_line_buffer: None,
+                    index_buffer: None,
+                    skin_buffer: None,
+                    attribute_buffer: None,
+                    has_uvs: sub_mesh.has_uvs,
+                    generated_tangents: sub_mesh.generated_tangents,
+                    material_index: sub_mesh.material_index,
+                }
+            })
+            .collect();
+        Mesh {
+            sub_meshes,
+            skeleton: None,
+            animations: Vec::new(),
+            aabb: grow(self.aabb),
+            embedded_images: Vec::new(),
+            lods: Vec::new(),
+        }
+    }
+
+    fn import_obj_material(material: &tobj::Material, index: u32) -> Material {
+        let texture = |name: &str| {
+            Path::new(name)
+                .file_name()
+                .and_then(OsStr::to_str)
+                .map(|file_name| file_name.to_string())
+        };
+        let color = Vec4::new(
+            material.diffuse[0],
+            material.diffuse[1],
+            material.diffuse[2],
+            material.dissolve,
+        );
+        let main_texture = texture(&material.diffuse_texture)
+            .unwrap_or_else(|| FallbackTexture::White.name().to_string());
+
+        if material.illumination_model == Some(0) {
+            let mut unlit = UnlitMaterial::new(main_texture, color, index);
+            if material.dissolve < 1.0 {
+                unlit.render_queue = RenderQueue::Transparent;
+            }
+            return Material::Unlit(unlit);
+        }
+
+        // The PBR extension to MTL has it's own keys that tobj doesn't know about.
+        let param = |key: &str| material.unknown_param.get(key).map(|value| value.trim());
+        let normal_texture = texture(&material.normal_texture)
+            .or_else(|| param("norm").and_then(texture))
+            .unwrap_or_else(|| FallbackTexture::Normal.name().to_string());
+        let mut pbr = PBRMaterial::new(
+            main_texture,
+            normal_texture,
+            FallbackTexture::White.name().to_string(),
+            color,
+            index,
+        );
+        // Without the PBR keys roughness is guessed from the specular exponent.
+        pbr.roughness = param("Pr")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_else(|| (2.0 / (material.shininess.max(0.0) + 2.0)).sqrt());
+        if let Some(metallic) = param("Pm").and_then(|value| value.parse().ok()) {
+            pbr.metallic = metallic;
+        }
+        if let Some(emissive) = param("Ke") {
+            let values: Vec<f32> = emissive
+                .split_whitespace()
+                .filter_map(|value| value.parse().ok())
+                .collect();
+            if values.len() == 3 {
+                pbr.emissive = Vec3::new(values[0], values[1], values[2]);
+            }
+        }
+        if let Some(emissive_texture) = param("map_Ke").and_then(texture) {
+            pbr.emissive_texture = emissive_texture;
+        }
+        if material.dissolve < 1.0 {
+            pbr.alpha_mode = AlphaMode::Blend;
+            pbr.render_queue = RenderQueue::Transparent;
+        }
+        Material::PBR(pbr)
+    }
+
+    // Smooth normals from the faces around each vertex, for files that don't have any.
+    fn generate_normals(vertices: &mut [MeshVertexData], indices: &[u32]) {
+        for triangle in indices.chunks_exact(3) {
+            let (a, b, c) = (
+                vertices[triangle[0] as usize].position,
+                vertices[triangle[1] as usize].position,
+                vertices[triangle[2] as usize].position,
             );
+            // Not normalized so bigger faces count for more.
+            let normal = (b - a).cross(&(c - a));
+            for index in triangle {
+                vertices[*index as usize].normal += normal;
+            }
+        }
+        for vertex in vertices.iter_mut() {
+            if vertex.normal.magnitude() > 0.0 {
+                vertex.normal = vertex.normal.normalize();
+            }
+        }
+    }
 
-            sub_mesh.tangent_line_buffer = Some(tangent_line_buffer);
-            sub_mesh.vertex_buffer = Some(vertex_buffer);
+    fn combined_aabb(sub_meshes: &[SubMesh]) -> Aabb {
+        sub_meshes
+            .iter()
+            .map(|sub_mesh| sub_mesh.aabb)
+            .fold(None, |aabb: Option<Aabb>, sub_aabb| {
+                Some(aabb.map_or(sub_aabb, |aabb| aabb.merge(&sub_aabb)))
+            })
+            .unwrap_or_default()
+    }
 
-            sub_meshes.push(sub_mesh);
+    fn file_name(path: &str) -> String {
+        Path::new(path)
+            .file_name()
+            .and_then(OsStr::to_str)
+            .unwrap_or(path)
+            .to_string()
+    }
+
+    fn embedded_image_name(file_name: &str, image_index: usize) -> String {
+        format!("{}#{}", file_name, image_index)
+    }
+
+    // A file next to `path`, like the .bin of a .gltf.
+    fn sibling_path(path: &str, relative: &str) -> String {
+        match Path::new(path).parent().and_then(Path::to_str) {
+            Some(parent) if !parent.is_empty() => format!("{}/{}", parent, relative),
+            _ => relative.to_string(),
+        }
+    }
+
+    // The contents of every buffer, from the .glb's binary chunk, a data URI or a file.
+    fn import_buffers(
+        document: &gltf::Document,
+        mut blob: Option<Vec<u8>>,
+        path: &str,
+        read: &dyn Fn(&str) -> Result<Vec<u8>, AssetError>,
+    ) -> Result<Vec<Vec<u8>>, AssetError> {
+        let mut buffers = Vec::new();
+        for buffer in document.buffers() {
+            let mut data = match buffer.source() {
+                gltf::buffer::Source::Bin => blob
+                    .take()
+                    .ok_or_else(|| AssetError::decode(path, "missing binary chunk"))?,
+                gltf::buffer::Source::Uri(uri) if uri.starts_with("data:") => {
+                    let encoded = uri.splitn(2, ";base64,").nth(1);
+                    encoded.and_then(decode_base64).ok_or_else(|| {
+                        AssetError::unsupported(path, "only base64 data URIs are supported")
+                    })?
+                }
+                gltf::buffer::Source::Uri(uri) => read(&Self::sibling_path(path, uri))?,
+            };
+            if data.len() < buffer.length() {
+                return Err(AssetError::decode(path, "buffer is shorter than it's length"));
+            }
+            // Buffers can be padded to 4 bytes.
+            data.truncate(buffer.length());
+            buffers.push(data);
+        }
+        Ok(buffers)
+    }
+
+    // Reads the geometry of a glTF primitive into a sub mesh.
+    fn import_primitive<'a, 's, F>(
+        primitive: &'a gltf::Primitive<'a>,
+        path: &str,
+        get_buffer_data: F,
+        material_index: u32,
+    ) -> Result<SubMesh, AssetError>
+    where
+        F: Clone + Fn(gltf::Buffer<'a>) -> Option<&'s [u8]>,
+    {
+        let reader = primitive.reader(get_buffer_data);
+        let positions: Vec<_> = reader
+            .read_positions()
+            .map(|iter| iter.collect())
+            .ok_or_else(|| AssetError::decode(path, "mesh primitive is missing positions"))?;
+
+        let mut vertices: Vec<MeshVertexData> = positions
+            .iter()
+            .map(|pos| MeshVertexData {
+                position: Vec3::from(pos.clone()),
+                ..MeshVertexData::default()
+            })
+            .collect();
+
+        if let Some(normals) = reader.read_normals() {
+            for (i, normal) in normals.enumerate() {
+                vertices[i].normal = Vec3::from(normal.clone());
+            }
+        }
+        let mut has_uvs = false;
+        if let Some(uvs) = reader.read_tex_coords(0) {
+            for (i, uv) in uvs.into_f32().enumerate() {
+                vertices[i].uv = Vec2::from(uv.clone());
+            }
+            has_uvs = true;
+        }
+
+        // Vertex colors and the second uv set share a buffer, whichever the file is missing
+        // gets white or a copy of the first uv set.
+        let colors: Option<Vec<[f32; 4]>> =
+            reader.read_colors(0).map(|colors| colors.into_rgba_f32().collect());
+        let uvs1: Option<Vec<[f32; 2]>> =
+            reader.read_tex_coords(1).map(|uvs| uvs.into_f32().collect());
+        let mut attribute_vertices = Vec::new();
+        if colors.is_some() || uvs1.is_some() {
+            attribute_vertices = vertices
+                .iter()
+                .enumerate()
+                .map(|(i, vertex)| AttributeVertexData {
+                    color: colors
+                        .as_ref()
+                        .and_then(|colors| colors.get(i))
+                        .map_or(Vec4::new(1.0, 1.0, 1.0, 1.0), |color| Vec4::from(*color)),
+                    uv1: uvs1
+                        .as_ref()
+                        .and_then(|uvs| uvs.get(i))
+                        .map_or(vertex.uv, |uv| Vec2::from(*uv)),
+                })
+                .collect();
+        }
+
+        let mut skin_vertices = Vec::new();
+        if let (Some(joints), Some(weights)) = (reader.read_joints(0), reader.read_weights(0)) {
+            skin_vertices = joints
+                .into_u16()
+                .zip(weights.into_f32())
+                .map(|(joints, weights)| SkinVertexData {
+                    joints: [
+                        joints[0] as u32,
+                        joints[1] as u32,
+                        joints[2] as u32,
+                        joints[3] as u32,
+                    ],
+                    weights: Vec4::new(weights[0], weights[1], weights[2], weights[3]),
+                })
+                .collect();
+        }
+
+        let mut had_tangents = false;
+        // Load tangents if we have them.
+        if let Some(tangents) = reader.read_tangents() {
+            for (i, tangent) in tangents.enumerate() {
+                vertices[i].tangent = Vec4::new(tangent[0], tangent[1], tangent[2], tangent[3]);
+            }
+            had_tangents = true;
+        } else {
+            // TODO: Calculate tangents if we don't have them.
+            //warn!("Don't have tangents for mesh.");
+        }
+
+        let indices: Vec<u32> = if let Some(index_enum) = reader.read_indices() {
+            index_enum.into_u32().collect()
+        } else {
+            return Err(AssetError::unsupported(path, "model doesn't have indices"));
+        };
+
+        let primitive_topology = Self::get_primitive_mode(primitive.mode())
+            .ok_or_else(|| AssetError::unsupported(path, "mesh topology isn't supported"))?;
+
+        let index_count = indices.len();
+        let aabb = Aabb::from_points(vertices.iter().map(|vertex| &vertex.position));
+
+        let mut sub_mesh = SubMesh {
+            vertices,
+            tangent_lines: Vec::new(),
+            skin_vertices,
+            attribute_vertices,
+            aabb,
+            bounding_sphere: BoundingSphere::from_aabb(&aabb),
+            indices,
+            index_count,
+            mode: primitive_topology,
+            material_id: primitive.material().index(),
+            vertex_buffer: None,
+            tangent_line_buffer: None,
+            index_buffer: None,
+            skin_buffer: None,
+            attribute_buffer: None,
+            has_uvs,
+            generated_tangents: false,
+            material_index,
+        };
+        sub_mesh.prepare_tangents(had_tangents, path);
+        Ok(sub_mesh)
+    }
+
+    // The number after "_LOD" at the end of a name, case doesn't matter.
+    fn lod_level(name: &str) -> Option<usize> {
+        let name = name.to_lowercase();
+        let index = name.rfind("_lod")?;
+        name[index + 4..].parse().ok()
+    }
+
+    // Reads the first skin and any animations that target it's joints.
+    fn import_skeleton<'a, 's, F>(
+        document: &'a gltf::Document,
+        path: &str,
+        get_buffer_data: F,
+    ) -> Result<(Option<Skeleton>, Vec<AnimationClip>), AssetError>
+    where
+        F: Clone + Fn(gltf::Buffer<'a>) -> Option<&'s [u8]>,
+    {
+        let skin = match document.skins().next() {
+            Some(skin) => skin,
+            None => return Ok((None, Vec::new())),
+        };
+        if document.skins().count() > 1 {
+            log::warn!("Currently we only support 1 skin per gltf object, the rest are ignored.");
+        }
+
+        let joint_nodes: Vec<gltf::Node<'_>> = skin.joints().collect();
+        if joint_nodes.len() > MAX_JOINTS {
+            return Err(AssetError::unsupported(
+                path,
+                format!("skin has more than {} joints", MAX_JOINTS),
+            ));
         }
+        let joint_lookup: HashMap<usize, usize> = joint_nodes
+            .iter()
+            .enumerate()
+            .map(|(joint, node)| (node.index(), joint))
+            .collect();
 
-        (Mesh { sub_meshes }, materials)
+        let inverse_binds: Vec<Mat4> = skin
+            .reader(get_buffer_data.clone())
+            .read_inverse_bind_matrices()
+            .map(|matrices| {
+                matrices
+                    .map(|matrix| Mat4::from_column_slice(&matrix.concat()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut joints: Vec<Joint> = joint_nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| {
+                let (translation, rotation, scale) = node.transform().decomposed();
+                Joint {
+                    name: node.name().map(|name| name.to_string()),
+                    parent: None,
+                    translation: Vec3::from(translation),
+                    rotation: Quat::new(rotation[3], rotation[0], rotation[1], rotation[2]),
+                    scale: Vec3::from(scale),
+                    inverse_bind: inverse_binds
+                        .get(index)
+                        .cloned()
+                        .unwrap_or_else(Mat4::identity),
+                }
+            })
+            .collect();
+        for (parent, node) in joint_nodes.iter().enumerate() {
+            for child in node.children() {
+                if let Some(child) = joint_lookup.get(&child.index()) {
+                    joints[*child].parent = Some(parent);
+                }
+            }
+        }
+
+        let mut animations = Vec::new();
+        for animation in document.animations() {
+            let mut channels = Vec::new();
+            for channel in animation.channels() {
+                let joint = match joint_lookup.get(&channel.target().node().index()) {
+                    Some(joint) => *joint,
+                    None => continue,
+                };
+                let reader = channel.reader(get_buffer_data.clone());
+                let times: Vec<f32> = match reader.read_inputs() {
+                    Some(inputs) => inputs.collect(),
+                    None => continue,
+                };
+                let values = match reader.read_outputs() {
+                    Some(gltf::animation::util::ReadOutputs::Translations(values)) => {
+                        ChannelValues::Translation(values.map(Vec3::from).collect())
+                    }
+                    Some(gltf::animation::util::ReadOutputs::Rotations(values)) => {
+                        ChannelValues::Rotation(
+                            values
+                                .into_f32()
+                                .map(|r| Quat::new(r[3], r[0], r[1], r[2]))
+                                .collect(),
+                        )
+                    }
+                    Some(gltf::animation::util::ReadOutputs::Scales(values)) => {
+                        ChannelValues::Scale(values.map(Vec3::from).collect())
+                    }
+                    // TODO: Morph targets.
+                    _ => continue,
+                };
+                let interpolation = match channel.sampler().interpolation() {
+                    gltf::animation::Interpolation::Step => Interpolation::Step,
+                    gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                    gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+                };
+                channels.push(Channel {
+                    joint,
+                    times,
+                    values,
+                    interpolation,
+                });
+            }
+
+            let duration = channels
+                .iter()
+                .filter_map(|channel| channel.times.last())
+                .fold(0.0, |duration: f32, time| duration.max(*time));
+            animations.push(AnimationClip {
+                name: animation
+                    .name()
+                    .map(|name| name.to_string())
+                    .unwrap_or_else(|| format!("animation_{}", animation.index())),
+                duration,
+                channels,
+            });
+        }
+
+        Ok((Some(Skeleton { joints }), animations))
+    }
+
+    /// Creates the vertex, index and tangent line buffers for a mesh made by `import`.
+    /// `usage` is added to the index, vertex and vertex attribute buffers.
+    pub(crate) fn create_buffers(&mut self, device: &wgpu::Device, usage: wgpu::BufferUsage) {
+        let lod_sub_meshes = self.lods.iter_mut().flat_map(|lod| lod.sub_meshes.iter_mut());
+        for sub_mesh in self.sub_meshes.iter_mut().chain(lod_sub_meshes) {
+            sub_mesh.index_buffer = Some(device.create_buffer_with_data(
+                &bytemuck::cast_slice(&sub_mesh.indices),
+                wgpu::BufferUsage::INDEX | usage,
+            ));
+            sub_mesh.tangent_line_buffer = Some(device.create_buffer_with_data(
+                &bytemuck::cast_slice(&sub_mesh.tangent_lines),
+                wgpu::BufferUsage::VERTEX,
+            ));
+            sub_mesh.vertex_buffer = Some(device.create_buffer_with_data(
+                &bytemuck::cast_slice(&sub_mesh.vertices),
+                wgpu::BufferUsage::VERTEX | usage,
+            ));
+            if !sub_mesh.skin_vertices.is_empty() {
+                sub_mesh.skin_buffer = Some(device.create_buffer_with_data(
+                    &bytemuck::cast_slice(&sub_mesh.skin_vertices),
+                    wgpu::BufferUsage::VERTEX,
+                ));
+            }
+            if !sub_mesh.attribute_vertices.is_empty() {
+                sub_mesh.attribute_buffer = Some(device.create_buffer_with_data(
+                    &bytemuck::cast_slice(&sub_mesh.attribute_vertices),
+                    wgpu::BufferUsage::VERTEX | usage,
+                ));
+            }
+        }
     }
 
-    fn get_primitive_mode(mode: gltf::mesh::Mode) -> wgpu::PrimitiveTopology {
+    fn get_primitive_mode(mode: gltf::mesh::Mode) -> Option<wgpu::PrimitiveTopology> {
         match mode {
-            gltf::mesh::Mode::Points => wgpu::PrimitiveTopology::PointList,
-            gltf::mesh::Mode::Lines => wgpu::PrimitiveTopology::LineList,
-            gltf::mesh::Mode::LineStrip => wgpu::PrimitiveTopology::LineStrip,
-            gltf::mesh::Mode::Triangles => wgpu::PrimitiveTopology::TriangleList,
-            gltf::mesh::Mode::TriangleStrip => wgpu::PrimitiveTopology::TriangleStrip,
-            _ => panic!(format!("Error loading mesht topology isn't supported!")),
+            gltf::mesh::Mode::Points => Some(wgpu::PrimitiveTopology::PointList),
+            gltf::mesh::Mode::Lines => Some(wgpu::PrimitiveTopology::LineList),
+            gltf::mesh::Mode::LineStrip => Some(wgpu::PrimitiveTopology::LineStrip),
+            gltf::mesh::Mode::Triangles => Some(wgpu::PrimitiveTopology::TriangleList),
+            gltf::mesh::Mode::TriangleStrip => Some(wgpu::PrimitiveTopology::TriangleStrip),
+            _ => None,
+        }
+    }
+
+    // Textures from other files are referred to by their file name, embedded ones by the mesh's.
+    fn get_source_file_name(texture: &gltf::Texture<'_>, mesh_file_name: &str) -> Option<String> {
+        match texture.source().source() {
+            gltf::image::Source::Uri { uri, .. } => Path::new(&uri)
+                .file_name()
+                .and_then(OsStr::to_str)
+                .map(|file_name| file_name.to_string()),
+            gltf::image::Source::View { .. } => Some(Self::embedded_image_name(
+                mesh_file_name,
+                texture.source().index(),
+            )),
         }
     }
 
     fn get_texture_url(
         info: &Option<gltf::texture::Info<'_>>,
-        images: &Vec<gltf::Image<'_>>,
+        mesh_file_name: &str,
     ) -> Option<String> {
-        let mut file_name = None;
-        if info.is_some() {
-            let info = info.as_ref().unwrap();
-            let tex = info.texture();
-
-            let image: Option<&gltf::Image<'_>> = images.get(tex.index());
-            if image.is_some() {
-                let image = image.unwrap();
-                let source = image.source();
-                match source {
-                    gltf::image::Source::Uri { uri, .. } => {
-                        let texture_file_name = Some(
-                            Path::new(&uri)
-                                .file_name()
-                                .and_then(OsStr::to_str)
-                                .unwrap()
-                                .to_string(),
-                        );
-                        if texture_file_name.is_some() {
-                            file_name = Some(texture_file_name.unwrap());
-                        }
-                    }
-                    _ => (),
-                }
-            }
+        info.as_ref()
+            .and_then(|info| Self::get_source_file_name(&info.texture(), mesh_file_name))
+    }
+}
+
+// Decodes standard base64, None if it has invalid characters.
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    };
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+    let (mut bits, mut count) = (0u32, 0);
+    for c in encoded.bytes().filter(|c| *c != b'=') {
+        bits = (bits << 6) | value(c)? as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
         }
-        file_name
     }
+    Some(bytes)
 }
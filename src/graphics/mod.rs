@@ -1,10 +1,122 @@
 pub mod renderer;
-pub use renderer::Renderer;
+pub use renderer::{FrameOutput, Renderer};
+
+pub(crate) mod capture;
+
+pub(crate) mod frame_export;
+pub use frame_export::{ExportTarget, FrameExport};
+
+pub(crate) mod readback;
+pub use readback::GpuReadback;
+
+pub(crate) mod cubemap;
+pub use cubemap::HdrCubemap;
+
+pub(crate) mod frames_in_flight;
+pub use frames_in_flight::FramesInFlight;
+
+pub(crate) mod uniform_arena;
+pub use uniform_arena::UniformArena;
+
+pub(crate) mod render_settings;
+pub use render_settings::{RenderPath, RenderSettings};
+
+pub(crate) mod capabilities;
+pub use capabilities::GpuCapabilities;
 
 pub mod material;
 
 pub mod mesh;
 
+pub(crate) mod conventions;
+pub use conventions::{MeshImport, UpAxis, UvOrigin};
+
+pub mod animation;
+
+pub mod instancing;
+
+pub mod bounds;
+
+pub mod queries;
+
+pub(crate) mod picking;
+pub use picking::{Pick, PickMode};
+
+pub mod noise;
+
+pub(crate) mod compute;
+pub use compute::ComputeBindings;
+
+pub(crate) mod atlas;
+pub use atlas::{AtlasBuilder, AtlasRegion, TextureAtlas};
+
+pub(crate) mod light_clusters;
+pub use light_clusters::MAX_CLUSTERED_LIGHTS;
+
+pub(crate) mod light_cookies;
+
+pub(crate) mod water;
+pub use water::MAX_WATER_VOLUMES;
+
+pub(crate) mod deformation;
+pub use deformation::{SurfaceDeformation, DEFORMATION_FORMAT, MAX_DEFORMERS};
+
+pub(crate) mod ocean;
+pub use ocean::{Ocean, OCEAN_FFT_SIZE};
+
+mod culling;
+pub use culling::FrustumCulling;
+
+mod post_processing;
+pub use post_processing::{
+    AutoExposure, Bloom, FinalPass, MeteringMode, PostProcessing, ProjectionWarp, Tonemapper,
+};
+
+pub(crate) mod clouds;
+pub use clouds::Clouds;
+
+pub(crate) mod atmosphere;
+pub use atmosphere::Atmosphere;
+
+pub(crate) mod ssgi;
+pub use ssgi::ScreenSpaceGI;
+
+pub(crate) mod dynamic_gi;
+pub use dynamic_gi::{DynamicGI, MAX_GI_PROBES};
+
+pub(crate) mod ao_volume;
+pub use ao_volume::{AoVolume, MAX_AO_VOXELS};
+
+pub(crate) mod debug;
+pub use debug::DebugDraw;
+
+pub(crate) mod grid;
+pub use grid::EditorGrid;
+
+pub(crate) mod texture_viewer;
+pub use texture_viewer::TextureViewer;
+
+pub(crate) mod clip;
+pub use clip::ClipRect;
+
+pub(crate) mod sprite;
+pub use sprite::SpriteRenderer;
+
+pub(crate) mod particles;
+pub use particles::ParticleRenderer;
+
+pub(crate) mod split_screen;
+pub use split_screen::{SplitLayout, SplitScreen, Viewport, MAX_PLAYERS};
+
+mod render_queue;
+pub use render_queue::RenderQueue;
+
+mod blend_mode;
+pub use blend_mode::BlendMode;
+
+mod depth_bias;
+pub use depth_bias::DepthBias;
+
 mod render_graph;
 pub use render_graph::{CommandBufferQueue, CommandQueueItem, RenderGraph};
 
@@ -18,3 +130,6 @@ pub mod resources;
 pub mod systems;
 
 pub mod pipeline_manager;
+
+mod frame_graph;
+pub use frame_graph::{AttachmentDesc, AttachmentSize};
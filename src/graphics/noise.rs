@@ -0,0 +1,356 @@
+use nalgebra_glm::Vec2;
+use std::sync::Arc;
+
+use super::material::{image::DecodedImage, Image, SamplerInfo};
+
+/// The kinds of noise `Noise` can generate.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NoiseKind {
+    /// Smooth gradient noise, the classic look for terrain.
+    Perlin,
+    /// Like perlin but with less grid artifacts.
+    Simplex,
+    /// Distance to the closest random point, gives cells for stone, scales or clouds.
+    Worley,
+}
+
+impl NoiseKind {
+    pub(crate) fn to_shader_value(&self) -> u32 {
+        match self {
+            NoiseKind::Perlin => 0,
+            NoiseKind::Simplex => 1,
+            NoiseKind::Worley => 2,
+        }
+    }
+}
+
+// Integer hash so we don't need a permutation table, the noise shader uses the same one
+// so the CPU and GPU noise match.
+fn hash(x: i32, y: i32, seed: u32) -> u32 {
+    let mut h = seed ^ (x as u32).wrapping_mul(0x27d4_eb2d) ^ (y as u32).wrapping_mul(0x1656_67b1);
+    h = (h ^ (h >> 15)).wrapping_mul(0x2c1b_3c6d);
+    h = (h ^ (h >> 12)).wrapping_mul(0x297a_2d39);
+    h ^ (h >> 15)
+}
+
+fn gradient(hash: u32, x: f32, y: f32) -> f32 {
+    const DIAGONAL: f32 = std::f32::consts::FRAC_1_SQRT_2;
+    let (gx, gy) = match hash & 7 {
+        0 => (1.0, 0.0),
+        1 => (-1.0, 0.0),
+        2 => (0.0, 1.0),
+        3 => (0.0, -1.0),
+        4 => (DIAGONAL, DIAGONAL),
+        5 => (-DIAGONAL, DIAGONAL),
+        6 => (DIAGONAL, -DIAGONAL),
+        _ => (-DIAGONAL, -DIAGONAL),
+    };
+    gx * x + gy * y
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// 2D perlin noise in the -1 to 1 range.
+pub fn perlin(x: f32, y: f32, seed: u32) -> f32 {
+    let (cell_x, cell_y) = (x.floor(), y.floor());
+    let (fx, fy) = (x - cell_x, y - cell_y);
+    let (ix, iy) = (cell_x as i32, cell_y as i32);
+
+    let n00 = gradient(hash(ix, iy, seed), fx, fy);
+    let n10 = gradient(hash(ix + 1, iy, seed), fx - 1.0, fy);
+    let n01 = gradient(hash(ix, iy + 1, seed), fx, fy - 1.0);
+    let n11 = gradient(hash(ix + 1, iy + 1, seed), fx - 1.0, fy - 1.0);
+
+    let (u, v) = (fade(fx), fade(fy));
+    // Unit gradients only reach about 0.7 so scale it back up to -1 to 1.
+    let value = lerp(lerp(n00, n10, u), lerp(n01, n11, u), v) * std::f32::consts::SQRT_2;
+    value.max(-1.0).min(1.0)
+}
+
+/// 2D simplex noise in the -1 to 1 range.
+pub fn simplex(x: f32, y: f32, seed: u32) -> f32 {
+    const SKEW: f32 = 0.366_025_4; // (sqrt(3) - 1) / 2
+    const UNSKEW: f32 = 0.211_324_87; // (3 - sqrt(3)) / 6
+
+    // Find the triangle we're in.
+    let skew = (x + y) * SKEW;
+    let (i, j) = ((x + skew).floor(), (y + skew).floor());
+    let unskew = (i + j) * UNSKEW;
+    let (x0, y0) = (x - (i - unskew), y - (j - unskew));
+    let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+    let (x1, y1) = (x0 - i1 as f32 + UNSKEW, y0 - j1 as f32 + UNSKEW);
+    let (x2, y2) = (x0 - 1.0 + 2.0 * UNSKEW, y0 - 1.0 + 2.0 * UNSKEW);
+    let (i, j) = (i as i32, j as i32);
+
+    let corner = |hash: u32, x: f32, y: f32| {
+        let t = 0.5 - x * x - y * y;
+        if t < 0.0 {
+            0.0
+        } else {
+            t * t * t * t * gradient(hash, x, y)
+        }
+    };
+    let value = corner(hash(i, j, seed), x0, y0)
+        + corner(hash(i + i1, j + j1, seed), x1, y1)
+        + corner(hash(i + 1, j + 1, seed), x2, y2);
+    (value * 70.0).max(-1.0).min(1.0)
+}
+
+/// 2D worley noise, the distance to the closest feature point with one point per cell.
+/// Mostly in the 0 to 1 range, it's clamped to stay there.
+pub fn worley(x: f32, y: f32, seed: u32) -> f32 {
+    let (cell_x, cell_y) = (x.floor() as i32, y.floor() as i32);
+    let mut closest = std::f32::MAX;
+    for offset_y in -1..=1 {
+        for offset_x in -1..=1 {
+            let (neighbour_x, neighbour_y) = (cell_x + offset_x, cell_y + offset_y);
+            let h = hash(neighbour_x, neighbour_y, seed);
+            let point = Vec2::new(
+                neighbour_x as f32 + (h & 0xffff) as f32 / 65535.0,
+                neighbour_y as f32 + (h >> 16) as f32 / 65535.0,
+            );
+            closest = closest.min(nalgebra_glm::distance(&point, &Vec2::new(x, y)));
+        }
+    }
+    closest.min(1.0)
+}
+
+fn hash3(x: i32, y: i32, z: i32, seed: u32) -> u32 {
+    hash(x, y, seed ^ (z as u32).wrapping_mul(0x5bd1_e995))
+}
+
+fn gradient3(hash: u32, x: f32, y: f32, z: f32) -> f32 {
+    // The 12 edges of a cube.
+    match hash % 12 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+/// 3D perlin noise in the -1 to 1 range that repeats every `period` cells on each axis,
+/// so it can fill a 3D texture that tiles.
+pub fn perlin3(x: f32, y: f32, z: f32, period: i32, seed: u32) -> f32 {
+    let period = period.max(1);
+    let (cell_x, cell_y, cell_z) = (x.floor(), y.floor(), z.floor());
+    let (fx, fy, fz) = (x - cell_x, y - cell_y, z - cell_z);
+    let (ix, iy, iz) = (cell_x as i32, cell_y as i32, cell_z as i32);
+    let corner = |ox: i32, oy: i32, oz: i32| {
+        let h = hash3(
+            (ix + ox).rem_euclid(period),
+            (iy + oy).rem_euclid(period),
+            (iz + oz).rem_euclid(period),
+            seed,
+        );
+        gradient3(h, fx - ox as f32, fy - oy as f32, fz - oz as f32)
+    };
+
+    let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+    let bottom = lerp(
+        lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+        lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+        v,
+    );
+    let top = lerp(
+        lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+        lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+        v,
+    );
+    lerp(bottom, top, w).max(-1.0).min(1.0)
+}
+
+/// 3D worley noise that repeats every `period` cells on each axis, clamped to 0 to 1.
+pub fn worley3(x: f32, y: f32, z: f32, period: i32, seed: u32) -> f32 {
+    let period = period.max(1);
+    let (cell_x, cell_y, cell_z) = (x.floor() as i32, y.floor() as i32, z.floor() as i32);
+    let mut closest = std::f32::MAX;
+    for offset_z in -1..=1 {
+        for offset_y in -1..=1 {
+            for offset_x in -1..=1 {
+                let (nx, ny, nz) = (cell_x + offset_x, cell_y + offset_y, cell_z + offset_z);
+                let h = hash3(
+                    nx.rem_euclid(period),
+                    ny.rem_euclid(period),
+                    nz.rem_euclid(period),
+                    seed,
+                );
+                let (px, py, pz) = (
+                    nx as f32 + (h & 0x3ff) as f32 / 1023.0,
+                    ny as f32 + ((h >> 10) & 0x3ff) as f32 / 1023.0,
+                    nz as f32 + ((h >> 20) & 0x3ff) as f32 / 1023.0,
+                );
+                let distance = ((px - x).powi(2) + (py - y).powi(2) + (pz - z).powi(2)).sqrt();
+                closest = closest.min(distance);
+            }
+        }
+    }
+    closest.min(1.0)
+}
+
+/// Settings for generating noise, use `sample` on the CPU or `NoiseGenerator` to fill a storage
+/// texture on the GPU. More than one octave layers the noise as fractal brownian motion (FBM).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Noise {
+    pub kind: NoiseKind,
+    pub seed: u32,
+    /// How many cells fit in the 0 to 1 range, images are generated over that range.
+    pub frequency: f32,
+    pub octaves: u32,
+    /// How much the frequency goes up each octave.
+    pub lacunarity: f32,
+    /// How much each octave's strength goes down, also called persistence.
+    pub gain: f32,
+}
+
+impl Noise {
+    pub fn new(kind: NoiseKind) -> Self {
+        Self {
+            kind,
+            seed: 0,
+            frequency: 8.0,
+            octaves: 1,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_frequency(mut self, frequency: f32) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    /// Layers `octaves` copies of the noise, each one `lacunarity` times smaller and `gain` times
+    /// weaker than the last.
+    pub fn with_fbm(mut self, octaves: u32, lacunarity: f32, gain: f32) -> Self {
+        self.octaves = octaves;
+        self.lacunarity = lacunarity;
+        self.gain = gain;
+        self
+    }
+
+    fn sample_octave(&self, x: f32, y: f32, seed: u32) -> f32 {
+        match self.kind {
+            NoiseKind::Perlin => perlin(x, y, seed) * 0.5 + 0.5,
+            NoiseKind::Simplex => simplex(x, y, seed) * 0.5 + 0.5,
+            NoiseKind::Worley => worley(x, y, seed),
+        }
+    }
+
+    /// Samples the noise at a position in the 0 to 1 range, returns a value from 0 to 1.
+    pub fn sample(&self, x: f32, y: f32) -> f32 {
+        let mut frequency = self.frequency;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut weight = 0.0;
+        for octave in 0..self.octaves.max(1) {
+            let seed = self.seed.wrapping_add(octave);
+            total += self.sample_octave(x * frequency, y * frequency, seed) * amplitude;
+            weight += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+        total / weight
+    }
+
+    /// Samples the noise for each pixel of an image, row by row.
+    pub fn generate(&self, width: u32, height: u32) -> Vec<f32> {
+        let mut values = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                values.push(self.sample(x as f32 / width as f32, y as f32 / height as f32));
+            }
+        }
+        values
+    }
+
+    /// Generates the noise on the CPU into a grayscale image, add it to the asset manager with
+    /// `insert_image` to use it in materials.
+    pub fn create_image<T: Into<String>>(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        name: T,
+        width: u32,
+        height: u32,
+    ) -> Image {
+        let width = width.max(1);
+        let height = height.max(1);
+        let bytes = self
+            .generate(width, height)
+            .into_iter()
+            .flat_map(|value| {
+                let value = (value * 255.0).round() as u8;
+                vec![value, value, value, 255]
+            })
+            .collect();
+        Image::from_decoded(
+            device,
+            encoder,
+            name.into(),
+            DecodedImage {
+                levels: vec![bytes],
+                extent: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth: 1,
+                },
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                keep_cpu_data: false,
+            },
+            Image::DEFAULT_USAGE,
+            SamplerInfo::default(),
+            Arc::new(SamplerInfo::default().create_sampler(device)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_noise_range() {
+        for kind in [NoiseKind::Perlin, NoiseKind::Simplex, NoiseKind::Worley].iter() {
+            let noise = Noise::new(*kind).with_seed(7).with_fbm(4, 2.0, 0.5);
+            for value in noise.generate(32, 32) {
+                assert!(value >= 0.0 && value <= 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_noise_seed() {
+        let noise = Noise::new(NoiseKind::Perlin);
+        assert_eq!(noise.sample(0.3, 0.7), noise.sample(0.3, 0.7));
+        assert_ne!(noise.sample(0.3, 0.7), noise.with_seed(1).sample(0.3, 0.7));
+        // Perlin noise is always zero on the grid.
+        assert_eq!(perlin(3.0, 5.0, 0), 0.0);
+    }
+
+    #[test]
+    fn test_noise_3d_tiles() {
+        let (x, y, z) = (0.3, 1.7, 2.2);
+        assert!((perlin3(x, y, z, 4, 3) - perlin3(x + 4.0, y, z - 4.0, 4, 3)).abs() < 0.0001);
+        assert!((worley3(x, y, z, 4, 3) - worley3(x, y + 4.0, z, 4, 3)).abs() < 0.0001);
+    }
+}
@@ -0,0 +1,287 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra_glm::{Vec2, Vec3, Vec4};
+use std::sync::Mutex;
+
+use super::GpuReadback;
+
+// This needs to match the ocean shaders.
+/// Width and height of the wave simulation, one FFT of this size runs per row and column.
+pub const OCEAN_FFT_SIZE: usize = 256;
+const GRAVITY: f32 = 9.81;
+
+/// An endless ocean at `height`, a resource. Waves come from a wind driven spectrum that's
+/// turned into a tiling height and displacement map with an FFT on the GPU every frame, the
+/// ocean is drawn as a grid projected from the screen onto the water so the detail follows
+/// the camera. Foam shows up where the waves are pinched together.
+/// `height_at` and `buoyancy` read the same waves back on the CPU for floating things, they
+/// lag a frame or two behind what's drawn.
+/// Note: The ocean is drawn after the meshes and blended ones don't show through it.
+pub struct Ocean {
+    pub enabled: bool,
+    /// Height of the calm sea.
+    pub height: f32,
+    /// Wind speed in meters per second, stronger winds make longer and higher waves.
+    pub wind_speed: f32,
+    /// The direction the wind blows along on the xz plane.
+    pub wind_direction: Vec2,
+    /// Scales the height of the waves, 1.0 is roughly a real sea for the wind speed.
+    pub amplitude: f32,
+    /// How much the waves are pulled into sharp crests, 0 gives round waves.
+    pub choppiness: f32,
+    /// World units one repeat of the wave maps covers.
+    pub patch_size: f32,
+    /// Foam starts where the surface is pinched below this, higher values give more foam.
+    pub foam_threshold: f32,
+    /// How fast foam fades away, the fraction left after a second.
+    pub foam_decay: f32,
+    pub deep_color: Vec3,
+    /// Light scattered through the tops of the waves.
+    pub scatter_color: Vec3,
+    /// Reflected near the horizon and straight up, the ocean reflects this instead of the
+    /// environment.
+    pub horizon_color: Vec3,
+    pub sky_color: Vec3,
+    pub foam_color: Vec3,
+    /// Vertices along each side of the projected grid.
+    pub grid_resolution: u32,
+    /// Changes the waves without changing their size or direction.
+    pub seed: u32,
+    pub(crate) time: f32,
+    // The spectrum settings the GPU spectrum was made with.
+    pub(crate) spectrum_key: Option<[u32; 5]>,
+    // The readback future isn't Sync.
+    pub(crate) readback: Mutex<Option<GpuReadback>>,
+    // The last displacement map that came back, (x offset, height, z offset, unused) per texel.
+    pub(crate) displacement: Vec<Vec4>,
+}
+
+impl Default for Ocean {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            height: 0.0,
+            wind_speed: 10.0,
+            wind_direction: Vec2::new(1.0, 0.3),
+            amplitude: 1.0,
+            choppiness: 1.0,
+            patch_size: 256.0,
+            foam_threshold: 0.4,
+            foam_decay: 0.3,
+            deep_color: Vec3::new(0.01, 0.05, 0.08),
+            scatter_color: Vec3::new(0.0, 0.12, 0.1),
+            horizon_color: Vec3::new(0.6, 0.7, 0.8),
+            sky_color: Vec3::new(0.2, 0.35, 0.6),
+            foam_color: Vec3::new(0.9, 0.9, 0.9),
+            grid_resolution: 256,
+            seed: 0,
+            time: 0.0,
+            spectrum_key: None,
+            readback: Mutex::new(None),
+            displacement: Vec::new(),
+        }
+    }
+}
+
+impl Ocean {
+    /// Height of the water surface above a point on the xz plane, the calm sea height until the
+    /// first waves have been read back.
+    pub fn height_at(&self, x: f32, z: f32) -> f32 {
+        if self.displacement.len() != OCEAN_FFT_SIZE * OCEAN_FFT_SIZE {
+            return self.height;
+        }
+        // The map moves the surface sideways too, find the point that ended up above (x, z).
+        let target = Vec2::new(x, z);
+        let mut point = target;
+        for _ in 0..4 {
+            let offset = self.sample(point);
+            point = target - Vec2::new(offset.x, offset.z);
+        }
+        self.height + self.sample(point).y
+    }
+
+    /// How much of a sphere is under water, from 0 to 1. Multiply it with the sphere's volume,
+    /// the water's density and gravity for the force pushing it up.
+    pub fn buoyancy(&self, center: Vec3, radius: f32) -> f32 {
+        let depth = self.height_at(center.x, center.z) - (center.y - radius);
+        let diameter = (radius * 2.0).max(0.0001);
+        let h = depth.max(0.0).min(diameter) / diameter;
+        // The volume of a spherical cap, relative to the whole sphere.
+        h * h * (3.0 - 2.0 * h)
+    }
+
+    // Bilinearly samples the displacement map, it tiles every `patch_size` units.
+    fn sample(&self, point: Vec2) -> Vec4 {
+        let size = OCEAN_FFT_SIZE as f32;
+        let texel = point / self.patch_size.max(0.0001) * size - Vec2::new(0.5, 0.5);
+        let base = Vec2::new(texel.x.floor(), texel.y.floor());
+        let t = texel - base;
+        let at = |x: f32, z: f32| {
+            let x = (x.rem_euclid(size)) as usize;
+            let z = (z.rem_euclid(size)) as usize;
+            self.displacement[z * OCEAN_FFT_SIZE + x]
+        };
+        let top = nalgebra_glm::lerp(&at(base.x, base.y), &at(base.x + 1.0, base.y), t.x);
+        let bottom = nalgebra_glm::lerp(
+            &at(base.x, base.y + 1.0),
+            &at(base.x + 1.0, base.y + 1.0),
+            t.x,
+        );
+        nalgebra_glm::lerp(&top, &bottom, t.y)
+    }
+
+    // The spectrum only has to be rebuilt when one of these changes.
+    pub(crate) fn spectrum_settings(&self) -> [u32; 5] {
+        [
+            self.wind_speed.to_bits(),
+            self.wind_direction.x.to_bits(),
+            self.wind_direction.y.to_bits(),
+            self.amplitude.to_bits() ^ self.patch_size.to_bits().rotate_left(16),
+            self.seed,
+        ]
+    }
+
+    /// The starting spectrum, (h0(k), conjugate of h0(-k)) as two complex numbers per texel.
+    pub(crate) fn initial_spectrum(&self) -> Vec<Vec4> {
+        let n = OCEAN_FFT_SIZE;
+        let wind = if self.wind_direction.magnitude() > 0.0 {
+            self.wind_direction.normalize()
+        } else {
+            Vec2::new(1.0, 0.0)
+        };
+        // Keeps the height of the waves the same for any patch size.
+        let scale = self.amplitude * 0.1 / (self.patch_size * self.patch_size).max(0.0001);
+        let h0 = |x: usize, z: usize| {
+            let k = wave_vector(x, z, self.patch_size);
+            let seed = self.seed.wrapping_mul(0x9e37_79b9) ^ (z * n + x) as u32;
+            let (r, i) = gaussian(seed);
+            let amplitude = (phillips(k, wind, self.wind_speed) * scale * 0.5).sqrt();
+            Vec2::new(r * amplitude, i * amplitude)
+        };
+        let mut spectrum = Vec::with_capacity(n * n);
+        for z in 0..n {
+            for x in 0..n {
+                let positive = h0(x, z);
+                let negative = h0((n - x) % n, (n - z) % n);
+                spectrum.push(Vec4::new(positive.x, positive.y, negative.x, -negative.y));
+            }
+        }
+        spectrum
+    }
+
+    pub(crate) fn uniform(&self, delta_time: f32) -> OceanUniform {
+        let wind = self.wind_direction;
+        let color = |color: Vec3, w: f32| Vec4::new(color.x, color.y, color.z, w);
+        OceanUniform {
+            surface: Vec4::new(
+                self.height,
+                self.patch_size.max(0.0001),
+                self.choppiness,
+                self.time,
+            ),
+            foam: Vec4::new(
+                self.foam_threshold,
+                self.foam_decay.max(0.0).min(1.0).powf(delta_time),
+                wind.x,
+                wind.y,
+            ),
+            deep_color: color(self.deep_color, 0.0),
+            scatter_color: color(self.scatter_color, 0.0),
+            horizon_color: color(self.horizon_color, 0.0),
+            sky_color: color(self.sky_color, 0.0),
+            foam_color: color(self.foam_color, 0.0),
+            info: Vec4::new(self.grid_resolution.max(2) as f32, 0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// The wave vector of a texel of the spectrum, the middle texel is k = 0.
+fn wave_vector(x: usize, z: usize, patch_size: f32) -> Vec2 {
+    let half = (OCEAN_FFT_SIZE / 2) as f32;
+    Vec2::new(x as f32 - half, z as f32 - half) * std::f32::consts::PI * 2.0
+        / patch_size.max(0.0001)
+}
+
+/// The Phillips spectrum, how much energy the waves with wave vector `k` have.
+fn phillips(k: Vec2, wind: Vec2, wind_speed: f32) -> f32 {
+    let length = k.magnitude();
+    if length < 0.000_001 {
+        return 0.0;
+    }
+    // The biggest waves the wind makes, and a cut off for tiny ones that only add aliasing.
+    let largest = wind_speed * wind_speed / GRAVITY;
+    let smallest = largest / 1000.0;
+    let alignment = k.dot(&wind) / length;
+    let k2 = length * length;
+    let mut energy = (-1.0 / (k2 * largest * largest)).exp() / (k2 * k2)
+        * alignment
+        * alignment
+        * (-k2 * smallest * smallest).exp();
+    // Waves moving against the wind die out.
+    if alignment < 0.0 {
+        energy *= 0.07;
+    }
+    energy
+}
+
+fn random(seed: u32) -> f32 {
+    let mut h = seed.wrapping_mul(0x27d4_eb2d) ^ 0x1656_67b1;
+    h = (h ^ (h >> 15)).wrapping_mul(0x2c1b_3c6d);
+    h = (h ^ (h >> 12)).wrapping_mul(0x297a_2d39);
+    h ^= h >> 15;
+    (h >> 8) as f32 / (1 << 24) as f32
+}
+
+/// Two normally distributed random numbers, with Box-Muller.
+fn gaussian(seed: u32) -> (f32, f32) {
+    let u = random(seed.wrapping_mul(2)).max(0.000_001);
+    let v = random(seed.wrapping_mul(2).wrapping_add(1));
+    let radius = (-2.0 * u.ln()).sqrt();
+    let angle = std::f32::consts::PI * 2.0 * v;
+    (radius * angle.cos(), radius * angle.sin())
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct OceanUniform {
+    // (sea height, patch size, choppiness, time)
+    pub surface: Vec4,
+    // (foam threshold, foam left this frame, wind x, wind z)
+    pub foam: Vec4,
+    pub deep_color: Vec4,
+    pub scatter_color: Vec4,
+    pub horizon_color: Vec4,
+    pub sky_color: Vec4,
+    pub foam_color: Vec4,
+    // (grid resolution, unused..)
+    pub info: Vec4,
+}
+
+unsafe impl Zeroable for OceanUniform {}
+unsafe impl Pod for OceanUniform {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ocean_spectrum_follows_wind() {
+        let wind = Vec2::new(1.0, 0.0);
+        assert_eq!(phillips(Vec2::zeros(), wind, 10.0), 0.0);
+        assert_eq!(phillips(Vec2::new(0.0, 0.1), wind, 10.0), 0.0);
+        let with = phillips(Vec2::new(0.1, 0.0), wind, 10.0);
+        let against = phillips(Vec2::new(-0.1, 0.0), wind, 10.0);
+        assert!(with > against && against > 0.0);
+    }
+
+    #[test]
+    fn test_ocean_height_at() {
+        let mut ocean = Ocean::default();
+        ocean.height = 2.0;
+        assert_eq!(ocean.height_at(10.0, 5.0), 2.0);
+        ocean.displacement = vec![Vec4::new(3.0, 0.5, 0.0, 0.0); OCEAN_FFT_SIZE * OCEAN_FFT_SIZE];
+        assert!((ocean.height_at(10.0, 5.0) - 2.5).abs() < 0.0001);
+        assert_eq!(ocean.buoyancy(Vec3::new(0.0, 10.0, 0.0), 1.0), 0.0);
+        assert_eq!(ocean.buoyancy(Vec3::new(0.0, -10.0, 0.0), 1.0), 1.0);
+        assert!((ocean.buoyancy(Vec3::new(0.0, 2.5, 0.0), 1.0) - 0.5).abs() < 0.0001);
+    }
+}
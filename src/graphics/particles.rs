@@ -0,0 +1,287 @@
+use bytemuck::{Pod, Zeroable};
+use legion::prelude::Entity;
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+use std::collections::HashMap;
+
+use crate::{
+    graphics::{pipeline_manager::PipelineVariant, BlendMode},
+    scene::components::{Curve, ParticleEmitter},
+};
+
+/// One particle in the storage buffer, the compute shader and the vertex shader read the same
+/// layout. Particles with an age past their lifetime are dead and aren't drawn.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct Particle {
+    pub position_age: Vec4,
+    pub velocity_lifetime: Vec4,
+}
+
+unsafe impl Zeroable for Particle {}
+unsafe impl Pod for Particle {}
+
+impl Particle {
+    pub fn is_alive(&self) -> bool {
+        self.position_age.w < self.velocity_lifetime.w
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct SimulationUniform {
+    /// (acceleration, delta time)
+    pub acceleration_dt: Vec4,
+    pub speeds: Vec4,
+    pub speed_times: Vec4,
+    /// (particle count, unused, unused, unused)
+    pub info: Vec4,
+}
+
+unsafe impl Zeroable for SimulationUniform {}
+unsafe impl Pod for SimulationUniform {}
+
+impl SimulationUniform {
+    pub fn new(emitter: &ParticleEmitter, capacity: u32, delta_time: f32) -> Self {
+        let (speed_times, speeds) = emitter.speed.padded();
+        Self {
+            acceleration_dt: Vec4::new(
+                emitter.acceleration.x,
+                emitter.acceleration.y,
+                emitter.acceleration.z,
+                delta_time,
+            ),
+            speeds: speeds.into(),
+            speed_times: speed_times.into(),
+            info: Vec4::new(capacity as f32, 0.0, 0.0, 0.0),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ParticleUniform {
+    pub colors: [Vec4; 4],
+    pub color_times: Vec4,
+    pub sizes: Vec4,
+    pub size_times: Vec4,
+}
+
+unsafe impl Zeroable for ParticleUniform {}
+unsafe impl Pod for ParticleUniform {}
+
+impl ParticleUniform {
+    pub fn new(emitter: &ParticleEmitter) -> Self {
+        let (color_times, colors) = emitter.color.padded();
+        let (size_times, sizes) = emitter.size.padded();
+        Self {
+            colors,
+            color_times: color_times.into(),
+            sizes: sizes.into(),
+            size_times: size_times.into(),
+        }
+    }
+}
+
+/// GPU state for one emitter.
+pub(crate) struct EmitterState {
+    /// STORAGE and VERTEX, the compute shader writes it and it's drawn instanced from it.
+    pub particles: wgpu::Buffer,
+    pub capacity: u32,
+    /// The ring buffer slot the next particle spawns in.
+    pub next_slot: u32,
+    /// Fractions of a particle left over from previous frames.
+    pub spawn_accumulator: f32,
+    pub seed: u32,
+    pub simulation: wgpu::Buffer,
+    pub simulation_bind_group: wgpu::BindGroup,
+    pub uniform: wgpu::Buffer,
+    /// The bind group and the name of the image it was made with.
+    pub bind_group: Option<(String, wgpu::BindGroup)>,
+    /// A copy of the particles when they're simulated on the CPU.
+    pub cpu_particles: Option<Vec<Particle>>,
+    pub position: Vec3,
+    pub queue: i32,
+    pub blend_mode: BlendMode,
+    pub visible: bool,
+}
+
+impl EmitterState {
+    pub fn pipeline_variant(&self) -> PipelineVariant {
+        particle_variant(&self.blend_mode)
+    }
+
+    /// Draws every particle, the "particle" pipeline and the globals at set 0 have to be set.
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        let bind_group = match self.bind_group.as_ref() {
+            Some((_, bind_group)) => bind_group,
+            None => return,
+        };
+        render_pass.set_bind_group(1, bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.particles.slice(..));
+        // A quad per particle, dead ones collapse to a point.
+        render_pass.draw(0..4, 0..self.capacity);
+    }
+}
+
+/// The variant of the "particle" pipeline for a blend mode, alpha blending is the default.
+pub(crate) fn particle_variant(blend_mode: &BlendMode) -> PipelineVariant {
+    PipelineVariant {
+        blend_mode: Some(blend_mode.clone()).filter(|mode| *mode != BlendMode::Alpha),
+        ..Default::default()
+    }
+}
+
+/// Simulates and draws every entity with a `ParticleEmitter` and a `Transform`, a resource.
+/// Particles are simulated by a compute shader, if it's not available they're simulated on
+/// the CPU and uploaded every frame instead.
+pub struct ParticleRenderer {
+    /// Simulate on the CPU even when compute shaders work, off by default.
+    pub force_cpu: bool,
+    pub(crate) emitters: HashMap<Entity, EmitterState>,
+    /// The asset manager's bind group generation the cached bind groups were made for.
+    pub(crate) bind_group_generation: u32,
+}
+
+impl Default for ParticleRenderer {
+    fn default() -> Self {
+        Self {
+            force_cpu: false,
+            emitters: HashMap::new(),
+            bind_group_generation: 0,
+        }
+    }
+}
+
+impl ParticleRenderer {
+    /// Particles alive last frame, only counted when they're simulated on the CPU.
+    pub fn cpu_particle_count(&self) -> usize {
+        self.emitters
+            .values()
+            .filter_map(|emitter| emitter.cpu_particles.as_ref())
+            .map(|particles| {
+                particles
+                    .iter()
+                    .filter(|particle| particle.is_alive())
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Render queues with a visible emitter in them.
+    pub(crate) fn queues(&self) -> impl Iterator<Item = i32> + '_ {
+        self.emitters
+            .values()
+            .filter(|emitter| emitter.visible)
+            .map(|emitter| emitter.queue)
+    }
+}
+
+/// A random number from 0 to 1 for each seed.
+fn random(seed: u32) -> f32 {
+    let mut h = seed.wrapping_mul(0x27d4_eb2d) ^ 0x1656_67b1;
+    h = (h ^ (h >> 15)).wrapping_mul(0x2c1b_3c6d);
+    h = (h ^ (h >> 12)).wrapping_mul(0x297a_2d39);
+    h ^= h >> 15;
+    (h >> 8) as f32 / (1 << 24) as f32
+}
+
+/// A random point in the unit sphere, uses three seeds from `seed`.
+fn random_in_sphere(seed: u32) -> Vec3 {
+    let point = Vec3::new(
+        random(seed.wrapping_mul(3)),
+        random(seed.wrapping_mul(3).wrapping_add(1)),
+        random(seed.wrapping_mul(3).wrapping_add(2)),
+    ) * 2.0
+        - Vec3::new(1.0, 1.0, 1.0);
+    if point.magnitude_squared() > 1.0 {
+        point.normalize()
+    } else {
+        point
+    }
+}
+
+/// A new particle at the emitter, `seed` picks the random offsets.
+pub(crate) fn spawn(emitter: &ParticleEmitter, matrix: &Mat4, seed: u32) -> Particle {
+    let offset = random_in_sphere(seed.wrapping_mul(2)) * emitter.spawn_radius;
+    let position = (matrix * Vec4::new(offset.x, offset.y, offset.z, 1.0)).xyz();
+    let velocity = emitter.velocity
+        + random_in_sphere(seed.wrapping_mul(2).wrapping_add(1)) * emitter.velocity_spread;
+    let velocity = (matrix * Vec4::new(velocity.x, velocity.y, velocity.z, 0.0)).xyz();
+    Particle {
+        position_age: Vec4::new(position.x, position.y, position.z, 0.0),
+        velocity_lifetime: Vec4::new(velocity.x, velocity.y, velocity.z, emitter.lifetime),
+    }
+}
+
+/// The CPU version of particle_simulation_comp.glsl.
+pub(crate) fn simulate(
+    particles: &mut [Particle],
+    acceleration: Vec3,
+    speed: &Curve<f32>,
+    delta_time: f32,
+) {
+    for particle in particles.iter_mut().filter(|particle| particle.is_alive()) {
+        let life = particle.position_age.w / particle.velocity_lifetime.w;
+        let velocity = particle.velocity_lifetime.xyz() + acceleration * delta_time;
+        let position = particle.position_age.xyz() + velocity * speed.sample(life) * delta_time;
+        particle.position_age = Vec4::new(
+            position.x,
+            position.y,
+            position.z,
+            particle.position_age.w + delta_time,
+        );
+        particle.velocity_lifetime = Vec4::new(
+            velocity.x,
+            velocity.y,
+            velocity.z,
+            particle.velocity_lifetime.w,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulate() {
+        let mut particles = [
+            Particle {
+                position_age: Vec4::new(0.0, 0.0, 0.0, 0.0),
+                velocity_lifetime: Vec4::new(1.0, 0.0, 0.0, 1.0),
+            },
+            Particle::zeroed(),
+        ];
+        simulate(
+            &mut particles,
+            Vec3::new(0.0, -2.0, 0.0),
+            &Curve::constant(1.0),
+            0.5,
+        );
+        assert_eq!(particles[0].position_age, Vec4::new(0.5, -0.5, 0.0, 0.5));
+        assert_eq!(
+            particles[0].velocity_lifetime,
+            Vec4::new(1.0, -1.0, 0.0, 1.0)
+        );
+        assert_eq!(particles[1], Particle::zeroed());
+
+        simulate(&mut particles, Vec3::zeros(), &Curve::constant(1.0), 0.5);
+        assert!(!particles[0].is_alive());
+    }
+
+    #[test]
+    fn test_spawn() {
+        let emitter = ParticleEmitter::new(10.0, 2.0)
+            .with_spawn_radius(1.0)
+            .with_velocity(Vec3::new(0.0, 1.0, 0.0), 0.5);
+        let center = Vec3::new(5.0, 0.0, 0.0);
+        let matrix = nalgebra_glm::translation(&center);
+        for seed in 0..100 {
+            let particle = spawn(&emitter, &matrix, seed);
+            assert!(particle.is_alive());
+            assert!(nalgebra_glm::distance(&particle.position_age.xyz(), &center) <= 1.0001);
+            assert!((particle.velocity_lifetime.xyz() - emitter.velocity).magnitude() <= 0.5001);
+        }
+        assert_ne!(spawn(&emitter, &matrix, 1), spawn(&emitter, &matrix, 2));
+    }
+}
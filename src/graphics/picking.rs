@@ -0,0 +1,111 @@
+use legion::prelude::*;
+use nalgebra_glm::{Vec2, Vec3};
+
+use super::{
+    queries::{raycast_bounds, raycast_mesh, Ray},
+    SplitScreen, Viewport,
+};
+use crate::{
+    scene::{components, resources::Visibility},
+    AssetManager,
+};
+
+/// How closely `Renderer::pick` tests the meshes under the cursor.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PickMode {
+    /// Only the bounding boxes, cheap but the space around a mesh counts as the mesh.
+    Bounds,
+    /// Every triangle of meshes whose bounds the ray hits.
+    Triangles,
+}
+
+impl Default for PickMode {
+    fn default() -> Self {
+        PickMode::Triangles
+    }
+}
+
+/// The closest entity under a point on the screen.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Pick {
+    pub entity: Entity,
+    /// Where the ray hit, in world space.
+    pub position: Vec3,
+    /// Distance from the camera along the ray.
+    pub distance: f32,
+}
+
+/// Finds the view under `screen_position` and casts a ray from it's camera into the world.
+/// Hidden entities and meshes that aren't loaded yet are skipped.
+pub(crate) fn pick(
+    world: &World,
+    resources: &Resources,
+    screen_position: Vec2,
+    screen_size: Vec2,
+    mode: PickMode,
+) -> Option<Pick> {
+    let asset_manager = resources.get::<AssetManager>()?;
+    let ray = camera_ray(world, resources, screen_position, screen_size)?;
+
+    let visibility = resources.get::<Visibility>();
+    let query = <(Read<components::Mesh>, Read<components::Transform>)>::query();
+    let mut closest: Option<(Entity, f32)> = None;
+    for (entity, (mesh, transform)) in query.iter_entities(world) {
+        if visibility
+            .as_ref()
+            .map_or(false, |visibility| !visibility.is_visible(entity))
+        {
+            continue;
+        }
+        let asset_mesh = match asset_manager.try_get_mesh(mesh.mesh_name.clone()) {
+            Ok(asset_mesh) => asset_mesh,
+            Err(_) => continue,
+        };
+        let matrix = transform.calculate_matrix();
+        let distance = match mode {
+            PickMode::Bounds => raycast_bounds(&ray, asset_mesh, &matrix),
+            PickMode::Triangles => raycast_mesh(&ray, asset_mesh, &matrix).map(|hit| hit.distance),
+        };
+        if let Some(distance) = distance {
+            if closest.map_or(true, |(_, closest)| distance < closest) {
+                closest = Some((entity, distance));
+            }
+        }
+    }
+
+    closest.map(|(entity, distance)| Pick {
+        entity,
+        position: ray.at(distance),
+        distance,
+    })
+}
+
+// With split screen the ray comes from the camera of the player the position is over.
+fn camera_ray(
+    world: &World,
+    resources: &Resources,
+    screen_position: Vec2,
+    screen_size: Vec2,
+) -> Option<Ray> {
+    let views = resources
+        .get::<SplitScreen>()
+        .map(|split_screen| split_screen.views())
+        .unwrap_or_else(|| vec![(None, Viewport::full())]);
+    let (camera_entity, viewport) = views
+        .into_iter()
+        .map(|(camera, viewport)| (camera, viewport.to_pixels(screen_size.x, screen_size.y)))
+        .find(|(_, viewport)| viewport.contains(screen_position))?;
+
+    let local_position = screen_position - Vec2::new(viewport.x, viewport.y);
+    let viewport_size = Vec2::new(viewport.width, viewport.height);
+    let ray = match camera_entity {
+        Some(entity) => world
+            .get_component::<components::CameraData>(entity)?
+            .screen_to_ray(local_position, viewport_size),
+        None => <Read<components::CameraData>>::query()
+            .iter(world)
+            .find(|camera| camera.active)?
+            .screen_to_ray(local_position, viewport_size),
+    };
+    Some(ray.into())
+}
@@ -56,11 +56,11 @@ pub trait SimplePipelineDesc: std::fmt::Debug {
     ) -> wgpu::RenderPipeline {
         let shader = self.load_shader(asset_manager);
         let vertex_stage = wgpu::ProgrammableStageDescriptor {
-            module: &shader.vertex,
+            module: shader.vertex_module(),
             entry_point: "main",
         };
         let fragment_stage = Some(wgpu::ProgrammableStageDescriptor {
-            module: &shader.fragment,
+            module: shader.fragment_module(),
             entry_point: "main",
         });
 
@@ -3,17 +3,24 @@ use std::collections::{hash_map::DefaultHasher, HashMap};
 use std::hash::{Hash, Hasher};
 
 use super::{
-    renderer::FRAME_FORMAT, resources::GPUResourceManager, CommandBufferQueue, VertexStateBuilder,
+    frame_graph::{AttachmentDesc, AttachmentSize, FrameGraph},
+    material::ShaderDefines,
+    mesh::AttributeVertexData,
+    renderer::{FRAME_FORMAT, HDR_FORMAT},
+    resources::GPUResourceManager,
+    BlendMode, CommandBufferQueue, DepthBias, VertexStateBuilder,
 };
-use crate::AssetManager;
+use crate::{AssetError, AssetManager};
 use solvent::DepGraph;
 
-/// A description of a render pipeline. 
+/// A description of a render pipeline.
 /// Note: You can call `default()` to get a base implementation.
 /// You'll still need to specify the correct shader at the very least.
 #[derive(Debug, Hash, Clone)]
 pub struct PipelineDesc {
     pub shader: String,
+    /// Compiles a variant of `shader` with these defines, see `PipelineManager::add_variant`.
+    pub shader_defines: ShaderDefines,
     pub vertex_state: VertexStateBuilder,
     pub primitive_topology: wgpu::PrimitiveTopology,
     pub color_state: wgpu::ColorStateDescriptor,
@@ -27,12 +34,20 @@ pub struct PipelineDesc {
     pub depth_bias: i32,
     pub depth_bias_slope_scale: OrderedFloat<f32>,
     pub depth_bias_clamp: OrderedFloat<f32>,
+    /// Skips the fragment stage and color output, used for shadow maps.
+    pub depth_only: bool,
+    /// Flattens geometry in front of the near plane onto it instead of clipping it, so shadow
+    /// casters behind the light still cast shadows (pancaking). wgpu can't clamp depth in
+    /// hardware yet so the shader does it, it's compiled with `DEPTH_CLAMP` defined and has to
+    /// clamp `gl_Position.z` itself. Conservative rasterization isn't available at all yet.
+    pub depth_clamp: bool,
 }
 
 impl Default for PipelineDesc {
     fn default() -> Self {
         Self {
             shader: "".to_string(),
+            shader_defines: ShaderDefines::default(),
             vertex_state: VertexStateBuilder::new(),
             primitive_topology: wgpu::PrimitiveTopology::TriangleList,
             color_state: wgpu::ColorStateDescriptor {
@@ -51,11 +66,68 @@ impl Default for PipelineDesc {
             depth_bias: 0,
             depth_bias_slope_scale: 0.0.into(),
             depth_bias_clamp: 0.0.into(),
+            depth_only: false,
+            depth_clamp: false,
         }
     }
 }
 
+/// What a material can change about the pipeline it's drawn with, see
+/// `PipelineManager::add_variant`. The default changes nothing.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct PipelineVariant {
+    pub shader_defines: ShaderDefines,
+    /// None keeps the pipeline's own blending.
+    pub blend_mode: Option<BlendMode>,
+    /// None keeps the pipeline's own depth bias.
+    pub depth_bias: Option<DepthBias>,
+    /// None keeps the pipeline's own winding, flip it for meshes with a mirrored transform.
+    pub front_face: Option<wgpu::FrontFace>,
+    /// None keeps the pipeline's own culling.
+    pub cull_mode: Option<wgpu::CullMode>,
+    /// Reads vertex colors and the second uv set from an extra vertex buffer, for meshes that
+    /// have them. The shader sees `VERTEX_ATTRIBUTES`.
+    pub vertex_attributes: bool,
+    /// Draws with one sample, for probes and other off-screen cube targets when the frame uses
+    /// MSAA. Alpha to coverage needs more than one sample so it's turned off.
+    pub single_sampled: bool,
+}
+
+impl PipelineVariant {
+    pub fn is_default(&self) -> bool {
+        self.shader_defines.is_empty()
+            && self.blend_mode.is_none()
+            && self.depth_bias.is_none()
+            && self.front_face.is_none()
+            && self.cull_mode.is_none()
+            && !self.vertex_attributes
+            && !self.single_sampled
+    }
+}
+
 impl PipelineDesc {
+    /// Sets the color and alpha blending of the color target.
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.color_state.color_blend = blend_mode.color_blend();
+        self.color_state.alpha_blend = blend_mode.alpha_blend();
+    }
+
+    pub fn set_depth_bias(&mut self, depth_bias: DepthBias) {
+        self.depth_bias = depth_bias.constant;
+        self.depth_bias_slope_scale = depth_bias.slope_scale.into();
+        self.depth_bias_clamp = depth_bias.clamp.into();
+    }
+
+    /// The defines the shader is compiled with, `shader_defines` plus the ones the rasterizer
+    /// options need. Load this variant of the shader before building the pipeline.
+    pub fn compiled_defines(&self) -> ShaderDefines {
+        if self.depth_clamp {
+            self.shader_defines.clone().with_flag("DEPTH_CLAMP")
+        } else {
+            self.shader_defines.clone()
+        }
+    }
+
     /// Creates a hash of the pipeline.
     pub fn create_hash(&self) -> u64 {
         let mut s = DefaultHasher::new();
@@ -70,15 +142,19 @@ impl PipelineDesc {
         device: &wgpu::Device,
         gpu_resource_manager: &GPUResourceManager,
     ) -> Pipeline {
-        let shader = asset_manager.get_shader(self.shader.clone());
+        let shader = asset_manager.get_shader(self.compiled_defines().variant_name(&self.shader));
         let vertex_stage = wgpu::ProgrammableStageDescriptor {
-            module: &shader.vertex,
+            module: shader.vertex_module(),
             entry_point: "main",
         };
-        let fragment_stage = Some(wgpu::ProgrammableStageDescriptor {
-            module: &shader.fragment,
-            entry_point: "main",
-        });
+        let fragment_stage = if self.depth_only {
+            None
+        } else {
+            Some(wgpu::ProgrammableStageDescriptor {
+                module: shader.fragment_module(),
+                entry_point: "main",
+            })
+        };
 
         let bind_group_layouts: Vec<&wgpu::BindGroupLayout> = self
             .layouts
@@ -97,7 +173,7 @@ impl PipelineDesc {
             depth_bias_clamp: self.depth_bias_clamp.into(),
         };
         let primitive_topology = self.primitive_topology;
-        let color_states = self.color_state.clone();
+        let color_states = [self.color_state.clone()];
         let depth_stencil_state = self.depth_state.clone();
         let vertex_state_builder = self.vertex_state.clone();
         let sample_count = self.sample_count;
@@ -130,7 +206,7 @@ impl PipelineDesc {
             vertex_stage,
             fragment_stage,
             primitive_topology,
-            color_states: &[color_states],
+            color_states: if self.depth_only { &[] } else { &color_states },
             rasterization_state: Some(rasterization_state),
             depth_stencil_state,
             vertex_state,
@@ -166,12 +242,17 @@ pub enum PipelineType {
 pub struct PipelineManager {
     pipelines: HashMap<String, HashMap<u64, PipelineType>>,
     pub(crate) current_pipelines: HashMap<String, u64>,
+    // The hash of each pipeline variant made with `add_variant`.
+    variants: HashMap<String, HashMap<PipelineVariant, u64>>,
     dep_graph: DepGraph<String>,
     order: Vec<String>,
+    frame_graph: FrameGraph,
+    // Pipelines built since the last frame, for the `HitchDetector`.
+    created_pipelines: Vec<String>,
 }
 
 impl PipelineManager {
-    /// Creates a new pipeline manager. 
+    /// Creates a new pipeline manager.
     pub fn new() -> Self {
         let mut dep_graph = DepGraph::new();
         dep_graph.register_node("root".to_string());
@@ -180,6 +261,9 @@ impl PipelineManager {
             dep_graph,
             order: Vec::new(),
             current_pipelines: HashMap::new(),
+            variants: HashMap::new(),
+            frame_graph: FrameGraph::default(),
+            created_pipelines: Vec::new(),
         }
     }
 
@@ -214,6 +298,7 @@ impl PipelineManager {
 
         let pipeline = pipeline_desc.build(&asset_manager, &device, &gpu_resource_manager);
         pipeline_hashmap.insert(hash, PipelineType::Pipeline(pipeline));
+        self.created_pipelines.push(name.clone());
 
         // Add to our graph
         self.dep_graph.register_node(name.clone());
@@ -268,6 +353,239 @@ impl PipelineManager {
         self.get_order();
     }
 
+    /// Adds a pass that reads and writes named attachments, instead of listing dependencies by hand.
+    /// A pass runs after the passes added before it that write any of the attachments it reads or writes.
+    /// The name should match the name of the `CommandQueueItem` the pass pushes.
+    /// The built-in passes use "globals", "joints", "shadow_map", "light_clusters", "frame", "depth"
+    /// and "swap_chain".
+    /// "frame" is the HDR texture the scene is drawn into, "bloom" and "post_process" read it and
+    /// the post processing passes write the swap chain.
+    pub fn add_pass<T: Into<String>>(&mut self, name: T, reads: Vec<&str>, writes: Vec<&str>) {
+        let to_strings = |list: Vec<&str>| {
+            list.iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<String>>()
+        };
+        self.frame_graph
+            .add_pass(name.into(), to_strings(reads), to_strings(writes));
+
+        for (pass, dependencies) in self.frame_graph.dependencies() {
+            if !self.pipelines.contains_key(&pass) {
+                let mut hasher = DefaultHasher::new();
+                pass.hash(&mut hasher);
+                self.pipelines.insert(pass.clone(), HashMap::new());
+                self.current_pipelines.insert(pass.clone(), hasher.finish());
+            }
+            self.dep_graph.register_node(pass.clone());
+            if dependencies.len() > 0 {
+                self.dep_graph.register_dependencies(pass, dependencies);
+            }
+        }
+
+        // Recalculate order.
+        self.get_order();
+    }
+
+    /// Adds the attachments and passes the engine draws with, the application does this before
+    /// `AppState::load` so user passes can slot in between them. `ldr_format` is the swap
+    /// chain's format.
+    pub(crate) fn add_builtin_passes(
+        &mut self,
+        sample_count: u32,
+        ldr_format: wgpu::TextureFormat,
+    ) {
+        // The scene is drawn in HDR, post processing turns it into the swap chain image.
+        self.add_attachment(
+            "frame",
+            AttachmentDesc::new(HDR_FORMAT, AttachmentSize::Frame),
+        );
+        // With MSAA the scene is drawn here and resolved into "frame".
+        if sample_count > 1 {
+            self.add_attachment(
+                "frame_msaa",
+                AttachmentDesc::new(HDR_FORMAT, AttachmentSize::Frame)
+                    .with_sample_count(sample_count),
+            );
+        }
+        // Clouds and SSGI are gathered at half resolution into a transient target, blended
+        // with their history and then copied over it for the next frame. The gather targets
+        // and the bloom targets are used at different times so they share memory.
+        let half = AttachmentDesc::new(HDR_FORMAT, AttachmentSize::Scaled(0.5));
+        for name in &["clouds", "ssgi"] {
+            self.add_attachment(
+                format!("{}_gather", name),
+                half.clone()
+                    .with_usage(wgpu::TextureUsage::COPY_SRC)
+                    .transient(),
+            );
+            self.add_attachment(
+                format!("{}_history", name),
+                half.clone().with_usage(wgpu::TextureUsage::COPY_DST),
+            );
+        }
+        // Bloom is blurred at half resolution, it's cheaper and looks just as good.
+        self.add_attachment("bloom_a", half.clone().transient());
+        self.add_attachment("bloom_b", half.transient());
+        // FXAA and sharpening need the tonemapped frame as a texture, with more than one LDR
+        // stage they ping pong between the two.
+        let ldr = AttachmentDesc::new(ldr_format, AttachmentSize::Frame).transient();
+        self.add_attachment("ldr", ldr.clone());
+        self.add_attachment("ldr_b", ldr);
+        self.add_pass("globals", vec![], vec!["globals"]);
+        self.add_pass("skinning", vec!["globals"], vec!["joints"]);
+        self.add_pass("deformation", vec![], vec!["deformed_meshes"]);
+        self.add_pass(
+            "shadow",
+            vec!["globals", "joints", "deformed_meshes"],
+            vec!["shadow_map"],
+        );
+        self.add_pass("light_culling", vec!["globals"], vec!["light_clusters"]);
+        self.add_pass("atmosphere", vec!["globals"], vec!["atmosphere_sky_view"]);
+        self.add_pass(
+            "skybox",
+            vec!["globals", "atmosphere_sky_view"],
+            vec!["frame"],
+        );
+        self.add_pass(
+            "clouds",
+            vec!["globals"],
+            vec!["frame", "clouds_gather", "clouds_history"],
+        );
+        self.add_pass("paint", vec![], vec!["paint_textures"]);
+        self.add_pass("particles", vec![], vec!["particles"]);
+        // Particles are drawn with the blended meshes.
+        self.add_pass(
+            "pbr",
+            vec![
+                "globals",
+                "joints",
+                "deformed_meshes",
+                "shadow_map",
+                "light_clusters",
+                "paint_textures",
+                "particles",
+            ],
+            vec!["frame", "depth"],
+        );
+        // Contact shadows darken what the meshes drew, the lights are in the clusters.
+        self.add_pass(
+            "contact_shadows",
+            vec!["globals", "depth", "light_clusters"],
+            vec!["frame"],
+        );
+        // Bounce light is gathered from the lit frame and added back onto it.
+        self.add_pass(
+            "ssgi",
+            vec!["depth", "frame"],
+            vec!["frame", "ssgi_gather", "ssgi_history"],
+        );
+        // Caustics go onto the meshes under water, before the ocean covers them.
+        self.add_pass("caustics", vec!["globals", "depth"], vec!["frame"]);
+        // The ocean only covers what the meshes left uncovered.
+        self.add_pass("ocean", vec!["globals"], vec!["frame", "depth"]);
+        self.add_pass("line", vec!["globals"], vec!["frame", "depth"]);
+        // Fades everything that wrote depth into the atmosphere.
+        self.add_pass(
+            "aerial_perspective",
+            vec!["globals", "depth"],
+            vec!["frame"],
+        );
+        // The grid is depth tested against the scene, debug lines are drawn over it.
+        self.add_pass("editor_grid", vec!["globals", "depth"], vec!["frame"]);
+        // Debug lines go over the fog so they stay readable far away.
+        self.add_pass("debug", vec!["globals", "depth"], vec!["frame"]);
+        // Sprites are drawn on top of the 3D scene.
+        self.add_pass("sprites", vec!["globals"], vec!["frame"]);
+    }
+
+    /// Adds the post processing passes, the application does this after `AppState::load` so
+    /// they come after anything else that draws to the frame.
+    pub(crate) fn add_post_process_passes(&mut self) {
+        // Auto-exposure metering and bloom only read the frame.
+        self.add_pass("bloom", vec!["frame"], vec!["bloom_a", "bloom_b"]);
+        // Tonemapping and the camera's warp, straight to the swap chain when they're last.
+        self.add_pass(
+            "post_process",
+            vec!["frame", "bloom_a"],
+            vec!["swap_chain", "ldr", "ldr_b"],
+        );
+        // FXAA and sharpening work on the tonemapped frame.
+        self.add_pass(
+            "post_process_ldr",
+            vec!["ldr", "ldr_b"],
+            vec!["swap_chain", "ldr", "ldr_b"],
+        );
+        // Text goes over the tonemapped frame, the imgui UI is drawn after everything.
+        self.add_pass("text", vec!["depth"], vec!["swap_chain"]);
+    }
+
+    /// Adds a transient texture passes can render into, it's created before the next frame
+    /// and recreated whenever it's size changes. Attachments made with
+    /// `AttachmentDesc::transient` share memory with each other when the passes that list them
+    /// don't overlap.
+    pub fn add_attachment<T: Into<String>>(&mut self, name: T, desc: AttachmentDesc) {
+        self.frame_graph.add_attachment(name.into(), desc);
+    }
+
+    /// Returns the view of a transient attachment, None until the first frame is rendered.
+    pub fn get_attachment<T: Into<String>>(&self, name: T) -> Option<&wgpu::TextureView> {
+        self.frame_graph.get_view(&name.into())
+    }
+
+    /// Copies one attachment into another of the same size and format, for keeping history.
+    /// The source needs `COPY_SRC` and the destination `COPY_DST` usage.
+    pub(crate) fn copy_attachment(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &str,
+        destination: &str,
+    ) {
+        let (source, destination, (width, height)) = match (
+            self.frame_graph.get_texture(source),
+            self.frame_graph.get_texture(destination),
+            self.frame_graph.get_extent(source),
+        ) {
+            (Some(source), Some(destination), Some(extent)) => (source, destination, extent),
+            _ => return,
+        };
+        encoder.copy_texture_to_texture(
+            wgpu::TextureCopyView {
+                texture: source,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::TextureCopyView {
+                texture: destination,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+        );
+    }
+
+    /// The width and height of a transient attachment.
+    pub fn get_attachment_size<T: Into<String>>(&self, name: T) -> Option<(u32, u32)> {
+        self.frame_graph.get_extent(&name.into())
+    }
+
+    /// The attachment and resolve target passes drawing the scene should use.
+    /// With MSAA it's "frame_msaa" resolved into "frame", otherwise it's just "frame".
+    pub fn get_frame_target(&self) -> Option<(&wgpu::TextureView, Option<&wgpu::TextureView>)> {
+        let frame = self.frame_graph.get_view("frame")?;
+        match self.frame_graph.get_view("frame_msaa") {
+            Some(frame_msaa) => Some((frame_msaa, Some(frame))),
+            None => Some((frame, None)),
+        }
+    }
+
+    pub(crate) fn prepare_attachments(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.frame_graph.prepare(device, width, height, &self.order);
+    }
+
     fn get_order(&mut self) {
         let mut order = Vec::new();
         for (name, _) in self.pipelines.iter() {
@@ -321,6 +639,110 @@ impl PipelineManager {
         }
     }
 
+    /// Like `get` but picks the variant built for `sample_count`, preferring the current one.
+    /// Probes draw single sampled so they use this to skip the MSAA pipelines.
+    pub fn get_with_sample_count<T: Into<String>>(
+        &self,
+        name: T,
+        sample_count: u32,
+    ) -> Option<&Pipeline> {
+        let name = name.into();
+        match self.get(name.as_str(), None) {
+            Some(pipeline) if pipeline.desc.sample_count == sample_count => Some(pipeline),
+            _ => self
+                .pipelines
+                .get(&name)?
+                .values()
+                .filter_map(|pipeline_type| match pipeline_type {
+                    PipelineType::Pipeline(pipeline) => Some(pipeline),
+                    _ => None,
+                })
+                .find(|pipeline| pipeline.desc.sample_count == sample_count),
+        }
+    }
+
+    /// Builds a copy of the current `name` pipeline with the variant's blend mode, depth bias and
+    /// rasterizer state and it's shader compiled with the variant's defines, materials use this to get a
+    /// specialized shader without any branching in it. Variants are cached so this is cheap to
+    /// call again.
+    pub fn add_variant<T: Into<String>>(
+        &mut self,
+        name: T,
+        variant: &PipelineVariant,
+        device: &wgpu::Device,
+        asset_manager: &mut AssetManager,
+        gpu_resource_manager: &GPUResourceManager,
+    ) -> Result<(), AssetError> {
+        let name = name.into();
+        let exists = self
+            .variants
+            .get(&name)
+            .map_or(false, |variants| variants.contains_key(variant));
+        if variant.is_default() || exists {
+            return Ok(());
+        }
+        let mut desc = self
+            .get(name.as_str(), None)
+            .ok_or_else(|| AssetError::missing("pipeline", name.as_str()))?
+            .desc
+            .clone();
+        // Defines the pipeline itself was built with, like the reduced path's, are kept.
+        desc.shader_defines = desc.shader_defines.merged(&variant.shader_defines);
+        if let Some(blend_mode) = variant.blend_mode.as_ref() {
+            desc.set_blend_mode(blend_mode.clone());
+        }
+        if let Some(depth_bias) = variant.depth_bias {
+            desc.set_depth_bias(depth_bias);
+        }
+        if let Some(front_face) = variant.front_face {
+            desc.front_face = front_face;
+        }
+        if let Some(cull_mode) = variant.cull_mode {
+            desc.cull_mode = cull_mode;
+        }
+        if variant.vertex_attributes {
+            let defines = ShaderDefines::new().with_flag("VERTEX_ATTRIBUTES");
+            desc.shader_defines = desc.shader_defines.merged(&defines);
+            AttributeVertexData::add_buffer_descriptor(&mut desc.vertex_state);
+        }
+        if variant.single_sampled {
+            desc.sample_count = 1;
+            desc.alpha_to_coverage_enabled = false;
+        }
+        asset_manager.load_shader_variant(device, &desc.shader, &desc.compiled_defines())?;
+
+        let hash = desc.create_hash();
+        let pipeline = desc.build(asset_manager, device, gpu_resource_manager);
+        self.pipelines
+            .get_mut(&name)
+            .unwrap()
+            .insert(hash, PipelineType::Pipeline(pipeline));
+        self.created_pipelines.push(name.clone());
+        self.variants
+            .entry(name)
+            .or_insert_with(HashMap::new)
+            .insert(variant.clone(), hash);
+        Ok(())
+    }
+
+    /// The variant of the current `name` pipeline, the default variant gives the current
+    /// pipeline itself. None until `add_variant` was called for it.
+    pub fn get_variant<T: Into<String>>(
+        &self,
+        name: T,
+        variant: &PipelineVariant,
+    ) -> Option<&Pipeline> {
+        let name = name.into();
+        if variant.is_default() {
+            return self.get(name, None);
+        }
+        let hash = self.variants.get(&name)?.get(variant)?;
+        match self.pipelines.get(&name)?.get(hash)? {
+            PipelineType::Pipeline(pipeline) => Some(pipeline),
+            _ => None,
+        }
+    }
+
     /// Get's the hash for the current pipeline being used.
     pub fn get_current_pipeline_hash<T: Into<String>>(&self, name: T) -> u64 {
         let name = name.into();
@@ -333,6 +755,33 @@ impl PipelineManager {
         self.current_pipelines.insert(name, hash);
     }
 
+    /// Rebuilds every pipeline that uses `shader`, used when a shader is hot reloaded.
+    pub(crate) fn reload_shader(
+        &mut self,
+        shader: &str,
+        device: &wgpu::Device,
+        asset_manager: &AssetManager,
+        gpu_resource_manager: &GPUResourceManager,
+    ) {
+        for (name, pipeline_hashmap) in self.pipelines.iter_mut() {
+            for pipeline_type in pipeline_hashmap.values_mut() {
+                if let PipelineType::Pipeline(pipeline) = pipeline_type {
+                    if pipeline.desc.shader == shader {
+                        *pipeline =
+                            pipeline
+                                .desc
+                                .build(asset_manager, device, gpu_resource_manager);
+                        self.created_pipelines.push(name.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn take_created_pipelines(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.created_pipelines)
+    }
+
     /// Collects command buffers for submission.
     pub(crate) fn collect_buffers(
         &self,
@@ -357,3 +806,50 @@ impl PipelineManager {
         command_buffers
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_builtin_passes_are_ordered() {
+        let mut pipeline_manager = PipelineManager::new();
+        pipeline_manager.add_builtin_passes(4, wgpu::TextureFormat::Bgra8UnormSrgb);
+        let position = |name: &str| pipeline_manager.order.iter().position(|pass| pass == name);
+        // Command buffers are only submitted for passes in the order.
+        for pass in &[
+            "globals",
+            "pbr",
+            "caustics",
+            "editor_grid",
+            "debug",
+            "sprites",
+        ] {
+            assert!(position(pass).is_some(), "{} isn't in the order", pass);
+        }
+        assert!(position("pbr") < position("caustics"));
+        assert!(position("caustics") < position("ocean"));
+        assert!(position("pbr") < position("editor_grid"));
+        assert!(position("editor_grid") < position("debug"));
+    }
+    #[test]
+    fn test_builtin_attachments_alias() {
+        let mut pipeline_manager = PipelineManager::new();
+        pipeline_manager.add_builtin_passes(1, wgpu::TextureFormat::Bgra8UnormSrgb);
+        pipeline_manager.add_post_process_passes();
+        let slots = pipeline_manager
+            .frame_graph
+            .alias(&pipeline_manager.order, 1280, 720);
+        // The gather targets are done with by the time the next one starts.
+        assert_eq!(slots["clouds_gather"], slots["ssgi_gather"]);
+        assert_eq!(slots["ssgi_gather"], slots["bloom_a"]);
+        // Used at the same time or kept between frames.
+        assert_ne!(slots["bloom_a"], slots["bloom_b"]);
+        assert_ne!(slots["ldr"], slots["ldr_b"]);
+        assert_ne!(slots["clouds_gather"], slots["clouds_history"]);
+        assert_ne!(slots["ssgi_gather"], slots["ssgi_history"]);
+        let textures: HashSet<usize> = slots.values().copied().collect();
+        assert!(textures.len() < slots.len());
+    }
+}
@@ -0,0 +1,242 @@
+use legion::prelude::Resources;
+
+use crate::{
+    graphics::{
+        atmosphere::{MULTI_SCATTERING_LUT_SIZE, TRANSMITTANCE_LUT_SIZE},
+        pipeline_manager::{PipelineDesc, PipelineManager},
+        renderer::{DEPTH_FORMAT, HDR_FORMAT},
+        resources::GPUResourceManager,
+        AttachmentDesc, AttachmentSize, GpuCapabilities, RenderSettings,
+    },
+    AssetManager,
+};
+
+/// Width and height of the sky view LUT, it's redrawn every frame.
+pub(crate) const SKY_VIEW_LUT_SIZE: (u32, u32) = (192, 108);
+
+/// The LUTs that only change with the atmosphere's medium, they're filled in on the CPU.
+/// `bind_group` and `sky_view_bind_group` are rebuilt each frame by the atmosphere system.
+pub(crate) struct AtmosphereResources {
+    pub transmittance_texture: wgpu::Texture,
+    pub transmittance_view: wgpu::TextureView,
+    pub multi_scattering_texture: wgpu::Texture,
+    pub multi_scattering_view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    pub bind_group: Option<wgpu::BindGroup>,
+    pub sky_view_bind_group: Option<wgpu::BindGroup>,
+}
+
+fn lut_texture(device: &wgpu::Device, width: u32, height: u32, label: &str) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba32Float,
+        usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        label: Some(label),
+    })
+}
+
+fn texture_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStage::FRAGMENT,
+        ty: wgpu::BindingType::SampledTexture {
+            component_type: wgpu::TextureComponentType::Float,
+            multisampled: false,
+            dimension: wgpu::TextureViewDimension::D2,
+        },
+    }
+}
+
+pub fn create(resources: &mut Resources) {
+    let atmosphere_resources = {
+        let asset_manager = resources.get::<AssetManager>().unwrap();
+        let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+        let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+        let device = resources.get::<wgpu::Device>().unwrap();
+        let sample_count = resources.get::<RenderSettings>().unwrap().sample_count;
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                },
+                texture_entry(2),
+                texture_entry(3),
+            ],
+            label: Some("atmosphere"),
+        });
+        resource_manager.add_bind_group_layout("atmosphere", layout);
+        let sky_view_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[texture_entry(0)],
+            label: Some("atmosphere_sky_view"),
+        });
+        resource_manager.add_bind_group_layout("atmosphere_sky_view", sky_view_layout);
+        // The depth buffer is multisampled along with the frame.
+        let depth_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    component_type: wgpu::TextureComponentType::Float,
+                    multisampled: sample_count > 1,
+                    dimension: wgpu::TextureViewDimension::D2,
+                },
+            }],
+            label: Some("atmosphere_depth"),
+        });
+        resource_manager.add_bind_group_layout("atmosphere_depth", depth_layout);
+
+        // Scattered light for every direction around the camera.
+        let mut sky_view_desc = PipelineDesc::default();
+        sky_view_desc.shader = "atmosphere_sky_view.shader".to_string();
+        sky_view_desc.color_state.format = HDR_FORMAT;
+        sky_view_desc.cull_mode = wgpu::CullMode::None;
+        sky_view_desc.layouts = vec!["globals".to_string(), "atmosphere".to_string()];
+        pipeline_manager.add_pipeline(
+            "atmosphere_sky_view",
+            &sky_view_desc,
+            vec![],
+            &device,
+            &asset_manager,
+            &resource_manager,
+        );
+
+        // Drawn by the skybox system in place of a cubemap.
+        let mut sky_desc = PipelineDesc::default();
+        sky_desc.shader = "atmosphere_sky.shader".to_string();
+        sky_desc.color_state.format = HDR_FORMAT;
+        sky_desc.sample_count = sample_count;
+        sky_desc.depth_state = Some(wgpu::DepthStencilStateDescriptor {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+            stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+            stencil_read_mask: 0,
+            stencil_write_mask: 0,
+        });
+        sky_desc.cull_mode = wgpu::CullMode::None;
+        sky_desc.layouts = vec![
+            "globals".to_string(),
+            "atmosphere".to_string(),
+            "atmosphere_sky_view".to_string(),
+        ];
+        pipeline_manager.add_pipeline(
+            "atmosphere_sky",
+            &sky_desc,
+            vec![],
+            &device,
+            &asset_manager,
+            &resource_manager,
+        );
+        // Probes render single sampled.
+        if sample_count > 1 {
+            let mut probe_desc = sky_desc.clone();
+            probe_desc.sample_count = 1;
+            pipeline_manager.add_pipeline(
+                "atmosphere_sky",
+                &probe_desc,
+                vec![],
+                &device,
+                &asset_manager,
+                &resource_manager,
+            );
+        }
+
+        // Aerial perspective stores how much of the geometry shows through in alpha.
+        let mut aerial_desc = PipelineDesc::default();
+        aerial_desc.shader = if sample_count > 1 {
+            "atmosphere_aerial_msaa.shader".to_string()
+        } else {
+            "atmosphere_aerial.shader".to_string()
+        };
+        aerial_desc.color_state.format = HDR_FORMAT;
+        aerial_desc.sample_count = sample_count;
+        aerial_desc.color_state.color_blend = wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::SrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        };
+        aerial_desc.color_state.alpha_blend = wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::Zero,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        aerial_desc.cull_mode = wgpu::CullMode::None;
+        aerial_desc.layouts = vec![
+            "globals".to_string(),
+            "atmosphere".to_string(),
+            "atmosphere_depth".to_string(),
+        ];
+        pipeline_manager.add_pipeline(
+            "atmosphere_aerial",
+            &aerial_desc,
+            vec![],
+            &device,
+            &asset_manager,
+            &resource_manager,
+        );
+
+        pipeline_manager.add_attachment(
+            "atmosphere_sky_view",
+            AttachmentDesc::new(
+                HDR_FORMAT,
+                AttachmentSize::Fixed(SKY_VIEW_LUT_SIZE.0, SKY_VIEW_LUT_SIZE.1),
+            ),
+        );
+
+        // The LUTs are 32 bit floats, they're sampled without filtering where that's
+        // not supported. It bands a little but the sky still shows up.
+        let lut_filter = match resources.get::<GpuCapabilities>() {
+            Some(capabilities) if !capabilities.float32_filterable => wgpu::FilterMode::Nearest,
+            _ => wgpu::FilterMode::Linear,
+        };
+        let transmittance_texture = lut_texture(
+            &device,
+            TRANSMITTANCE_LUT_SIZE.0,
+            TRANSMITTANCE_LUT_SIZE.1,
+            "atmosphere_transmittance",
+        );
+        let multi_scattering_texture = lut_texture(
+            &device,
+            MULTI_SCATTERING_LUT_SIZE,
+            MULTI_SCATTERING_LUT_SIZE,
+            "atmosphere_multi_scattering",
+        );
+        AtmosphereResources {
+            transmittance_view: transmittance_texture.create_default_view(),
+            transmittance_texture,
+            multi_scattering_view: multi_scattering_texture.create_default_view(),
+            multi_scattering_texture,
+            sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                label: None,
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: lut_filter,
+                min_filter: lut_filter,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                lod_min_clamp: -100.0,
+                lod_max_clamp: 100.0,
+                compare: wgpu::CompareFunction::Undefined,
+            }),
+            bind_group: None,
+            sky_view_bind_group: None,
+        }
+    };
+    resources.insert(atmosphere_resources);
+}
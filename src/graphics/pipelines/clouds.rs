@@ -0,0 +1,192 @@
+use legion::prelude::Resources;
+
+use crate::{
+    graphics::{
+        noise::{perlin3, worley3},
+        pipeline_manager::{PipelineDesc, PipelineManager},
+        renderer::HDR_FORMAT,
+        resources::GPUResourceManager,
+        RenderSettings,
+    },
+    AssetManager,
+};
+
+/// Width, height and depth of the tiling cloud noise texture.
+const NOISE_SIZE: u32 = 64;
+
+/// The 3D noise the clouds are carved out of and the sampler that reads it.
+pub(crate) struct CloudResources {
+    #[allow(dead_code)]
+    noise_texture: wgpu::Texture,
+    pub noise_view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+}
+
+// Red is perlin-worley for the overall cloud shapes, green is finer worley to erode the edges.
+fn generate_noise() -> Vec<u8> {
+    let size = NOISE_SIZE as usize;
+    let mut bytes = Vec::with_capacity(size * size * size * 4);
+    for z in 0..NOISE_SIZE {
+        for y in 0..NOISE_SIZE {
+            for x in 0..NOISE_SIZE {
+                let (x, y, z) = (
+                    x as f32 / NOISE_SIZE as f32,
+                    y as f32 / NOISE_SIZE as f32,
+                    z as f32 / NOISE_SIZE as f32,
+                );
+                let perlin = perlin3(x * 4.0, y * 4.0, z * 4.0, 4, 0) * 0.5 + 0.5;
+                let cells = 1.0 - worley3(x * 4.0, y * 4.0, z * 4.0, 4, 1);
+                let shape = (perlin * cells * 1.5).min(1.0);
+                let detail = (1.0 - worley3(x * 8.0, y * 8.0, z * 8.0, 8, 2)) * 0.625
+                    + (1.0 - worley3(x * 16.0, y * 16.0, z * 16.0, 16, 3)) * 0.375;
+                bytes.push((shape * 255.0).round() as u8);
+                bytes.push((detail * 255.0).round() as u8);
+                bytes.push(0);
+                bytes.push(255);
+            }
+        }
+    }
+    bytes
+}
+
+pub fn create(resources: &mut Resources) {
+    let cloud_resources = {
+        let asset_manager = resources.get::<AssetManager>().unwrap();
+        let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+        let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+        let device = resources.get::<wgpu::Device>().unwrap();
+        let mut queue = resources.get_mut::<wgpu::Queue>().unwrap();
+
+        // Binding 2 is last frame's clouds when raymarching and this frame's when compositing.
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: false,
+                        dimension: wgpu::TextureViewDimension::D3,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: false,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                },
+            ],
+            label: Some("clouds"),
+        });
+        resource_manager.add_bind_group_layout("clouds", layout);
+
+        let mut desc = PipelineDesc::default();
+        desc.shader = "clouds.shader".to_string();
+        desc.color_state.format = HDR_FORMAT;
+        desc.cull_mode = wgpu::CullMode::None;
+        desc.layouts = vec!["clouds".to_string()];
+        pipeline_manager.add_pipeline(
+            "clouds",
+            &desc,
+            vec![],
+            &device,
+            &asset_manager,
+            &resource_manager,
+        );
+
+        // The clouds store how much light gets through in alpha, so the frame is scaled by it.
+        let mut composite_desc = PipelineDesc::default();
+        composite_desc.shader = "clouds_composite.shader".to_string();
+        composite_desc.color_state.format = HDR_FORMAT;
+        composite_desc.sample_count = resources.get::<RenderSettings>().unwrap().sample_count;
+        composite_desc.color_state.color_blend = wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::SrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        };
+        composite_desc.color_state.alpha_blend = wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::Zero,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        composite_desc.cull_mode = wgpu::CullMode::None;
+        composite_desc.layouts = vec!["clouds".to_string()];
+        pipeline_manager.add_pipeline(
+            "clouds_composite",
+            &composite_desc,
+            vec![],
+            &device,
+            &asset_manager,
+            &resource_manager,
+        );
+
+        let extent = wgpu::Extent3d {
+            width: NOISE_SIZE,
+            height: NOISE_SIZE,
+            depth: NOISE_SIZE,
+        };
+        let noise_texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+            label: Some("cloud_noise"),
+        });
+        let noise_buffer =
+            device.create_buffer_with_data(&generate_noise(), wgpu::BufferUsage::COPY_SRC);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("cloud_noise"),
+        });
+        // 64 RGBA8 pixels is exactly 256 bytes so rows don't need padding.
+        encoder.copy_buffer_to_texture(
+            wgpu::BufferCopyView {
+                buffer: &noise_buffer,
+                layout: wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: NOISE_SIZE * 4,
+                    rows_per_image: NOISE_SIZE,
+                },
+            },
+            wgpu::TextureCopyView {
+                texture: &noise_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            extent,
+        );
+        queue.submit(Some(encoder.finish()));
+
+        CloudResources {
+            noise_view: noise_texture.create_default_view(),
+            noise_texture,
+            sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                label: None,
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::Repeat,
+                address_mode_w: wgpu::AddressMode::Repeat,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                lod_min_clamp: -100.0,
+                lod_max_clamp: 100.0,
+                compare: wgpu::CompareFunction::Undefined,
+            }),
+        }
+    };
+    resources.insert(cloud_resources);
+}
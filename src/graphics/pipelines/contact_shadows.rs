@@ -0,0 +1,97 @@
+use legion::prelude::Resources;
+
+use crate::{
+    graphics::{
+        pipeline_manager::{PipelineDesc, PipelineManager},
+        renderer::HDR_FORMAT,
+        resources::GPUResourceManager,
+        RenderSettings,
+    },
+    AssetManager,
+};
+
+/// The contact shadow pass reads the depth buffer through this, the bind group is made each
+/// frame since the depth buffer is recreated on resize.
+pub(crate) struct ContactShadowResources {
+    pub sampler: wgpu::Sampler,
+}
+
+pub fn create(resources: &mut Resources) {
+    let contact_shadow_resources = {
+        let asset_manager = resources.get::<AssetManager>().unwrap();
+        let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+        let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+        let device = resources.get::<wgpu::Device>().unwrap();
+        let sample_count = resources.get::<RenderSettings>().unwrap().sample_count;
+
+        // The depth buffer is multisampled along with the frame.
+        let depth_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: sample_count > 1,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                },
+            ],
+            label: Some("contact_shadows_depth"),
+        });
+        resource_manager.add_bind_group_layout("contact_shadows_depth", depth_layout);
+
+        // Multiplies the frame with how much light reaches each pixel.
+        let mut contact_shadows_desc = PipelineDesc::default();
+        contact_shadows_desc.shader = if sample_count > 1 {
+            "contact_shadows_msaa.shader".to_string()
+        } else {
+            "contact_shadows.shader".to_string()
+        };
+        contact_shadows_desc.color_state.format = HDR_FORMAT;
+        contact_shadows_desc.sample_count = sample_count;
+        contact_shadows_desc.color_state.color_blend = wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::Zero,
+            dst_factor: wgpu::BlendFactor::SrcColor,
+            operation: wgpu::BlendOperation::Add,
+        };
+        contact_shadows_desc.color_state.alpha_blend = wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::Zero,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        contact_shadows_desc.cull_mode = wgpu::CullMode::None;
+        // The lights and clusters are in the globals, set 1 like the PBR shader.
+        contact_shadows_desc.layouts =
+            vec!["contact_shadows_depth".to_string(), "globals".to_string()];
+        pipeline_manager.add_pipeline(
+            "contact_shadows",
+            &contact_shadows_desc,
+            vec![],
+            &device,
+            &asset_manager,
+            &resource_manager,
+        );
+
+        ContactShadowResources {
+            sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("contact_shadows_depth"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Nearest,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                lod_min_clamp: -100.0,
+                lod_max_clamp: 100.0,
+                compare: wgpu::CompareFunction::Undefined,
+            }),
+        }
+    };
+    resources.insert(contact_shadow_resources);
+}
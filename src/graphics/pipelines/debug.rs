@@ -0,0 +1,66 @@
+use legion::prelude::Resources;
+
+use crate::{
+    graphics::{
+        debug::DebugVertex,
+        pipeline_manager::{PipelineDesc, PipelineManager},
+        renderer::{DEPTH_FORMAT, HDR_FORMAT},
+        resources::GPUResourceManager,
+        RenderSettings,
+    },
+    AssetManager,
+};
+
+pub fn create(resources: &Resources) {
+    let asset_manager = resources.get::<AssetManager>().unwrap();
+    let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+    let resource_manager = resources.get::<GPUResourceManager>().unwrap();
+    let device = resources.get::<wgpu::Device>().unwrap();
+
+    let mut debug_desc = PipelineDesc::default();
+    debug_desc.shader = "line.shader".to_string();
+    debug_desc.primitive_topology = wgpu::PrimitiveTopology::LineList;
+    debug_desc.color_state.format = HDR_FORMAT;
+    debug_desc.sample_count = resources.get::<RenderSettings>().unwrap().sample_count;
+    debug_desc.cull_mode = wgpu::CullMode::None;
+    debug_desc.depth_state = Some(wgpu::DepthStencilStateDescriptor {
+        format: DEPTH_FORMAT,
+        depth_write_enabled: false,
+        depth_compare: wgpu::CompareFunction::LessEqual,
+        stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+        stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+        stencil_read_mask: 0,
+        stencil_write_mask: 0,
+    });
+    debug_desc.layouts = vec!["globals".to_string()];
+    debug_desc
+        .vertex_state
+        .set_index_format(wgpu::IndexFormat::Uint16)
+        .new_buffer_descriptor(
+            std::mem::size_of::<DebugVertex>() as wgpu::BufferAddress,
+            wgpu::InputStepMode::Vertex,
+            wgpu::vertex_attr_array![0 => Float3, 1 => Float3].to_vec(),
+        );
+    pipeline_manager.add_pipeline(
+        "debug_lines",
+        &debug_desc,
+        vec![],
+        &device,
+        &asset_manager,
+        &resource_manager,
+    );
+
+    // Drawn in the same pass, so it still needs a depth state.
+    let mut overlay_desc = debug_desc.clone();
+    if let Some(depth_state) = overlay_desc.depth_state.as_mut() {
+        depth_state.depth_compare = wgpu::CompareFunction::Always;
+    }
+    pipeline_manager.add_pipeline(
+        "debug_lines_overlay",
+        &overlay_desc,
+        vec![],
+        &device,
+        &asset_manager,
+        &resource_manager,
+    );
+}
@@ -0,0 +1,83 @@
+use legion::prelude::Resources;
+use log::warn;
+
+use crate::{
+    graphics::{resources::GPUResourceManager, DEFORMATION_FORMAT},
+    AssetManager,
+};
+
+fn uniform_buffer() -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStage::COMPUTE,
+        ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+    }
+}
+
+fn storage_buffer(binding: u32, readonly: bool) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStage::COMPUTE,
+        ty: wgpu::BindingType::StorageBuffer {
+            dynamic: false,
+            readonly,
+        },
+    }
+}
+
+fn heightfield(binding: u32, readonly: bool) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStage::COMPUTE,
+        ty: wgpu::BindingType::StorageTexture {
+            dimension: wgpu::TextureViewDimension::D2,
+            component_type: wgpu::TextureComponentType::Float,
+            format: DEFORMATION_FORMAT,
+            readonly,
+        },
+    }
+}
+
+pub fn create(resources: &Resources) {
+    let asset_manager = resources.get::<AssetManager>().unwrap();
+    let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+    let device = resources.get::<wgpu::Device>().unwrap();
+
+    // Presses the deformers into the heightfield.
+    let deformation_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        bindings: &[uniform_buffer(), heightfield(1, false)],
+        label: Some("deformation"),
+    });
+
+    // Pushes the vertices down, from the rest vertices and attributes into the mesh's own.
+    let displace_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        bindings: &[
+            uniform_buffer(),
+            storage_buffer(1, true),
+            storage_buffer(2, false),
+            storage_buffer(3, true),
+            storage_buffer(4, false),
+            heightfield(5, true),
+        ],
+        label: Some("deformation_displace"),
+    });
+
+    for (name, layout) in [
+        ("deformation", &deformation_layout),
+        ("deformation_displace", &displace_layout),
+    ]
+    .iter()
+    {
+        if let Err(error) = resource_manager.add_compute_pipeline(
+            *name,
+            &format!("{}.shader", name),
+            &[layout],
+            &device,
+            &asset_manager,
+        ) {
+            warn!("{}, surfaces won't be deformed.", error);
+        }
+    }
+    resource_manager.add_bind_group_layout("deformation", deformation_layout);
+    resource_manager.add_bind_group_layout("deformation_displace", displace_layout);
+}
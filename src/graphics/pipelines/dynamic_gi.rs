@@ -0,0 +1,87 @@
+use legion::prelude::Resources;
+use log::warn;
+use std::sync::Arc;
+
+use crate::{
+    graphics::{
+        dynamic_gi::GI_PROBE_RESOLUTION,
+        renderer::HDR_FORMAT,
+        resources::{GPUResourceManager, RenderTarget},
+        GpuCapabilities,
+    },
+    AssetManager,
+};
+
+/// The cube map the dynamic GI probes are rendered into, one probe at a time.
+pub(crate) struct DynamicGIResources {
+    pub cube: Arc<RenderTarget>,
+}
+
+pub fn create(resources: &mut Resources) {
+    let dynamic_gi_resources = {
+        let asset_manager = resources.get::<AssetManager>().unwrap();
+        let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+        let device = resources.get::<wgpu::Device>().unwrap();
+
+        // Turns a probe's cube map into the six sides of it's ambient cube.
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::SampledTexture {
+                        multisampled: false,
+                        component_type: wgpu::TextureComponentType::Float,
+                        dimension: wgpu::TextureViewDimension::Cube,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::StorageBuffer {
+                        dynamic: false,
+                        readonly: false,
+                    },
+                },
+            ],
+            label: Some("dynamic_gi"),
+        });
+        if !resources.get::<GpuCapabilities>().unwrap().compute {
+            warn!("Compute shaders aren't supported, dynamic GI is turned off.");
+        } else if let Err(error) = resource_manager.add_compute_pipeline(
+            "dynamic_gi",
+            "dynamic_gi.shader",
+            &[&layout],
+            &device,
+            &asset_manager,
+        ) {
+            warn!("{}, dynamic GI is turned off.", error);
+        }
+        resource_manager.add_bind_group_layout("dynamic_gi", layout);
+
+        let mut cube = RenderTarget::new(
+            &device,
+            GI_PROBE_RESOLUTION as f32,
+            GI_PROBE_RESOLUTION as f32,
+            6,
+            1,
+            HDR_FORMAT,
+            wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+        );
+        cube.with_depth(&device);
+        DynamicGIResources {
+            cube: Arc::new(cube),
+        }
+    };
+    resources.insert(dynamic_gi_resources);
+}
@@ -1,12 +1,70 @@
+use legion::prelude::Resources;
+
 use crate::{
     graphics::{
         pipeline::VertexStateBuilder,
+        pipeline_manager::{PipelineDesc, PipelineManager},
         resources::{GPUResourceManager, RenderTarget},
         SimplePipeline, SimplePipelineDesc,
     },
     AssetManager,
 };
 
+/// Creates the pipeline used to project an equirectangular texture onto the faces of an environment probe.
+/// Faces are rendered stacked on top of each other, X+ X- Y+ Y- Z+ Z-.
+pub fn create(resources: &Resources, format: wgpu::TextureFormat) {
+    let asset_manager = resources.get_mut::<AssetManager>().unwrap();
+    let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+    let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+    let device = resources.get::<wgpu::Device>().unwrap();
+
+    // Only one pipeline is needed no matter how many probes there are.
+    if pipeline_manager.get("equirectangular", None).is_some() {
+        return;
+    }
+
+    let equirectangular_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        multisampled: false,
+                        component_type: wgpu::TextureComponentType::Float,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                },
+            ],
+            label: None,
+        });
+    resource_manager.add_bind_group_layout("equirectangular", equirectangular_bind_group_layout);
+
+    let mut equirectangular_desc = PipelineDesc::default();
+    equirectangular_desc.shader = "hdr_to_cubemap.shader".to_string();
+    equirectangular_desc.color_state.format = format;
+
+    equirectangular_desc.layouts = vec!["equirectangular".to_string()];
+    equirectangular_desc.cull_mode = wgpu::CullMode::None;
+    equirectangular_desc
+        .vertex_state
+        .set_index_format(wgpu::IndexFormat::Uint16);
+
+    pipeline_manager.add_pipeline(
+        "equirectangular",
+        &equirectangular_desc,
+        vec![],
+        &device,
+        &asset_manager,
+        &resource_manager,
+    );
+}
+
 pub struct CubeProjectionPipeline {
     texture: String,
     size: f32,
@@ -0,0 +1,57 @@
+use legion::prelude::Resources;
+
+use crate::{
+    graphics::{
+        pipeline_manager::{PipelineDesc, PipelineManager},
+        renderer::{DEPTH_FORMAT, HDR_FORMAT},
+        resources::GPUResourceManager,
+        BlendMode, RenderSettings,
+    },
+    AssetManager,
+};
+
+pub fn create(resources: &Resources) {
+    let asset_manager = resources.get::<AssetManager>().unwrap();
+    let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+    let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+    let device = resources.get::<wgpu::Device>().unwrap();
+
+    let grid_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        bindings: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+            ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+        }],
+        label: Some("grid"),
+    });
+    resource_manager.add_bind_group_layout("grid", grid_layout);
+
+    let mut grid_desc = PipelineDesc::default();
+    grid_desc.shader = "grid.shader".to_string();
+    grid_desc.color_state.format = HDR_FORMAT;
+    grid_desc.sample_count = resources.get::<RenderSettings>().unwrap().sample_count;
+    grid_desc.set_blend_mode(BlendMode::Alpha);
+    grid_desc.cull_mode = wgpu::CullMode::None;
+    // The fragment shader writes the plane's depth, so the scene hides the grid.
+    grid_desc.depth_state = Some(wgpu::DepthStencilStateDescriptor {
+        format: DEPTH_FORMAT,
+        depth_write_enabled: false,
+        depth_compare: wgpu::CompareFunction::LessEqual,
+        stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+        stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+        stencil_read_mask: 0,
+        stencil_write_mask: 0,
+    });
+    grid_desc.layouts = vec!["globals".to_string(), "grid".to_string()];
+    grid_desc
+        .vertex_state
+        .set_index_format(wgpu::IndexFormat::Uint16);
+    pipeline_manager.add_pipeline(
+        "grid",
+        &grid_desc,
+        vec![],
+        &device,
+        &asset_manager,
+        &resource_manager,
+    );
+}
@@ -0,0 +1,57 @@
+use legion::prelude::Resources;
+use log::{error, warn};
+
+use crate::{
+    graphics::{material::shader::compile_compute, resources::GPUResourceManager, GpuCapabilities},
+    AssetManager,
+};
+
+/// The compute pipeline that sorts point and spot lights into clusters.
+/// It's None when the shader failed to compile or the GPU can't run compute shaders, then no
+/// point or spot lights are drawn.
+pub(crate) struct LightCulling {
+    pub pipeline: Option<wgpu::ComputePipeline>,
+}
+
+pub fn create(resources: &mut Resources) {
+    if !resources.get::<GpuCapabilities>().unwrap().compute {
+        warn!("Compute shaders aren't supported, point and spot lights are turned off.");
+        resources.insert(LightCulling { pipeline: None });
+        return;
+    }
+
+    let pipeline = {
+        let asset_manager = resources.get::<AssetManager>().unwrap();
+        let resource_manager = resources.get::<GPUResourceManager>().unwrap();
+        let device = resources.get::<wgpu::Device>().unwrap();
+
+        match compile_compute(
+            &device,
+            asset_manager.vfs(),
+            &asset_manager.core_shader_path(),
+            "light_culling_comp.glsl",
+        ) {
+            Ok(module) => {
+                let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    bind_group_layouts: &[resource_manager
+                        .get_bind_group_layout("light_culling")
+                        .unwrap()],
+                });
+                Some(
+                    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                        layout: &layout,
+                        compute_stage: wgpu::ProgrammableStageDescriptor {
+                            module: &module,
+                            entry_point: "main",
+                        },
+                    }),
+                )
+            }
+            Err(err) => {
+                error!("{}", err);
+                None
+            }
+        }
+    };
+    resources.insert(LightCulling { pipeline });
+}
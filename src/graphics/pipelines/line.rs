@@ -1,6 +1,9 @@
 use crate::graphics::{
-    mesh::MeshTangentLine, pipeline::VertexStateBuilder, renderer::DEPTH_FORMAT,
-    resources::GPUResourceManager, SimplePipeline, SimplePipelineDesc,
+    mesh::MeshTangentLine,
+    pipeline::VertexStateBuilder,
+    renderer::{DEPTH_FORMAT, HDR_FORMAT},
+    resources::GPUResourceManager,
+    SimplePipeline, SimplePipelineDesc,
 };
 
 #[derive(Debug)]
@@ -8,8 +11,17 @@ pub struct LinePipeline {}
 
 impl SimplePipeline for LinePipeline {}
 
-#[derive(Debug, Default)]
-pub struct LinePipelineDesc;
+#[derive(Debug)]
+pub struct LinePipelineDesc {
+    sample_count: u32,
+}
+
+impl LinePipelineDesc {
+    /// `sample_count` has to match the frame it draws into.
+    pub fn new(sample_count: u32) -> Self {
+        Self { sample_count }
+    }
+}
 
 impl SimplePipelineDesc for LinePipelineDesc {
     type Pipeline = LinePipeline;
@@ -43,10 +55,10 @@ impl SimplePipelineDesc for LinePipelineDesc {
     }
     fn color_states_desc(
         &self,
-        sc_desc: &wgpu::SwapChainDescriptor,
+        _sc_desc: &wgpu::SwapChainDescriptor,
     ) -> Vec<wgpu::ColorStateDescriptor> {
         vec![wgpu::ColorStateDescriptor {
-            format: sc_desc.format,
+            format: HDR_FORMAT,
             color_blend: wgpu::BlendDescriptor::REPLACE,
             alpha_blend: wgpu::BlendDescriptor::REPLACE,
             write_mask: wgpu::ColorWrite::ALL,
@@ -77,6 +89,10 @@ impl SimplePipelineDesc for LinePipelineDesc {
         vertex_state_builder
     }
 
+    fn create_samplers(&self, _device: &wgpu::Device) -> u32 {
+        self.sample_count
+    }
+
     fn build(
         self,
         _device: &wgpu::Device,
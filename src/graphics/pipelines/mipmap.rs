@@ -4,11 +4,12 @@ use crate::{
     graphics::{
         pipeline_manager::{PipelineDesc, PipelineManager},
         resources::GPUResourceManager,
+        FramesInFlight,
     },
     AssetManager,
 };
 
-// mipmaps always run pretty much right away.
+// Mipmaps run with the rest of the frame, after the work that drew the original texture.
 pub fn create(
     resources: &Resources,
     original_texture: &wgpu::Texture,
@@ -161,8 +162,10 @@ pub fn create(
         }
     }
 
-    let queue = resources.get::<wgpu::Queue>().unwrap();
-    queue.submit(Some(encoder.finish()));
+    resources
+        .get::<FramesInFlight>()
+        .unwrap()
+        .submit(Some(encoder.finish()));
 
     // device.poll(wgpu::Maintain::Wait);
 
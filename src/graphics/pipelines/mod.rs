@@ -6,11 +6,49 @@ pub(crate) use unlit::UnlitPipelineDesc;
 
 pub mod pbr;
 
+pub mod shadow;
+
 mod line;
 pub(crate) use line::LinePipelineDesc;
 
 pub mod mipmap;
 
+pub mod text;
+
+pub(crate) mod light_culling;
+
+pub mod post_process;
+
+pub mod paint;
+
+pub mod noise;
+
+pub(crate) mod clouds;
+
+pub(crate) mod atmosphere;
+
+pub(crate) mod debug;
+
+pub(crate) mod grid;
+
+pub(crate) mod sprite;
+
+pub(crate) mod particle;
+
+pub(crate) mod deformation;
+
+pub(crate) mod ocean;
+
+pub(crate) mod water;
+
+pub(crate) mod contact_shadows;
+
+pub(crate) mod ssgi;
+
+pub(crate) mod dynamic_gi;
+
+pub(crate) mod texture_viewer;
+
 pub(crate) mod brdf;
 
 pub(crate) mod specular2;
@@ -62,30 +100,11 @@ impl Default for DirectionalLight {
     }
 }
 
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct PointLight {
-    pub position: Vec4,
-    pub color: Vec4,
-    pub attenuation: Vec4,
-}
-
-impl Default for PointLight {
-    fn default() -> Self {
-        Self {
-            attenuation: Vec4::zeros(),
-            position: Vec4::zeros(),
-            color: Vec4::zeros(),
-        }
-    }
-}
-
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct LightingUniform {
     pub light_num: Vec4,
     pub directional_lights: [DirectionalLight; MAX_LIGHTS / 2],
-    pub point_lights: [PointLight; MAX_LIGHTS / 2],
 }
 
 impl Default for LightingUniform {
@@ -99,13 +118,6 @@ impl Default for LightingUniform {
                 DirectionalLight::default(),
                 DirectionalLight::default(),
             ],
-            point_lights: [
-                PointLight::default(),
-                PointLight::default(),
-                PointLight::default(),
-                PointLight::default(),
-                PointLight::default(),
-            ],
         }
     }
 }
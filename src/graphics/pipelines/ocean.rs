@@ -0,0 +1,328 @@
+use legion::prelude::Resources;
+use log::warn;
+
+use crate::{
+    graphics::{
+        ocean::OceanUniform,
+        pipeline_manager::{PipelineDesc, PipelineManager},
+        renderer::{DEPTH_FORMAT, HDR_FORMAT},
+        resources::GPUResourceManager,
+        RenderSettings, OCEAN_FFT_SIZE,
+    },
+    AssetManager,
+};
+
+// The spectrum and the displacement need the precision, the normals and foam are sampled.
+const SPECTRUM_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+pub(crate) const DISPLACEMENT_BYTES_PER_PIXEL: u32 = 16;
+
+/// The wave maps the ocean's compute shaders write every frame and the bind groups to run them.
+pub(crate) struct OceanResources {
+    pub uniform: wgpu::Buffer,
+    // The starting spectrum, uploaded again when the wind changes.
+    pub initial_spectrum: wgpu::Buffer,
+    pub displacement: wgpu::Texture,
+    pub spectrum_bind_group: wgpu::BindGroup,
+    // Rows first, then columns.
+    pub fft_bind_groups: [wgpu::BindGroup; 2],
+    pub maps_bind_group: wgpu::BindGroup,
+    pub bind_group: wgpu::BindGroup,
+    // Only used through the bind groups.
+    #[allow(dead_code)]
+    textures: Vec<wgpu::Texture>,
+    #[allow(dead_code)]
+    views: Vec<wgpu::TextureView>,
+    #[allow(dead_code)]
+    fft_uniforms: Vec<wgpu::Buffer>,
+    #[allow(dead_code)]
+    sampler: wgpu::Sampler,
+}
+
+fn uniform_buffer(binding: u32, visibility: wgpu::ShaderStage) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+    }
+}
+
+fn storage_texture(
+    binding: u32,
+    format: wgpu::TextureFormat,
+    readonly: bool,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStage::COMPUTE,
+        ty: wgpu::BindingType::StorageTexture {
+            dimension: wgpu::TextureViewDimension::D2,
+            component_type: wgpu::TextureComponentType::Float,
+            format,
+            readonly,
+        },
+    }
+}
+
+fn sampled_texture(binding: u32, visibility: wgpu::ShaderStage) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::SampledTexture {
+            component_type: wgpu::TextureComponentType::Float,
+            multisampled: false,
+            dimension: wgpu::TextureViewDimension::D2,
+        },
+    }
+}
+
+fn create_map(device: &wgpu::Device, format: wgpu::TextureFormat, label: &str) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: OCEAN_FFT_SIZE as u32,
+            height: OCEAN_FFT_SIZE as u32,
+            depth: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsage::STORAGE
+            | wgpu::TextureUsage::SAMPLED
+            | wgpu::TextureUsage::COPY_SRC,
+        label: Some(label),
+    })
+}
+
+fn bind_buffer(binding: u32, buffer: &wgpu::Buffer) -> wgpu::Binding<'_> {
+    wgpu::Binding {
+        binding,
+        resource: wgpu::BindingResource::Buffer(buffer.slice(..)),
+    }
+}
+
+fn bind_view(binding: u32, view: &wgpu::TextureView) -> wgpu::Binding<'_> {
+    wgpu::Binding {
+        binding,
+        resource: wgpu::BindingResource::TextureView(view),
+    }
+}
+
+pub fn create(resources: &mut Resources) {
+    let ocean_resources = {
+        let asset_manager = resources.get::<AssetManager>().unwrap();
+        let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+        let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+        let device = resources.get::<wgpu::Device>().unwrap();
+        let compute = wgpu::ShaderStage::COMPUTE;
+
+        // Animates the spectrum to the current time.
+        let spectrum_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                uniform_buffer(0, compute),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: compute,
+                    ty: wgpu::BindingType::StorageBuffer {
+                        dynamic: false,
+                        readonly: true,
+                    },
+                },
+                storage_texture(2, SPECTRUM_FORMAT, false),
+                storage_texture(3, SPECTRUM_FORMAT, false),
+            ],
+            label: Some("ocean_spectrum"),
+        });
+        // Turns the spectrum into waves in place, one row or column per workgroup.
+        let fft_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                uniform_buffer(0, compute),
+                storage_texture(1, SPECTRUM_FORMAT, false),
+                storage_texture(2, SPECTRUM_FORMAT, false),
+            ],
+            label: Some("ocean_fft"),
+        });
+        // Writes the displacement map and the normals with foam, last frame's foam fades out.
+        let maps_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                uniform_buffer(0, compute),
+                storage_texture(1, SPECTRUM_FORMAT, true),
+                storage_texture(2, SPECTRUM_FORMAT, true),
+                storage_texture(3, SPECTRUM_FORMAT, false),
+                storage_texture(4, NORMAL_FORMAT, false),
+            ],
+            label: Some("ocean_maps"),
+        });
+        for (name, layout) in [
+            ("ocean_spectrum", &spectrum_layout),
+            ("ocean_fft", &fft_layout),
+            ("ocean_maps", &maps_layout),
+        ]
+        .iter()
+        {
+            if let Err(error) = resource_manager.add_compute_pipeline(
+                *name,
+                &format!("{}.shader", name),
+                &[layout],
+                &device,
+                &asset_manager,
+            ) {
+                warn!("{}, the ocean won't move.", error);
+            }
+        }
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                uniform_buffer(0, wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT),
+                sampled_texture(1, wgpu::ShaderStage::VERTEX),
+                sampled_texture(2, wgpu::ShaderStage::FRAGMENT),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                },
+            ],
+            label: Some("ocean"),
+        });
+
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("ocean"),
+            size: std::mem::size_of::<OceanUniform>() as u64,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+        let initial_spectrum = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("ocean_initial_spectrum"),
+            size: (OCEAN_FFT_SIZE * OCEAN_FFT_SIZE * 16) as u64,
+            usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+        });
+        // Each texel holds four complex numbers, two in each texture.
+        let spectrum_a = create_map(&device, SPECTRUM_FORMAT, "ocean_spectrum_a");
+        let spectrum_b = create_map(&device, SPECTRUM_FORMAT, "ocean_spectrum_b");
+        let displacement = create_map(&device, SPECTRUM_FORMAT, "ocean_displacement");
+        let normals = create_map(&device, NORMAL_FORMAT, "ocean_normals");
+        let spectrum_a_view = spectrum_a.create_default_view();
+        let spectrum_b_view = spectrum_b.create_default_view();
+        let displacement_view = displacement.create_default_view();
+        let normals_view = normals.create_default_view();
+
+        let spectrum_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("ocean_spectrum"),
+            layout: &spectrum_layout,
+            bindings: &[
+                bind_buffer(0, &uniform),
+                bind_buffer(1, &initial_spectrum),
+                bind_view(2, &spectrum_a_view),
+                bind_view(3, &spectrum_b_view),
+            ],
+        });
+        let fft_uniforms: Vec<wgpu::Buffer> = [0.0f32, 1.0]
+            .iter()
+            .map(|direction| {
+                let data = [*direction, 0.0, 0.0, 0.0];
+                device.create_buffer_with_data(
+                    bytemuck::cast_slice(&data),
+                    wgpu::BufferUsage::UNIFORM,
+                )
+            })
+            .collect();
+        let fft_bind_group = |uniform: &wgpu::Buffer| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("ocean_fft"),
+                layout: &fft_layout,
+                bindings: &[
+                    bind_buffer(0, uniform),
+                    bind_view(1, &spectrum_a_view),
+                    bind_view(2, &spectrum_b_view),
+                ],
+            })
+        };
+        let fft_bind_groups = [
+            fft_bind_group(&fft_uniforms[0]),
+            fft_bind_group(&fft_uniforms[1]),
+        ];
+        let maps_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("ocean_maps"),
+            layout: &maps_layout,
+            bindings: &[
+                bind_buffer(0, &uniform),
+                bind_view(1, &spectrum_a_view),
+                bind_view(2, &spectrum_b_view),
+                bind_view(3, &displacement_view),
+                bind_view(4, &normals_view),
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: -100.0,
+            lod_max_clamp: 100.0,
+            compare: wgpu::CompareFunction::Undefined,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("ocean"),
+            layout: &layout,
+            bindings: &[
+                bind_buffer(0, &uniform),
+                bind_view(1, &displacement_view),
+                bind_view(2, &normals_view),
+                wgpu::Binding {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+        resource_manager.add_bind_group_layout("ocean", layout);
+
+        // The grid is projected from the screen in the vertex shader, there's no vertex buffer.
+        let mut desc = PipelineDesc::default();
+        desc.shader = "ocean.shader".to_string();
+        desc.color_state.format = HDR_FORMAT;
+        desc.sample_count = resources.get::<RenderSettings>().unwrap().sample_count;
+        desc.cull_mode = wgpu::CullMode::None;
+        desc.depth_state = Some(wgpu::DepthStencilStateDescriptor {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+            stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+            stencil_read_mask: 0,
+            stencil_write_mask: 0,
+        });
+        desc.layouts = vec!["ocean".to_string(), "globals".to_string()];
+        desc.vertex_state
+            .set_index_format(wgpu::IndexFormat::Uint16);
+        pipeline_manager.add_pipeline(
+            "ocean",
+            &desc,
+            vec![],
+            &device,
+            &asset_manager,
+            &resource_manager,
+        );
+
+        OceanResources {
+            uniform,
+            initial_spectrum,
+            displacement,
+            spectrum_bind_group,
+            fft_bind_groups,
+            maps_bind_group,
+            bind_group,
+            textures: vec![spectrum_a, spectrum_b, normals],
+            views: vec![
+                spectrum_a_view,
+                spectrum_b_view,
+                displacement_view,
+                normals_view,
+            ],
+            fft_uniforms,
+            sampler,
+        }
+    };
+    resources.insert(ocean_resources);
+}
@@ -0,0 +1,162 @@
+use bytemuck::{Pod, Zeroable};
+use legion::prelude::Resources;
+use nalgebra_glm::{Mat4, Vec4};
+
+use crate::{
+    graphics::{
+        mesh::MeshVertexData,
+        pipeline_manager::{PipelineDesc, PipelineManager},
+        resources::GPUResourceManager,
+        BlendMode,
+    },
+    scene::components::PaintBrush,
+    AssetManager,
+};
+
+/// Paint textures are always sRGB like the main textures they start out as.
+pub const PAINT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PaintUniform {
+    pub world: Mat4,
+    // (position, radius)
+    pub brush: Vec4,
+    pub color: Vec4,
+    // (direction, hardness)
+    pub direction: Vec4,
+    // (uses direction, unused..)
+    pub info: Vec4,
+}
+
+unsafe impl Zeroable for PaintUniform {}
+unsafe impl Pod for PaintUniform {}
+
+impl PaintUniform {
+    pub fn new(world: Mat4, brush: &PaintBrush) -> Self {
+        let direction = brush
+            .direction
+            .map(|direction| direction.normalize())
+            .unwrap_or_else(nalgebra_glm::Vec3::zeros);
+        Self {
+            world,
+            brush: Vec4::new(
+                brush.position.x,
+                brush.position.y,
+                brush.position.z,
+                brush.radius.max(0.0001),
+            ),
+            color: brush.color,
+            direction: Vec4::new(
+                direction.x,
+                direction.y,
+                direction.z,
+                brush.hardness.max(0.0).min(1.0),
+            ),
+            info: Vec4::new(
+                if brush.direction.is_some() { 1.0 } else { 0.0 },
+                0.0,
+                0.0,
+                0.0,
+            ),
+        }
+    }
+}
+
+/// The sampler used to copy main textures into new paint textures.
+pub(crate) struct PaintSampler(pub wgpu::Sampler);
+
+pub fn create(resources: &mut Resources) {
+    let sampler = {
+        let asset_manager = resources.get::<AssetManager>().unwrap();
+        let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+        let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+        let device = resources.get::<wgpu::Device>().unwrap();
+
+        let paint_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+            }],
+            label: Some("paint"),
+        });
+        resource_manager.add_bind_group_layout("paint", paint_layout);
+
+        let copy_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: false,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                },
+            ],
+            label: Some("paint_copy"),
+        });
+        resource_manager.add_bind_group_layout("paint_copy", copy_layout);
+
+        // Unwraps the mesh into it's UV space and blends the brush on top of what's there.
+        let mut paint_desc = PipelineDesc::default();
+        paint_desc.shader = "paint.shader".to_string();
+        paint_desc.color_state.format = PAINT_FORMAT;
+        paint_desc.set_blend_mode(BlendMode::Alpha);
+        // UV islands can be mirrored, so both sides are drawn.
+        paint_desc.cull_mode = wgpu::CullMode::None;
+        paint_desc.layouts = vec!["paint".to_string()];
+        let vertex_size = std::mem::size_of::<MeshVertexData>();
+        paint_desc
+            .vertex_state
+            .set_index_format(wgpu::IndexFormat::Uint32)
+            .new_buffer_descriptor(
+                vertex_size as wgpu::BufferAddress,
+                wgpu::InputStepMode::Vertex,
+                wgpu::vertex_attr_array![0 => Float3, 1 => Float3, 2 => Float2, 3 => Float4]
+                    .to_vec(),
+            );
+        pipeline_manager.add_pipeline(
+            "paint",
+            &paint_desc,
+            vec![],
+            &device,
+            &asset_manager,
+            &resource_manager,
+        );
+
+        let mut copy_desc = PipelineDesc::default();
+        copy_desc.shader = "paint_copy.shader".to_string();
+        copy_desc.color_state.format = PAINT_FORMAT;
+        copy_desc.cull_mode = wgpu::CullMode::None;
+        copy_desc.layouts = vec!["paint_copy".to_string()];
+        pipeline_manager.add_pipeline(
+            "paint_copy",
+            &copy_desc,
+            vec![],
+            &device,
+            &asset_manager,
+            &resource_manager,
+        );
+
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: -100.0,
+            lod_max_clamp: 100.0,
+            compare: wgpu::CompareFunction::Undefined,
+        })
+    };
+    resources.insert(PaintSampler(sampler));
+}
@@ -0,0 +1,130 @@
+use legion::prelude::Resources;
+use log::warn;
+
+use crate::{
+    graphics::{
+        particles::Particle,
+        pipeline_manager::{PipelineDesc, PipelineManager, PipelineVariant},
+        renderer::{DEPTH_FORMAT, HDR_FORMAT},
+        resources::GPUResourceManager,
+        BlendMode, RenderSettings,
+    },
+    AssetManager,
+};
+
+pub fn create(resources: &Resources) {
+    let mut asset_manager = resources.get_mut::<AssetManager>().unwrap();
+    let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+    let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+    let device = resources.get::<wgpu::Device>().unwrap();
+
+    // Simulation, the settings uniform and the particles.
+    let simulation_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        bindings: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::COMPUTE,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStage::COMPUTE,
+                ty: wgpu::BindingType::StorageBuffer {
+                    dynamic: false,
+                    readonly: false,
+                },
+            },
+        ],
+        label: Some("particle_simulation"),
+    });
+    if let Err(error) = resource_manager.add_compute_pipeline(
+        "particle_simulation",
+        "particle_simulation.shader",
+        &[&simulation_layout],
+        &device,
+        &asset_manager,
+    ) {
+        warn!("{}, particles will be simulated on the CPU.", error);
+    }
+    resource_manager.add_bind_group_layout("particle_simulation", simulation_layout);
+
+    let particle_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        bindings: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Sampler { comparison: false },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    component_type: wgpu::TextureComponentType::Float,
+                    dimension: wgpu::TextureViewDimension::D2,
+                },
+            },
+        ],
+        label: Some("particle"),
+    });
+    resource_manager.add_bind_group_layout("particle", particle_layout);
+
+    let mut particle_desc = PipelineDesc::default();
+    particle_desc.shader = "particle.shader".to_string();
+    particle_desc.color_state.format = HDR_FORMAT;
+    // Drawn inside the pbr pass so it has to match the frame.
+    let sample_count = resources.get::<RenderSettings>().unwrap().sample_count;
+    particle_desc.sample_count = sample_count;
+    particle_desc.set_blend_mode(BlendMode::Alpha);
+    particle_desc.cull_mode = wgpu::CullMode::None;
+    particle_desc.primitive_topology = wgpu::PrimitiveTopology::TriangleStrip;
+    // Tested against the scene but they don't write depth, they're sorted like blended meshes.
+    particle_desc.depth_state = Some(wgpu::DepthStencilStateDescriptor {
+        format: DEPTH_FORMAT,
+        depth_write_enabled: false,
+        depth_compare: wgpu::CompareFunction::LessEqual,
+        stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+        stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+        stencil_read_mask: 0,
+        stencil_write_mask: 0,
+    });
+    particle_desc.layouts = vec!["globals".to_string(), "particle".to_string()];
+    particle_desc
+        .vertex_state
+        .set_index_format(wgpu::IndexFormat::Uint16)
+        .new_buffer_descriptor(
+            std::mem::size_of::<Particle>() as wgpu::BufferAddress,
+            wgpu::InputStepMode::Instance,
+            wgpu::vertex_attr_array![0 => Float4, 1 => Float4].to_vec(),
+        );
+    pipeline_manager.add_pipeline(
+        "particle",
+        &particle_desc,
+        vec![],
+        &device,
+        &asset_manager,
+        &resource_manager,
+    );
+
+    // Probes render single sampled.
+    if sample_count > 1 {
+        let single_sampled = PipelineVariant {
+            single_sampled: true,
+            ..Default::default()
+        };
+        pipeline_manager
+            .add_variant(
+                "particle",
+                &single_sampled,
+                &device,
+                &mut asset_manager,
+                &resource_manager,
+            )
+            .unwrap();
+    }
+}
@@ -2,24 +2,49 @@ use legion::prelude::Resources;
 
 use crate::{
     graphics::{
-        mesh::MeshVertexData,
-        pipeline_manager::{PipelineDesc, PipelineManager},
-        renderer::DEPTH_FORMAT,
+        instancing::InstanceData,
+        mesh::{MeshVertexData, SkinVertexData},
+        pipeline_manager::{PipelineDesc, PipelineManager, PipelineVariant},
+        renderer::{DEPTH_FORMAT, HDR_FORMAT},
         resources::GPUResourceManager,
+        material::ShaderDefines,
+        BlendMode, RenderSettings,
     },
     AssetManager,
 };
 
+// Every pipeline meshes are drawn with.
+const PBR_PIPELINES: [&str; 9] = [
+    "pbr",
+    "pbr_masked",
+    "pbr_double_sided",
+    "pbr_masked_double_sided",
+    "pbr_blended",
+    "pbr_blended_double_sided",
+    "pbr_instanced",
+    "pbr_instanced_masked",
+    "pbr_skinned",
+];
+
 pub fn create(resources: &Resources) {
-    let asset_manager = resources.get_mut::<AssetManager>().unwrap();
+    let mut asset_manager = resources.get_mut::<AssetManager>().unwrap();
     let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
     let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
     let device = resources.get::<wgpu::Device>().unwrap();
-    let sc_desc = resources.get::<wgpu::SwapChainDescriptor>().unwrap();
 
     let mut pbr_desc = PipelineDesc::default();
     pbr_desc.shader = "pbr.shader".to_string();
-    pbr_desc.color_state.format = sc_desc.format;
+    pbr_desc.color_state.format = HDR_FORMAT;
+    // Every variant below is cloned from this one, so they all match the frame.
+    let settings = resources.get::<RenderSettings>().unwrap();
+    pbr_desc.sample_count = settings.sample_count;
+    // The reduced path skips reflection probes and soft shadows.
+    if settings.reduced() {
+        pbr_desc.shader_defines = ShaderDefines::new().with_flag("REDUCED");
+    }
+    asset_manager
+        .load_shader_variant(&device, &pbr_desc.shader, &pbr_desc.compiled_defines())
+        .unwrap();
     pbr_desc.depth_state = Some(wgpu::DepthStencilStateDescriptor {
         format: DEPTH_FORMAT,
         depth_write_enabled: true,
@@ -70,6 +95,24 @@ pub fn create(resources: &Resources) {
                     dimension: wgpu::TextureViewDimension::D2,
                 },
             },
+            wgpu::BindGroupLayoutEntry {
+                binding: 5,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    component_type: wgpu::TextureComponentType::Float,
+                    dimension: wgpu::TextureViewDimension::D2,
+                },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 6,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    component_type: wgpu::TextureComponentType::Float,
+                    dimension: wgpu::TextureViewDimension::D2,
+                },
+            },
         ],
         label: Some("pbr_material"),
     });
@@ -104,6 +147,48 @@ pub fn create(resources: &Resources) {
                     dimension: wgpu::TextureViewDimension::D2,
                 },
             },
+            // Reflection probes, see `MAX_REFLECTION_PROBES`.
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    component_type: wgpu::TextureComponentType::Float,
+                    dimension: wgpu::TextureViewDimension::Cube,
+                },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    component_type: wgpu::TextureComponentType::Float,
+                    dimension: wgpu::TextureViewDimension::Cube,
+                },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 5,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    component_type: wgpu::TextureComponentType::Float,
+                    dimension: wgpu::TextureViewDimension::Cube,
+                },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 6,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    component_type: wgpu::TextureComponentType::Float,
+                    dimension: wgpu::TextureViewDimension::Cube,
+                },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 7,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+            },
         ],
         label: Some("pbr_probe_material"),
     });
@@ -130,9 +215,164 @@ pub fn create(resources: &Resources) {
     pipeline_manager.add_pipeline(
         "pbr",
         &pbr_desc,
-        vec!["globals", "skybox"],
+        vec![],
         &device,
         &asset_manager,
         &resource_manager,
     );
+
+    // Alpha masked materials (foliage, fences, etc) get smooth edges from alpha to coverage,
+    // but that only does something when we have more than one sample.
+    let mut pbr_masked_desc = pbr_desc.clone();
+    pbr_masked_desc.alpha_to_coverage_enabled = pbr_masked_desc.sample_count > 1;
+    pipeline_manager.add_pipeline(
+        "pbr_masked",
+        &pbr_masked_desc,
+        vec![],
+        &device,
+        &asset_manager,
+        &resource_manager,
+    );
+
+    // Double sided materials draw their back faces too, the shader flips the normal for them.
+    let mut pbr_double_sided_desc = pbr_desc.clone();
+    pbr_double_sided_desc.cull_mode = wgpu::CullMode::None;
+    pipeline_manager.add_pipeline(
+        "pbr_double_sided",
+        &pbr_double_sided_desc,
+        vec![],
+        &device,
+        &asset_manager,
+        &resource_manager,
+    );
+
+    let mut pbr_masked_double_sided_desc = pbr_masked_desc.clone();
+    pbr_masked_double_sided_desc.cull_mode = wgpu::CullMode::None;
+    pipeline_manager.add_pipeline(
+        "pbr_masked_double_sided",
+        &pbr_masked_double_sided_desc,
+        vec![],
+        &device,
+        &asset_manager,
+        &resource_manager,
+    );
+
+    // Alpha blended materials are sorted back to front and don't write depth,
+    // that way everything behind them still gets drawn.
+    let mut pbr_blended_desc = pbr_desc.clone();
+    pbr_blended_desc.set_blend_mode(BlendMode::Alpha);
+    if let Some(depth_state) = pbr_blended_desc.depth_state.as_mut() {
+        depth_state.depth_write_enabled = false;
+    }
+    pipeline_manager.add_pipeline(
+        "pbr_blended",
+        &pbr_blended_desc,
+        vec![],
+        &device,
+        &asset_manager,
+        &resource_manager,
+    );
+
+    let mut pbr_blended_double_sided_desc = pbr_blended_desc.clone();
+    pbr_blended_double_sided_desc.cull_mode = wgpu::CullMode::None;
+    pipeline_manager.add_pipeline(
+        "pbr_blended_double_sided",
+        &pbr_blended_double_sided_desc,
+        vec![],
+        &device,
+        &asset_manager,
+        &resource_manager,
+    );
+
+    // Instanced meshes read their world matrix and color from a second per instance buffer.
+    // Bind group 0 is still part of the layout so the material and probe bind groups line up.
+    let mut pbr_instanced_desc = pbr_desc.clone();
+    pbr_instanced_desc.shader = "pbr_instanced.shader".to_string();
+    asset_manager
+        .load_shader_variant(&device, &pbr_instanced_desc.shader, &pbr_desc.compiled_defines())
+        .unwrap();
+    let instance_size = std::mem::size_of::<InstanceData>();
+    pbr_instanced_desc.vertex_state.new_buffer_descriptor(
+        instance_size as wgpu::BufferAddress,
+        wgpu::InputStepMode::Instance,
+        wgpu::vertex_attr_array![4 => Float4, 5 => Float4, 6 => Float4, 7 => Float4, 8 => Float4]
+            .to_vec(),
+    );
+    pipeline_manager.add_pipeline(
+        "pbr_instanced",
+        &pbr_instanced_desc,
+        vec![],
+        &device,
+        &asset_manager,
+        &resource_manager,
+    );
+
+    let mut pbr_instanced_masked_desc = pbr_instanced_desc.clone();
+    pbr_instanced_masked_desc.alpha_to_coverage_enabled = pbr_masked_desc.alpha_to_coverage_enabled;
+    pipeline_manager.add_pipeline(
+        "pbr_instanced_masked",
+        &pbr_instanced_masked_desc,
+        vec![],
+        &device,
+        &asset_manager,
+        &resource_manager,
+    );
+
+    // Skinned meshes read their joints from a second buffer in the locals bind group,
+    // we are already using all 4 bind groups.
+    let skinned_locals_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        bindings: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+            },
+        ],
+        label: Some("skinned_locals"),
+    });
+    resource_manager.add_bind_group_layout("skinned_locals", skinned_locals_layout);
+    let mut pbr_skinned_desc = pbr_desc.clone();
+    pbr_skinned_desc.shader = "pbr_skinned.shader".to_string();
+    asset_manager
+        .load_shader_variant(&device, &pbr_skinned_desc.shader, &pbr_desc.compiled_defines())
+        .unwrap();
+    pbr_skinned_desc.layouts[0] = "skinned_locals".to_string();
+    let skin_vertex_size = std::mem::size_of::<SkinVertexData>();
+    pbr_skinned_desc.vertex_state.new_buffer_descriptor(
+        skin_vertex_size as wgpu::BufferAddress,
+        wgpu::InputStepMode::Vertex,
+        wgpu::vertex_attr_array![4 => Uint4, 5 => Float4].to_vec(),
+    );
+    pipeline_manager.add_pipeline(
+        "pbr_skinned",
+        &pbr_skinned_desc,
+        vec![],
+        &device,
+        &asset_manager,
+        &resource_manager,
+    );
+
+    // Probes render single sampled, material variants get their copy from `shader_variants`.
+    if settings.msaa() {
+        let single_sampled = PipelineVariant {
+            single_sampled: true,
+            ..Default::default()
+        };
+        for name in PBR_PIPELINES.iter() {
+            pipeline_manager
+                .add_variant(
+                    *name,
+                    &single_sampled,
+                    &device,
+                    &mut asset_manager,
+                    &resource_manager,
+                )
+                .unwrap();
+        }
+    }
 }
@@ -0,0 +1,144 @@
+use legion::prelude::Resources;
+use std::sync::Mutex;
+
+use crate::{
+    graphics::{
+        pipeline_manager::{PipelineDesc, PipelineManager},
+        renderer::HDR_FORMAT,
+        resources::GPUResourceManager,
+        GpuReadback,
+    },
+    AssetManager,
+};
+
+/// The sampler every post processing pass reads its textures with.
+pub(crate) struct PostProcessSampler(pub wgpu::Sampler);
+
+/// Seconds since the first frame, animates the film grain.
+pub(crate) struct PostProcessClock(pub f32);
+
+/// Width and height of the texture auto-exposure meters the frame into.
+pub(crate) const EXPOSURE_METER_SIZE: u32 = 64;
+const EXPOSURE_METER_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Float;
+pub(crate) const EXPOSURE_METER_BYTES_PER_PIXEL: u32 = 8;
+
+/// Auto-exposure's metering texture and it's state, the texture isn't a frame graph attachment
+/// since it's read back to the CPU.
+pub(crate) struct ExposureMeter {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub readback: Mutex<Option<GpuReadback>>,
+    /// The EV100 of the last reading, the exposure adapts towards it.
+    pub target: Option<f32>,
+    /// The adapted EV100, None until the first reading arrives.
+    pub ev: Option<f32>,
+}
+
+pub fn create(resources: &mut Resources) {
+    let (sampler, meter) = {
+        let asset_manager = resources.get::<AssetManager>().unwrap();
+        let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+        let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+        let device = resources.get::<wgpu::Device>().unwrap();
+        let sc_desc = resources.get::<wgpu::SwapChainDescriptor>().unwrap();
+
+        // Every pass reads a source texture, an optional second texture and the settings.
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: false,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: false,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                },
+            ],
+            label: Some("post_process"),
+        });
+        resource_manager.add_bind_group_layout("post_process", layout);
+
+        let passes = [
+            ("bloom_threshold", HDR_FORMAT),
+            ("bloom_blur", HDR_FORMAT),
+            ("tonemap", sc_desc.format),
+            ("warp", sc_desc.format),
+            ("fxaa", sc_desc.format),
+            ("sharpen", sc_desc.format),
+            ("exposure_meter", EXPOSURE_METER_FORMAT),
+        ];
+        for (name, format) in passes.iter() {
+            let mut desc = PipelineDesc::default();
+            desc.shader = format!("{}.shader", name);
+            desc.color_state.format = *format;
+            desc.cull_mode = wgpu::CullMode::None;
+            desc.layouts = vec!["post_process".to_string()];
+            pipeline_manager.add_pipeline(
+                *name,
+                &desc,
+                vec![],
+                &device,
+                &asset_manager,
+                &resource_manager,
+            );
+        }
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: EXPOSURE_METER_SIZE,
+                height: EXPOSURE_METER_SIZE,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: EXPOSURE_METER_FORMAT,
+            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+            label: Some("exposure_meter"),
+        });
+        let meter = ExposureMeter {
+            view: texture.create_default_view(),
+            texture,
+            readback: Mutex::new(None),
+            target: None,
+            ev: None,
+        };
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: -100.0,
+            lod_max_clamp: 100.0,
+            compare: wgpu::CompareFunction::Undefined,
+        });
+        (sampler, meter)
+    };
+    resources.insert(PostProcessSampler(sampler));
+    resources.insert(meter);
+    resources.insert(PostProcessClock(0.0));
+}
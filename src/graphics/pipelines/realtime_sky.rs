@@ -3,8 +3,9 @@ use legion::prelude::Resources;
 use crate::{
     graphics::{
         pipeline_manager::{PipelineDesc, PipelineManager},
-        renderer::DEPTH_FORMAT,
+        renderer::{DEPTH_FORMAT, HDR_FORMAT},
         resources::GPUResourceManager,
+        RenderSettings,
     },
     AssetManager,
 };
@@ -14,11 +15,12 @@ pub fn create(resources: &Resources) {
     let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
     let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
     let device = resources.get::<wgpu::Device>().unwrap();
-    let sc_desc = resources.get::<wgpu::SwapChainDescriptor>().unwrap();
+    let sample_count = resources.get::<RenderSettings>().unwrap().sample_count;
 
     let mut skybox_desc = PipelineDesc::default();
     skybox_desc.shader = "sky.shader".to_string();
-    skybox_desc.color_state.format = sc_desc.format;
+    skybox_desc.color_state.format = HDR_FORMAT;
+    skybox_desc.sample_count = sample_count;
     skybox_desc.depth_state = Some(wgpu::DepthStencilStateDescriptor {
         format: DEPTH_FORMAT,
         depth_write_enabled: false,
@@ -68,9 +70,23 @@ pub fn create(resources: &Resources) {
     pipeline_manager.add_pipeline(
         "realtime_skybox",
         &skybox_desc,
-        vec!["globals"],
+        vec![],
         &device,
         &asset_manager,
         &resource_manager,
     );
+
+    // Probes render single sampled.
+    if sample_count > 1 {
+        let mut probe_desc = skybox_desc.clone();
+        probe_desc.sample_count = 1;
+        pipeline_manager.add_pipeline(
+            "realtime_skybox",
+            &probe_desc,
+            vec![],
+            &device,
+            &asset_manager,
+            &resource_manager,
+        );
+    }
 }
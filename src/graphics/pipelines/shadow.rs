@@ -0,0 +1,260 @@
+use bytemuck::{Pod, Zeroable};
+use legion::prelude::Resources;
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+
+use crate::{
+    graphics::{
+        mesh::MeshVertexData,
+        pipeline_manager::{PipelineDesc, PipelineManager},
+        resources::{BindGroup, GPUResourceManager},
+    },
+    AssetManager,
+};
+
+/// The number of cascades used by the directional light's shadow map.
+pub const SHADOW_CASCADES: usize = 4;
+/// Width and height of each cascade in the shadow map.
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+/// The most spot lights that can cast shadows, they share the layer after the cascades as an
+/// atlas with a quarter of it each.
+pub const MAX_SPOT_SHADOWS: usize = 4;
+/// The cascades and the spot light atlas.
+pub const SHADOW_LAYERS: usize = SHADOW_CASCADES + 1;
+pub const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Shadow data shared with every shader through the globals bind group.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowUniform {
+    pub cascades: [Mat4; SHADOW_CASCADES],
+    // The view space distance where each cascade ends.
+    pub splits: Vec4,
+    // (depth bias, slope bias, normal bias, light index or -1 when there are no shadows)
+    pub bias: Vec4,
+    // (filter: 0 = hard, 1 = pcf, 2 = pcss, kernel size or filter samples, light size, blocker samples)
+    pub filter: Vec4,
+    pub spot_matrices: [Mat4; MAX_SPOT_SHADOWS],
+    // (depth bias, slope bias, normal bias, pcf kernel size) of each spot light.
+    pub spot_bias: [Vec4; MAX_SPOT_SHADOWS],
+}
+
+impl Default for ShadowUniform {
+    fn default() -> Self {
+        Self {
+            cascades: [Mat4::identity(); SHADOW_CASCADES],
+            splits: Vec4::zeros(),
+            bias: Vec4::new(0.0, 0.0, 0.0, -1.0),
+            filter: Vec4::zeros(),
+            spot_matrices: [Mat4::identity(); MAX_SPOT_SHADOWS],
+            spot_bias: [Vec4::zeros(); MAX_SPOT_SHADOWS],
+        }
+    }
+}
+
+unsafe impl Zeroable for ShadowUniform {}
+unsafe impl Pod for ShadowUniform {}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowCasterUniform {
+    pub view_projection: Mat4,
+}
+
+unsafe impl Zeroable for ShadowCasterUniform {}
+unsafe impl Pod for ShadowCasterUniform {}
+
+/// Splits the camera's depth range up between the cascades.
+/// Blends between uniform and logarithmic splits, `lambda` of 1.0 is fully logarithmic.
+/// Returns the far distance of each cascade.
+pub fn cascade_splits(near: f32, far: f32, lambda: f32) -> [f32; SHADOW_CASCADES] {
+    let mut splits = [0.0; SHADOW_CASCADES];
+    for (i, split) in splits.iter_mut().enumerate() {
+        let p = (i + 1) as f32 / SHADOW_CASCADES as f32;
+        let log = near * (far / near).powf(p);
+        let uniform = near + (far - near) * p;
+        *split = lambda * log + (1.0 - lambda) * uniform;
+    }
+    splits
+}
+
+/// Creates an orthographic view projection for the light that covers the given frustum corners.
+/// The cascade is fit to a sphere and snapped to texels so it doesn't shimmer when the camera moves.
+pub fn cascade_matrix(corners: &[Vec3; 8], light_direction: Vec3) -> Mat4 {
+    let center = corners
+        .iter()
+        .fold(Vec3::zeros(), |sum, corner| sum + corner)
+        / 8.0;
+    let radius = corners
+        .iter()
+        .map(|corner| (corner - center).magnitude())
+        .fold(0.0, f32::max)
+        .ceil();
+
+    let direction = light_direction.normalize();
+    let up = if direction.y.abs() > 0.99 {
+        Vec3::new(0.0, 0.0, 1.0)
+    } else {
+        Vec3::new(0.0, 1.0, 0.0)
+    };
+
+    // Move the center in whole texel steps.
+    let texel_size = (radius * 2.0) / SHADOW_MAP_SIZE as f32;
+    let light_view = nalgebra_glm::look_at_rh(&Vec3::zeros(), &-direction, &up);
+    let mut light_center = (light_view * Vec4::new(center.x, center.y, center.z, 1.0)).xyz();
+    light_center.x = (light_center.x / texel_size).floor() * texel_size;
+    light_center.y = (light_center.y / texel_size).floor() * texel_size;
+    let center = (nalgebra_glm::inverse(&light_view)
+        * Vec4::new(light_center.x, light_center.y, light_center.z, 1.0))
+    .xyz();
+
+    // Pull the light back so casters outside of the camera's view still land in the map.
+    let eye = center + direction * radius * 2.0;
+    let view = nalgebra_glm::look_at_rh(&eye, &center, &up);
+    let projection = nalgebra_glm::ortho_rh_zo(-radius, radius, -radius, radius, 0.0, radius * 4.0);
+    projection * view
+}
+
+/// A perspective view projection covering a spot light's outer cone out to it's range.
+pub fn spot_matrix(position: Vec3, direction: Vec3, outer_angle: f32, range: f32) -> Mat4 {
+    let direction = direction.normalize();
+    let up = if direction.y.abs() > 0.99 {
+        Vec3::new(0.0, 0.0, 1.0)
+    } else {
+        Vec3::new(0.0, 1.0, 0.0)
+    };
+    let view = nalgebra_glm::look_at_rh(&position, &(position + direction), &up);
+    let fov = (outer_angle.to_radians() * 2.0).max(0.01).min(3.1);
+    let far = range.max(0.1);
+    let projection = nalgebra_glm::perspective_rh_zo(1.0, fov, far * 0.001, far);
+    projection * view
+}
+
+/// The (x, y, size) of a spot light's tile in the atlas layer, in texels.
+pub fn spot_shadow_viewport(tile: usize) -> (f32, f32, f32) {
+    let size = SHADOW_MAP_SIZE as f32 * 0.5;
+    ((tile % 2) as f32 * size, (tile / 2) as f32 * size, size)
+}
+
+pub fn create(resources: &Resources) {
+    let mut asset_manager = resources.get_mut::<AssetManager>().unwrap();
+    let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+    let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+    let device = resources.get::<wgpu::Device>().unwrap();
+
+    let shadow_caster_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        bindings: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStage::VERTEX,
+            ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+        }],
+        label: Some("shadow_caster"),
+    });
+
+    // Each cascade get's it's own light matrix, followed by the spot lights.
+    for caster in 0..(SHADOW_CASCADES + MAX_SPOT_SHADOWS) as u32 {
+        let buffer = device.create_buffer_with_data(
+            bytemuck::bytes_of(&ShadowCasterUniform {
+                view_projection: Mat4::identity(),
+            }),
+            wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &shadow_caster_layout,
+            bindings: &[wgpu::Binding {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(buffer.slice(..)),
+            }],
+            label: Some("shadow_caster"),
+        });
+        resource_manager.add_multi_bind_group(
+            "shadow_caster",
+            BindGroup::new(1, bind_group),
+            caster,
+        );
+        resource_manager.add_multi_buffer("shadow_caster", buffer, caster);
+    }
+    resource_manager.add_bind_group_layout("shadow_caster", shadow_caster_layout);
+
+    let mut shadow_desc = PipelineDesc::default();
+    shadow_desc.shader = "shadow.shader".to_string();
+    shadow_desc.depth_only = true;
+    shadow_desc.depth_state = Some(wgpu::DepthStencilStateDescriptor {
+        format: SHADOW_FORMAT,
+        depth_write_enabled: true,
+        depth_compare: wgpu::CompareFunction::Less,
+        stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+        stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+        stencil_read_mask: 0,
+        stencil_write_mask: 0,
+    });
+    shadow_desc.layouts = vec!["locals".to_string(), "shadow_caster".to_string()];
+    // Render back faces into the shadow map, it hides most of the acne on lit surfaces.
+    shadow_desc.cull_mode = wgpu::CullMode::Front;
+    // Casters between the light and the near plane get flattened onto it instead of clipped.
+    shadow_desc.depth_clamp = true;
+    asset_manager
+        .load_shader_variant(
+            &device,
+            &shadow_desc.shader,
+            &shadow_desc.compiled_defines(),
+        )
+        .unwrap();
+    let vertex_size = std::mem::size_of::<MeshVertexData>();
+    shadow_desc
+        .vertex_state
+        .set_index_format(wgpu::IndexFormat::Uint32)
+        .new_buffer_descriptor(
+            vertex_size as wgpu::BufferAddress,
+            wgpu::InputStepMode::Vertex,
+            wgpu::vertex_attr_array![0 => Float3, 1 => Float3, 2 => Float2, 3 => Float4].to_vec(),
+        );
+
+    pipeline_manager.add_pipeline(
+        "shadow",
+        &shadow_desc,
+        vec![],
+        &device,
+        &asset_manager,
+        &resource_manager,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cascade_splits() {
+        let splits = cascade_splits(0.1, 100.0, 0.75);
+        assert!((splits[SHADOW_CASCADES - 1] - 100.0).abs() < 0.001);
+        for i in 1..SHADOW_CASCADES {
+            assert!(splits[i] > splits[i - 1]);
+        }
+        // Closer cascades should be a lot smaller than uniform splits would give us.
+        assert!(splits[0] < 100.0 / SHADOW_CASCADES as f32);
+    }
+
+    #[test]
+    fn test_spot_matrix() {
+        let matrix = spot_matrix(
+            Vec3::new(0.0, 5.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            30.0,
+            10.0,
+        );
+        let project = |point: Vec3| {
+            let clip = matrix * Vec4::new(point.x, point.y, point.z, 1.0);
+            clip.xyz() / clip.w
+        };
+        // Straight down the cone lands in the middle, closer points have less depth.
+        let near = project(Vec3::new(0.0, 4.0, 0.0));
+        let far = project(Vec3::new(0.0, 0.0, 0.0));
+        assert!(near.x.abs() < 0.001 && near.y.abs() < 0.001);
+        assert!(0.0 < near.z && near.z < far.z && far.z < 1.0);
+        // The edge of the outer cone is the edge of the map.
+        let edge = project(Vec3::new(5.0 * 30f32.to_radians().tan(), 0.0, 0.0));
+        assert!((edge.x.abs().max(edge.y.abs()) - 1.0).abs() < 0.001);
+
+        assert_eq!(spot_shadow_viewport(3), (1024.0, 1024.0, 1024.0));
+    }
+}
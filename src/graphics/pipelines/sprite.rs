@@ -0,0 +1,66 @@
+use legion::prelude::Resources;
+
+use crate::{
+    graphics::{
+        pipeline_manager::{PipelineDesc, PipelineManager},
+        renderer::HDR_FORMAT,
+        resources::GPUResourceManager,
+        sprite::SpriteVertex,
+        BlendMode, RenderSettings,
+    },
+    AssetManager,
+};
+
+pub fn create(resources: &Resources) {
+    let asset_manager = resources.get::<AssetManager>().unwrap();
+    let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+    let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+    let device = resources.get::<wgpu::Device>().unwrap();
+
+    let sprite_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        bindings: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Sampler { comparison: false },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    component_type: wgpu::TextureComponentType::Float,
+                    dimension: wgpu::TextureViewDimension::D2,
+                },
+            },
+        ],
+        label: Some("sprite"),
+    });
+    resource_manager.add_bind_group_layout("sprite", sprite_layout);
+
+    let mut sprite_desc = PipelineDesc::default();
+    sprite_desc.shader = "sprite.shader".to_string();
+    sprite_desc.color_state.format = HDR_FORMAT;
+    sprite_desc.sample_count = resources.get::<RenderSettings>().unwrap().sample_count;
+    sprite_desc.set_blend_mode(BlendMode::Alpha);
+    sprite_desc.cull_mode = wgpu::CullMode::None;
+    // Layers decide what's on top, so there's no depth test.
+    sprite_desc.depth_state = None;
+    sprite_desc.layouts = vec!["globals".to_string(), "sprite".to_string()];
+    sprite_desc
+        .vertex_state
+        .set_index_format(wgpu::IndexFormat::Uint16)
+        .new_buffer_descriptor(
+            std::mem::size_of::<SpriteVertex>() as wgpu::BufferAddress,
+            wgpu::InputStepMode::Vertex,
+            wgpu::vertex_attr_array![0 => Float3, 1 => Float2, 2 => Float4].to_vec(),
+        );
+    pipeline_manager.add_pipeline(
+        "sprite",
+        &sprite_desc,
+        vec![],
+        &device,
+        &asset_manager,
+        &resource_manager,
+    );
+}
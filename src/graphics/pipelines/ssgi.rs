@@ -0,0 +1,133 @@
+use legion::prelude::Resources;
+
+use crate::{
+    graphics::{
+        pipeline_manager::{PipelineDesc, PipelineManager},
+        renderer::HDR_FORMAT,
+        resources::GPUResourceManager,
+        RenderSettings,
+    },
+    AssetManager,
+};
+
+/// The sampler the SSGI passes read the frame and their history with.
+pub(crate) struct SsgiResources {
+    pub sampler: wgpu::Sampler,
+}
+
+pub fn create(resources: &mut Resources) {
+    let ssgi_resources = {
+        let asset_manager = resources.get::<AssetManager>().unwrap();
+        let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+        let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+        let device = resources.get::<wgpu::Device>().unwrap();
+        let sample_count = resources.get::<RenderSettings>().unwrap().sample_count;
+
+        // Binding 2 is the lit frame when gathering and the gathered light when compositing,
+        // binding 3 is last frame's gathered light. The depth buffer is multisampled along
+        // with the frame.
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: sample_count > 1,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: false,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: false,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                },
+            ],
+            label: Some("ssgi"),
+        });
+        resource_manager.add_bind_group_layout("ssgi", layout);
+
+        let mut desc = PipelineDesc::default();
+        desc.shader = if sample_count > 1 {
+            "ssgi_msaa.shader".to_string()
+        } else {
+            "ssgi.shader".to_string()
+        };
+        desc.color_state.format = HDR_FORMAT;
+        desc.cull_mode = wgpu::CullMode::None;
+        desc.layouts = vec!["ssgi".to_string()];
+        pipeline_manager.add_pipeline(
+            "ssgi",
+            &desc,
+            vec![],
+            &device,
+            &asset_manager,
+            &resource_manager,
+        );
+
+        // The gathered light is added on top of the frame.
+        let mut composite_desc = PipelineDesc::default();
+        composite_desc.shader = "ssgi_composite.shader".to_string();
+        composite_desc.color_state.format = HDR_FORMAT;
+        composite_desc.sample_count = sample_count;
+        composite_desc.color_state.color_blend = wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        composite_desc.color_state.alpha_blend = wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::Zero,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        composite_desc.cull_mode = wgpu::CullMode::None;
+        composite_desc.layouts = vec!["ssgi".to_string()];
+        pipeline_manager.add_pipeline(
+            "ssgi_composite",
+            &composite_desc,
+            vec![],
+            &device,
+            &asset_manager,
+            &resource_manager,
+        );
+
+        SsgiResources {
+            sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("ssgi"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                lod_min_clamp: -100.0,
+                lod_max_clamp: 100.0,
+                compare: wgpu::CompareFunction::Undefined,
+            }),
+        }
+    };
+    resources.insert(ssgi_resources);
+}
@@ -0,0 +1,96 @@
+use legion::prelude::Resources;
+
+use crate::{
+    graphics::{
+        pipeline_manager::{PipelineDesc, PipelineManager},
+        resources::{GPUResourceManager, GlyphInstance},
+        BlendMode, RenderSettings,
+    },
+    AssetManager,
+};
+
+pub fn create(resources: &Resources) {
+    let asset_manager = resources.get_mut::<AssetManager>().unwrap();
+    let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+    let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+    let device = resources.get::<wgpu::Device>().unwrap();
+    let sc_desc = resources.get::<wgpu::SwapChainDescriptor>().unwrap();
+    let sample_count = resources.get::<RenderSettings>().unwrap().sample_count;
+
+    // Text is drawn over the tonemapped frame so HDR doesn't wash it out. The sRGB target
+    // blends in linear space and the glyphs are premultiplied so their edges don't darken.
+    let mut text_desc = PipelineDesc::default();
+    text_desc.shader = if sample_count > 1 {
+        "text_msaa.shader".to_string()
+    } else {
+        "text.shader".to_string()
+    };
+    text_desc.color_state.format = sc_desc.format;
+    text_desc.set_blend_mode(BlendMode::Premultiplied);
+    text_desc.primitive_topology = wgpu::PrimitiveTopology::TriangleStrip;
+    text_desc.cull_mode = wgpu::CullMode::None;
+
+    let text_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        bindings: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Sampler { comparison: false },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    multisampled: false,
+                    component_type: wgpu::TextureComponentType::Float,
+                    dimension: wgpu::TextureViewDimension::D2,
+                },
+            },
+        ],
+        label: Some("text"),
+    });
+    resource_manager.add_bind_group_layout("text", text_layout);
+
+    // The scene's depth is tested in the shader, the swap chain isn't multisampled.
+    let depth_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        bindings: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+            ty: wgpu::BindingType::SampledTexture {
+                component_type: wgpu::TextureComponentType::Float,
+                multisampled: sample_count > 1,
+                dimension: wgpu::TextureViewDimension::D2,
+            },
+        }],
+        label: Some("text_depth"),
+    });
+    resource_manager.add_bind_group_layout("text_depth", depth_layout);
+
+    text_desc.layouts = vec!["text".to_string(), "text_depth".to_string()];
+
+    // Each glyph is a single instance, the quad's corners come from the vertex index.
+    let instance_size = std::mem::size_of::<GlyphInstance>();
+    text_desc
+        .vertex_state
+        .set_index_format(wgpu::IndexFormat::Uint16)
+        .new_buffer_descriptor(
+            instance_size as wgpu::BufferAddress,
+            wgpu::InputStepMode::Instance,
+            wgpu::vertex_attr_array![0 => Float3, 1 => Float2, 2 => Float2, 3 => Float2, 4 => Float4]
+                .to_vec(),
+        );
+
+    pipeline_manager.add_pipeline(
+        "text",
+        &text_desc,
+        vec![],
+        &device,
+        &asset_manager,
+        &resource_manager,
+    );
+}
@@ -0,0 +1,64 @@
+use legion::prelude::Resources;
+
+use crate::{
+    graphics::{
+        pipeline_manager::{PipelineDesc, PipelineManager},
+        resources::GPUResourceManager,
+        texture_viewer::PREVIEW_FORMAT,
+        TextureViewer,
+    },
+    AssetManager,
+};
+
+pub fn create(resources: &mut Resources) {
+    let texture_viewer = {
+        let asset_manager = resources.get::<AssetManager>().unwrap();
+        let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+        let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+        let device = resources.get::<wgpu::Device>().unwrap();
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: false,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+            ],
+            label: Some("texture_viewer"),
+        });
+        resource_manager.add_bind_group_layout("texture_viewer", layout);
+
+        for name in ["texture_preview", "material_preview"].iter() {
+            let mut desc = PipelineDesc::default();
+            desc.shader = format!("{}.shader", name);
+            desc.color_state.format = PREVIEW_FORMAT;
+            desc.cull_mode = wgpu::CullMode::None;
+            desc.layouts = vec!["texture_viewer".to_string()];
+            pipeline_manager.add_pipeline(
+                *name,
+                &desc,
+                vec![],
+                &device,
+                &asset_manager,
+                &resource_manager,
+            );
+        }
+        TextureViewer::new(&device)
+    };
+    resources.insert(texture_viewer);
+}
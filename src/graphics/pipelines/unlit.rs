@@ -2,9 +2,10 @@ use std::mem;
 
 use crate::{
     graphics::{
-        mesh::MeshVertexData,
+        material::ShaderDefines,
+        mesh::{AttributeVertexData, MeshVertexData},
         pipeline::VertexStateBuilder,
-        renderer::DEPTH_FORMAT,
+        renderer::{DEPTH_FORMAT, HDR_FORMAT},
         resources::{GPUResourceManager, RenderTarget},
         SimplePipeline, SimplePipelineDesc,
     },
@@ -62,8 +63,36 @@ impl SimplePipeline for UnlitPipeline {
     }
 }
 
-#[derive(Debug, Default)]
-pub struct UnlitPipelineDesc;
+#[derive(Debug)]
+pub struct UnlitPipelineDesc {
+    sample_count: u32,
+    vertex_attributes: bool,
+}
+
+impl UnlitPipelineDesc {
+    /// `sample_count` has to match the frame it draws into.
+    pub fn new(sample_count: u32) -> Self {
+        Self {
+            sample_count,
+            vertex_attributes: false,
+        }
+    }
+
+    /// Reads vertex colors from a second vertex buffer, see `AttributeVertexData`.
+    /// The shader variant from `shader_defines` has to be loaded first.
+    pub fn with_vertex_attributes(mut self, vertex_attributes: bool) -> Self {
+        self.vertex_attributes = vertex_attributes;
+        self
+    }
+
+    pub fn shader_defines(&self) -> ShaderDefines {
+        if self.vertex_attributes {
+            ShaderDefines::new().with_flag("VERTEX_ATTRIBUTES")
+        } else {
+            ShaderDefines::new()
+        }
+    }
+}
 
 impl SimplePipelineDesc for UnlitPipelineDesc {
     type Pipeline = UnlitPipeline;
@@ -72,7 +101,7 @@ impl SimplePipelineDesc for UnlitPipelineDesc {
         &self,
         asset_manager: &'a crate::AssetManager,
     ) -> &'a crate::graphics::material::Shader {
-        asset_manager.get_shader("unlit.shader")
+        asset_manager.get_shader(self.shader_defines().variant_name("unlit.shader"))
     }
 
     fn create_layout<'a>(
@@ -80,33 +109,36 @@ impl SimplePipelineDesc for UnlitPipelineDesc {
         device: &wgpu::Device,
         resource_manager: &'a mut GPUResourceManager,
     ) -> Vec<&'a wgpu::BindGroupLayout> {
-        let material_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                bindings: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStage::VERTEX,
-                        ty: wgpu::BindingType::UniformBuffer { dynamic: false },
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStage::FRAGMENT,
-                        ty: wgpu::BindingType::SampledTexture {
-                            multisampled: false,
-                            component_type: wgpu::TextureComponentType::Float,
-                            dimension: wgpu::TextureViewDimension::D2,
+        // The vertex attributes variant shares the layout so materials work with both.
+        if resource_manager.get_bind_group_layout("unlit_material").is_none() {
+            let material_bind_group_layout =
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    bindings: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStage::VERTEX,
+                            ty: wgpu::BindingType::UniformBuffer { dynamic: false },
                         },
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStage::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler { comparison: false },
-                    },
-                ],
-                label: None,
-            });
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStage::FRAGMENT,
+                            ty: wgpu::BindingType::SampledTexture {
+                                multisampled: false,
+                                component_type: wgpu::TextureComponentType::Float,
+                                dimension: wgpu::TextureViewDimension::D2,
+                            },
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStage::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler { comparison: false },
+                        },
+                    ],
+                    label: None,
+                });
 
-        resource_manager.add_bind_group_layout("unlit_material", material_bind_group_layout);
+            resource_manager.add_bind_group_layout("unlit_material", material_bind_group_layout);
+        }
         let material_bind_group_layout = resource_manager
             .get_bind_group_layout("unlit_material")
             .unwrap();
@@ -129,10 +161,10 @@ impl SimplePipelineDesc for UnlitPipelineDesc {
     }
     fn color_states_desc(
         &self,
-        sc_desc: &wgpu::SwapChainDescriptor,
+        _sc_desc: &wgpu::SwapChainDescriptor,
     ) -> Vec<wgpu::ColorStateDescriptor> {
         vec![wgpu::ColorStateDescriptor {
-            format: sc_desc.format,
+            format: HDR_FORMAT,
             color_blend: wgpu::BlendDescriptor::REPLACE,
             alpha_blend: wgpu::BlendDescriptor::REPLACE,
             write_mask: wgpu::ColorWrite::ALL,
@@ -185,9 +217,17 @@ impl SimplePipelineDesc for UnlitPipelineDesc {
                 ],
             );
 
+        if self.vertex_attributes {
+            AttributeVertexData::add_buffer_descriptor(&mut vertex_state_builder);
+        }
+
         vertex_state_builder
     }
 
+    fn create_samplers(&self, _device: &wgpu::Device) -> u32 {
+        self.sample_count
+    }
+
     fn build(
         self,
         _device: &wgpu::Device,
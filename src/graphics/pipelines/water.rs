@@ -0,0 +1,97 @@
+use legion::prelude::Resources;
+
+use crate::{
+    graphics::{
+        pipeline_manager::{PipelineDesc, PipelineManager},
+        renderer::HDR_FORMAT,
+        resources::GPUResourceManager,
+        RenderSettings,
+    },
+    AssetManager,
+};
+
+/// The caustics pass reads the depth buffer through this, the bind group is made each frame
+/// since the depth buffer is recreated on resize.
+pub(crate) struct CausticsResources {
+    pub sampler: wgpu::Sampler,
+}
+
+pub fn create(resources: &mut Resources) {
+    let caustics_resources = {
+        let asset_manager = resources.get::<AssetManager>().unwrap();
+        let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+        let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+        let device = resources.get::<wgpu::Device>().unwrap();
+        let sample_count = resources.get::<RenderSettings>().unwrap().sample_count;
+
+        // The depth buffer is multisampled along with the frame.
+        let depth_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            bindings: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: sample_count > 1,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                },
+            ],
+            label: Some("caustics_depth"),
+        });
+        resource_manager.add_bind_group_layout("caustics_depth", depth_layout);
+
+        // Multiplies the frame with the caustics, which brighten the lines and darken
+        // what's between them.
+        let mut caustics_desc = PipelineDesc::default();
+        caustics_desc.shader = if sample_count > 1 {
+            "caustics_msaa.shader".to_string()
+        } else {
+            "caustics.shader".to_string()
+        };
+        caustics_desc.color_state.format = HDR_FORMAT;
+        caustics_desc.sample_count = sample_count;
+        caustics_desc.color_state.color_blend = wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::Zero,
+            dst_factor: wgpu::BlendFactor::SrcColor,
+            operation: wgpu::BlendOperation::Add,
+        };
+        caustics_desc.color_state.alpha_blend = wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::Zero,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        caustics_desc.cull_mode = wgpu::CullMode::None;
+        // The shaders share the PBR shader's includes, which have the globals in set 1.
+        caustics_desc.layouts = vec!["caustics_depth".to_string(), "globals".to_string()];
+        pipeline_manager.add_pipeline(
+            "caustics",
+            &caustics_desc,
+            vec![],
+            &device,
+            &asset_manager,
+            &resource_manager,
+        );
+
+        CausticsResources {
+            sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("caustics_depth"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Nearest,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                lod_min_clamp: -100.0,
+                lod_max_clamp: 100.0,
+                compare: wgpu::CompareFunction::Undefined,
+            }),
+        }
+    };
+    resources.insert(caustics_resources);
+}
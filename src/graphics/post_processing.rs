@@ -0,0 +1,421 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra_glm::{Vec2, Vec4};
+
+use crate::scene::components::CameraData;
+
+/// The curve that maps the HDR scene into the range the screen can show.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Tonemapper {
+    Aces,
+    Reinhard,
+    /// No curve, anything above 1.0 just clips.
+    None,
+}
+
+impl Tonemapper {
+    fn value(&self) -> f32 {
+        match self {
+            Tonemapper::None => 0.0,
+            Tonemapper::Aces => 1.0,
+            Tonemapper::Reinhard => 2.0,
+        }
+    }
+}
+
+/// Makes bright parts of the scene bleed light into their surroundings.
+#[derive(Debug, Clone)]
+pub struct Bloom {
+    pub enabled: bool,
+    /// Only colors brighter than this glow.
+    pub threshold: f32,
+    pub intensity: f32,
+    /// How many times the glow is blurred, each pass spreads it further.
+    pub blur_passes: u32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 1.0,
+            intensity: 0.3,
+            blur_passes: 3,
+        }
+    }
+}
+
+/// Warps the rendered image so wide fields of view look natural instead of stretching things at
+/// the edges of the screen, set it on a camera with `CameraData::with_warp`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ProjectionWarp {
+    /// Keeps vertical lines straight and squeezes the sides in. A `distance` of 0.0 doesn't
+    /// change anything and 1.0 is the full effect, `crop_to_fit` zooms in from 0.0 to 1.0 so
+    /// the black edges the warp leaves don't show.
+    Panini { distance: f32, crop_to_fit: f32 },
+    /// Bends the image like a fisheye lens, `strength` goes from 0.0 to 1.0.
+    Fisheye { strength: f32 },
+}
+
+impl ProjectionWarp {
+    // (mode, parameter, scale) for the shader, `extents` are the tangents of half the fov.
+    fn value(&self, extents: Vec2) -> (f32, f32, f32) {
+        match *self {
+            ProjectionWarp::Panini {
+                distance,
+                crop_to_fit,
+            } => {
+                let distance = distance.max(0.0).min(1.0);
+                let crop = panini_crop_extents(extents, distance);
+                let fit = (crop.x / extents.x).min(crop.y / extents.y).min(1.0);
+                (
+                    1.0,
+                    distance,
+                    1.0 + (fit - 1.0) * crop_to_fit.max(0.0).min(1.0),
+                )
+            }
+            ProjectionWarp::Fisheye { strength } => (2.0, strength.max(0.0).min(1.0), 1.0),
+        }
+    }
+}
+
+// How far into the image the panini projection of it's edge lands, from Unity's post stack.
+fn panini_crop_extents(extents: Vec2, distance: f32) -> Vec2 {
+    let hypotenuse = (extents.x * extents.x + 1.0).sqrt();
+    let cylinder_distance_minus_d = 1.0 / hypotenuse;
+    let cylinder_distance = cylinder_distance_minus_d + distance;
+    extents * cylinder_distance_minus_d * ((1.0 + distance) / cylinder_distance)
+}
+
+/// Touches applied by the very last pass, after tonemapping and FXAA. Each one can be turned on
+/// by itself.
+#[derive(Debug, Clone)]
+pub struct FinalPass {
+    /// Animated noise like on film.
+    pub film_grain: bool,
+    pub grain_intensity: f32,
+    /// Contrast adaptive sharpening, it brings back detail that TAA and FXAA soften without
+    /// making edges ring.
+    pub sharpen: bool,
+    /// From 0.0 to 1.0.
+    pub sharpness: f32,
+    /// Adds a little blue noise before the frame is stored with 8 bits per channel, it hides the
+    /// banding in smooth gradients like the sky.
+    pub dither: bool,
+}
+
+impl Default for FinalPass {
+    fn default() -> Self {
+        Self {
+            film_grain: false,
+            grain_intensity: 0.05,
+            sharpen: false,
+            sharpness: 0.5,
+            dither: true,
+        }
+    }
+}
+
+/// Which part of the screen auto-exposure looks at.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MeteringMode {
+    /// Every pixel counts the same.
+    Average,
+    /// The middle of the screen counts the most, the edges barely count.
+    CenterWeighted,
+    /// Only a circle in the middle counts, `radius` is a fraction of the screen's height.
+    Spot { radius: f32 },
+}
+
+impl MeteringMode {
+    fn value(&self) -> (f32, f32) {
+        match self {
+            MeteringMode::Average => (0.0, 0.0),
+            MeteringMode::CenterWeighted => (1.0, 0.0),
+            MeteringMode::Spot { radius } => (2.0, *radius),
+        }
+    }
+}
+
+/// Adjusts the exposure to the brightness of the scene over time, like an eye or a camera.
+/// Exposures are in EV100, the scene is metered on the GPU and read back a frame or two later.
+#[derive(Debug, Clone)]
+pub struct AutoExposure {
+    pub enabled: bool,
+    pub metering: MeteringMode,
+    /// A texture from the asset manager, only the white parts of the screen are metered.
+    /// Use it to leave out UI or the sky so they don't skew the exposure.
+    pub mask: Option<String>,
+    pub min_ev: f32,
+    pub max_ev: f32,
+    /// Shifts the metered exposure, positive values brighten the image.
+    pub compensation: f32,
+    /// How fast the exposure adapts when the scene gets brighter, higher is faster.
+    pub speed_up: f32,
+    /// How fast the exposure adapts when the scene gets darker, eyes are slower at this.
+    pub speed_down: f32,
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            metering: MeteringMode::CenterWeighted,
+            mask: None,
+            min_ev: -4.0,
+            max_ev: 16.0,
+            compensation: 0.0,
+            speed_up: 3.0,
+            speed_down: 1.0,
+        }
+    }
+}
+
+impl AutoExposure {
+    /// The EV100 of a scene with this average log2 luminance, clamped to the min and max.
+    pub(crate) fn target_ev(&self, average_log_luminance: f32) -> f32 {
+        // EV100 = log2(luminance * 100 / 12.5)
+        let ev = average_log_luminance + 3.0;
+        ev.max(self.min_ev).min(self.max_ev.max(self.min_ev))
+    }
+
+    /// Moves `current` towards `target` smoothly, `delta_time` is in seconds.
+    pub(crate) fn adapt(&self, current: f32, target: f32, delta_time: f32) -> f32 {
+        let speed = if target > current {
+            self.speed_up
+        } else {
+            self.speed_down
+        };
+        current + (target - current) * (1.0 - (-delta_time * speed.max(0.0)).exp())
+    }
+
+    /// The multiplier applied to the scene's colors at `ev`.
+    pub(crate) fn exposure(&self, ev: f32) -> f32 {
+        1.0 / (1.2 * 2.0f32.powf(ev - self.compensation))
+    }
+}
+
+/// The average log2 luminance of the metering texture, its texels are
+/// (log2 luminance * weight, weight). None when nothing was metered, like a fully masked screen.
+pub(crate) fn average_log_luminance(texels: &[[f32; 2]]) -> Option<f32> {
+    let (sum, weight) = texels.iter().fold((0.0, 0.0), |(sum, weight), texel| {
+        (sum + texel[0], weight + texel[1])
+    });
+    if weight > 0.0001 {
+        Some(sum / weight)
+    } else {
+        None
+    }
+}
+
+/// Effects applied to the frame after the scene has rendered, a resource.
+/// The scene renders into the HDR "frame" attachment, these passes turn it into what's on screen.
+#[derive(Debug, Clone)]
+pub struct PostProcessing {
+    pub tonemapper: Tonemapper,
+    /// Scales the scene's colors before they are tonemapped.
+    pub exposure: f32,
+    pub bloom: Bloom,
+    /// Overrides `exposure` while it's enabled, cameras with their own exposure still win.
+    pub auto_exposure: AutoExposure,
+    /// Smooths out jagged edges.
+    pub fxaa: bool,
+    pub final_pass: FinalPass,
+    // The camera's warp and vertical fov in radians.
+    pub(crate) warp: Option<(ProjectionWarp, f32)>,
+}
+
+impl Default for PostProcessing {
+    fn default() -> Self {
+        Self {
+            tonemapper: Tonemapper::Aces,
+            exposure: 1.0,
+            bloom: Bloom::default(),
+            auto_exposure: AutoExposure::default(),
+            fxaa: true,
+            final_pass: FinalPass::default(),
+            warp: None,
+        }
+    }
+}
+
+impl PostProcessing {
+    /// The settings with the camera's overrides applied, `metered` is the auto-exposure if
+    /// there is one yet.
+    pub(crate) fn for_camera(
+        &self,
+        camera: Option<&CameraData>,
+        metered: Option<f32>,
+    ) -> PostProcessing {
+        let mut settings = self.clone();
+        if self.auto_exposure.enabled {
+            settings.exposure = metered.unwrap_or(self.exposure);
+        }
+        if let Some(camera) = camera {
+            settings.exposure = camera.exposure.unwrap_or(settings.exposure);
+            settings.tonemapper = camera.tonemapper.unwrap_or(self.tonemapper);
+            settings.warp = camera
+                .warp
+                .and_then(|warp| camera.vertical_fov().map(|fov| (warp, fov.to_radians())));
+        }
+        settings
+    }
+
+    pub(crate) fn bloom_enabled(&self) -> bool {
+        self.bloom.enabled && self.bloom.intensity > 0.0
+    }
+
+    /// `direction` is only used by the blur, `size` is the size of the texture being read.
+    pub(crate) fn uniform(&self, direction: (f32, f32), size: (u32, u32)) -> PostProcessUniform {
+        let bloom_intensity = if self.bloom_enabled() {
+            self.bloom.intensity
+        } else {
+            0.0
+        };
+        let (metering_mode, metering_radius) = self.auto_exposure.metering.value();
+        let has_mask = if self.auto_exposure.mask.is_some() {
+            1.0
+        } else {
+            0.0
+        };
+        let warp = match self.warp {
+            Some((warp, fov)) => {
+                let tan_half_fov = (fov * 0.5).tan();
+                let aspect = size.0 as f32 / size.1.max(1) as f32;
+                let extents = Vec2::new(tan_half_fov * aspect, tan_half_fov);
+                let (mode, parameter, scale) = warp.value(extents);
+                Vec4::new(mode, parameter, scale, tan_half_fov)
+            }
+            None => Vec4::zeros(),
+        };
+        PostProcessUniform {
+            settings: Vec4::new(
+                self.exposure,
+                self.tonemapper.value(),
+                bloom_intensity,
+                self.bloom.threshold,
+            ),
+            texel: Vec4::new(
+                direction.0,
+                direction.1,
+                1.0 / size.0 as f32,
+                1.0 / size.1 as f32,
+            ),
+            metering: Vec4::new(metering_mode, metering_radius, has_mask, 0.0),
+            final_pass: Vec4::zeros(),
+            warp,
+        }
+    }
+
+    /// The uniform for the pass that writes the finished frame, it adds the grain and
+    /// dithering. `time` in seconds animates the grain.
+    pub(crate) fn final_uniform(&self, size: (u32, u32), time: f32) -> PostProcessUniform {
+        let final_pass = &self.final_pass;
+        let grain = if final_pass.film_grain {
+            final_pass.grain_intensity.max(0.0)
+        } else {
+            0.0
+        };
+        let dither = if final_pass.dither { 1.0 } else { 0.0 };
+        PostProcessUniform {
+            final_pass: Vec4::new(grain, dither, final_pass.sharpness.max(0.0).min(1.0), time),
+            ..self.uniform((0.0, 0.0), size)
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PostProcessUniform {
+    // (exposure, tonemapper, bloom intensity, bloom threshold)
+    pub settings: Vec4,
+    // (blur direction x, blur direction y, 1 / width, 1 / height)
+    pub texel: Vec4,
+    // (metering mode, spot radius, 1 if there's a mask, unused)
+    pub metering: Vec4,
+    // (grain intensity, 1 to dither, sharpness, time), only set for the last pass
+    pub final_pass: Vec4,
+    // (warp: 0 = none, 1 = panini, 2 = fisheye, distance or strength, scale, tan(fov / 2))
+    pub warp: Vec4,
+}
+
+unsafe impl Zeroable for PostProcessUniform {}
+unsafe impl Pod for PostProcessUniform {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_camera_overrides() {
+        let post_processing = PostProcessing::default();
+        let camera = CameraData::default().with_exposure(2.5);
+        let settings = post_processing.for_camera(Some(&camera), None);
+        assert_eq!(settings.exposure, 2.5);
+        assert_eq!(settings.tonemapper, Tonemapper::Aces);
+
+        let camera = camera.with_tonemapper(Tonemapper::Reinhard);
+        let settings = post_processing.for_camera(Some(&camera), None);
+        assert_eq!(settings.tonemapper, Tonemapper::Reinhard);
+        assert_eq!(post_processing.for_camera(None, None).exposure, 1.0);
+    }
+
+    #[test]
+    fn test_panini_crop() {
+        let extents = Vec2::new(1.0, 0.5);
+        assert_eq!(panini_crop_extents(extents, 0.0), extents);
+        let crop = panini_crop_extents(extents, 1.0);
+        let expected = 2.0 / (2.0f32.sqrt() + 1.0);
+        assert!((crop.x - expected).abs() < 0.0001);
+        let warp = ProjectionWarp::Panini {
+            distance: 1.0,
+            crop_to_fit: 1.0,
+        };
+        assert!((warp.value(extents).2 - expected).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_final_pass_only_in_final_uniform() {
+        let mut post_processing = PostProcessing::default();
+        post_processing.final_pass.film_grain = true;
+        assert_eq!(
+            post_processing.uniform((0.0, 0.0), (4, 4)).final_pass,
+            Vec4::zeros()
+        );
+        let uniform = post_processing.final_uniform((4, 4), 2.0);
+        assert_eq!(uniform.final_pass, Vec4::new(0.05, 1.0, 0.5, 2.0));
+        assert_eq!(
+            uniform.settings,
+            post_processing.uniform((0.0, 0.0), (4, 4)).settings
+        );
+    }
+
+    #[test]
+    fn test_auto_exposure() {
+        let mut post_processing = PostProcessing::default();
+        post_processing.auto_exposure.enabled = true;
+        assert_eq!(post_processing.for_camera(None, Some(0.5)).exposure, 0.5);
+        let camera = CameraData::default().with_exposure(2.5);
+        assert_eq!(
+            post_processing
+                .for_camera(Some(&camera), Some(0.5))
+                .exposure,
+            2.5
+        );
+
+        let auto_exposure = &post_processing.auto_exposure;
+        assert_eq!(
+            average_log_luminance(&[[2.0, 1.0], [0.0, 0.0], [6.0, 1.0]]),
+            Some(4.0)
+        );
+        assert_eq!(average_log_luminance(&[[0.0, 0.0]]), None);
+        assert_eq!(auto_exposure.target_ev(-20.0), auto_exposure.min_ev);
+        assert_eq!(auto_exposure.target_ev(1.0), 4.0);
+
+        let brighter = auto_exposure.adapt(0.0, 4.0, 0.1);
+        let darker = auto_exposure.adapt(4.0, 0.0, 0.1);
+        assert!(brighter > 0.0 && brighter < 4.0);
+        // Adapting to brighter scenes is faster by default.
+        assert!(brighter > 4.0 - darker);
+        assert!((auto_exposure.adapt(0.0, 4.0, 100.0) - 4.0).abs() < 0.001);
+    }
+}
@@ -0,0 +1,542 @@
+use legion::prelude::*;
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+
+use super::{
+    bounds::{Aabb, BoundingSphere},
+    mesh::Mesh,
+};
+use crate::{scene::components, AssetManager};
+
+/// A ray for geometric queries, the direction is normalized so hit distances are in world units.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    /// The point the given distance along the ray.
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    // The direction isn't normalized afterwards, that way distances along the ray stay the same
+    // in both spaces.
+    fn transform(&self, matrix: &Mat4) -> Self {
+        let origin = matrix * Vec4::new(self.origin.x, self.origin.y, self.origin.z, 1.0);
+        let direction =
+            matrix * Vec4::new(self.direction.x, self.direction.y, self.direction.z, 0.0);
+        Self {
+            origin: origin.xyz(),
+            direction: direction.xyz(),
+        }
+    }
+}
+
+/// Lets you pass the result of `CameraData::screen_to_ray` straight in.
+impl From<(Vec3, Vec3)> for Ray {
+    fn from((origin, direction): (Vec3, Vec3)) -> Self {
+        Self::new(origin, direction)
+    }
+}
+
+/// Where a ray hit a mesh or heightfield, in world space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RayHit {
+    pub distance: f32,
+    pub position: Vec3,
+    /// The triangle's normal, facing back towards the ray.
+    pub normal: Vec3,
+    pub sub_mesh: usize,
+    pub triangle: usize,
+}
+
+pub(crate) fn transform_point(matrix: &Mat4, point: &Vec3) -> Vec3 {
+    (matrix * Vec4::new(point.x, point.y, point.z, 1.0)).xyz()
+}
+
+// Normals need the inverse transpose so they stay correct with non-uniform scale.
+fn world_hit(ray: &Ray, inverse: &Mat4, mut hit: RayHit) -> RayHit {
+    let normal = Vec4::new(hit.normal.x, hit.normal.y, hit.normal.z, 0.0);
+    let mut normal = (nalgebra_glm::transpose(inverse) * normal)
+        .xyz()
+        .normalize();
+    if normal.dot(&ray.direction) > 0.0 {
+        normal = -normal;
+    }
+    hit.position = ray.at(hit.distance);
+    hit.normal = normal;
+    hit
+}
+
+// Slab test, returns the distances along the ray where it enters and leaves the box.
+fn ray_aabb_range(ray: &Ray, aabb: &Aabb) -> Option<(f32, f32)> {
+    let mut t_min = 0.0f32;
+    let mut t_max = std::f32::MAX;
+    for axis in 0..3 {
+        if ray.direction[axis].abs() < std::f32::EPSILON {
+            if ray.origin[axis] < aabb.min[axis] || ray.origin[axis] > aabb.max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let inverse = 1.0 / ray.direction[axis];
+        let mut near = (aabb.min[axis] - ray.origin[axis]) * inverse;
+        let mut far = (aabb.max[axis] - ray.origin[axis]) * inverse;
+        if near > far {
+            std::mem::swap(&mut near, &mut far);
+        }
+        t_min = t_min.max(near);
+        t_max = t_max.min(far);
+        if t_min > t_max {
+            return None;
+        }
+    }
+    Some((t_min, t_max))
+}
+
+/// Returns the distance along the ray to the box, 0 if the ray starts inside it.
+pub fn ray_aabb(ray: &Ray, aabb: &Aabb) -> Option<f32> {
+    ray_aabb_range(ray, aabb).map(|(near, _)| near)
+}
+
+/// Returns the distance along the ray to the sphere, 0 if the ray starts inside it.
+pub fn ray_sphere(ray: &Ray, sphere: &BoundingSphere) -> Option<f32> {
+    let to_center = sphere.center - ray.origin;
+    let radius2 = sphere.radius * sphere.radius;
+    if to_center.norm_squared() <= radius2 {
+        return Some(0.0);
+    }
+    let direction_length2 = ray.direction.norm_squared();
+    let along = to_center.dot(&ray.direction) / direction_length2;
+    if along < 0.0 {
+        return None;
+    }
+    let closest2 = (to_center - ray.direction * along).norm_squared();
+    if closest2 > radius2 {
+        return None;
+    }
+    Some(along - ((radius2 - closest2) / direction_length2).sqrt())
+}
+
+/// Möller–Trumbore, both sides of the triangle count as a hit.
+pub fn ray_triangle(ray: &Ray, triangle: &[Vec3; 3]) -> Option<f32> {
+    let edge1 = triangle[1] - triangle[0];
+    let edge2 = triangle[2] - triangle[0];
+    let p = ray.direction.cross(&edge2);
+    let determinant = edge1.dot(&p);
+    if determinant.abs() < std::f32::EPSILON {
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+    let s = ray.origin - triangle[0];
+    let u = s.dot(&p) * inverse;
+    if u < 0.0 || u > 1.0 {
+        return None;
+    }
+    let q = s.cross(&edge1);
+    let v = ray.direction.dot(&q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let distance = edge2.dot(&q) * inverse;
+    if distance < 0.0 {
+        return None;
+    }
+    Some(distance)
+}
+
+/// Casts a ray against the bounding box of a mesh placed with `matrix`, returning the distance.
+/// Much cheaper than `raycast_mesh` but hits the empty space around the mesh too.
+pub fn raycast_bounds(ray: &Ray, mesh: &Mesh, matrix: &Mat4) -> Option<f32> {
+    ray_aabb(&ray.transform(&nalgebra_glm::inverse(matrix)), &mesh.aabb)
+}
+
+/// Casts a ray against every triangle of a mesh placed with `matrix`, returning the closest hit.
+/// Skinned meshes are tested in their bind pose.
+pub fn raycast_mesh(ray: &Ray, mesh: &Mesh, matrix: &Mat4) -> Option<RayHit> {
+    let inverse = nalgebra_glm::inverse(matrix);
+    let local = ray.transform(&inverse);
+    ray_aabb(&local, &mesh.aabb)?;
+
+    let mut closest: Option<RayHit> = None;
+    for (sub_mesh_index, sub_mesh) in mesh.sub_meshes.iter().enumerate() {
+        if ray_aabb(&local, &sub_mesh.aabb).is_none() {
+            continue;
+        }
+        for (triangle_index, triangle) in sub_mesh.triangles().enumerate() {
+            let distance = match ray_triangle(&local, &triangle) {
+                Some(distance) => distance,
+                None => continue,
+            };
+            if closest.map_or(true, |hit| distance < hit.distance) {
+                closest = Some(RayHit {
+                    distance,
+                    position: Vec3::zeros(),
+                    normal: (triangle[1] - triangle[0]).cross(&(triangle[2] - triangle[0])),
+                    sub_mesh: sub_mesh_index,
+                    triangle: triangle_index,
+                });
+            }
+        }
+    }
+    closest.map(|hit| world_hit(ray, &inverse, hit))
+}
+
+/// Casts a ray against every mesh in the world and returns the closest entity it hit.
+/// Works from the CPU copy of the meshes, meshes that aren't loaded yet are skipped.
+pub fn raycast_world(
+    world: &World,
+    asset_manager: &AssetManager,
+    ray: &Ray,
+) -> Option<(Entity, RayHit)> {
+    let query = <(Read<components::Mesh>, Read<components::Transform>)>::query();
+    let mut closest: Option<(Entity, RayHit)> = None;
+    for (entity, (mesh, transform)) in query.iter_entities(world) {
+        let asset_mesh = match asset_manager.try_get_mesh(mesh.mesh_name.clone()) {
+            Ok(asset_mesh) => asset_mesh,
+            Err(_) => continue,
+        };
+        let matrix = transform.calculate_matrix();
+        let hit = match raycast_mesh(ray, asset_mesh, &matrix) {
+            Some(hit) => hit,
+            None => continue,
+        };
+        if closest.map_or(true, |(_, closest)| hit.distance < closest.distance) {
+            closest = Some((entity, hit));
+        }
+    }
+    closest
+}
+
+/// The closest point on a triangle, from Real-Time Collision Detection.
+pub fn closest_point_on_triangle(point: &Vec3, triangle: &[Vec3; 3]) -> Vec3 {
+    let (a, b, c) = (triangle[0], triangle[1], triangle[2]);
+    let ab = b - a;
+    let ac = c - a;
+    let ap = point - a;
+    let d1 = ab.dot(&ap);
+    let d2 = ac.dot(&ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    let bp = point - b;
+    let d3 = ab.dot(&bp);
+    let d4 = ac.dot(&bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+
+    let cp = point - c;
+    let d5 = ab.dot(&cp);
+    let d6 = ac.dot(&cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+
+    let denominator = 1.0 / (va + vb + vc);
+    a + ab * (vb * denominator) + ac * (vc * denominator)
+}
+
+/// The closest point on the surface of a mesh placed with `matrix`, None if it has no triangles.
+/// The triangles are moved into world space first so this is exact with any scale.
+pub fn closest_point_on_mesh(point: &Vec3, mesh: &Mesh, matrix: &Mat4) -> Option<Vec3> {
+    mesh.sub_meshes
+        .iter()
+        .flat_map(|sub_mesh| sub_mesh.triangles())
+        .map(|triangle| {
+            let triangle = [
+                transform_point(matrix, &triangle[0]),
+                transform_point(matrix, &triangle[1]),
+                transform_point(matrix, &triangle[2]),
+            ];
+            closest_point_on_triangle(point, &triangle)
+        })
+        .min_by(|a, b| {
+            nalgebra_glm::distance2(a, point)
+                .partial_cmp(&nalgebra_glm::distance2(b, point))
+                .unwrap()
+        })
+}
+
+pub fn aabb_overlaps_aabb(a: &Aabb, b: &Aabb) -> bool {
+    (0..3).all(|axis| a.min[axis] <= b.max[axis] && a.max[axis] >= b.min[axis])
+}
+
+pub fn sphere_overlaps_aabb(sphere: &BoundingSphere, aabb: &Aabb) -> bool {
+    let closest = nalgebra_glm::clamp_vec(&sphere.center, &aabb.min, &aabb.max);
+    nalgebra_glm::distance2(&closest, &sphere.center) <= sphere.radius * sphere.radius
+}
+
+pub fn sphere_overlaps_sphere(a: &BoundingSphere, b: &BoundingSphere) -> bool {
+    let radius = a.radius + b.radius;
+    nalgebra_glm::distance2(&a.center, &b.center) <= radius * radius
+}
+
+/// Tests a world space sphere against the triangles of a mesh placed with `matrix`.
+pub fn sphere_overlaps_mesh(sphere: &BoundingSphere, mesh: &Mesh, matrix: &Mat4) -> bool {
+    if !sphere_overlaps_aabb(sphere, &mesh.aabb.transform(matrix)) {
+        return false;
+    }
+    closest_point_on_mesh(&sphere.center, mesh, matrix).map_or(false, |closest| {
+        nalgebra_glm::distance2(&closest, &sphere.center) <= sphere.radius * sphere.radius
+    })
+}
+
+/// A grid of heights like a terrain, for queries without having to build a mesh.
+/// Sample (x, z) sits at (x * scale.x, height * scale.y, z * scale.z) in it's local space and
+/// each cell is split into two triangles along the diagonal from (x, z) to (x + 1, z + 1).
+#[derive(Debug, Clone)]
+pub struct Heightfield {
+    /// Number of samples along X.
+    pub width: usize,
+    /// Number of samples along Z.
+    pub depth: usize,
+    /// Row major, `heights[z * width + x]`.
+    pub heights: Vec<f32>,
+    pub scale: Vec3,
+}
+
+impl Heightfield {
+    pub fn new(width: usize, depth: usize, heights: Vec<f32>, scale: Vec3) -> Self {
+        assert_eq!(
+            heights.len(),
+            width * depth,
+            "Heightfield needs width * depth heights"
+        );
+        Self {
+            width,
+            depth,
+            heights,
+            scale,
+        }
+    }
+
+    fn point(&self, x: usize, z: usize) -> Vec3 {
+        Vec3::new(
+            x as f32 * self.scale.x,
+            self.heights[z * self.width + x] * self.scale.y,
+            z as f32 * self.scale.z,
+        )
+    }
+
+    fn cell_triangles(&self, x: usize, z: usize) -> [[Vec3; 3]; 2] {
+        let p00 = self.point(x, z);
+        let p10 = self.point(x + 1, z);
+        let p01 = self.point(x, z + 1);
+        let p11 = self.point(x + 1, z + 1);
+        [[p00, p11, p10], [p00, p01, p11]]
+    }
+
+    /// Bounds in local space.
+    pub fn aabb(&self) -> Aabb {
+        let (min, max) = self
+            .heights
+            .iter()
+            .fold((std::f32::MAX, std::f32::MIN), |(min, max), height| {
+                (min.min(*height), max.max(*height))
+            });
+        let (min, max) = if self.scale.y < 0.0 {
+            (max, min)
+        } else {
+            (min, max)
+        };
+        Aabb::new(
+            Vec3::new(0.0, min * self.scale.y, 0.0),
+            Vec3::new(
+                (self.width.max(1) - 1) as f32 * self.scale.x,
+                max * self.scale.y,
+                (self.depth.max(1) - 1) as f32 * self.scale.z,
+            ),
+        )
+    }
+
+    /// The height of the surface at a local position, None when it's outside of the grid.
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        if self.width < 2 || self.depth < 2 {
+            return None;
+        }
+        let grid_x = x / self.scale.x;
+        let grid_z = z / self.scale.z;
+        if grid_x < 0.0
+            || grid_z < 0.0
+            || grid_x > (self.width - 1) as f32
+            || grid_z > (self.depth - 1) as f32
+        {
+            return None;
+        }
+        let cell_x = (grid_x as usize).min(self.width - 2);
+        let cell_z = (grid_z as usize).min(self.depth - 2);
+        let (fx, fz) = (grid_x - cell_x as f32, grid_z - cell_z as f32);
+        let h00 = self.point(cell_x, cell_z).y;
+        let h10 = self.point(cell_x + 1, cell_z).y;
+        let h01 = self.point(cell_x, cell_z + 1).y;
+        let h11 = self.point(cell_x + 1, cell_z + 1).y;
+        // Interpolate across the same triangles the raycast uses.
+        if fx >= fz {
+            Some(h00 + (h10 - h00) * (fx - fz) + (h11 - h00) * fz)
+        } else {
+            Some(h00 + (h01 - h00) * (fz - fx) + (h11 - h00) * fx)
+        }
+    }
+
+    /// Casts a ray against the heightfield placed with `matrix`, walking the cells under the ray
+    /// so only a handful of triangles get tested.
+    pub fn raycast(&self, ray: &Ray, matrix: &Mat4) -> Option<RayHit> {
+        if self.width < 2 || self.depth < 2 {
+            return None;
+        }
+        let inverse = nalgebra_glm::inverse(matrix);
+        let local = ray.transform(&inverse);
+        let (enter, exit) = ray_aabb_range(&local, &self.aabb())?;
+
+        let start = local.at(enter);
+        let clamp_cell =
+            |value: f32, samples: usize| (value.max(0.0) as usize).min(samples - 2) as isize;
+        let mut cell_x = clamp_cell(start.x / self.scale.x, self.width);
+        let mut cell_z = clamp_cell(start.z / self.scale.z, self.depth);
+
+        // Distance along the ray to the next cell boundary on each axis.
+        let step = |direction: f32, origin: f32, cell: isize, size: f32| {
+            if direction > 0.0 {
+                (
+                    1,
+                    ((cell + 1) as f32 * size - origin) / direction,
+                    size / direction,
+                )
+            } else if direction < 0.0 {
+                (
+                    -1,
+                    (cell as f32 * size - origin) / direction,
+                    -size / direction,
+                )
+            } else {
+                (0, std::f32::MAX, std::f32::MAX)
+            }
+        };
+        let (step_x, mut next_x, delta_x) =
+            step(local.direction.x, local.origin.x, cell_x, self.scale.x);
+        let (step_z, mut next_z, delta_z) =
+            step(local.direction.z, local.origin.z, cell_z, self.scale.z);
+
+        loop {
+            // Triangles can't leave their cell so the first cell with a hit has the closest one.
+            let triangles = self.cell_triangles(cell_x as usize, cell_z as usize);
+            let hit = triangles
+                .iter()
+                .enumerate()
+                .filter_map(|(index, triangle)| {
+                    ray_triangle(&local, triangle).map(|distance| (index, triangle, distance))
+                })
+                .min_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+            if let Some((index, triangle, distance)) = hit {
+                let hit = RayHit {
+                    distance,
+                    position: Vec3::zeros(),
+                    normal: (triangle[1] - triangle[0]).cross(&(triangle[2] - triangle[0])),
+                    sub_mesh: 0,
+                    triangle: (cell_z as usize * (self.width - 1) + cell_x as usize) * 2 + index,
+                };
+                return Some(world_hit(ray, &inverse, hit));
+            }
+
+            if next_x < next_z {
+                if next_x > exit {
+                    return None;
+                }
+                cell_x += step_x;
+                next_x += delta_x;
+            } else {
+                if next_z > exit {
+                    return None;
+                }
+                cell_z += step_z;
+                next_z += delta_z;
+            }
+            if cell_x < 0
+                || cell_z < 0
+                || cell_x > self.width as isize - 2
+                || cell_z > self.depth as isize - 2
+            {
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ray_triangle() {
+        let triangle = [
+            Vec3::new(-1.0, 0.0, -1.0),
+            Vec3::new(1.0, 0.0, -1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ];
+        let down = Ray::new(Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let distance = ray_triangle(&down, &triangle).unwrap();
+        assert!((distance - 5.0).abs() < 0.0001);
+
+        let up = Ray::new(Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(ray_triangle(&up, &triangle), None);
+
+        let beside = Ray::new(Vec3::new(3.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(ray_triangle(&beside, &triangle), None);
+    }
+
+    #[test]
+    fn test_closest_point_on_triangle() {
+        let triangle = [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ];
+        let above = closest_point_on_triangle(&Vec3::new(0.25, 2.0, 0.25), &triangle);
+        assert!(nalgebra_glm::distance(&above, &Vec3::new(0.25, 0.0, 0.25)) < 0.0001);
+
+        let corner = closest_point_on_triangle(&Vec3::new(-1.0, 0.0, -1.0), &triangle);
+        assert_eq!(corner, triangle[0]);
+    }
+
+    #[test]
+    fn test_heightfield_raycast() {
+        // A slope going up along X.
+        let heights = (0..16).map(|index| (index % 4) as f32).collect();
+        let heightfield = Heightfield::new(4, 4, heights, Vec3::new(1.0, 1.0, 1.0));
+        assert_eq!(heightfield.height_at(1.5, 1.5), Some(1.5));
+        assert_eq!(heightfield.height_at(5.0, 1.0), None);
+
+        let ray = Ray::new(Vec3::new(2.5, 10.0, 0.5), Vec3::new(0.0, -1.0, 0.0));
+        let hit = heightfield.raycast(&ray, &Mat4::identity()).unwrap();
+        assert!((hit.position.y - 2.5).abs() < 0.0001);
+        assert!(hit.normal.y > 0.0);
+
+        let moved = nalgebra_glm::translation(&Vec3::new(100.0, 0.0, 0.0));
+        assert!(heightfield.raycast(&ray, &moved).is_none());
+    }
+}
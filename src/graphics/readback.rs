@@ -0,0 +1,183 @@
+use futures::FutureExt;
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+type MapFuture = Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+// How texture rows are laid out in the readback buffer, they're padded to 256 bytes.
+struct RowLayout {
+    row_bytes: u32,
+    padded_bytes_per_row: u32,
+    rows: u32,
+}
+
+/// Data being copied back from the GPU, for things like picking buffers, compute results and
+/// screenshots. It's a future that resolves to the bytes once the copy has run, the engine
+/// polls the device every frame so it finishes by itself a frame or two after the copy is
+/// submitted. `.await` it or check `try_take` each update:
+/// ```ignore
+/// let mut readback = GpuReadback::from_buffer(&device, &mut encoder, &results, 0, size);
+/// queue.submit(Some(encoder.finish()));
+/// // Later on.
+/// if let Some(Ok(bytes)) = readback.try_take() {
+///     let results: &[f32] = bytemuck::cast_slice(&bytes);
+/// }
+/// ```
+pub struct GpuReadback {
+    buffer: Option<wgpu::Buffer>,
+    size: usize,
+    rows: Option<RowLayout>,
+    mapping: MapFuture,
+}
+
+impl GpuReadback {
+    /// Copies `size` bytes of `source` starting at `offset`, the source needs `COPY_SRC` usage.
+    /// The copy is recorded into `encoder` and nothing arrives until it's submitted.
+    pub fn from_buffer(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+        size: wgpu::BufferAddress,
+    ) -> Self {
+        // Copies have to be a multiple of 4 bytes, the extra bytes are dropped afterwards.
+        let aligned_size = (size + 3) & !3;
+        let buffer = Self::create_buffer(device, aligned_size);
+        encoder.copy_buffer_to_buffer(source, offset, &buffer, 0, aligned_size);
+        Self::new(buffer, size as usize, None)
+    }
+
+    /// Copies a region of one mip level of `texture`, it needs `COPY_SRC` usage.
+    /// The bytes come back tightly packed, `bytes_per_pixel` has to match the format.
+    pub fn from_texture(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        mip_level: u32,
+        origin: wgpu::Origin3d,
+        extent: wgpu::Extent3d,
+        bytes_per_pixel: u32,
+    ) -> Self {
+        let row_bytes = extent.width * bytes_per_pixel;
+        let padded_bytes_per_row = (row_bytes + 255) & !255;
+        let rows = extent.height * extent.depth;
+        let buffer = Self::create_buffer(device, (padded_bytes_per_row * rows) as u64);
+        encoder.copy_texture_to_buffer(
+            wgpu::TextureCopyView {
+                texture,
+                mip_level,
+                origin,
+            },
+            wgpu::BufferCopyView {
+                buffer: &buffer,
+                layout: wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: padded_bytes_per_row,
+                    rows_per_image: extent.height,
+                },
+            },
+            extent,
+        );
+        let layout = RowLayout {
+            row_bytes,
+            padded_bytes_per_row,
+            rows,
+        };
+        Self::new(buffer, (row_bytes * rows) as usize, Some(layout))
+    }
+
+    fn create_buffer(device: &wgpu::Device, size: wgpu::BufferAddress) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+        })
+    }
+
+    fn new(buffer: wgpu::Buffer, size: usize, rows: Option<RowLayout>) -> Self {
+        let mapping = Box::pin(buffer.slice(..).map_async(wgpu::MapMode::Read));
+        Self {
+            buffer: Some(buffer),
+            size,
+            rows,
+            mapping,
+        }
+    }
+
+    /// The bytes if they've arrived, None while it's still waiting or once they were taken.
+    pub fn try_take(&mut self) -> Option<Result<Vec<u8>, wgpu::BufferAsyncError>> {
+        if self.buffer.is_none() {
+            return None;
+        }
+        let waker = futures::task::noop_waker();
+        match self.poll_unpin(&mut Context::from_waker(&waker)) {
+            Poll::Ready(result) => Some(result),
+            Poll::Pending => None,
+        }
+    }
+
+    /// True once `try_take` or awaiting it has returned the data.
+    pub fn is_taken(&self) -> bool {
+        self.buffer.is_none()
+    }
+}
+
+impl Future for GpuReadback {
+    type Output = Result<Vec<u8>, wgpu::BufferAsyncError>;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        match this.mapping.poll_unpin(context) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Err(error)) => {
+                this.buffer = None;
+                Poll::Ready(Err(error))
+            }
+            Poll::Ready(Ok(())) => {
+                let buffer = this
+                    .buffer
+                    .take()
+                    .expect("GpuReadback polled after it finished.");
+                let bytes = {
+                    let mapped = buffer.slice(..).get_mapped_range();
+                    match this.rows.as_ref() {
+                        Some(rows) => unpad_rows(&mapped, rows),
+                        None => mapped[..this.size].to_vec(),
+                    }
+                };
+                buffer.unmap();
+                Poll::Ready(Ok(bytes))
+            }
+        }
+    }
+}
+
+fn unpad_rows(bytes: &[u8], layout: &RowLayout) -> Vec<u8> {
+    let mut unpadded = Vec::with_capacity((layout.row_bytes * layout.rows) as usize);
+    for row in bytes
+        .chunks(layout.padded_bytes_per_row as usize)
+        .take(layout.rows as usize)
+    {
+        unpadded.extend_from_slice(&row[..layout.row_bytes as usize]);
+    }
+    unpadded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unpad_rows() {
+        let layout = RowLayout {
+            row_bytes: 3,
+            padded_bytes_per_row: 4,
+            rows: 2,
+        };
+        let bytes = [1, 2, 3, 0, 4, 5, 6, 0, 9, 9, 9, 9];
+        assert_eq!(unpad_rows(&bytes, &layout), [1, 2, 3, 4, 5, 6]);
+    }
+}
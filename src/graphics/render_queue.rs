@@ -0,0 +1,35 @@
+/// Decides when a material is drawn, lower queues are drawn first.
+/// Queues from `Overlay` up are drawn after everything else with a cleared depth buffer,
+/// so they always end up on top, for example a first person weapon.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RenderQueue {
+    Background,
+    Opaque,
+    Transparent,
+    Overlay,
+    /// Any queue value, for example `Custom(2500)` draws between opaque and transparent.
+    Custom(i32),
+}
+
+impl Default for RenderQueue {
+    fn default() -> Self {
+        RenderQueue::Opaque
+    }
+}
+
+impl RenderQueue {
+    pub const BACKGROUND: i32 = 1000;
+    pub const OPAQUE: i32 = 2000;
+    pub const TRANSPARENT: i32 = 3000;
+    pub const OVERLAY: i32 = 4000;
+
+    pub fn value(&self) -> i32 {
+        match self {
+            RenderQueue::Background => Self::BACKGROUND,
+            RenderQueue::Opaque => Self::OPAQUE,
+            RenderQueue::Transparent => Self::TRANSPARENT,
+            RenderQueue::Overlay => Self::OVERLAY,
+            RenderQueue::Custom(value) => *value,
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use super::MAX_GI_PROBES;
+
+/// How much the renderer does per frame, see `RenderSettings::render_path`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RenderPath {
+    /// Everything the engine supports.
+    Full,
+    /// For GL, the web and other low end adapters. MSAA is turned off, 32 bit float
+    /// textures are avoided where they'd need filtering, and the PBR shader skips
+    /// reflection probes and soft shadows. Light culling is skipped without compute and
+    /// dynamic GI is turned off.
+    Reduced,
+}
+
+/// Renderer options picked when the application is created, see `Application::new_with_settings`.
+/// It's also a resource so systems can read the settings that were actually used.
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    /// Samples per pixel for MSAA, 1 turns it off.
+    /// It's lowered to the closest count the adapter supports, usually 1, 2, 4 or 8.
+    /// Custom pipelines that draw into "frame" need the same `PipelineDesc::sample_count`.
+    /// Only read when the app starts: the "frame_msaa" attachment, the depth buffer and every
+    /// pipeline that draws into "frame", including the ones custom code made in
+    /// `AppState::load`, are built with it. Changing it on the resource afterwards isn't
+    /// supported, a settings menu should save it and apply it the next time the app starts.
+    pub sample_count: u32,
+    /// Stores text glyphs as signed distance fields, they stay sharp when scaled and fewer
+    /// sizes need their own glyphs in the atlas. Off by default since small text looks a
+    /// little softer. Only read when the app starts.
+    pub sdf_text: bool,
+    /// None picks `RenderPath::Reduced` on low end adapters, see
+    /// `GpuCapabilities::is_low_end`, and `RenderPath::Full` everywhere else.
+    pub render_path: Option<RenderPath>,
+    /// `Fifo` waits for vsync, `Mailbox` and `Immediate` don't. Can be changed while running
+    /// with `Application::set_present_mode`.
+    pub present_mode: wgpu::PresentMode,
+    /// Which GPU to ask for on machines with more than one.
+    pub power_preference: wgpu::PowerPreference,
+    /// Backends to look for an adapter on first, None means Vulkan, Metal and DX12.
+    /// If none of them have one any backend is used.
+    pub backends: Option<wgpu::BackendBit>,
+    /// Probes only capture entities marked `Static`, so whatever happened to be moving past
+    /// when a probe was baked doesn't stay in it's reflections.
+    pub bake_static_only: bool,
+    /// How many frames the CPU can submit before it waits for the GPU to finish the oldest,
+    /// from 1 to 3. More smooths out uneven frames at the cost of a little latency.
+    pub frames_in_flight: u32,
+    /// Makes screen text and the imgui UI bigger or smaller on top of the window's DPI
+    /// scaling. Can be changed while running with `Application::set_ui_scale`.
+    pub ui_scale: f32,
+    /// Lights the scene with a grid of probes that are re-rendered while the game runs,
+    /// see `DynamicGI`. Off by default and on the reduced path, it's meant for high end GPUs.
+    pub dynamic_gi: bool,
+    /// How many dynamic GI probes are re-rendered each frame, each one draws the scene six
+    /// times. More makes the light react faster to changes.
+    pub gi_probe_updates: u32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            sample_count: 1,
+            sdf_text: false,
+            render_path: None,
+            present_mode: wgpu::PresentMode::Fifo,
+            power_preference: wgpu::PowerPreference::Default,
+            backends: None,
+            bake_static_only: false,
+            frames_in_flight: 2,
+            ui_scale: 1.0,
+            dynamic_gi: false,
+            gi_probe_updates: 2,
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    pub fn with_sdf_text(mut self, sdf_text: bool) -> Self {
+        self.sdf_text = sdf_text;
+        self
+    }
+
+    pub fn with_render_path(mut self, render_path: RenderPath) -> Self {
+        self.render_path = Some(render_path);
+        self
+    }
+
+    pub fn with_present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    pub fn with_power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    pub fn with_backends(mut self, backends: wgpu::BackendBit) -> Self {
+        self.backends = Some(backends);
+        self
+    }
+
+    pub fn with_bake_static_only(mut self, bake_static_only: bool) -> Self {
+        self.bake_static_only = bake_static_only;
+        self
+    }
+
+    pub fn with_frames_in_flight(mut self, frames_in_flight: u32) -> Self {
+        self.frames_in_flight = frames_in_flight;
+        self
+    }
+
+    pub fn with_ui_scale(mut self, ui_scale: f32) -> Self {
+        self.ui_scale = ui_scale;
+        self
+    }
+
+    pub fn with_dynamic_gi(mut self, dynamic_gi: bool) -> Self {
+        self.dynamic_gi = dynamic_gi;
+        self
+    }
+
+    pub fn with_gi_probe_updates(mut self, gi_probe_updates: u32) -> Self {
+        self.gi_probe_updates = gi_probe_updates;
+        self
+    }
+
+    /// True when the reduced render path is used, only known once the app has started.
+    pub fn reduced(&self) -> bool {
+        self.render_path == Some(RenderPath::Reduced)
+    }
+
+    /// True when the scene is drawn into a multisampled target and resolved into "frame".
+    pub fn msaa(&self) -> bool {
+        self.sample_count > 1
+    }
+
+    // WebGPU only guarantees 1 and 4 samples, native backends handle up to 8.
+    pub(crate) fn supported_sample_counts(backend: wgpu::Backend) -> &'static [u32] {
+        match backend {
+            wgpu::Backend::BrowserWebGpu => &[1, 4],
+            _ => &[1, 2, 4, 8],
+        }
+    }
+
+    /// Lowers anything the adapter can't do to the closest setting it can.
+    pub(crate) fn validate(&mut self, backend: wgpu::Backend) {
+        let sample_count = Self::supported_sample_counts(backend)
+            .iter()
+            .rev()
+            .find(|count| **count <= self.sample_count)
+            .copied()
+            .unwrap_or(1);
+        if sample_count != self.sample_count {
+            log::warn!(
+                "A sample count of {} isn't supported by the {:?} backend, using {} instead.",
+                self.sample_count,
+                backend,
+                sample_count
+            );
+            self.sample_count = sample_count;
+        }
+        self.frames_in_flight = self.frames_in_flight.max(1).min(3);
+        if self.ui_scale.is_nan() || self.ui_scale <= 0.0 {
+            self.ui_scale = 1.0;
+        }
+        self.gi_probe_updates = self.gi_probe_updates.max(1).min(MAX_GI_PROBES as u32);
+    }
+
+    /// Picks the render path if it wasn't set and turns off what the reduced path can't do.
+    pub(crate) fn adapt(&mut self, low_end: bool) {
+        let render_path = *self.render_path.get_or_insert(if low_end {
+            RenderPath::Reduced
+        } else {
+            RenderPath::Full
+        });
+        if render_path == RenderPath::Reduced {
+            log::info!("Using the reduced render path.");
+            self.sample_count = 1;
+            self.dynamic_gi = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_sample_count() {
+        let mut settings = RenderSettings::default().with_sample_count(6);
+        settings.validate(wgpu::Backend::Vulkan);
+        assert_eq!(settings.sample_count, 4);
+
+        let mut settings = RenderSettings::default().with_sample_count(8);
+        settings.validate(wgpu::Backend::BrowserWebGpu);
+        assert_eq!(settings.sample_count, 4);
+
+        let mut settings = RenderSettings::default().with_sample_count(0);
+        settings.validate(wgpu::Backend::Metal);
+        assert_eq!(settings.sample_count, 1);
+        assert_eq!(settings.frames_in_flight, 2);
+
+        let mut settings = RenderSettings::default().with_frames_in_flight(8);
+        settings.validate(wgpu::Backend::Vulkan);
+        assert_eq!(settings.frames_in_flight, 3);
+
+        let mut settings = RenderSettings::default().with_ui_scale(-2.0);
+        settings.validate(wgpu::Backend::Vulkan);
+        assert_eq!(settings.ui_scale, 1.0);
+
+        let mut settings = RenderSettings::default().with_gi_probe_updates(0);
+        settings.validate(wgpu::Backend::Vulkan);
+        assert_eq!(settings.gi_probe_updates, 1);
+    }
+
+    #[test]
+    fn test_adapt_render_path() {
+        let mut settings = RenderSettings::default().with_sample_count(4);
+        settings.adapt(false);
+        assert_eq!(settings.render_path, Some(RenderPath::Full));
+        assert_eq!(settings.sample_count, 4);
+
+        let mut settings = RenderSettings::default()
+            .with_sample_count(4)
+            .with_dynamic_gi(true);
+        settings.adapt(true);
+        assert!(settings.reduced());
+        assert_eq!(settings.sample_count, 1);
+        assert!(!settings.dynamic_gi);
+
+        let mut settings = RenderSettings::default().with_render_path(RenderPath::Full);
+        settings.adapt(true);
+        assert!(!settings.reduced());
+    }
+}
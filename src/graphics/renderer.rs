@@ -1,17 +1,75 @@
-use super::resources::GPUResourceManager;
+use futures::channel::oneshot;
+use legion::prelude::{Entity, World};
 use legion::systems::resource::Resources;
+use nalgebra_glm::Vec2;
+use std::path::PathBuf;
+
+use super::{
+    capture::{create_capture_texture, CaptureDestination, CaptureTarget, PendingCapture},
+    picking::{self, Pick, PickMode},
+    resources::GPUResourceManager,
+    FramesInFlight, GpuCapabilities, RenderSettings,
+};
 
 pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 pub const FRAME_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8UnormSrgb;
+/// The scene renders into a texture of this format, post processing maps it to `FRAME_FORMAT`.
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
 
 pub struct DepthTexture(pub wgpu::TextureView);
 
+/// Creates the depth buffer the scene is drawn with, it has the same sample count as the frame.
+pub(crate) fn create_depth_texture(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+    sample_count: u32,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format: DEPTH_FORMAT,
+        // Sampled by aerial perspective.
+        usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+        label: None,
+    })
+}
+
+/// The texture a frame is drawn into, the swap chain's or the renderer's own when headless.
+pub enum FrameOutput {
+    SwapChain(wgpu::SwapChainOutput),
+    Texture(wgpu::TextureView),
+}
+
+impl FrameOutput {
+    pub fn view(&self) -> &wgpu::TextureView {
+        match self {
+            FrameOutput::SwapChain(output) => &output.view,
+            FrameOutput::Texture(view) => view,
+        }
+    }
+}
+
 pub struct Renderer {
-    pub(crate) surface: wgpu::Surface,
+    /// None when rendering headless.
+    pub(crate) surface: Option<wgpu::Surface>,
     pub size: winit::dpi::PhysicalSize<u32>,
     adapter: wgpu::Adapter,
-    pub(crate) swap_chain: wgpu::SwapChain,
-    pub(crate) window: winit::window::Window,
+    pub(crate) swap_chain: Option<wgpu::SwapChain>,
+    /// None when rendering headless.
+    pub(crate) window: Option<winit::window::Window>,
+    // Frames are drawn into this instead of a swap chain when rendering headless.
+    offscreen: Option<wgpu::Texture>,
+    // The final image is copied out of this when a capture is requested with a window.
+    capture_texture: Option<wgpu::Texture>,
+    capture_requests: Vec<CaptureDestination>,
+    pending_captures: Vec<PendingCapture>,
 }
 
 impl Renderer {
@@ -19,26 +77,60 @@ impl Renderer {
         window: winit::window::Window,
         size: winit::dpi::PhysicalSize<u32>,
         resources: &mut Resources,
+        settings: RenderSettings,
     ) -> Self {
         let instance = wgpu::Instance::new();
         let surface = unsafe { instance.create_surface(&window) };
-        let adapter = instance
-            .request_adapter(
-                &wgpu::RequestAdapterOptions {
-                    power_preference: wgpu::PowerPreference::Default,
-                    compatible_surface: Some(&surface),
-                },
-                wgpu::BackendBit::PRIMARY,
-            )
-            .await
-            .unwrap();
+        Self::create(instance, Some(surface), Some(window), size, resources, settings).await
+    }
+
+    /// Creates a renderer without a window, frames are drawn into a texture that can be read
+    /// back with `capture_frame`. Used for tests and tools that don't need to show anything.
+    pub(crate) async fn new_headless(
+        size: winit::dpi::PhysicalSize<u32>,
+        resources: &mut Resources,
+        settings: RenderSettings,
+    ) -> Self {
+        Self::create(wgpu::Instance::new(), None, None, size, resources, settings).await
+    }
+
+    async fn create(
+        instance: wgpu::Instance,
+        surface: Option<wgpu::Surface>,
+        window: Option<winit::window::Window>,
+        size: winit::dpi::PhysicalSize<u32>,
+        resources: &mut Resources,
+        mut settings: RenderSettings,
+    ) -> Self {
+        let options = wgpu::RequestAdapterOptions {
+            power_preference: settings.power_preference,
+            compatible_surface: surface.as_ref(),
+        };
+        let preferred = settings.backends.unwrap_or(wgpu::BackendBit::PRIMARY);
+        let adapter = match instance.request_adapter(&options, preferred).await {
+            Some(adapter) => adapter,
+            // GL and DX11 when Vulkan, Metal and DX12 aren't around, they get the reduced path.
+            None => {
+                log::warn!("No adapter found for {:?}, trying any backend.", preferred);
+                instance
+                    .request_adapter(&options, wgpu::BackendBit::all())
+                    .await
+                    .unwrap()
+            }
+        };
+        settings.validate(adapter.get_info().backend);
+
+        let limits = wgpu::Limits::default();
+        let capabilities = GpuCapabilities::new(&adapter.get_info(), &limits);
+        log::info!("Using {} on {:?}.", capabilities.adapter_name, capabilities.backend);
+        settings.adapt(capabilities.is_low_end());
 
         let (device, queue) = adapter
             .request_device(&wgpu::DeviceDescriptor {
                 extensions: wgpu::Extensions {
                     anisotropic_filtering: false,
                 },
-                limits: wgpu::Limits::default(),
+                limits,
             }, None)
             .await
             .unwrap();
@@ -48,29 +140,31 @@ impl Renderer {
             format: FRAME_FORMAT,
             width: size.width,
             height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
+            present_mode: settings.present_mode,
+        };
+        let swap_chain = surface
+            .as_ref()
+            .map(|surface| device.create_swap_chain(surface, &sc_desc));
+        let offscreen = match surface {
+            Some(_) => None,
+            None => Some(create_capture_texture(&device, size.width, size.height)),
         };
-        let swap_chain = device.create_swap_chain(&surface, &sc_desc);
-
-        let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
-            size: wgpu::Extent3d {
-                width: sc_desc.width,
-                height: sc_desc.height,
-                depth: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: DEPTH_FORMAT,
-            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
-            label: None,
-        });
+
+        let depth_texture = create_depth_texture(
+            &device,
+            sc_desc.width,
+            sc_desc.height,
+            settings.sample_count,
+        );
 
         resources.insert(GPUResourceManager::new(&device));
         resources.insert(sc_desc);
         resources.insert(queue);
         resources.insert(device);
         resources.insert(DepthTexture(depth_texture.create_default_view()));
+        resources.insert(CaptureTarget(None));
+        resources.insert(settings);
+        resources.insert(capabilities);
 
         Self {
             surface,
@@ -78,12 +172,124 @@ impl Renderer {
             adapter,
             swap_chain,
             window,
+            offscreen,
+            capture_texture: None,
+            capture_requests: Vec::new(),
+            pending_captures: Vec::new(),
+        }
+    }
+
+    pub(crate) fn render(&mut self) -> FrameOutput {
+        match (self.swap_chain.as_mut(), self.offscreen.as_ref()) {
+            (Some(swap_chain), _) => FrameOutput::SwapChain(swap_chain.get_next_texture().unwrap()),
+            (None, Some(offscreen)) => FrameOutput::Texture(offscreen.create_default_view()),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    /// The closest mesh entity under a position on the screen in pixels, like the mouse position.
+    /// Tests the meshes' triangles on the CPU, with split screen it picks through the camera of
+    /// the player the position is over.
+    pub fn pick(
+        &self,
+        world: &World,
+        resources: &Resources,
+        screen_position: Vec2,
+    ) -> Option<Entity> {
+        self.pick_with(world, resources, screen_position, PickMode::Triangles)
+            .map(|pick| pick.entity)
+    }
+
+    /// Same as `pick` but lets you choose how precise it is and returns where it hit.
+    pub fn pick_with(
+        &self,
+        world: &World,
+        resources: &Resources,
+        screen_position: Vec2,
+        mode: PickMode,
+    ) -> Option<Pick> {
+        let screen_size = Vec2::new(self.size.width as f32, self.size.height as f32);
+        picking::pick(world, resources, screen_position, screen_size, mode)
+    }
+
+    /// Reads the next frame back once it's drawn, without the UI.
+    /// The image arrives a frame or two later, check the receiver with `try_recv` each update
+    /// or use `wait_for_captures` to block until it's there.
+    pub fn capture_frame(&mut self) -> oneshot::Receiver<image::RgbaImage> {
+        let (sender, receiver) = oneshot::channel();
+        self.capture_requests.push(CaptureDestination::Image(sender));
+        receiver
+    }
+
+    /// Same as `capture_frame` but writes the image to a PNG file.
+    pub fn save_screenshot<T: Into<PathBuf>>(&mut self, path: T) {
+        self.capture_requests.push(CaptureDestination::Png(path.into()));
+    }
+
+    /// Blocks until every capture that was copied out of a frame has been read back.
+    pub fn wait_for_captures(&mut self, resources: &Resources) {
+        if !self.pending_captures.is_empty() {
+            resources
+                .get::<wgpu::Device>()
+                .unwrap()
+                .poll(wgpu::Maintain::Wait);
+            self.poll_captures();
         }
     }
 
-    pub(crate) fn render(&mut self) -> wgpu::SwapChainOutput {
-        let output = self.swap_chain.get_next_texture().unwrap();
+    // Hands out the captures that finished reading back.
+    pub(crate) fn poll_captures(&mut self) {
+        let pending = std::mem::replace(&mut self.pending_captures, Vec::new());
+        self.pending_captures = pending
+            .into_iter()
+            .filter_map(|mut capture| if capture.finish() { None } else { Some(capture) })
+            .collect();
+    }
 
-        output
+    // Gives post processing somewhere to draw the final image when this frame is captured.
+    pub(crate) fn prepare_capture(&mut self, resources: &Resources) {
+        if self.capture_requests.is_empty() || self.offscreen.is_some() {
+            return;
+        }
+        let device = resources.get::<wgpu::Device>().unwrap();
+        let (width, height) = (self.size.width, self.size.height);
+        let texture = create_capture_texture(&device, width, height);
+        resources.get_mut::<CaptureTarget>().unwrap().0 = Some(texture.create_default_view());
+        self.capture_texture = Some(texture);
+    }
+
+    // Copies the frame that was just submitted out for each capture request.
+    pub(crate) fn finish_capture(&mut self, resources: &Resources) {
+        if self.capture_requests.is_empty() {
+            return;
+        }
+        resources.get_mut::<CaptureTarget>().unwrap().0 = None;
+        let capture_texture = self.capture_texture.take();
+        let texture = match capture_texture.as_ref().or_else(|| self.offscreen.as_ref()) {
+            Some(texture) => texture,
+            // Requested after this frame's capture target was set up, it'll be in the next one.
+            None => return,
+        };
+
+        let device = resources.get::<wgpu::Device>().unwrap();
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("frame capture"),
+        });
+        let (width, height) = (self.size.width, self.size.height);
+        for destination in self.capture_requests.drain(..) {
+            self.pending_captures.push(PendingCapture::new(
+                &device,
+                &mut encoder,
+                texture,
+                width,
+                height,
+                destination,
+            ));
+        }
+        // Goes after the frame's passes in the same submission.
+        resources
+            .get::<FramesInFlight>()
+            .unwrap()
+            .submit(Some(encoder.finish()));
     }
 }
@@ -1,7 +1,19 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
-use super::BindGroup;
-use crate::graphics::pipelines::{GlobalUniform, LightingUniform};
+use super::{BindGroup, SamplerCache};
+use crate::graphics::{
+    ao_volume::{AoVolumeUniform, AO_VOLUME_FORMAT, MAX_AO_VOXELS},
+    dynamic_gi::{gi_probe_buffer_size, DynamicGIUniform},
+    light_clusters::{cluster_buffer_size, ClusterUniform, GpuLight, MAX_CLUSTERED_LIGHTS},
+    light_cookies::{COOKIE_FORMAT, COOKIE_LAYERS, COOKIE_SIZE},
+    material::SamplerInfo,
+    pipelines::{
+        shadow::{ShadowUniform, SHADOW_FORMAT, SHADOW_LAYERS, SHADOW_MAP_SIZE},
+        GlobalUniform, LightingUniform,
+    },
+    water::WaterUniform,
+};
+use crate::{AssetError, AssetManager};
 
 /// Stores bind groups for consumption by pipelines.
 /// Also can store buffers, but it's not required.
@@ -13,10 +25,183 @@ pub struct GPUResourceManager {
     multi_buffer: HashMap<String, HashMap<u32, wgpu::Buffer>>,
     
     buffers: HashMap<String, wgpu::Buffer>,
+    compute_pipelines: HashMap<String, wgpu::ComputePipeline>,
 
     pub global_uniform_buffer: wgpu::Buffer,
     pub global_lighting_buffer: wgpu::Buffer,
+    pub global_shadow_buffer: wgpu::Buffer,
+    /// Water volumes for caustics and wet surfaces.
+    pub(crate) global_water_buffer: wgpu::Buffer,
     pub global_bind_group: wgpu::BindGroup,
+    /// Same as the global bind group but with the camera's view model projection.
+    pub view_model_uniform_buffer: wgpu::Buffer,
+    pub view_model_bind_group: wgpu::BindGroup,
+
+    // Point and spot lights, the cluster settings and the lights in each cluster.
+    pub(crate) light_buffer: wgpu::Buffer,
+    pub(crate) cluster_uniform_buffer: wgpu::Buffer,
+    #[allow(dead_code)]
+    cluster_buffer: wgpu::Buffer,
+    pub(crate) light_culling_bind_group: wgpu::BindGroup,
+
+    // One view per cascade and one for the spot light atlas, used as the depth attachments
+    // when rendering shadows.
+    pub(crate) shadow_views: Vec<wgpu::TextureView>,
+    #[allow(dead_code)]
+    shadow_texture: wgpu::Texture,
+    shadow_array_view: wgpu::TextureView,
+    shadow_compare_sampler: wgpu::Sampler,
+    shadow_depth_sampler: wgpu::Sampler,
+
+    // Spot and point light cookies, loaded into it's layers by `LightCookies`.
+    pub(crate) cookie_texture: wgpu::Texture,
+    cookie_view: wgpu::TextureView,
+    cookie_sampler: wgpu::Sampler,
+
+    // The dynamic GI grid and the irradiance of it's probes, see `DynamicGI`.
+    pub(crate) gi_uniform_buffer: wgpu::Buffer,
+    pub(crate) gi_probe_buffer: wgpu::Buffer,
+
+    // The baked ambient occlusion of static geometry, see `AoVolume`.
+    pub(crate) ao_volume_uniform_buffer: wgpu::Buffer,
+    pub(crate) ao_volume_texture: wgpu::Texture,
+    ao_volume_view: wgpu::TextureView,
+
+    // Split-screen players after the first, the first player uses the bindings above.
+    player_views: Vec<PlayerView>,
+
+    samplers: SamplerCache,
+}
+
+/// The camera and light cluster bindings of a split-screen player.
+struct PlayerView {
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    view_model_uniform_buffer: wgpu::Buffer,
+    view_model_bind_group: wgpu::BindGroup,
+    cluster_uniform_buffer: wgpu::Buffer,
+    #[allow(dead_code)]
+    cluster_buffer: wgpu::Buffer,
+    light_culling_bind_group: wgpu::BindGroup,
+}
+
+fn create_global_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    uniform_buffer: &wgpu::Buffer,
+    lighting_buffer: &wgpu::Buffer,
+    shadow_buffer: &wgpu::Buffer,
+    shadow_view: &wgpu::TextureView,
+    shadow_compare_sampler: &wgpu::Sampler,
+    shadow_depth_sampler: &wgpu::Sampler,
+    light_buffer: &wgpu::Buffer,
+    cluster_uniform_buffer: &wgpu::Buffer,
+    cluster_buffer: &wgpu::Buffer,
+    water_buffer: &wgpu::Buffer,
+    cookie_view: &wgpu::TextureView,
+    cookie_sampler: &wgpu::Sampler,
+    gi_uniform_buffer: &wgpu::Buffer,
+    gi_probe_buffer: &wgpu::Buffer,
+    ao_volume_uniform_buffer: &wgpu::Buffer,
+    ao_volume_view: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        bindings: &[
+            wgpu::Binding {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(uniform_buffer.slice(..)),
+            },
+            wgpu::Binding {
+                binding: 1,
+                resource: wgpu::BindingResource::Buffer(lighting_buffer.slice(..)),
+            },
+            wgpu::Binding {
+                binding: 2,
+                resource: wgpu::BindingResource::Buffer(shadow_buffer.slice(..)),
+            },
+            wgpu::Binding {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(shadow_view),
+            },
+            wgpu::Binding {
+                binding: 4,
+                resource: wgpu::BindingResource::Sampler(shadow_compare_sampler),
+            },
+            wgpu::Binding {
+                binding: 5,
+                resource: wgpu::BindingResource::Sampler(shadow_depth_sampler),
+            },
+            wgpu::Binding {
+                binding: 6,
+                resource: wgpu::BindingResource::Buffer(light_buffer.slice(..)),
+            },
+            wgpu::Binding {
+                binding: 7,
+                resource: wgpu::BindingResource::Buffer(cluster_uniform_buffer.slice(..)),
+            },
+            wgpu::Binding {
+                binding: 8,
+                resource: wgpu::BindingResource::Buffer(cluster_buffer.slice(..)),
+            },
+            wgpu::Binding {
+                binding: 9,
+                resource: wgpu::BindingResource::Buffer(water_buffer.slice(..)),
+            },
+            wgpu::Binding {
+                binding: 10,
+                resource: wgpu::BindingResource::TextureView(cookie_view),
+            },
+            wgpu::Binding {
+                binding: 11,
+                resource: wgpu::BindingResource::Sampler(cookie_sampler),
+            },
+            wgpu::Binding {
+                binding: 12,
+                resource: wgpu::BindingResource::Buffer(gi_uniform_buffer.slice(..)),
+            },
+            wgpu::Binding {
+                binding: 13,
+                resource: wgpu::BindingResource::Buffer(gi_probe_buffer.slice(..)),
+            },
+            wgpu::Binding {
+                binding: 14,
+                resource: wgpu::BindingResource::Buffer(ao_volume_uniform_buffer.slice(..)),
+            },
+            wgpu::Binding {
+                binding: 15,
+                resource: wgpu::BindingResource::TextureView(ao_volume_view),
+            },
+        ],
+        label: Some("Globals"),
+    })
+}
+
+fn create_light_culling_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    light_buffer: &wgpu::Buffer,
+    cluster_uniform_buffer: &wgpu::Buffer,
+    cluster_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        bindings: &[
+            wgpu::Binding {
+                binding: 6,
+                resource: wgpu::BindingResource::Buffer(light_buffer.slice(..)),
+            },
+            wgpu::Binding {
+                binding: 7,
+                resource: wgpu::BindingResource::Buffer(cluster_uniform_buffer.slice(..)),
+            },
+            wgpu::Binding {
+                binding: 8,
+                resource: wgpu::BindingResource::Buffer(cluster_buffer.slice(..)),
+            },
+        ],
+        label: Some("light_culling"),
+    })
 }
 
 impl GPUResourceManager {
@@ -36,6 +221,163 @@ impl GPUResourceManager {
             wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
         );
 
+        let global_shadow_buffer = device.create_buffer_with_data(
+            bytemuck::bytes_of(&ShadowUniform::default()),
+            wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        );
+
+        let global_water_buffer = device.create_buffer_with_data(
+            bytemuck::bytes_of(&WaterUniform::default()),
+            wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        );
+
+        let light_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("lights"),
+            size: (MAX_CLUSTERED_LIGHTS * std::mem::size_of::<GpuLight>()) as u64,
+            usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+        });
+        let cluster_uniform_buffer = device.create_buffer_with_data(
+            bytemuck::bytes_of(&ClusterUniform::default()),
+            wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        );
+        // Filled in by the light culling compute pass.
+        let cluster_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("light_clusters"),
+            size: cluster_buffer_size(),
+            usage: wgpu::BufferUsage::STORAGE,
+        });
+
+        // Shadow map cascades are stored as layers in a single texture, the spot light atlas
+        // is the last layer.
+        let shadow_texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: SHADOW_MAP_SIZE,
+                height: SHADOW_MAP_SIZE,
+                depth: SHADOW_LAYERS as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_FORMAT,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            label: Some("shadow_map"),
+        });
+        let shadow_array_view = shadow_texture.create_view(&wgpu::TextureViewDescriptor {
+            label: None,
+            format: SHADOW_FORMAT,
+            dimension: wgpu::TextureViewDimension::D2Array,
+            aspect: wgpu::TextureAspect::default(),
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            array_layer_count: SHADOW_LAYERS as u32,
+        });
+        let shadow_views = (0..SHADOW_LAYERS as u32)
+            .map(|layer| {
+                shadow_texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: None,
+                    format: SHADOW_FORMAT,
+                    dimension: wgpu::TextureViewDimension::D2,
+                    aspect: wgpu::TextureAspect::default(),
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer: layer,
+                    array_layer_count: 1,
+                })
+            })
+            .collect();
+        let shadow_compare_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("shadow_compare"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: -100.0,
+            lod_max_clamp: 100.0,
+            compare: wgpu::CompareFunction::LessEqual,
+        });
+        // PCSS needs the raw depth values for it's blocker search.
+        let shadow_depth_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("shadow_depth"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: -100.0,
+            lod_max_clamp: 100.0,
+            compare: wgpu::CompareFunction::Undefined,
+        });
+
+        let cookie_texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: COOKIE_SIZE,
+                height: COOKIE_SIZE,
+                depth: COOKIE_LAYERS,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: COOKIE_FORMAT,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+            label: Some("light_cookies"),
+        });
+        let cookie_view = cookie_texture.create_view(&wgpu::TextureViewDescriptor {
+            label: None,
+            format: COOKIE_FORMAT,
+            dimension: wgpu::TextureViewDimension::D2Array,
+            aspect: wgpu::TextureAspect::default(),
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            array_layer_count: COOKIE_LAYERS,
+        });
+        let cookie_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("light_cookies"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: -100.0,
+            lod_max_clamp: 100.0,
+            compare: wgpu::CompareFunction::Undefined,
+        });
+
+        let gi_uniform_buffer = device.create_buffer_with_data(
+            bytemuck::bytes_of(&DynamicGIUniform::default()),
+            wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        );
+        let gi_probe_buffer = device.create_buffer_with_data(
+            &vec![0u8; gi_probe_buffer_size() as usize],
+            wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+        );
+
+        // Baked volumes are copied into the corner of the texture, the uniform says how much
+        // of it they use. It's sampled with the cookie sampler.
+        let ao_volume_uniform_buffer = device.create_buffer_with_data(
+            bytemuck::bytes_of(&AoVolumeUniform::default()),
+            wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        );
+        let ao_volume_texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: MAX_AO_VOXELS,
+                height: MAX_AO_VOXELS,
+                depth: MAX_AO_VOXELS,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: AO_VOLUME_FORMAT,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+            label: Some("ao_volume"),
+        });
+        let ao_volume_view = ao_volume_texture.create_default_view();
+
         let global_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 bindings: &[
@@ -51,27 +393,180 @@ impl GPUResourceManager {
                         visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
                         ty: wgpu::BindingType::UniformBuffer { dynamic: false },
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        // SHADOW DATA
+                        binding: 2,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        // SHADOW MAP
+                        binding: 3,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::SampledTexture {
+                            multisampled: false,
+                            component_type: wgpu::TextureComponentType::Float,
+                            dimension: wgpu::TextureViewDimension::D2Array,
+                        },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler { comparison: true },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler { comparison: false },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        // POINT AND SPOT LIGHTS
+                        binding: 6,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::StorageBuffer {
+                            dynamic: false,
+                            readonly: true,
+                        },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        // CLUSTER INFO
+                        binding: 7,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        // LIGHTS PER CLUSTER
+                        binding: 8,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::StorageBuffer {
+                            dynamic: false,
+                            readonly: true,
+                        },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        // WATER VOLUMES
+                        binding: 9,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        // LIGHT COOKIES
+                        binding: 10,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::SampledTexture {
+                            multisampled: false,
+                            component_type: wgpu::TextureComponentType::Float,
+                            dimension: wgpu::TextureViewDimension::D2Array,
+                        },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 11,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler { comparison: false },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        // DYNAMIC GI GRID
+                        binding: 12,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        // DYNAMIC GI PROBES
+                        binding: 13,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::StorageBuffer {
+                            dynamic: false,
+                            readonly: true,
+                        },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        // AO VOLUME
+                        binding: 14,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 15,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::SampledTexture {
+                            multisampled: false,
+                            component_type: wgpu::TextureComponentType::Float,
+                            dimension: wgpu::TextureViewDimension::D3,
+                        },
+                    },
                 ],
                 label: Some("Globals"),
             });
 
-        let global_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &global_bind_group_layout,
-            bindings: &[
-                wgpu::Binding {
-                    binding: 0,
-                    resource: wgpu::BindingResource::Buffer(global_uniform_buffer.slice(..)),
-                },
-                wgpu::Binding {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Buffer(global_lighting_buffer.slice(..)),
-                },
-            ],
-            label: Some("Globals"),
-        });
+        let global_bind_group_for = |uniform_buffer: &wgpu::Buffer| {
+            create_global_bind_group(
+                device,
+                &global_bind_group_layout,
+                uniform_buffer,
+                &global_lighting_buffer,
+                &global_shadow_buffer,
+                &shadow_array_view,
+                &shadow_compare_sampler,
+                &shadow_depth_sampler,
+                &light_buffer,
+                &cluster_uniform_buffer,
+                &cluster_buffer,
+                &global_water_buffer,
+                &cookie_view,
+                &cookie_sampler,
+                &gi_uniform_buffer,
+                &gi_probe_buffer,
+                &ao_volume_uniform_buffer,
+                &ao_volume_view,
+            )
+        };
+        let global_bind_group = global_bind_group_for(&global_uniform_buffer);
+
+        let view_model_uniform_buffer = device.create_buffer_with_data(
+            bytemuck::bytes_of(&GlobalUniform::default()),
+            wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        );
+        let view_model_bind_group = global_bind_group_for(&view_model_uniform_buffer);
 
         bind_group_layouts.insert("globals".to_string(), global_bind_group_layout);
 
+        // The light culling compute pass uses the same bindings as the globals but writes the clusters.
+        let light_culling_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                bindings: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: wgpu::ShaderStage::COMPUTE,
+                        ty: wgpu::BindingType::StorageBuffer {
+                            dynamic: false,
+                            readonly: true,
+                        },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 7,
+                        visibility: wgpu::ShaderStage::COMPUTE,
+                        ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 8,
+                        visibility: wgpu::ShaderStage::COMPUTE,
+                        ty: wgpu::BindingType::StorageBuffer {
+                            dynamic: false,
+                            readonly: false,
+                        },
+                    },
+                ],
+                label: Some("light_culling"),
+            });
+        let light_culling_bind_group = create_light_culling_bind_group(
+            device,
+            &light_culling_layout,
+            &light_buffer,
+            &cluster_uniform_buffer,
+            &cluster_buffer,
+        );
+        bind_group_layouts.insert("light_culling".to_string(), light_culling_layout);
+
         // Local bind group layout
         let local_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -87,12 +582,136 @@ impl GPUResourceManager {
         Self {
             bind_group_layouts,
             buffers: HashMap::new(),
+            compute_pipelines: HashMap::new(),
             single_bind_groups: HashMap::new(),
             multi_bind_groups: HashMap::new(),
             multi_buffer: HashMap::new(),
             global_bind_group,
+            view_model_uniform_buffer,
+            view_model_bind_group,
             global_lighting_buffer,
+            global_shadow_buffer,
+            global_water_buffer,
             global_uniform_buffer,
+            light_buffer,
+            cluster_uniform_buffer,
+            cluster_buffer,
+            light_culling_bind_group,
+            shadow_views,
+            shadow_texture,
+            shadow_array_view,
+            shadow_compare_sampler,
+            shadow_depth_sampler,
+            cookie_texture,
+            cookie_view,
+            cookie_sampler,
+            gi_uniform_buffer,
+            gi_probe_buffer,
+            ao_volume_uniform_buffer,
+            ao_volume_texture,
+            ao_volume_view,
+            player_views: Vec::new(),
+            samplers: SamplerCache::default(),
+        }
+    }
+
+    /// Returns the sampler for `info`, creating it the first time it's asked for.
+    pub fn get_sampler(&self, device: &wgpu::Device, info: &SamplerInfo) -> Arc<wgpu::Sampler> {
+        self.samplers.get(device, info)
+    }
+
+    /// How many unique samplers have been created.
+    pub fn sampler_count(&self) -> usize {
+        self.samplers.len()
+    }
+
+    /// Makes sure there's bindings for the given number of split-screen players.
+    pub(crate) fn prepare_player_views(&mut self, device: &wgpu::Device, players: usize) {
+        while self.player_views.len() + 1 < players {
+            let create_uniform_buffer = || {
+                device.create_buffer_with_data(
+                    bytemuck::bytes_of(&GlobalUniform::default()),
+                    wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+                )
+            };
+            let uniform_buffer = create_uniform_buffer();
+            let view_model_uniform_buffer = create_uniform_buffer();
+            let cluster_uniform_buffer = device.create_buffer_with_data(
+                bytemuck::bytes_of(&ClusterUniform::default()),
+                wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            );
+            let cluster_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("light_clusters"),
+                size: cluster_buffer_size(),
+                usage: wgpu::BufferUsage::STORAGE,
+            });
+
+            let layout = self.get_bind_group_layout("globals").unwrap();
+            let global_bind_group_for = |uniform_buffer: &wgpu::Buffer| {
+                create_global_bind_group(
+                    device,
+                    layout,
+                    uniform_buffer,
+                    &self.global_lighting_buffer,
+                    &self.global_shadow_buffer,
+                    &self.shadow_array_view,
+                    &self.shadow_compare_sampler,
+                    &self.shadow_depth_sampler,
+                    &self.light_buffer,
+                    &cluster_uniform_buffer,
+                    &cluster_buffer,
+                    &self.global_water_buffer,
+                    &self.cookie_view,
+                    &self.cookie_sampler,
+                    &self.gi_uniform_buffer,
+                    &self.gi_probe_buffer,
+                    &self.ao_volume_uniform_buffer,
+                    &self.ao_volume_view,
+                )
+            };
+            let bind_group = global_bind_group_for(&uniform_buffer);
+            let view_model_bind_group = global_bind_group_for(&view_model_uniform_buffer);
+            let light_culling_bind_group = create_light_culling_bind_group(
+                device,
+                self.get_bind_group_layout("light_culling").unwrap(),
+                &self.light_buffer,
+                &cluster_uniform_buffer,
+                &cluster_buffer,
+            );
+
+            self.player_views.push(PlayerView {
+                uniform_buffer,
+                bind_group,
+                view_model_uniform_buffer,
+                view_model_bind_group,
+                cluster_uniform_buffer,
+                cluster_buffer,
+                light_culling_bind_group,
+            });
+        }
+    }
+
+    /// The globals and view model uniform buffers of a split-screen player.
+    pub(crate) fn player_uniform_buffers(&self, player: usize) -> (&wgpu::Buffer, &wgpu::Buffer) {
+        match player.checked_sub(1).and_then(|index| self.player_views.get(index)) {
+            Some(view) => (&view.uniform_buffer, &view.view_model_uniform_buffer),
+            None => (&self.global_uniform_buffer, &self.view_model_uniform_buffer),
+        }
+    }
+
+    /// The globals and view model bind groups of a split-screen player.
+    pub(crate) fn player_bind_groups(&self, player: usize) -> (&wgpu::BindGroup, &wgpu::BindGroup) {
+        match player.checked_sub(1).and_then(|index| self.player_views.get(index)) {
+            Some(view) => (&view.bind_group, &view.view_model_bind_group),
+            None => (&self.global_bind_group, &self.view_model_bind_group),
+        }
+    }
+
+    /// The cluster uniform buffer and light culling bind group of a split-screen player.
+    pub(crate) fn player_light_culling(&self, player: usize) -> (&wgpu::Buffer, &wgpu::BindGroup) {
+        match player.checked_sub(1).and_then(|index| self.player_views.get(index)) {
+            Some(view) => (&view.cluster_uniform_buffer, &view.light_culling_bind_group),
+            None => (&self.cluster_uniform_buffer, &self.light_culling_bind_group),
         }
     }
 
@@ -165,6 +784,26 @@ impl GPUResourceManager {
         }
     }
     
+    /// Removes a multi-buffer, dropping the buffer frees it's GPU memory.
+    pub fn remove_multi_buffer<T: Into<String>>(
+        &mut self,
+        key: T,
+        item_index: u32,
+    ) -> Option<wgpu::Buffer> {
+        self.multi_buffer
+            .get_mut(&key.into())
+            .and_then(|buffers| buffers.remove(&item_index))
+    }
+
+    /// Removes an item's bind groups from every binding index of a multi-bind group.
+    pub fn remove_multi_bind_group<T: Into<String>>(&mut self, key: T, item_index: u32) {
+        if let Some(bindings_hash_map) = self.multi_bind_groups.get_mut(&key.into()) {
+            for bind_groups in bindings_hash_map.values_mut() {
+                bind_groups.remove(&item_index);
+            }
+        }
+    }
+
     /// Let's you retrieve a multi-buffer.
     pub fn get_multi_buffer<T: Into<String>>(
         &self,
@@ -203,6 +842,19 @@ impl GPUResourceManager {
         bind_group.as_ref().unwrap()
     }
 
+    /// Same as `get_multi_bind_group` but returns None instead of panicking.
+    pub fn get_multi_bind_group_option<T: Into<String>>(
+        &self,
+        key: T,
+        binding_index: u32,
+        item_index: u32,
+    ) -> Option<&BindGroup> {
+        self.multi_bind_groups
+            .get(&key.into())
+            .and_then(|bind_groups| bind_groups.get(&binding_index))
+            .and_then(|bind_groups| bind_groups.get(&item_index))
+    }
+
     /// Get's a bind group.
     /// binding_index is associated with an index set inside of the BindGroup.
     pub fn get_bind_group<T: Into<String>>(
@@ -282,4 +934,65 @@ impl GPUResourceManager {
     pub fn get_buffer<T: Into<String>>(&self, name: T) -> &wgpu::Buffer {
         self.buffers.get(&name.into()).unwrap()
     }
+
+    /// Builds a compute pipeline from a `.shader` asset with a compute stage, replacing any
+    /// pipeline with the same name. `layouts` are the bind group layouts for each set in order,
+    /// `ComputeBindings` can make them. Hot reloading the shader doesn't rebuild the pipeline,
+    /// add it again to pick up the changes.
+    pub fn add_compute_pipeline<T: Into<String>>(
+        &mut self,
+        name: T,
+        shader: &str,
+        layouts: &[&wgpu::BindGroupLayout],
+        device: &wgpu::Device,
+        asset_manager: &AssetManager,
+    ) -> Result<(), AssetError> {
+        let module = asset_manager
+            .try_get_shader(shader)?
+            .compute
+            .as_ref()
+            .ok_or_else(|| AssetError::unsupported(shader, "it doesn't have a compute stage"))?;
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: layouts,
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            layout: &layout,
+            compute_stage: wgpu::ProgrammableStageDescriptor {
+                module,
+                entry_point: "main",
+            },
+        });
+        self.compute_pipelines.insert(name.into(), pipeline);
+        Ok(())
+    }
+
+    pub fn get_compute_pipeline<T: Into<String>>(
+        &self,
+        name: T,
+    ) -> Option<&wgpu::ComputePipeline> {
+        self.compute_pipelines.get(&name.into())
+    }
+
+    /// Records a compute pass running the named pipeline with `workgroups` work groups in each
+    /// dimension. The bind groups are set in order starting at set 0.
+    pub fn dispatch<T: Into<String>>(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        name: T,
+        bind_groups: &[&wgpu::BindGroup],
+        workgroups: [u32; 3],
+    ) -> Result<(), AssetError> {
+        let name = name.into();
+        let pipeline = self
+            .compute_pipelines
+            .get(&name)
+            .ok_or_else(|| AssetError::missing("compute pipeline", name.as_str()))?;
+        let mut compute_pass = encoder.begin_compute_pass();
+        compute_pass.set_pipeline(pipeline);
+        for (index, bind_group) in bind_groups.iter().enumerate() {
+            compute_pass.set_bind_group(index as u32, bind_group, &[]);
+        }
+        compute_pass.dispatch(workgroups[0], workgroups[1], workgroups[2]);
+        Ok(())
+    }
 }
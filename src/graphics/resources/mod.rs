@@ -3,13 +3,19 @@ mod gpu_resource_manager;
 mod probe;
 mod probe_manager;
 mod render_target;
+mod sampler_cache;
+mod text_renderer;
 
 pub use bind_group::BindGroup;
 pub use gpu_resource_manager::GPUResourceManager;
 pub use render_target::RenderTarget;
+pub use text_renderer::{GlyphInstance, TextAlign, TextRenderer, TextSection, TextSpan};
 
-pub(crate) use probe::CurrentRenderTarget;
+pub(crate) use probe::{render_cube, CurrentRenderTarget};
 
 pub use probe::{Probe, ProbeFormat, ProbeQuality, ProbeUniform};
 
 pub(crate) use probe_manager::ProbeManager;
+pub use probe_manager::MAX_REFLECTION_PROBES;
+
+pub(crate) use sampler_cache::SamplerCache;
@@ -3,9 +3,11 @@ use legion::prelude::*;
 use nalgebra_glm::{Vec3, Vec4};
 use std::sync::Arc;
 
-use super::{BindGroup, GPUResourceManager, RenderTarget};
+use super::{GPUResourceManager, RenderTarget};
 use crate::{
-    graphics::{pipeline_manager::PipelineManager},
+    graphics::{
+        pipeline_manager::PipelineManager, renderer::HDR_FORMAT, FramesInFlight, GpuCapabilities,
+    },
     scene::components::CameraData,
     AssetManager,
 };
@@ -28,6 +30,22 @@ impl Into<wgpu::TextureFormat> for ProbeFormat {
     }
 }
 
+impl ProbeFormat {
+    // 32 bit cubes become 16 bit on adapters that can't filter them.
+    fn supported(self, resources: &Resources) -> Self {
+        let filterable = resources
+            .get::<GpuCapabilities>()
+            .map_or(true, |capabilities| capabilities.float32_filterable);
+        match self {
+            ProbeFormat::RGBA32 if !filterable => {
+                log::warn!("RGBA32 probes can't be filtered on this adapter, using RGBA16.");
+                ProbeFormat::RGBA16
+            }
+            format => format,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum ProbeQuality {
     Low,
@@ -85,6 +103,10 @@ pub struct Probe {
     irradiance_target: RenderTarget,
     specular_target: RenderTarget,
     brdf_texture: RenderTarget,
+    // Equirectangular texture the probe is projected from instead of rendering the scene.
+    environment: Option<String>,
+    // Reflection probes only light what's around them, the others light the whole scene.
+    pub(crate) local: bool,
     pub(crate) has_rendered: bool,
 }
 
@@ -103,6 +125,7 @@ impl Probe {
         let probe_resoultion = quality.get_probe_resoultion();
         let irradiance_resoultion = quality.get_irradiance_resoultion();
         let specular_resoultion = quality.get_specular_resoultion();
+        let format = format.supported(resources);
         let wgpu_format: wgpu::TextureFormat = format.into();
 
         // Create the specular workflow pipeline
@@ -134,6 +157,7 @@ impl Probe {
             wgpu_format,
             wgpu::TextureUsage::SAMPLED
                 | wgpu::TextureUsage::COPY_SRC
+                | wgpu::TextureUsage::COPY_DST
                 | wgpu::TextureUsage::OUTPUT_ATTACHMENT,
         );
         // Probe cube needs depth buffer as we are rendering the scene to it.
@@ -157,37 +181,6 @@ impl Probe {
             wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
         );
 
-        // Create bind group
-        let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
-        let bind_group_layout = resource_manager
-            .get_bind_group_layout("probe_material_layout")
-            .unwrap();
-
-        let bind_group = BindGroup::new(
-            3,
-            device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("Probe"),
-                layout: &bind_group_layout,
-                bindings: &[
-                    wgpu::Binding {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(
-                            &irradiance_target.texture_view,
-                        ),
-                    },
-                    wgpu::Binding {
-                        binding: 1,
-                        resource: wgpu::BindingResource::TextureView(&specular_target.texture_view),
-                    },
-                    wgpu::Binding {
-                        binding: 2,
-                        resource: wgpu::BindingResource::TextureView(&brdf_texture.texture_view),
-                    },
-                ],
-            }),
-        );
-        resource_manager.add_single_bind_group("probe_material", bind_group);
-
         Self {
             id,
             position,
@@ -205,12 +198,48 @@ impl Probe {
             specular_resoultion,
             specular_target,
             brdf_texture,
+            environment: None,
+            local: false,
         }
     }
 
+    /// Creates a probe from an equirectangular HDR texture instead of the scene.
+    /// The texture is projected onto a cubemap which is then convolved like any other probe.
+    pub(crate) fn new_environment(
+        id: u32,
+        texture: String,
+        resources: &Resources,
+        quality: ProbeQuality,
+        format: ProbeFormat,
+    ) -> Self {
+        let format = format.supported(resources);
+        crate::graphics::pipelines::equirectangular::create(resources, format.into());
+        let mut probe = Self::new(id, Vec3::zeros(), resources, quality, format);
+        probe.environment = Some(texture);
+        probe
+    }
+
+    pub(crate) fn irradiance_view(&self) -> &wgpu::TextureView {
+        &self.irradiance_target.texture_view
+    }
+
+    pub(crate) fn specular_view(&self) -> &wgpu::TextureView {
+        &self.specular_target.texture_view
+    }
+
+    pub(crate) fn brdf_view(&self) -> &wgpu::TextureView {
+        &self.brdf_texture.texture_view
+    }
+
+    // Meshes are drawn with the frame's pipelines, or their single sampled variants with MSAA,
+    // so they only fit a probe with the frame's format. Otherwise the probe only sees the sky.
+    fn captures_meshes(&self) -> bool {
+        let format: wgpu::TextureFormat = self.format.into();
+        format == HDR_FORMAT
+    }
+
     // Render's scene to the cube
     // This is considered a very "HEAVY" operation, and shouldn't be treated lightly
-    // TODO: If wgpu ever adds multi-view's use that instead..
     pub(crate) fn render_scene(
         &mut self,
         resources: &mut Resources,
@@ -224,130 +253,35 @@ impl Probe {
         self.samples_remaining = self.sample_count;
         self.sample_offset = 0;
 
-        // Create new render schedule has to be different from normal as we want to not queue items up right away.
-        // TODO: Have more systems support our CurrentRenderTarget.
-        let mut render_schedule =
-            Schedule::builder() //create_render_schedule_builder()
-                .add_system(crate::graphics::systems::globals::create())
-                .add_system(crate::graphics::systems::skybox::create())
-                .flush()
-                .add_thread_local_fn(crate::graphics::systems::render::create())
-                .build();
-
-        // First we need to create new pipelines using the correct texture format
-        let current_pipelines;
-        {
-            let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
-            current_pipelines = pipeline_manager.current_pipelines.clone();
-            let device = resources.get::<wgpu::Device>().unwrap();
-            let asset_manager = resources.get::<AssetManager>().unwrap();
-            let resource_manager = resources.get::<GPUResourceManager>().unwrap();
-            let skybox_pipeline = pipeline_manager.get("skybox", None).unwrap();
-            let realtime_skybox_pipeline = pipeline_manager.get("realtime_skybox", None).unwrap();
-            let mut new_skybox_desc = skybox_pipeline.desc.clone();
-            let mut new_realtime_skybox_desc = realtime_skybox_pipeline.desc.clone();
-            new_skybox_desc.color_state.format = self.format.into();
-            new_realtime_skybox_desc.color_state.format = self.format.into();
-            let hash = new_skybox_desc.create_hash();
-            let realtime_hash = new_realtime_skybox_desc.create_hash();
-            pipeline_manager.add_pipeline(
-                "skybox",
-                &new_skybox_desc,
-                vec![],
-                &device,
-                &asset_manager,
-                &resource_manager,
-            );
-            pipeline_manager.add_pipeline(
-                "realtime_skybox",
-                &new_realtime_skybox_desc,
-                vec![],
-                &device,
-                &asset_manager,
-                &resource_manager,
-            );
-            pipeline_manager.set_current_pipeline_hash("skybox", hash);
-            pipeline_manager.set_current_pipeline_hash("realtime_skybox", realtime_hash);
+        if let Some(texture) = self.environment.clone() {
+            self.project_environment(resources, &texture);
+            self.generate_mips(resources);
+            return;
         }
 
-        {
-            let camera_query = <(Write<CameraData>,)>::query();
-            for (mut camera_data,) in camera_query.iter_mut(&mut scene.world) {
-                if camera_data.active {
-                    camera_data.active = false;
-                }
-            }
-
-            // Add our special camera to the scene.
-            let probe_resoultion = self.quality.get_probe_resoultion();
-            let camera = CameraData::new_perspective(
-                90.0,
-                probe_resoultion as f32,
-                probe_resoultion as f32,
-                0.01,
-                1000.0,
+        let meshes = self.captures_meshes();
+        if !meshes {
+            log::warn!(
+                "Probe {} only captures the sky, meshes need the {:?} format.",
+                self.id,
+                HDR_FORMAT
             );
-            scene.world.insert((), vec![(camera,)]);
-
-            // Order of faces: X+ X- Y+ Y- Z+ Z-
-            // Render scene to each face..
-            for i in 0..6 {
-                // TODO: cache views?
-                let view = self
-                    .probe_cube
-                    .texture
-                    .create_view(&wgpu::TextureViewDescriptor {
-                        label: None,
-                        format: self.format.into(),
-                        dimension: wgpu::TextureViewDimension::D2,
-                        aspect: wgpu::TextureAspect::default(),
-                        base_mip_level: 0,
-                        level_count: 1,
-                        base_array_layer: i,
-                        array_layer_count: 1,
-                    });
-                // Insert the cube as the current render target.
-                resources.insert(CurrentRenderTarget(Some((self.probe_cube.clone(), view))));
-                // Update camera with new view
-                let camera_query = <(Write<CameraData>,)>::query();
-
-                for (mut camera_data,) in camera_query.iter_mut(&mut scene.world) {
-                    if camera_data.active {
-                        Self::update_camera(self.position, &mut camera_data, i);
-                    }
-                }
-                
-                // Submit our queue.
-                render_schedule.execute(&mut scene.world, resources);
-            }
-
-            // Reset pipelines.
-            {
-                let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
-                pipeline_manager.current_pipelines = current_pipelines;
-            }
         }
+        render_cube(
+            resources,
+            scene,
+            self.position,
+            &self.probe_cube,
+            self.format.into(),
+            self.quality.get_probe_resoultion(),
+            meshes,
+        );
 
-        // Remove camera_enttiy
-        {
-            let mut command = CommandBuffer::new(&scene.world);
-            let camera_query = <(Read<CameraData>,)>::query();
-            for (entity, camera_data) in camera_query.iter_entities_mut(&mut scene.world) {
-                if camera_data.0.active {
-                    command.remove_component::<CameraData>(entity);
-                }
-            }
-            command.write(&mut scene.world);
-
-            // And reactiveate all cameras deactived..
-            // TODO: Figure out how to tell which camera was actually activated before this..
-            let camera_query = <(Write<CameraData>,)>::query();
-            for (mut camera_data,) in camera_query.iter_mut(&mut scene.world) {
-                camera_data.active = true;
-            }
-        }
+        self.generate_mips(resources);
+    }
 
-        // Generate mip maps for the resulting cube map
+    // Generate mip maps for the resulting cube map
+    fn generate_mips(&mut self, resources: &Resources) {
         let probe_resoultion = self.quality.get_probe_resoultion();
         let mut probe_cube = {
             let device = resources.get::<wgpu::Device>().unwrap();
@@ -385,8 +319,99 @@ impl Probe {
                 array_layer_count: 6,
             });
         self.probe_cube = Arc::new(probe_cube);
+    }
+
+    // Projects the equirectangular texture onto the faces of the probe cube.
+    fn project_environment(&mut self, resources: &Resources, texture: &str) {
+        let device = resources.get::<wgpu::Device>().unwrap();
+        let asset_manager = resources.get::<AssetManager>().unwrap();
+        let resource_manager = resources.get::<GPUResourceManager>().unwrap();
+        let pipeline_manager = resources.get::<PipelineManager>().unwrap();
+        let probe_resoultion = self.quality.get_probe_resoultion();
+
+        let pipeline = pipeline_manager.get("equirectangular", None).unwrap();
+        let image = asset_manager.get_image(texture);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("equirectangular"),
+        });
+
+        let output = RenderTarget::new(
+            &device,
+            probe_resoultion as f32,
+            probe_resoultion as f32 * 6.0,
+            1,
+            1,
+            self.format.into(),
+            wgpu::TextureUsage::COPY_SRC | wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+        );
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: resource_manager
+                .get_bind_group_layout("equirectangular")
+                .unwrap(),
+            bindings: &[
+                wgpu::Binding {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&image.view),
+                },
+                wgpu::Binding {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&image.sampler),
+                },
+            ],
+            label: None,
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: &output.texture_view,
+                    resolve_target: None,
+                    load_op: wgpu::LoadOp::Clear,
+                    store_op: wgpu::StoreOp::Store,
+                    clear_color: wgpu::Color {
+                        r: 0.0,
+                        g: 0.0,
+                        b: 0.0,
+                        a: 1.0,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(&pipeline.render_pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..6, 0..6);
+        }
+
+        for i in 0..6 {
+            encoder.copy_texture_to_texture(
+                wgpu::TextureCopyView {
+                    texture: &output.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: probe_resoultion * i,
+                        z: 0,
+                    },
+                },
+                wgpu::TextureCopyView {
+                    texture: &self.probe_cube.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x: 0, y: 0, z: i },
+                },
+                wgpu::Extent3d {
+                    width: probe_resoultion,
+                    height: probe_resoultion,
+                    depth: 1,
+                },
+            );
+        }
 
-        resources.insert(CurrentRenderTarget(None));
+        resources
+            .get::<FramesInFlight>()
+            .unwrap()
+            .submit(Some(encoder.finish()));
     }
 
     pub(crate) fn render_brdf(
@@ -527,8 +552,10 @@ impl Probe {
             );
         }
 
-        let queue = resources.get::<wgpu::Queue>().unwrap();
-        queue.submit(Some(encoder.finish()));
+        resources
+            .get::<FramesInFlight>()
+            .unwrap()
+            .submit(Some(encoder.finish()));
     }
 
     fn render_specular(&mut self, resources: &Resources) {
@@ -673,8 +700,10 @@ impl Probe {
             }
         }
 
-        let queue = resources.get::<wgpu::Queue>().unwrap();
-        queue.submit(Some(encoder.finish()));
+        resources
+            .get::<FramesInFlight>()
+            .unwrap()
+            .submit(Some(encoder.finish()));
     }
 
     fn update_camera(position: Vec3, camera: &mut CameraData, face_id: u32) {
@@ -703,8 +732,154 @@ impl Probe {
             } // Z-
             _ => (),
         }
-        camera.update_view(eye, position, up);
+        // `eye` points back at the camera, the face looks the other way.
+        camera.position = position;
+        camera.update_view(position, position - eye, up);
+    }
+}
+
+/// Renders the scene into the six layers of `cube` from `position`, the meshes are only drawn
+/// when `meshes` is true. Used by probes, cube map captures and the dynamic GI probes.
+/// The faces are drawn into the frame's `FramesInFlight` batch, work reading the cube has to
+/// be submitted there too so it runs after them.
+/// TODO: If wgpu ever adds multi-view's use that instead..
+pub(crate) fn render_cube(
+    resources: &mut Resources,
+    scene: &mut crate::scene::Scene,
+    position: Vec3,
+    cube: &Arc<RenderTarget>,
+    format: wgpu::TextureFormat,
+    resolution: u32,
+    meshes: bool,
+) {
+    // Create new render schedule has to be different from normal as we want to not queue items
+    // up right away.
+    // TODO: Have more systems support our CurrentRenderTarget.
+    let mut render_schedule_builder = Schedule::builder() //create_render_schedule_builder()
+        .add_system(crate::graphics::systems::globals::create())
+        .add_system(crate::graphics::systems::skybox::create());
+    if meshes {
+        render_schedule_builder = render_schedule_builder
+            .flush()
+            .add_system(crate::graphics::systems::mesh::create());
+    }
+    let mut render_schedule = render_schedule_builder
+        .flush()
+        .add_thread_local_fn(crate::graphics::systems::render::create())
+        .build();
+
+    // First we need to create new pipelines using the correct texture format
+    let current_pipelines;
+    {
+        let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+        current_pipelines = pipeline_manager.current_pipelines.clone();
+        let device = resources.get::<wgpu::Device>().unwrap();
+        let asset_manager = resources.get::<AssetManager>().unwrap();
+        let resource_manager = resources.get::<GPUResourceManager>().unwrap();
+        let skybox_pipeline = pipeline_manager.get("skybox", None).unwrap();
+        let realtime_skybox_pipeline = pipeline_manager.get("realtime_skybox", None).unwrap();
+        let mut new_skybox_desc = skybox_pipeline.desc.clone();
+        let mut new_realtime_skybox_desc = realtime_skybox_pipeline.desc.clone();
+        new_skybox_desc.color_state.format = format;
+        new_realtime_skybox_desc.color_state.format = format;
+        let hash = new_skybox_desc.create_hash();
+        let realtime_hash = new_realtime_skybox_desc.create_hash();
+        pipeline_manager.add_pipeline(
+            "skybox",
+            &new_skybox_desc,
+            vec![],
+            &device,
+            &asset_manager,
+            &resource_manager,
+        );
+        pipeline_manager.add_pipeline(
+            "realtime_skybox",
+            &new_realtime_skybox_desc,
+            vec![],
+            &device,
+            &asset_manager,
+            &resource_manager,
+        );
+        pipeline_manager.set_current_pipeline_hash("skybox", hash);
+        pipeline_manager.set_current_pipeline_hash("realtime_skybox", realtime_hash);
     }
+
+    {
+        let camera_query = <(Write<CameraData>,)>::query();
+        for (mut camera_data,) in camera_query.iter_mut(&mut scene.world) {
+            if camera_data.active {
+                camera_data.active = false;
+            }
+        }
+
+        // Add our special camera to the scene.
+        let camera = CameraData::new_perspective(
+            90.0,
+            resolution as f32,
+            resolution as f32,
+            0.01,
+            1000.0,
+        );
+        scene.world.insert((), vec![(camera,)]);
+
+        // Order of faces: X+ X- Y+ Y- Z+ Z-
+        // Render scene to each face..
+        for i in 0..6 {
+            // TODO: cache views?
+            let view = cube
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor {
+                    label: None,
+                    format,
+                    dimension: wgpu::TextureViewDimension::D2,
+                    aspect: wgpu::TextureAspect::default(),
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer: i,
+                    array_layer_count: 1,
+                });
+            // Insert the cube as the current render target.
+            resources.insert(CurrentRenderTarget(Some((cube.clone(), view))));
+            // Update camera with new view
+            let camera_query = <(Write<CameraData>,)>::query();
+
+            for (mut camera_data,) in camera_query.iter_mut(&mut scene.world) {
+                if camera_data.active {
+                    Probe::update_camera(position, &mut camera_data, i);
+                }
+            }
+
+            // Submit our queue.
+            render_schedule.execute(&mut scene.world, resources);
+        }
+
+        // Reset pipelines.
+        {
+            let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+            pipeline_manager.current_pipelines = current_pipelines;
+        }
+    }
+
+    // Remove camera_enttiy
+    {
+        let mut command = CommandBuffer::new(&scene.world);
+        let camera_query = <(Read<CameraData>,)>::query();
+        for (entity, camera_data) in camera_query.iter_entities_mut(&mut scene.world) {
+            if camera_data.0.active {
+                command.remove_component::<CameraData>(entity);
+            }
+        }
+        command.write(&mut scene.world);
+
+        // And reactiveate all cameras deactived..
+        // TODO: Figure out how to tell which camera was actually activated before this..
+        let camera_query = <(Write<CameraData>,)>::query();
+        for (mut camera_data,) in camera_query.iter_mut(&mut scene.world) {
+            camera_data.active = true;
+        }
+    }
+
+    resources.insert(CurrentRenderTarget(None));
 }
 
 #[repr(C)]
@@ -1,19 +1,70 @@
+use bytemuck::{Pod, Zeroable};
+use futures::channel::oneshot;
 use legion::prelude::*;
-use nalgebra_glm::Vec3;
+use nalgebra_glm::{Vec3, Vec4};
+use std::{path::PathBuf, sync::Arc};
 
-use super::{Probe, ProbeFormat, ProbeQuality};
-use crate::scene::components;
+use super::{
+    probe::render_cube, BindGroup, GPUResourceManager, Probe, ProbeFormat, ProbeQuality,
+    RenderTarget,
+};
+use crate::{
+    graphics::{
+        cubemap::{CubemapDestination, CubemapRequest, PendingCubemap},
+        renderer::HDR_FORMAT,
+        FramesInFlight, HdrCubemap,
+    },
+    scene::components,
+};
+
+/// The most reflection probes the PBR shader blends between.
+pub const MAX_REFLECTION_PROBES: usize = 4;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct ReflectionProbeUniform {
+    // xyz is where the probe was captured, w the blend distance.
+    position: Vec4,
+    // The influence box, w in min is 1.0 for box projection.
+    box_min: Vec4,
+    box_max: Vec4,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct ReflectionProbesUniform {
+    probes: [ReflectionProbeUniform; MAX_REFLECTION_PROBES],
+    // x is the number of probes.
+    count: Vec4,
+}
+
+unsafe impl Zeroable for ReflectionProbeUniform {}
+unsafe impl Pod for ReflectionProbeUniform {}
+unsafe impl Zeroable for ReflectionProbesUniform {}
+unsafe impl Pod for ReflectionProbesUniform {}
 
 /// Keeps track of probes matches them up with entities for updates.
+/// The last probe that isn't a reflection probe lights the whole scene, reflection probes are
+/// blended over it where they have influence.
 /// TODO: Calculate probes based off of distance to camera. Prioritized baised off of distance.
 /// TODO: Some how stream probes in an out depending on distance. We likely shouldn't keep them in memory.
 pub struct ProbeManager {
     probes: Vec<Probe>,
+    reflection_buffer: Option<wgpu::Buffer>,
+    reflection_uniform: Option<ReflectionProbesUniform>,
+    cubemap_requests: Vec<CubemapRequest>,
+    pending_cubemaps: Vec<PendingCubemap>,
 }
 
 impl ProbeManager {
     pub fn new() -> Self {
-        Self { probes: Vec::new() }
+        Self {
+            probes: Vec::new(),
+            reflection_buffer: None,
+            reflection_uniform: None,
+            cubemap_requests: Vec::new(),
+            pending_cubemaps: Vec::new(),
+        }
     }
 
     pub fn create(
@@ -26,10 +77,96 @@ impl ProbeManager {
         let id = self.probes.len() as u32;
         self.probes
             .push(Probe::new(id, position, resources, quality, format));
+        self.create_bind_group(resources);
+        id
+    }
+
+    /// Creates a probe from an equirectangular HDR image, used to light the scene from a skybox.
+    pub fn create_environment<T>(
+        &mut self,
+        texture: T,
+        resources: &Resources,
+        quality: ProbeQuality,
+        format: ProbeFormat,
+    ) -> u32
+    where
+        T: Into<String>,
+    {
+        let id = self.probes.len() as u32;
+        self.probes.push(Probe::new_environment(
+            id,
+            texture.into(),
+            resources,
+            quality,
+            format,
+        ));
+        self.create_bind_group(resources);
         id
     }
 
+    /// Creates a probe that only reflects what's around it, it's entity needs a
+    /// `ReflectionProbe` component for the shader to use it.
+    /// Only the first `MAX_REFLECTION_PROBES` reflection probes are used.
+    pub fn create_reflection(
+        &mut self,
+        position: Vec3,
+        resources: &Resources,
+        quality: ProbeQuality,
+        format: ProbeFormat,
+    ) -> u32 {
+        let id = self.probes.len() as u32;
+        let mut probe = Probe::new(id, position, resources, quality, format);
+        probe.local = true;
+        self.probes.push(probe);
+        self.create_bind_group(resources);
+        id
+    }
+
+    /// Renders the probe again in the next frame, for when it moved or the scene around it
+    /// changed. This is expensive, don't do it every frame.
+    pub fn rebake(&mut self, id: u32) {
+        if let Some(probe) = self.probes.get_mut(id as usize) {
+            probe.has_rendered = false;
+        }
+    }
+
+    /// Renders the scene around `position` into an HDR cube map with faces `resolution` pixels
+    /// wide, for baking environment maps and probes from the engine's own scenes.
+    /// It's rendered with the next frame and arrives a frame or two later, check the receiver
+    /// with `try_recv` each update.
+    pub fn capture_cubemap(
+        &mut self,
+        position: Vec3,
+        resolution: u32,
+    ) -> oneshot::Receiver<HdrCubemap> {
+        let (sender, receiver) = oneshot::channel();
+        self.cubemap_requests.push(CubemapRequest {
+            position,
+            resolution: resolution.max(1),
+            destination: CubemapDestination::Cubemap(sender),
+        });
+        receiver
+    }
+
+    /// Same as `capture_cubemap` but writes the cube map to an equirectangular `.hdr` file
+    /// that's `width` pixels wide, it can be loaded again with `create_environment`.
+    pub fn save_cubemap<T: Into<PathBuf>>(
+        &mut self,
+        position: Vec3,
+        resolution: u32,
+        path: T,
+        width: u32,
+    ) {
+        self.cubemap_requests.push(CubemapRequest {
+            position,
+            resolution: resolution.max(1),
+            destination: CubemapDestination::Hdr(path.into(), width.max(2)),
+        });
+    }
+
     pub(crate) fn render(&mut self, resources: &mut Resources, scene: &mut crate::scene::Scene) {
+        self.render_cubemaps(resources, scene);
+
         //TODO: Fix this as it's not very well optimized. Perhaps a oct tree would work better?
 
         let query = <(Read<components::Probe>, Read<components::Transform>)>::query();
@@ -40,10 +177,202 @@ impl ProbeManager {
 
         for (probe_id, position) in probe_ids {
             let probe = &mut self.probes[probe_id as usize];
-            probe.position = position;
+            if !probe.has_rendered {
+                probe.position = position;
+            }
             probe.render_scene(resources, scene);
             probe.render_brdf(resources, scene);
             probe.has_rendered = true;
         }
+
+        self.update_reflection_probes(resources, &scene.world);
+    }
+
+    // Renders the requested cube maps and hands out the ones that were read back.
+    fn render_cubemaps(&mut self, resources: &mut Resources, scene: &mut crate::scene::Scene) {
+        for request in self.cubemap_requests.drain(..) {
+            let cube = {
+                let device = resources.get::<wgpu::Device>().unwrap();
+                let mut cube = RenderTarget::new(
+                    &device,
+                    request.resolution as f32,
+                    request.resolution as f32,
+                    6,
+                    1,
+                    HDR_FORMAT,
+                    wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+                );
+                cube.with_depth(&device);
+                Arc::new(cube)
+            };
+            render_cube(
+                resources,
+                scene,
+                request.position,
+                &cube,
+                HDR_FORMAT,
+                request.resolution,
+                true,
+            );
+
+            let device = resources.get::<wgpu::Device>().unwrap();
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("cubemap_capture"),
+            });
+            self.pending_cubemaps.push(PendingCubemap::new(
+                &device,
+                &mut encoder,
+                &cube.texture,
+                request.resolution,
+                request.destination,
+            ));
+            resources
+                .get::<FramesInFlight>()
+                .unwrap()
+                .submit(Some(encoder.finish()));
+        }
+
+        let pending = std::mem::replace(&mut self.pending_cubemaps, Vec::new());
+        self.pending_cubemaps = pending
+            .into_iter()
+            .filter_map(|mut cubemap| if cubemap.finish() { None } else { Some(cubemap) })
+            .collect();
+    }
+
+    // The global probe and the reflection probes in the order the shader sees them.
+    fn bound_probes(&self) -> Option<(&Probe, Vec<&Probe>)> {
+        let global = self
+            .probes
+            .iter()
+            .rev()
+            .find(|probe| !probe.local)
+            .or_else(|| self.probes.last())?;
+        let local = self
+            .probes
+            .iter()
+            .filter(|probe| probe.local)
+            .take(MAX_REFLECTION_PROBES)
+            .collect();
+        Some((global, local))
+    }
+
+    // Binds the global probe and the reflection probes for the PBR shader.
+    fn create_bind_group(&mut self, resources: &Resources) {
+        let device = resources.get::<wgpu::Device>().unwrap();
+        if self.reflection_buffer.is_none() {
+            self.reflection_buffer = Some(device.create_buffer_with_data(
+                bytemuck::bytes_of(&ReflectionProbesUniform::zeroed()),
+                wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            ));
+            self.reflection_uniform = None;
+        }
+        let (global, local) = match self.bound_probes() {
+            Some(probes) => probes,
+            None => return,
+        };
+
+        // Unused slots still need a cube, they get the global one.
+        let reflection_view = |slot: usize| {
+            local
+                .get(slot)
+                .map_or(global.specular_view(), |probe| probe.specular_view())
+        };
+        let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Probe"),
+            layout: resource_manager
+                .get_bind_group_layout("probe_material_layout")
+                .unwrap(),
+            bindings: &[
+                wgpu::Binding {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(global.irradiance_view()),
+                },
+                wgpu::Binding {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(global.specular_view()),
+                },
+                wgpu::Binding {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(global.brdf_view()),
+                },
+                wgpu::Binding {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(reflection_view(0)),
+                },
+                wgpu::Binding {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(reflection_view(1)),
+                },
+                wgpu::Binding {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(reflection_view(2)),
+                },
+                wgpu::Binding {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(reflection_view(3)),
+                },
+                wgpu::Binding {
+                    binding: 7,
+                    resource: wgpu::BindingResource::Buffer(
+                        self.reflection_buffer.as_ref().unwrap().slice(..),
+                    ),
+                },
+            ],
+        });
+        resource_manager.add_single_bind_group("probe_material", BindGroup::new(3, bind_group));
+    }
+
+    // Uploads the influence boxes of the reflection probes when they change.
+    fn update_reflection_probes(&mut self, resources: &Resources, world: &World) {
+        let buffer = match self.reflection_buffer.as_ref() {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let local_ids: Vec<u32> = match self.bound_probes() {
+            Some((_, local)) => local.iter().map(|probe| probe.id).collect(),
+            None => return,
+        };
+
+        let mut uniform = ReflectionProbesUniform::zeroed();
+        let query = <(Read<components::Probe>, Read<components::ReflectionProbe>)>::query();
+        for (probe, reflection) in query.iter(world) {
+            let slot = match local_ids.iter().position(|id| *id == probe.id) {
+                Some(slot) => slot,
+                None => continue,
+            };
+            // Box projection needs the position the cube was captured from.
+            let position = self.probes[probe.id as usize].position;
+            let min = position - reflection.extents;
+            let max = position + reflection.extents;
+            let box_projection = if reflection.box_projection { 1.0 } else { 0.0 };
+            uniform.probes[slot] = ReflectionProbeUniform {
+                position: Vec4::new(
+                    position.x,
+                    position.y,
+                    position.z,
+                    reflection.blend_distance,
+                ),
+                box_min: Vec4::new(min.x, min.y, min.z, box_projection),
+                box_max: Vec4::new(max.x, max.y, max.z, 0.0),
+            };
+        }
+        // Probes without a component keep an empty box so they're never used.
+        uniform.count.x = local_ids.len() as f32;
+
+        if self.reflection_uniform == Some(uniform) {
+            return;
+        }
+        let device = resources.get::<wgpu::Device>().unwrap();
+        let queue = resources.get::<wgpu::Queue>().unwrap();
+        let staging = device
+            .create_buffer_with_data(bytemuck::bytes_of(&uniform), wgpu::BufferUsage::COPY_SRC);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("reflection_probes"),
+        });
+        let size = std::mem::size_of::<ReflectionProbesUniform>() as wgpu::BufferAddress;
+        encoder.copy_buffer_to_buffer(&staging, 0, buffer, 0, size);
+        queue.submit(Some(encoder.finish()));
+        self.reflection_uniform = Some(uniform);
     }
 }
@@ -0,0 +1,29 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::graphics::material::SamplerInfo;
+
+/// Hands out one sampler per unique `SamplerInfo`.
+/// It's behind a mutex so samplers can be created while other parts of the
+/// `GPUResourceManager` are borrowed.
+#[derive(Default)]
+pub(crate) struct SamplerCache {
+    samplers: Mutex<HashMap<SamplerInfo, Arc<wgpu::Sampler>>>,
+}
+
+impl SamplerCache {
+    pub fn get(&self, device: &wgpu::Device, info: &SamplerInfo) -> Arc<wgpu::Sampler> {
+        self.samplers
+            .lock()
+            .unwrap()
+            .entry(*info)
+            .or_insert_with(|| Arc::new(info.create_sampler(device)))
+            .clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samplers.lock().unwrap().len()
+    }
+}
@@ -0,0 +1,749 @@
+use bytemuck::{Pod, Zeroable};
+use glyph_brush::{
+    rusttype::{Font, Rect, Scale},
+    BrushAction, BrushError, FontId, GlyphBrush, GlyphBrushBuilder, GlyphCruncher, GlyphVertex,
+    HorizontalAlign, Layout, SectionText, VariedSection,
+};
+use nalgebra_glm::{Mat4, Vec2, Vec4};
+use std::collections::HashMap;
+
+use crate::{
+    graphics::{clip::ClipStack, ClipRect},
+    AssetManager,
+};
+
+const INITIAL_ATLAS_SIZE: u32 = 256;
+
+/// How far the distance field reaches on either side of a glyph's edge, in pixels.
+const SDF_SPREAD: f32 = 4.0;
+
+/// A single glyph on screen. Every glyph is drawn as an instanced quad.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct GlyphInstance {
+    pub left_top: [f32; 3],
+    pub right_bottom: [f32; 2],
+    pub tex_left_top: [f32; 2],
+    pub tex_right_bottom: [f32; 2],
+    pub color: [f32; 4],
+}
+
+unsafe impl Zeroable for GlyphInstance {}
+unsafe impl Pod for GlyphInstance {}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TextUniform {
+    pub ortho: Mat4,
+    // (is sdf, unused..)
+    pub info: Vec4,
+}
+
+unsafe impl Zeroable for TextUniform {}
+unsafe impl Pod for TextUniform {}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+/// A run of text inside a `TextSection` with it's own font, size or color.
+/// Anything left as None uses the section's.
+#[derive(Debug, Clone, Default)]
+pub struct TextSpan {
+    pub text: String,
+    pub font: Option<String>,
+    pub size: Option<f32>,
+    pub color: Option<Vec4>,
+}
+
+impl TextSpan {
+    pub fn new<T: Into<String>>(text: T) -> Self {
+        Self {
+            text: text.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_font<T: Into<String>>(mut self, font: T) -> Self {
+        self.font = Some(font.into());
+        self
+    }
+
+    pub fn with_size(mut self, size: f32) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = Some(color);
+        self
+    }
+}
+
+/// Some text to draw on the screen this frame. Positions and sizes are in UI pixels, they're
+/// multiplied by `TextRenderer::scale` to get pixels on screen.
+/// ```ignore
+/// text_renderer.queue(&TextSection {
+///     spans: vec![
+///         TextSpan::new("Health: "),
+///         TextSpan::new("12").with_color(Vec4::new(1.0, 0.0, 0.0, 1.0)),
+///     ],
+///     bounds: Vec2::new(200.0, std::f32::INFINITY),
+///     align: TextAlign::Right,
+///     ..TextSection::default()
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct TextSection {
+    pub text: String,
+    /// Rich text, drawn instead of `text` when it's not empty.
+    pub spans: Vec<TextSpan>,
+    /// The font file name, if none the first font is used.
+    pub font: Option<String>,
+    /// Position in pixels, it's the top left, top middle or top right of the text
+    /// depending on `align`.
+    pub position: Vec2,
+    /// Max width and height in pixels, text outside of this is clipped.
+    pub bounds: Vec2,
+    /// Font size in pixels.
+    pub size: f32,
+    pub color: Vec4,
+    /// Depth from 0.0 to 1.0, text is hidden behind geometry closer than this.
+    pub z: f32,
+    pub align: TextAlign,
+    /// Breaks lines between words so they fit in `bounds`, otherwise only newlines do.
+    pub wrap: bool,
+    /// Higher layers are drawn on top of lower ones, inside a layer text is drawn in the
+    /// order it was queued.
+    pub layer: i32,
+}
+
+impl Default for TextSection {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            spans: Vec::new(),
+            font: None,
+            position: Vec2::zeros(),
+            bounds: Vec2::new(std::f32::INFINITY, std::f32::INFINITY),
+            size: 16.0,
+            color: Vec4::new(1.0, 1.0, 1.0, 1.0),
+            z: 0.0,
+            align: TextAlign::Left,
+            wrap: true,
+            layer: 0,
+        }
+    }
+}
+
+/// Draws text using a glyph atlas that persists between frames.
+/// All queued text ends up in a single instance buffer so it only takes one draw call.
+/// Text has to be queued every frame, if nothing changed the previous instance buffer is reused.
+/// With `RenderSettings::sdf_text` the atlas stores distance fields instead of coverage, so
+/// glyphs can be reused at a wider range of sizes and stay sharp when they're scaled up.
+/// Text is drawn after post processing, so it isn't tonemapped or bloomed and isn't
+/// multisampled, only the depth test uses the scene's depth.
+/// Text queued between `push_clip` and `pop_clip` is cut off outside of the clip rect:
+/// ```ignore
+/// text_renderer.push_clip(ClipRect::new(10.0, 10.0, 200.0, 100.0));
+/// for (i, line) in lines.iter().enumerate() {
+///     text_renderer.queue(&TextSection {
+///         text: line.clone(),
+///         position: Vec2::new(10.0, 10.0 + i as f32 * 20.0 - scroll),
+///         ..TextSection::default()
+///     });
+/// }
+/// text_renderer.pop_clip();
+/// ```
+pub struct TextRenderer {
+    glyph_brush: Option<GlyphBrush<'static, GlyphInstance>>,
+    fonts: HashMap<String, FontId>,
+    sdf: bool,
+    scale: f32,
+    // The glyph brush keeps it's own copy of each font's data.
+    font_bytes: usize,
+    atlas: wgpu::Texture,
+    atlas_view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    pub(crate) uniform_buffer: wgpu::Buffer,
+    pub(crate) bind_group: wgpu::BindGroup,
+    pub(crate) instance_buffer: Option<wgpu::Buffer>,
+    pub(crate) instance_count: u32,
+    clip_stack: ClipStack,
+    // The layer and clip rect of each group of queued sections this frame, and the group and
+    // real z of the sections by the z they were queued with.
+    groups: Vec<(i32, Option<ClipRect>)>,
+    group_keys: HashMap<u32, (usize, f32)>,
+    drawn_groups: Vec<(i32, Option<ClipRect>)>,
+    // The glyphs from the glyph brush before they're clipped and sorted.
+    glyphs: Vec<GlyphInstance>,
+}
+
+impl TextRenderer {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        asset_manager: &AssetManager,
+        layout: &wgpu::BindGroupLayout,
+        sdf: bool,
+        scale: f32,
+    ) -> Self {
+        // Sort the fonts so the default font doesn't change between runs.
+        let mut font_names: Vec<&String> = asset_manager.fonts.keys().collect();
+        font_names.sort();
+
+        let mut fonts = HashMap::new();
+        let mut glyph_brush = None;
+        let mut font_bytes = 0;
+        for name in font_names {
+            let data = asset_manager.fonts.get(name).unwrap().data.clone();
+            let size = data.len();
+            let font = match Font::from_bytes(data) {
+                Ok(font) => font,
+                Err(error) => {
+                    log::warn!("Text Error: Could not parse font {}: {:?}", name, error);
+                    continue;
+                }
+            };
+
+            let font_id = match glyph_brush.as_mut() {
+                Some(brush) => brush.add_font(font),
+                None => {
+                    // Distance fields scale well, so a glyph can be reused for sizes up to
+                    // twice as big or small instead of being rasterized again.
+                    let scale_tolerance = if sdf { 2.0 } else { 0.5 };
+                    glyph_brush = Some(
+                        GlyphBrushBuilder::using_font(font)
+                            .initial_cache_size((INITIAL_ATLAS_SIZE, INITIAL_ATLAS_SIZE))
+                            .gpu_cache_scale_tolerance(scale_tolerance)
+                            .build(),
+                    );
+                    FontId::default()
+                }
+            };
+            fonts.insert(name.clone(), font_id);
+            font_bytes += size;
+        }
+
+        let (width, height) = glyph_brush
+            .as_ref()
+            .map(|brush| brush.texture_dimensions())
+            .unwrap_or((INITIAL_ATLAS_SIZE, INITIAL_ATLAS_SIZE));
+        let (atlas, atlas_view) = Self::create_atlas(device, width, height);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: -100.0,
+            lod_max_clamp: 100.0,
+            compare: wgpu::CompareFunction::Undefined,
+        });
+
+        let uniform_buffer = device.create_buffer_with_data(
+            bytemuck::bytes_of(&TextUniform {
+                ortho: Mat4::identity(),
+                info: Vec4::zeros(),
+            }),
+            wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        );
+
+        let bind_group =
+            Self::create_bind_group(device, layout, &uniform_buffer, &atlas_view, &sampler);
+
+        Self {
+            glyph_brush,
+            fonts,
+            sdf,
+            scale,
+            font_bytes,
+            atlas,
+            atlas_view,
+            sampler,
+            uniform_buffer,
+            bind_group,
+            instance_buffer: None,
+            instance_count: 0,
+            clip_stack: ClipStack::default(),
+            groups: Vec::new(),
+            group_keys: HashMap::new(),
+            drawn_groups: Vec::new(),
+            glyphs: Vec::new(),
+        }
+    }
+
+    /// Bytes of font data the renderer parsed it's glyphs from.
+    pub fn font_bytes(&self) -> usize {
+        self.font_bytes
+    }
+
+    /// How many screen pixels a UI pixel is, see `RenderSettings::ui_scale`.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub(crate) fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+    }
+
+    /// Queues text to be drawn this frame.
+    pub fn queue(&mut self, section: &TextSection) {
+        if self.glyph_brush.is_none() {
+            return;
+        }
+        // The glyph brush doesn't say which section a glyph came from, so each layer and clip
+        // rect moves the z a tiny bit to find them again.
+        let group = (section.layer, self.clip_stack.current());
+        let index = match self.groups.iter().position(|other| *other == group) {
+            Some(index) => index,
+            None => {
+                self.groups.push(group);
+                self.groups.len() - 1
+            }
+        };
+        let key = section.z.to_bits().wrapping_add(index as u32);
+        self.group_keys.insert(key, (index, section.z));
+
+        let mut varied_section = self.varied_section(section);
+        varied_section.z = f32::from_bits(key);
+        self.glyph_brush.as_mut().unwrap().queue(varied_section);
+    }
+
+    /// Clips text queued after this to `rect`, cut down to any clip rect that's already
+    /// pushed. Every push needs a `pop_clip` before the end of the frame.
+    pub fn push_clip(&mut self, rect: ClipRect) {
+        let scale = self.scale;
+        self.clip_stack.push(ClipRect::new(
+            rect.x * scale,
+            rect.y * scale,
+            rect.width * scale,
+            rect.height * scale,
+        ));
+    }
+
+    pub fn pop_clip(&mut self) {
+        if self.clip_stack.pop().is_none() {
+            log::warn!("Text Error: pop_clip called without a clip rect pushed!");
+        }
+    }
+
+    /// The size the text would take up in UI pixels, after wrapping.
+    /// None if there's nothing to draw or no fonts were loaded.
+    pub fn measure(&mut self, section: &TextSection) -> Option<Vec2> {
+        let varied_section = self.varied_section(section);
+        let bounds = self.glyph_brush.as_mut()?.pixel_bounds(varied_section)?;
+        Some(Vec2::new(bounds.width() as f32, bounds.height() as f32) / self.scale)
+    }
+
+    fn varied_section<'a>(&self, section: &'a TextSection) -> VariedSection<'a> {
+        let font_id = |font: Option<&String>| match font {
+            Some(name) => self.fonts.get(name).copied().unwrap_or_else(|| {
+                log::warn!("Text Error: Could not find {} font asset!", name);
+                FontId::default()
+            }),
+            None => FontId::default(),
+        };
+        let color = |color: Vec4| [color.x, color.y, color.z, color.w];
+        // Glyphs are rasterized at their size on screen so scaled text stays sharp.
+        let scale = self.scale;
+        let section_font = font_id(section.font.as_ref());
+
+        let text = if section.spans.is_empty() {
+            vec![SectionText {
+                text: &section.text,
+                scale: Scale::uniform(section.size * scale),
+                color: color(section.color),
+                font_id: section_font,
+            }]
+        } else {
+            section
+                .spans
+                .iter()
+                .map(|span| SectionText {
+                    text: &span.text,
+                    scale: Scale::uniform(span.size.unwrap_or(section.size) * scale),
+                    color: color(span.color.unwrap_or(section.color)),
+                    font_id: span
+                        .font
+                        .as_ref()
+                        .map_or(section_font, |font| font_id(Some(font))),
+                })
+                .collect()
+        };
+
+        let h_align = match section.align {
+            TextAlign::Left => HorizontalAlign::Left,
+            TextAlign::Center => HorizontalAlign::Center,
+            TextAlign::Right => HorizontalAlign::Right,
+        };
+        let layout = if section.wrap {
+            Layout::default_wrap()
+        } else {
+            Layout::default_single_line()
+        };
+        VariedSection {
+            screen_position: (section.position.x * scale, section.position.y * scale),
+            bounds: (section.bounds.x * scale, section.bounds.y * scale),
+            z: section.z,
+            layout: layout.h_align(h_align),
+            text,
+        }
+    }
+
+    /// Uploads any new glyphs to the atlas and rebuilds the instance buffer if the text changed.
+    pub(crate) fn process(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        layout: &wgpu::BindGroupLayout,
+        screen_size: (u32, u32),
+    ) {
+        let glyph_brush = match self.glyph_brush.as_mut() {
+            Some(glyph_brush) => glyph_brush,
+            None => return,
+        };
+
+        // Maps pixel coordinates to clip space with the origin in the top left.
+        let uniform = TextUniform {
+            ortho: nalgebra_glm::ortho_lh_zo(
+                0.0,
+                screen_size.0 as f32,
+                screen_size.1 as f32,
+                0.0,
+                0.0,
+                1.0,
+            ),
+            info: Vec4::new(if self.sdf { 1.0 } else { 0.0 }, 0.0, 0.0, 0.0),
+        };
+        let uniform_staging = device
+            .create_buffer_with_data(bytemuck::bytes_of(&uniform), wgpu::BufferUsage::COPY_SRC);
+        encoder.copy_buffer_to_buffer(
+            &uniform_staging,
+            0,
+            &self.uniform_buffer,
+            0,
+            std::mem::size_of::<TextUniform>() as wgpu::BufferAddress,
+        );
+
+        // The same text with a moved clip rect is a redraw for the glyph brush.
+        let mut redraw = self.groups != self.drawn_groups;
+        loop {
+            let atlas = &self.atlas;
+            let sdf = self.sdf;
+            let result = glyph_brush.process_queued(
+                |rect, data| {
+                    if sdf {
+                        let field = coverage_to_sdf(data, rect.width(), rect.height());
+                        Self::update_atlas(device, encoder, atlas, rect, &field)
+                    } else {
+                        Self::update_atlas(device, encoder, atlas, rect, data)
+                    }
+                },
+                to_instance,
+            );
+
+            match result {
+                Ok(BrushAction::Draw(instances)) => {
+                    self.glyphs = instances;
+                    redraw = true;
+                    break;
+                }
+                Ok(BrushAction::ReDraw) => break,
+                Err(BrushError::TextureTooSmall { suggested }) => {
+                    let (width, height) = suggested;
+                    log::info!("Text: Resizing glyph atlas to {}x{}", width, height);
+                    let (atlas, atlas_view) = Self::create_atlas(device, width, height);
+                    self.bind_group = Self::create_bind_group(
+                        device,
+                        layout,
+                        &self.uniform_buffer,
+                        &atlas_view,
+                        &self.sampler,
+                    );
+                    self.atlas = atlas;
+                    self.atlas_view = atlas_view;
+                    glyph_brush.resize_texture(width, height);
+                }
+            }
+        }
+
+        if redraw {
+            let instances = arrange_glyphs(&self.glyphs, &self.group_keys, &self.groups);
+            self.instance_count = instances.len() as u32;
+            self.instance_buffer = if instances.is_empty() {
+                None
+            } else {
+                Some(device.create_buffer_with_data(
+                    bytemuck::cast_slice(&instances),
+                    wgpu::BufferUsage::VERTEX,
+                ))
+            };
+            self.drawn_groups = self.groups.clone();
+        }
+        self.groups.clear();
+        self.group_keys.clear();
+        if !self.clip_stack.is_empty() {
+            log::warn!("Text Error: push_clip called without a matching pop_clip!");
+            self.clip_stack.clear();
+        }
+    }
+
+    fn create_atlas(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let atlas = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("glyph_atlas"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        });
+        let atlas_view = atlas.create_default_view();
+        (atlas, atlas_view)
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        atlas_view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            bindings: &[
+                wgpu::Binding {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(uniform_buffer.slice(..)),
+                },
+                wgpu::Binding {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::Binding {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(atlas_view),
+                },
+            ],
+            label: Some("text"),
+        })
+    }
+
+    fn update_atlas(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        atlas: &wgpu::Texture,
+        rect: Rect<u32>,
+        data: &[u8],
+    ) {
+        let width = rect.width();
+        let height = rect.height();
+
+        // Rows have to be aligned to 256 bytes when copying from a buffer.
+        let padded_width = (width + 255) & !255;
+        let mut padded_data = vec![0; (padded_width * height) as usize];
+        for row in 0..height as usize {
+            let source = row * width as usize;
+            let target = row * padded_width as usize;
+            padded_data[target..target + width as usize]
+                .copy_from_slice(&data[source..source + width as usize]);
+        }
+
+        let staging = device.create_buffer_with_data(&padded_data, wgpu::BufferUsage::COPY_SRC);
+        encoder.copy_buffer_to_texture(
+            wgpu::BufferCopyView {
+                buffer: &staging,
+                layout: wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: padded_width,
+                    rows_per_image: 0,
+                },
+            },
+            wgpu::TextureCopyView {
+                texture: atlas,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: rect.min.x,
+                    y: rect.min.y,
+                    z: 0,
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+        );
+    }
+}
+
+// Turns a glyph's coverage into a signed distance to it's edge, 0.5 is on the edge and it goes
+// up inside. Glyphs are small so a brute force search around each pixel is fast enough.
+fn coverage_to_sdf(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let (width, height) = (width as i32, height as i32);
+    let inside = |x: i32, y: i32| data[(y * width + x) as usize] >= 128;
+    let radius = SDF_SPREAD.ceil() as i32;
+
+    let mut field = Vec::with_capacity(data.len());
+    for y in 0..height {
+        for x in 0..width {
+            let is_inside = inside(x, y);
+            let mut distance = SDF_SPREAD;
+            for search_y in (y - radius).max(0)..(y + radius + 1).min(height) {
+                for search_x in (x - radius).max(0)..(x + radius + 1).min(width) {
+                    if inside(search_x, search_y) != is_inside {
+                        let (dx, dy) = ((search_x - x) as f32, (search_y - y) as f32);
+                        // The edge is half way between the two pixels.
+                        distance = distance.min((dx * dx + dy * dy).sqrt() - 0.5);
+                    }
+                }
+            }
+            let signed = if is_inside { distance } else { -distance };
+            let value = 0.5 + signed / (SDF_SPREAD * 2.0);
+            field.push((value.max(0.0).min(1.0) * 255.0) as u8);
+        }
+    }
+    field
+}
+
+/// Converts a glyph into an instance, clipping it against the section bounds.
+fn to_instance(glyph: GlyphVertex) -> GlyphInstance {
+    let GlyphVertex {
+        tex_coords,
+        pixel_coords,
+        bounds,
+        color,
+        z,
+    } = glyph;
+
+    let instance = GlyphInstance {
+        left_top: [pixel_coords.min.x as f32, pixel_coords.min.y as f32, z],
+        right_bottom: [pixel_coords.max.x as f32, pixel_coords.max.y as f32],
+        tex_left_top: [tex_coords.min.x, tex_coords.min.y],
+        tex_right_bottom: [tex_coords.max.x, tex_coords.max.y],
+        color,
+    };
+    clip_glyph(
+        instance,
+        [bounds.min.x, bounds.min.y],
+        [bounds.max.x, bounds.max.y],
+    )
+}
+
+// Cuts a glyph down to a rectangle, keeping the texture coordinates in sync with the quad.
+fn clip_glyph(mut glyph: GlyphInstance, min: [f32; 2], max: [f32; 2]) -> GlyphInstance {
+    for axis in 0..2 {
+        let (start, end) = (glyph.left_top[axis], glyph.right_bottom[axis]);
+        if end <= start {
+            continue;
+        }
+        let (tex_start, tex_end) = (glyph.tex_left_top[axis], glyph.tex_right_bottom[axis]);
+        let tex_per_pixel = (tex_end - tex_start) / (end - start);
+        let (clipped_start, clipped_end) = (start.max(min[axis]), end.min(max[axis]));
+        glyph.left_top[axis] = clipped_start;
+        glyph.right_bottom[axis] = clipped_end;
+        glyph.tex_left_top[axis] = tex_start + (clipped_start - start) * tex_per_pixel;
+        glyph.tex_right_bottom[axis] = tex_end - (end - clipped_end) * tex_per_pixel;
+    }
+    glyph
+}
+
+// Puts the glyphs back on their real z, clips them to their group's clip rect and sorts them
+// by layer. Glyphs clipped away completely are dropped.
+fn arrange_glyphs(
+    glyphs: &[GlyphInstance],
+    group_keys: &HashMap<u32, (usize, f32)>,
+    groups: &[(i32, Option<ClipRect>)],
+) -> Vec<GlyphInstance> {
+    let mut arranged: Vec<(i32, GlyphInstance)> = glyphs
+        .iter()
+        .filter_map(|glyph| {
+            let (group, z) = match group_keys.get(&glyph.left_top[2].to_bits()) {
+                Some((group, z)) => (groups[*group], *z),
+                None => return Some((0, *glyph)),
+            };
+            let (layer, clip) = group;
+            let mut glyph = *glyph;
+            glyph.left_top[2] = z;
+            if let Some(clip) = clip {
+                glyph = clip_glyph(
+                    glyph,
+                    [clip.x, clip.y],
+                    [clip.x + clip.width, clip.y + clip.height],
+                );
+                if glyph.right_bottom[0] <= glyph.left_top[0]
+                    || glyph.right_bottom[1] <= glyph.left_top[1]
+                {
+                    return None;
+                }
+            }
+            Some((layer, glyph))
+        })
+        .collect();
+    // Stable, so text in a layer keeps the order it was queued in.
+    arranged.sort_by_key(|(layer, _)| *layer);
+    arranged.into_iter().map(|(_, glyph)| glyph).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coverage_to_sdf() {
+        // A 2 pixel wide vertical bar in the middle of an 8x1 glyph.
+        let coverage = [0, 0, 0, 255, 255, 0, 0, 0];
+        let field = coverage_to_sdf(&coverage, 8, 1);
+        assert!(field[3] > 128 && field[4] > 128);
+        assert!(field[2] < 128 && field[5] < 128);
+        // Further from the edge means further from the middle value.
+        assert!(field[0] < field[1] && field[1] < field[2]);
+        assert_eq!(field[3], field[4]);
+    }
+
+    #[test]
+    fn test_arrange_glyphs() {
+        let glyph = |x: f32, z: f32| GlyphInstance {
+            left_top: [x, 0.0, z],
+            right_bottom: [x + 10.0, 10.0],
+            tex_left_top: [0.0, 0.0],
+            tex_right_bottom: [1.0, 1.0],
+            color: [1.0; 4],
+        };
+        let mut group_keys = HashMap::new();
+        group_keys.insert(0.5f32.to_bits(), (0, 0.5));
+        group_keys.insert(0.5f32.to_bits() + 1, (1, 0.5));
+        let groups = [
+            (1, None),
+            (0, Some(ClipRect::new(0.0, 0.0, 15.0, 100.0))),
+        ];
+        let glyphs = [
+            glyph(0.0, 0.5),
+            glyph(0.0, f32::from_bits(0.5f32.to_bits() + 1)),
+            glyph(10.0, f32::from_bits(0.5f32.to_bits() + 1)),
+            glyph(20.0, f32::from_bits(0.5f32.to_bits() + 1)),
+        ];
+        let arranged = arrange_glyphs(&glyphs, &group_keys, &groups);
+
+        // The glyph outside the clip rect is gone and the lower layer comes first.
+        assert_eq!(arranged.len(), 3);
+        assert_eq!(arranged[0].left_top, [0.0, 0.0, 0.5]);
+        assert_eq!(arranged[1].right_bottom, [15.0, 10.0]);
+        assert_eq!(arranged[1].tex_right_bottom, [0.5, 1.0]);
+        assert_eq!(arranged[2].left_top[0], 0.0);
+    }
+}
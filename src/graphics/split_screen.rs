@@ -0,0 +1,301 @@
+use legion::prelude::Entity;
+use nalgebra_glm::Vec2;
+use std::collections::HashMap;
+use winit::event::VirtualKeyCode;
+
+use crate::{core::input::Input, scene::components::CameraData};
+
+/// The most players the screen can be split between.
+pub const MAX_PLAYERS: usize = 4;
+
+/// How the screen is divided between two or three players, four players always get a quarter each.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SplitLayout {
+    /// Players sit next to each other.
+    SideBySide,
+    /// Players are stacked on top of each other.
+    Stacked,
+}
+
+impl Default for SplitLayout {
+    fn default() -> Self {
+        SplitLayout::Stacked
+    }
+}
+
+/// A region of the screen with the origin in the top left.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Covers the whole screen.
+    pub fn full() -> Self {
+        Self::new(0.0, 0.0, 1.0, 1.0)
+    }
+
+    /// Turns a viewport in the 0..1 range into pixels, edges are rounded so neighbouring
+    /// viewports never overlap or leave a gap.
+    pub fn to_pixels(&self, width: f32, height: f32) -> Self {
+        let x = (self.x * width).round();
+        let y = (self.y * height).round();
+        Self::new(
+            x,
+            y,
+            ((self.x + self.width) * width).round() - x,
+            ((self.y + self.height) * height).round() - y,
+        )
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.x >= self.x
+            && point.y >= self.y
+            && point.x < self.x + self.width
+            && point.y < self.y + self.height
+    }
+}
+
+/// Works out the viewport of each player, with three players the first one gets the biggest.
+pub(crate) fn split_viewports(count: usize, layout: SplitLayout) -> Vec<Viewport> {
+    let stacked = layout == SplitLayout::Stacked;
+    match count {
+        0 => vec![],
+        1 => vec![Viewport::full()],
+        2 if stacked => vec![
+            Viewport::new(0.0, 0.0, 1.0, 0.5),
+            Viewport::new(0.0, 0.5, 1.0, 0.5),
+        ],
+        2 => vec![
+            Viewport::new(0.0, 0.0, 0.5, 1.0),
+            Viewport::new(0.5, 0.0, 0.5, 1.0),
+        ],
+        3 if stacked => vec![
+            Viewport::new(0.0, 0.0, 1.0, 0.5),
+            Viewport::new(0.0, 0.5, 0.5, 0.5),
+            Viewport::new(0.5, 0.5, 0.5, 0.5),
+        ],
+        3 => vec![
+            Viewport::new(0.0, 0.0, 0.5, 1.0),
+            Viewport::new(0.5, 0.0, 0.5, 0.5),
+            Viewport::new(0.5, 0.5, 0.5, 0.5),
+        ],
+        _ => vec![
+            Viewport::new(0.0, 0.0, 0.5, 0.5),
+            Viewport::new(0.5, 0.0, 0.5, 0.5),
+            Viewport::new(0.0, 0.5, 0.5, 0.5),
+            Viewport::new(0.5, 0.5, 0.5, 0.5),
+        ],
+    }
+}
+
+/// Whether this is the camera a view renders from, views without a camera use the active one.
+pub(crate) fn is_view_camera(view: Option<Entity>, entity: Entity, camera: &CameraData) -> bool {
+    match view {
+        Some(view) => view == entity,
+        None => camera.active,
+    }
+}
+
+/// One player in a split-screen game.
+pub struct Player {
+    /// The camera this player sees the world through.
+    pub camera: Entity,
+    bindings: HashMap<String, VirtualKeyCode>,
+    /// The camera's own viewport, it replaces the one from the layout.
+    viewport: Option<Viewport>,
+}
+
+/// Splits the screen between 2 to 4 players, a resource.
+/// Every player sees the same world through their own camera and the cameras are resized to fit
+/// their viewports. With no players everything renders from the active camera like usual.
+/// Cameras with a `viewport` use it instead of the layout's.
+/// Shadows are fitted to the first player's camera.
+#[derive(Default)]
+pub struct SplitScreen {
+    pub layout: SplitLayout,
+    players: Vec<Player>,
+    /// The active camera's viewport when there's no players.
+    main_viewport: Option<Viewport>,
+}
+
+impl SplitScreen {
+    /// Adds a player that sees the world through `camera` and returns it's index.
+    /// Returns None if there's already `MAX_PLAYERS` players.
+    pub fn add_player(&mut self, camera: Entity) -> Option<usize> {
+        if self.players.len() >= MAX_PLAYERS {
+            return None;
+        }
+        self.players.push(Player {
+            camera,
+            bindings: HashMap::new(),
+            viewport: None,
+        });
+        Some(self.players.len() - 1)
+    }
+
+    /// Removes a player, the players after it move down an index.
+    pub fn remove_player(&mut self, player: usize) {
+        if player < self.players.len() {
+            self.players.remove(player);
+        }
+    }
+
+    /// Removes every player, going back to a single view.
+    pub fn clear(&mut self) {
+        self.players.clear();
+    }
+
+    pub fn players(&self) -> &[Player] {
+        &self.players
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.players.is_empty()
+    }
+
+    /// The player's viewport in the 0..1 range.
+    pub fn viewport(&self, player: usize) -> Option<Viewport> {
+        self.player_viewports().get(player).copied()
+    }
+
+    /// Finds the player whose viewport has the given point in pixels, useful for routing the mouse.
+    pub fn player_at(&self, position: Vec2, width: f32, height: f32) -> Option<usize> {
+        self.player_viewports()
+            .iter()
+            .position(|viewport| viewport.to_pixels(width, height).contains(position))
+    }
+
+    /// Binds an action to a key for a player, so each player can have their own controls.
+    pub fn bind_key<T: Into<String>>(&mut self, player: usize, action: T, key: VirtualKeyCode) {
+        if let Some(player) = self.players.get_mut(player) {
+            player.bindings.insert(action.into(), key);
+        }
+    }
+
+    fn key(&self, player: usize, action: &str) -> Option<VirtualKeyCode> {
+        self.players
+            .get(player)
+            .and_then(|player| player.bindings.get(action))
+            .copied()
+    }
+
+    /// Will return true if the key bound to the player's action is down.
+    pub fn is_action_down(&self, player: usize, action: &str, input: &Input) -> bool {
+        self.key(player, action)
+            .map_or(false, |key| input.is_key_down(key))
+    }
+
+    /// Will return true if the key bound to the player's action was pressed this frame.
+    pub fn is_action_pressed(&self, player: usize, action: &str, input: &Input) -> bool {
+        self.key(player, action)
+            .map_or(false, |key| input.is_key_pressed(key))
+    }
+
+    /// Will return true if the key bound to the player's action was released this frame.
+    pub fn is_action_released(&self, player: usize, action: &str, input: &Input) -> bool {
+        self.key(player, action)
+            .map_or(false, |key| input.is_key_released(key))
+    }
+
+    /// Picks up the cameras' own viewports, `camera_viewport` gives the viewport of a camera.
+    pub(crate) fn update_viewports(
+        &mut self,
+        main_viewport: Option<Viewport>,
+        camera_viewport: impl Fn(Entity) -> Option<Viewport>,
+    ) {
+        self.main_viewport = main_viewport;
+        for player in self.players.iter_mut() {
+            player.viewport = camera_viewport(player.camera);
+        }
+    }
+
+    fn player_viewports(&self) -> Vec<Viewport> {
+        self.players
+            .iter()
+            .zip(split_viewports(self.players.len(), self.layout))
+            .map(|(player, viewport)| player.viewport.unwrap_or(viewport))
+            .collect()
+    }
+
+    /// The views to render, the camera is None when it should be the active camera.
+    pub(crate) fn views(&self) -> Vec<(Option<Entity>, Viewport)> {
+        if self.players.is_empty() {
+            return vec![(None, self.main_viewport.unwrap_or_else(Viewport::full))];
+        }
+        self.players
+            .iter()
+            .zip(self.player_viewports())
+            .map(|(player, viewport)| (Some(player.camera), viewport))
+            .collect()
+    }
+
+    /// Each view's viewport in pixels, a single None when there's one view covering the screen.
+    pub(crate) fn pixel_viewports(&self, (width, height): (u32, u32)) -> Vec<Option<Viewport>> {
+        if self.players.is_empty() {
+            let viewport = self.main_viewport;
+            return vec![viewport.map(|viewport| viewport.to_pixels(width as f32, height as f32))];
+        }
+        self.player_viewports()
+            .iter()
+            .map(|viewport| Some(viewport.to_pixels(width as f32, height as f32)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_viewports_cover_the_screen() {
+        for layout in [SplitLayout::SideBySide, SplitLayout::Stacked].iter() {
+            for count in 1..=MAX_PLAYERS {
+                let viewports = split_viewports(count, *layout);
+                assert_eq!(viewports.len(), count);
+                let area: f32 = viewports.iter().map(|v| v.width * v.height).sum();
+                assert!((area - 1.0).abs() < 0.0001);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pixel_viewports_dont_overlap() {
+        let viewports = split_viewports(2, SplitLayout::SideBySide);
+        let left = viewports[0].to_pixels(1281.0, 720.0);
+        let right = viewports[1].to_pixels(1281.0, 720.0);
+        assert_eq!(left.x + left.width, right.x);
+        assert_eq!(right.x + right.width, 1281.0);
+    }
+
+    #[test]
+    fn test_camera_viewports_replace_the_layout() {
+        let mut world = legion::prelude::Universe::new().create_world();
+        let cameras = world.insert((), vec![(0u32,), (1u32,)]).to_vec();
+        let mut split_screen = SplitScreen::default();
+        split_screen.add_player(cameras[0]);
+        split_screen.add_player(cameras[1]);
+        let corner = Viewport::new(0.7, 0.7, 0.25, 0.25);
+        split_screen.update_viewports(None, |camera| Some(corner).filter(|_| camera == cameras[1]));
+
+        assert_eq!(
+            split_screen.viewport(0),
+            Some(Viewport::new(0.0, 0.0, 1.0, 0.5))
+        );
+        assert_eq!(split_screen.viewport(1), Some(corner));
+        let views = split_screen.pixel_viewports((100, 100));
+        assert_eq!(views[1], Some(Viewport::new(70.0, 70.0, 25.0, 25.0)));
+    }
+}
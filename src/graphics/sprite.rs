@@ -0,0 +1,179 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra_glm::{Mat4, Vec2, Vec3, Vec4};
+use std::{collections::HashMap, ops::Range};
+
+use super::ClipRect;
+use crate::scene::components::Sprite;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct SpriteVertex {
+    pub position: Vec3,
+    pub uv: Vec2,
+    pub color: Vec4,
+}
+
+unsafe impl Zeroable for SpriteVertex {}
+unsafe impl Pod for SpriteVertex {}
+
+/// A run of sprites using the same image and clip rect, drawn with a single draw call.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SpriteBatch {
+    pub image: String,
+    pub clip: Option<ClipRect>,
+    pub vertices: Range<u32>,
+}
+
+/// Draws every entity with a `Sprite` and a `Transform` on top of the scene, a resource.
+/// Sprites are sorted by layer and grouped by image inside each layer, then they all go into
+/// one vertex buffer with a draw call per group. Put sprites from a `TextureAtlas` on the
+/// same layer and they're drawn together. Sprites with a `clip` rect are cut off with a
+/// scissor rect, they only share a batch with sprites clipped to the same rect.
+pub struct SpriteRenderer {
+    /// Nearest filtering with clamped edges so pixel art stays sharp, off by default.
+    pub pixel_perfect: bool,
+    /// The vertex buffer and it's capacity, it grows as needed.
+    pub(crate) buffer: Option<(wgpu::Buffer, u64)>,
+    /// Texture bind groups for each image and whether they're pixel perfect.
+    pub(crate) bind_groups: HashMap<(String, bool), wgpu::BindGroup>,
+    /// The asset manager's bind group generation the cached bind groups were made for.
+    pub(crate) bind_group_generation: u32,
+    pub(crate) draw_calls: usize,
+}
+
+impl Default for SpriteRenderer {
+    fn default() -> Self {
+        Self {
+            pixel_perfect: false,
+            buffer: None,
+            bind_groups: HashMap::new(),
+            bind_group_generation: 0,
+            draw_calls: 0,
+        }
+    }
+}
+
+impl SpriteRenderer {
+    /// Batches drawn last frame, each one is a draw call for every view.
+    pub fn draw_calls(&self) -> usize {
+        self.draw_calls
+    }
+}
+
+/// Builds the quads for `sprites` and splits them into batches.
+pub(crate) fn batch_sprites(
+    mut sprites: Vec<(&Sprite, &Mat4)>,
+) -> (Vec<SpriteVertex>, Vec<SpriteBatch>) {
+    // Stable, so sprites keep their order inside a batch.
+    sprites.sort_by(|(a, _), (b, _)| {
+        a.layer
+            .cmp(&b.layer)
+            .then_with(|| a.image.name().cmp(b.image.name()))
+    });
+
+    let mut vertices = Vec::with_capacity(sprites.len() * 6);
+    let mut batches: Vec<SpriteBatch> = Vec::new();
+    for (sprite, matrix) in sprites {
+        let start = vertices.len() as u32;
+        // Two triangles, the top of the image is at +y.
+        let corners = [
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (0.0, 1.0),
+            (0.0, 1.0),
+            (1.0, 0.0),
+            (1.0, 1.0),
+        ];
+        for &(x, y) in corners.iter() {
+            let local = Vec4::new(
+                (x - 0.5) * sprite.size.x,
+                (y - 0.5) * sprite.size.y,
+                0.0,
+                1.0,
+            );
+            let u = if sprite.flip_x { 1.0 - x } else { x };
+            let v = if sprite.flip_y { y } else { 1.0 - y };
+            let uv = Vec2::new(
+                sprite.uv_rect.x + u * sprite.uv_rect.z,
+                sprite.uv_rect.y + v * sprite.uv_rect.w,
+            );
+            vertices.push(SpriteVertex {
+                position: (*matrix * local).xyz(),
+                uv,
+                color: sprite.color,
+            });
+        }
+        let end = vertices.len() as u32;
+
+        match batches.last_mut() {
+            Some(batch) if batch.image == sprite.image.name() && batch.clip == sprite.clip => {
+                batch.vertices.end = end
+            }
+            _ => batches.push(SpriteBatch {
+                image: sprite.image.name().to_string(),
+                clip: sprite.clip,
+                vertices: start..end,
+            }),
+        }
+    }
+    (vertices, batches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{graphics::UvOrigin, Handle};
+
+    #[test]
+    fn test_batch_sprites() {
+        let sprite = |image: &str, layer| {
+            Sprite::new(Handle::new(image.to_string()), Vec2::new(2.0, 2.0)).with_layer(layer)
+        };
+        let sprites = [
+            sprite("b", 0),
+            sprite("a", 1),
+            sprite("a", 0),
+            sprite("b", 0),
+        ];
+        let matrix = Mat4::identity();
+        let (vertices, batches) = batch_sprites(sprites.iter().map(|s| (s, &matrix)).collect());
+
+        assert_eq!(vertices.len(), 24);
+        let images: Vec<&str> = batches.iter().map(|batch| batch.image.as_str()).collect();
+        assert_eq!(images, vec!["a", "b", "a"]);
+        assert_eq!(batches[1].vertices, 6..18);
+        assert_eq!(vertices[0].position, Vec3::new(-1.0, -1.0, 0.0));
+        assert_eq!(vertices[0].uv, Vec2::new(0.0, 1.0));
+    }
+
+    #[test]
+    fn test_batch_sprites_flips() {
+        let sprite = Sprite::new(Handle::new("a".to_string()), Vec2::new(1.0, 1.0))
+            .with_uv_rect(Vec4::new(0.5, 0.0, 0.5, 0.25), UvOrigin::BottomLeft)
+            .with_flip(true, true);
+        let matrix = Mat4::identity();
+        let (vertices, _) = batch_sprites(vec![(&sprite, &matrix)]);
+
+        // The bottom left corner shows the rect's top right.
+        assert_eq!(vertices[0].position, Vec3::new(-0.5, -0.5, 0.0));
+        assert_eq!(vertices[0].uv, Vec2::new(1.0, 0.75));
+    }
+
+    #[test]
+    fn test_batch_sprites_splits_clips() {
+        let sprite = Sprite::new(Handle::new("a".to_string()), Vec2::new(1.0, 1.0));
+        let clip = ClipRect::new(0.0, 0.0, 10.0, 10.0);
+        let sprites = [
+            sprite.clone(),
+            sprite.clone().with_clip(clip),
+            sprite.clone().with_clip(clip),
+        ];
+        let matrix = Mat4::identity();
+        let (_, batches) = batch_sprites(sprites.iter().map(|s| (s, &matrix)).collect());
+
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].clip, None);
+        assert_eq!(batches[1].clip, Some(clip));
+        assert_eq!(batches[1].vertices, 6..18);
+    }
+}
@@ -0,0 +1,105 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+
+/// Screen-space global illumination, a resource. Light bouncing off what's on screen is
+/// gathered from the lit frame at half resolution and blended with the last frames, so it
+/// shows up in the next frame's image. It's experimental and off by default.
+/// There's no albedo buffer, the bounce light is tinted by the color of the surface it lands
+/// on instead. Only light from surfaces on screen bounces, the environment map still lights
+/// the rest.
+/// Note: It's only drawn for the first split-screen view and skipped on the reduced render path.
+#[derive(Debug, Clone)]
+pub struct ScreenSpaceGI {
+    pub enabled: bool,
+    /// Scales the bounce light.
+    pub intensity: f32,
+    /// How far in world units the rays look for surfaces to gather light from.
+    pub radius: f32,
+    /// Rays per pixel each frame, the temporal blend averages them over a few frames.
+    pub samples: u32,
+    /// Steps along each ray.
+    pub steps: u32,
+    /// Blends each frame with the last ones. Without it the bounce light is very noisy.
+    pub temporal_reprojection: bool,
+    pub(crate) frame: u32,
+    pub(crate) previous_view_projection: Option<Mat4>,
+}
+
+impl Default for ScreenSpaceGI {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            intensity: 1.0,
+            radius: 2.0,
+            samples: 4,
+            steps: 8,
+            temporal_reprojection: true,
+            frame: 0,
+            previous_view_projection: None,
+        }
+    }
+}
+
+impl ScreenSpaceGI {
+    /// Call whenever the camera jumps somewhere new so last frame's light isn't blended in.
+    pub fn reset_history(&mut self) {
+        self.previous_view_projection = None;
+    }
+
+    pub(crate) fn uniform(
+        &self,
+        view_projection: Mat4,
+        camera_position: Vec3,
+        frame_size: (u32, u32),
+        viewport: Vec4,
+    ) -> SsgiUniform {
+        // The first frame doesn't have anything to blend with.
+        let history_weight = match self.previous_view_projection {
+            Some(_) if self.temporal_reprojection => 0.9,
+            _ => 0.0,
+        };
+        SsgiUniform {
+            view_projection,
+            inverse_view_projection: nalgebra_glm::inverse(&view_projection),
+            previous_view_projection: self.previous_view_projection.unwrap_or(view_projection),
+            camera_position: Vec4::new(
+                camera_position.x,
+                camera_position.y,
+                camera_position.z,
+                self.intensity.max(0.0),
+            ),
+            info: Vec4::new(
+                self.radius.max(0.01),
+                self.samples.max(1) as f32,
+                self.steps.max(1) as f32,
+                history_weight,
+            ),
+            screen: Vec4::new(
+                frame_size.0 as f32,
+                frame_size.1 as f32,
+                (self.frame % 64) as f32,
+                0.0,
+            ),
+            viewport,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SsgiUniform {
+    pub view_projection: Mat4,
+    pub inverse_view_projection: Mat4,
+    pub previous_view_projection: Mat4,
+    // (camera position, intensity)
+    pub camera_position: Vec4,
+    // (radius, samples, steps, history weight)
+    pub info: Vec4,
+    // (frame width, frame height, frame, unused)
+    pub screen: Vec4,
+    // The view's (x, y, width, height) in uv space.
+    pub viewport: Vec4,
+}
+
+unsafe impl Zeroable for SsgiUniform {}
+unsafe impl Pod for SsgiUniform {}
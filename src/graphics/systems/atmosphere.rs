@@ -0,0 +1,280 @@
+use legion::prelude::*;
+use nalgebra_glm::Vec3;
+
+use crate::{
+    graphics::{
+        atmosphere::{MULTI_SCATTERING_LUT_SIZE, TRANSMITTANCE_LUT_SIZE},
+        material::{skybox::SkyboxType, Skybox},
+        pipeline_manager::PipelineManager,
+        pipelines::atmosphere::AtmosphereResources,
+        renderer::DepthTexture,
+        resources::{CurrentRenderTarget, GPUResourceManager},
+        split_screen::is_view_camera,
+        Atmosphere, CommandBufferQueue, CommandQueueItem, SplitScreen,
+    },
+    scene::components,
+};
+
+// Copies CPU side LUT texels into one of the float textures.
+fn upload_lut(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    texture: &wgpu::Texture,
+    data: &[f32],
+    width: u32,
+    height: u32,
+) {
+    let buffer =
+        device.create_buffer_with_data(bytemuck::cast_slice(data), wgpu::BufferUsage::COPY_SRC);
+    // Both LUTs are wide enough that their rows are a multiple of 256 bytes.
+    encoder.copy_buffer_to_texture(
+        wgpu::BufferCopyView {
+            buffer: &buffer,
+            layout: wgpu::TextureDataLayout {
+                offset: 0,
+                bytes_per_row: width * 16,
+                rows_per_image: height,
+            },
+        },
+        wgpu::TextureCopyView {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        },
+    );
+}
+
+/// Bakes the atmosphere's LUTs when it changes and draws the sky view LUT the skybox samples.
+/// Aerial perspective is drawn over the frame after everything else that writes depth.
+/// Only runs while a skybox uses `SkyboxType::Atmosphere`.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("atmosphere")
+        .write_resource::<Atmosphere>()
+        .write_resource::<AtmosphereResources>()
+        .write_resource::<CommandBufferQueue>()
+        .read_resource::<PipelineManager>()
+        .read_resource::<GPUResourceManager>()
+        .read_resource::<SplitScreen>()
+        .read_resource::<CurrentRenderTarget>()
+        .read_resource::<DepthTexture>()
+        .read_resource::<wgpu::Device>()
+        .with_query(<(Read<Skybox>,)>::query())
+        .with_query(<(Read<components::CameraData>,)>::query())
+        .with_query(<(Read<components::DirectionalLightData>,)>::query())
+        .build(
+            |_,
+             world,
+             (
+                atmosphere,
+                atmosphere_resources,
+                command_buffer_queue,
+                pipeline_manager,
+                resource_manager,
+                split_screen,
+                current_render_target,
+                depth_texture,
+                device,
+            ),
+             (skyboxes, cameras, directional_lights)| {
+                let uses_atmosphere = skyboxes
+                    .iter(&world)
+                    .any(|(skybox,)| skybox.skybox_type == SkyboxType::Atmosphere);
+                if !uses_atmosphere {
+                    atmosphere_resources.bind_group = None;
+                    atmosphere_resources.sky_view_bind_group = None;
+                    return;
+                }
+                // Probes reuse the LUTs from the main view and don't get aerial perspective.
+                if current_render_target.0.is_some() {
+                    return;
+                }
+
+                let views = split_screen.views();
+                let (camera_entity, _) = views[0];
+                let camera_position = match cameras
+                    .iter_entities(&world)
+                    .find(|(entity, (camera,))| is_view_camera(camera_entity, *entity, camera))
+                {
+                    Some((_, (camera,))) => camera.position,
+                    None => return,
+                };
+                let (sun_direction, sun_color) = directional_lights
+                    .iter(&world)
+                    .next()
+                    .map(|(light,)| (light.direction, light.color))
+                    .unwrap_or_else(|| (Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 1.0, 1.0)));
+                let (sky_view, (frame, resolve_target), frame_size) = match (
+                    pipeline_manager.get_attachment("atmosphere_sky_view"),
+                    pipeline_manager.get_frame_target(),
+                    pipeline_manager.get_attachment_size("frame"),
+                ) {
+                    (Some(sky_view), Some(frame_target), Some(frame_size)) => {
+                        (sky_view, frame_target, frame_size)
+                    }
+                    _ => return,
+                };
+
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("atmosphere"),
+                });
+
+                let medium = atmosphere.medium_key();
+                if atmosphere.baked != Some(medium) {
+                    let transmittance = atmosphere.transmittance_lut();
+                    let multi_scattering = atmosphere.multi_scattering_lut(&transmittance);
+                    upload_lut(
+                        device,
+                        &mut encoder,
+                        &atmosphere_resources.transmittance_texture,
+                        &transmittance,
+                        TRANSMITTANCE_LUT_SIZE.0,
+                        TRANSMITTANCE_LUT_SIZE.1,
+                    );
+                    upload_lut(
+                        device,
+                        &mut encoder,
+                        &atmosphere_resources.multi_scattering_texture,
+                        &multi_scattering,
+                        MULTI_SCATTERING_LUT_SIZE,
+                        MULTI_SCATTERING_LUT_SIZE,
+                    );
+                    atmosphere.baked = Some(medium);
+                }
+
+                let uniform = atmosphere.uniform(camera_position, sun_direction, sun_color);
+                let uniform_buffer = device.create_buffer_with_data(
+                    bytemuck::bytes_of(&uniform),
+                    wgpu::BufferUsage::UNIFORM,
+                );
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("atmosphere"),
+                    layout: resource_manager.get_bind_group_layout("atmosphere").unwrap(),
+                    bindings: &[
+                        wgpu::Binding {
+                            binding: 0,
+                            resource: wgpu::BindingResource::Buffer(uniform_buffer.slice(..)),
+                        },
+                        wgpu::Binding {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&atmosphere_resources.sampler),
+                        },
+                        wgpu::Binding {
+                            binding: 2,
+                            resource: wgpu::BindingResource::TextureView(
+                                &atmosphere_resources.transmittance_view,
+                            ),
+                        },
+                        wgpu::Binding {
+                            binding: 3,
+                            resource: wgpu::BindingResource::TextureView(
+                                &atmosphere_resources.multi_scattering_view,
+                            ),
+                        },
+                    ],
+                });
+                let texture_bind_group = |layout: &str, view: &wgpu::TextureView| {
+                    device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: Some(layout),
+                        layout: resource_manager.get_bind_group_layout(layout).unwrap(),
+                        bindings: &[wgpu::Binding {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(view),
+                        }],
+                    })
+                };
+
+                {
+                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                            attachment: sky_view,
+                            resolve_target: None,
+                            load_op: wgpu::LoadOp::Clear,
+                            store_op: wgpu::StoreOp::Store,
+                            clear_color: wgpu::Color::BLACK,
+                        }],
+                        depth_stencil_attachment: None,
+                    });
+                    let (globals, _) = resource_manager.player_bind_groups(0);
+                    render_pass.set_pipeline(
+                        &pipeline_manager
+                            .get("atmosphere_sky_view", None)
+                            .unwrap()
+                            .render_pipeline,
+                    );
+                    render_pass.set_bind_group(0, globals, &[]);
+                    render_pass.set_bind_group(1, &bind_group, &[]);
+                    render_pass.draw(0..3, 0..1);
+                }
+                command_buffer_queue
+                    .push(CommandQueueItem {
+                        buffer: encoder.finish(),
+                        name: "atmosphere".to_string(),
+                    })
+                    .unwrap();
+
+                if atmosphere.aerial_perspective {
+                    let mut encoder =
+                        device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some("aerial_perspective"),
+                        });
+                    let depth_bind_group =
+                        texture_bind_group("atmosphere_depth", &depth_texture.0);
+                    {
+                        let mut render_pass =
+                            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                                    attachment: frame,
+                                    resolve_target,
+                                    load_op: wgpu::LoadOp::Load,
+                                    store_op: wgpu::StoreOp::Store,
+                                    clear_color: wgpu::Color::BLACK,
+                                }],
+                                depth_stencil_attachment: None,
+                            });
+                        render_pass.set_pipeline(
+                            &pipeline_manager
+                                .get("atmosphere_aerial", None)
+                                .unwrap()
+                                .render_pipeline,
+                        );
+                        render_pass.set_bind_group(1, &bind_group, &[]);
+                        render_pass.set_bind_group(2, &depth_bind_group, &[]);
+                        for (player, viewport) in split_screen
+                            .pixel_viewports(frame_size)
+                            .iter()
+                            .enumerate()
+                        {
+                            if let Some(viewport) = viewport {
+                                render_pass.set_viewport(
+                                    viewport.x,
+                                    viewport.y,
+                                    viewport.width,
+                                    viewport.height,
+                                    0.0,
+                                    1.0,
+                                );
+                            }
+                            let (globals, _) = resource_manager.player_bind_groups(player);
+                            render_pass.set_bind_group(0, globals, &[]);
+                            render_pass.draw(0..3, 0..1);
+                        }
+                    }
+                    command_buffer_queue
+                        .push(CommandQueueItem {
+                            buffer: encoder.finish(),
+                            name: "aerial_perspective".to_string(),
+                        })
+                        .unwrap();
+                }
+
+                atmosphere_resources.sky_view_bind_group =
+                    Some(texture_bind_group("atmosphere_sky_view", sky_view));
+                atmosphere_resources.bind_group = Some(bind_group);
+            },
+        )
+}
@@ -0,0 +1,183 @@
+use legion::prelude::*;
+use nalgebra_glm::Vec3;
+
+use crate::{
+    graphics::{
+        pipeline_manager::{Pipeline, PipelineManager},
+        pipelines::clouds::CloudResources,
+        resources::{CurrentRenderTarget, GPUResourceManager},
+        split_screen::is_view_camera,
+        Clouds, CommandBufferQueue, CommandQueueItem, SplitScreen,
+    },
+    scene::{components, resources::DeltaTime},
+};
+
+// Draws a full screen triangle with the cloud bindings.
+fn cloud_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    pipeline: &Pipeline,
+    bind_group: &wgpu::BindGroup,
+    target: &wgpu::TextureView,
+    resolve_target: Option<&wgpu::TextureView>,
+    load_op: wgpu::LoadOp,
+    viewport: Option<(f32, f32, f32, f32)>,
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+            attachment: target,
+            resolve_target,
+            load_op,
+            store_op: wgpu::StoreOp::Store,
+            clear_color: wgpu::Color::BLACK,
+        }],
+        depth_stencil_attachment: None,
+    });
+    if let Some((x, y, width, height)) = viewport {
+        render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+    }
+    render_pass.set_pipeline(&pipeline.render_pipeline);
+    render_pass.set_bind_group(0, bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+}
+
+/// Raymarches the clouds at half resolution, blends them with last frame's and draws them over
+/// the sky. Runs after the skybox so opaque geometry ends up in front of the clouds.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("clouds")
+        .write_resource::<Clouds>()
+        .write_resource::<CommandBufferQueue>()
+        .read_resource::<DeltaTime>()
+        .read_resource::<PipelineManager>()
+        .read_resource::<GPUResourceManager>()
+        .read_resource::<SplitScreen>()
+        .read_resource::<CloudResources>()
+        .read_resource::<CurrentRenderTarget>()
+        .read_resource::<wgpu::Device>()
+        .with_query(<(Read<components::CameraData>,)>::query())
+        .with_query(<(Read<components::DirectionalLightData>,)>::query())
+        .build(
+            |_,
+             world,
+             (
+                clouds,
+                command_buffer_queue,
+                delta_time,
+                pipeline_manager,
+                resource_manager,
+                split_screen,
+                cloud_resources,
+                current_render_target,
+                device,
+            ),
+             (cameras, directional_lights)| {
+                if !clouds.enabled {
+                    clouds.reset_history();
+                    return;
+                }
+                // Probes capture the sky without clouds.
+                if current_render_target.0.is_some() {
+                    return;
+                }
+
+                let (camera_entity, viewport) = split_screen.views()[0];
+                let camera = match cameras
+                    .iter_entities(&world)
+                    .find(|(entity, (camera,))| is_view_camera(camera_entity, *entity, camera))
+                {
+                    Some((_, (camera,))) => camera,
+                    None => return,
+                };
+                let (sun_direction, sun_color) = directional_lights
+                    .iter(&world)
+                    .next()
+                    .map(|(light,)| (light.direction, light.color))
+                    .unwrap_or_else(|| (Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 1.0, 1.0)));
+
+                let ((frame, resolve_target), frame_size) = match (
+                    pipeline_manager.get_frame_target(),
+                    pipeline_manager.get_attachment_size("frame"),
+                ) {
+                    (Some(frame_target), Some(frame_size)) => (frame_target, frame_size),
+                    _ => return,
+                };
+                // Gathered into a transient target and copied into the history afterwards.
+                let (target, history) = match (
+                    pipeline_manager.get_attachment("clouds_gather"),
+                    pipeline_manager.get_attachment("clouds_history"),
+                ) {
+                    (Some(target), Some(history)) => (target, history),
+                    _ => return,
+                };
+
+                clouds.time += delta_time.0;
+                let view_projection = camera.get_matrix();
+                let uniform =
+                    clouds.uniform(view_projection, camera.position, sun_direction, sun_color);
+                let uniform_buffer = device.create_buffer_with_data(
+                    bytemuck::bytes_of(&uniform),
+                    wgpu::BufferUsage::UNIFORM,
+                );
+                let layout = resource_manager.get_bind_group_layout("clouds").unwrap();
+                let create_bind_group = |clouds_view: &wgpu::TextureView| {
+                    device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: Some("clouds"),
+                        layout,
+                        bindings: &[
+                            wgpu::Binding {
+                                binding: 0,
+                                resource: wgpu::BindingResource::Buffer(uniform_buffer.slice(..)),
+                            },
+                            wgpu::Binding {
+                                binding: 1,
+                                resource: wgpu::BindingResource::TextureView(
+                                    &cloud_resources.noise_view,
+                                ),
+                            },
+                            wgpu::Binding {
+                                binding: 2,
+                                resource: wgpu::BindingResource::TextureView(clouds_view),
+                            },
+                            wgpu::Binding {
+                                binding: 3,
+                                resource: wgpu::BindingResource::Sampler(&cloud_resources.sampler),
+                            },
+                        ],
+                    })
+                };
+
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("clouds"),
+                });
+                cloud_pass(
+                    &mut encoder,
+                    pipeline_manager.get("clouds", None).unwrap(),
+                    &create_bind_group(history),
+                    target,
+                    None,
+                    wgpu::LoadOp::Clear,
+                    None,
+                );
+                let viewport = viewport.to_pixels(frame_size.0 as f32, frame_size.1 as f32);
+                cloud_pass(
+                    &mut encoder,
+                    pipeline_manager.get("clouds_composite", None).unwrap(),
+                    &create_bind_group(target),
+                    frame,
+                    resolve_target,
+                    wgpu::LoadOp::Load,
+                    Some((viewport.x, viewport.y, viewport.width, viewport.height)),
+                );
+                pipeline_manager.copy_attachment(&mut encoder, "clouds_gather", "clouds_history");
+
+                clouds.previous_view_projection = Some(view_projection);
+                clouds.frame = clouds.frame.wrapping_add(1);
+
+                command_buffer_queue
+                    .push(CommandQueueItem {
+                        buffer: encoder.finish(),
+                        name: "clouds".to_string(),
+                    })
+                    .unwrap();
+            },
+        )
+}
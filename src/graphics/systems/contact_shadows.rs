@@ -0,0 +1,132 @@
+use legion::prelude::*;
+
+use crate::{
+    graphics::{
+        pipeline_manager::PipelineManager,
+        pipelines::contact_shadows::ContactShadowResources,
+        renderer::DepthTexture,
+        resources::{CurrentRenderTarget, GPUResourceManager},
+        CommandBufferQueue, CommandQueueItem, SplitScreen,
+    },
+    scene::components,
+};
+
+/// Darkens what the meshes drew where a short ray towards a light with `contact_shadows` hits
+/// the depth buffer. Skipped when no light has them turned on.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("contact_shadows")
+        .write_resource::<CommandBufferQueue>()
+        .read_resource::<ContactShadowResources>()
+        .read_resource::<PipelineManager>()
+        .read_resource::<GPUResourceManager>()
+        .read_resource::<SplitScreen>()
+        .read_resource::<CurrentRenderTarget>()
+        .read_resource::<DepthTexture>()
+        .read_resource::<wgpu::Device>()
+        .with_query(<(Read<components::DirectionalLightData>,)>::query())
+        .with_query(<(Read<components::PointLightData>,)>::query())
+        .with_query(<(Read<components::SpotLightData>,)>::query())
+        .build(
+            |_,
+             world,
+             (
+                command_buffer_queue,
+                contact_shadow_resources,
+                pipeline_manager,
+                resource_manager,
+                split_screen,
+                current_render_target,
+                depth_texture,
+                device,
+            ),
+             (directional_lights, point_lights, spot_lights)| {
+                // Probes don't use the main depth buffer.
+                if current_render_target.0.is_some() {
+                    return;
+                }
+                let has_contact_shadows = directional_lights
+                    .iter(&world)
+                    .any(|(light,)| light.shadow.contact_shadows)
+                    || point_lights
+                        .iter(&world)
+                        .any(|(light,)| light.shadow.contact_shadows)
+                    || spot_lights
+                        .iter(&world)
+                        .any(|(light,)| light.shadow.contact_shadows);
+                if !has_contact_shadows {
+                    return;
+                }
+                let ((frame, resolve_target), frame_size) = match (
+                    pipeline_manager.get_frame_target(),
+                    pipeline_manager.get_attachment_size("frame"),
+                ) {
+                    (Some(frame_target), Some(frame_size)) => (frame_target, frame_size),
+                    _ => return,
+                };
+
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("contact_shadows"),
+                });
+                let depth_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("contact_shadows_depth"),
+                    layout: resource_manager
+                        .get_bind_group_layout("contact_shadows_depth")
+                        .unwrap(),
+                    bindings: &[
+                        wgpu::Binding {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&depth_texture.0),
+                        },
+                        wgpu::Binding {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(
+                                &contact_shadow_resources.sampler,
+                            ),
+                        },
+                    ],
+                });
+                {
+                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                            attachment: frame,
+                            resolve_target,
+                            load_op: wgpu::LoadOp::Load,
+                            store_op: wgpu::StoreOp::Store,
+                            clear_color: wgpu::Color::BLACK,
+                        }],
+                        depth_stencil_attachment: None,
+                    });
+                    render_pass.set_pipeline(
+                        &pipeline_manager
+                            .get("contact_shadows", None)
+                            .unwrap()
+                            .render_pipeline,
+                    );
+                    render_pass.set_bind_group(0, &depth_bind_group, &[]);
+                    for (player, viewport) in
+                        split_screen.pixel_viewports(frame_size).iter().enumerate()
+                    {
+                        if let Some(viewport) = viewport {
+                            render_pass.set_viewport(
+                                viewport.x,
+                                viewport.y,
+                                viewport.width,
+                                viewport.height,
+                                0.0,
+                                1.0,
+                            );
+                        }
+                        let (globals, _) = resource_manager.player_bind_groups(player);
+                        render_pass.set_bind_group(1, globals, &[]);
+                        render_pass.draw(0..3, 0..1);
+                    }
+                }
+                command_buffer_queue
+                    .push(CommandQueueItem {
+                        buffer: encoder.finish(),
+                        name: "contact_shadows".to_string(),
+                    })
+                    .unwrap();
+            },
+        )
+}
@@ -0,0 +1,141 @@
+use legion::prelude::*;
+
+use crate::graphics::{
+    pipeline_manager::PipelineManager,
+    renderer::DepthTexture,
+    resources::{CurrentRenderTarget, GPUResourceManager},
+    CommandBufferQueue, CommandQueueItem, DebugDraw, SplitScreen,
+};
+
+/// Draws the `DebugDraw` lines over the frame for every view and clears them.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("debug_draw")
+        .write_resource::<DebugDraw>()
+        .write_resource::<CommandBufferQueue>()
+        .read_resource::<PipelineManager>()
+        .read_resource::<GPUResourceManager>()
+        .read_resource::<SplitScreen>()
+        .read_resource::<CurrentRenderTarget>()
+        .read_resource::<DepthTexture>()
+        .read_resource::<wgpu::Device>()
+        .build(
+            |_,
+             _,
+             (
+                debug_draw,
+                command_buffer_queue,
+                pipeline_manager,
+                resource_manager,
+                split_screen,
+                current_render_target,
+                depth_texture,
+                device,
+            ),
+             _| {
+                // Probes shouldn't capture debug lines, they're kept for the main view.
+                if current_render_target.0.is_some() {
+                    return;
+                }
+                let line_count = debug_draw.lines.len();
+                let overlay_count = debug_draw.overlay_lines.len();
+                if line_count + overlay_count == 0 {
+                    return;
+                }
+                let ((frame, resolve_target), frame_size) = match (
+                    pipeline_manager.get_frame_target(),
+                    pipeline_manager.get_attachment_size("frame"),
+                ) {
+                    (Some(frame_target), Some(frame_size)) => (frame_target, frame_size),
+                    _ => {
+                        debug_draw.clear();
+                        return;
+                    }
+                };
+
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("debug"),
+                });
+
+                // Both lists share one buffer, the overlay lines come after the depth tested ones.
+                let mut vertices = Vec::with_capacity(line_count + overlay_count);
+                vertices.extend_from_slice(&debug_draw.lines);
+                vertices.extend_from_slice(&debug_draw.overlay_lines);
+                let data: &[u8] = bytemuck::cast_slice(&vertices);
+                let size = data.len() as u64;
+                let capacity = debug_draw.buffer.as_ref().map_or(0, |(_, capacity)| *capacity);
+                if capacity < size {
+                    let capacity = size.next_power_of_two();
+                    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("debug_lines"),
+                        size: capacity,
+                        usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+                    });
+                    debug_draw.buffer = Some((buffer, capacity));
+                }
+                let (buffer, _) = debug_draw.buffer.as_ref().unwrap();
+                let staging = device.create_buffer_with_data(data, wgpu::BufferUsage::COPY_SRC);
+                encoder.copy_buffer_to_buffer(&staging, 0, buffer, 0, size);
+
+                {
+                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                            attachment: frame,
+                            resolve_target,
+                            load_op: wgpu::LoadOp::Load,
+                            store_op: wgpu::StoreOp::Store,
+                            clear_color: wgpu::Color::BLACK,
+                        }],
+                        depth_stencil_attachment: Some(
+                            wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                                attachment: &depth_texture.0,
+                                depth_load_op: wgpu::LoadOp::Load,
+                                depth_store_op: wgpu::StoreOp::Store,
+                                stencil_load_op: wgpu::LoadOp::Load,
+                                stencil_store_op: wgpu::StoreOp::Store,
+                                clear_depth: 1.0,
+                                clear_stencil: 0,
+                            },
+                        ),
+                    });
+                    render_pass.set_vertex_buffer(0, buffer.slice(..size));
+                    let lines = pipeline_manager.get("debug_lines", None).unwrap();
+                    let overlay = pipeline_manager.get("debug_lines_overlay", None).unwrap();
+                    for (player, viewport) in split_screen
+                        .pixel_viewports(frame_size)
+                        .iter()
+                        .enumerate()
+                    {
+                        if let Some(viewport) = viewport {
+                            render_pass.set_viewport(
+                                viewport.x,
+                                viewport.y,
+                                viewport.width,
+                                viewport.height,
+                                0.0,
+                                1.0,
+                            );
+                        }
+                        let (globals, _) = resource_manager.player_bind_groups(player);
+                        render_pass.set_bind_group(0, globals, &[]);
+                        if line_count > 0 {
+                            render_pass.set_pipeline(&lines.render_pipeline);
+                            render_pass.draw(0..line_count as u32, 0..1);
+                        }
+                        if overlay_count > 0 {
+                            render_pass.set_pipeline(&overlay.render_pipeline);
+                            let end = (line_count + overlay_count) as u32;
+                            render_pass.draw(line_count as u32..end, 0..1);
+                        }
+                    }
+                }
+
+                command_buffer_queue
+                    .push(CommandQueueItem {
+                        buffer: encoder.finish(),
+                        name: "debug".to_string(),
+                    })
+                    .unwrap();
+                debug_draw.clear();
+            },
+        )
+}
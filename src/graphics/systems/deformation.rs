@@ -0,0 +1,293 @@
+use legion::prelude::*;
+use nalgebra_glm::Vec3;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{
+    graphics::{
+        deformation::{DeformationUniform, DisplaceState, DisplaceUniform, SurfaceState},
+        resources::{CurrentRenderTarget, GPUResourceManager},
+        CommandBufferQueue, CommandQueueItem, SurfaceDeformation, DEFORMATION_FORMAT,
+    },
+    scene::{components, resources::DeltaTime},
+    AssetManager,
+};
+
+// Mesh copies and heightfields need unique names in the asset manager.
+static NEXT_SURFACE: AtomicU32 = AtomicU32::new(0);
+
+fn buffer_binding(binding: u32, buffer: &wgpu::Buffer) -> wgpu::Binding<'_> {
+    wgpu::Binding {
+        binding,
+        resource: wgpu::BindingResource::Buffer(buffer.slice(..)),
+    }
+}
+
+// Copies the entity's mesh and creates it's heightfield, None if the mesh isn't loaded yet.
+fn create_state(
+    device: &wgpu::Device,
+    asset_manager: &mut AssetManager,
+    resource_manager: &GPUResourceManager,
+    surface: &components::DeformableSurface,
+    source_mesh: String,
+) -> Option<SurfaceState> {
+    let id = NEXT_SURFACE.fetch_add(1, Ordering::Relaxed);
+    let mut mesh = asset_manager
+        .try_get_mesh(source_mesh.clone())
+        .ok()?
+        .deformable_copy(surface.max_depth);
+    mesh.create_buffers(device, wgpu::BufferUsage::STORAGE);
+    let heightfield = asset_manager.create_storage_texture(
+        device,
+        format!("deformation_{}", id),
+        surface.resolution.max(1),
+        surface.resolution.max(1),
+        DEFORMATION_FORMAT,
+    );
+    let heightfield_view = &asset_manager.get_image(heightfield.name()).view;
+
+    let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("deformation"),
+        size: std::mem::size_of::<DeformationUniform>() as u64,
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("deformation"),
+        layout: resource_manager.get_bind_group_layout("deformation").unwrap(),
+        bindings: &[
+            buffer_binding(0, &uniform),
+            wgpu::Binding {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(heightfield_view),
+            },
+        ],
+    });
+
+    let displace_layout = resource_manager
+        .get_bind_group_layout("deformation_displace")
+        .unwrap();
+    let sub_meshes = mesh
+        .sub_meshes
+        .iter()
+        .map(|sub_mesh| {
+            let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("deformation_displace"),
+                size: std::mem::size_of::<DisplaceUniform>() as u64,
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            });
+            let rest_vertices = device.create_buffer_with_data(
+                bytemuck::cast_slice(&sub_mesh.vertices),
+                wgpu::BufferUsage::STORAGE,
+            );
+            let rest_attributes = device.create_buffer_with_data(
+                bytemuck::cast_slice(&sub_mesh.attribute_vertices),
+                wgpu::BufferUsage::STORAGE,
+            );
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("deformation_displace"),
+                layout: displace_layout,
+                bindings: &[
+                    buffer_binding(0, &uniform),
+                    buffer_binding(1, &rest_vertices),
+                    buffer_binding(2, sub_mesh.vertex_buffer.as_ref().unwrap()),
+                    buffer_binding(3, &rest_attributes),
+                    buffer_binding(4, sub_mesh.attribute_buffer.as_ref().unwrap()),
+                    wgpu::Binding {
+                        binding: 5,
+                        resource: wgpu::BindingResource::TextureView(heightfield_view),
+                    },
+                ],
+            });
+            DisplaceState {
+                uniform,
+                bind_group,
+                vertex_count: sub_mesh.vertices.len(),
+                rest_vertices,
+                rest_attributes,
+            }
+        })
+        .collect();
+
+    let mesh_name = format!("deformation_{}.mesh", id);
+    asset_manager.insert_mesh(device, mesh_name.clone(), mesh);
+    Some(SurfaceState {
+        source_mesh,
+        mesh_name,
+        heightfield,
+        uniform,
+        bind_group,
+        sub_meshes,
+    })
+}
+
+/// Presses every `Deformer` into the `DeformableSurface`s under it and pushes the surfaces'
+/// vertices down. The first time it sees a surface the entity's mesh is swapped for a copy.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("deformation")
+        .write_resource::<SurfaceDeformation>()
+        .write_resource::<AssetManager>()
+        .write_resource::<CommandBufferQueue>()
+        .read_resource::<GPUResourceManager>()
+        .read_resource::<CurrentRenderTarget>()
+        .read_resource::<DeltaTime>()
+        .read_resource::<wgpu::Device>()
+        .with_query(<(Read<components::Deformer>, Read<components::Transform>)>::query())
+        .with_query(<(
+            Read<components::DeformableSurface>,
+            Write<components::Mesh>,
+            Read<components::Transform>,
+        )>::query())
+        .with_query(<(Write<components::Mesh>,)>::query())
+        .build(
+            |_,
+             mut world,
+             (
+                deformation,
+                asset_manager,
+                command_buffer_queue,
+                resource_manager,
+                current_render_target,
+                delta_time,
+                device,
+            ),
+             (deformer_query, surface_query, mesh_query)| {
+                // Probes see the surfaces as they are, they shouldn't press them in again.
+                if current_render_target.0.is_some() {
+                    return;
+                }
+                let ready = resource_manager.get_compute_pipeline("deformation").is_some()
+                    && resource_manager
+                        .get_compute_pipeline("deformation_displace")
+                        .is_some();
+                if !ready {
+                    return;
+                }
+
+                let deformers: Vec<(Vec3, components::Deformer)> = deformer_query
+                    .iter(&world)
+                    .map(|(deformer, transform)| (transform.position, *deformer))
+                    .collect();
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("deformation"),
+                });
+                let mut surfaces = HashSet::new();
+
+                for (entity, (surface, mut mesh, transform)) in
+                    surface_query.iter_entities_mut(&mut world)
+                {
+                    if !deformation.surfaces.contains_key(&entity) {
+                        // Copies of copies, from cloned entities, still go back to the original.
+                        let source_mesh = deformation
+                            .surfaces
+                            .values()
+                            .find(|state| state.mesh_name == mesh.mesh_name)
+                            .map_or(mesh.mesh_name.clone(), |state| state.source_mesh.clone());
+                        let state = match create_state(
+                            &device,
+                            &mut asset_manager,
+                            &resource_manager,
+                            &surface,
+                            source_mesh,
+                        ) {
+                            Some(state) => state,
+                            None => continue,
+                        };
+                        mesh.mesh_name = state.mesh_name.clone();
+                        deformation.surfaces.insert(entity, state);
+                    }
+                    surfaces.insert(entity);
+                    let state = deformation.surfaces.get(&entity).unwrap();
+
+                    let uniform = DeformationUniform::new(
+                        &surface,
+                        transform.position,
+                        deformers.iter().map(|(position, deformer)| (*position, deformer)),
+                        delta_time.0,
+                    );
+                    let staging = device.create_buffer_with_data(
+                        bytemuck::bytes_of(&uniform),
+                        wgpu::BufferUsage::COPY_SRC,
+                    );
+                    encoder.copy_buffer_to_buffer(
+                        &staging,
+                        0,
+                        &state.uniform,
+                        0,
+                        std::mem::size_of::<DeformationUniform>() as u64,
+                    );
+                    // 8x8 texels per workgroup.
+                    let image = asset_manager.get_image(state.heightfield.name());
+                    let workgroups = [
+                        (image.extent.width + 7) / 8,
+                        (image.extent.height + 7) / 8,
+                        1,
+                    ];
+                    if let Err(error) = resource_manager.dispatch(
+                        &mut encoder,
+                        "deformation",
+                        &[&state.bind_group],
+                        workgroups,
+                    ) {
+                        log::error!("{}", error);
+                    }
+
+                    for sub_mesh in state.sub_meshes.iter() {
+                        let uniform = DisplaceUniform::new(
+                            &surface,
+                            transform.position,
+                            transform.matrix,
+                            sub_mesh.vertex_count,
+                        );
+                        let staging = device.create_buffer_with_data(
+                            bytemuck::bytes_of(&uniform),
+                            wgpu::BufferUsage::COPY_SRC,
+                        );
+                        encoder.copy_buffer_to_buffer(
+                            &staging,
+                            0,
+                            &sub_mesh.uniform,
+                            0,
+                            std::mem::size_of::<DisplaceUniform>() as u64,
+                        );
+                        // 64 vertices per workgroup.
+                        if let Err(error) = resource_manager.dispatch(
+                            &mut encoder,
+                            "deformation_displace",
+                            &[&sub_mesh.bind_group],
+                            [(sub_mesh.vertex_count as u32 + 63) / 64, 1, 1],
+                        ) {
+                            log::error!("{}", error);
+                        }
+                    }
+                }
+
+                // Surfaces that are gone take their heightfield and mesh copy with them,
+                // entities that only lost the component get their old mesh back.
+                let removed: Vec<Entity> = deformation
+                    .surfaces
+                    .keys()
+                    .filter(|entity| !surfaces.contains(entity))
+                    .copied()
+                    .collect();
+                for entity in removed {
+                    let state = deformation.surfaces.remove(&entity).unwrap();
+                    for (mut mesh,) in mesh_query.iter_mut(&mut world) {
+                        if mesh.mesh_name == state.mesh_name {
+                            mesh.mesh_name = state.source_mesh.clone();
+                        }
+                    }
+                    asset_manager.remove_image(state.heightfield.name());
+                    asset_manager.remove_mesh(state.mesh_name);
+                }
+
+                if !surfaces.is_empty() {
+                    command_buffer_queue
+                        .push(CommandQueueItem {
+                            buffer: encoder.finish(),
+                            name: "deformation".to_string(),
+                        })
+                        .unwrap();
+                }
+            },
+        )
+}
@@ -4,29 +4,33 @@ use std::convert::TryInto;
 
 use crate::{
     graphics::{
-        pipelines::{DirectionalLight, GlobalUniform, LightingUniform, PointLight, MAX_LIGHTS},
+        pipelines::{DirectionalLight, GlobalUniform, LightingUniform, MAX_LIGHTS},
         resources::GPUResourceManager,
-        CommandBufferQueue, CommandQueueItem,
+        split_screen::is_view_camera,
+        water::WaterUniform,
+        CommandBufferQueue, CommandQueueItem, SplitScreen,
     },
-    scene::components,
+    scene::{components, resources::DeltaTime},
 };
 
 pub fn create() -> Box<dyn Schedulable> {
+    // Drives the water caustics.
+    let mut time = 0.0;
     SystemBuilder::new("encoder_globals")
         .write_resource::<CommandBufferQueue>()
         .read_resource::<GPUResourceManager>()
+        .read_resource::<SplitScreen>()
+        .read_resource::<DeltaTime>()
         .read_resource::<wgpu::Device>()
         .with_query(<(Read<components::CameraData>,)>::query())
         .with_query(<(Read<components::DirectionalLightData>,)>::query())
-        .with_query(<(
-            Read<components::PointLightData>,
-            Read<components::Transform>,
-        )>::query())
+        .with_query(<(Read<components::WaterVolume>, Read<components::Transform>)>::query())
         .build(
-            |_,
-             world,
-             (command_buffer_queue, resource_manager, device),
-             (camera_data, directional_lights, point_lights)| {
+            move |_,
+                  world,
+                  (command_buffer_queue, resource_manager, split_screen, delta_time, device),
+                  (camera_data, directional_lights, water_volumes)| {
+                time += delta_time.0;
                 let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
                     label: Some("globals"),
                 });
@@ -34,21 +38,20 @@ pub fn create() -> Box<dyn Schedulable> {
                 // ******************************************************************************
                 // This section is meant to prepare our global uniforms and pass them to the GPU.
                 // ******************************************************************************
-                {
-                    let filtered_camera_data: Vec<_> = camera_data
-                        .iter(&world)
-                        .filter(|(camera,)| camera.active)
-                        .collect();
-                    let camera_data: Option<&(
-                        legion::borrow::Ref<'_, crate::scene::components::camera_data::CameraData>,
-                    )> = filtered_camera_data.first();
-
-                    if camera_data.is_none() {
-                        return;
-                    }
-
-                    let camera_data = &camera_data.as_ref().unwrap().0;
+                // Every split-screen player gets their own camera uniforms.
+                for (player, (camera_entity, _)) in split_screen.views().into_iter().enumerate() {
+                    let camera_data = camera_data
+                        .iter_entities(&world)
+                        .find(|(entity, (camera,))| is_view_camera(camera_entity, *entity, camera));
+
+                    let camera_data = match camera_data {
+                        Some((_, (camera_data,))) => camera_data,
+                        None if player == 0 => return,
+                        None => continue,
+                    };
                     let camera_matrix = camera_data.get_matrix();
+                    let (uniform_buffer, view_model_uniform_buffer) =
+                        resource_manager.player_uniform_buffers(player);
 
                     let uniforms = GlobalUniform {
                         view_projection: camera_matrix,
@@ -70,7 +73,26 @@ pub fn create() -> Box<dyn Schedulable> {
                     encoder.copy_buffer_to_buffer(
                         &constants_buffer,
                         0,
-                        &resource_manager.global_uniform_buffer,
+                        uniform_buffer,
+                        0,
+                        std::mem::size_of::<GlobalUniform>() as u64,
+                    );
+
+                    // View models get the same camera with their own projection.
+                    let view_model_projection = camera_data.view_model_projection();
+                    let view_model_uniforms = GlobalUniform {
+                        view_projection: view_model_projection * camera_data.view,
+                        projection: view_model_projection,
+                        ..uniforms
+                    };
+                    let view_model_buffer = device.create_buffer_with_data(
+                        bytemuck::bytes_of(&view_model_uniforms),
+                        wgpu::BufferUsage::COPY_SRC,
+                    );
+                    encoder.copy_buffer_to_buffer(
+                        &view_model_buffer,
+                        0,
+                        view_model_uniform_buffer,
                         0,
                         std::mem::size_of::<GlobalUniform>() as u64,
                     );
@@ -87,47 +109,24 @@ pub fn create() -> Box<dyn Schedulable> {
                                 data.direction.x,
                                 data.direction.y,
                                 data.direction.z,
-                                0.0,
+                                data.shadow.contact_shadow_ray(),
                             ),
                             color: Vec4::new(data.color.x, data.color.y, data.color.z, 1.0),
                         })
                         .collect();
 
-                    // TODO: Use some sort of distance calculation to get the closest lights.
-                    let mut point_light_data_vec: Vec<PointLight> = point_lights
-                        .iter(&world)
-                        .map(|(data, transform)| PointLight {
-                            attenuation: Vec4::new(data.attenuation, 0.0, 0.0, 0.0),
-                            color: Vec4::new(data.color.x, data.color.y, data.color.z, 1.0),
-                            position: Vec4::new(
-                                transform.position.x,
-                                transform.position.y,
-                                transform.position.z,
-                                0.0,
-                            ),
-                        })
-                        .collect();
-
                     let total_dir_lights = directional_light_data_vec.len() as u32;
-                    let total_point_lights = point_light_data_vec.len() as u32;
 
                     // Fill in missing data if we don't have it.
-                    point_light_data_vec.resize_with(MAX_LIGHTS / 2, || PointLight::default());
                     directional_light_data_vec
                         .resize_with(MAX_LIGHTS / 2, || DirectionalLight::default());
 
                     let light_uniform = LightingUniform {
-                        light_num: Vec4::new(
-                            total_dir_lights as f32,
-                            total_point_lights as f32,
-                            0.0,
-                            0.0,
-                        ),
+                        light_num: Vec4::new(total_dir_lights as f32, 0.0, 0.0, 0.0),
                         directional_lights: directional_light_data_vec
                             .as_slice()
                             .try_into()
                             .unwrap(),
-                        point_lights: point_light_data_vec.as_slice().try_into().unwrap(),
                     };
 
                     let lighting_buffer = device.create_buffer_with_data(
@@ -144,6 +143,27 @@ pub fn create() -> Box<dyn Schedulable> {
                     );
                 }
 
+                // ******************************************************************************
+                // Water volumes for caustics and wet surfaces.
+                // ******************************************************************************
+                let volumes = water_volumes
+                    .iter(&world)
+                    .map(|(volume, transform)| (transform.position, *volume))
+                    .collect::<Vec<_>>();
+                let volumes = volumes.iter().map(|(position, volume)| (*position, volume));
+                let water_uniform = WaterUniform::new(volumes, time);
+                let water_buffer = device.create_buffer_with_data(
+                    bytemuck::bytes_of(&water_uniform),
+                    wgpu::BufferUsage::COPY_SRC,
+                );
+                encoder.copy_buffer_to_buffer(
+                    &water_buffer,
+                    0,
+                    &resource_manager.global_water_buffer,
+                    0,
+                    std::mem::size_of::<WaterUniform>() as u64,
+                );
+
                 command_buffer_queue
                     .push(CommandQueueItem {
                         buffer: encoder.finish(),
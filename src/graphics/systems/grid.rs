@@ -0,0 +1,126 @@
+use legion::prelude::*;
+
+use crate::graphics::{
+    pipeline_manager::PipelineManager,
+    renderer::DepthTexture,
+    resources::{CurrentRenderTarget, GPUResourceManager},
+    CommandBufferQueue, CommandQueueItem, EditorGrid, SplitScreen,
+};
+
+/// Draws the `EditorGrid` over the scene for every view while it's enabled.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("editor_grid")
+        .write_resource::<EditorGrid>()
+        .write_resource::<CommandBufferQueue>()
+        .read_resource::<PipelineManager>()
+        .read_resource::<GPUResourceManager>()
+        .read_resource::<SplitScreen>()
+        .read_resource::<CurrentRenderTarget>()
+        .read_resource::<DepthTexture>()
+        .read_resource::<wgpu::Device>()
+        .build(
+            |_,
+             _,
+             (
+                grid,
+                command_buffer_queue,
+                pipeline_manager,
+                resource_manager,
+                split_screen,
+                current_render_target,
+                depth_texture,
+                device,
+            ),
+             _| {
+                // Probes shouldn't capture the grid.
+                if !grid.enabled || current_render_target.0.is_some() {
+                    return;
+                }
+                let ((frame, resolve_target), frame_size) = match (
+                    pipeline_manager.get_frame_target(),
+                    pipeline_manager.get_attachment_size("frame"),
+                ) {
+                    (Some(frame_target), Some(frame_size)) => (frame_target, frame_size),
+                    _ => return,
+                };
+
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("editor_grid"),
+                });
+
+                let uniform = grid.uniform_data();
+                let data: &[u8] = bytemuck::bytes_of(&uniform);
+                if grid.uniform.is_none() {
+                    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("editor_grid"),
+                        size: data.len() as u64,
+                        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+                    });
+                    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: Some("editor_grid"),
+                        layout: resource_manager.get_bind_group_layout("grid").unwrap(),
+                        bindings: &[wgpu::Binding {
+                            binding: 0,
+                            resource: wgpu::BindingResource::Buffer(buffer.slice(..)),
+                        }],
+                    });
+                    grid.uniform = Some((buffer, bind_group));
+                }
+                let (buffer, bind_group) = grid.uniform.as_ref().unwrap();
+                let staging = device.create_buffer_with_data(data, wgpu::BufferUsage::COPY_SRC);
+                encoder.copy_buffer_to_buffer(&staging, 0, buffer, 0, data.len() as u64);
+
+                {
+                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                            attachment: frame,
+                            resolve_target,
+                            load_op: wgpu::LoadOp::Load,
+                            store_op: wgpu::StoreOp::Store,
+                            clear_color: wgpu::Color::BLACK,
+                        }],
+                        depth_stencil_attachment: Some(
+                            wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                                attachment: &depth_texture.0,
+                                depth_load_op: wgpu::LoadOp::Load,
+                                depth_store_op: wgpu::StoreOp::Store,
+                                stencil_load_op: wgpu::LoadOp::Load,
+                                stencil_store_op: wgpu::StoreOp::Store,
+                                clear_depth: 1.0,
+                                clear_stencil: 0,
+                            },
+                        ),
+                    });
+                    let pipeline = pipeline_manager.get("grid", None).unwrap();
+                    render_pass.set_pipeline(&pipeline.render_pipeline);
+                    render_pass.set_bind_group(1, bind_group, &[]);
+                    for (player, viewport) in split_screen
+                        .pixel_viewports(frame_size)
+                        .iter()
+                        .enumerate()
+                    {
+                        if let Some(viewport) = viewport {
+                            render_pass.set_viewport(
+                                viewport.x,
+                                viewport.y,
+                                viewport.width,
+                                viewport.height,
+                                0.0,
+                                1.0,
+                            );
+                        }
+                        let (globals, _) = resource_manager.player_bind_groups(player);
+                        render_pass.set_bind_group(0, globals, &[]);
+                        render_pass.draw(0..3, 0..1);
+                    }
+                }
+
+                command_buffer_queue
+                    .push(CommandQueueItem {
+                        buffer: encoder.finish(),
+                        name: "editor_grid".to_string(),
+                    })
+                    .unwrap();
+            },
+        )
+}
@@ -0,0 +1,198 @@
+use legion::prelude::*;
+use nalgebra_glm::{Vec3, Vec4};
+
+use crate::{
+    graphics::{
+        light_clusters::{ClusterUniform, GpuLight, CLUSTER_Z, MAX_CLUSTERED_LIGHTS},
+        light_cookies::LightCookies,
+        pipelines::{light_culling::LightCulling, shadow::MAX_SPOT_SHADOWS},
+        resources::GPUResourceManager,
+        split_screen::is_view_camera,
+        CommandBufferQueue, CommandQueueItem, SplitScreen,
+    },
+    scene::components,
+    AssetManager,
+};
+
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("light_culling")
+        .write_resource::<CommandBufferQueue>()
+        .write_resource::<LightCookies>()
+        .read_resource::<AssetManager>()
+        .read_resource::<GPUResourceManager>()
+        .read_resource::<LightCulling>()
+        .read_resource::<SplitScreen>()
+        .read_resource::<wgpu::Device>()
+        .read_resource::<wgpu::SwapChainDescriptor>()
+        .with_query(<(Read<components::CameraData>,)>::query())
+        .with_query(<(
+            Read<components::PointLightData>,
+            Read<components::Transform>,
+        )>::query())
+        .with_query(<(
+            Read<components::SpotLightData>,
+            Read<components::Transform>,
+        )>::query())
+        .build(
+            |_,
+             world,
+             (
+                command_buffer_queue,
+                light_cookies,
+                asset_manager,
+                resource_manager,
+                light_culling,
+                split_screen,
+                device,
+                sc_desc,
+            ),
+             (cameras, point_lights, spot_lights)| {
+                let pipeline = match light_culling.pipeline.as_ref() {
+                    Some(pipeline) => pipeline,
+                    None => return,
+                };
+                // Every split-screen player gets their own clusters, the first player's camera
+                // decides which lights are kept when there's too many.
+                let views: Vec<_> = split_screen
+                    .views()
+                    .into_iter()
+                    .zip(split_screen.pixel_viewports((sc_desc.width, sc_desc.height)))
+                    .enumerate()
+                    .filter_map(|(player, ((camera_entity, _), viewport))| {
+                        cameras
+                            .iter_entities(&world)
+                            .find(|(entity, (camera,))| {
+                                is_view_camera(camera_entity, *entity, camera)
+                            })
+                            .map(|(_, (camera,))| (player, camera, viewport))
+                    })
+                    .collect();
+                let camera = match views.first() {
+                    Some((_, camera, _)) => camera,
+                    None => return,
+                };
+
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("light_culling"),
+                });
+                // New cookies are copied into the cookie texture with the lights.
+                let mut cookie_layer = |name: &Option<String>, cube: bool| {
+                    name.as_ref().and_then(|name| {
+                        light_cookies.layer(
+                            name,
+                            cube,
+                            &asset_manager,
+                            &device,
+                            &mut encoder,
+                            &resource_manager.cookie_texture,
+                        )
+                    })
+                };
+
+                let mut lights: Vec<GpuLight> = Vec::new();
+                for (light, transform) in point_lights.iter(&world) {
+                    let mut gpu_light = GpuLight::point(
+                        transform.position,
+                        light.color,
+                        light.intensity,
+                        light.range,
+                    )
+                    .with_contact_shadows(light.shadow.contact_shadow_ray());
+                    if let Some(layer) = cookie_layer(&light.cookie, true) {
+                        gpu_light = gpu_light.with_cookie(&transform.rotation, layer);
+                    }
+                    lights.push(gpu_light);
+                }
+                // The shadow system hands out the atlas tiles in the same order.
+                let mut shadow_tile = 0;
+                for (light, transform) in spot_lights.iter(&world) {
+                    let direction = nalgebra_glm::quat_rotate_vec3(
+                        &transform.rotation,
+                        &Vec3::new(0.0, 0.0, -1.0),
+                    );
+                    let mut gpu_light = GpuLight::spot(
+                        transform.position,
+                        direction,
+                        light.color,
+                        light.intensity,
+                        light.range,
+                        light.inner_angle,
+                        light.outer_angle,
+                    )
+                    .with_contact_shadows(light.shadow.contact_shadow_ray());
+                    if let Some(layer) = cookie_layer(&light.cookie, false) {
+                        gpu_light = gpu_light.with_cookie(&transform.rotation, layer);
+                    }
+                    if light.casts_shadows && shadow_tile < MAX_SPOT_SHADOWS {
+                        gpu_light = gpu_light.with_shadow(shadow_tile);
+                        shadow_tile += 1;
+                    }
+                    lights.push(gpu_light);
+                }
+
+                // Too many lights, keep the ones closest to the camera.
+                if lights.len() > MAX_CLUSTERED_LIGHTS {
+                    let distance = |light: &GpuLight| {
+                        nalgebra_glm::distance2(&light.position.xyz(), &camera.position)
+                    };
+                    lights.sort_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap());
+                    lights.truncate(MAX_CLUSTERED_LIGHTS);
+                }
+
+                if !lights.is_empty() {
+                    let light_buffer = device.create_buffer_with_data(
+                        bytemuck::cast_slice(&lights),
+                        wgpu::BufferUsage::COPY_SRC,
+                    );
+                    encoder.copy_buffer_to_buffer(
+                        &light_buffer,
+                        0,
+                        &resource_manager.light_buffer,
+                        0,
+                        (lights.len() * std::mem::size_of::<GpuLight>()) as u64,
+                    );
+                }
+
+                for (player, camera, viewport) in views.iter() {
+                    let (x, y, width, height) = match viewport {
+                        Some(viewport) => (viewport.x, viewport.y, viewport.width, viewport.height),
+                        None => (0.0, 0.0, sc_desc.width as f32, sc_desc.height as f32),
+                    };
+                    let (near, far) = camera.depth_range();
+                    let uniform = ClusterUniform {
+                        inverse_projection: nalgebra_glm::inverse(&camera.projection),
+                        view: camera.view,
+                        screen: Vec4::new(width, height, near, far),
+                        counts: Vec4::new(lights.len() as f32, x, y, 0.0),
+                    };
+                    let (cluster_uniform_buffer, bind_group) =
+                        resource_manager.player_light_culling(*player);
+
+                    let uniform_buffer = device.create_buffer_with_data(
+                        bytemuck::bytes_of(&uniform),
+                        wgpu::BufferUsage::COPY_SRC,
+                    );
+                    encoder.copy_buffer_to_buffer(
+                        &uniform_buffer,
+                        0,
+                        cluster_uniform_buffer,
+                        0,
+                        std::mem::size_of::<ClusterUniform>() as u64,
+                    );
+
+                    // One workgroup per depth slice, each thread handles one cluster in the slice.
+                    let mut compute_pass = encoder.begin_compute_pass();
+                    compute_pass.set_pipeline(pipeline);
+                    compute_pass.set_bind_group(0, bind_group, &[]);
+                    compute_pass.dispatch(1, 1, CLUSTER_Z);
+                }
+
+                command_buffer_queue
+                    .push(CommandQueueItem {
+                        buffer: encoder.finish(),
+                        name: "light_culling".to_string(),
+                    })
+                    .unwrap();
+            },
+        )
+}
@@ -1,14 +1,14 @@
 use crate::{
     graphics::{
-        pipelines::GlobalUniform, render_graph::RenderGraphNode, renderer::DepthTexture,
-        resources::GPUResourceManager, CommandBufferQueue, CommandQueueItem, RenderGraph,
+        pipeline_manager::PipelineManager, pipelines::GlobalUniform,
+        render_graph::RenderGraphNode, renderer::DepthTexture, resources::GPUResourceManager,
+        CommandBufferQueue, CommandQueueItem, RenderGraph,
     },
     scene::components,
     AssetManager,
 };
 use legion::prelude::*;
 use nalgebra_glm::Vec4;
-use std::sync::Arc;
 
 pub fn create() -> Box<dyn Schedulable> {
     SystemBuilder::new("render_lines")
@@ -16,7 +16,7 @@ pub fn create() -> Box<dyn Schedulable> {
         .write_resource::<CommandBufferQueue>()
         .read_resource::<RenderGraph>()
         .read_resource::<wgpu::Device>()
-        .read_resource::<Arc<wgpu::SwapChainOutput>>()
+        .read_resource::<PipelineManager>()
         .read_resource::<GPUResourceManager>()
         .read_resource::<DepthTexture>()
         .with_query(<(Read<components::CameraData>,)>::query())
@@ -29,11 +29,15 @@ pub fn create() -> Box<dyn Schedulable> {
                 command_buffer_queue,
                 render_graph,
                 device,
-                output,
+                pipeline_manager,
                 resource_manager,
                 depth_texture,
             ),
              (camera_data, mesh_query)| {
+                let (frame_view, resolve_target) = match pipeline_manager.get_frame_target() {
+                    Some(frame_target) => frame_target,
+                    None => return,
+                };
                 let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
                     label: Some("line_renderer"),
                 });
@@ -88,8 +92,8 @@ pub fn create() -> Box<dyn Schedulable> {
                 {
                     let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                         color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                            attachment: &output.view,
-                            resolve_target: None,
+                            attachment: frame_view,
+                            resolve_target,
                             load_op: wgpu::LoadOp::Load,
                             store_op: wgpu::StoreOp::Store,
                             clear_color: wgpu::Color {
@@ -0,0 +1,89 @@
+use legion::prelude::*;
+use nalgebra_glm::Vec4;
+
+use crate::{
+    graphics::{material::Material, resources::GPUResourceManager},
+    scene::components,
+    AssetManager,
+};
+
+/// Creates bind groups for material instances whenever their overrides change.
+/// Each instance gets it's own uniform buffer, textures are shared with the material.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("material_instance")
+        .read_resource::<AssetManager>()
+        .read_resource::<wgpu::Device>()
+        .write_resource::<GPUResourceManager>()
+        .with_query(<(
+            Write<components::MaterialInstance>,
+            Read<components::Material>,
+            Read<components::Transform>,
+        )>::query())
+        .build(
+            |_, mut world, (asset_manager, device, resource_manager), query| {
+                let generation = asset_manager.bind_group_generation();
+                for (mut instance, material, transform) in query.iter_mut(&mut world) {
+                    let uploaded = Some((material.index, generation, instance.overrides()));
+                    if instance.slot == Some(transform.index) && instance.uploaded == uploaded {
+                        continue;
+                    }
+
+                    let pbr_material = match asset_manager.try_get_material(material.index) {
+                        Ok(Material::PBR(pbr_material)) => pbr_material,
+                        _ => continue,
+                    };
+
+                    let mut uniform = pbr_material.uniform();
+                    if let Some(color) = instance.color {
+                        uniform.color = color;
+                    }
+                    // z and w tell the shader to use the value instead of the texture.
+                    if let Some(metallic) = instance.metallic {
+                        uniform.info.x = metallic;
+                        uniform.info.z = 1.0;
+                    }
+                    if let Some(roughness) = instance.roughness {
+                        uniform.info.y = roughness;
+                        uniform.info.w = 1.0;
+                    }
+                    if let Some(emissive) = instance.emissive {
+                        uniform.emissive = Vec4::new(emissive.x, emissive.y, emissive.z, 0.0);
+                    }
+
+                    let uniform_buf = device.create_buffer_with_data(
+                        bytemuck::bytes_of(&uniform),
+                        wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+                    );
+                    let bind_group = pbr_material.create_bind_group_with(
+                        &asset_manager.images,
+                        &device,
+                        resource_manager
+                            .get_bind_group_layout("pbr_material_layout")
+                            .unwrap(),
+                        &uniform_buf,
+                        instance
+                            .main_texture
+                            .as_deref()
+                            .unwrap_or(&pbr_material.main_texture),
+                    );
+
+                    // The transform changed, drop the old bindings.
+                    if let Some(slot) = instance.slot {
+                        if slot != transform.index {
+                            resource_manager.remove_multi_bind_group("pbr_instance", slot);
+                            resource_manager.remove_multi_buffer("pbr_instance", slot);
+                        }
+                    }
+                    resource_manager.add_multi_bind_group(
+                        "pbr_instance",
+                        bind_group,
+                        transform.index,
+                    );
+                    resource_manager.add_multi_buffer("pbr_instance", uniform_buf, transform.index);
+
+                    instance.slot = Some(transform.index);
+                    instance.uploaded = uploaded;
+                }
+            },
+        )
+}
@@ -1,14 +1,29 @@
 use crate::{
+    core::FrameStats,
     graphics::{
-        material::Material, pipeline_manager::PipelineManager, renderer::DepthTexture,
-        resources::GPUResourceManager, CommandBufferQueue, CommandQueueItem, RenderGraph,
+        bounds::{BoundingSphere, Frustum},
+        culling::aabb_lines,
+        instancing::{build_batches, InstanceData, StaticCache},
+        material::{Material, PBRMaterial},
+        mesh::SubMesh,
+        particles::EmitterState,
+        pipeline_manager::{PipelineManager, PipelineVariant},
+        pipelines::{
+            shadow::{spot_shadow_viewport, MAX_SPOT_SHADOWS, SHADOW_CASCADES},
+            MAX_LIGHTS,
+        },
+        renderer::DepthTexture,
+        resources::{CurrentRenderTarget, GPUResourceManager},
+        split_screen::is_view_camera,
+        CommandBufferQueue, CommandQueueItem, FrustumCulling, ParticleRenderer, RenderGraph,
+        RenderQueue, RenderSettings, SplitScreen,
     },
-    scene::components,
+    scene::{components, resources::Visibility},
     AssetManager,
 };
 use components::transform::LocalUniform;
 use legion::prelude::*;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
 
 pub fn create() -> Box<dyn Schedulable> {
     SystemBuilder::new("render_mesh")
@@ -16,16 +31,31 @@ pub fn create() -> Box<dyn Schedulable> {
         .write_resource::<CommandBufferQueue>()
         .read_resource::<RenderGraph>()
         .read_resource::<wgpu::Device>()
-        .read_resource::<Arc<wgpu::SwapChainOutput>>()
         .read_resource::<GPUResourceManager>()
         .read_resource::<DepthTexture>()
+        .read_resource::<CurrentRenderTarget>()
         .read_resource::<PipelineManager>()
+        .read_resource::<Visibility>()
+        .read_resource::<SplitScreen>()
+        .read_resource::<ParticleRenderer>()
+        .read_resource::<FrameStats>()
+        .write_resource::<FrustumCulling>()
+        .write_resource::<StaticCache>()
+        .read_resource::<RenderSettings>()
         .with_query(<(Write<components::Transform>,)>::query())
         .with_query(<(
             Read<components::Mesh>,
             Read<components::Material>,
             Read<components::Transform>,
         )>::query())
+        .with_query(<(Read<components::DirectionalLightData>,)>::query())
+        .with_query(<(Read<components::SpotLightData>,)>::query())
+        .with_query(<(Read<components::InstanceColor>,)>::query())
+        .with_query(<(Read<components::CameraData>,)>::query())
+        .with_query(<(Read<components::SortBias>,)>::query())
+        .with_query(<(Read<components::ViewModel>,)>::query())
+        .with_query(<(Read<components::Static>,)>::query())
+        .with_query(<(Read<components::ShadowFlags>,)>::query())
         .build(
             |_,
              mut world,
@@ -34,12 +64,61 @@ pub fn create() -> Box<dyn Schedulable> {
                 command_buffer_queue,
                 render_graph,
                 device,
-                output,
                 resource_manager,
                 depth_texture,
+                current_render_target,
                 pipeline_manager,
+                visibility,
+                split_screen,
+                particle_renderer,
+                frame_stats,
+                frustum_culling,
+                static_cache,
+                render_settings,
             ),
-             (transform_query, mesh_query)| {
+             (
+                transform_query,
+                mesh_query,
+                directional_lights,
+                spot_lights,
+                instance_colors,
+                cameras,
+                sort_bias_query,
+                view_model_query,
+                static_query,
+                shadow_flags_query,
+            )| {
+                // Probes render the scene into a face of their own cube with a single view.
+                let probe_target = current_render_target.0.as_ref();
+                let ((frame_view, resolve_target), frame_size) = match (
+                    probe_target,
+                    pipeline_manager.get_frame_target(),
+                    pipeline_manager.get_attachment_size("frame"),
+                ) {
+                    (Some((target, view)), _, _) => ((view, None), (target.width, target.height)),
+                    (None, Some(frame_target), Some(size)) => (frame_target, size),
+                    _ => return,
+                };
+                let depth_view = match probe_target {
+                    Some((target, _)) => target.depth_texture_view.as_ref().unwrap(),
+                    None => &depth_texture.0,
+                };
+                // Probe cubes are single sampled, with MSAA on they're drawn with the single
+                // sampled variants of each pipeline.
+                let single_sampled = probe_target.is_some() && render_settings.msaa();
+                let probe_variant = |variant: PipelineVariant| PipelineVariant {
+                    single_sampled,
+                    ..variant
+                };
+                let unlit_name = |name: &str| {
+                    if single_sampled {
+                        format!("{}_single_sampled", name)
+                    } else {
+                        name.to_string()
+                    }
+                };
+                let _scope = frame_stats.scope("render_mesh");
+
                 // Create mesh encoder
                 let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
                     label: Some("mesh"),
@@ -48,32 +127,50 @@ pub fn create() -> Box<dyn Schedulable> {
                 // ******************************************************************************
                 // This section is where we upload our transforms to the GPU
                 // ******************************************************************************
-                if transform_query.iter_mut(&mut world).count() > 0 {
-                    let size = std::mem::size_of::<LocalUniform>();
-                    let mut_world = &mut world;
-                    let mut temp_buf_data = device.create_buffer_mapped(&wgpu::BufferDescriptor {
-                        size: (transform_query.iter_mut(mut_world).count() * size) as u64,
-                        usage: wgpu::BufferUsage::COPY_SRC,
-                        label: None,
-                    });
-
-                    // FIXME: Align and use `LayoutVerified`
-                    for ((mut transform,), slot) in transform_query
-                        .iter_mut(mut_world)
-                        .zip(temp_buf_data.data().chunks_exact_mut(size))
-                    {
-                        transform.update();
-                        slot.copy_from_slice(bytemuck::bytes_of(&LocalUniform {
-                            world: transform.matrix,
-                        }));
+                // Static transforms are only uploaded the first time they're seen.
+                let statics: HashSet<Entity> = static_query
+                    .iter_entities(&world)
+                    .map(|(entity, _)| entity)
+                    .collect();
+                let shadow_flags: HashMap<Entity, components::ShadowFlags> = shadow_flags_query
+                    .iter_entities(&world)
+                    .map(|(entity, (flags,))| (entity, *flags))
+                    .collect();
+                let flags = |entity: Entity| shadow_flags.get(&entity).copied().unwrap_or_default();
+                let mut uploads = Vec::new();
+                for (entity, (mut transform,)) in transform_query.iter_entities_mut(&mut world) {
+                    if statics.contains(&entity) {
+                        if !static_cache.uploaded.insert(transform.index) {
+                            continue;
+                        }
+                    } else {
+                        static_cache.uploaded.remove(&transform.index);
                     }
-
-                    let temp_buf = temp_buf_data.finish();
-
-                    let mut i = 0;
-                    for (transform,) in transform_query.iter_mut(mut_world) {
+                    transform.update();
+                    let receive_shadows = if flags(entity).receive_shadows {
+                        1.0
+                    } else {
+                        0.0
+                    };
+                    uploads.push((
+                        transform.index,
+                        LocalUniform {
+                            world: transform.matrix,
+                            info: nalgebra_glm::vec4(receive_shadows, 0.0, 0.0, 0.0),
+                        },
+                    ));
+                }
+                if !uploads.is_empty() {
+                    let size = std::mem::size_of::<LocalUniform>();
+                    let uniforms: Vec<LocalUniform> =
+                        uploads.iter().map(|(_, uniform)| *uniform).collect();
+                    let temp_buf = device.create_buffer_with_data(
+                        bytemuck::cast_slice(&uniforms),
+                        wgpu::BufferUsage::COPY_SRC,
+                    );
+                    for (i, (index, _)) in uploads.iter().enumerate() {
                         let transform_buffer =
-                            resource_manager.get_multi_buffer("transform", transform.index);
+                            resource_manager.get_multi_buffer("transform", *index);
                         encoder.copy_buffer_to_buffer(
                             &temp_buf,
                             (i * size) as wgpu::BufferAddress,
@@ -81,18 +178,396 @@ pub fn create() -> Box<dyn Schedulable> {
                             0,
                             size as wgpu::BufferAddress,
                         );
-                        i += 1;
                     }
                 }
 
+                // ******************************************************************************
+                // This section picks each mesh's level of detail from how much of the screen
+                // it covers, with split-screen the player it looks biggest to decides.
+                // Skinned meshes always use their full detail mesh.
+                // ******************************************************************************
+                let lod_views: Vec<(nalgebra_glm::Vec3, nalgebra_glm::Mat4)> = split_screen
+                    .views()
+                    .into_iter()
+                    .filter_map(|(camera_entity, _)| {
+                        cameras
+                            .iter_entities(&world)
+                            .find(|(entity, (camera,))| {
+                                is_view_camera(camera_entity, *entity, camera)
+                            })
+                            .map(|(_, (camera,))| (camera.position, camera.projection))
+                    })
+                    .collect();
+                let lods: HashMap<Entity, usize> = mesh_query
+                    .iter_entities(&world)
+                    .filter_map(|(entity, (mesh, _, transform))| {
+                        let asset_mesh = asset_manager.get_mesh(mesh.mesh_name.clone());
+                        if asset_mesh.lods.is_empty()
+                            || asset_mesh.is_skinned()
+                            || lod_views.is_empty()
+                        {
+                            return None;
+                        }
+                        let bounds = BoundingSphere::from_aabb(
+                            &asset_mesh.aabb.transform(&transform.matrix),
+                        );
+                        let screen_size = lod_views
+                            .iter()
+                            .map(|(eye, projection)| bounds.screen_size(eye, projection))
+                            .fold(0.0, f32::max);
+                        Some((entity, asset_mesh.select_lod(screen_size)))
+                    })
+                    .collect();
+                let lod = |entity: Entity| lods.get(&entity).copied().unwrap_or(0);
+
+                // ******************************************************************************
+                // This section is where we render the shadow maps, the shadow system has
+                // already uploaded the light matrices for each cascade and spot light.
+                // ******************************************************************************
+                let casts_shadows = directional_lights
+                    .iter(&world)
+                    .take(MAX_LIGHTS / 2)
+                    .any(|(light,)| light.casts_shadows);
+                let spot_shadows = spot_lights
+                    .iter(&world)
+                    .filter(|(light,)| light.casts_shadows)
+                    .take(MAX_SPOT_SHADOWS)
+                    .count();
+                // Each pass renders into a layer of the shadow map, the spot lights share the
+                // last one and each draw into their own tile of it.
+                let mut shadow_passes: Vec<(usize, Vec<(usize, Option<(f32, f32, f32)>)>)> =
+                    Vec::new();
+                if casts_shadows {
+                    shadow_passes.extend(
+                        (0..SHADOW_CASCADES).map(|cascade| (cascade, vec![(cascade, None)])),
+                    );
+                }
+                if spot_shadows > 0 {
+                    let tiles = (0..spot_shadows)
+                        .map(|tile| (SHADOW_CASCADES + tile, Some(spot_shadow_viewport(tile))))
+                        .collect();
+                    shadow_passes.push((SHADOW_CASCADES, tiles));
+                }
+                if !shadow_passes.is_empty() {
+                    let non_casting: HashSet<u32> = asset_manager
+                        .get_materials()
+                        .iter()
+                        .filter_map(|material| match material {
+                            Material::PBR(data) if !data.cast_shadows => Some(data.index),
+                            _ => None,
+                        })
+                        .collect();
+                    let shadow_pipeline = pipeline_manager.get("shadow", None).unwrap();
+                    for (layer, casters) in shadow_passes {
+                        let mut render_pass =
+                            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                                color_attachments: &[],
+                                depth_stencil_attachment: Some(
+                                    wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                                        attachment: &resource_manager.shadow_views[layer],
+                                        depth_load_op: wgpu::LoadOp::Clear,
+                                        depth_store_op: wgpu::StoreOp::Store,
+                                        stencil_load_op: wgpu::LoadOp::Clear,
+                                        stencil_store_op: wgpu::StoreOp::Store,
+                                        clear_depth: 1.0,
+                                        clear_stencil: 0,
+                                    },
+                                ),
+                            });
+                        render_pass.set_pipeline(&shadow_pipeline.render_pipeline);
+                        for (caster, viewport) in casters {
+                            if let Some((x, y, size)) = viewport {
+                                render_pass.set_viewport(x, y, size, size, 0.0, 1.0);
+                            }
+                            resource_manager.set_multi_bind_group(
+                                &mut render_pass,
+                                "shadow_caster",
+                                1,
+                                caster as u32,
+                            );
+
+                            // Hidden meshes don't cast shadows, but meshes outside of the
+                            // camera's view can still cast shadows into it so they aren't
+                            // frustum culled.
+                            let casting_meshes = mesh_query.iter_entities(&world).filter(
+                                |(entity, (_, material, _))| {
+                                    visibility.is_visible(*entity)
+                                        && flags(*entity).cast_shadows
+                                        && !non_casting.contains(&material.index)
+                                },
+                            );
+                            for (entity, (mesh, _, transform)) in casting_meshes {
+                                resource_manager.set_multi_bind_group(
+                                    &mut render_pass,
+                                    "transform",
+                                    0,
+                                    transform.index,
+                                );
+                                let asset_mesh = asset_manager.get_mesh(mesh.mesh_name.clone());
+                                for sub_mesh in asset_mesh.lod_sub_meshes(lod(entity)) {
+                                    render_pass.set_index_buffer(
+                                        sub_mesh.index_buffer.as_ref().unwrap().slice(..),
+                                    );
+                                    render_pass.set_vertex_buffer(
+                                        0,
+                                        sub_mesh.vertex_buffer.as_ref().unwrap().slice(..),
+                                    );
+                                    let index_count = sub_mesh.index_count as u32;
+                                    render_pass.draw_indexed(0..index_count, 0, 0..1);
+                                    frame_stats.record_draw(index_count / 3);
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // ******************************************************************************
+                // This section culls meshes that are outside of the active camera's view.
+                // With split-screen a mesh only has to be seen by one of the players.
+                // ******************************************************************************
+                // Probes look in every direction so nothing is culled for them.
+                let frustums: Vec<Frustum> = split_screen
+                    .views()
+                    .into_iter()
+                    .filter(|_| probe_target.is_none())
+                    .filter_map(|(camera_entity, _)| {
+                        cameras
+                            .iter_entities(&world)
+                            .find(|(entity, (camera,))| {
+                                is_view_camera(camera_entity, *entity, camera)
+                            })
+                            .map(|(_, (camera,))| Frustum::from_matrix(&camera.get_matrix()))
+                    })
+                    .collect();
+                // Blended meshes are sorted by their distance to each player's camera.
+                let camera_positions: Vec<Option<nalgebra_glm::Vec3>> = split_screen
+                    .views()
+                    .into_iter()
+                    .map(|(camera_entity, _)| {
+                        cameras
+                            .iter_entities(&world)
+                            .find(|(entity, (camera,))| {
+                                is_view_camera(camera_entity, *entity, camera)
+                            })
+                            .map(|(_, (camera,))| camera.position)
+                    })
+                    .collect();
+                // View models use their own projection so they aren't culled.
+                let view_models: HashSet<Entity> = view_model_query
+                    .iter_entities(&world)
+                    .map(|(entity, _)| entity)
+                    .collect();
+                let mut culled = HashSet::new();
+                let mut bounds_lines = Vec::new();
+                if !frustums.is_empty() && frustum_culling.enabled {
+                    for (entity, (mesh, _, transform)) in mesh_query.iter_entities(&world) {
+                        let asset_mesh = asset_manager.get_mesh(mesh.mesh_name.clone());
+                        if asset_mesh.is_skinned() || view_models.contains(&entity) {
+                            continue;
+                        }
+                        let aabb = asset_mesh.aabb.transform(&transform.matrix);
+                        let is_culled = !frustums
+                            .iter()
+                            .any(|frustum| frustum.intersects_aabb(&aabb));
+                        if is_culled {
+                            culled.insert(entity);
+                        }
+                        if frustum_culling.draw_bounds && visibility.is_visible(entity) {
+                            let color = if is_culled {
+                                nalgebra_glm::vec3(1.0, 0.0, 0.0)
+                            } else {
+                                nalgebra_glm::vec3(0.0, 1.0, 0.0)
+                            };
+                            bounds_lines.extend(aabb_lines(&aabb, color));
+                        }
+                    }
+                }
+                frustum_culling.culled_count = culled.len();
+                let bake_static_only = probe_target.is_some() && render_settings.bake_static_only;
+                let is_drawn = |entity: Entity| {
+                    visibility.is_visible(entity)
+                        && !culled.contains(&entity)
+                        && (!bake_static_only || statics.contains(&entity))
+                };
+                let bounds_buffer = if bounds_lines.is_empty() {
+                    None
+                } else {
+                    Some(device.create_buffer_with_data(
+                        bytemuck::cast_slice(&bounds_lines),
+                        wgpu::BufferUsage::VERTEX,
+                    ))
+                };
+
+                // ******************************************************************************
+                // This section works out which render queue each entity is drawn in, overlay
+                // queues get their own pass with a cleared depth buffer.
+                // View models are drawn last in their own pass with the view model projection.
+                // Particles are drawn in their emitter's queue, never with the view models.
+                // ******************************************************************************
+                let material_queues: HashMap<u32, i32> = asset_manager
+                    .get_materials()
+                    .iter()
+                    .map(|material| match material {
+                        Material::PBR(data) => (data.index, data.render_queue.value()),
+                        Material::Unlit(data) => (data.index, data.render_queue.value()),
+                    })
+                    .collect();
+                let sort_biases: HashMap<Entity, i32> = sort_bias_query
+                    .iter_entities(&world)
+                    .map(|(entity, (bias,))| (entity, bias.0))
+                    .collect();
+                let entity_queues: HashMap<Entity, i32> = mesh_query
+                    .iter_entities(&world)
+                    .filter(|(entity, _)| is_drawn(*entity))
+                    .map(|(entity, (_, material, _))| {
+                        let queue = material_queues
+                            .get(&material.index)
+                            .copied()
+                            .unwrap_or(RenderQueue::OPAQUE);
+                        (
+                            entity,
+                            queue + sort_biases.get(&entity).copied().unwrap_or(0),
+                        )
+                    })
+                    .collect();
+                let sorted_queues = |view_model: bool| {
+                    let mut queues: Vec<i32> = entity_queues
+                        .iter()
+                        .filter(|(entity, _)| view_models.contains(entity) == view_model)
+                        .map(|(_, queue)| *queue)
+                        .chain(particle_renderer.queues().filter(|_| !view_model))
+                        .collect();
+                    queues.sort();
+                    queues.dedup();
+                    queues
+                };
+                let (overlay_queues, main_queues): (Vec<i32>, Vec<i32>) = sorted_queues(false)
+                    .into_iter()
+                    .partition(|queue| *queue >= RenderQueue::OVERLAY);
+                let view_model_queues = sorted_queues(true);
+
+                // ******************************************************************************
+                // This section batches up PBR meshes that share a mesh and material so they
+                // can be drawn with a single instanced draw call.
+                // Skinned meshes and material instances need their own bind groups so they
+                // are still drawn one at a time, double sided and blended materials and meshes
+                // with vertex colors or a second uv set need their own pipelines.
+                // ******************************************************************************
+                let pbr_masked: HashMap<u32, bool> = asset_manager
+                    .get_materials()
+                    .iter()
+                    .filter_map(|material| match material {
+                        Material::PBR(data)
+                            if !data.double_sided
+                                && !data.alpha_mode.is_blended()
+                                && data.uses_default_pipelines() =>
+                        {
+                            Some((data.index, data.alpha_mode.is_masked()))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                let colors: HashMap<Entity, nalgebra_glm::Vec4> = instance_colors
+                    .iter_entities(&world)
+                    .map(|(entity, (color,))| (entity, color.0))
+                    .collect();
+                // Static entities go in batches of their own that are only rebuilt when one of
+                // them comes into or goes out of view, or changes it's LOD.
+                let (static_items, dynamic_items): (Vec<_>, Vec<_>) = mesh_query
+                    .iter_entities(&world)
+                    .filter(|(entity, (mesh, material, transform))| {
+                        pbr_masked.contains_key(&material.index)
+                            && is_drawn(*entity)
+                            && flags(*entity).receive_shadows
+                            && !sort_biases.contains_key(entity)
+                            && !view_models.contains(entity)
+                            && !asset_manager
+                                .get_mesh(mesh.mesh_name.clone())
+                                .has_vertex_attributes()
+                            && !(asset_manager.get_mesh(mesh.mesh_name.clone()).is_skinned()
+                                && resource_manager
+                                    .get_multi_bind_group_option(
+                                        "skinned_transform",
+                                        0,
+                                        transform.index,
+                                    )
+                                    .is_some())
+                            && resource_manager
+                                .get_multi_bind_group_option("pbr_instance", 2, transform.index)
+                                .is_none()
+                    })
+                    .map(|(entity, (mesh, material, transform))| {
+                        let color = colors
+                            .get(&entity)
+                            .copied()
+                            .unwrap_or_else(|| nalgebra_glm::vec4(1.0, 1.0, 1.0, 1.0));
+                        (
+                            entity,
+                            mesh.mesh_name.clone(),
+                            lod(entity),
+                            material.index,
+                            transform.index,
+                            InstanceData {
+                                world: transform.matrix,
+                                color,
+                            },
+                        )
+                    })
+                    .partition(|item| statics.contains(&item.0));
+                let (batches, instance_data) = build_batches(dynamic_items);
+                if let Some(static_data) = static_cache.update_batches(static_items) {
+                    static_cache.buffer = if static_data.is_empty() {
+                        None
+                    } else {
+                        Some(device.create_buffer_with_data(
+                            bytemuck::cast_slice(&static_data),
+                            wgpu::BufferUsage::VERTEX,
+                        ))
+                    };
+                }
+                let batched: HashSet<Entity> = batches
+                    .iter()
+                    .chain(static_cache.batches.iter())
+                    .flat_map(|batch| batch.entities.iter().copied())
+                    .collect();
+                let instance_buffer = if instance_data.is_empty() {
+                    None
+                } else {
+                    Some(device.create_buffer_with_data(
+                        bytemuck::cast_slice(&instance_data),
+                        wgpu::BufferUsage::VERTEX,
+                    ))
+                };
+
                 // ******************************************************************************
                 // This section is where we actually render our meshes.
+                // Each split-screen player draws every pass into their own viewport.
                 // ******************************************************************************
-                {
+                let viewports = match probe_target {
+                    Some(_) => vec![None],
+                    None => split_screen.pixel_viewports(frame_size),
+                };
+                let mut passes = Vec::new();
+                for (player, viewport) in viewports.into_iter().enumerate() {
+                    passes.push((player, viewport, false, false, &main_queues));
+                    passes.push((player, viewport, true, false, &overlay_queues));
+                    passes.push((player, viewport, true, true, &view_model_queues));
+                }
+                for (player, viewport, overlay, view_model, pass_queues) in passes.iter() {
+                    if *overlay && pass_queues.is_empty() {
+                        continue;
+                    }
+                    let (player_globals, player_view_model_globals) =
+                        resource_manager.player_bind_groups(*player);
+                    let globals = if *view_model {
+                        player_view_model_globals
+                    } else {
+                        player_globals
+                    };
                     let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                         color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                            attachment: &output.view,
-                            resolve_target: None,
+                            attachment: frame_view,
+                            resolve_target,
                             load_op: wgpu::LoadOp::Load,
                             store_op: wgpu::StoreOp::Store,
                             clear_color: wgpu::Color {
@@ -104,8 +579,12 @@ pub fn create() -> Box<dyn Schedulable> {
                         }],
                         depth_stencil_attachment: Some(
                             wgpu::RenderPassDepthStencilAttachmentDescriptor {
-                                attachment: &depth_texture.0,
-                                depth_load_op: wgpu::LoadOp::Load,
+                                attachment: depth_view,
+                                depth_load_op: if *overlay {
+                                    wgpu::LoadOp::Clear
+                                } else {
+                                    wgpu::LoadOp::Load
+                                },
                                 depth_store_op: wgpu::StoreOp::Store,
                                 stencil_load_op: wgpu::LoadOp::Load,
                                 stencil_store_op: wgpu::StoreOp::Store,
@@ -114,8 +593,22 @@ pub fn create() -> Box<dyn Schedulable> {
                             },
                         ),
                     });
+                    if let Some(viewport) = viewport {
+                        render_pass.set_viewport(
+                            viewport.x,
+                            viewport.y,
+                            viewport.width,
+                            viewport.height,
+                            0.0,
+                            1.0,
+                        );
+                    }
 
-                    if mesh_query.iter(&world).count() > 0 {
+                    for queue in pass_queues.iter() {
+                        let in_queue = |entity: Entity| {
+                            entity_queues.get(&entity) == Some(queue)
+                                && view_models.contains(&entity) == *view_model
+                        };
                         // Collect materials in to their groups.
                         let asset_materials = asset_manager.get_materials();
                         let pbr_materials: Vec<_> = asset_materials
@@ -134,9 +627,12 @@ pub fn create() -> Box<dyn Schedulable> {
                             .collect();
 
                         // Render unlit materials.
-                        let unlit_node = render_graph.get("unlit");
+                        let unlit_node = render_graph.get(unlit_name("unlit"));
+                        let unlit_attributes_node =
+                            render_graph.get(unlit_name("unlit_vertex_attributes"));
                         render_pass.set_pipeline(&unlit_node.pipeline);
-                        render_pass.set_bind_group(1, &resource_manager.global_bind_group, &[]);
+                        let mut unlit_attributes = false;
+                        render_pass.set_bind_group(1, globals, &[]);
                         for material in unlit_materials.iter() {
                             match material {
                                 Material::Unlit(data) => {
@@ -145,9 +641,11 @@ pub fn create() -> Box<dyn Schedulable> {
                                         &data.bind_group_data.as_ref().unwrap().bind_group,
                                         &[],
                                     );
-                                    for (mesh, _, transform) in mesh_query
-                                        .iter(&world)
-                                        .filter(|(_, material, _)| material.index == data.index)
+                                    for (entity, (mesh, _, transform)) in mesh_query
+                                        .iter_entities(&world)
+                                        .filter(|(entity, (_, material, _))| {
+                                            material.index == data.index && in_queue(*entity)
+                                        })
                                     {
                                         resource_manager.set_multi_bind_group(
                                             &mut render_pass,
@@ -157,19 +655,35 @@ pub fn create() -> Box<dyn Schedulable> {
                                         );
                                         let asset_mesh =
                                             asset_manager.get_mesh(mesh.mesh_name.clone());
-                                        for sub_mesh in asset_mesh.sub_meshes.iter() {
+                                        for sub_mesh in asset_mesh.lod_sub_meshes(lod(entity)) {
+                                            let attribute_buffer =
+                                                sub_mesh.attribute_buffer.as_ref();
+                                            if attribute_buffer.is_some() != unlit_attributes {
+                                                unlit_attributes = attribute_buffer.is_some();
+                                                let node = if unlit_attributes {
+                                                    unlit_attributes_node
+                                                } else {
+                                                    unlit_node
+                                                };
+                                                render_pass.set_pipeline(&node.pipeline);
+                                            }
                                             render_pass.set_index_buffer(
-                                                sub_mesh.index_buffer.slice(..)
+                                                sub_mesh.index_buffer.as_ref().unwrap().slice(..),
                                             );
                                             render_pass.set_vertex_buffer(
                                                 0,
                                                 sub_mesh.vertex_buffer.as_ref().unwrap().slice(..),
                                             );
+                                            if let Some(buffer) = attribute_buffer {
+                                                render_pass.set_vertex_buffer(1, buffer.slice(..));
+                                            }
                                             render_pass.draw_indexed(
                                                 0..sub_mesh.index_count as u32,
                                                 0,
                                                 0..1,
                                             );
+                                            frame_stats
+                                                .record_draw(sub_mesh.index_count as u32 / 3);
                                         }
                                     }
                                 }
@@ -177,51 +691,394 @@ pub fn create() -> Box<dyn Schedulable> {
                             }
                         }
 
-                        // Render pbr materials.
-                        let pbr_node = pipeline_manager.get("pbr", None).unwrap();
-                        render_pass.set_pipeline(&pbr_node.render_pipeline);
-                        render_pass.set_bind_group(1, &resource_manager.global_bind_group, &[]);
-                        resource_manager.set_bind_group(&mut render_pass, "probe_material", 3);
-                        for material in pbr_materials.iter() {
-                            match material {
-                                Material::PBR(data) => {
+                        // Render instanced pbr batches, static batches have their own buffer.
+                        let instanced = [
+                            (instance_buffer.as_ref(), &batches),
+                            (static_cache.buffer.as_ref(), &static_cache.batches),
+                        ];
+                        for (instance_buffer, batches) in instanced
+                            .iter()
+                            .filter_map(|(buffer, batches)| buffer.map(|buffer| (buffer, batches)))
+                        {
+                            let base_variant = probe_variant(PipelineVariant::default());
+                            let pbr_instanced_node = pipeline_manager
+                                .get_variant("pbr_instanced", &base_variant)
+                                .unwrap();
+                            let pbr_instanced_masked_node = pipeline_manager
+                                .get_variant("pbr_instanced_masked", &base_variant)
+                                .unwrap();
+                            for (pbr_pipeline, masked) in [
+                                (pbr_instanced_node, false),
+                                (pbr_instanced_masked_node, true),
+                            ]
+                            .iter()
+                            {
+                                render_pass.set_pipeline(&pbr_pipeline.render_pipeline);
+                                render_pass.set_bind_group(1, globals, &[]);
+                                resource_manager.set_bind_group(
+                                    &mut render_pass,
+                                    "probe_material",
+                                    3,
+                                );
+                                render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+                                for batch in batches.iter().filter(|batch| {
+                                    pbr_masked[&batch.material_index] == *masked
+                                        && material_queues[&batch.material_index] == *queue
+                                }) {
+                                    resource_manager.set_multi_bind_group(
+                                        &mut render_pass,
+                                        "transform",
+                                        0,
+                                        batch.first_transform,
+                                    );
                                     resource_manager.set_multi_bind_group(
                                         &mut render_pass,
                                         "pbr",
                                         2,
-                                        data.index as u32,
+                                        batch.material_index,
                                     );
-                                    for (mesh, _, transform) in mesh_query
-                                        .iter(&world)
-                                        .filter(|(_, material, _)| material.index == data.index)
-                                    {
-                                        resource_manager.set_multi_bind_group(
-                                            &mut render_pass,
-                                            "transform",
+                                    let asset_mesh =
+                                        asset_manager.get_mesh(batch.mesh_name.clone());
+                                    for sub_mesh in asset_mesh.lod_sub_meshes(batch.lod) {
+                                        render_pass.set_index_buffer(
+                                            sub_mesh.index_buffer.as_ref().unwrap().slice(..),
+                                        );
+                                        render_pass.set_vertex_buffer(
                                             0,
-                                            transform.index,
+                                            sub_mesh.vertex_buffer.as_ref().unwrap().slice(..),
                                         );
-                                        let asset_mesh =
-                                            asset_manager.get_mesh(mesh.mesh_name.clone());
-                                        for sub_mesh in asset_mesh.sub_meshes.iter() {
-                                            render_pass.set_index_buffer(
-                                                sub_mesh.index_buffer.slice(..)
-                                            );
-                                            render_pass.set_vertex_buffer(
-                                                0,
-                                                sub_mesh.vertex_buffer.as_ref().unwrap().slice(..),
-                                            );
-                                            render_pass.draw_indexed(
-                                                0..sub_mesh.index_count as u32,
-                                                0,
-                                                0..1,
+                                        render_pass.draw_indexed(
+                                            0..sub_mesh.index_count as u32,
+                                            0,
+                                            batch.range.clone(),
+                                        );
+                                        frame_stats.record_draw(
+                                            sub_mesh.index_count as u32 / 3
+                                                * batch.range.len() as u32,
+                                        );
+                                    }
+                                }
+                            }
+                        }
+
+                        // Render the pbr meshes that couldn't be batched.
+                        // Alpha masked materials go last with their own pipeline,
+                        // that way they can use alpha to coverage.
+                        // Animated skinned meshes and double sided materials use their own
+                        // pipelines as well, materials with shader defines, blend modes or a depth
+                        // bias use a variant.
+                        let pbr_pipelines = [
+                            ("pbr", false, false, false),
+                            ("pbr_masked", true, false, false),
+                            ("pbr_double_sided", false, false, true),
+                            ("pbr_masked_double_sided", true, false, true),
+                            ("pbr_skinned", false, true, false),
+                        ];
+                        for (pipeline_name, masked, skinned, double_sided) in pbr_pipelines.iter() {
+                            let pbr_pipeline = pipeline_manager
+                                .get_variant(*pipeline_name, &probe_variant(Default::default()))
+                                .unwrap();
+                            render_pass.set_pipeline(&pbr_pipeline.render_pipeline);
+                            render_pass.set_bind_group(1, globals, &[]);
+                            resource_manager.set_bind_group(&mut render_pass, "probe_material", 3);
+                            let mut current_pipeline = pbr_pipeline;
+                            for material in pbr_materials.iter() {
+                                match material {
+                                    Material::PBR(data)
+                                        if *skinned
+                                            || (data.alpha_mode.is_masked() == *masked
+                                                && !data.alpha_mode.is_blended()
+                                                && data.double_sided == *double_sided) =>
+                                    {
+                                        let variant =
+                                            probe_variant(data.pipeline_variant(pipeline_name));
+                                        let pipeline = pipeline_manager
+                                            .get_variant(*pipeline_name, &variant)
+                                            .unwrap_or(pbr_pipeline);
+                                        // Sub meshes with vertex colors or a second uv set use
+                                        // a variant that reads them, once it's built.
+                                        let attribute_variant = PipelineVariant {
+                                            vertex_attributes: true,
+                                            ..variant
+                                        };
+                                        let attribute_pipeline = pipeline_manager
+                                            .get_variant(*pipeline_name, &attribute_variant);
+                                        for (entity, (mesh, _, transform)) in mesh_query
+                                            .iter_entities(&world)
+                                            .filter(|(entity, (_, material, _))| {
+                                                material.index == data.index
+                                                    && in_queue(*entity)
+                                                    && !batched.contains(entity)
+                                            })
+                                        {
+                                            let asset_mesh =
+                                                asset_manager.get_mesh(mesh.mesh_name.clone());
+                                            // Only entities with an animator have joints uploaded.
+                                            let skin_bind_group = resource_manager
+                                                .get_multi_bind_group_option(
+                                                    "skinned_transform",
+                                                    0,
+                                                    transform.index,
+                                                )
+                                                .filter(|_| asset_mesh.is_skinned());
+                                            if skin_bind_group.is_some() != *skinned {
+                                                continue;
+                                            }
+
+                                            match skin_bind_group {
+                                                Some(skin_bind_group) => render_pass
+                                                    .set_bind_group(0, &skin_bind_group.group, &[]),
+                                                None => resource_manager.set_multi_bind_group(
+                                                    &mut render_pass,
+                                                    "transform",
+                                                    0,
+                                                    transform.index,
+                                                ),
+                                            }
+                                            // Material instances override the material's bind group.
+                                            let instance = resource_manager
+                                                .get_multi_bind_group_option(
+                                                    "pbr_instance",
+                                                    2,
+                                                    transform.index,
+                                                );
+                                            let material_bind_group =
+                                                instance.unwrap_or_else(|| {
+                                                    resource_manager.get_multi_bind_group(
+                                                        "pbr",
+                                                        2,
+                                                        data.index as u32,
+                                                    )
+                                                });
+                                            render_pass.set_bind_group(
+                                                2,
+                                                &material_bind_group.group,
+                                                &[],
                                             );
+                                            for sub_mesh in asset_mesh.lod_sub_meshes(lod(entity)) {
+                                                let attributes = match (
+                                                    sub_mesh.attribute_buffer.as_ref(),
+                                                    attribute_pipeline,
+                                                ) {
+                                                    (Some(buffer), Some(pipeline)) => {
+                                                        Some((buffer, pipeline))
+                                                    }
+                                                    _ => None,
+                                                };
+                                                let sub_mesh_pipeline = attributes
+                                                    .map_or(pipeline, |(_, pipeline)| pipeline);
+                                                let changed = !std::ptr::eq(
+                                                    sub_mesh_pipeline,
+                                                    current_pipeline,
+                                                );
+                                                if changed {
+                                                    render_pass.set_pipeline(
+                                                        &sub_mesh_pipeline.render_pipeline,
+                                                    );
+                                                    current_pipeline = sub_mesh_pipeline;
+                                                }
+                                                render_pass.set_index_buffer(
+                                                    sub_mesh
+                                                        .index_buffer
+                                                        .as_ref()
+                                                        .unwrap()
+                                                        .slice(..),
+                                                );
+                                                render_pass.set_vertex_buffer(
+                                                    0,
+                                                    sub_mesh
+                                                        .vertex_buffer
+                                                        .as_ref()
+                                                        .unwrap()
+                                                        .slice(..),
+                                                );
+                                                if *skinned {
+                                                    render_pass.set_vertex_buffer(
+                                                        1,
+                                                        sub_mesh
+                                                            .skin_buffer
+                                                            .as_ref()
+                                                            .unwrap()
+                                                            .slice(..),
+                                                    );
+                                                }
+                                                // It goes after the skin buffer, if there is one.
+                                                if let Some((buffer, _)) = attributes {
+                                                    let slot = if *skinned { 2 } else { 1 };
+                                                    render_pass
+                                                        .set_vertex_buffer(slot, buffer.slice(..));
+                                                }
+                                                render_pass.draw_indexed(
+                                                    0..sub_mesh.index_count as u32,
+                                                    0,
+                                                    0..1,
+                                                );
+                                                frame_stats
+                                                    .record_draw(sub_mesh.index_count as u32 / 3);
+                                            }
                                         }
                                     }
+                                    _ => (),
+                                }
+                            }
+                        }
+
+                        // Render alpha blended pbr meshes and particle emitters back to front,
+                        // one sub mesh at a time so the ones behind show through the ones in
+                        // front.
+                        let camera_position = camera_positions.get(*player).copied().flatten();
+                        let mut blended = Vec::new();
+                        for material in pbr_materials.iter() {
+                            let data = match material {
+                                Material::PBR(data) if data.alpha_mode.is_blended() => data,
+                                _ => continue,
+                            };
+                            for (entity, (mesh, _, transform)) in mesh_query
+                                .iter_entities(&world)
+                                .filter(|(entity, (_, material, _))| {
+                                    material.index == data.index && in_queue(*entity)
+                                })
+                            {
+                                let asset_mesh = asset_manager.get_mesh(mesh.mesh_name.clone());
+                                // Animated skinned meshes were drawn with the skinned pipeline.
+                                let skinned = asset_mesh.is_skinned()
+                                    && resource_manager
+                                        .get_multi_bind_group_option(
+                                            "skinned_transform",
+                                            0,
+                                            transform.index,
+                                        )
+                                        .is_some();
+                                if skinned {
+                                    continue;
+                                }
+                                for sub_mesh in asset_mesh.lod_sub_meshes(lod(entity)) {
+                                    let center = sub_mesh.bounding_sphere.center;
+                                    let center = transform.matrix
+                                        * nalgebra_glm::vec4(center.x, center.y, center.z, 1.0);
+                                    let distance = camera_position.map_or(0.0, |position| {
+                                        nalgebra_glm::distance2(&position, &center.xyz())
+                                    });
+                                    let item = Blended::Mesh(data, transform.index, sub_mesh);
+                                    blended.push((distance, item));
                                 }
-                                _ => (),
                             }
                         }
+                        for emitter in particle_renderer.emitters.values().filter(|emitter| {
+                            emitter.visible && emitter.queue == *queue && !*view_model
+                        }) {
+                            let distance = camera_position.map_or(0.0, |position| {
+                                nalgebra_glm::distance2(&position, &emitter.position)
+                            });
+                            blended.push((distance, Blended::Particles(emitter)));
+                        }
+                        blended.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+                        let mut current_pipeline = None;
+                        for (_, item) in blended.iter() {
+                            let (data, transform_index, sub_mesh) = match item {
+                                Blended::Mesh(data, transform_index, sub_mesh) => {
+                                    (data, transform_index, sub_mesh)
+                                }
+                                Blended::Particles(emitter) => {
+                                    let pipeline = pipeline_manager
+                                        .get_variant(
+                                            "particle",
+                                            &probe_variant(emitter.pipeline_variant()),
+                                        )
+                                        .or_else(|| {
+                                            pipeline_manager.get_variant(
+                                                "particle",
+                                                &probe_variant(Default::default()),
+                                            )
+                                        })
+                                        .unwrap();
+                                    render_pass.set_pipeline(&pipeline.render_pipeline);
+                                    render_pass.set_bind_group(0, globals, &[]);
+                                    emitter.draw(&mut render_pass);
+                                    frame_stats.record_draw(emitter.capacity * 2);
+                                    // The next mesh sets the pipeline and bind groups again.
+                                    current_pipeline = None;
+                                    continue;
+                                }
+                            };
+                            let pipeline_name = if data.double_sided {
+                                "pbr_blended_double_sided"
+                            } else {
+                                "pbr_blended"
+                            };
+                            // Falls back to the regular pipeline until the variant is built.
+                            let material_variant =
+                                probe_variant(data.pipeline_variant(pipeline_name));
+                            let variant = PipelineVariant {
+                                vertex_attributes: sub_mesh.has_vertex_attributes(),
+                                ..material_variant.clone()
+                            };
+                            let attribute_pipeline = pipeline_manager
+                                .get_variant(pipeline_name, &variant)
+                                .filter(|_| variant.vertex_attributes);
+                            let pipeline = attribute_pipeline
+                                .or_else(|| {
+                                    pipeline_manager.get_variant(pipeline_name, &material_variant)
+                                })
+                                .or_else(|| {
+                                    pipeline_manager.get_variant(
+                                        pipeline_name,
+                                        &probe_variant(Default::default()),
+                                    )
+                                })
+                                .unwrap();
+                            let changed = current_pipeline
+                                .map_or(true, |current| !std::ptr::eq(current, pipeline));
+                            if changed {
+                                render_pass.set_pipeline(&pipeline.render_pipeline);
+                                render_pass.set_bind_group(1, globals, &[]);
+                                resource_manager.set_bind_group(
+                                    &mut render_pass,
+                                    "probe_material",
+                                    3,
+                                );
+                                current_pipeline = Some(pipeline);
+                            }
+                            resource_manager.set_multi_bind_group(
+                                &mut render_pass,
+                                "transform",
+                                0,
+                                *transform_index,
+                            );
+                            // Material instances override the material's bind group.
+                            let material_bind_group = resource_manager
+                                .get_multi_bind_group_option("pbr_instance", 2, *transform_index)
+                                .unwrap_or_else(|| {
+                                    resource_manager.get_multi_bind_group("pbr", 2, data.index)
+                                });
+                            render_pass.set_bind_group(2, &material_bind_group.group, &[]);
+                            render_pass.set_index_buffer(
+                                sub_mesh.index_buffer.as_ref().unwrap().slice(..),
+                            );
+                            render_pass.set_vertex_buffer(
+                                0,
+                                sub_mesh.vertex_buffer.as_ref().unwrap().slice(..),
+                            );
+                            if attribute_pipeline.is_some() {
+                                render_pass.set_vertex_buffer(
+                                    1,
+                                    sub_mesh.attribute_buffer.as_ref().unwrap().slice(..),
+                                );
+                            }
+                            render_pass.draw_indexed(0..sub_mesh.index_count as u32, 0, 0..1);
+                            frame_stats.record_draw(sub_mesh.index_count as u32 / 3);
+                        }
+                    }
+
+                    // Debug view of the mesh bounds, probes don't see it.
+                    let draw_bounds = !*overlay && probe_target.is_none();
+                    if let Some(bounds_buffer) = bounds_buffer.as_ref().filter(|_| draw_bounds) {
+                        let line_node = render_graph.get("line");
+                        render_pass.set_pipeline(&line_node.pipeline);
+                        render_pass.set_bind_group(0, player_globals, &[]);
+                        render_pass.set_vertex_buffer(0, bounds_buffer.slice(..));
+                        render_pass.draw(0..bounds_lines.len() as u32, 0..1);
                     }
                 }
 
@@ -234,3 +1091,9 @@ pub fn create() -> Box<dyn Schedulable> {
             },
         )
 }
+
+/// Something drawn back to front with the other blended things in a queue.
+enum Blended<'a> {
+    Mesh(&'a PBRMaterial, u32, &'a SubMesh),
+    Particles(&'a EmitterState),
+}
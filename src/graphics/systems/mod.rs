@@ -1,15 +1,40 @@
+pub mod atmosphere;
+pub mod clouds;
+pub mod contact_shadows;
+pub mod debug;
+pub mod deformation;
 pub mod globals;
+pub mod grid;
+pub mod light_culling;
 pub mod line;
+pub mod material_instance;
 pub mod mesh;
+pub mod ocean;
+pub mod paint;
+pub mod particles;
+pub mod post_process;
 pub mod render;
+pub mod shader_variants;
+pub mod shadow;
+pub mod skinning;
 pub mod skybox;
+pub mod split_screen;
+pub mod sprite;
+pub mod ssgi;
+pub mod text;
+pub mod water;
+pub mod world_text;
 
 use legion::prelude::*;
 use legion::systems::schedule::Builder;
 pub fn create_render_schedule_builder() -> Builder {
     Schedule::builder()
+        .add_system(split_screen::create())
         .add_system(crate::graphics::systems::globals::create())
+        .add_system(atmosphere::create())
         .add_system(skybox::create())
+        .add_system(world_text::create())
+        .add_system(text::create())
     // .add_system(line::create())
     // .add_system(mesh::create())
 }
@@ -0,0 +1,210 @@
+use legion::prelude::*;
+use nalgebra_glm::Vec4;
+
+use crate::{
+    graphics::{
+        ocean::OceanUniform,
+        pipeline_manager::PipelineManager,
+        pipelines::ocean::{OceanResources, DISPLACEMENT_BYTES_PER_PIXEL},
+        renderer::DepthTexture,
+        resources::{CurrentRenderTarget, GPUResourceManager},
+        CommandBufferQueue, CommandQueueItem, GpuReadback, Ocean, SplitScreen, OCEAN_FFT_SIZE,
+    },
+    scene::resources::DeltaTime,
+};
+
+fn upload(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    data: &[u8],
+    buffer: &wgpu::Buffer,
+) {
+    let staging = device.create_buffer_with_data(data, wgpu::BufferUsage::COPY_SRC);
+    encoder.copy_buffer_to_buffer(&staging, 0, buffer, 0, data.len() as u64);
+}
+
+/// Moves the `Ocean`'s waves forward, copies them back for `Ocean::height_at` and draws the
+/// ocean into every view while it's enabled.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("ocean")
+        .write_resource::<Ocean>()
+        .write_resource::<CommandBufferQueue>()
+        .read_resource::<OceanResources>()
+        .read_resource::<PipelineManager>()
+        .read_resource::<GPUResourceManager>()
+        .read_resource::<SplitScreen>()
+        .read_resource::<CurrentRenderTarget>()
+        .read_resource::<DepthTexture>()
+        .read_resource::<DeltaTime>()
+        .read_resource::<wgpu::Device>()
+        .build(
+            |_,
+             _,
+             (
+                ocean,
+                command_buffer_queue,
+                ocean_resources,
+                pipeline_manager,
+                resource_manager,
+                split_screen,
+                current_render_target,
+                depth_texture,
+                delta_time,
+                device,
+            ),
+             _| {
+                // Probes render with the frame's waves, they shouldn't move them on.
+                if !ocean.enabled || current_render_target.0.is_some() {
+                    return;
+                }
+                let ((frame, resolve_target), frame_size) = match (
+                    pipeline_manager.get_frame_target(),
+                    pipeline_manager.get_attachment_size("frame"),
+                ) {
+                    (Some(frame_target), Some(frame_size)) => (frame_target, frame_size),
+                    _ => return,
+                };
+                let simulated = ["ocean_spectrum", "ocean_fft", "ocean_maps"]
+                    .iter()
+                    .all(|name| resource_manager.get_compute_pipeline(*name).is_some());
+
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("ocean"),
+                });
+
+                let settings = ocean.spectrum_settings();
+                if ocean.spectrum_key != Some(settings) {
+                    let spectrum: Vec<f32> = ocean
+                        .initial_spectrum()
+                        .iter()
+                        .flat_map(|texel| vec![texel.x, texel.y, texel.z, texel.w])
+                        .collect();
+                    let data = bytemuck::cast_slice(&spectrum);
+                    upload(
+                        &device,
+                        &mut encoder,
+                        data,
+                        &ocean_resources.initial_spectrum,
+                    );
+                    ocean.spectrum_key = Some(settings);
+                }
+                ocean.time += delta_time.0;
+                let uniform: OceanUniform = ocean.uniform(delta_time.0);
+                let data = bytemuck::bytes_of(&uniform);
+                upload(&device, &mut encoder, data, &ocean_resources.uniform);
+
+                if simulated {
+                    // 8x8 texels per workgroup, and one workgroup per row or column.
+                    let size = OCEAN_FFT_SIZE as u32;
+                    let dispatches = [
+                        (
+                            "ocean_spectrum",
+                            &ocean_resources.spectrum_bind_group,
+                            [size / 8; 2],
+                        ),
+                        ("ocean_fft", &ocean_resources.fft_bind_groups[0], [size, 1]),
+                        ("ocean_fft", &ocean_resources.fft_bind_groups[1], [size, 1]),
+                        (
+                            "ocean_maps",
+                            &ocean_resources.maps_bind_group,
+                            [size / 8; 2],
+                        ),
+                    ];
+                    for &(name, bind_group, [x, y]) in dispatches.iter() {
+                        let dispatched =
+                            resource_manager.dispatch(&mut encoder, name, &[bind_group], [x, y, 1]);
+                        if let Err(error) = dispatched {
+                            log::error!("{}", error);
+                        }
+                    }
+
+                    // One copy in flight at a time, the next starts once it's arrived.
+                    let mut readback = ocean.readback.lock().unwrap().take();
+                    if let Some(result) = readback.as_mut().and_then(GpuReadback::try_take) {
+                        if let Ok(bytes) = result {
+                            ocean.displacement = bytes
+                                .chunks_exact(DISPLACEMENT_BYTES_PER_PIXEL as usize)
+                                .map(|texel| {
+                                    let value = |i: usize| {
+                                        let mut float = [0; 4];
+                                        float.copy_from_slice(&texel[i * 4..i * 4 + 4]);
+                                        f32::from_le_bytes(float)
+                                    };
+                                    Vec4::new(value(0), value(1), value(2), value(3))
+                                })
+                                .collect();
+                        }
+                        readback = None;
+                    }
+                    if readback.is_none() {
+                        readback = Some(GpuReadback::from_texture(
+                            &device,
+                            &mut encoder,
+                            &ocean_resources.displacement,
+                            0,
+                            wgpu::Origin3d::ZERO,
+                            wgpu::Extent3d {
+                                width: size,
+                                height: size,
+                                depth: 1,
+                            },
+                            DISPLACEMENT_BYTES_PER_PIXEL,
+                        ));
+                    }
+                    *ocean.readback.lock().unwrap() = readback;
+                }
+
+                let cells = ocean.grid_resolution.max(2) - 1;
+                {
+                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                            attachment: frame,
+                            resolve_target,
+                            load_op: wgpu::LoadOp::Load,
+                            store_op: wgpu::StoreOp::Store,
+                            clear_color: wgpu::Color::BLACK,
+                        }],
+                        depth_stencil_attachment: Some(
+                            wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                                attachment: &depth_texture.0,
+                                depth_load_op: wgpu::LoadOp::Load,
+                                depth_store_op: wgpu::StoreOp::Store,
+                                stencil_load_op: wgpu::LoadOp::Load,
+                                stencil_store_op: wgpu::StoreOp::Store,
+                                clear_depth: 1.0,
+                                clear_stencil: 0,
+                            },
+                        ),
+                    });
+                    let pipeline = pipeline_manager.get("ocean", None).unwrap();
+                    render_pass.set_pipeline(&pipeline.render_pipeline);
+                    render_pass.set_bind_group(0, &ocean_resources.bind_group, &[]);
+                    for (player, viewport) in
+                        split_screen.pixel_viewports(frame_size).iter().enumerate()
+                    {
+                        if let Some(viewport) = viewport {
+                            render_pass.set_viewport(
+                                viewport.x,
+                                viewport.y,
+                                viewport.width,
+                                viewport.height,
+                                0.0,
+                                1.0,
+                            );
+                        }
+                        let (globals, _) = resource_manager.player_bind_groups(player);
+                        render_pass.set_bind_group(1, globals, &[]);
+                        // Two triangles per cell of the projected grid.
+                        render_pass.draw(0..cells * cells * 6, 0..1);
+                    }
+                }
+
+                command_buffer_queue
+                    .push(CommandQueueItem {
+                        buffer: encoder.finish(),
+                        name: "ocean".to_string(),
+                    })
+                    .unwrap();
+            },
+        )
+}
@@ -0,0 +1,179 @@
+use legion::prelude::*;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{
+    graphics::{
+        material::{FallbackTexture, Material},
+        pipeline_manager::PipelineManager,
+        pipelines::paint::{PaintSampler, PaintUniform, PAINT_FORMAT},
+        resources::GPUResourceManager,
+        CommandBufferQueue, CommandQueueItem,
+    },
+    scene::components,
+    AssetManager,
+};
+
+// Paint images need unique names in the asset manager.
+static NEXT_PAINT_TEXTURE: AtomicU32 = AtomicU32::new(0);
+
+/// Creates paint textures and draws any pending brush strokes into them.
+/// New paint textures start out as a copy of the material's main texture.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("paint")
+        .write_resource::<AssetManager>()
+        .write_resource::<CommandBufferQueue>()
+        .read_resource::<GPUResourceManager>()
+        .read_resource::<PipelineManager>()
+        .read_resource::<PaintSampler>()
+        .read_resource::<wgpu::Device>()
+        .with_query(<(
+            Write<components::PaintTexture>,
+            Write<components::MaterialInstance>,
+            Read<components::Mesh>,
+            Read<components::Material>,
+            Read<components::Transform>,
+        )>::query())
+        .build(
+            |_,
+             mut world,
+             (
+                asset_manager,
+                command_buffer_queue,
+                resource_manager,
+                pipeline_manager,
+                sampler,
+                device,
+            ),
+             query| {
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("paint"),
+                });
+                let mut painted = false;
+
+                for (mut paint, mut instance, mesh, material, transform) in
+                    query.iter_mut(&mut world)
+                {
+                    if paint.image.is_none() {
+                        let main_texture = match asset_manager.try_get_material(material.index) {
+                            Ok(Material::PBR(pbr_material)) => pbr_material.main_texture.clone(),
+                            _ => continue,
+                        };
+                        let name = format!(
+                            "paint_{}",
+                            NEXT_PAINT_TEXTURE.fetch_add(1, Ordering::Relaxed)
+                        );
+                        let handle = asset_manager.create_render_texture(
+                            &device,
+                            name.clone(),
+                            paint.size,
+                            paint.size,
+                            PAINT_FORMAT,
+                        );
+
+                        // Start with the main texture so the paint goes on top of it.
+                        let fallback = FallbackTexture::White.name();
+                        let source = asset_manager
+                            .get_image_option(main_texture)
+                            .or_else(|| asset_manager.get_image_option(fallback));
+                        let copy_layout = resource_manager
+                            .get_bind_group_layout("paint_copy")
+                            .unwrap();
+                        if let Some(source) = source {
+                            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                                label: Some("paint_copy"),
+                                layout: copy_layout,
+                                bindings: &[
+                                    wgpu::Binding {
+                                        binding: 0,
+                                        resource: wgpu::BindingResource::TextureView(&source.view),
+                                    },
+                                    wgpu::Binding {
+                                        binding: 1,
+                                        resource: wgpu::BindingResource::Sampler(&sampler.0),
+                                    },
+                                ],
+                            });
+                            let mut render_pass =
+                                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                                    color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                                        attachment: &asset_manager.get_image(name.clone()).view,
+                                        resolve_target: None,
+                                        load_op: wgpu::LoadOp::Clear,
+                                        store_op: wgpu::StoreOp::Store,
+                                        clear_color: wgpu::Color::WHITE,
+                                    }],
+                                    depth_stencil_attachment: None,
+                                });
+                            render_pass.set_pipeline(
+                                &pipeline_manager.get("paint_copy", None).unwrap().render_pipeline,
+                            );
+                            render_pass.set_bind_group(0, &bind_group, &[]);
+                            render_pass.draw(0..3, 0..1);
+                            painted = true;
+                        }
+
+                        instance.main_texture = Some(name);
+                        paint.image = Some(handle);
+                    }
+
+                    if paint.strokes.is_empty() {
+                        continue;
+                    }
+                    let image = asset_manager.get_image(paint.image.as_ref().unwrap().name());
+                    let asset_mesh = match asset_manager.try_get_mesh(mesh.mesh_name.clone()) {
+                        Ok(asset_mesh) => asset_mesh,
+                        Err(_) => continue,
+                    };
+                    let pipeline = pipeline_manager.get("paint", None).unwrap();
+
+                    for brush in paint.strokes.drain(..) {
+                        let uniform_buffer = device.create_buffer_with_data(
+                            bytemuck::bytes_of(&PaintUniform::new(transform.matrix, &brush)),
+                            wgpu::BufferUsage::UNIFORM,
+                        );
+                        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                            label: Some("paint"),
+                            layout: resource_manager.get_bind_group_layout("paint").unwrap(),
+                            bindings: &[wgpu::Binding {
+                                binding: 0,
+                                resource: wgpu::BindingResource::Buffer(uniform_buffer.slice(..)),
+                            }],
+                        });
+
+                        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                                attachment: &image.view,
+                                resolve_target: None,
+                                load_op: wgpu::LoadOp::Load,
+                                store_op: wgpu::StoreOp::Store,
+                                clear_color: wgpu::Color::WHITE,
+                            }],
+                            depth_stencil_attachment: None,
+                        });
+                        render_pass.set_pipeline(&pipeline.render_pipeline);
+                        render_pass.set_bind_group(0, &bind_group, &[]);
+                        for sub_mesh in asset_mesh.sub_meshes.iter() {
+                            render_pass.set_index_buffer(
+                                sub_mesh.index_buffer.as_ref().unwrap().slice(..),
+                            );
+                            render_pass.set_vertex_buffer(
+                                0,
+                                sub_mesh.vertex_buffer.as_ref().unwrap().slice(..),
+                            );
+                            render_pass.draw_indexed(0..sub_mesh.index_count as u32, 0, 0..1);
+                        }
+                        painted = true;
+                    }
+                }
+
+                if painted {
+                    command_buffer_queue
+                        .push(CommandQueueItem {
+                            buffer: encoder.finish(),
+                            name: "paint".to_string(),
+                        })
+                        .unwrap();
+                }
+            },
+        )
+}
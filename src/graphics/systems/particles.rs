@@ -0,0 +1,314 @@
+use bytemuck::Zeroable;
+use legion::prelude::*;
+use std::collections::HashSet;
+
+use crate::{
+    core::FrameStats,
+    graphics::{
+        material::FallbackTexture,
+        particles::{simulate, spawn, EmitterState, Particle, ParticleUniform, SimulationUniform},
+        resources::{CurrentRenderTarget, GPUResourceManager},
+        CommandBufferQueue, CommandQueueItem, ParticleRenderer,
+    },
+    scene::{
+        components,
+        resources::{DeltaTime, Visibility},
+    },
+    AssetManager,
+};
+
+const PARTICLE_SIZE: u64 = std::mem::size_of::<Particle>() as u64;
+
+/// Spawns and simulates the particles of every `ParticleEmitter`, the mesh system draws them
+/// with the blended meshes.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("update_particles")
+        .write_resource::<ParticleRenderer>()
+        .write_resource::<CommandBufferQueue>()
+        .read_resource::<AssetManager>()
+        .read_resource::<GPUResourceManager>()
+        .read_resource::<CurrentRenderTarget>()
+        .read_resource::<Visibility>()
+        .read_resource::<DeltaTime>()
+        .read_resource::<wgpu::Device>()
+        .read_resource::<FrameStats>()
+        .with_query(<(
+            Read<components::ParticleEmitter>,
+            Read<components::Transform>,
+        )>::query())
+        .build(
+            |_,
+             world,
+             (
+                particle_renderer,
+                command_buffer_queue,
+                asset_manager,
+                resource_manager,
+                current_render_target,
+                visibility,
+                delta_time,
+                device,
+                frame_stats,
+            ),
+             emitter_query| {
+                // Probes draw the particles as they are, they shouldn't move them again.
+                if current_render_target.0.is_some() {
+                    return;
+                }
+                let _scope = frame_stats.scope("update_particles");
+                let delta_time = delta_time.0;
+                let use_compute = !particle_renderer.force_cpu
+                    && resource_manager
+                        .get_compute_pipeline("particle_simulation")
+                        .is_some();
+
+                // Replaced images need new bind groups.
+                if particle_renderer.bind_group_generation != asset_manager.bind_group_generation()
+                {
+                    for state in particle_renderer.emitters.values_mut() {
+                        state.bind_group = None;
+                    }
+                    particle_renderer.bind_group_generation = asset_manager.bind_group_generation();
+                }
+
+                let simulation_layout = resource_manager
+                    .get_bind_group_layout("particle_simulation")
+                    .unwrap();
+                let particle_layout = resource_manager.get_bind_group_layout("particle").unwrap();
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("particles"),
+                });
+
+                let mut emitters = HashSet::new();
+                for (entity, (emitter, transform)) in emitter_query.iter_entities(&world) {
+                    emitters.insert(entity);
+                    let capacity = emitter.max_particles.max(1);
+                    let recreate = particle_renderer
+                        .emitters
+                        .get(&entity)
+                        .map_or(true, |state| {
+                            state.capacity != capacity
+                                || state.cpu_particles.is_none() != use_compute
+                        });
+                    if recreate {
+                        let seed = entity.index().wrapping_mul(0x9e37_79b9);
+                        let state = create_state(
+                            &device,
+                            simulation_layout,
+                            capacity,
+                            seed,
+                            use_compute,
+                            &emitter,
+                        );
+                        particle_renderer.emitters.insert(entity, state);
+                    }
+                    let state = particle_renderer.emitters.get_mut(&entity).unwrap();
+                    state.position =
+                        (transform.matrix * nalgebra_glm::vec4(0.0, 0.0, 0.0, 1.0)).xyz();
+                    state.queue = emitter.render_queue.value();
+                    state.blend_mode = emitter.blend_mode.clone();
+                    state.visible = visibility.is_visible(entity);
+
+                    let mut spawned = Vec::new();
+                    if emitter.emitting && emitter.lifetime > 0.0 {
+                        state.spawn_accumulator += emitter.spawn_rate * delta_time;
+                        let count = state.spawn_accumulator.floor();
+                        state.spawn_accumulator -= count;
+                        for _ in 0..count as u32 {
+                            spawned.push(spawn(&emitter, &transform.matrix, state.seed));
+                            state.seed = state.seed.wrapping_add(1);
+                        }
+                    }
+                    // Only the newest particles fit when more spawn than the buffer holds.
+                    let spawned = &spawned[spawned.len().saturating_sub(capacity as usize)..];
+
+                    let uniform = ParticleUniform::new(&emitter);
+                    let staging = device.create_buffer_with_data(
+                        bytemuck::bytes_of(&uniform),
+                        wgpu::BufferUsage::COPY_SRC,
+                    );
+                    encoder.copy_buffer_to_buffer(
+                        &staging,
+                        0,
+                        &state.uniform,
+                        0,
+                        std::mem::size_of::<ParticleUniform>() as u64,
+                    );
+
+                    match state.cpu_particles.as_mut() {
+                        Some(particles) => {
+                            simulate(particles, emitter.acceleration, &emitter.speed, delta_time);
+                            for particle in spawned {
+                                particles[state.next_slot as usize] = *particle;
+                                state.next_slot = (state.next_slot + 1) % capacity;
+                            }
+                            let staging = device.create_buffer_with_data(
+                                bytemuck::cast_slice(particles),
+                                wgpu::BufferUsage::COPY_SRC,
+                            );
+                            encoder.copy_buffer_to_buffer(
+                                &staging,
+                                0,
+                                &state.particles,
+                                0,
+                                capacity as u64 * PARTICLE_SIZE,
+                            );
+                        }
+                        None => {
+                            let simulation = SimulationUniform::new(&emitter, capacity, delta_time);
+                            let staging = device.create_buffer_with_data(
+                                bytemuck::bytes_of(&simulation),
+                                wgpu::BufferUsage::COPY_SRC,
+                            );
+                            encoder.copy_buffer_to_buffer(
+                                &staging,
+                                0,
+                                &state.simulation,
+                                0,
+                                std::mem::size_of::<SimulationUniform>() as u64,
+                            );
+                            // 64 particles per workgroup.
+                            if let Err(error) = resource_manager.dispatch(
+                                &mut encoder,
+                                "particle_simulation",
+                                &[&state.simulation_bind_group],
+                                [(capacity + 63) / 64, 1, 1],
+                            ) {
+                                log::error!("{}", error);
+                            }
+
+                            // New particles go after the simulation so they start at age 0,
+                            // wrapping around the end of the ring buffer.
+                            if !spawned.is_empty() {
+                                let staging = device.create_buffer_with_data(
+                                    bytemuck::cast_slice(spawned),
+                                    wgpu::BufferUsage::COPY_SRC,
+                                );
+                                let first =
+                                    spawned.len().min((capacity - state.next_slot) as usize);
+                                encoder.copy_buffer_to_buffer(
+                                    &staging,
+                                    0,
+                                    &state.particles,
+                                    state.next_slot as u64 * PARTICLE_SIZE,
+                                    first as u64 * PARTICLE_SIZE,
+                                );
+                                if first < spawned.len() {
+                                    encoder.copy_buffer_to_buffer(
+                                        &staging,
+                                        first as u64 * PARTICLE_SIZE,
+                                        &state.particles,
+                                        0,
+                                        (spawned.len() - first) as u64 * PARTICLE_SIZE,
+                                    );
+                                }
+                                state.next_slot = ((state.next_slot as usize + spawned.len())
+                                    % capacity as usize)
+                                    as u32;
+                            }
+                        }
+                    }
+
+                    // Missing images get the white fallback until they're loaded.
+                    let texture = emitter
+                        .texture
+                        .as_ref()
+                        .map_or("", |texture| texture.name());
+                    let image =
+                        FallbackTexture::White.get_or_fallback(&asset_manager.images, texture);
+                    let stale = state
+                        .bind_group
+                        .as_ref()
+                        .map_or(true, |(name, _)| *name != image.name);
+                    if stale {
+                        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                            label: Some("particle"),
+                            layout: particle_layout,
+                            bindings: &[
+                                wgpu::Binding {
+                                    binding: 0,
+                                    resource: wgpu::BindingResource::Buffer(
+                                        state.uniform.slice(..),
+                                    ),
+                                },
+                                wgpu::Binding {
+                                    binding: 1,
+                                    resource: wgpu::BindingResource::Sampler(&image.sampler),
+                                },
+                                wgpu::Binding {
+                                    binding: 2,
+                                    resource: wgpu::BindingResource::TextureView(&image.view),
+                                },
+                            ],
+                        });
+                        state.bind_group = Some((image.name.clone(), bind_group));
+                    }
+                }
+                // Forget emitters that were removed.
+                particle_renderer
+                    .emitters
+                    .retain(|entity, _| emitters.contains(entity));
+
+                command_buffer_queue
+                    .push(CommandQueueItem {
+                        buffer: encoder.finish(),
+                        name: "particles".to_string(),
+                    })
+                    .unwrap();
+            },
+        )
+}
+
+fn create_state(
+    device: &wgpu::Device,
+    simulation_layout: &wgpu::BindGroupLayout,
+    capacity: u32,
+    seed: u32,
+    use_compute: bool,
+    emitter: &components::ParticleEmitter,
+) -> EmitterState {
+    // Zeroed particles are dead.
+    let dead = vec![Particle::zeroed(); capacity as usize];
+    let particles = device.create_buffer_with_data(
+        bytemuck::cast_slice(&dead),
+        wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+    );
+    let simulation = device.create_buffer_with_data(
+        bytemuck::bytes_of(&SimulationUniform::new(emitter, capacity, 0.0)),
+        wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    );
+    let simulation_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("particle_simulation"),
+        layout: simulation_layout,
+        bindings: &[
+            wgpu::Binding {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(simulation.slice(..)),
+            },
+            wgpu::Binding {
+                binding: 1,
+                resource: wgpu::BindingResource::Buffer(particles.slice(..)),
+            },
+        ],
+    });
+    let uniform = device.create_buffer_with_data(
+        bytemuck::bytes_of(&ParticleUniform::new(emitter)),
+        wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+    );
+    EmitterState {
+        particles,
+        capacity,
+        next_slot: 0,
+        spawn_accumulator: 0.0,
+        seed,
+        simulation,
+        simulation_bind_group,
+        uniform,
+        bind_group: None,
+        cpu_particles: if use_compute { None } else { Some(dead) },
+        position: nalgebra_glm::Vec3::zeros(),
+        queue: emitter.render_queue.value(),
+        blend_mode: emitter.blend_mode.clone(),
+        visible: false,
+    }
+}
@@ -0,0 +1,362 @@
+use crate::{
+    core::FrameStats,
+    graphics::{
+        capture::CaptureTarget,
+        pipeline_manager::{Pipeline, PipelineManager},
+        pipelines::post_process::{
+            ExposureMeter, PostProcessClock, PostProcessSampler,
+            EXPOSURE_METER_BYTES_PER_PIXEL, EXPOSURE_METER_SIZE,
+        },
+        post_processing::{average_log_luminance, PostProcessUniform},
+        resources::GPUResourceManager,
+        split_screen::is_view_camera,
+        CommandBufferQueue, CommandQueueItem, FrameOutput, GpuReadback, PostProcessing,
+        SplitScreen, UniformArena,
+    },
+    scene::{components, resources::DeltaTime},
+    AssetManager,
+};
+use legion::prelude::*;
+use std::sync::Arc;
+
+// Draws a full screen triangle into `target`.
+fn fullscreen_pass(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    uniform_arena: &mut UniformArena,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    pipeline: &Pipeline,
+    target: &wgpu::TextureView,
+    source: &wgpu::TextureView,
+    second: &wgpu::TextureView,
+    uniform: PostProcessUniform,
+) {
+    // Only when the arena is full this frame.
+    let mut uniform_buffer = None;
+    let uniform_binding = match uniform_arena.push(&uniform) {
+        Some(offset) => uniform_arena.binding::<PostProcessUniform>(offset),
+        None => wgpu::BindingResource::Buffer(
+            uniform_buffer
+                .get_or_insert(device.create_buffer_with_data(
+                    bytemuck::bytes_of(&uniform),
+                    wgpu::BufferUsage::UNIFORM,
+                ))
+                .slice(..),
+        ),
+    };
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("post_process"),
+        layout,
+        bindings: &[
+            wgpu::Binding {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(source),
+            },
+            wgpu::Binding {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(second),
+            },
+            wgpu::Binding {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::Binding {
+                binding: 3,
+                resource: uniform_binding,
+            },
+        ],
+    });
+
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+            attachment: target,
+            resolve_target: None,
+            load_op: wgpu::LoadOp::Clear,
+            store_op: wgpu::StoreOp::Store,
+            clear_color: wgpu::Color::BLACK,
+        }],
+        depth_stencil_attachment: None,
+    });
+    render_pass.set_pipeline(&pipeline.render_pipeline);
+    render_pass.set_bind_group(0, &bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+}
+
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("post_process")
+        .write_resource::<CommandBufferQueue>()
+        .read_resource::<PostProcessing>()
+        .read_resource::<PipelineManager>()
+        .read_resource::<GPUResourceManager>()
+        .read_resource::<PostProcessSampler>()
+        .read_resource::<wgpu::Device>()
+        .read_resource::<Arc<FrameOutput>>()
+        .read_resource::<CaptureTarget>()
+        .read_resource::<FrameStats>()
+        .read_resource::<SplitScreen>()
+        .read_resource::<DeltaTime>()
+        .read_resource::<AssetManager>()
+        .write_resource::<ExposureMeter>()
+        .write_resource::<PostProcessClock>()
+        .write_resource::<UniformArena>()
+        .with_query(<(Read<components::CameraData>,)>::query())
+        .build(
+            |_,
+             world,
+             (
+                command_buffer_queue,
+                post_processing,
+                pipeline_manager,
+                resource_manager,
+                sampler,
+                device,
+                output,
+                capture_target,
+                frame_stats,
+                split_screen,
+                delta_time,
+                asset_manager,
+                meter,
+                clock,
+                uniform_arena,
+            ),
+             cameras| {
+                let (frame, frame_size) = match (
+                    pipeline_manager.get_attachment("frame"),
+                    pipeline_manager.get_attachment_size("frame"),
+                ) {
+                    (Some(frame), Some(frame_size)) => (frame, frame_size),
+                    _ => return,
+                };
+                let _scope = frame_stats.scope("post_process");
+                let auto_exposure = &post_processing.auto_exposure;
+                let mut readback = meter.readback.lock().unwrap().take();
+                if let Some(result) = readback.as_mut().and_then(GpuReadback::try_take) {
+                    if let Ok(bytes) = result {
+                        let texels: Vec<[f32; 2]> = bytes
+                            .chunks_exact(EXPOSURE_METER_BYTES_PER_PIXEL as usize)
+                            .map(|texel| {
+                                let value = |i: usize| {
+                                    let mut float = [0; 4];
+                                    float.copy_from_slice(&texel[i * 4..i * 4 + 4]);
+                                    f32::from_le_bytes(float)
+                                };
+                                [value(0), value(1)]
+                            })
+                            .collect();
+                        meter.target = average_log_luminance(&texels)
+                            .map(|luminance| auto_exposure.target_ev(luminance))
+                            .or(meter.target);
+                    }
+                    readback = None;
+                }
+                if !auto_exposure.enabled {
+                    meter.target = None;
+                }
+                meter.ev = meter.target.map(|target| match meter.ev {
+                    Some(ev) => auto_exposure.adapt(ev, target, delta_time.0),
+                    None => target,
+                });
+                let metered = meter.ev.map(|ev| auto_exposure.exposure(ev));
+
+                // Cameras can override the exposure and tonemapper, with split screen the first
+                // player's camera is used since the whole frame is tonemapped at once.
+                let first_camera = split_screen.players().first().map(|player| player.camera);
+                let post_processing = post_processing.for_camera(
+                    cameras
+                        .iter_entities(&world)
+                        .find(|(entity, (camera,))| is_view_camera(first_camera, *entity, camera))
+                        .map(|(_, (camera,))| camera)
+                        .as_deref(),
+                    metered,
+                );
+                let layout = resource_manager
+                    .get_bind_group_layout("post_process")
+                    .unwrap();
+                let sampler = &sampler.0;
+
+                // Bloom, tonemapping and the LDR stages are separate passes in the frame graph
+                // so their attachments only live as long as they're used.
+                let create_encoder = |label| {
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some(label),
+                    })
+                };
+                let mut encoder = create_encoder("bloom");
+                let mut tonemap_encoder = create_encoder("post_process");
+                let mut ldr_encoder = create_encoder("post_process_ldr");
+
+                // Auto-exposure meters the frame into a small texture and reads it back, one copy
+                // in flight at a time.
+                if post_processing.auto_exposure.enabled {
+                    let mask = post_processing
+                        .auto_exposure
+                        .mask
+                        .as_ref()
+                        .and_then(|mask| asset_manager.try_get_image(mask.as_str()).ok());
+                    // Meter everything until the mask has loaded.
+                    let mut uniform = post_processing.uniform((0.0, 0.0), frame_size);
+                    if mask.is_none() {
+                        uniform.metering.z = 0.0;
+                    }
+                    fullscreen_pass(
+                        &device,
+                        &mut encoder,
+                        &mut uniform_arena,
+                        layout,
+                        sampler,
+                        pipeline_manager.get("exposure_meter", None).unwrap(),
+                        &meter.view,
+                        frame,
+                        mask.map_or(frame, |mask| &*mask.view),
+                        uniform,
+                    );
+                    if readback.is_none() {
+                        readback = Some(GpuReadback::from_texture(
+                            &device,
+                            &mut encoder,
+                            &meter.texture,
+                            0,
+                            wgpu::Origin3d::ZERO,
+                            wgpu::Extent3d {
+                                width: EXPOSURE_METER_SIZE,
+                                height: EXPOSURE_METER_SIZE,
+                                depth: 1,
+                            },
+                            EXPOSURE_METER_BYTES_PER_PIXEL,
+                        ));
+                    }
+                } else {
+                    readback = None;
+                }
+                *meter.readback.lock().unwrap() = readback;
+
+                // Bloom: keep the bright parts of the frame then blur them back and forth.
+                let mut bloom = frame;
+                if post_processing.bloom_enabled() {
+                    let bloom_a = pipeline_manager.get_attachment("bloom_a").unwrap();
+                    let bloom_b = pipeline_manager.get_attachment("bloom_b").unwrap();
+                    let bloom_size = pipeline_manager.get_attachment_size("bloom_a").unwrap();
+                    fullscreen_pass(
+                        &device,
+                        &mut encoder,
+                        &mut uniform_arena,
+                        layout,
+                        sampler,
+                        pipeline_manager.get("bloom_threshold", None).unwrap(),
+                        bloom_a,
+                        frame,
+                        frame,
+                        post_processing.uniform((0.0, 0.0), frame_size),
+                    );
+                    let blur = pipeline_manager.get("bloom_blur", None).unwrap();
+                    for _ in 0..post_processing.bloom.blur_passes {
+                        fullscreen_pass(
+                            &device,
+                            &mut encoder,
+                            &mut uniform_arena,
+                            layout,
+                            sampler,
+                            blur,
+                            bloom_b,
+                            bloom_a,
+                            bloom_a,
+                            post_processing.uniform((1.0, 0.0), bloom_size),
+                        );
+                        fullscreen_pass(
+                            &device,
+                            &mut encoder,
+                            &mut uniform_arena,
+                            layout,
+                            sampler,
+                            blur,
+                            bloom_a,
+                            bloom_b,
+                            bloom_b,
+                            post_processing.uniform((0.0, 1.0), bloom_size),
+                        );
+                    }
+                    bloom = bloom_a;
+                }
+
+                // The last pass draws to the screen, and into the capture target when the frame
+                // is being captured.
+                let targets: Vec<&wgpu::TextureView> = std::iter::once(output.view())
+                    .chain(capture_target.0.as_ref())
+                    .collect();
+
+                // Tonemap, the camera's warp, FXAA and sharpening run in that order, the ones
+                // that aren't last write into the LDR attachments and the last one adds grain
+                // and dithering.
+                let mut stages = vec!["tonemap"];
+                if post_processing.warp.is_some() {
+                    stages.push("warp");
+                }
+                if post_processing.fxaa {
+                    stages.push("fxaa");
+                }
+                if post_processing.final_pass.sharpen {
+                    stages.push("sharpen");
+                }
+                clock.0 += delta_time.0;
+                let ldr = [
+                    pipeline_manager.get_attachment("ldr").unwrap(),
+                    pipeline_manager.get_attachment("ldr_b").unwrap(),
+                ];
+                let (mut source, mut second) = (frame, bloom);
+                for (i, stage) in stages.iter().enumerate() {
+                    let pipeline = pipeline_manager.get(*stage, None).unwrap();
+                    let encoder = match *stage {
+                        "fxaa" | "sharpen" => &mut ldr_encoder,
+                        _ => &mut tonemap_encoder,
+                    };
+                    if i + 1 == stages.len() {
+                        for target in targets.iter() {
+                            fullscreen_pass(
+                                &device,
+                                encoder,
+                                &mut uniform_arena,
+                                layout,
+                                sampler,
+                                pipeline,
+                                target,
+                                source,
+                                second,
+                                post_processing.final_uniform(frame_size, clock.0),
+                            );
+                        }
+                    } else {
+                        let target = ldr[i % 2];
+                        fullscreen_pass(
+                            &device,
+                            encoder,
+                            &mut uniform_arena,
+                            layout,
+                            sampler,
+                            pipeline,
+                            target,
+                            source,
+                            second,
+                            post_processing.uniform((0.0, 0.0), frame_size),
+                        );
+                        source = target;
+                        second = target;
+                    }
+                }
+
+                for (encoder, name) in vec![
+                    (encoder, "bloom"),
+                    (tonemap_encoder, "post_process"),
+                    (ldr_encoder, "post_process_ldr"),
+                ] {
+                    command_buffer_queue
+                        .push(CommandQueueItem {
+                            buffer: encoder.finish(),
+                            name: name.to_string(),
+                        })
+                        .unwrap();
+                }
+            },
+        )
+}
@@ -1,4 +1,4 @@
-use crate::graphics::{pipeline_manager::PipelineManager, CommandBufferQueue};
+use crate::graphics::{pipeline_manager::PipelineManager, CommandBufferQueue, FramesInFlight};
 use legion::prelude::*;
 
 pub fn create() -> Box<dyn Fn(&mut World, &mut Resources) -> ()> {
@@ -7,12 +7,13 @@ pub fn create() -> Box<dyn Fn(&mut World, &mut Resources) -> ()> {
 
         // Moved this out into application run loop.
         //let _swap_chain_output = resources.remove::<Arc<wgpu::SwapChainOutput>>().unwrap();
-        let queue = resources.get::<wgpu::Queue>().unwrap();
+        let frames_in_flight = resources.get::<FramesInFlight>().unwrap();
         let pipeline_manager = resources.get::<PipelineManager>().unwrap();
         let mut command_queue = resources.get_mut::<CommandBufferQueue>().unwrap();
         command_buffers.extend(pipeline_manager.collect_buffers(&mut command_queue));
 
-        queue.submit(command_buffers);
+        // Submitted with everything else at the end of the frame.
+        frames_in_flight.submit(command_buffers);
     });
     thread
 }
@@ -0,0 +1,156 @@
+use legion::prelude::*;
+use std::collections::HashSet;
+
+use crate::{
+    graphics::{
+        material::Material,
+        particles::particle_variant,
+        pipeline_manager::{PipelineManager, PipelineVariant},
+        resources::GPUResourceManager,
+        BlendMode, RenderSettings,
+    },
+    scene::components::ParticleEmitter,
+    AssetManager,
+};
+
+// The variants the loaded PBR materials need, with and without vertex attributes.
+fn material_variants(asset_manager: &AssetManager) -> Vec<(&'static str, PipelineVariant)> {
+    let attribute_materials: HashSet<u32> = asset_manager
+        .get_meshes()
+        .iter()
+        .flat_map(|mesh| mesh.sub_meshes.iter())
+        .filter(|sub_mesh| sub_mesh.has_vertex_attributes())
+        .map(|sub_mesh| sub_mesh.material_index)
+        .collect();
+    asset_manager
+        .get_materials()
+        .iter()
+        .filter_map(|material| match material {
+            Material::PBR(data) => Some(data),
+            _ => None,
+        })
+        .flat_map(|data| {
+            let attributes = attribute_materials.contains(&data.index);
+            data.pipeline_names().iter().flat_map(move |name| {
+                let variant = data.pipeline_variant(name);
+                let attribute_variant = PipelineVariant {
+                    vertex_attributes: true,
+                    ..variant.clone()
+                };
+                Some((*name, variant))
+                    .into_iter()
+                    .chain(Some((*name, attribute_variant)).filter(|_| attributes))
+            })
+        })
+        .collect()
+}
+
+// Builds the variants that don't exist yet and returns how many that was. With MSAA each one
+// gets a single sampled copy as well, for drawing into probes.
+fn build_variants(
+    variants: Vec<(&'static str, PipelineVariant)>,
+    render_settings: &RenderSettings,
+    pipeline_manager: &mut PipelineManager,
+    device: &wgpu::Device,
+    asset_manager: &mut AssetManager,
+    resource_manager: &GPUResourceManager,
+) -> usize {
+    let single_sampled: Vec<_> = variants
+        .iter()
+        .filter(|_| render_settings.msaa())
+        .map(|(pipeline_name, variant)| {
+            let variant = PipelineVariant {
+                single_sampled: true,
+                ..variant.clone()
+            };
+            (*pipeline_name, variant)
+        })
+        .collect();
+    let mut built = 0;
+    for (pipeline_name, variant) in variants.into_iter().chain(single_sampled) {
+        if variant.is_default() || pipeline_manager.get_variant(pipeline_name, &variant).is_some()
+        {
+            continue;
+        }
+        match pipeline_manager.add_variant(
+            pipeline_name,
+            &variant,
+            device,
+            asset_manager,
+            resource_manager,
+        ) {
+            Ok(()) => built += 1,
+            Err(error) => log::error!("{}", error),
+        }
+    }
+    built
+}
+
+/// Builds every pipeline variant and material bind group the loaded assets can need ahead of
+/// time, so nothing is compiled the first time something is drawn. Particle variants are made
+/// for every blend mode since emitters can be spawned at any time. Returns how many pipelines
+/// were built, see `Application::warm_up`.
+pub(crate) fn warm_up(resources: &Resources) -> usize {
+    let mut asset_manager = resources.get_mut::<AssetManager>().unwrap();
+    let mut pipeline_manager = resources.get_mut::<PipelineManager>().unwrap();
+    let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+    let device = resources.get::<wgpu::Device>().unwrap();
+    let render_settings = resources.get::<RenderSettings>().unwrap();
+
+    asset_manager.refresh_bind_groups(&device, &mut resource_manager);
+    let mut variants = material_variants(&asset_manager);
+    let blend_modes = [
+        BlendMode::Alpha,
+        BlendMode::Premultiplied,
+        BlendMode::Additive,
+        BlendMode::Multiply,
+    ];
+    variants.extend(
+        blend_modes
+            .iter()
+            .map(|blend_mode| ("particle", particle_variant(blend_mode))),
+    );
+    build_variants(
+        variants,
+        &render_settings,
+        &mut pipeline_manager,
+        &device,
+        &mut asset_manager,
+        &resource_manager,
+    )
+}
+
+/// Builds the pipeline variants for PBR materials with `shader_defines`, a custom `blend_mode`
+/// or a `depth_bias`, for materials used by meshes with vertex colors or a second uv set, and
+/// for particle emitters with a blend mode other than alpha, before they're drawn. With MSAA
+/// their single sampled copies for probes are built too.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("shader_variants")
+        .write_resource::<AssetManager>()
+        .write_resource::<PipelineManager>()
+        .read_resource::<GPUResourceManager>()
+        .read_resource::<wgpu::Device>()
+        .read_resource::<RenderSettings>()
+        .with_query(<(Read<ParticleEmitter>,)>::query())
+        .build(
+            |_,
+             world,
+             (asset_manager, pipeline_manager, resource_manager, device, render_settings),
+             emitters| {
+                let mut variants = material_variants(&asset_manager);
+                variants.extend(
+                    emitters
+                        .iter(&world)
+                        .map(|(emitter,)| ("particle", particle_variant(&emitter.blend_mode))),
+                );
+                build_variants(
+                    variants,
+                    &render_settings,
+                    &mut pipeline_manager,
+                    &device,
+                    &mut asset_manager,
+                    &resource_manager,
+                );
+            },
+        )
+}
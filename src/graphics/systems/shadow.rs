@@ -0,0 +1,196 @@
+use legion::prelude::*;
+use nalgebra_glm::{Vec3, Vec4};
+
+use crate::{
+    graphics::{
+        pipelines::{
+            shadow::{
+                cascade_matrix, cascade_splits, spot_matrix, ShadowCasterUniform, ShadowUniform,
+                MAX_SPOT_SHADOWS,
+            },
+            MAX_LIGHTS,
+        },
+        resources::GPUResourceManager,
+        split_screen::is_view_camera,
+        CommandBufferQueue, CommandQueueItem, SplitScreen,
+    },
+    scene::components::{self, ShadowFilter},
+};
+
+// How much the cascade splits lean towards logarithmic.
+const SPLIT_LAMBDA: f32 = 0.75;
+
+/// Fits the shadow cascades to the active camera and uploads the light matrices, along with
+/// the matrices of the spot lights that cast shadows.
+/// With split-screen they're fitted to the first player's camera.
+/// The shadow maps themselves are rendered by the mesh system once transforms are uploaded.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("encoder_shadow")
+        .write_resource::<CommandBufferQueue>()
+        .read_resource::<GPUResourceManager>()
+        .read_resource::<SplitScreen>()
+        .read_resource::<wgpu::Device>()
+        .with_query(<(Read<components::CameraData>,)>::query())
+        .with_query(<(Read<components::DirectionalLightData>,)>::query())
+        .with_query(<(
+            Read<components::SpotLightData>,
+            Read<components::Transform>,
+        )>::query())
+        .build(
+            |_,
+             world,
+             (command_buffer_queue, resource_manager, split_screen, device),
+             (camera_data, directional_lights, spot_lights)| {
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("shadow"),
+                });
+
+                let first_camera = split_screen.players().first().map(|player| player.camera);
+                let camera = camera_data
+                    .iter_entities(&world)
+                    .find(|(entity, (camera,))| is_view_camera(first_camera, *entity, camera))
+                    .map(|(_, (camera,))| camera);
+                // Lights past MAX_LIGHTS / 2 never make it into the lighting uniform.
+                let light = directional_lights
+                    .iter(&world)
+                    .take(MAX_LIGHTS / 2)
+                    .enumerate()
+                    .find(|(_, (light,))| light.casts_shadows);
+
+                let mut uniform = ShadowUniform::default();
+                if let (Some(camera), Some((light_index, (light,)))) = (camera, light) {
+                    let (z_near, z_far) = camera.depth_range();
+                    let (shadow_near, shadow_far) = match light.shadow.depth_range {
+                        Some((near, far)) => (near.max(z_near).min(z_far), far.min(z_far)),
+                        None => (z_near, z_far.min(light.shadow_distance)),
+                    };
+                    let shadow_far = shadow_far.max(shadow_near);
+                    let splits = cascade_splits(shadow_near, shadow_far, SPLIT_LAMBDA);
+
+                    // Our projections map depth from -1 to 1.
+                    let inverse_view_projection = nalgebra_glm::inverse(&camera.get_matrix());
+                    let mut near_corners = [Vec3::zeros(); 4];
+                    let mut far_corners = [Vec3::zeros(); 4];
+                    for (i, (x, y)) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                        .iter()
+                        .enumerate()
+                    {
+                        let near = inverse_view_projection * Vec4::new(*x, *y, -1.0, 1.0);
+                        let far = inverse_view_projection * Vec4::new(*x, *y, 1.0, 1.0);
+                        near_corners[i] = near.xyz() / near.w;
+                        far_corners[i] = far.xyz() / far.w;
+                    }
+
+                    let mut cascade_start = shadow_near;
+                    for (cascade, split) in splits.iter().enumerate() {
+                        let start = (cascade_start - z_near) / (z_far - z_near);
+                        let end = (split - z_near) / (z_far - z_near);
+                        let mut corners = [Vec3::zeros(); 8];
+                        for i in 0..4 {
+                            let ray = far_corners[i] - near_corners[i];
+                            corners[i] = near_corners[i] + ray * start;
+                            corners[i + 4] = near_corners[i] + ray * end;
+                        }
+                        uniform.cascades[cascade] = cascade_matrix(&corners, light.direction);
+                        cascade_start = *split;
+                    }
+
+                    let settings = &light.shadow;
+                    uniform.splits = Vec4::new(splits[0], splits[1], splits[2], splits[3]);
+                    uniform.bias = Vec4::new(
+                        settings.depth_bias,
+                        settings.slope_bias,
+                        settings.normal_bias,
+                        light_index as f32,
+                    );
+                    uniform.filter = match settings.filter {
+                        ShadowFilter::Hard => Vec4::new(0.0, 1.0, 0.0, 0.0),
+                        ShadowFilter::PCF { kernel_size } => {
+                            Vec4::new(1.0, kernel_size.max(1) as f32, 0.0, 0.0)
+                        }
+                        ShadowFilter::PCSS {
+                            light_size,
+                            blocker_samples,
+                            filter_samples,
+                        } => Vec4::new(
+                            2.0,
+                            filter_samples.max(1) as f32,
+                            light_size,
+                            blocker_samples.max(1) as f32,
+                        ),
+                    };
+                }
+
+                // Light culling hands the tiles to the spot lights in the same order.
+                let spots = spot_lights
+                    .iter(&world)
+                    .filter(|(light, _)| light.casts_shadows)
+                    .take(MAX_SPOT_SHADOWS);
+                for (tile, (light, transform)) in spots.enumerate() {
+                    let direction = nalgebra_glm::quat_rotate_vec3(
+                        &transform.rotation,
+                        &Vec3::new(0.0, 0.0, -1.0),
+                    );
+                    uniform.spot_matrices[tile] = spot_matrix(
+                        transform.position,
+                        direction,
+                        light.outer_angle,
+                        light.range,
+                    );
+                    let settings = &light.shadow;
+                    let kernel_size = match settings.filter {
+                        ShadowFilter::Hard => 1,
+                        ShadowFilter::PCF { kernel_size } => kernel_size.max(1),
+                        ShadowFilter::PCSS { .. } => 3,
+                    };
+                    uniform.spot_bias[tile] = Vec4::new(
+                        settings.depth_bias,
+                        settings.slope_bias,
+                        settings.normal_bias,
+                        kernel_size as f32,
+                    );
+                }
+
+                let casters = uniform
+                    .cascades
+                    .iter()
+                    .chain(uniform.spot_matrices.iter())
+                    .enumerate();
+                for (caster, view_projection) in casters {
+                    let caster_buffer = device.create_buffer_with_data(
+                        bytemuck::bytes_of(&ShadowCasterUniform {
+                            view_projection: *view_projection,
+                        }),
+                        wgpu::BufferUsage::COPY_SRC,
+                    );
+                    encoder.copy_buffer_to_buffer(
+                        &caster_buffer,
+                        0,
+                        resource_manager.get_multi_buffer("shadow_caster", caster as u32),
+                        0,
+                        std::mem::size_of::<ShadowCasterUniform>() as u64,
+                    );
+                }
+
+                // Always upload, turning off shadows needs to reset the light index.
+                let shadow_buffer = device.create_buffer_with_data(
+                    bytemuck::bytes_of(&uniform),
+                    wgpu::BufferUsage::COPY_SRC,
+                );
+                encoder.copy_buffer_to_buffer(
+                    &shadow_buffer,
+                    0,
+                    &resource_manager.global_shadow_buffer,
+                    0,
+                    std::mem::size_of::<ShadowUniform>() as u64,
+                );
+
+                command_buffer_queue
+                    .push(CommandQueueItem {
+                        buffer: encoder.finish(),
+                        name: "shadow".to_string(),
+                    })
+                    .unwrap();
+            },
+        )
+}
@@ -0,0 +1,102 @@
+use legion::prelude::*;
+
+use crate::{
+    graphics::{
+        animation::{JointUniform, MAX_JOINTS},
+        resources::{BindGroup, GPUResourceManager},
+        CommandBufferQueue, CommandQueueItem,
+    },
+    scene::components,
+};
+
+/// Uploads the joint matrices of every `Animator` to the GPU.
+/// Each animated entity gets it's own locals bind group with the transform and joints in it.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("encoder_skinning")
+        .write_resource::<CommandBufferQueue>()
+        .write_resource::<GPUResourceManager>()
+        .read_resource::<wgpu::Device>()
+        .with_query(<(Write<components::Animator>, Read<components::Transform>)>::query())
+        .build(
+            |_, mut world, (command_buffer_queue, resource_manager, device), query| {
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("skinning"),
+                });
+
+                for (mut animator, transform) in query.iter_mut(&mut world) {
+                    if animator.joint_matrices.is_empty() {
+                        continue;
+                    }
+
+                    if animator.slot != Some(transform.index) {
+                        if let Some(slot) = animator.slot {
+                            resource_manager.remove_multi_bind_group("skinned_transform", slot);
+                            resource_manager.remove_multi_buffer("skinned_joints", slot);
+                        }
+
+                        let joint_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                            label: Some("skinned_joints"),
+                            size: (MAX_JOINTS * std::mem::size_of::<JointUniform>()) as u64,
+                            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+                        });
+                        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                            layout: resource_manager
+                                .get_bind_group_layout("skinned_locals")
+                                .unwrap(),
+                            bindings: &[
+                                wgpu::Binding {
+                                    binding: 0,
+                                    resource: wgpu::BindingResource::Buffer(
+                                        resource_manager
+                                            .get_multi_buffer("transform", transform.index)
+                                            .slice(..),
+                                    ),
+                                },
+                                wgpu::Binding {
+                                    binding: 1,
+                                    resource: wgpu::BindingResource::Buffer(joint_buffer.slice(..)),
+                                },
+                            ],
+                            label: Some("skinned_locals"),
+                        });
+                        resource_manager.add_multi_bind_group(
+                            "skinned_transform",
+                            BindGroup::new(0, bind_group),
+                            transform.index,
+                        );
+                        resource_manager.add_multi_buffer(
+                            "skinned_joints",
+                            joint_buffer,
+                            transform.index,
+                        );
+                        animator.slot = Some(transform.index);
+                    }
+
+                    let joints: Vec<JointUniform> = animator
+                        .joint_matrices
+                        .iter()
+                        .take(MAX_JOINTS)
+                        .map(|matrix| JointUniform { matrix: *matrix })
+                        .collect();
+                    let temp_buffer = device.create_buffer_with_data(
+                        bytemuck::cast_slice(&joints),
+                        wgpu::BufferUsage::COPY_SRC,
+                    );
+                    encoder.copy_buffer_to_buffer(
+                        &temp_buffer,
+                        0,
+                        resource_manager.get_multi_buffer("skinned_joints", transform.index),
+                        0,
+                        (joints.len() * std::mem::size_of::<JointUniform>()) as u64,
+                    );
+                }
+
+                command_buffer_queue
+                    .push(CommandQueueItem {
+                        buffer: encoder.finish(),
+                        name: "skinning".to_string(),
+                    })
+                    .unwrap();
+            },
+        )
+}
@@ -1,12 +1,12 @@
 use crate::graphics::{
     material::{skybox::SkyboxType, Skybox},
     pipeline_manager::{Pipeline, PipelineManager},
+    pipelines::atmosphere::AtmosphereResources,
     renderer::DepthTexture,
     resources::{CurrentRenderTarget, GPUResourceManager},
-    CommandBufferQueue, CommandQueueItem,
+    CommandBufferQueue, CommandQueueItem, RenderSettings, SplitScreen,
 };
 use legion::prelude::*;
-use std::sync::Arc;
 
 pub fn create() -> Box<dyn Schedulable> {
     SystemBuilder::new("render_skybox")
@@ -14,9 +14,11 @@ pub fn create() -> Box<dyn Schedulable> {
         .read_resource::<CurrentRenderTarget>()
         .read_resource::<GPUResourceManager>()
         .read_resource::<PipelineManager>()
+        .read_resource::<SplitScreen>()
         .read_resource::<wgpu::Device>()
-        .read_resource::<Arc<wgpu::SwapChainOutput>>()
         .read_resource::<DepthTexture>()
+        .read_resource::<AtmosphereResources>()
+        .read_resource::<RenderSettings>()
         .with_query(<(Read<Skybox>,)>::query())
         .build(
             |_,
@@ -26,19 +28,24 @@ pub fn create() -> Box<dyn Schedulable> {
                 current_render_target,
                 resource_manager,
                 pipeline_manager,
+                split_screen,
                 device,
-                output,
                 depth_texture,
+                atmosphere_resources,
+                render_settings,
             ),
              skyboxes| {
                 let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
                     label: Some("skybox_clear_pass"),
                 });
                 
-                let view_attachment = if current_render_target.0.is_some() {
-                    &current_render_target.0.as_ref().unwrap().1
+                let (view_attachment, resolve_target) = if current_render_target.0.is_some() {
+                    (&current_render_target.0.as_ref().unwrap().1, None)
                 } else {
-                    &output.view
+                    match pipeline_manager.get_frame_target() {
+                        Some(frame_target) => frame_target,
+                        None => return,
+                    }
                 };
                 
                 let depth_attachment = if current_render_target.0.is_some() {
@@ -54,14 +61,36 @@ pub fn create() -> Box<dyn Schedulable> {
                     &depth_texture.0
                 };
                 
-                let pipeline: &Pipeline = pipeline_manager.get("skybox", None).unwrap();
-                let pipeline_realtime: &Pipeline = pipeline_manager.get("realtime_skybox", None).unwrap();
+                // Probes render their own single view.
+                let viewports = match pipeline_manager.get_attachment_size("frame") {
+                    Some(size) if current_render_target.0.is_none() => {
+                        split_screen.pixel_viewports(size)
+                    }
+                    _ => vec![None],
+                };
+
+                // Probes don't have MSAA so they need the single sampled pipelines.
+                let sample_count = if current_render_target.0.is_some() {
+                    1
+                } else {
+                    render_settings.sample_count
+                };
+                let pipeline: &Pipeline =
+                    pipeline_manager.get_with_sample_count("skybox", sample_count).unwrap();
+                let pipeline_realtime: &Pipeline = pipeline_manager
+                    .get_with_sample_count("realtime_skybox", sample_count)
+                    .unwrap();
+                let pipeline_atmosphere: &Pipeline = pipeline_manager
+                    .get_with_sample_count("atmosphere_sky", sample_count)
+                    .unwrap();
 
+                let mut cleared = false;
                 for (skybox,) in skyboxes.iter(&world) {
+                    cleared = true;
                     let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                         color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
                             attachment: view_attachment,
-                            resolve_target: None,
+                            resolve_target,
                             load_op: wgpu::LoadOp::Clear,
                             store_op: wgpu::StoreOp::Store,
                             clear_color: wgpu::Color {
@@ -84,28 +113,78 @@ pub fn create() -> Box<dyn Schedulable> {
                         ),
                     });
 
-                    if skybox.skybox_type == SkyboxType::HdrCubemap {
-                        render_pass.set_pipeline(&pipeline.render_pipeline);
-                        render_pass.set_bind_group(0, &resource_manager.global_bind_group, &[]);
+                    for (player, viewport) in viewports.iter().enumerate() {
+                        if let Some(viewport) = viewport {
+                            render_pass.set_viewport(
+                                viewport.x,
+                                viewport.y,
+                                viewport.width,
+                                viewport.height,
+                                0.0,
+                                1.0,
+                            );
+                        }
+                        let (globals, _) = resource_manager.player_bind_groups(player);
+
+                        if skybox.skybox_type == SkyboxType::HdrCubemap {
+                            render_pass.set_pipeline(&pipeline.render_pipeline);
+                            render_pass.set_bind_group(0, globals, &[]);
 
-                        render_pass.set_bind_group(
-                            1,
-                            skybox.cubemap_bind_group.as_ref().unwrap(),
-                            &[],
-                        );
-                        render_pass.draw(0..3 as u32, 0..1);
-                    } else if skybox.skybox_type == SkyboxType::RealTime {
-                        render_pass.set_pipeline(&pipeline_realtime.render_pipeline);
-                        render_pass.set_bind_group(0, &resource_manager.global_bind_group, &[]);
-                        render_pass.set_bind_group(
-                            1,
-                            skybox.cubemap_bind_group.as_ref().unwrap(),
-                            &[],
-                        );
-                        render_pass.draw(0..3 as u32, 0..1);
+                            render_pass.set_bind_group(
+                                1,
+                                skybox.cubemap_bind_group.as_ref().unwrap(),
+                                &[],
+                            );
+                            render_pass.draw(0..3 as u32, 0..1);
+                        } else if skybox.skybox_type == SkyboxType::RealTime {
+                            render_pass.set_pipeline(&pipeline_realtime.render_pipeline);
+                            render_pass.set_bind_group(0, globals, &[]);
+                            render_pass.set_bind_group(
+                                1,
+                                skybox.cubemap_bind_group.as_ref().unwrap(),
+                                &[],
+                            );
+                            render_pass.draw(0..3 as u32, 0..1);
+                        } else if skybox.skybox_type == SkyboxType::Atmosphere {
+                            // The bind groups are made by the atmosphere system once it has a camera.
+                            if let (Some(atmosphere), Some(sky_view)) = (
+                                atmosphere_resources.bind_group.as_ref(),
+                                atmosphere_resources.sky_view_bind_group.as_ref(),
+                            ) {
+                                render_pass.set_pipeline(&pipeline_atmosphere.render_pipeline);
+                                render_pass.set_bind_group(0, globals, &[]);
+                                render_pass.set_bind_group(1, atmosphere, &[]);
+                                render_pass.set_bind_group(2, sky_view, &[]);
+                                render_pass.draw(0..3, 0..1);
+                            }
+                        }
                     }
                 }
 
+                // The frame texture is kept between frames, so it still needs clearing without a skybox.
+                if !cleared {
+                    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                            attachment: view_attachment,
+                            resolve_target,
+                            load_op: wgpu::LoadOp::Clear,
+                            store_op: wgpu::StoreOp::Store,
+                            clear_color: wgpu::Color::BLACK,
+                        }],
+                        depth_stencil_attachment: Some(
+                            wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                                attachment: depth_attachment,
+                                depth_load_op: wgpu::LoadOp::Clear,
+                                depth_store_op: wgpu::StoreOp::Store,
+                                stencil_load_op: wgpu::LoadOp::Clear,
+                                stencil_store_op: wgpu::StoreOp::Store,
+                                clear_depth: 1.0,
+                                clear_stencil: 0,
+                            },
+                        ),
+                    });
+                }
+
                 command_buffer_queue
                     .push(CommandQueueItem {
                         buffer: encoder.finish(),
@@ -0,0 +1,53 @@
+use legion::prelude::*;
+use std::collections::HashMap;
+
+use crate::{
+    graphics::{resources::GPUResourceManager, split_screen::is_view_camera, SplitScreen},
+    scene::components,
+};
+
+/// Keeps every view's camera the same shape as it's viewport, so cameras follow window resizes,
+/// and makes sure every player has their own bindings.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("split_screen")
+        .write_resource::<SplitScreen>()
+        .write_resource::<GPUResourceManager>()
+        .read_resource::<wgpu::Device>()
+        .read_resource::<wgpu::SwapChainDescriptor>()
+        .with_query(<(Write<components::CameraData>,)>::query())
+        .build(
+            |_, mut world, (split_screen, resource_manager, device, sc_desc), cameras| {
+                let mut camera_viewports = HashMap::new();
+                let mut main_viewport = None;
+                for (entity, (camera,)) in cameras.iter_entities_mut(&mut world) {
+                    camera_viewports.insert(entity, camera.viewport);
+                    if camera.active && main_viewport.is_none() {
+                        main_viewport = Some(camera.viewport);
+                    }
+                }
+                split_screen.update_viewports(main_viewport.flatten(), |camera| {
+                    camera_viewports.get(&camera).copied().flatten()
+                });
+                if split_screen.is_active() {
+                    resource_manager.prepare_player_views(&device, split_screen.players().len());
+                }
+
+                let views = split_screen.views();
+                for (entity, (mut camera,)) in cameras.iter_entities_mut(&mut world) {
+                    let view = views
+                        .iter()
+                        .find(|(view, _)| is_view_camera(*view, entity, &camera));
+                    if let Some((_, viewport)) = view {
+                        let viewport =
+                            viewport.to_pixels(sc_desc.width as f32, sc_desc.height as f32);
+                        let changed =
+                            camera.width != viewport.width || camera.height != viewport.height;
+                        // Tiny viewports would give a broken projection.
+                        if changed && viewport.width >= 1.0 && viewport.height >= 1.0 {
+                            camera.resize(viewport.width, viewport.height);
+                        }
+                    }
+                }
+            },
+        )
+}
@@ -0,0 +1,206 @@
+use legion::prelude::*;
+
+use crate::{
+    core::FrameStats,
+    graphics::{
+        material::{FallbackTexture, FilterMode, SamplerInfo},
+        pipeline_manager::PipelineManager,
+        resources::{CurrentRenderTarget, GPUResourceManager},
+        sprite::batch_sprites,
+        ClipRect, CommandBufferQueue, CommandQueueItem, SplitScreen, SpriteRenderer,
+    },
+    scene::{components, resources::Visibility},
+    AssetManager,
+};
+
+/// Batches every visible `Sprite` and draws them on top of the scene for every view.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("render_sprites")
+        .write_resource::<SpriteRenderer>()
+        .write_resource::<CommandBufferQueue>()
+        .read_resource::<AssetManager>()
+        .read_resource::<PipelineManager>()
+        .read_resource::<GPUResourceManager>()
+        .read_resource::<SplitScreen>()
+        .read_resource::<CurrentRenderTarget>()
+        .read_resource::<Visibility>()
+        .read_resource::<wgpu::Device>()
+        .read_resource::<FrameStats>()
+        .with_query(<(Read<components::Sprite>, Read<components::Transform>)>::query())
+        .build(
+            |_,
+             world,
+             (
+                sprite_renderer,
+                command_buffer_queue,
+                asset_manager,
+                pipeline_manager,
+                resource_manager,
+                split_screen,
+                current_render_target,
+                visibility,
+                device,
+                frame_stats,
+            ),
+             sprite_query| {
+                sprite_renderer.draw_calls = 0;
+                // Probes only capture the 3D scene.
+                if current_render_target.0.is_some() {
+                    return;
+                }
+                let ((frame, resolve_target), frame_size) = match (
+                    pipeline_manager.get_frame_target(),
+                    pipeline_manager.get_attachment_size("frame"),
+                ) {
+                    (Some(frame_target), Some(frame_size)) => (frame_target, frame_size),
+                    _ => return,
+                };
+                let _scope = frame_stats.scope("render_sprites");
+
+                let sprites = sprite_query
+                    .iter_entities(&world)
+                    .filter(|(entity, _)| visibility.is_visible(*entity))
+                    .map(|(_, components)| components)
+                    .collect::<Vec<_>>();
+                if sprites.is_empty() {
+                    return;
+                }
+                let (vertices, batches) = batch_sprites(
+                    sprites
+                        .iter()
+                        .map(|(sprite, transform)| (&**sprite, &transform.matrix))
+                        .collect(),
+                );
+
+                // Replaced images need new bind groups.
+                if sprite_renderer.bind_group_generation != asset_manager.bind_group_generation() {
+                    sprite_renderer.bind_groups.clear();
+                    sprite_renderer.bind_group_generation = asset_manager.bind_group_generation();
+                }
+                let pixel_perfect = sprite_renderer.pixel_perfect;
+                let pixel_sampler = SamplerInfo {
+                    mag_filter: FilterMode::Nearest,
+                    min_filter: FilterMode::Nearest,
+                    mipmap_filter: FilterMode::Nearest,
+                    ..SamplerInfo::clamped()
+                };
+                let layout = resource_manager.get_bind_group_layout("sprite").unwrap();
+                for batch in batches.iter() {
+                    // Missing images get the white fallback until they're loaded.
+                    let image =
+                        FallbackTexture::White.get_or_fallback(&asset_manager.images, &batch.image);
+                    let key = (image.name.clone(), pixel_perfect);
+                    if sprite_renderer.bind_groups.contains_key(&key) {
+                        continue;
+                    }
+                    let sampler = if pixel_perfect {
+                        resource_manager.get_sampler(&device, &pixel_sampler)
+                    } else {
+                        image.sampler.clone()
+                    };
+                    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: Some("sprite"),
+                        layout,
+                        bindings: &[
+                            wgpu::Binding {
+                                binding: 0,
+                                resource: wgpu::BindingResource::Sampler(&sampler),
+                            },
+                            wgpu::Binding {
+                                binding: 1,
+                                resource: wgpu::BindingResource::TextureView(&image.view),
+                            },
+                        ],
+                    });
+                    sprite_renderer.bind_groups.insert(key, bind_group);
+                }
+
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("sprites"),
+                });
+
+                let data: &[u8] = bytemuck::cast_slice(&vertices);
+                let size = data.len() as u64;
+                let capacity = sprite_renderer
+                    .buffer
+                    .as_ref()
+                    .map_or(0, |(_, capacity)| *capacity);
+                if capacity < size {
+                    let capacity = size.next_power_of_two();
+                    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("sprites"),
+                        size: capacity,
+                        usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+                    });
+                    sprite_renderer.buffer = Some((buffer, capacity));
+                }
+                let staging = device.create_buffer_with_data(data, wgpu::BufferUsage::COPY_SRC);
+                {
+                    let (buffer, _) = sprite_renderer.buffer.as_ref().unwrap();
+                    encoder.copy_buffer_to_buffer(&staging, 0, buffer, 0, size);
+
+                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                            attachment: frame,
+                            resolve_target,
+                            load_op: wgpu::LoadOp::Load,
+                            store_op: wgpu::StoreOp::Store,
+                            clear_color: wgpu::Color::BLACK,
+                        }],
+                        depth_stencil_attachment: None,
+                    });
+                    let pipeline = pipeline_manager.get("sprite", None).unwrap();
+                    render_pass.set_pipeline(&pipeline.render_pipeline);
+                    render_pass.set_vertex_buffer(0, buffer.slice(..size));
+                    for (player, viewport) in
+                        split_screen.pixel_viewports(frame_size).iter().enumerate()
+                    {
+                        if let Some(viewport) = viewport {
+                            render_pass.set_viewport(
+                                viewport.x,
+                                viewport.y,
+                                viewport.width,
+                                viewport.height,
+                                0.0,
+                                1.0,
+                            );
+                        }
+                        let (globals, _) = resource_manager.player_bind_groups(player);
+                        render_pass.set_bind_group(0, globals, &[]);
+                        let view_rect = viewport.map_or(
+                            ClipRect::new(0.0, 0.0, frame_size.0 as f32, frame_size.1 as f32),
+                            ClipRect::from,
+                        );
+                        for batch in batches.iter() {
+                            // Scissor rects replace each other so unclipped batches get the
+                            // whole view back.
+                            let clip = batch
+                                .clip
+                                .map_or(view_rect, |clip| clip.intersect(&view_rect));
+                            match clip.to_scissor(frame_size.0, frame_size.1) {
+                                Some((x, y, width, height)) => {
+                                    render_pass.set_scissor_rect(x, y, width, height)
+                                }
+                                None => continue,
+                            }
+                            let image = FallbackTexture::White
+                                .get_or_fallback(&asset_manager.images, &batch.image);
+                            let bind_group =
+                                &sprite_renderer.bind_groups[&(image.name.clone(), pixel_perfect)];
+                            render_pass.set_bind_group(1, bind_group, &[]);
+                            render_pass.draw(batch.vertices.clone(), 0..1);
+                            frame_stats.record_draw(batch.vertices.len() as u32 / 3);
+                        }
+                    }
+                }
+                sprite_renderer.draw_calls = batches.len();
+
+                command_buffer_queue
+                    .push(CommandQueueItem {
+                        buffer: encoder.finish(),
+                        name: "sprites".to_string(),
+                    })
+                    .unwrap();
+            },
+        )
+}
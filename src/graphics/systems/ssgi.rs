@@ -0,0 +1,192 @@
+use legion::prelude::*;
+use nalgebra_glm::Vec4;
+
+use crate::{
+    graphics::{
+        pipeline_manager::{Pipeline, PipelineManager},
+        pipelines::ssgi::SsgiResources,
+        renderer::DepthTexture,
+        resources::{CurrentRenderTarget, GPUResourceManager},
+        split_screen::is_view_camera,
+        CommandBufferQueue, CommandQueueItem, RenderSettings, ScreenSpaceGI, SplitScreen,
+    },
+    scene::components,
+};
+
+// Draws a full screen triangle with the SSGI bindings.
+fn ssgi_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    pipeline: &Pipeline,
+    bind_group: &wgpu::BindGroup,
+    target: &wgpu::TextureView,
+    resolve_target: Option<&wgpu::TextureView>,
+    load_op: wgpu::LoadOp,
+    viewport: Option<(f32, f32, f32, f32)>,
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+            attachment: target,
+            resolve_target,
+            load_op,
+            store_op: wgpu::StoreOp::Store,
+            clear_color: wgpu::Color::BLACK,
+        }],
+        depth_stencil_attachment: None,
+    });
+    if let Some((x, y, width, height)) = viewport {
+        render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+    }
+    render_pass.set_pipeline(&pipeline.render_pipeline);
+    render_pass.set_bind_group(0, bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+}
+
+/// Gathers the light bouncing off what the meshes drew at half resolution, blends it with last
+/// frame's and adds it to the frame. It reads the lit frame, so it runs after the meshes.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("ssgi")
+        .write_resource::<ScreenSpaceGI>()
+        .write_resource::<CommandBufferQueue>()
+        .read_resource::<PipelineManager>()
+        .read_resource::<GPUResourceManager>()
+        .read_resource::<SplitScreen>()
+        .read_resource::<SsgiResources>()
+        .read_resource::<RenderSettings>()
+        .read_resource::<CurrentRenderTarget>()
+        .read_resource::<DepthTexture>()
+        .read_resource::<wgpu::Device>()
+        .with_query(<(Read<components::CameraData>,)>::query())
+        .build(
+            |_,
+             world,
+             (
+                ssgi,
+                command_buffer_queue,
+                pipeline_manager,
+                resource_manager,
+                split_screen,
+                ssgi_resources,
+                render_settings,
+                current_render_target,
+                depth_texture,
+                device,
+            ),
+             cameras| {
+                if !ssgi.enabled || render_settings.reduced() {
+                    ssgi.reset_history();
+                    return;
+                }
+                // Probes don't use the main depth buffer.
+                if current_render_target.0.is_some() {
+                    return;
+                }
+
+                let (camera_entity, viewport) = split_screen.views()[0];
+                let camera = match cameras
+                    .iter_entities(&world)
+                    .find(|(entity, (camera,))| is_view_camera(camera_entity, *entity, camera))
+                {
+                    Some((_, (camera,))) => camera,
+                    None => return,
+                };
+                let ((frame, resolve_target), frame_size) = match (
+                    pipeline_manager.get_frame_target(),
+                    pipeline_manager.get_attachment_size("frame"),
+                ) {
+                    (Some(frame_target), Some(frame_size)) => (frame_target, frame_size),
+                    _ => return,
+                };
+                // With MSAA the lit frame has been resolved here.
+                let lit_frame = pipeline_manager.get_attachment("frame").unwrap();
+                // Gathered into a transient target and copied into the history afterwards.
+                let (target, history) = match (
+                    pipeline_manager.get_attachment("ssgi_gather"),
+                    pipeline_manager.get_attachment("ssgi_history"),
+                ) {
+                    (Some(target), Some(history)) => (target, history),
+                    _ => return,
+                };
+
+                let viewport = viewport.to_pixels(frame_size.0 as f32, frame_size.1 as f32);
+                let (width, height) = (frame_size.0 as f32, frame_size.1 as f32);
+                let view_projection = camera.get_matrix();
+                let uniform = ssgi.uniform(
+                    view_projection,
+                    camera.position,
+                    frame_size,
+                    Vec4::new(
+                        viewport.x / width,
+                        viewport.y / height,
+                        viewport.width / width,
+                        viewport.height / height,
+                    ),
+                );
+                let uniform_buffer = device.create_buffer_with_data(
+                    bytemuck::bytes_of(&uniform),
+                    wgpu::BufferUsage::UNIFORM,
+                );
+                let layout = resource_manager.get_bind_group_layout("ssgi").unwrap();
+                let create_bind_group = |color: &wgpu::TextureView, history: &wgpu::TextureView| {
+                    device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: Some("ssgi"),
+                        layout,
+                        bindings: &[
+                            wgpu::Binding {
+                                binding: 0,
+                                resource: wgpu::BindingResource::Buffer(uniform_buffer.slice(..)),
+                            },
+                            wgpu::Binding {
+                                binding: 1,
+                                resource: wgpu::BindingResource::TextureView(&depth_texture.0),
+                            },
+                            wgpu::Binding {
+                                binding: 2,
+                                resource: wgpu::BindingResource::TextureView(color),
+                            },
+                            wgpu::Binding {
+                                binding: 3,
+                                resource: wgpu::BindingResource::TextureView(history),
+                            },
+                            wgpu::Binding {
+                                binding: 4,
+                                resource: wgpu::BindingResource::Sampler(&ssgi_resources.sampler),
+                            },
+                        ],
+                    })
+                };
+
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("ssgi"),
+                });
+                ssgi_pass(
+                    &mut encoder,
+                    pipeline_manager.get("ssgi", None).unwrap(),
+                    &create_bind_group(lit_frame, history),
+                    target,
+                    None,
+                    wgpu::LoadOp::Clear,
+                    None,
+                );
+                ssgi_pass(
+                    &mut encoder,
+                    pipeline_manager.get("ssgi_composite", None).unwrap(),
+                    &create_bind_group(target, history),
+                    frame,
+                    resolve_target,
+                    wgpu::LoadOp::Load,
+                    Some((viewport.x, viewport.y, viewport.width, viewport.height)),
+                );
+                pipeline_manager.copy_attachment(&mut encoder, "ssgi_gather", "ssgi_history");
+
+                ssgi.previous_view_projection = Some(view_projection);
+                ssgi.frame = ssgi.frame.wrapping_add(1);
+
+                command_buffer_queue
+                    .push(CommandQueueItem {
+                        buffer: encoder.finish(),
+                        name: "ssgi".to_string(),
+                    })
+                    .unwrap();
+            },
+        )
+}
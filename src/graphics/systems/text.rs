@@ -0,0 +1,96 @@
+use crate::{
+    core::FrameStats,
+    graphics::{
+        capture::CaptureTarget,
+        pipeline_manager::PipelineManager,
+        renderer::DepthTexture,
+        resources::{GPUResourceManager, TextRenderer},
+        CommandBufferQueue, CommandQueueItem, FrameOutput,
+    },
+};
+use legion::prelude::*;
+use std::sync::Arc;
+
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("render_text")
+        .write_resource::<TextRenderer>()
+        .write_resource::<CommandBufferQueue>()
+        .read_resource::<GPUResourceManager>()
+        .read_resource::<PipelineManager>()
+        .read_resource::<wgpu::Device>()
+        .read_resource::<wgpu::SwapChainDescriptor>()
+        .read_resource::<DepthTexture>()
+        .read_resource::<Arc<FrameOutput>>()
+        .read_resource::<CaptureTarget>()
+        .read_resource::<FrameStats>()
+        .build(
+            |_,
+             _world,
+             (
+                text_renderer,
+                command_buffer_queue,
+                resource_manager,
+                pipeline_manager,
+                device,
+                sc_desc,
+                depth_texture,
+                output,
+                capture_target,
+                frame_stats,
+            ),
+             _| {
+                let _scope = frame_stats.scope("render_text");
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("text"),
+                });
+
+                let layout = resource_manager.get_bind_group_layout("text").unwrap();
+                text_renderer.process(
+                    &device,
+                    &mut encoder,
+                    layout,
+                    (sc_desc.width, sc_desc.height),
+                );
+
+                if let Some(instance_buffer) = text_renderer.instance_buffer.as_ref() {
+                    let depth_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: Some("text_depth"),
+                        layout: resource_manager.get_bind_group_layout("text_depth").unwrap(),
+                        bindings: &[wgpu::Binding {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&depth_texture.0),
+                        }],
+                    });
+                    let pipeline = pipeline_manager.get("text", None).unwrap();
+                    // Drawn over the tonemapped frame, and into the capture target with it.
+                    let targets = std::iter::once(output.view()).chain(capture_target.0.as_ref());
+                    for target in targets {
+                        let mut render_pass =
+                            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                                    attachment: target,
+                                    resolve_target: None,
+                                    load_op: wgpu::LoadOp::Load,
+                                    store_op: wgpu::StoreOp::Store,
+                                    clear_color: wgpu::Color::TRANSPARENT,
+                                }],
+                                depth_stencil_attachment: None,
+                            });
+                        render_pass.set_pipeline(&pipeline.render_pipeline);
+                        render_pass.set_bind_group(0, &text_renderer.bind_group, &[]);
+                        render_pass.set_bind_group(1, &depth_bind_group, &[]);
+                        render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+                        render_pass.draw(0..4, 0..text_renderer.instance_count);
+                    }
+                    frame_stats.record_draw(text_renderer.instance_count * 2);
+                }
+
+                command_buffer_queue
+                    .push(CommandQueueItem {
+                        buffer: encoder.finish(),
+                        name: "text".to_string(),
+                    })
+                    .unwrap();
+            },
+        )
+}
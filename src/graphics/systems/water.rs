@@ -0,0 +1,122 @@
+use legion::prelude::*;
+
+use crate::{
+    graphics::{
+        pipeline_manager::PipelineManager,
+        pipelines::water::CausticsResources,
+        renderer::DepthTexture,
+        resources::{CurrentRenderTarget, GPUResourceManager},
+        CommandBufferQueue, CommandQueueItem, SplitScreen,
+    },
+    scene::components,
+};
+
+/// Projects caustics onto everything the meshes drew inside a `WaterVolume`. It reads the
+/// depth buffer so it runs after the meshes and before the ocean covers them.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("caustics")
+        .write_resource::<CommandBufferQueue>()
+        .read_resource::<CausticsResources>()
+        .read_resource::<PipelineManager>()
+        .read_resource::<GPUResourceManager>()
+        .read_resource::<SplitScreen>()
+        .read_resource::<CurrentRenderTarget>()
+        .read_resource::<DepthTexture>()
+        .read_resource::<wgpu::Device>()
+        .with_query(<(Read<components::WaterVolume>,)>::query())
+        .build(
+            |_,
+             world,
+             (
+                command_buffer_queue,
+                caustics_resources,
+                pipeline_manager,
+                resource_manager,
+                split_screen,
+                current_render_target,
+                depth_texture,
+                device,
+            ),
+             water_volumes| {
+                // Probes don't use the main depth buffer.
+                if current_render_target.0.is_some() {
+                    return;
+                }
+                let has_caustics = water_volumes
+                    .iter(&world)
+                    .any(|(volume,)| volume.caustics_intensity > 0.0);
+                if !has_caustics {
+                    return;
+                }
+                let ((frame, resolve_target), frame_size) = match (
+                    pipeline_manager.get_frame_target(),
+                    pipeline_manager.get_attachment_size("frame"),
+                ) {
+                    (Some(frame_target), Some(frame_size)) => (frame_target, frame_size),
+                    _ => return,
+                };
+
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("caustics"),
+                });
+                let depth_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("caustics_depth"),
+                    layout: resource_manager
+                        .get_bind_group_layout("caustics_depth")
+                        .unwrap(),
+                    bindings: &[
+                        wgpu::Binding {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&depth_texture.0),
+                        },
+                        wgpu::Binding {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&caustics_resources.sampler),
+                        },
+                    ],
+                });
+                {
+                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                            attachment: frame,
+                            resolve_target,
+                            load_op: wgpu::LoadOp::Load,
+                            store_op: wgpu::StoreOp::Store,
+                            clear_color: wgpu::Color::BLACK,
+                        }],
+                        depth_stencil_attachment: None,
+                    });
+                    render_pass.set_pipeline(
+                        &pipeline_manager
+                            .get("caustics", None)
+                            .unwrap()
+                            .render_pipeline,
+                    );
+                    render_pass.set_bind_group(0, &depth_bind_group, &[]);
+                    for (player, viewport) in
+                        split_screen.pixel_viewports(frame_size).iter().enumerate()
+                    {
+                        if let Some(viewport) = viewport {
+                            render_pass.set_viewport(
+                                viewport.x,
+                                viewport.y,
+                                viewport.width,
+                                viewport.height,
+                                0.0,
+                                1.0,
+                            );
+                        }
+                        let (globals, _) = resource_manager.player_bind_groups(player);
+                        render_pass.set_bind_group(1, globals, &[]);
+                        render_pass.draw(0..3, 0..1);
+                    }
+                }
+                command_buffer_queue
+                    .push(CommandQueueItem {
+                        buffer: encoder.finish(),
+                        name: "caustics".to_string(),
+                    })
+                    .unwrap();
+            },
+        )
+}
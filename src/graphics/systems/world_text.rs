@@ -0,0 +1,74 @@
+use legion::prelude::*;
+use nalgebra_glm::Vec2;
+
+use crate::{
+    graphics::resources::{TextAlign, TextRenderer, TextSection},
+    scene::{components, resources::Visibility},
+};
+
+/// Projects `WorldText` components onto the screen and queues them with the text renderer.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("world_text")
+        .write_resource::<TextRenderer>()
+        .read_resource::<wgpu::SwapChainDescriptor>()
+        .read_resource::<Visibility>()
+        .with_query(<(Read<components::CameraData>,)>::query())
+        .with_query(<(Read<components::WorldText>, Read<components::Transform>)>::query())
+        .build(
+            |_, world, (text_renderer, sc_desc, visibility), (camera_query, world_text_query)| {
+                let camera = match camera_query.iter(&world).find(|(camera,)| camera.active) {
+                    Some((camera,)) => camera,
+                    None => return,
+                };
+                let screen_size = Vec2::new(sc_desc.width as f32, sc_desc.height as f32);
+
+                for (entity, (world_text, transform)) in world_text_query.iter_entities(&world) {
+                    if !visibility.is_visible(entity) {
+                        continue;
+                    }
+                    let position = transform.position + world_text.offset;
+                    let distance = nalgebra_glm::distance(&camera.position, &position);
+                    if world_text
+                        .max_distance
+                        .map_or(false, |max_distance| distance > max_distance)
+                    {
+                        continue;
+                    }
+
+                    let screen_position = match camera.world_to_screen(position, screen_size) {
+                        Some(screen_position) => screen_position,
+                        None => continue,
+                    };
+                    if screen_position.z > 1.0 {
+                        continue;
+                    }
+
+                    let size = match world_text.scale_distance {
+                        Some(scale_distance) => {
+                            world_text.size * scale_distance / distance.max(std::f32::EPSILON)
+                        }
+                        None => world_text.size,
+                    };
+
+                    // Sections are in UI pixels, the projection gives screen pixels.
+                    let position =
+                        Vec2::new(screen_position.x, screen_position.y) / text_renderer.scale();
+                    text_renderer.queue(&TextSection {
+                        text: world_text.text.clone(),
+                        font: world_text.font.clone(),
+                        position: Vec2::new(position.x, position.y - size * 0.5),
+                        size,
+                        color: world_text.color,
+                        // Screen text sits at depth zero so it's never hidden.
+                        z: if world_text.occluded {
+                            screen_position.z.max(0.0)
+                        } else {
+                            0.0
+                        },
+                        align: TextAlign::Center,
+                        ..TextSection::default()
+                    });
+                }
+            },
+        )
+}
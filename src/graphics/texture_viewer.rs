@@ -0,0 +1,514 @@
+use bytemuck::{Pod, Zeroable};
+use imgui::{im_str, Condition, ImString};
+use nalgebra_glm::Vec4;
+use std::sync::Mutex;
+
+use crate::{
+    graphics::{
+        material::{FallbackTexture, Image, Material},
+        pipeline_manager::PipelineManager,
+        resources::GPUResourceManager,
+        GpuReadback,
+    },
+    AssetManager,
+};
+
+/// Width and height the previews are rendered at.
+pub(crate) const PREVIEW_SIZE: u32 = 256;
+pub(crate) const PREVIEW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct PreviewUniform {
+    // (mip, decode sRGB, single channel or -1, unused)
+    settings: Vec4,
+    channels: Vec4,
+    color: Vec4,
+    info: Vec4,
+    emissive: Vec4,
+    uv_transform: Vec4,
+}
+
+unsafe impl Zeroable for PreviewUniform {}
+unsafe impl Pod for PreviewUniform {}
+
+// What a preview shows, it's only rendered again when this changes.
+#[derive(Debug, Clone, PartialEq)]
+enum PreviewKey {
+    Image {
+        name: String,
+        mip: u32,
+        channels: [bool; 4],
+        srgb: bool,
+        generation: u32,
+    },
+    Material {
+        index: u32,
+        generation: u32,
+    },
+}
+
+struct Preview {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    readback: Mutex<Option<(PreviewKey, GpuReadback)>>,
+    shown: Option<(PreviewKey, imgui::TextureId)>,
+}
+
+impl Preview {
+    fn new(device: &wgpu::Device, label: &str) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: PREVIEW_SIZE,
+                height: PREVIEW_SIZE,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: PREVIEW_FORMAT,
+            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+            label: Some(label),
+        });
+        Self {
+            view: texture.create_default_view(),
+            texture,
+            readback: Mutex::new(None),
+            shown: None,
+        }
+    }
+
+    // True if `key` is already shown or on it's way.
+    fn has(&self, key: &PreviewKey) -> bool {
+        let pending = self.readback.lock().unwrap();
+        self.shown.as_ref().map(|(shown, _)| shown) == Some(key)
+            || pending.as_ref().map(|(pending, _)| pending) == Some(key)
+    }
+
+    // Hands a finished readback to imgui.
+    fn poll(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &mut wgpu::Queue,
+        imgui_renderer: &mut imgui_wgpu::Renderer,
+    ) {
+        let mut readback = self.readback.lock().unwrap();
+        let data = match readback
+            .as_mut()
+            .and_then(|(_, readback)| readback.try_take())
+        {
+            Some(data) => data,
+            None => return,
+        };
+        let (key, _) = readback.take().unwrap();
+        match data {
+            // This version of imgui-wgpu can't free textures, so only previews that changed
+            // are uploaded again.
+            Ok(data) => {
+                let texture_id =
+                    imgui_renderer.upload_texture(device, queue, &data, PREVIEW_SIZE, PREVIEW_SIZE);
+                self.shown = Some((key, texture_id));
+            }
+            Err(error) => log::error!("Couldn't read back the texture preview: {:?}", error),
+        }
+    }
+}
+
+/// Debug widgets for checking imported assets without external tools, a resource.
+/// While it's enabled a "Textures" window shows any loaded image, with a mip level, a single
+/// channel or the raw sRGB values picked out, along with a lit sphere using a PBR material.
+/// ```ignore
+/// let mut texture_viewer = resources.get_mut::<TextureViewer>().unwrap();
+/// texture_viewer.enabled = true;
+/// texture_viewer.image = Some("rock_normal.png".to_string());
+/// ```
+pub struct TextureViewer {
+    pub enabled: bool,
+    /// The image to show, the first one by name if it's None.
+    pub image: Option<String>,
+    /// Levels past the image's last mip show it's smallest one.
+    pub mip: u32,
+    /// Which of red, green, blue and alpha are shown. With only one on it's shown in grey.
+    pub channels: [bool; 4],
+    /// Shows the colors shaders read, turn it off to see the stored values of linear images
+    /// as if they were sRGB. Handy for spotting a normal map that was imported as color.
+    pub srgb: bool,
+    /// Index of the PBR material drawn on the sphere, the first one if it's None.
+    pub material: Option<u32>,
+    sampler: wgpu::Sampler,
+    image_preview: Preview,
+    material_preview: Preview,
+}
+
+impl TextureViewer {
+    pub(crate) fn new(device: &wgpu::Device) -> Self {
+        // Point sampling so single texels can be told apart.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("texture_viewer"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: -100.0,
+            lod_max_clamp: 100.0,
+            compare: wgpu::CompareFunction::Undefined,
+        });
+        Self {
+            enabled: false,
+            image: None,
+            mip: 0,
+            channels: [true; 4],
+            srgb: true,
+            material: None,
+            sampler,
+            image_preview: Preview::new(device, "image_preview"),
+            material_preview: Preview::new(device, "material_preview"),
+        }
+    }
+
+    /// Renders the previews that changed and picks up the ones that finished, called before the
+    /// UI is drawn.
+    pub(crate) fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &mut wgpu::Queue,
+        asset_manager: &AssetManager,
+        pipeline_manager: &PipelineManager,
+        resource_manager: &GPUResourceManager,
+        imgui_renderer: &mut imgui_wgpu::Renderer,
+    ) {
+        self.image_preview.poll(device, queue, imgui_renderer);
+        self.material_preview.poll(device, queue, imgui_renderer);
+
+        let generation = asset_manager.bind_group_generation();
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("texture_viewer"),
+        });
+        let mut rendered = false;
+
+        let image = self
+            .image
+            .as_ref()
+            .and_then(|name| asset_manager.images.get(name))
+            .filter(|image| previewable(image));
+        if let Some(image) = image {
+            let key = PreviewKey::Image {
+                name: image.name.clone(),
+                mip: self.mip,
+                channels: self.channels,
+                srgb: self.srgb,
+                generation,
+            };
+            if !self.image_preview.has(&key) {
+                let shown: Vec<usize> = (0..4).filter(|i| self.channels[*i]).collect();
+                let single = if shown.len() == 1 {
+                    shown[0] as f32
+                } else {
+                    -1.0
+                };
+                let decode = !self.srgb && !is_srgb(image.format);
+                let mut channels = Vec4::zeros();
+                for i in shown {
+                    channels[i] = 1.0;
+                }
+                let uniform = PreviewUniform {
+                    settings: Vec4::new(self.mip as f32, decode as u32 as f32, single, 0.0),
+                    channels,
+                    color: Vec4::zeros(),
+                    info: Vec4::zeros(),
+                    emissive: Vec4::zeros(),
+                    uv_transform: Vec4::zeros(),
+                };
+                render(
+                    device,
+                    &mut encoder,
+                    pipeline_manager,
+                    resource_manager,
+                    "texture_preview",
+                    &self.image_preview,
+                    key,
+                    &image.view,
+                    &self.sampler,
+                    uniform,
+                );
+                rendered = true;
+            }
+        }
+
+        let material = asset_manager
+            .get_materials()
+            .into_iter()
+            .filter_map(|material| match material {
+                Material::PBR(data) => Some(data),
+                _ => None,
+            })
+            .find(|data| self.material.map_or(true, |index| index == data.index));
+        if let Some(material) = material {
+            let key = PreviewKey::Material {
+                index: material.index,
+                generation,
+            };
+            if !self.material_preview.has(&key) {
+                let main_texture = FallbackTexture::White
+                    .get_or_fallback(&asset_manager.images, &material.main_texture);
+                let material_uniform = material.uniform();
+                let uniform = PreviewUniform {
+                    settings: Vec4::zeros(),
+                    channels: Vec4::zeros(),
+                    color: material_uniform.color,
+                    info: material_uniform.info,
+                    emissive: material_uniform.emissive,
+                    uv_transform: material_uniform.uv_transform,
+                };
+                render(
+                    device,
+                    &mut encoder,
+                    pipeline_manager,
+                    resource_manager,
+                    "material_preview",
+                    &self.material_preview,
+                    key,
+                    &main_texture.view,
+                    &main_texture.sampler,
+                    uniform,
+                );
+                rendered = true;
+            }
+        }
+
+        if rendered {
+            queue.submit(Some(encoder.finish()));
+        }
+    }
+
+    pub(crate) fn draw_ui(&mut self, ui: &imgui::Ui<'_>, asset_manager: &AssetManager) {
+        let mut image_names: Vec<&String> = asset_manager.images.keys().collect();
+        image_names.sort();
+        if self.image.is_none() {
+            self.image = image_names.first().map(|name| name.to_string());
+        }
+        let mut materials: Vec<(u32, &String)> = asset_manager
+            .get_materials()
+            .into_iter()
+            .filter_map(|material| match material {
+                Material::PBR(data) => Some((data.index, &data.main_texture)),
+                _ => None,
+            })
+            .collect();
+        materials.sort();
+        if self.material.is_none() {
+            self.material = materials.first().map(|(index, _)| *index);
+        }
+
+        imgui::Window::new(im_str!("Textures"))
+            .size([300.0, 700.0], Condition::FirstUseEver)
+            .build(ui, || {
+                let labels: Vec<ImString> = image_names
+                    .iter()
+                    .map(|name| ImString::new(name.as_str()))
+                    .collect();
+                let labels: Vec<&ImString> = labels.iter().collect();
+                let mut current = image_names
+                    .iter()
+                    .position(|name| Some(*name) == self.image.as_ref())
+                    .unwrap_or(0);
+                if imgui::ComboBox::new(im_str!("Image")).build_simple_string(
+                    ui,
+                    &mut current,
+                    &labels,
+                ) {
+                    self.image = Some(image_names[current].clone());
+                    self.mip = 0;
+                }
+
+                let image = self
+                    .image
+                    .as_ref()
+                    .and_then(|name| asset_manager.images.get(name));
+                if let Some(image) = image {
+                    let mip_count = mip_count(image.extent);
+                    ui.text(im_str!(
+                        "{}x{}x{} {:?}",
+                        image.extent.width,
+                        image.extent.height,
+                        image.extent.depth,
+                        image.format
+                    ));
+                    imgui::Slider::new(im_str!("Mip"), 0..=mip_count - 1).build(ui, &mut self.mip);
+                    let labels = [im_str!("R"), im_str!("G"), im_str!("B"), im_str!("A")];
+                    for (i, label) in labels.iter().enumerate() {
+                        if i > 0 {
+                            ui.same_line(0.0);
+                        }
+                        ui.checkbox(label, &mut self.channels[i]);
+                    }
+                    ui.checkbox(im_str!("Decode sRGB"), &mut self.srgb);
+
+                    if !previewable(image) {
+                        ui.text(im_str!("Only 2D float textures can be previewed."));
+                    } else if let Some((_, texture_id)) = self.image_preview.shown.as_ref() {
+                        // Keeps the image's aspect ratio.
+                        let aspect = image.extent.height as f32 / image.extent.width as f32;
+                        let size = PREVIEW_SIZE as f32;
+                        let size = if aspect > 1.0 {
+                            [size / aspect, size]
+                        } else {
+                            [size, size * aspect]
+                        };
+                        imgui::Image::new(*texture_id, size).build(ui);
+                    }
+                }
+
+                ui.separator();
+                let labels: Vec<ImString> = materials
+                    .iter()
+                    .map(|(index, main_texture)| im_str!("{}: {}", index, main_texture))
+                    .collect();
+                let labels: Vec<&ImString> = labels.iter().collect();
+                let mut current = materials
+                    .iter()
+                    .position(|(index, _)| Some(*index) == self.material)
+                    .unwrap_or(0);
+                if imgui::ComboBox::new(im_str!("Material")).build_simple_string(
+                    ui,
+                    &mut current,
+                    &labels,
+                ) {
+                    self.material = Some(materials[current].0);
+                }
+                if let Some((_, texture_id)) = self.material_preview.shown.as_ref() {
+                    let size = PREVIEW_SIZE as f32;
+                    imgui::Image::new(*texture_id, [size, size]).build(ui);
+                }
+            });
+    }
+}
+
+// Draws a preview and starts reading it back.
+fn render(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    pipeline_manager: &PipelineManager,
+    resource_manager: &GPUResourceManager,
+    pipeline_name: &str,
+    preview: &Preview,
+    key: PreviewKey,
+    texture_view: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+    uniform: PreviewUniform,
+) {
+    let buffer =
+        device.create_buffer_with_data(bytemuck::bytes_of(&uniform), wgpu::BufferUsage::UNIFORM);
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("texture_viewer"),
+        layout: resource_manager
+            .get_bind_group_layout("texture_viewer")
+            .unwrap(),
+        bindings: &[
+            wgpu::Binding {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(buffer.slice(..)),
+            },
+            wgpu::Binding {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::Binding {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(texture_view),
+            },
+        ],
+    });
+    {
+        let pipeline = pipeline_manager.get(pipeline_name, None).unwrap();
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: &preview.view,
+                resolve_target: None,
+                load_op: wgpu::LoadOp::Clear,
+                store_op: wgpu::StoreOp::Store,
+                clear_color: wgpu::Color::BLACK,
+            }],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&pipeline.render_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+    let readback = GpuReadback::from_texture(
+        device,
+        encoder,
+        &preview.texture,
+        0,
+        wgpu::Origin3d::ZERO,
+        wgpu::Extent3d {
+            width: PREVIEW_SIZE,
+            height: PREVIEW_SIZE,
+            depth: 1,
+        },
+        4,
+    );
+    *preview.readback.lock().unwrap() = Some((key, readback));
+}
+
+// How many mips a full chain for `extent` has.
+fn mip_count(extent: wgpu::Extent3d) -> u32 {
+    32 - extent.width.max(extent.height).max(1).leading_zeros()
+}
+
+fn is_srgb(format: wgpu::TextureFormat) -> bool {
+    match format {
+        wgpu::TextureFormat::Rgba8UnormSrgb | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        _ => false,
+    }
+}
+
+// Arrays, cubemaps and integer or depth textures can't be bound as a regular 2D texture.
+fn previewable(image: &Image) -> bool {
+    let integer_or_depth = match image.format {
+        wgpu::TextureFormat::R8Uint
+        | wgpu::TextureFormat::R8Sint
+        | wgpu::TextureFormat::R16Uint
+        | wgpu::TextureFormat::R16Sint
+        | wgpu::TextureFormat::Rg8Uint
+        | wgpu::TextureFormat::Rg8Sint
+        | wgpu::TextureFormat::R32Uint
+        | wgpu::TextureFormat::R32Sint
+        | wgpu::TextureFormat::Rg16Uint
+        | wgpu::TextureFormat::Rg16Sint
+        | wgpu::TextureFormat::Rgba8Uint
+        | wgpu::TextureFormat::Rgba8Sint
+        | wgpu::TextureFormat::Rg32Uint
+        | wgpu::TextureFormat::Rg32Sint
+        | wgpu::TextureFormat::Rgba16Uint
+        | wgpu::TextureFormat::Rgba16Sint
+        | wgpu::TextureFormat::Rgba32Uint
+        | wgpu::TextureFormat::Rgba32Sint
+        | wgpu::TextureFormat::Depth32Float
+        | wgpu::TextureFormat::Depth24Plus
+        | wgpu::TextureFormat::Depth24PlusStencil8 => true,
+        _ => false,
+    };
+    image.extent.depth == 1
+        && image.usage.contains(wgpu::TextureUsage::SAMPLED)
+        && !integer_or_depth
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mip_count() {
+        let extent = |width, height| wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        };
+        assert_eq!(mip_count(extent(1, 1)), 1);
+        assert_eq!(mip_count(extent(256, 256)), 9);
+        assert_eq!(mip_count(extent(300, 17)), 9);
+    }
+}
@@ -0,0 +1,109 @@
+use bytemuck::Pod;
+
+// Uniform buffer offsets have to be a multiple of this.
+const ALIGNMENT: u64 = 256;
+const INITIAL_CAPACITY: u64 = 64 * 1024;
+
+/// Somewhere to put small uniforms that change every frame, a resource. Systems push their
+/// uniforms here instead of creating a buffer for each one, and they're uploaded together
+/// before the frame is submitted. There's a buffer per frame in flight so a frame's uniforms
+/// aren't overwritten while the GPU still reads them.
+/// ```ignore
+/// let offset = uniform_arena.push(&uniform).unwrap();
+/// let binding = uniform_arena.binding::<MyUniform>(offset);
+/// ```
+pub struct UniformArena {
+    buffers: Vec<wgpu::Buffer>,
+    capacity: u64,
+    slot: usize,
+    // This frame's uniforms, uploaded by `flush`.
+    data: Vec<u8>,
+    // Something didn't fit this frame, the buffers grow for the next one.
+    overflowed: bool,
+}
+
+impl UniformArena {
+    pub(crate) fn new(device: &wgpu::Device, frames_in_flight: u32) -> Self {
+        let count = frames_in_flight.max(1) as usize;
+        Self {
+            buffers: (0..count)
+                .map(|_| Self::create_buffer(device, INITIAL_CAPACITY))
+                .collect(),
+            capacity: INITIAL_CAPACITY,
+            slot: 0,
+            data: Vec::new(),
+            overflowed: false,
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, size: u64) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("uniform_arena"),
+            size,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        })
+    }
+
+    /// Adds a uniform to this frame and returns it's offset, None when the arena is full.
+    /// It grows for the next frame, until then create a buffer like before.
+    pub fn push<T: Pod>(&mut self, value: &T) -> Option<wgpu::BufferAddress> {
+        let offset = aligned(self.data.len() as u64);
+        let bytes = bytemuck::bytes_of(value);
+        if offset + bytes.len() as u64 > self.capacity {
+            self.overflowed = true;
+            return None;
+        }
+        self.data.resize(offset as usize, 0);
+        self.data.extend_from_slice(bytes);
+        Some(offset)
+    }
+
+    /// The part of this frame's buffer holding a `T` pushed at `offset`, for a bind group.
+    pub fn binding<T: Pod>(&self, offset: wgpu::BufferAddress) -> wgpu::BindingResource {
+        let size = std::mem::size_of::<T>() as wgpu::BufferAddress;
+        wgpu::BindingResource::Buffer(self.buffers[self.slot].slice(offset..offset + size))
+    }
+
+    // Copies this frame's uniforms into it's buffer.
+    pub(crate) fn flush(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+        if self.data.is_empty() {
+            return;
+        }
+        // Copies have to be a multiple of 4 bytes.
+        self.data.resize((self.data.len() + 3) & !3, 0);
+        let staging = device.create_buffer_with_data(&self.data, wgpu::BufferUsage::COPY_SRC);
+        let buffer = &self.buffers[self.slot];
+        encoder.copy_buffer_to_buffer(&staging, 0, buffer, 0, self.data.len() as u64);
+    }
+
+    // Moves on to the next frame's buffer.
+    pub(crate) fn begin_frame(&mut self, device: &wgpu::Device, slot: usize) {
+        self.data.clear();
+        self.slot = slot % self.buffers.len();
+        if self.overflowed {
+            self.overflowed = false;
+            self.capacity *= 2;
+            let count = self.buffers.len();
+            self.buffers = (0..count)
+                .map(|_| Self::create_buffer(device, self.capacity))
+                .collect();
+        }
+    }
+}
+
+fn aligned(offset: u64) -> u64 {
+    (offset + ALIGNMENT - 1) / ALIGNMENT * ALIGNMENT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alignment() {
+        assert_eq!(aligned(0), 0);
+        assert_eq!(aligned(1), 256);
+        assert_eq!(aligned(256), 256);
+        assert_eq!(aligned(300), 512);
+    }
+}
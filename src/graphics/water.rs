@@ -0,0 +1,76 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra_glm::{Vec3, Vec4};
+
+use crate::scene::components::WaterVolume;
+
+// This needs to match library/water.glsl.
+/// The most water volumes the shaders check, the rest are ignored.
+pub const MAX_WATER_VOLUMES: usize = 4;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct WaterVolumeUniform {
+    // The box, w in min is the caustics intensity and in max the wetness height.
+    box_min: Vec4,
+    box_max: Vec4,
+    // (caustics scale, caustics speed, unused..)
+    caustics: Vec4,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct WaterUniform {
+    volumes: [WaterVolumeUniform; MAX_WATER_VOLUMES],
+    // (volume count, time in seconds, unused..)
+    info: Vec4,
+}
+
+unsafe impl Zeroable for WaterVolumeUniform {}
+unsafe impl Pod for WaterVolumeUniform {}
+unsafe impl Zeroable for WaterUniform {}
+unsafe impl Pod for WaterUniform {}
+
+impl Default for WaterUniform {
+    fn default() -> Self {
+        Self::zeroed()
+    }
+}
+
+impl WaterUniform {
+    /// Takes the volumes with their world position, `time` animates the caustics.
+    pub(crate) fn new<'a>(
+        volumes: impl Iterator<Item = (Vec3, &'a WaterVolume)>,
+        time: f32,
+    ) -> Self {
+        let mut uniform = Self::zeroed();
+        let mut count = 0;
+        for (position, volume) in volumes.take(MAX_WATER_VOLUMES) {
+            let min = position - volume.extents;
+            let max = position + volume.extents;
+            uniform.volumes[count] = WaterVolumeUniform {
+                box_min: Vec4::new(min.x, min.y, min.z, volume.caustics_intensity),
+                box_max: Vec4::new(max.x, max.y, max.z, volume.wetness_height),
+                caustics: Vec4::new(volume.caustics_scale, volume.caustics_speed, 0.0, 0.0),
+            };
+            count += 1;
+        }
+        uniform.info = Vec4::new(count as f32, time, 0.0, 0.0);
+        uniform
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_water_volume_limit() {
+        let volume = WaterVolume::new(Vec3::new(1.0, 2.0, 1.0));
+        let volumes = (0..6).map(|i| (Vec3::new(i as f32, 0.0, 0.0), &volume));
+        let uniform = WaterUniform::new(volumes, 3.0);
+        assert_eq!(uniform.info.x, MAX_WATER_VOLUMES as f32);
+        assert_eq!(uniform.info.y, 3.0);
+        assert_eq!(uniform.volumes[1].box_min.x, 0.0);
+        assert_eq!(uniform.volumes[1].box_max.y, 2.0);
+    }
+}
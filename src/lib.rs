@@ -3,16 +3,24 @@
 #![allow(clippy::module_inception)]
 #![allow(clippy::too_many_arguments)]
 
+pub mod ai;
 pub mod core;
 pub mod graphics;
 pub mod scene;
 
+mod app_builder;
 mod application;
 mod assets;
+mod window_settings;
 mod winit_state;
 
+pub use app_builder::{AppBuilder, Stage};
 pub use application::{AppState, Application};
-pub use assets::AssetManager;
+pub use assets::{
+    build_pack, AssetError, AssetManager, AssetPack, AssetStats, Handle, LoadState, Migrations,
+    Severity, UploadBudget, ValidationIssue, ValidationReport, ValidationRules, VirtualFileSystem,
+};
+pub use window_settings::{WindowMode, WindowSettings};
 pub use winit_state::WinitState;
 
 pub struct TransformCount(u32);
@@ -0,0 +1,173 @@
+use legion::prelude::*;
+use nalgebra_glm::Vec3;
+
+use crate::{
+    graphics,
+    scene::components::{CameraData, LightType, Material, Mesh, Transform},
+    AssetManager,
+};
+
+/// A group of components that are commonly spawned together.
+/// Spawning many bundles at once puts them into the world with a single insert.
+pub trait Bundle: Sized {
+    /// Spawns every bundle and returns the new entities in the same order.
+    fn spawn_batch<I>(world: &mut World, bundles: I) -> Vec<Entity>
+    where
+        I: IntoIterator<Item = Self>;
+
+    /// Spawns a single bundle.
+    fn spawn(self, world: &mut World) -> Entity {
+        Self::spawn_batch(world, Some(self))[0]
+    }
+}
+
+/// Spawns a batch of bundles, same as calling `B::spawn_batch`.
+pub fn spawn_batch<B, I>(world: &mut World, bundles: I) -> Vec<Entity>
+where
+    B: Bundle,
+    I: IntoIterator<Item = B>,
+{
+    B::spawn_batch(world, bundles)
+}
+
+macro_rules! impl_bundle {
+    ($bundle:ty, $($field:ident),+) => {
+        impl Bundle for $bundle {
+            fn spawn_batch<I>(world: &mut World, bundles: I) -> Vec<Entity>
+            where
+                I: IntoIterator<Item = Self>,
+            {
+                let components: Vec<_> = bundles
+                    .into_iter()
+                    .map(|bundle| ($(bundle.$field,)+))
+                    .collect();
+                if components.is_empty() {
+                    return Vec::new();
+                }
+                world.insert((), components).to_vec()
+            }
+        }
+    };
+}
+
+/// A renderable mesh.
+pub struct MeshBundle {
+    pub mesh: Mesh,
+    pub material: Material,
+    pub transform: Transform,
+}
+
+impl MeshBundle {
+    pub fn new<T>(mesh_name: T, material_index: u32, transform: Transform) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            mesh: Mesh::new(mesh_name),
+            material: Material::new(material_index),
+            transform,
+        }
+    }
+}
+
+impl MeshBundle {
+    /// The name the ground plane's mesh is stored under in the asset manager.
+    pub const GROUND_PLANE: &'static str = "ground_plane";
+
+    /// A flat ground `size` units across at the origin, the plane mesh is added to the asset
+    /// manager the first time. `BuiltinMaterial::Grid` makes a good material for blocking out.
+    pub fn ground_plane(resources: &Resources, size: f32, material_index: u32) -> Self {
+        {
+            let mut asset_manager = resources.get_mut::<AssetManager>().unwrap();
+            if asset_manager.try_get_mesh(Self::GROUND_PLANE).is_err() {
+                let device = resources.get::<wgpu::Device>().unwrap();
+                let plane = graphics::mesh::Mesh::plane(1.0, 0);
+                asset_manager.insert_mesh(&device, Self::GROUND_PLANE, plane);
+            }
+        }
+        let mut transform = Transform::new_from_resources(resources);
+        transform.scale = Vec3::new(size, 1.0, size);
+        transform.update();
+        Self::new(Self::GROUND_PLANE, material_index, transform)
+    }
+}
+
+impl_bundle!(MeshBundle, mesh, material, transform);
+
+/// A camera, remember only the active camera is rendered from.
+pub struct CameraBundle {
+    pub camera: CameraData,
+}
+
+impl CameraBundle {
+    pub fn new(camera: CameraData) -> Self {
+        Self { camera }
+    }
+}
+
+impl_bundle!(CameraBundle, camera);
+
+/// A directional, point or spot light.
+pub struct LightBundle {
+    pub light: LightType,
+    pub transform: Transform,
+}
+
+impl LightBundle {
+    pub fn new(light: LightType, transform: Transform) -> Self {
+        Self { light, transform }
+    }
+}
+
+impl Bundle for LightBundle {
+    fn spawn_batch<I>(world: &mut World, bundles: I) -> Vec<Entity>
+    where
+        I: IntoIterator<Item = Self>,
+    {
+        // Each light type is it's own archetype, so insert them separately and restore the order afterwards.
+        let mut directional = Vec::new();
+        let mut point = Vec::new();
+        let mut spot = Vec::new();
+        let mut order = Vec::new();
+        for bundle in bundles {
+            match bundle.light {
+                LightType::Directional(data) => {
+                    order.push((0, directional.len()));
+                    directional.push((data, bundle.transform));
+                }
+                LightType::Point(data) => {
+                    order.push((1, point.len()));
+                    point.push((data, bundle.transform));
+                }
+                LightType::Spot(data) => {
+                    order.push((2, spot.len()));
+                    spot.push((data, bundle.transform));
+                }
+            }
+        }
+
+        let directional = if directional.is_empty() {
+            Vec::new()
+        } else {
+            world.insert((), directional).to_vec()
+        };
+        let point = if point.is_empty() {
+            Vec::new()
+        } else {
+            world.insert((), point).to_vec()
+        };
+        let spot = if spot.is_empty() {
+            Vec::new()
+        } else {
+            world.insert((), spot).to_vec()
+        };
+        order
+            .into_iter()
+            .map(|(light_type, index)| match light_type {
+                0 => directional[index],
+                1 => point[index],
+                _ => spot[index],
+            })
+            .collect()
+    }
+}
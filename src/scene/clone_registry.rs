@@ -0,0 +1,211 @@
+use legion::prelude::*;
+use std::{any::TypeId, sync::Arc};
+
+use crate::scene::components;
+
+type CloneFn<T> = Arc<dyn Fn(&T, &Resources) -> T + Send + Sync>;
+type CopyFn = Box<dyn Fn(&World, Entity) -> Option<Box<dyn ComponentSnapshot>> + Send + Sync>;
+
+/// A single copied component waiting to be pasted.
+trait ComponentSnapshot: Send + Sync {
+    /// Creates a new entity with just this component on it.
+    fn spawn(&self, world: &mut World, resources: &Resources) -> Entity;
+    /// Adds this component to an existing entity.
+    fn paste(&self, world: &mut World, resources: &Resources, entity: Entity);
+}
+
+struct Snapshot<T: Component> {
+    component: T,
+    clone: CloneFn<T>,
+}
+
+impl<T: Component> ComponentSnapshot for Snapshot<T> {
+    fn spawn(&self, world: &mut World, resources: &Resources) -> Entity {
+        world.insert((), vec![((self.clone)(&self.component, resources),)])[0]
+    }
+
+    fn paste(&self, world: &mut World, resources: &Resources, entity: Entity) {
+        let component = (self.clone)(&self.component, resources);
+        if world.add_component(entity, component).is_err() {
+            log::warn!(
+                "Clone Registry: Could not paste component onto {:?}",
+                entity
+            );
+        }
+    }
+}
+
+/// A copy of an entity and it's children that can be pasted any number of times.
+pub struct EntitySnapshot {
+    components: Vec<Box<dyn ComponentSnapshot>>,
+    children: Vec<EntitySnapshot>,
+}
+
+impl EntitySnapshot {
+    /// Pastes the snapshot into a world and returns the new root entity.
+    /// Returns None if none of the entity's components were registered with the `CloneRegistry`.
+    pub fn paste(&self, world: &mut World, resources: &Resources) -> Option<Entity> {
+        let (first, rest) = self.components.split_first()?;
+        let entity = first.spawn(world, resources);
+        for component in rest {
+            component.paste(world, resources, entity);
+        }
+
+        for child in self.children.iter() {
+            if let Some(child_entity) = child.paste(world, resources) {
+                let parent = components::Parent(entity);
+                if let Some(mut current) =
+                    world.get_component_mut::<components::Parent>(child_entity)
+                {
+                    *current = parent;
+                    continue;
+                }
+                world.add_component(child_entity, parent).ok();
+            }
+        }
+
+        Some(entity)
+    }
+}
+
+/// Keeps track of how to clone each component type so entities can be duplicated at runtime.
+/// Components that are not registered are skipped when copying.
+/// Engine components are registered by default, user components can be added with `register`.
+pub struct CloneRegistry {
+    types: Vec<TypeId>,
+    copy_fns: Vec<CopyFn>,
+}
+
+impl Default for CloneRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            types: Vec::new(),
+            copy_fns: Vec::new(),
+        };
+        registry.register::<components::Mesh>();
+        registry.register::<components::Material>();
+        // Instances get their own bind group once pasted.
+        registry.register_with::<components::MaterialInstance, _>(|instance, _| {
+            let mut instance = instance.clone();
+            instance.slot = None;
+            instance.uploaded = None;
+            instance
+        });
+        // Copies start with a fresh paint texture.
+        registry.register_with::<components::PaintTexture, _>(|paint, _| {
+            components::PaintTexture::new(paint.size)
+        });
+        registry.register_with::<components::Animator, _>(|animator, _| {
+            let mut animator = animator.clone();
+            animator.slot = None;
+            animator
+        });
+        registry.register::<components::Parent>();
+        registry.register::<components::Name>();
+        registry.register_with::<components::EntityId, _>(|_, _| components::EntityId::new());
+        registry.register::<components::Tags>();
+        registry.register::<components::WorldText>();
+        registry.register::<components::Visible>();
+        registry.register::<components::Static>();
+        registry.register::<components::ShadowFlags>();
+        registry.register::<components::WaterVolume>();
+        registry.register::<components::DeformableSurface>();
+        registry.register::<components::Deformer>();
+        registry.register::<crate::ai::Agent>();
+        registry.register::<components::DirectionalLightData>();
+        registry.register::<components::PointLightData>();
+        registry.register::<components::SpotLightData>();
+        // Transforms own GPU bindings so they need a fresh index.
+        registry.register_copy_with::<components::Transform, _, _>(
+            components::Transform::copy_with_index,
+            |transform, resources| transform.duplicate(resources),
+        );
+        registry
+    }
+}
+
+impl CloneRegistry {
+    /// Registers a component using it's `Clone` implementation.
+    pub fn register<T: Component + Clone>(&mut self) {
+        self.register_with::<T, _>(|component, _| component.clone());
+    }
+
+    /// Registers a component with a custom clone function that runs every time it's pasted.
+    /// Useful for components that reference GPU data or need to allocate something new.
+    pub fn register_with<T, F>(&mut self, clone: F)
+    where
+        T: Component + Clone,
+        F: Fn(&T, &Resources) -> T + Send + Sync + 'static,
+    {
+        self.register_copy_with(T::clone, clone);
+    }
+
+    /// Same as `register_with` for components that aren't `Clone`, `copy` makes the plain copy
+    /// that's kept around until pasting.
+    fn register_copy_with<T, C, F>(&mut self, copy: C, clone: F)
+    where
+        T: Component,
+        C: Fn(&T) -> T + Send + Sync + 'static,
+        F: Fn(&T, &Resources) -> T + Send + Sync + 'static,
+    {
+        let type_id = TypeId::of::<T>();
+        if self.types.contains(&type_id) {
+            log::warn!(
+                "Clone Registry: {} is already registered.",
+                std::any::type_name::<T>()
+            );
+            return;
+        }
+
+        let clone: CloneFn<T> = Arc::new(clone);
+        self.types.push(type_id);
+        self.copy_fns.push(Box::new(move |world, entity| {
+            // Keep a plain copy around, the registered clone function runs when pasting.
+            let component = copy(&*world.get_component::<T>(entity)?);
+            let snapshot: Box<dyn ComponentSnapshot> = Box::new(Snapshot {
+                component,
+                clone: clone.clone(),
+            });
+            Some(snapshot)
+        }));
+    }
+
+    /// Returns true if the component type can be cloned.
+    pub fn is_registered<T: Component>(&self) -> bool {
+        self.types.contains(&TypeId::of::<T>())
+    }
+
+    /// Copies an entity and all of it's children.
+    pub fn copy(&self, world: &World, entity: Entity) -> EntitySnapshot {
+        let components = self
+            .copy_fns
+            .iter()
+            .filter_map(|copy| copy(world, entity))
+            .collect();
+
+        let query = <Read<components::Parent>>::query();
+        let children: Vec<Entity> = query
+            .iter_entities(world)
+            .filter(|(_, parent)| parent.0 == entity)
+            .map(|(child, _)| child)
+            .collect();
+
+        EntitySnapshot {
+            components,
+            children: children
+                .into_iter()
+                .map(|child| self.copy(world, child))
+                .collect(),
+        }
+    }
+
+    /// Duplicates an entity and all of it's children, returning the new root entity.
+    pub fn duplicate(
+        &self,
+        world: &mut World,
+        resources: &Resources,
+        entity: Entity,
+    ) -> Option<Entity> {
+        self.copy(world, entity).paste(world, resources)
+    }
+}
@@ -0,0 +1,206 @@
+use legion::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{any::TypeId, collections::BTreeMap};
+
+use crate::scene::scene_desc::set;
+
+type SaveFn = Box<dyn Fn(&World, Entity) -> Option<Result<String, ron::ser::Error>> + Send + Sync>;
+type LoadFn =
+    Box<dyn Fn(&mut World, &mut Option<Entity>, &str) -> Result<(), ron::de::Error> + Send + Sync>;
+type RemoveFn = Box<dyn Fn(&mut World, Entity) + Send + Sync>;
+
+struct Registration {
+    name: String,
+    type_id: TypeId,
+    save: SaveFn,
+    load: LoadFn,
+    remove: RemoveFn,
+}
+
+/// Lets your own components go wherever the engine's do, a resource. Registered components are
+/// stored in `EntityDesc::components` by name, so they're saved with scenes, sent in
+/// `WorldDiff`s and can be read and edited as RON by inspectors like the remote debugger.
+/// ```ignore
+/// #[derive(Clone, Serialize, Deserialize)]
+/// struct Health(f32);
+///
+/// resources.get_mut::<ComponentRegistry>().unwrap().register::<Health>("health");
+/// ```
+#[derive(Default)]
+pub struct ComponentRegistry {
+    registrations: Vec<Registration>,
+}
+
+impl ComponentRegistry {
+    /// `name` is what scene files and diffs call the component, keep it the same once scenes
+    /// have been saved with it.
+    pub fn register<T>(&mut self, name: &str)
+    where
+        T: Component + Serialize + DeserializeOwned,
+    {
+        let type_id = TypeId::of::<T>();
+        if self
+            .registrations
+            .iter()
+            .any(|registration| registration.type_id == type_id || registration.name == name)
+        {
+            log::warn!(
+                "Component Registry: {} or the name {} is already registered.",
+                std::any::type_name::<T>(),
+                name
+            );
+            return;
+        }
+
+        self.registrations.push(Registration {
+            name: name.to_string(),
+            type_id,
+            save: Box::new(|world, entity| {
+                let component = world.get_component::<T>(entity)?;
+                Some(ron::ser::to_string(&*component))
+            }),
+            load: Box::new(|world, entity, source| {
+                let component: T = ron::de::from_str(source)?;
+                set(world, entity, component);
+                Ok(())
+            }),
+            remove: Box::new(|world, entity| {
+                let _ = world.remove_component::<T>(entity);
+            }),
+        });
+    }
+
+    pub fn is_registered<T: Component>(&self) -> bool {
+        let type_id = TypeId::of::<T>();
+        self.registrations
+            .iter()
+            .any(|registration| registration.type_id == type_id)
+    }
+
+    /// The names of every registered component.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.registrations
+            .iter()
+            .map(|registration| registration.name.as_str())
+    }
+
+    /// The entity's component called `name` as RON, None if it doesn't have one.
+    pub fn get_ron(&self, world: &World, entity: Entity, name: &str) -> Option<String> {
+        let registration = self.find(name)?;
+        match (registration.save)(world, entity)? {
+            Ok(source) => Some(source),
+            Err(error) => {
+                log::warn!("Component Registry: Couldn't save {}: {}", name, error);
+                None
+            }
+        }
+    }
+
+    /// Replaces the entity's component called `name` with one read from RON, or adds it.
+    /// This is how inspectors write their edits back.
+    pub fn set_ron(
+        &self,
+        world: &mut World,
+        entity: Entity,
+        name: &str,
+        source: &str,
+    ) -> Result<(), ron::de::Error> {
+        let registration = self.find(name).ok_or_else(|| {
+            ron::de::Error::Message(format!("{} isn't a registered component.", name))
+        })?;
+        (registration.load)(world, &mut Some(entity), source)
+    }
+
+    fn find(&self, name: &str) -> Option<&Registration> {
+        self.registrations
+            .iter()
+            .find(|registration| registration.name == name)
+    }
+
+    /// Every registered component the entity has, as RON by name.
+    pub(crate) fn save(&self, world: &World, entity: Entity) -> BTreeMap<String, String> {
+        self.registrations
+            .iter()
+            .filter_map(|registration| {
+                let source = self.get_ron(world, entity, &registration.name)?;
+                Some((registration.name.clone(), source))
+            })
+            .collect()
+    }
+
+    /// Sets the components in `components`, spawning the entity if it's None, and removes the
+    /// registered ones that aren't in it. Components that fail to load are skipped.
+    pub(crate) fn load(
+        &self,
+        world: &mut World,
+        entity: &mut Option<Entity>,
+        components: &BTreeMap<String, String>,
+    ) {
+        for registration in self.registrations.iter() {
+            match components.get(&registration.name) {
+                Some(source) => {
+                    if let Err(error) = (registration.load)(world, entity, source) {
+                        log::warn!(
+                            "Component Registry: Couldn't load {}: {}",
+                            registration.name,
+                            error
+                        );
+                    }
+                }
+                None => {
+                    if let Some(entity) = *entity {
+                        (registration.remove)(world, entity);
+                    }
+                }
+            }
+        }
+        for name in components.keys() {
+            if self.find(name).is_none() {
+                log::warn!(
+                    "Component Registry: {} isn't registered, it's skipped.",
+                    name
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Health {
+        current: f32,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut registry = ComponentRegistry::default();
+        registry.register::<Health>("health");
+        registry.register::<Health>("again");
+        assert_eq!(registry.names().collect::<Vec<_>>(), vec!["health"]);
+
+        let mut world = Universe::new().create_world();
+        let entity = world.insert((), vec![(Health { current: 5.0 },)])[0];
+        let saved = registry.save(&world, entity);
+        assert_eq!(saved.len(), 1);
+
+        let mut copy = None;
+        registry.load(&mut world, &mut copy, &saved);
+        let copy = copy.unwrap();
+        assert_eq!(
+            *world.get_component::<Health>(copy).unwrap(),
+            Health { current: 5.0 }
+        );
+
+        registry
+            .set_ron(&mut world, copy, "health", "(current: 2.0)")
+            .unwrap();
+        assert_eq!(world.get_component::<Health>(copy).unwrap().current, 2.0);
+        assert!(registry.set_ron(&mut world, copy, "armor", "()").is_err());
+        registry.load(&mut world, &mut Some(copy), &BTreeMap::new());
+        assert!(world.get_component::<Health>(copy).is_none());
+    }
+}
@@ -0,0 +1,53 @@
+use nalgebra_glm::Mat4;
+
+/// Plays animation clips on an entity with a skinned `Mesh`.
+/// Remember to add `scene::systems::animation::create()` to your scene's schedule.
+#[derive(Debug, Clone)]
+pub struct Animator {
+    /// The name of the clip that's playing, clips are stored on the mesh.
+    pub clip: Option<String>,
+    /// Current time in seconds.
+    pub time: f32,
+    pub speed: f32,
+    pub looping: bool,
+    pub playing: bool,
+    // The skinning matrices from the last update, uploaded by the skinning render system.
+    pub(crate) joint_matrices: Vec<Mat4>,
+    // The transform index the joint buffer and bind group are stored under.
+    pub(crate) slot: Option<u32>,
+}
+
+impl Default for Animator {
+    fn default() -> Self {
+        Self {
+            clip: None,
+            time: 0.0,
+            speed: 1.0,
+            looping: true,
+            playing: false,
+            joint_matrices: Vec::new(),
+            slot: None,
+        }
+    }
+}
+
+impl Animator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts playing a clip from the beginning.
+    pub fn play<T: Into<String>>(&mut self, clip: T) {
+        self.clip = Some(clip.into());
+        self.time = 0.0;
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn resume(&mut self) {
+        self.playing = true;
+    }
+}
@@ -0,0 +1,158 @@
+use nalgebra_glm::Vec3;
+use winit::event::VirtualKeyCode;
+
+use crate::core::input::MouseButton;
+
+/// Moves the entity's `CameraData` like a free flying editor camera, WASD to move, Q and E to
+/// go down and up and hold shift to go faster. It only moves while the camera is active.
+/// Remember to add `scene::systems::camera_controller::create()` to your scene's schedule.
+#[derive(Debug, Clone)]
+pub struct FlyCamera {
+    /// Units per second.
+    pub speed: f32,
+    /// Multiplies the speed while shift is held.
+    pub fast_multiplier: f32,
+    /// Radians per pixel the mouse moves.
+    pub sensitivity: f32,
+    /// The camera only turns while this is held, None always turns.
+    pub look_button: Option<MouseButton>,
+}
+
+impl Default for FlyCamera {
+    fn default() -> Self {
+        Self {
+            speed: 5.0,
+            fast_multiplier: 4.0,
+            sensitivity: 0.003,
+            look_button: Some(MouseButton::Right),
+        }
+    }
+}
+
+impl FlyCamera {
+    pub fn new(speed: f32) -> Self {
+        Self {
+            speed,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_look_button(mut self, look_button: Option<MouseButton>) -> Self {
+        self.look_button = look_button;
+        self
+    }
+
+    /// Which way the keys push the camera, x is right, y is up and z is forward.
+    pub(crate) fn key_direction(is_down: impl Fn(VirtualKeyCode) -> bool) -> Vec3 {
+        let axis = |negative, positive| {
+            let mut value = 0.0;
+            if is_down(negative) {
+                value -= 1.0;
+            }
+            if is_down(positive) {
+                value += 1.0;
+            }
+            value
+        };
+        Vec3::new(
+            axis(VirtualKeyCode::A, VirtualKeyCode::D),
+            axis(VirtualKeyCode::Q, VirtualKeyCode::E),
+            axis(VirtualKeyCode::S, VirtualKeyCode::W),
+        )
+    }
+}
+
+/// Circles the entity's `CameraData` around a target, drag to orbit and use the mouse wheel to
+/// zoom. It only moves while the camera is active and is ignored if the camera has a `FlyCamera`.
+/// Remember to add `scene::systems::camera_controller::create()` to your scene's schedule.
+#[derive(Debug, Clone)]
+pub struct OrbitCamera {
+    pub target: Vec3,
+    pub distance: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Radians per pixel the mouse moves.
+    pub sensitivity: f32,
+    /// How much of the distance one line of the mouse wheel zooms.
+    pub zoom_speed: f32,
+    /// The camera only orbits while this is held, None always orbits.
+    pub orbit_button: Option<MouseButton>,
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self {
+            target: Vec3::zeros(),
+            distance: 5.0,
+            min_distance: 0.5,
+            max_distance: 100.0,
+            sensitivity: 0.005,
+            zoom_speed: 0.1,
+            orbit_button: Some(MouseButton::Left),
+        }
+    }
+}
+
+impl OrbitCamera {
+    pub fn new(target: Vec3, distance: f32) -> Self {
+        Self {
+            target,
+            distance,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_distance_range(mut self, min_distance: f32, max_distance: f32) -> Self {
+        self.min_distance = min_distance;
+        self.max_distance = max_distance;
+        self
+    }
+
+    pub fn with_orbit_button(mut self, orbit_button: Option<MouseButton>) -> Self {
+        self.orbit_button = orbit_button;
+        self
+    }
+
+    /// Where the camera sits when it's looking at the target with this yaw and pitch.
+    pub fn eye(&self, yaw: f32, pitch: f32) -> Vec3 {
+        self.target - look_direction(yaw, pitch) * self.distance
+    }
+
+    /// Zooms in for positive lines, keeping the distance in range.
+    pub(crate) fn zoom(&mut self, lines: f32) {
+        let distance = self.distance * (1.0 - self.zoom_speed).powf(lines);
+        self.distance = distance.max(self.min_distance).min(self.max_distance);
+    }
+}
+
+/// The direction a camera with this yaw and pitch looks in, both in radians.
+/// Yaw 0 looks down -z, positive yaw turns left and positive pitch looks up.
+pub fn look_direction(yaw: f32, pitch: f32) -> Vec3 {
+    Vec3::new(
+        -yaw.sin() * pitch.cos(),
+        pitch.sin(),
+        -yaw.cos() * pitch.cos(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orbit_camera() {
+        let mut orbit = OrbitCamera::new(Vec3::new(1.0, 0.0, 0.0), 2.0);
+        assert!(nalgebra_glm::distance(&orbit.eye(0.0, 0.0), &Vec3::new(1.0, 0.0, 2.0)) < 1e-5);
+        // Looking down from above.
+        let eye = orbit.eye(0.0, -std::f32::consts::FRAC_PI_2);
+        assert!(nalgebra_glm::distance(&eye, &Vec3::new(1.0, 2.0, 0.0)) < 1e-5);
+
+        orbit.zoom(1.0);
+        assert!((orbit.distance - 1.8).abs() < 1e-5);
+        orbit.zoom(-100.0);
+        assert_eq!(orbit.distance, orbit.max_distance);
+
+        let direction = FlyCamera::key_direction(|key| key == VirtualKeyCode::W);
+        assert_eq!(direction, Vec3::new(0.0, 0.0, 1.0));
+    }
+}
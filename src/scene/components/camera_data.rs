@@ -1,6 +1,22 @@
-use nalgebra_glm::{Mat4, Vec3};
+use legion::prelude::*;
+use nalgebra_glm::{Mat4, Vec2, Vec3, Vec4};
 
-enum ProjectionData {
+use crate::graphics::{ProjectionWarp, Tonemapper, Viewport};
+
+/// Which way a perspective camera's field of view is measured.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FovAxis {
+    /// Wider screens see more to the sides, the default.
+    Vertical,
+    /// Wider screens see less above and below, racing games like this so the track always
+    /// fills the same width.
+    Horizontal,
+}
+
+// Rectilinear projections break down at 180 degrees, past this use a `ProjectionWarp`.
+const MAX_FOV: f32 = 170.0;
+
+pub(crate) enum ProjectionData {
     Perspective {
         fov: f32,
         z_near: f32,
@@ -14,10 +30,33 @@ enum ProjectionData {
 }
 
 impl ProjectionData {
+    fn depth_range(&self) -> (f32, f32) {
+        match self {
+            ProjectionData::Perspective { z_near, z_far, .. } => (*z_near, *z_far),
+            ProjectionData::Orthographic { z_near, z_far, .. } => (*z_near, *z_far),
+        }
+    }
+
+    /// The vertical field of view in degrees at this aspect ratio, None for orthographic.
+    fn vertical_fov(&self, axis: FovAxis, width: f32, height: f32) -> Option<f32> {
+        match (self, axis) {
+            (ProjectionData::Perspective { fov, .. }, FovAxis::Vertical) => {
+                Some(fov.max(1.0).min(MAX_FOV))
+            }
+            (ProjectionData::Perspective { fov, .. }, FovAxis::Horizontal) => {
+                let aspect = width.max(1.0) / height.max(1.0);
+                let half = (fov.max(1.0).min(MAX_FOV).to_radians() * 0.5).tan() / aspect;
+                Some((2.0 * half.atan()).to_degrees().max(1.0).min(MAX_FOV))
+            }
+            (ProjectionData::Orthographic { .. }, _) => None,
+        }
+    }
+
     /// get_projection calculates a new projection for the specified viewport width & height. TODO: Div by 0 possible for orthographic.
-    fn get_projection(&self, width: f32, height: f32) -> Mat4 {
+    fn get_projection(&self, axis: FovAxis, width: f32, height: f32) -> Mat4 {
         match self {
-            ProjectionData::Perspective { fov, z_near, z_far } => {
+            ProjectionData::Perspective { z_near, z_far, .. } => {
+                let fov = self.vertical_fov(axis, width, height).unwrap();
                 nalgebra_glm::perspective_fov_rh_no(
                     fov.to_radians(),
                     width,
@@ -44,7 +83,10 @@ impl ProjectionData {
 
 /// CameraData holds all necessary data to calculate the cameras matrices
 /// and offers basic constructors.
+/// Cameras are resized to fit their view when the window resizes.
 pub struct CameraData {
+    /// The camera everything is rendered from when the screen isn't split, there should only
+    /// be one, see `CameraData::make_active`.
     pub active: bool,
     pub position: Vec3,
     pub projection: Mat4,
@@ -53,6 +95,24 @@ pub struct CameraData {
     pub pitch: f32,
     pub width: f32,
     pub height: f32,
+    /// Field of view in degrees used to draw `ViewModel` entities.
+    pub view_model_fov: f32,
+    /// Near and far planes used to draw `ViewModel` entities.
+    pub view_model_depth_range: (f32, f32),
+    /// Part of the screen this camera draws to in the 0..1 range, None uses the whole screen or
+    /// the player's part of a `SplitScreen`. Views are drawn one pass at a time so split screen
+    /// viewports mustn't overlap.
+    pub viewport: Option<Viewport>,
+    /// Replaces `PostProcessing::exposure` while this camera is the one on screen, for
+    /// cutscene cameras and dark rooms. With split screen the first player's camera decides.
+    pub exposure: Option<f32>,
+    /// Replaces `PostProcessing::tonemapper` like `exposure`.
+    pub tonemapper: Option<Tonemapper>,
+    /// Warps the rendered image, for very wide fields of view. It's applied to the whole frame
+    /// so it's meant for cameras that fill the screen, and `world_to_screen` doesn't include it.
+    pub warp: Option<ProjectionWarp>,
+    /// Change it with `with_fov_axis` so the projection is updated.
+    pub fov_axis: FovAxis,
     projection_data: ProjectionData,
 }
 
@@ -72,11 +132,21 @@ impl Default for CameraData {
             yaw: 0.0,
             width: 0.0,
             height: 0.0,
+            view_model_fov: Self::VIEW_MODEL_FOV,
+            view_model_depth_range: Self::VIEW_MODEL_DEPTH_RANGE,
+            viewport: None,
+            exposure: None,
+            tonemapper: None,
+            warp: None,
+            fov_axis: FovAxis::Vertical,
         }
     }
 }
 
 impl CameraData {
+    const VIEW_MODEL_FOV: f32 = 60.0;
+    const VIEW_MODEL_DEPTH_RANGE: (f32, f32) = (0.01, 10.0);
+
     /// new_perspective constructs a new Perspective Camera
     ///
     /// # Arguments
@@ -93,11 +163,18 @@ impl CameraData {
             height,
             pitch: 0.0,
             position: Vec3::zeros(),
-            projection: projection_data.get_projection(width, height),
+            projection: projection_data.get_projection(FovAxis::Vertical, width, height),
             projection_data,
             view: Mat4::identity(),
             width,
             yaw: 0.0,
+            view_model_fov: Self::VIEW_MODEL_FOV,
+            view_model_depth_range: Self::VIEW_MODEL_DEPTH_RANGE,
+            viewport: None,
+            exposure: None,
+            tonemapper: None,
+            warp: None,
+            fov_axis: FovAxis::Vertical,
         }
     }
 
@@ -128,17 +205,93 @@ impl CameraData {
             height,
             pitch: 0.0,
             position: Vec3::zeros(),
-            projection: projection_data.get_projection(width, height),
+            projection: projection_data.get_projection(FovAxis::Vertical, width, height),
             projection_data,
             view: Mat4::identity(),
             width,
             yaw: 0.0,
+            view_model_fov: Self::VIEW_MODEL_FOV,
+            view_model_depth_range: Self::VIEW_MODEL_DEPTH_RANGE,
+            viewport: None,
+            exposure: None,
+            tonemapper: None,
+            warp: None,
+            fov_axis: FovAxis::Vertical,
+        }
+    }
+
+    /// An orthographic camera for 2D scenes and sprites, one world unit is a pixel at the
+    /// viewport size it's created with and (0, 0) is the middle of the screen.
+    pub fn new_2d(width: f32, height: f32) -> Self {
+        // Looks down -z from the origin, so sprites on either side of z = 0 are still visible.
+        Self::new_orthographic(height, width, height, -1000.0, 1000.0)
+    }
+
+    pub fn with_viewport(mut self, viewport: Viewport) -> Self {
+        self.viewport = Some(viewport);
+        self
+    }
+
+    pub fn with_exposure(mut self, exposure: f32) -> Self {
+        self.exposure = Some(exposure);
+        self
+    }
+
+    pub fn with_tonemapper(mut self, tonemapper: Tonemapper) -> Self {
+        self.tonemapper = Some(tonemapper);
+        self
+    }
+
+    pub fn with_warp(mut self, warp: ProjectionWarp) -> Self {
+        self.warp = Some(warp);
+        self
+    }
+
+    pub fn with_fov_axis(mut self, fov_axis: FovAxis) -> Self {
+        self.fov_axis = fov_axis;
+        if self.width > 0.0 && self.height > 0.0 {
+            self.projection = self
+                .projection_data
+                .get_projection(fov_axis, self.width, self.height);
+        }
+        self
+    }
+
+    /// The vertical field of view in degrees after `fov_axis` is applied, None for
+    /// orthographic cameras.
+    pub fn vertical_fov(&self) -> Option<f32> {
+        self.projection_data
+            .vertical_fov(self.fov_axis, self.width, self.height)
+    }
+
+    /// Makes `camera` the active camera and deactivates every other camera.
+    pub fn make_active(world: &mut World, camera: Entity) {
+        let query = <(Write<CameraData>,)>::query();
+        for (entity, (mut camera_data,)) in query.iter_entities_mut(world) {
+            camera_data.active = entity == camera;
         }
     }
 
-    /// resize recalculates the projection matrix. Needs to be called on window resize
+    /// resize recalculates the projection matrix, the split screen system calls this when the
+    /// window or the camera's viewport changes size.
     pub fn resize(&mut self, width: f32, height: f32) {
-        self.projection = self.projection_data.get_projection(width, height);
+        self.width = width;
+        self.height = height;
+        self.projection = self
+            .projection_data
+            .get_projection(self.fov_axis, width, height);
+    }
+
+    /// The projection used to draw `ViewModel` entities, always perspective.
+    pub fn view_model_projection(&self) -> Mat4 {
+        let (z_near, z_far) = self.view_model_depth_range;
+        nalgebra_glm::perspective_fov_rh_no(
+            self.view_model_fov.to_radians(),
+            self.width.max(1.0),
+            self.height.max(1.0),
+            z_near,
+            z_far,
+        )
     }
 
     /// updates the view matrix. Needs to be called when the camera moved
@@ -146,15 +299,107 @@ impl CameraData {
         self.view = nalgebra_glm::look_at_rh(&eye, &at, &up);
     }
 
+    pub(crate) fn projection_data(&self) -> &ProjectionData {
+        &self.projection_data
+    }
+
+    /// returns the distance to the near and far clipping planes
+    pub fn depth_range(&self) -> (f32, f32) {
+        self.projection_data.depth_range()
+    }
+
     /// returns the view-projection matrix
     pub fn get_matrix(&self) -> Mat4 {
         self.projection * self.view
     }
+
+    /// Projects a world position onto the screen.
+    /// Returns the position in pixels (origin top left) and the depth in z,
+    /// or None if the position is behind the camera.
+    pub fn world_to_screen(&self, position: Vec3, screen_size: Vec2) -> Option<Vec3> {
+        let clip = self.get_matrix() * Vec4::new(position.x, position.y, position.z, 1.0);
+        if clip.w <= 0.0 {
+            return None;
+        }
+        let ndc = clip.xyz() / clip.w;
+        Some(Vec3::new(
+            (ndc.x * 0.5 + 0.5) * screen_size.x,
+            (0.5 - ndc.y * 0.5) * screen_size.y,
+            ndc.z,
+        ))
+    }
+
+    /// Turns a screen position (in pixels) and depth back into a world position.
+    pub fn screen_to_world(&self, screen_position: Vec2, depth: f32, screen_size: Vec2) -> Vec3 {
+        let ndc = Vec4::new(
+            screen_position.x / screen_size.x * 2.0 - 1.0,
+            1.0 - screen_position.y / screen_size.y * 2.0,
+            depth,
+            1.0,
+        );
+        let world = nalgebra_glm::inverse(&self.get_matrix()) * ndc;
+        world.xyz() / world.w
+    }
+
+    /// Returns a ray (origin, direction) going from the camera through a screen position.
+    pub fn screen_to_ray(&self, screen_position: Vec2, screen_size: Vec2) -> (Vec3, Vec3) {
+        // Our projections map depth from -1 to 1.
+        let near = self.screen_to_world(screen_position, -1.0, screen_size);
+        let far = self.screen_to_world(screen_position, 1.0, screen_size);
+        (near, (far - near).normalize())
+    }
+
+    /// Projects a world position onto the screen, keeping it inside the screen's edges.
+    /// Useful for waypoint markers that should point towards something that is off screen.
+    /// Returns the position in pixels and if the position was actually on screen.
+    pub fn world_to_screen_clamped(
+        &self,
+        position: Vec3,
+        screen_size: Vec2,
+        margin: f32,
+    ) -> (Vec2, bool) {
+        let clip = self.get_matrix() * Vec4::new(position.x, position.y, position.z, 1.0);
+        let ndc = clip.xy() / clip.w.abs().max(std::f32::EPSILON);
+        if clip.w > 0.0 && ndc.x.abs() <= 1.0 && ndc.y.abs() <= 1.0 {
+            let screen_position = Vec2::new(
+                (ndc.x * 0.5 + 0.5) * screen_size.x,
+                (0.5 - ndc.y * 0.5) * screen_size.y,
+            );
+            return (screen_position, true);
+        }
+
+        // Things behind the camera end up mirrored so flip them back.
+        let direction = if clip.w > 0.0 { ndc } else { -ndc };
+        let half_size = screen_size * 0.5;
+        let offset = Vec2::new(direction.x * half_size.x, -direction.y * half_size.y);
+        let bounds = Vec2::new(
+            (half_size.x - margin).max(0.0),
+            (half_size.y - margin).max(0.0),
+        );
+        let scale_x = if offset.x != 0.0 {
+            bounds.x / offset.x.abs()
+        } else {
+            std::f32::MAX
+        };
+        let scale_y = if offset.y != 0.0 {
+            bounds.y / offset.y.abs()
+        } else {
+            std::f32::MAX
+        };
+        let scale = scale_x.min(scale_y);
+        if scale == std::f32::MAX {
+            // Straight behind the camera, there isn't a good edge to pick.
+            return (Vec2::new(half_size.x, screen_size.y - margin), false);
+        }
+
+        (half_size + offset * scale, false)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::CameraData;
+    use super::{CameraData, FovAxis};
+    use nalgebra_glm::{Vec2, Vec3};
     ///just tests for projection matrix calculation
     #[test]
     fn test_perspective_projection() {
@@ -167,6 +412,17 @@ mod tests {
             nalgebra_glm::perspective_fov_rh_no(fov, width, height, z_near, z_far)
         );
     }
+    #[test]
+    fn test_horizontal_fov() {
+        let camera_data = CameraData::new_perspective(90.0, 1600.0, 800.0, 0.1, 100.0)
+            .with_fov_axis(FovAxis::Horizontal);
+        // tan(45°) / 2 = tan(vertical / 2)
+        let expected = (2.0 * 0.5f32.atan()).to_degrees();
+        assert!((camera_data.vertical_fov().unwrap() - expected).abs() < 0.001);
+        let wide = CameraData::new_perspective(200.0, 800.0, 600.0, 0.1, 100.0);
+        assert_eq!(wide.vertical_fov(), Some(170.0));
+    }
+
     ///just tests for projection matrix calculation
     #[test]
     fn test_orthographic_projection() {
@@ -188,4 +444,32 @@ mod tests {
             )
         );
     }
+
+    ///checks that projecting to the screen and back ends up at the same position
+    #[test]
+    fn test_world_to_screen_round_trip() {
+        let screen_size = Vec2::new(800.0, 600.0);
+        let mut camera_data = CameraData::new_perspective(70.0, 800.0, 600.0, 0.1, 100.0);
+        camera_data.update_view(
+            Vec3::new(0.0, 0.0, 5.0),
+            Vec3::zeros(),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+
+        let position = Vec3::new(1.0, 0.5, -2.0);
+        let screen = camera_data.world_to_screen(position, screen_size).unwrap();
+        let world = camera_data.screen_to_world(screen.xy(), screen.z, screen_size);
+        assert!(nalgebra_glm::distance(&position, &world) < 0.001);
+
+        // The origin is straight ahead so it should be in the middle of the screen.
+        let center = camera_data
+            .world_to_screen(Vec3::zeros(), screen_size)
+            .unwrap();
+        assert!((center.x - 400.0).abs() < 0.001 && (center.y - 300.0).abs() < 0.001);
+
+        let behind = Vec3::new(0.0, 0.0, 10.0);
+        assert!(camera_data.world_to_screen(behind, screen_size).is_none());
+        let (_, on_screen) = camera_data.world_to_screen_clamped(behind, screen_size, 10.0);
+        assert!(!on_screen);
+    }
 }
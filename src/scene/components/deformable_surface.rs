@@ -0,0 +1,69 @@
+use nalgebra_glm::{Vec2, Vec4};
+
+/// Snow, sand or mud that entities with a `Deformer` leave tracks in.
+/// A heightfield covering `size` world units around the entity's `Transform` on the xz plane
+/// records how deep the surface was pressed in, the mesh's vertices are pushed down by it and
+/// tinted with `trampled_color` through their vertex colors. Use a mesh with enough vertices
+/// for the tracks to show, like `Mesh::grid`.
+/// The entity gets it's own copy of it's `Mesh`, the copy is made on the first frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeformableSurface {
+    /// Width and depth of the area that can be deformed, in world units.
+    pub size: Vec2,
+    /// Width and height of the heightfield texture, changing it after the first frame does
+    /// nothing.
+    pub resolution: u32,
+    /// How deep tracks can get, the thickness of the snow.
+    pub max_depth: f32,
+    /// How fast tracks fill back in, in world units per second. 0 keeps them forever.
+    pub recovery: f32,
+    /// Multiplied with the material's color where the surface is pressed all the way down.
+    pub trampled_color: Vec4,
+}
+
+impl DeformableSurface {
+    pub fn new(size: Vec2) -> Self {
+        Self {
+            size,
+            resolution: 256,
+            max_depth: 0.3,
+            recovery: 0.0,
+            trampled_color: Vec4::new(0.75, 0.75, 0.8, 1.0),
+        }
+    }
+
+    pub fn with_resolution(mut self, resolution: u32) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: f32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_recovery(mut self, recovery: f32) -> Self {
+        self.recovery = recovery;
+        self
+    }
+
+    pub fn with_trampled_color(mut self, trampled_color: Vec4) -> Self {
+        self.trampled_color = trampled_color;
+        self
+    }
+}
+
+/// Presses a round footprint into any `DeformableSurface` it's over, while it's within
+/// `radius` of the surface's height. Only the first `MAX_DEFORMERS` over a surface count.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Deformer {
+    pub radius: f32,
+    /// How far the footprint presses in, it's capped by the surface's `max_depth`.
+    pub depth: f32,
+}
+
+impl Deformer {
+    pub fn new(radius: f32, depth: f32) -> Self {
+        Self { radius, depth }
+    }
+}
@@ -0,0 +1,21 @@
+use crate::core::Uuid;
+
+/// A stable id for an entity that's saved with the scene, so saved games, links between
+/// entities and network messages can refer to it across sessions and renames.
+/// Find it's entity with `EntityIndex::find_by_id`. Copies made with the `CloneRegistry` get
+/// a new one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct EntityId(pub Uuid);
+
+impl EntityId {
+    /// A new random id.
+    pub fn new() -> Self {
+        Self(Uuid::new())
+    }
+}
+
+impl Default for EntityId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,14 @@
+use nalgebra_glm::Vec4;
+
+/// Tints an entity's PBR material, multiplied with the material's color.
+/// This is cheaper than a `MaterialInstance` because the entity can still be drawn instanced
+/// together with other entities that share it's mesh and material.
+/// Entities with a `MaterialInstance` or an `Animator` ignore it.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct InstanceColor(pub Vec4);
+
+impl Default for InstanceColor {
+    fn default() -> Self {
+        InstanceColor(Vec4::new(1.0, 1.0, 1.0, 1.0))
+    }
+}
@@ -6,14 +6,95 @@ pub enum LightType {
     Directional(DirectionalLightData),
     /// Point Light
     Point(PointLightData),
+    /// Spot Light
+    Spot(SpotLightData),
+}
+
+/// How shadow edges are filtered.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ShadowFilter {
+    /// A single shadow map sample, cheapest but aliased.
+    Hard,
+    /// Percentage closer filtering over a `kernel_size` x `kernel_size` grid of samples.
+    PCF { kernel_size: u32 },
+    /// Percentage closer soft shadows, shadows get softer the further they are from the occluder.
+    /// `light_size` is in world units.
+    PCSS {
+        light_size: f32,
+        blocker_samples: u32,
+        filter_samples: u32,
+    },
+}
+
+impl Default for ShadowFilter {
+    fn default() -> Self {
+        ShadowFilter::PCF { kernel_size: 3 }
+    }
+}
+
+/// Per light shadow settings.
+/// Note: These are only used once the light actually casts shadows, except for contact shadows.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShadowSettings {
+    pub filter: ShadowFilter,
+    /// Constant depth bias.
+    pub depth_bias: f32,
+    /// Bias that scales with the slope of the surface relative to the light.
+    pub slope_bias: f32,
+    /// Offsets the lookup position along the surface normal, in world units.
+    pub normal_bias: f32,
+    /// Distance from the camera shadows start and end at, instead of the camera's near
+    /// plane and the light's `shadow_distance`. A bigger near puts more of the shadow map's
+    /// detail where it's needed, shadows closer than it use the first cascade and can be cut
+    /// off. Directional lights only, spot light shadows cover the light's range.
+    pub depth_range: Option<(f32, f32)>,
+    /// Ray-marches the depth buffer towards the light for the small shadows a shadow map is
+    /// too coarse for, like where things touch the ground. Works on every light type, with or
+    /// without a shadow map.
+    pub contact_shadows: bool,
+    /// How far the contact shadow rays go in world units, they're screen-space so keep it short.
+    pub contact_shadow_length: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            filter: ShadowFilter::default(),
+            depth_bias: 0.005,
+            slope_bias: 1.0,
+            normal_bias: 0.02,
+            depth_range: None,
+            contact_shadows: false,
+            contact_shadow_length: 0.25,
+        }
+    }
+}
+
+impl ShadowSettings {
+    /// The contact shadow ray length the shaders get, 0.0 turns them off.
+    pub(crate) fn contact_shadow_ray(&self) -> f32 {
+        if self.contact_shadows {
+            self.contact_shadow_length.max(0.0)
+        } else {
+            0.0
+        }
+    }
 }
 
 /// Directional light information
+#[derive(Debug, Clone)]
 pub struct DirectionalLightData {
     /// The direction of the light.
     pub direction: Vec3,
     /// The color of the light.
     pub color: Vec3,
+    /// Shadow filtering and bias.
+    pub shadow: ShadowSettings,
+    /// Renders a shadow map for this light.
+    /// Note: Only the first directional light that casts shadows gets them.
+    pub casts_shadows: bool,
+    /// How far from the camera shadows are drawn, capped by the camera's far plane.
+    pub shadow_distance: f32,
 }
 
 impl Default for DirectionalLightData {
@@ -21,26 +102,78 @@ impl Default for DirectionalLightData {
         Self {
             direction: Vec3::zeros(),
             color: Vec3::zeros(),
+            shadow: ShadowSettings::default(),
+            casts_shadows: false,
+            shadow_distance: 100.0,
         }
     }
 }
 
 /// Point light information
 /// Position is defined by the transform.
-/// Currently point lights do not render.
-/// TODO: Fix point lighting rendering.
+#[derive(Debug, Clone)]
 pub struct PointLightData {
     /// Color of the light.
     pub color: Vec3,
-    /// Light attenuation.
-    pub attenuation: f32,
+    /// Scales the color, HDR so it can go well above 1.0.
+    pub intensity: f32,
+    /// The light fades out to nothing at this distance, it's also used to cull the light.
+    pub range: f32,
+    /// Shadow filtering and bias.
+    pub shadow: ShadowSettings,
+    /// An image in the asset folder that tints the light in every direction, oriented by the
+    /// transform. It's six square faces side by side in the order X+ X- Y+ Y- Z+ Z-.
+    pub cookie: Option<String>,
 }
 
 impl Default for PointLightData {
     fn default() -> Self {
         Self {
             color: Vec3::zeros(),
-            attenuation: 0.0,
+            intensity: 1.0,
+            range: 10.0,
+            shadow: ShadowSettings::default(),
+            cookie: None,
+        }
+    }
+}
+
+/// Spot light information
+/// Position and direction are defined by the transform, the light points down the transform's -Z.
+#[derive(Debug, Clone)]
+pub struct SpotLightData {
+    /// Color of the light.
+    pub color: Vec3,
+    /// Scales the color, HDR so it can go well above 1.0.
+    pub intensity: f32,
+    /// The light fades out to nothing at this distance, it's also used to cull the light.
+    pub range: f32,
+    /// Angle in degrees from the center where the light starts to fade.
+    pub inner_angle: f32,
+    /// Angle in degrees from the center where the light is gone.
+    pub outer_angle: f32,
+    /// Shadow filtering and bias. The depth bias is scaled by the distance to the light and
+    /// PCSS is filtered like 3 x 3 PCF.
+    pub shadow: ShadowSettings,
+    /// Renders a shadow map for this light.
+    /// Note: Only the first `MAX_SPOT_SHADOWS` spot lights that cast shadows get them.
+    pub casts_shadows: bool,
+    /// An image in the asset folder projected along the light like a flashlight's pattern,
+    /// it's stretched over the outer cone.
+    pub cookie: Option<String>,
+}
+
+impl Default for SpotLightData {
+    fn default() -> Self {
+        Self {
+            color: Vec3::zeros(),
+            intensity: 1.0,
+            range: 10.0,
+            inner_angle: 20.0,
+            outer_angle: 30.0,
+            shadow: ShadowSettings::default(),
+            casts_shadows: false,
+            cookie: None,
         }
     }
 }
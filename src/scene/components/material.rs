@@ -1,5 +1,5 @@
 /// A handle to a material.
-#[derive(Default)]
+#[derive(Default, Debug, Clone, PartialEq)]
 pub struct Material {
     // We might have more than one material per mesh.
     pub index: u32,
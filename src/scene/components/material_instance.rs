@@ -0,0 +1,68 @@
+use nalgebra_glm::{Vec3, Vec4};
+
+pub(crate) type Overrides = (
+    Option<Vec4>,
+    Option<f32>,
+    Option<f32>,
+    Option<Vec3>,
+    Option<String>,
+);
+
+/// Overrides properties of an entity's PBR material without touching the material itself.
+/// Anything left as `None` comes from the material, changes show up on the next frame.
+/// The entity also needs a `Material` and `Transform` component.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MaterialInstance {
+    pub color: Option<Vec4>,
+    pub roughness: Option<f32>,
+    pub metallic: Option<f32>,
+    pub emissive: Option<Vec3>,
+    /// The name of an image to use instead of the material's main texture.
+    pub main_texture: Option<String>,
+    // The transform index the instance's bind group is stored under.
+    pub(crate) slot: Option<u32>,
+    // The material index, asset bind group generation and overrides that were last uploaded,
+    // used to skip unchanged instances.
+    pub(crate) uploaded: Option<(u32, u32, Overrides)>,
+}
+
+impl MaterialInstance {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: f32) -> Self {
+        self.roughness = Some(roughness);
+        self
+    }
+
+    pub fn with_metallic(mut self, metallic: f32) -> Self {
+        self.metallic = Some(metallic);
+        self
+    }
+
+    pub fn with_emissive(mut self, emissive: Vec3) -> Self {
+        self.emissive = Some(emissive);
+        self
+    }
+
+    pub fn with_main_texture<T: Into<String>>(mut self, image_name: T) -> Self {
+        self.main_texture = Some(image_name.into());
+        self
+    }
+
+    pub(crate) fn overrides(&self) -> Overrides {
+        (
+            self.color,
+            self.roughness,
+            self.metallic,
+            self.emissive,
+            self.main_texture.clone(),
+        )
+    }
+}
@@ -1,5 +1,5 @@
 /// A reference to the mesh.
-#[derive(Default)]
+#[derive(Default, Debug, Clone)]
 pub struct Mesh {
     pub mesh_name: String,
 }
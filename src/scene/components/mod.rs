@@ -1,15 +1,33 @@
 pub(crate) mod mesh;
 pub use mesh::Mesh;
 
+pub(crate) mod animator;
+pub use animator::Animator;
+
 pub(crate) mod transform;
 pub use transform::Transform;
 
 pub(crate) mod camera_data;
-pub use camera_data::CameraData;
+pub use camera_data::{CameraData, FovAxis};
 
 pub(crate) mod material;
 pub use material::Material;
 
+pub(crate) mod material_instance;
+pub use material_instance::MaterialInstance;
+
+pub(crate) mod paint_texture;
+pub use paint_texture::{PaintBrush, PaintTexture};
+
+pub(crate) mod instance_color;
+pub use instance_color::InstanceColor;
+
+pub(crate) mod sort_bias;
+pub use sort_bias::SortBias;
+
+pub(crate) mod view_model;
+pub use view_model::ViewModel;
+
 pub(crate) mod skybox;
 pub use skybox::SkyboxData;
 
@@ -18,3 +36,44 @@ pub use light_data::*;
 
 pub(crate) mod probe;
 pub use probe::*;
+
+pub(crate) mod parent;
+pub use parent::Parent;
+
+pub(crate) mod name;
+pub use name::{Name, Tags};
+
+pub(crate) mod entity_id;
+pub use entity_id::EntityId;
+
+pub(crate) mod world_text;
+pub use world_text::WorldText;
+
+pub(crate) mod visible;
+pub use visible::Visible;
+
+pub(crate) mod static_marker;
+pub use static_marker::Static;
+
+pub(crate) mod shadow_flags;
+pub use shadow_flags::ShadowFlags;
+
+pub(crate) mod water_volume;
+pub use water_volume::WaterVolume;
+
+pub(crate) mod deformable_surface;
+pub use deformable_surface::{DeformableSurface, Deformer};
+
+pub(crate) mod sprite;
+pub use sprite::Sprite;
+
+pub(crate) mod particle_emitter;
+pub use particle_emitter::{Curve, ParticleEmitter, MAX_CURVE_KEYS};
+
+pub(crate) mod camera_controller;
+pub use camera_controller::{look_direction, FlyCamera, OrbitCamera};
+
+#[cfg(feature = "physics2d")]
+pub(crate) mod physics2d;
+#[cfg(feature = "physics2d")]
+pub use physics2d::{BodyType2D, Collider2D, RigidBody2D, Shape2D};
@@ -0,0 +1,31 @@
+/// A human readable name for an entity.
+/// Names don't have to be unique, but `EntityIndex::find_by_name` will only return one of them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Name(pub String);
+
+impl Name {
+    pub fn new<T>(name: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self(name.into())
+    }
+}
+
+/// A list of tags used to group entities together, for example "enemy" or "checkpoint".
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tags(pub Vec<String>);
+
+impl Tags {
+    pub fn new<T>(tags: Vec<T>) -> Self
+    where
+        T: Into<String>,
+    {
+        Self(tags.into_iter().map(|tag| tag.into()).collect())
+    }
+
+    /// Returns true if the entity has the given tag.
+    pub fn has(&self, tag: &str) -> bool {
+        self.0.iter().any(|current| current == tag)
+    }
+}
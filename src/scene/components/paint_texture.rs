@@ -0,0 +1,71 @@
+use nalgebra_glm::{Vec3, Vec4};
+
+use crate::{assets::Handle, graphics::material::Image};
+
+/// A world space brush, the parts of the mesh inside the sphere get painted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaintBrush {
+    pub position: Vec3,
+    pub radius: f32,
+    /// The alpha is how much of the color is laid down.
+    pub color: Vec4,
+    /// 0 fades out from the center of the brush, 1 is a hard edge.
+    pub hardness: f32,
+    /// Only paints surfaces facing into this direction, e.g. straight down for snow.
+    pub direction: Option<Vec3>,
+}
+
+impl PaintBrush {
+    pub fn new(position: Vec3, radius: f32, color: Vec4) -> Self {
+        Self {
+            position,
+            radius,
+            color,
+            hardness: 0.5,
+            direction: None,
+        }
+    }
+
+    pub fn with_hardness(mut self, hardness: f32) -> Self {
+        self.hardness = hardness;
+        self
+    }
+
+    pub fn with_direction(mut self, direction: Vec3) -> Self {
+        self.direction = Some(direction);
+        self
+    }
+}
+
+/// Gives an entity it's own copy of it's material's main texture that can be painted on,
+/// for decals, scorch marks or snow piling up. Paint is drawn in the mesh's UV space so the
+/// mesh needs UVs that don't overlap.
+/// The entity also needs a `Mesh`, `Material`, `Transform` and `MaterialInstance`, the paint
+/// replaces the instance's main texture.
+#[derive(Debug, Clone)]
+pub struct PaintTexture {
+    /// Width and height of the texture.
+    pub size: u32,
+    pub(crate) image: Option<Handle<Image>>,
+    pub(crate) strokes: Vec<PaintBrush>,
+}
+
+impl PaintTexture {
+    pub fn new(size: u32) -> Self {
+        Self {
+            size,
+            image: None,
+            strokes: Vec::new(),
+        }
+    }
+
+    /// Paints with the brush on the next frame.
+    pub fn paint(&mut self, brush: PaintBrush) {
+        self.strokes.push(brush);
+    }
+
+    /// The image that holds the paint, None until the first frame it's rendered.
+    pub fn image(&self) -> Option<&Handle<Image>> {
+        self.image.as_ref()
+    }
+}
@@ -0,0 +1,6 @@
+use legion::prelude::Entity;
+
+/// Attaches an entity to a parent entity.
+/// Entities with a parent are treated as children of that entity, for example when duplicating.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Parent(pub Entity);
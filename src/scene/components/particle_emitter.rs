@@ -0,0 +1,200 @@
+use nalgebra_glm::{Vec3, Vec4};
+use std::ops::{Add, Mul};
+
+use crate::{
+    graphics::{material::Image, BlendMode, RenderQueue},
+    Handle,
+};
+
+/// The most keys a `Curve` can have, they're packed into a uniform for the GPU.
+pub const MAX_CURVE_KEYS: usize = 4;
+
+/// A value over a particle's life, keys are (time, value) with time going from 0 at spawn to 1
+/// when it dies. Values are linearly blended between keys and held before the first and after
+/// the last one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve<T> {
+    keys: Vec<(f32, T)>,
+}
+
+impl<T: Copy + Add<Output = T> + Mul<f32, Output = T>> Curve<T> {
+    pub fn constant(value: T) -> Self {
+        Self {
+            keys: vec![(0.0, value)],
+        }
+    }
+
+    pub fn linear(start: T, end: T) -> Self {
+        Self {
+            keys: vec![(0.0, start), (1.0, end)],
+        }
+    }
+
+    /// Adds a key, keys past `MAX_CURVE_KEYS` are ignored.
+    pub fn with_key(mut self, time: f32, value: T) -> Self {
+        if self.keys.len() >= MAX_CURVE_KEYS {
+            log::warn!("Curves can only have {} keys.", MAX_CURVE_KEYS);
+            return self;
+        }
+        let index = self.keys.iter().take_while(|(key, _)| *key <= time).count();
+        self.keys.insert(index, (time, value));
+        self
+    }
+
+    pub fn keys(&self) -> &[(f32, T)] {
+        &self.keys
+    }
+
+    /// Matches the sampling in the particle shaders.
+    pub fn sample(&self, time: f32) -> T {
+        let mut value = self.keys[0].1;
+        for pair in self.keys.windows(2) {
+            let ((start, from), (end, to)) = (pair[0], pair[1]);
+            if time >= start {
+                let t = ((time - start) / (end - start).max(0.0001))
+                    .max(0.0)
+                    .min(1.0);
+                value = from * (1.0 - t) + to * t;
+            }
+        }
+        value
+    }
+
+    /// The keys padded to `MAX_CURVE_KEYS` by repeating the last one.
+    pub(crate) fn padded(&self) -> ([f32; MAX_CURVE_KEYS], [T; MAX_CURVE_KEYS]) {
+        let last = *self.keys.last().unwrap();
+        let mut times = [last.0; MAX_CURVE_KEYS];
+        let mut values = [last.1; MAX_CURVE_KEYS];
+        for (index, (time, value)) in self.keys.iter().enumerate() {
+            times[index] = *time;
+            values[index] = *value;
+        }
+        (times, values)
+    }
+}
+
+/// Spawns particles that are simulated and drawn by the `ParticleRenderer`.
+/// Particles are camera facing quads, they're sorted with the blended meshes in the same
+/// render queue by the emitter's position.
+#[derive(Debug, Clone)]
+pub struct ParticleEmitter {
+    /// Particles spawned per second.
+    pub spawn_rate: f32,
+    /// Seconds each particle lives.
+    pub lifetime: f32,
+    /// The most particles alive at once, when it's full the oldest particles are replaced.
+    pub max_particles: u32,
+    /// Particles spawn at a random point in a sphere of this radius around the emitter.
+    pub spawn_radius: f32,
+    /// Starting velocity in the emitter's local space.
+    pub velocity: Vec3,
+    /// A random velocity up to this long is added to each particle.
+    pub velocity_spread: f32,
+    /// Scales the particle's velocity over it's life.
+    pub speed: Curve<f32>,
+    /// World space acceleration, for example gravity.
+    pub acceleration: Vec3,
+    pub color: Curve<Vec4>,
+    /// Size of the quad in world units.
+    pub size: Curve<f32>,
+    /// Multiplied with the color, particles are plain squares without one.
+    pub texture: Option<Handle<Image>>,
+    /// Use `BlendMode::Additive` for fire and sparks, `BlendMode::Alpha` for smoke.
+    pub blend_mode: BlendMode,
+    pub render_queue: RenderQueue,
+    /// Stops spawning when false, particles that are alive finish their life.
+    pub emitting: bool,
+}
+
+impl ParticleEmitter {
+    pub fn new(spawn_rate: f32, lifetime: f32) -> Self {
+        Self {
+            spawn_rate,
+            lifetime,
+            max_particles: (spawn_rate * lifetime).ceil().max(1.0) as u32,
+            spawn_radius: 0.0,
+            velocity: Vec3::new(0.0, 1.0, 0.0),
+            velocity_spread: 0.0,
+            speed: Curve::constant(1.0),
+            acceleration: Vec3::zeros(),
+            color: Curve::constant(Vec4::new(1.0, 1.0, 1.0, 1.0)),
+            size: Curve::constant(0.1),
+            texture: None,
+            blend_mode: BlendMode::Alpha,
+            render_queue: RenderQueue::default(),
+            emitting: true,
+        }
+    }
+
+    pub fn with_max_particles(mut self, max_particles: u32) -> Self {
+        self.max_particles = max_particles.max(1);
+        self
+    }
+
+    pub fn with_spawn_radius(mut self, spawn_radius: f32) -> Self {
+        self.spawn_radius = spawn_radius;
+        self
+    }
+
+    pub fn with_velocity(mut self, velocity: Vec3, spread: f32) -> Self {
+        self.velocity = velocity;
+        self.velocity_spread = spread;
+        self
+    }
+
+    pub fn with_speed(mut self, speed: Curve<f32>) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn with_acceleration(mut self, acceleration: Vec3) -> Self {
+        self.acceleration = acceleration;
+        self
+    }
+
+    pub fn with_color(mut self, color: Curve<Vec4>) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_size(mut self, size: Curve<f32>) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_texture(mut self, texture: Handle<Image>) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    pub fn with_render_queue(mut self, render_queue: RenderQueue) -> Self {
+        self.render_queue = render_queue;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curve_sample() {
+        let curve = Curve::linear(0.0, 1.0).with_key(0.5, 4.0);
+        assert_eq!(curve.keys().len(), 3);
+        assert_eq!(curve.sample(-1.0), 0.0);
+        assert_eq!(curve.sample(0.25), 2.0);
+        assert_eq!(curve.sample(0.5), 4.0);
+        assert_eq!(curve.sample(0.75), 2.5);
+        assert_eq!(curve.sample(2.0), 1.0);
+
+        let (times, values) = curve.padded();
+        assert_eq!(times, [0.0, 0.5, 1.0, 1.0]);
+        assert_eq!(values, [0.0, 4.0, 1.0, 1.0]);
+        assert_eq!(Curve::constant(3.0).sample(0.5), 3.0);
+    }
+}
@@ -0,0 +1,200 @@
+use nalgebra_glm::Vec2;
+
+use super::Sprite;
+
+/// How a `RigidBody2D` moves.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BodyType2D {
+    /// Never moves, for floors and walls.
+    Static,
+    /// Moved by gravity, collisions and impulses.
+    Dynamic,
+    /// Follows it's transform and pushes dynamic bodies out of the way, for moving platforms.
+    Kinematic,
+}
+
+/// A 2D physics body, needs a `Collider2D` and a `Transform`. Bodies move on the XY plane,
+/// the transform's z is left alone and it's only rotated around the Z axis, which matches
+/// sprites and `CameraData::new_2d`. See `Physics2D` for setting velocities and impulses.
+#[derive(Debug, Clone)]
+pub struct RigidBody2D {
+    pub body_type: BodyType2D,
+    /// Keeps the body from turning, for characters.
+    pub fixed_rotation: bool,
+    pub(crate) handle: Option<rapier2d::dynamics::RigidBodyHandle>,
+}
+
+impl RigidBody2D {
+    pub fn new(body_type: BodyType2D) -> Self {
+        Self {
+            body_type,
+            fixed_rotation: false,
+            handle: None,
+        }
+    }
+
+    pub fn with_fixed_rotation(mut self, fixed_rotation: bool) -> Self {
+        self.fixed_rotation = fixed_rotation;
+        self
+    }
+}
+
+/// A shape in a `Collider2D`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Shape2D {
+    /// A box, in half the width and height.
+    Rect(Vec2),
+    /// A circle with the given radius.
+    Circle(f32),
+    /// A pill standing up along y, a rect with half circles on the top and bottom.
+    Capsule { half_height: f32, radius: f32 },
+}
+
+/// The shapes a `RigidBody2D` collides with, in local units. They're scaled by the
+/// transform's scale when the body is created, scaling it later doesn't change them.
+#[derive(Debug, Clone)]
+pub struct Collider2D {
+    /// Every shape with it's offset from the entity's position.
+    pub shapes: Vec<(Vec2, Shape2D)>,
+    pub friction: f32,
+    /// How bouncy it is, from 0.0 to 1.0.
+    pub restitution: f32,
+    /// Mass per area, only used by dynamic bodies.
+    pub density: f32,
+    /// Only detects overlaps and doesn't push bodies apart, they show up as
+    /// `CollisionEvent2D::Trigger`.
+    pub sensor: bool,
+}
+
+impl Collider2D {
+    pub fn new(shapes: Vec<(Vec2, Shape2D)>) -> Self {
+        Self {
+            shapes,
+            friction: 0.5,
+            restitution: 0.0,
+            density: 1.0,
+            sensor: false,
+        }
+    }
+
+    /// A box of the given width and height.
+    pub fn rect(size: Vec2) -> Self {
+        Self::new(vec![(Vec2::zeros(), Shape2D::Rect(size * 0.5))])
+    }
+
+    pub fn circle(radius: f32) -> Self {
+        Self::new(vec![(Vec2::zeros(), Shape2D::Circle(radius))])
+    }
+
+    /// A box the size of the sprite's quad.
+    pub fn from_sprite(sprite: &Sprite) -> Self {
+        Self::rect(sprite.size)
+    }
+
+    /// Boxes covering the solid tiles of a tilemap, `tiles` is stored row by row from the
+    /// top left and the rows are `columns` tiles long. Neighbouring tiles are merged into as
+    /// few boxes as it can, so bodies don't catch on the seams between them.
+    /// The top left corner of the map is at the entity's position and rows go down along -y.
+    pub fn from_tiles(tiles: &[bool], columns: usize, tile_size: Vec2) -> Self {
+        let rects = merge_tiles(tiles, columns)
+            .into_iter()
+            .map(|(column, row, width, height)| {
+                let half_size = Vec2::new(
+                    width as f32 * tile_size.x * 0.5,
+                    height as f32 * tile_size.y * 0.5,
+                );
+                let corner = Vec2::new(column as f32 * tile_size.x, -(row as f32) * tile_size.y);
+                (
+                    Vec2::new(corner.x + half_size.x, corner.y - half_size.y),
+                    Shape2D::Rect(half_size),
+                )
+            })
+            .collect();
+        Self::new(rects)
+    }
+
+    pub fn with_friction(mut self, friction: f32) -> Self {
+        self.friction = friction;
+        self
+    }
+
+    pub fn with_restitution(mut self, restitution: f32) -> Self {
+        self.restitution = restitution;
+        self
+    }
+
+    pub fn with_density(mut self, density: f32) -> Self {
+        self.density = density;
+        self
+    }
+
+    pub fn with_sensor(mut self, sensor: bool) -> Self {
+        self.sensor = sensor;
+        self
+    }
+}
+
+// Greedily grows each run of solid tiles down over the rows below it.
+// Returns (column, row, width, height) in tiles.
+fn merge_tiles(tiles: &[bool], columns: usize) -> Vec<(usize, usize, usize, usize)> {
+    if columns == 0 {
+        return Vec::new();
+    }
+    let rows = tiles.len() / columns;
+    let mut used = vec![false; rows * columns];
+    let free = |used: &[bool], column: usize, row: usize| {
+        tiles[row * columns + column] && !used[row * columns + column]
+    };
+
+    let mut rects = Vec::new();
+    for row in 0..rows {
+        let mut column = 0;
+        while column < columns {
+            if !free(&used, column, row) {
+                column += 1;
+                continue;
+            }
+            let mut width = 1;
+            while column + width < columns && free(&used, column + width, row) {
+                width += 1;
+            }
+            let mut height = 1;
+            while row + height < rows
+                && (column..column + width).all(|x| free(&used, x, row + height))
+            {
+                height += 1;
+            }
+            for y in row..row + height {
+                for x in column..column + width {
+                    used[y * columns + x] = true;
+                }
+            }
+            rects.push((column, row, width, height));
+            column += width;
+        }
+    }
+    rects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_tiles() {
+        #[rustfmt::skip]
+        let tiles = [
+            true, true, false,
+            true, true, true,
+            false, false, true,
+        ];
+        assert_eq!(merge_tiles(&tiles, 3), vec![(0, 0, 2, 2), (2, 1, 1, 2)]);
+
+        let collider = Collider2D::from_tiles(&tiles, 3, Vec2::new(2.0, 1.0));
+        assert_eq!(
+            collider.shapes[0],
+            (Vec2::new(2.0, -1.0), Shape2D::Rect(Vec2::new(2.0, 1.0)))
+        );
+        assert!(merge_tiles(&tiles, 0).is_empty());
+    }
+}
@@ -1,4 +1,44 @@
+use nalgebra_glm::Vec3;
+
 /// Reference to the probe.
 pub struct Probe {
     pub id: u32,
 }
+
+/// Makes a probe only reflect what's around it, inside a box centered on the probe.
+/// Inside the box the PBR shader uses the probe's reflections instead of the environment's,
+/// fading between the two near the edges. Probes are baked once, call
+/// `ProbeManager::rebake` after moving one or changing what's around it.
+/// Moving objects only show up in reflections through `DynamicGI`, there's no screen-space
+/// reflection pass. Normals can come from depth like `ScreenSpaceGI` does, but the forward
+/// renderer keeps no roughness or metalness per pixel to tell how sharp a reflection should be.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ReflectionProbe {
+    /// Half the size of the box, in world units.
+    pub extents: Vec3,
+    /// How far inside the box the probe fades out into the environment.
+    pub blend_distance: f32,
+    /// Bends reflections as if they came from the box's walls instead of infinitely far away,
+    /// which lines them up in rooms and corridors. Turn it off for open areas.
+    pub box_projection: bool,
+}
+
+impl ReflectionProbe {
+    pub fn new(extents: Vec3) -> Self {
+        Self {
+            extents,
+            blend_distance: 1.0,
+            box_projection: true,
+        }
+    }
+
+    pub fn with_blend_distance(mut self, blend_distance: f32) -> Self {
+        self.blend_distance = blend_distance;
+        self
+    }
+
+    pub fn with_box_projection(mut self, box_projection: bool) -> Self {
+        self.box_projection = box_projection;
+        self
+    }
+}
@@ -0,0 +1,39 @@
+/// Turns off casting or receiving shadows for an entity, entities without it do both.
+/// Skyboxes, text in the world and tiny props rarely need either. Materials have the same
+/// flags, an entity only casts or receives shadows if both it and it's material do.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShadowFlags {
+    /// Drawn into the shadow maps.
+    pub cast_shadows: bool,
+    /// Darkened by shadows, entities that don't aren't drawn instanced.
+    pub receive_shadows: bool,
+}
+
+impl Default for ShadowFlags {
+    fn default() -> Self {
+        Self {
+            cast_shadows: true,
+            receive_shadows: true,
+        }
+    }
+}
+
+impl ShadowFlags {
+    /// Neither casts nor receives shadows.
+    pub fn none() -> Self {
+        Self {
+            cast_shadows: false,
+            receive_shadows: false,
+        }
+    }
+
+    pub fn with_cast_shadows(mut self, cast_shadows: bool) -> Self {
+        self.cast_shadows = cast_shadows;
+        self
+    }
+
+    pub fn with_receive_shadows(mut self, receive_shadows: bool) -> Self {
+        self.receive_shadows = receive_shadows;
+        self
+    }
+}
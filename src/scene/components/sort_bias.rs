@@ -0,0 +1,4 @@
+/// Moves an entity to another render queue than it's material's, the bias is added to the
+/// material's `RenderQueue` value. Entities with a bias aren't drawn instanced.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct SortBias(pub i32);
@@ -0,0 +1,80 @@
+use nalgebra_glm::{Vec2, Vec4};
+
+use crate::{
+    graphics::{material::Image, AtlasRegion, ClipRect, UvOrigin},
+    Handle,
+};
+
+/// A textured quad drawn by the `SpriteRenderer` at the entity's transform.
+/// Sprites face +z, so they line up with a camera made with `CameraData::new_2d`.
+#[derive(Debug, Clone)]
+pub struct Sprite {
+    pub image: Handle<Image>,
+    /// (offset x, offset y, scale x, scale y) in UV space starting at the top left, the whole
+    /// image by default. See `with_uv_rect` for rects from tools that start at the bottom left.
+    pub uv_rect: Vec4,
+    /// Mirrors the image horizontally, the quad itself stays put.
+    pub flip_x: bool,
+    /// Mirrors the image vertically.
+    pub flip_y: bool,
+    /// Multiplied with the image's color.
+    pub color: Vec4,
+    /// Size in world units before the transform's scale, centered on it's position.
+    pub size: Vec2,
+    /// Higher layers are drawn on top of lower ones.
+    pub layer: i32,
+    /// Cuts the sprite off outside of this rectangle on the screen, in pixels.
+    pub clip: Option<ClipRect>,
+}
+
+impl Sprite {
+    pub fn new(image: Handle<Image>, size: Vec2) -> Self {
+        Self {
+            image,
+            uv_rect: Vec4::new(0.0, 0.0, 1.0, 1.0),
+            flip_x: false,
+            flip_y: false,
+            color: Vec4::new(1.0, 1.0, 1.0, 1.0),
+            size,
+            layer: 0,
+            clip: None,
+        }
+    }
+
+    /// A sprite showing part of a `TextureAtlas`, sized from the region's pixels.
+    pub fn from_region(region: &AtlasRegion, pixels_per_unit: f32) -> Self {
+        let size = Vec2::new(region.width as f32, region.height as f32) / pixels_per_unit;
+        Self {
+            uv_rect: region.uv_rect,
+            ..Self::new(region.page.clone(), size)
+        }
+    }
+
+    /// Shows part of the image, `origin` is where the rect's uvs start.
+    pub fn with_uv_rect(mut self, uv_rect: Vec4, origin: UvOrigin) -> Self {
+        self.uv_rect = origin.rect_to_top_left(uv_rect);
+        self
+    }
+
+    /// Handy for characters that turn around.
+    pub fn with_flip(mut self, flip_x: bool, flip_y: bool) -> Self {
+        self.flip_x = flip_x;
+        self.flip_y = flip_y;
+        self
+    }
+
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_layer(mut self, layer: i32) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    pub fn with_clip(mut self, clip: ClipRect) -> Self {
+        self.clip = Some(clip);
+        self
+    }
+}
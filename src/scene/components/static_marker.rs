@@ -0,0 +1,8 @@
+/// Promises the entity's transform never changes after it's spawned. The renderer works out
+/// it's world matrix and uploads it once, and batches it with other static entities into
+/// batches that aren't rebuilt every frame. See `RenderSettings::bake_static_only` for
+/// keeping everything else out of probes.
+/// To move a static entity anyway remove this first, it's uploaded again the frame after
+/// it's added back.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Static;
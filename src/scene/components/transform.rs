@@ -1,14 +1,18 @@
 use crate::{
     graphics::resources::{BindGroup, GPUResourceManager},
+    scene::resources::Tracked,
     Application, TransformCount,
 };
 use bytemuck::{Pod, Zeroable};
-use nalgebra_glm::{Mat4, Quat, Vec3};
+use legion::prelude::Resources;
+use nalgebra_glm::{Mat4, Quat, Vec3, Vec4};
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct LocalUniform {
     pub world: Mat4,
+    // (receives shadows, unused..)
+    pub info: Vec4,
 }
 unsafe impl Zeroable for LocalUniform {}
 unsafe impl Pod for LocalUniform {}
@@ -17,6 +21,7 @@ impl Default for LocalUniform {
     fn default() -> Self {
         Self {
             world: Mat4::identity(),
+            info: Vec4::new(1.0, 0.0, 0.0, 0.0),
         }
     }
 }
@@ -27,7 +32,8 @@ impl Default for LocalUniform {
 /// scale: A Vector3 representing it's world scale.
 /// rotation: A quaternion representing it's world rotation.
 /// matrix: A world matrix.
-#[derive(Debug)]
+/// Note: Transforms aren't `Clone` since the index points at GPU data, use `duplicate` instead.
+#[derive(Debug, PartialEq)]
 pub struct Transform {
     pub index: u32,
     pub position: Vec3,
@@ -39,12 +45,20 @@ pub struct Transform {
 impl Transform {
     /// Creates a new transform with default values.
     pub fn new(app: &mut Application) -> Self {
-        let mut index = app.resources.get_mut::<TransformCount>().unwrap();
-        index.0 += 1;
-        Self::create_bindings(app, index.0);
+        Self::new_from_resources(&app.resources)
+    }
+
+    /// Same as `new` but only needs access to the resources.
+    pub fn new_from_resources(resources: &Resources) -> Self {
+        let index = {
+            let mut index = resources.get_mut::<TransformCount>().unwrap();
+            index.0 += 1;
+            index.0
+        };
+        Self::create_bindings(resources, index);
 
         Self {
-            index: index.0,
+            index,
             position: Vec3::new(0.0, 0.0, 0.0),
             scale: Vec3::new(1.0, 1.0, 1.0),
             rotation: Quat::identity(),
@@ -52,6 +66,28 @@ impl Transform {
         }
     }
 
+    /// Creates a copy of this transform with it's own GPU bindings.
+    pub fn duplicate(&self, resources: &Resources) -> Self {
+        let mut transform = Self::new_from_resources(resources);
+        transform.position = self.position;
+        transform.scale = self.scale;
+        transform.rotation = self.rotation;
+        transform.matrix = self.matrix;
+        transform
+    }
+
+    /// Copies the transform along with it's index, so only one of the two can be in the world.
+    /// Used to bring back despawned entities.
+    pub(crate) fn copy_with_index(&self) -> Self {
+        Self {
+            index: self.index,
+            position: self.position,
+            scale: self.scale,
+            rotation: self.rotation,
+            matrix: self.matrix,
+        }
+    }
+
     /// Lets you rotate the transform along a specific axis.
     pub fn rotate_on_axis<'a>(&'a mut self, axis: Vec3, angle: f32) -> &'a mut Self {
         self.rotation = self.rotation * nalgebra_glm::quat_angle_axis(angle, &axis);
@@ -91,17 +127,22 @@ impl Transform {
     /// Used internally to recalculate the world matrix.
     /// Can also be used if an updated world matrix is needed.
     pub fn update(&mut self) {
+        self.matrix = self.calculate_matrix();
+    }
+
+    /// Calculates the world matrix without storing it.
+    pub fn calculate_matrix(&self) -> Mat4 {
         let scale = nalgebra_glm::scaling(&self.scale);
         let rotation = nalgebra_glm::quat_to_mat4(&self.rotation);
         let translation = nalgebra_glm::translation(&self.position);
-        self.matrix = translation * rotation * scale;
+        translation * rotation * scale
     }
 
-    pub(crate) fn create_bindings(app: &Application, index: u32) {
-        let mut resource_manager = app.resources.get_mut::<GPUResourceManager>().unwrap();
+    pub(crate) fn create_bindings(resources: &Resources, index: u32) {
+        let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
         let bind_group_layout = resource_manager.get_bind_group_layout("locals").unwrap();
         // This data needs to be saved and passed onto the pipeline.
-        let device = app.resources.get_mut::<wgpu::Device>().unwrap();
+        let device = resources.get_mut::<wgpu::Device>().unwrap();
         let local_buffer = device.create_buffer_with_data(
             bytemuck::bytes_of(&LocalUniform::default()),
             wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
@@ -123,4 +164,21 @@ impl Transform {
         );
         resource_manager.add_multi_buffer("transform", local_buffer, index);
     }
+
+    /// Frees the GPU buffer and bind group created for a transform index.
+    pub(crate) fn release_bindings(resources: &Resources, index: u32) {
+        let mut resource_manager = resources.get_mut::<GPUResourceManager>().unwrap();
+        resource_manager.remove_multi_bind_group("transform", index);
+        resource_manager.remove_multi_buffer("transform", index);
+    }
+}
+
+// Transforms aren't `Clone`, a `ChangeTracker<Transform>` compares the position, rotation and
+// scale instead. The index never changes and the matrix is calculated from the rest.
+impl Tracked for Transform {
+    type Snapshot = (Vec3, Quat, Vec3);
+
+    fn snapshot(&self) -> Self::Snapshot {
+        (self.position, self.rotation, self.scale)
+    }
 }
@@ -0,0 +1,5 @@
+/// Marks an entity as part of the first person view model, like a weapon or hands.
+/// View models are drawn after everything else with a cleared depth buffer, using the active
+/// camera's view model FOV and depth range so they don't clip into walls.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct ViewModel;
@@ -0,0 +1,10 @@
+/// Shows or hides an entity and all of it's children.
+/// Entities without this component are visible.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Visible(pub bool);
+
+impl Default for Visible {
+    fn default() -> Self {
+        Visible(true)
+    }
+}
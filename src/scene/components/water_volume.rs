@@ -0,0 +1,43 @@
+use nalgebra_glm::Vec3;
+
+/// A box of water centered on the entity's `Transform`, the top of the box is the surface.
+/// The caustics pass projects the first directional light's caustics onto surfaces inside it
+/// and materials near the surface get wet, see `PBRMaterial::wetness`. The box ignores the
+/// transform's rotation and scale, and only the first `MAX_WATER_VOLUMES` volumes are used.
+/// The volume doesn't draw the water itself, give the entity a mesh with a blended material.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WaterVolume {
+    /// Half the size of the box, in world units.
+    pub extents: Vec3,
+    /// How bright the caustics are, 0 turns them off.
+    pub caustics_intensity: f32,
+    /// Size of the caustic pattern in world units.
+    pub caustics_scale: f32,
+    pub caustics_speed: f32,
+    /// How far above the surface things are still wet, from splashes and waves.
+    pub wetness_height: f32,
+}
+
+impl WaterVolume {
+    pub fn new(extents: Vec3) -> Self {
+        Self {
+            extents,
+            caustics_intensity: 1.0,
+            caustics_scale: 2.0,
+            caustics_speed: 1.0,
+            wetness_height: 0.5,
+        }
+    }
+
+    pub fn with_caustics(mut self, intensity: f32, scale: f32, speed: f32) -> Self {
+        self.caustics_intensity = intensity;
+        self.caustics_scale = scale;
+        self.caustics_speed = speed;
+        self
+    }
+
+    pub fn with_wetness_height(mut self, wetness_height: f32) -> Self {
+        self.wetness_height = wetness_height;
+        self
+    }
+}
@@ -0,0 +1,48 @@
+use nalgebra_glm::{Vec3, Vec4};
+
+/// Text that is drawn at the entity's transform position and always faces the camera.
+/// Useful for nameplates, markers and debug annotations.
+#[derive(Debug, Clone)]
+pub struct WorldText {
+    pub text: String,
+    /// The font file name, if none the first font is used.
+    pub font: Option<String>,
+    /// Offset from the transform's position in world units.
+    pub offset: Vec3,
+    /// Font size in pixels.
+    pub size: f32,
+    pub color: Vec4,
+    /// If set the text shrinks with distance, `size` is the size at this distance.
+    pub scale_distance: Option<f32>,
+    /// Text further away than this is not drawn.
+    pub max_distance: Option<f32>,
+    /// When true the text is hidden behind geometry, otherwise it's always drawn on top.
+    pub occluded: bool,
+}
+
+impl Default for WorldText {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            font: None,
+            offset: Vec3::zeros(),
+            size: 16.0,
+            color: Vec4::new(1.0, 1.0, 1.0, 1.0),
+            scale_distance: None,
+            max_distance: None,
+            occluded: false,
+        }
+    }
+}
+
+impl WorldText {
+    pub fn new<T>(text: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            text: text.into(),
+            ..Self::default()
+        }
+    }
+}
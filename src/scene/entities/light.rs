@@ -6,5 +6,6 @@ pub fn create(world: &mut World, light_type: LightType, transform: Transform) ->
     match light_type {
         LightType::Directional(data) => world.insert((), vec![(data, transform)]),
         LightType::Point(data) => world.insert((), vec![(data, transform)]),
+        LightType::Spot(data) => world.insert((), vec![(data, transform)]),
     }
 }
@@ -29,3 +29,48 @@ pub fn create(
         .world
         .insert((), vec![(probe_component, transform)])[0]
 }
+
+/// Creates a probe that lights the scene from an equirectangular HDR image, for example the skybox texture.
+/// The PBR material uses whichever probe was created last, not counting reflection probes.
+pub fn create_environment<T>(
+    app: &mut Application,
+    texture: T,
+    quality: ProbeQuality,
+    format: ProbeFormat,
+) -> Entity
+where
+    T: Into<String>,
+{
+    let probe_id = {
+        app.probe_manager
+            .create_environment(texture, &app.resources, quality, format)
+    };
+    let probe_component = components::Probe { id: probe_id };
+    let transform = components::Transform::new(app);
+
+    app.current_scene
+        .world
+        .insert((), vec![(probe_component, transform)])[0]
+}
+
+/// Creates a reflection probe that captures the scene around `position` and is used by the
+/// meshes inside the probe's box, see `ReflectionProbe`.
+pub fn create_reflection(
+    app: &mut Application,
+    position: Vec3,
+    reflection: components::ReflectionProbe,
+    quality: ProbeQuality,
+    format: ProbeFormat,
+) -> Entity {
+    let probe_id = app
+        .probe_manager
+        .create_reflection(position, &app.resources, quality, format);
+    let probe_component = components::Probe { id: probe_id };
+
+    let mut transform = components::Transform::new(app);
+    transform.position = position;
+
+    app.current_scene
+        .world
+        .insert((), vec![(probe_component, reflection, transform)])[0]
+}
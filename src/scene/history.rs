@@ -0,0 +1,391 @@
+use legion::prelude::*;
+use nalgebra_glm::{Quat, Vec3};
+
+use crate::scene::components;
+
+/// Returned by a command when applying or reverting it re-created an entity.
+/// Legion hands out a new `Entity` id on every insert, so the history uses this
+/// to patch any other commands that still reference the old id.
+#[derive(Debug, Copy, Clone)]
+pub struct Respawned {
+    pub old: Entity,
+    pub new: Entity,
+}
+
+/// A reversible mutation of the world.
+pub trait Command: Send + Sync + 'static {
+    /// A short human readable name, useful for showing the history in a UI.
+    fn name(&self) -> &str;
+
+    /// Applies the command to the world.
+    fn apply(&mut self, world: &mut World) -> Option<Respawned>;
+
+    /// Reverts whatever `apply` did.
+    fn revert(&mut self, world: &mut World) -> Option<Respawned>;
+
+    /// Called when another command re-created an entity this command might reference.
+    fn remap_entity(&mut self, _old: Entity, _new: Entity) {}
+}
+
+/// An undo/redo stack of commands.
+/// Application inserts one of these as a resource, but you can create your own as well.
+pub struct History {
+    undo_stack: Vec<Box<dyn Command>>,
+    redo_stack: Vec<Box<dyn Command>>,
+    /// The max amount of commands we keep around. Oldest commands are dropped first.
+    pub max_len: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(100)
+    }
+}
+
+impl History {
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            max_len,
+        }
+    }
+
+    /// Applies a command and pushes it onto the undo stack.
+    /// Note: This clears the redo stack.
+    pub fn execute<T: Command>(&mut self, mut command: T, world: &mut World) {
+        let respawned = command.apply(world);
+        self.redo_stack.clear();
+        self.remap(respawned);
+        self.undo_stack.push(Box::new(command));
+
+        if self.undo_stack.len() > self.max_len {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    /// Reverts the last command. Returns false if there was nothing to undo.
+    pub fn undo(&mut self, world: &mut World) -> bool {
+        match self.undo_stack.pop() {
+            Some(mut command) => {
+                let respawned = command.revert(world);
+                self.redo_stack.push(command);
+                self.remap(respawned);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Re-applies the last undone command. Returns false if there was nothing to redo.
+    pub fn redo(&mut self, world: &mut World) -> bool {
+        match self.redo_stack.pop() {
+            Some(mut command) => {
+                let respawned = command.apply(world);
+                self.undo_stack.push(command);
+                self.remap(respawned);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Names of the commands on the undo stack, oldest first.
+    pub fn undo_names(&self) -> Vec<&str> {
+        self.undo_stack
+            .iter()
+            .map(|command| command.name())
+            .collect()
+    }
+
+    /// Names of the commands on the redo stack, next redo last.
+    pub fn redo_names(&self) -> Vec<&str> {
+        self.redo_stack
+            .iter()
+            .map(|command| command.name())
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    fn remap(&mut self, respawned: Option<Respawned>) {
+        if let Some(respawned) = respawned {
+            for command in self.undo_stack.iter_mut().chain(self.redo_stack.iter_mut()) {
+                command.remap_entity(respawned.old, respawned.new);
+            }
+        }
+    }
+}
+
+fn remap(entity: &mut Option<Entity>, old: Entity, new: Entity) {
+    if *entity == Some(old) {
+        *entity = Some(new);
+    }
+}
+
+/// Spawns a mesh entity and despawns it again on undo.
+pub struct SpawnMesh {
+    mesh: components::Mesh,
+    material: components::Material,
+    transform: components::Transform,
+    entity: Option<Entity>,
+}
+
+impl SpawnMesh {
+    pub fn new(
+        mesh: components::Mesh,
+        material: components::Material,
+        transform: components::Transform,
+    ) -> Self {
+        Self {
+            mesh,
+            material,
+            transform,
+            entity: None,
+        }
+    }
+
+    /// The entity that was spawned, if the command has been applied.
+    pub fn entity(&self) -> Option<Entity> {
+        self.entity
+    }
+}
+
+impl Command for SpawnMesh {
+    fn name(&self) -> &str {
+        "Spawn"
+    }
+
+    fn apply(&mut self, world: &mut World) -> Option<Respawned> {
+        let entity = world.insert(
+            (),
+            vec![(
+                self.mesh.clone(),
+                self.material.clone(),
+                self.transform.copy_with_index(),
+            )],
+        )[0];
+        let old = self.entity.replace(entity);
+        old.map(|old| Respawned { old, new: entity })
+    }
+
+    fn revert(&mut self, world: &mut World) -> Option<Respawned> {
+        if let Some(entity) = self.entity {
+            world.delete(entity);
+        }
+        None
+    }
+
+    fn remap_entity(&mut self, old: Entity, new: Entity) {
+        remap(&mut self.entity, old, new);
+    }
+}
+
+/// Despawns a mesh entity, keeping a copy of its components around so undo can bring it back.
+pub struct DespawnMesh {
+    entity: Option<Entity>,
+    removed: Option<(
+        components::Mesh,
+        components::Material,
+        components::Transform,
+    )>,
+}
+
+impl DespawnMesh {
+    pub fn new(entity: Entity) -> Self {
+        Self {
+            entity: Some(entity),
+            removed: None,
+        }
+    }
+}
+
+impl Command for DespawnMesh {
+    fn name(&self) -> &str {
+        "Despawn"
+    }
+
+    fn apply(&mut self, world: &mut World) -> Option<Respawned> {
+        let entity = self.entity?;
+        let mesh = (*world.get_component::<components::Mesh>(entity)?).clone();
+        let material = (*world.get_component::<components::Material>(entity)?).clone();
+        let transform = world
+            .get_component::<components::Transform>(entity)?
+            .copy_with_index();
+        self.removed = Some((mesh, material, transform));
+        world.delete(entity);
+        None
+    }
+
+    fn revert(&mut self, world: &mut World) -> Option<Respawned> {
+        let components = self.removed.take()?;
+        let new = world.insert((), vec![components])[0];
+        let old = self.entity.replace(new)?;
+        Some(Respawned { old, new })
+    }
+
+    fn remap_entity(&mut self, old: Entity, new: Entity) {
+        remap(&mut self.entity, old, new);
+    }
+}
+
+/// Sets the position, rotation and scale of an entity's transform.
+pub struct EditTransform {
+    entity: Option<Entity>,
+    position: Vec3,
+    rotation: Quat,
+    scale: Vec3,
+    previous: Option<(Vec3, Quat, Vec3)>,
+}
+
+impl EditTransform {
+    pub fn new(entity: Entity, position: Vec3, rotation: Quat, scale: Vec3) -> Self {
+        Self {
+            entity: Some(entity),
+            position,
+            rotation,
+            scale,
+            previous: None,
+        }
+    }
+
+    fn set(
+        world: &mut World,
+        entity: Entity,
+        (position, rotation, scale): (Vec3, Quat, Vec3),
+    ) -> Option<(Vec3, Quat, Vec3)> {
+        let mut transform = world.get_component_mut::<components::Transform>(entity)?;
+        let previous = (transform.position, transform.rotation, transform.scale);
+        transform.position = position;
+        transform.rotation = rotation;
+        transform.scale = scale;
+        Some(previous)
+    }
+}
+
+impl Command for EditTransform {
+    fn name(&self) -> &str {
+        "Edit Transform"
+    }
+
+    fn apply(&mut self, world: &mut World) -> Option<Respawned> {
+        let entity = self.entity?;
+        self.previous = Self::set(world, entity, (self.position, self.rotation, self.scale));
+        None
+    }
+
+    fn revert(&mut self, world: &mut World) -> Option<Respawned> {
+        let entity = self.entity?;
+        Self::set(world, entity, self.previous.take()?);
+        None
+    }
+
+    fn remap_entity(&mut self, old: Entity, new: Entity) {
+        remap(&mut self.entity, old, new);
+    }
+}
+
+/// Overrides which material an entity renders with.
+pub struct SetMaterial {
+    entity: Option<Entity>,
+    material_index: u32,
+    previous: Option<u32>,
+}
+
+impl SetMaterial {
+    pub fn new(entity: Entity, material_index: u32) -> Self {
+        Self {
+            entity: Some(entity),
+            material_index,
+            previous: None,
+        }
+    }
+
+    fn set(world: &mut World, entity: Entity, index: u32) -> Option<u32> {
+        let mut material = world.get_component_mut::<components::Material>(entity)?;
+        let previous = material.index;
+        material.index = index;
+        Some(previous)
+    }
+}
+
+impl Command for SetMaterial {
+    fn name(&self) -> &str {
+        "Set Material"
+    }
+
+    fn apply(&mut self, world: &mut World) -> Option<Respawned> {
+        let entity = self.entity?;
+        self.previous = Self::set(world, entity, self.material_index);
+        None
+    }
+
+    fn revert(&mut self, world: &mut World) -> Option<Respawned> {
+        let entity = self.entity?;
+        Self::set(world, entity, self.previous.take()?);
+        None
+    }
+
+    fn remap_entity(&mut self, old: Entity, new: Entity) {
+        remap(&mut self.entity, old, new);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn material_index(world: &World, entity: Entity) -> u32 {
+        world
+            .get_component::<components::Material>(entity)
+            .unwrap()
+            .index
+    }
+
+    #[test]
+    fn test_undo_redo_material() {
+        let mut world = Universe::new().create_world();
+        let entity = world.insert((), vec![(components::Material::new(0),)])[0];
+        let mut history = History::default();
+
+        history.execute(SetMaterial::new(entity, 1), &mut world);
+        history.execute(SetMaterial::new(entity, 2), &mut world);
+        assert_eq!(material_index(&world, entity), 2);
+
+        assert!(history.undo(&mut world));
+        assert_eq!(material_index(&world, entity), 1);
+        assert!(history.undo(&mut world));
+        assert_eq!(material_index(&world, entity), 0);
+        assert!(!history.undo(&mut world));
+
+        assert!(history.redo(&mut world));
+        assert_eq!(material_index(&world, entity), 1);
+
+        // New commands drop whatever was left to redo.
+        history.execute(SetMaterial::new(entity, 5), &mut world);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn test_history_max_len() {
+        let mut world = Universe::new().create_world();
+        let entity = world.insert((), vec![(components::Material::new(0),)])[0];
+        let mut history = History::new(2);
+
+        for index in 1..5 {
+            history.execute(SetMaterial::new(entity, index), &mut world);
+        }
+        assert_eq!(history.undo_names().len(), 2);
+    }
+}
@@ -0,0 +1,157 @@
+use legion::prelude::*;
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+
+use crate::{
+    assets::AssetError,
+    scene::{WorldDiff, WorldSnapshot},
+};
+
+/// Lets an editor running in another process push changes into a running game.
+/// The game binds a `LiveLinkServer` and calls `apply` once per update, the editor connects
+/// with a `LiveLinkClient` and calls `sync` whenever it's world changes.
+/// Each message is a `WorldDiff` written as RON on a single line.
+/// ```ignore
+/// // In the game.
+/// let mut live_link = LiveLinkServer::bind("127.0.0.1:7878")?;
+/// live_link.apply(&mut app.current_scene.world, &app.resources)?;
+///
+/// // In the editor.
+/// let mut live_link = LiveLinkClient::connect("127.0.0.1:7878")?;
+/// live_link.sync(&app.current_scene.world, &app.resources)?;
+/// ```
+pub struct LiveLinkServer {
+    listener: TcpListener,
+    // Each client keeps the part of a line that's arrived so far. It's kept as bytes since a
+    // read can stop in the middle of a character.
+    clients: Vec<(BufReader<TcpStream>, Vec<u8>)>,
+}
+
+impl LiveLinkServer {
+    pub fn bind<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    /// Accepts new connections and returns the diffs that arrived since the last call.
+    /// Never blocks, clients that disconnect or error are dropped.
+    pub fn poll(&mut self) -> Vec<WorldDiff> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, address)) => {
+                    if let Err(error) = stream.set_nonblocking(true) {
+                        log::warn!("Live Link: couldn't set up {}: {}", address, error);
+                        continue;
+                    }
+                    log::info!("Live Link: {} connected.", address);
+                    self.clients.push((BufReader::new(stream), Vec::new()));
+                }
+                Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    log::warn!("Live Link: accept failed: {}", error);
+                    break;
+                }
+            }
+        }
+
+        let mut diffs = Vec::new();
+        let mut clients = Vec::with_capacity(self.clients.len());
+        for (mut client, mut line) in self.clients.drain(..) {
+            let connected = loop {
+                match client.read_until(b'\n', &mut line) {
+                    Ok(0) => break false,
+                    Ok(_) if line.ends_with(b"\n") => {
+                        let diff = std::str::from_utf8(&line)
+                            .map_err(|error| error.to_string())
+                            .and_then(|line| {
+                                WorldDiff::from_ron(line.trim_end())
+                                    .map_err(|error| error.to_string())
+                            });
+                        match diff {
+                            Ok(diff) => diffs.push(diff),
+                            Err(error) => log::warn!("Live Link: bad message: {}", error),
+                        }
+                        line.clear();
+                    }
+                    Ok(_) => (),
+                    Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => break true,
+                    Err(error) => {
+                        log::warn!("Live Link: client dropped: {}", error);
+                        break false;
+                    }
+                }
+            };
+            if connected {
+                clients.push((client, line));
+            }
+        }
+        self.clients = clients;
+        diffs
+    }
+
+    /// Polls and applies every diff that arrived, returns how many were applied.
+    /// A diff that fails doesn't stop the ones after it, the first error is returned once
+    /// they've all been applied.
+    pub fn apply(&mut self, world: &mut World, resources: &Resources) -> Result<usize, AssetError> {
+        let mut first_error = None;
+        let mut applied = 0;
+        for diff in self.poll() {
+            match diff.apply(world, resources) {
+                Ok(()) => applied += 1,
+                Err(error) => {
+                    log::warn!("Live Link: couldn't apply a diff: {}", error);
+                    first_error = first_error.or(Some(error));
+                }
+            }
+        }
+        match first_error {
+            Some(error) => Err(error),
+            None => Ok(applied),
+        }
+    }
+}
+
+/// The editor side of the live link, see `LiveLinkServer`.
+pub struct LiveLinkClient {
+    stream: TcpStream,
+    last_sent: WorldSnapshot,
+}
+
+impl LiveLinkClient {
+    /// Nothing has been sent yet, so the first `sync` sends every named entity.
+    pub fn connect<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            last_sent: WorldSnapshot::default(),
+        })
+    }
+
+    /// Sends whatever changed since the last sync, returns false if there was nothing to send.
+    pub fn sync(&mut self, world: &World, resources: &Resources) -> io::Result<bool> {
+        let snapshot = WorldSnapshot::from_world(world, resources);
+        let diff = self.last_sent.diff(&snapshot);
+        if diff.is_empty() {
+            return Ok(false);
+        }
+        self.send(&diff)?;
+        self.last_sent = snapshot;
+        Ok(true)
+    }
+
+    /// Sends a diff as is, it's not included in what the next `sync` compares against.
+    pub fn send(&mut self, diff: &WorldDiff) -> io::Result<()> {
+        let message = diff
+            .to_ron()
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+        self.stream.write_all(message.as_bytes())?;
+        self.stream.write_all(b"\n")
+    }
+}
@@ -1,7 +1,34 @@
 pub mod components;
 pub mod entities;
+pub mod history;
 pub mod resources;
 pub mod systems;
 
 mod scene;
 pub use scene::Scene;
+
+mod scene_desc;
+pub use scene_desc::{
+    CameraDesc, EntityDesc, LightDesc, ProjectionDesc, SceneDesc, TransformDesc,
+};
+
+mod timeline;
+pub use timeline::{Key, Timeline, Track};
+
+mod world_diff;
+pub use world_diff::{WorldDiff, WorldSnapshot};
+
+mod live_link;
+pub use live_link::{LiveLinkClient, LiveLinkServer};
+
+mod clone_registry;
+pub use clone_registry::{CloneRegistry, EntitySnapshot};
+
+mod component_registry;
+pub use component_registry::ComponentRegistry;
+
+mod removal_hooks;
+pub use removal_hooks::RemovalHooks;
+
+mod bundles;
+pub use bundles::{spawn_batch, Bundle, CameraBundle, LightBundle, MeshBundle};
@@ -0,0 +1,120 @@
+use legion::prelude::*;
+use std::any::TypeId;
+
+use crate::scene::components;
+
+type ReleaseFn = Box<dyn Fn(&World, &Resources, Entity) + Send + Sync>;
+
+/// Runs cleanup code when components are removed, for example freeing GPU data.
+/// Hooks only run when entities or components are removed through `RemovalHooks`,
+/// calling `world.delete` directly skips them.
+/// Engine components are registered by default, user components can be added with `register`.
+pub struct RemovalHooks {
+    types: Vec<TypeId>,
+    release_fns: Vec<ReleaseFn>,
+}
+
+impl Default for RemovalHooks {
+    fn default() -> Self {
+        let mut hooks = Self {
+            types: Vec::new(),
+            release_fns: Vec::new(),
+        };
+        hooks.register::<components::Transform, _>(|transform, resources| {
+            components::Transform::release_bindings(resources, transform.index);
+        });
+        hooks.register::<components::MaterialInstance, _>(|instance, resources| {
+            if let Some(slot) = instance.slot {
+                let mut resource_manager = resources
+                    .get_mut::<crate::graphics::resources::GPUResourceManager>()
+                    .unwrap();
+                resource_manager.remove_multi_bind_group("pbr_instance", slot);
+                resource_manager.remove_multi_buffer("pbr_instance", slot);
+            }
+        });
+        hooks.register::<components::PaintTexture, _>(|paint, resources| {
+            if let Some(image) = paint.image.as_ref() {
+                let mut asset_manager = resources.get_mut::<crate::AssetManager>().unwrap();
+                asset_manager.remove_image(image.name());
+            }
+        });
+        hooks.register::<components::Animator, _>(|animator, resources| {
+            if let Some(slot) = animator.slot {
+                let mut resource_manager = resources
+                    .get_mut::<crate::graphics::resources::GPUResourceManager>()
+                    .unwrap();
+                resource_manager.remove_multi_bind_group("skinned_transform", slot);
+                resource_manager.remove_multi_buffer("skinned_joints", slot);
+            }
+        });
+        hooks
+    }
+}
+
+impl RemovalHooks {
+    /// Registers a function that runs right before a component of type `T` is removed.
+    pub fn register<T, F>(&mut self, release: F)
+    where
+        T: Component,
+        F: Fn(&T, &Resources) + Send + Sync + 'static,
+    {
+        let type_id = TypeId::of::<T>();
+        if self.types.contains(&type_id) {
+            log::warn!(
+                "Removal Hooks: {} is already registered.",
+                std::any::type_name::<T>()
+            );
+            return;
+        }
+
+        self.types.push(type_id);
+        self.release_fns
+            .push(Box::new(move |world, resources, entity| {
+                if let Some(component) = world.get_component::<T>(entity) {
+                    release(&component, resources);
+                }
+            }));
+    }
+
+    /// Removes a single component from an entity, running it's hook first.
+    pub fn remove_component<T: Component>(
+        &self,
+        world: &mut World,
+        resources: &Resources,
+        entity: Entity,
+    ) -> bool {
+        let type_id = TypeId::of::<T>();
+        if let Some(index) = self.types.iter().position(|t| *t == type_id) {
+            (self.release_fns[index])(world, resources, entity);
+        }
+        world.remove_component::<T>(entity).is_ok()
+    }
+
+    /// Deletes an entity after running the hooks for all of it's components.
+    /// Children are left alone, see `despawn_recursive`.
+    pub fn despawn(&self, world: &mut World, resources: &Resources, entity: Entity) -> bool {
+        if !world.is_alive(entity) {
+            return false;
+        }
+        for release in self.release_fns.iter() {
+            release(world, resources, entity);
+        }
+        world.delete(entity)
+    }
+
+    /// Deletes an entity and all of it's children.
+    pub fn despawn_recursive(&self, world: &mut World, resources: &Resources, entity: Entity) {
+        let query = <Read<components::Parent>>::query();
+        let children: Vec<Entity> = query
+            .iter_entities(world)
+            .filter(|(_, parent)| parent.0 == entity)
+            .map(|(child, _)| child)
+            .collect();
+
+        // Delete the parent first so a parent cycle can't recurse forever.
+        self.despawn(world, resources, entity);
+        for child in children {
+            self.despawn_recursive(world, resources, child);
+        }
+    }
+}
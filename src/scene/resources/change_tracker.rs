@@ -0,0 +1,149 @@
+use legion::prelude::*;
+use std::collections::HashMap;
+
+/// Keeps track of which entities had a component added, changed or removed since the last update.
+/// Insert one as a resource and add `systems::change_tracker::create::<T>()` to the start of your
+/// schedule, systems that run after it can then skip entities that didn't change.
+/// Changes are found by comparing against a snapshot of the component from the last update.
+pub struct ChangeTracker<T: Tracked> {
+    values: HashMap<Entity, T::Snapshot>,
+    added: Vec<Entity>,
+    changed: Vec<Entity>,
+    removed: Vec<Entity>,
+}
+
+/// How a `ChangeTracker` remembers a component. Every `Clone + PartialEq` component is its own
+/// snapshot, components that can't be cloned like `Transform` snapshot the parts that matter.
+pub trait Tracked: Component {
+    type Snapshot: PartialEq + Send + Sync + 'static;
+
+    fn snapshot(&self) -> Self::Snapshot;
+}
+
+impl<T: Component + Clone + PartialEq> Tracked for T {
+    type Snapshot = T;
+
+    fn snapshot(&self) -> T {
+        self.clone()
+    }
+}
+
+impl<T: Tracked> Default for ChangeTracker<T> {
+    fn default() -> Self {
+        Self {
+            values: HashMap::new(),
+            added: Vec::new(),
+            changed: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+}
+
+impl<T: Tracked> ChangeTracker<T> {
+    /// Entities that got the component since the last update.
+    pub fn added(&self) -> &[Entity] {
+        &self.added
+    }
+
+    /// Entities whose component was modified since the last update, doesn't include added ones.
+    pub fn changed(&self) -> &[Entity] {
+        &self.changed
+    }
+
+    /// Entities that lost the component or were deleted since the last update.
+    pub fn removed(&self) -> &[Entity] {
+        &self.removed
+    }
+
+    /// Returns true if the entity's component was added or changed since the last update.
+    pub fn is_dirty(&self, entity: Entity) -> bool {
+        self.added.contains(&entity) || self.changed.contains(&entity)
+    }
+
+    pub(crate) fn update<I>(&mut self, components: I)
+    where
+        I: Iterator<Item = (Entity, T::Snapshot)>,
+    {
+        self.added.clear();
+        self.changed.clear();
+        self.removed.clear();
+
+        let mut values = HashMap::with_capacity(self.values.len());
+        for (entity, snapshot) in components {
+            match self.values.remove(&entity) {
+                Some(previous) if previous == snapshot => (),
+                Some(_) => self.changed.push(entity),
+                None => self.added.push(entity),
+            }
+            values.insert(entity, snapshot);
+        }
+
+        // Whatever wasn't seen this time around is gone.
+        self.removed.extend(self.values.keys().copied());
+        self.values = values;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::components::{Name, Transform};
+    use nalgebra_glm::{Mat4, Quat, Vec3};
+
+    fn update<T: Tracked>(tracker: &mut ChangeTracker<T>, world: &World) {
+        let query = <Read<T>>::query();
+        tracker.update(
+            query
+                .iter_entities(world)
+                .map(|(entity, component)| (entity, component.snapshot())),
+        );
+    }
+
+    #[test]
+    fn test_added_changed_removed() {
+        let mut world = Universe::new().create_world();
+        let mut tracker = ChangeTracker::<Name>::default();
+        let first = world.insert((), vec![(Name::new("first"),)])[0];
+        let second = world.insert((), vec![(Name::new("second"),)])[0];
+
+        update(&mut tracker, &world);
+        assert_eq!(tracker.added().len(), 2);
+        assert!(tracker.is_dirty(first));
+
+        update(&mut tracker, &world);
+        assert!(tracker.added().is_empty());
+        assert!(!tracker.is_dirty(first));
+
+        *world.get_component_mut::<Name>(first).unwrap() = Name::new("renamed");
+        world.delete(second);
+        update(&mut tracker, &world);
+        assert_eq!(tracker.changed(), &[first]);
+        assert_eq!(tracker.removed(), &[second]);
+    }
+    #[test]
+    fn test_transform() {
+        let mut world = Universe::new().create_world();
+        let mut tracker = ChangeTracker::<Transform>::default();
+        let transform = Transform {
+            index: 0,
+            position: Vec3::zeros(),
+            scale: Vec3::new(1.0, 1.0, 1.0),
+            rotation: Quat::identity(),
+            matrix: Mat4::identity(),
+        };
+        let entity = world.insert((), vec![(transform,)])[0];
+
+        update(&mut tracker, &world);
+        assert_eq!(tracker.added(), &[entity]);
+
+        update(&mut tracker, &world);
+        assert!(!tracker.is_dirty(entity));
+
+        world
+            .get_component_mut::<Transform>(entity)
+            .unwrap()
+            .position = Vec3::new(1.0, 0.0, 0.0);
+        update(&mut tracker, &world);
+        assert_eq!(tracker.changed(), &[entity]);
+    }
+}
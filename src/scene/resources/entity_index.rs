@@ -0,0 +1,164 @@
+use legion::prelude::*;
+use std::collections::HashMap;
+
+use crate::{
+    core::Uuid,
+    scene::{
+        components::{EntityId, Name, Tags},
+        resources::{ChangeTracker, Tracked},
+    },
+};
+
+/// Lookup tables from names and tags to entities.
+/// The engine updates the index every time the scene updates, so changes made during a frame
+/// show up on the next update. Only entities whose name, tags or id were added, changed or
+/// removed since then are moved around in the tables.
+#[derive(Default)]
+pub struct EntityIndex {
+    names: HashMap<String, Vec<Entity>>,
+    tags: HashMap<String, Vec<Entity>>,
+    ids: HashMap<Uuid, Entity>,
+    // What each entity is indexed under, so it can be taken out again when that changes.
+    entity_names: HashMap<Entity, String>,
+    entity_tags: HashMap<Entity, Vec<String>>,
+    entity_ids: HashMap<Entity, Uuid>,
+    name_changes: ChangeTracker<Name>,
+    tag_changes: ChangeTracker<Tags>,
+    id_changes: ChangeTracker<EntityId>,
+}
+
+impl EntityIndex {
+    /// Finds an entity by it's name.
+    pub fn find_by_name(&self, name: &str) -> Option<Entity> {
+        self.names
+            .get(name)
+            .and_then(|entities| entities.first().copied())
+    }
+
+    /// Finds all entities that share a name.
+    pub fn find_all_by_name(&self, name: &str) -> &[Entity] {
+        self.names
+            .get(name)
+            .map(|entities| entities.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Finds the entity with an `EntityId`.
+    pub fn find_by_id(&self, id: Uuid) -> Option<Entity> {
+        self.ids.get(&id).copied()
+    }
+
+    /// Iterates over all of the entities with a given tag.
+    pub fn iter_with_tag<'a>(&'a self, tag: &str) -> impl Iterator<Item = Entity> + 'a {
+        self.tags
+            .get(tag)
+            .into_iter()
+            .flat_map(|entities| entities.iter().copied())
+    }
+
+    pub(crate) fn update(&mut self, world: &World) {
+        track(&mut self.name_changes, world);
+        track(&mut self.tag_changes, world);
+        track(&mut self.id_changes, world);
+
+        let changes = &self.name_changes;
+        for entity in changes.removed().iter().chain(changes.changed()) {
+            if let Some(name) = self.entity_names.remove(entity) {
+                unindex(&mut self.names, &name, *entity);
+            }
+        }
+        for entity in changes.added().iter().chain(changes.changed()) {
+            if let Some(name) = world.get_component::<Name>(*entity) {
+                index(&mut self.names, &name.0, *entity);
+                self.entity_names.insert(*entity, name.0.clone());
+            }
+        }
+
+        let changes = &self.tag_changes;
+        for entity in changes.removed().iter().chain(changes.changed()) {
+            for tag in self.entity_tags.remove(entity).unwrap_or_default() {
+                unindex(&mut self.tags, &tag, *entity);
+            }
+        }
+        for entity in changes.added().iter().chain(changes.changed()) {
+            if let Some(tags) = world.get_component::<Tags>(*entity) {
+                for tag in tags.0.iter() {
+                    index(&mut self.tags, tag, *entity);
+                }
+                self.entity_tags.insert(*entity, tags.0.clone());
+            }
+        }
+
+        let changes = &self.id_changes;
+        for entity in changes.removed().iter().chain(changes.changed()) {
+            if let Some(id) = self.entity_ids.remove(entity) {
+                // A copy might have taken the id over already.
+                if self.ids.get(&id) == Some(entity) {
+                    self.ids.remove(&id);
+                }
+            }
+        }
+        for entity in changes.added().iter().chain(changes.changed()) {
+            if let Some(id) = world.get_component::<EntityId>(*entity) {
+                self.ids.insert(id.0, *entity);
+                self.entity_ids.insert(*entity, id.0);
+            }
+        }
+    }
+}
+
+fn track<T: Tracked>(tracker: &mut ChangeTracker<T>, world: &World) {
+    let query = <Read<T>>::query();
+    tracker.update(
+        query
+            .iter_entities(world)
+            .map(|(entity, component)| (entity, component.snapshot())),
+    );
+}
+
+fn index(table: &mut HashMap<String, Vec<Entity>>, key: &str, entity: Entity) {
+    table
+        .entry(key.to_string())
+        .or_insert_with(Vec::new)
+        .push(entity);
+}
+
+fn unindex(table: &mut HashMap<String, Vec<Entity>>, key: &str, entity: Entity) {
+    if let Some(entities) = table.get_mut(key) {
+        entities.retain(|current| *current != entity);
+        if entities.is_empty() {
+            table.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incremental_update() {
+        let mut world = Universe::new().create_world();
+        let mut index = EntityIndex::default();
+        let player = world.insert((), vec![(Name::new("player"), Tags::new(vec!["hero"]))])[0];
+        let enemy = world.insert((), vec![(Name::new("enemy"), Tags::new(vec!["foe"]))])[0];
+
+        index.update(&world);
+        assert_eq!(index.find_by_name("player"), Some(player));
+        assert_eq!(index.iter_with_tag("foe").collect::<Vec<_>>(), vec![enemy]);
+
+        *world.get_component_mut::<Name>(player).unwrap() = Name::new("hero");
+        *world.get_component_mut::<Tags>(enemy).unwrap() = Tags::new(vec!["foe", "boss"]);
+        index.update(&world);
+        assert_eq!(index.find_by_name("player"), None);
+        assert_eq!(index.find_by_name("hero"), Some(player));
+        assert_eq!(index.iter_with_tag("boss").collect::<Vec<_>>(), vec![enemy]);
+        assert_eq!(index.iter_with_tag("foe").count(), 1);
+
+        world.delete(enemy);
+        index.update(&world);
+        assert_eq!(index.find_by_name("enemy"), None);
+        assert_eq!(index.iter_with_tag("foe").count(), 0);
+        assert_eq!(index.find_all_by_name("hero"), &[player]);
+    }
+}
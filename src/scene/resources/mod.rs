@@ -1,2 +1,22 @@
 #[derive(Default)]
 pub struct DeltaTime(pub f32);
+
+mod entity_index;
+pub use entity_index::EntityIndex;
+
+mod pointer;
+pub use pointer::{Pointer, PointerEvent};
+
+mod visibility;
+pub use visibility::Visibility;
+
+mod change_tracker;
+pub use change_tracker::{ChangeTracker, Tracked};
+
+mod sequencer;
+pub use sequencer::{Sequencer, SequencerEvent};
+
+#[cfg(feature = "physics2d")]
+mod physics2d;
+#[cfg(feature = "physics2d")]
+pub use physics2d::{CollisionEvent2D, Phase2D, Physics2D};
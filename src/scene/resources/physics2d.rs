@@ -0,0 +1,393 @@
+use crossbeam::channel::{self, Receiver};
+use legion::prelude::Entity;
+use nalgebra_glm::{Vec2, Vec3};
+use rapier2d::{
+    dynamics::{
+        BodyStatus, IntegrationParameters, JointSet, RigidBodyBuilder, RigidBodyHandle,
+        RigidBodySet,
+    },
+    geometry::{
+        BroadPhase, ColliderBuilder, ColliderHandle, ColliderSet, ContactEvent, NarrowPhase,
+        Proximity, ProximityEvent,
+    },
+    na::{Isometry2, Vector2},
+    pipeline::{ChannelEventCollector, PhysicsPipeline},
+};
+use std::collections::{HashMap, HashSet};
+
+use crate::scene::components::{BodyType2D, Collider2D, RigidBody2D, Shape2D, Transform};
+
+/// Where a pair of colliders is at in touching each other.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Phase2D {
+    /// They started touching during the last update.
+    Enter,
+    /// They were already touching and still are.
+    Stay,
+    /// They stopped touching during the last update, or one of them was removed.
+    Exit,
+}
+
+/// A pair of colliders touching during the last `Physics2D` update, there's one event per
+/// pair of colliders so entities with several shapes can get more than one.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CollisionEvent2D {
+    /// Two solid colliders.
+    Contact(Entity, Entity, Phase2D),
+    /// A sensor overlapping another collider, for checkpoints, damage zones and other trigger
+    /// volumes. The sensor's entity comes first.
+    Trigger(Entity, Entity, Phase2D),
+}
+
+impl CollisionEvent2D {
+    pub fn entities(&self) -> (Entity, Entity) {
+        match self {
+            CollisionEvent2D::Contact(a, b, _) => (*a, *b),
+            CollisionEvent2D::Trigger(a, b, _) => (*a, *b),
+        }
+    }
+
+    pub fn phase(&self) -> Phase2D {
+        match self {
+            CollisionEvent2D::Contact(_, _, phase) => *phase,
+            CollisionEvent2D::Trigger(_, _, phase) => *phase,
+        }
+    }
+}
+
+// Two touching colliders' entities and if one of them is a sensor.
+#[derive(Debug, Copy, Clone)]
+struct Touch {
+    entities: (Entity, Entity),
+    trigger: bool,
+}
+
+impl Touch {
+    fn event(&self, phase: Phase2D) -> CollisionEvent2D {
+        let (a, b) = self.entities;
+        if self.trigger {
+            CollisionEvent2D::Trigger(a, b, phase)
+        } else {
+            CollisionEvent2D::Contact(a, b, phase)
+        }
+    }
+}
+
+/// The 2D physics world, a resource. Remember to add `scene::systems::physics2d::create()` to
+/// your scene's schedule, it creates the bodies and moves the transforms.
+/// The simulation runs in fixed steps so it behaves the same at any frame rate, at most
+/// `max_steps` per update so a long frame doesn't make the next one longer.
+/// Contacts and sensor overlaps are reported by `events`, each update replaces them.
+/// Only available with the `physics2d` feature.
+pub struct Physics2D {
+    pub gravity: Vec2,
+    /// Seconds per step.
+    pub timestep: f32,
+    pub max_steps: u32,
+    accumulator: f32,
+    pipeline: PhysicsPipeline,
+    integration_parameters: IntegrationParameters,
+    broad_phase: BroadPhase,
+    narrow_phase: NarrowPhase,
+    bodies: RigidBodySet,
+    colliders: ColliderSet,
+    joints: JointSet,
+    // The body of each entity, bodies are removed with their entity or `RigidBody2D`.
+    entities: HashMap<Entity, RigidBodyHandle>,
+    // The entity each collider belongs to and if it's a sensor, to tell who the events are
+    // about.
+    collider_entities: HashMap<ColliderHandle, (Entity, bool)>,
+    // Colliders touching after the last update, rapier keeps the order of each pair.
+    touching: HashMap<(ColliderHandle, ColliderHandle), Touch>,
+    event_collector: ChannelEventCollector,
+    contact_events: Receiver<ContactEvent>,
+    proximity_events: Receiver<ProximityEvent>,
+    events: Vec<CollisionEvent2D>,
+}
+
+impl Default for Physics2D {
+    fn default() -> Self {
+        let (proximity_sender, proximity_events) = channel::unbounded();
+        let (contact_sender, contact_events) = channel::unbounded();
+        Self {
+            gravity: Vec2::new(0.0, -9.81),
+            timestep: 1.0 / 60.0,
+            max_steps: 4,
+            accumulator: 0.0,
+            pipeline: PhysicsPipeline::new(),
+            integration_parameters: IntegrationParameters::default(),
+            broad_phase: BroadPhase::new(),
+            narrow_phase: NarrowPhase::new(),
+            bodies: RigidBodySet::new(),
+            colliders: ColliderSet::new(),
+            joints: JointSet::new(),
+            entities: HashMap::new(),
+            collider_entities: HashMap::new(),
+            touching: HashMap::new(),
+            event_collector: ChannelEventCollector::new(proximity_sender, contact_sender),
+            contact_events,
+            proximity_events,
+            events: Vec::new(),
+        }
+    }
+}
+
+impl Physics2D {
+    /// Every pair of colliders that touched during the last update, with whether they just
+    /// started, are still touching or just stopped.
+    pub fn events(&self) -> &[CollisionEvent2D] {
+        &self.events
+    }
+
+    /// The events involving `entity`, on either side.
+    pub fn events_for(&self, entity: Entity) -> impl Iterator<Item = &CollisionEvent2D> {
+        self.events.iter().filter(move |event| {
+            let (a, b) = event.entities();
+            a == entity || b == entity
+        })
+    }
+
+    /// The body's velocity in world units per second.
+    pub fn velocity(&self, body: &RigidBody2D) -> Option<Vec2> {
+        let body = self.bodies.get(body.handle?)?;
+        Some(Vec2::new(body.linvel.x, body.linvel.y))
+    }
+
+    /// Replaces the velocity of a dynamic body, for jumps and character movement.
+    pub fn set_velocity(&mut self, body: &RigidBody2D, velocity: Vec2) {
+        if let Some(mut body) = body.handle.and_then(|handle| self.bodies.get_mut(handle)) {
+            body.linvel = Vector2::new(velocity.x, velocity.y);
+            body.wake_up(true);
+        }
+    }
+
+    /// Pushes a dynamic body, the change in velocity depends on it's mass.
+    pub fn apply_impulse(&mut self, body: &RigidBody2D, impulse: Vec2) {
+        if let Some(mut body) = body.handle.and_then(|handle| self.bodies.get_mut(handle)) {
+            body.apply_impulse(Vector2::new(impulse.x, impulse.y));
+        }
+    }
+
+    /// Number of bodies in the world.
+    pub fn body_count(&self) -> usize {
+        self.bodies.len()
+    }
+
+    // True if the entity's body is in this world, bodies cloned with their entity or left
+    // over from another scene aren't.
+    pub(crate) fn has_body(&self, entity: Entity, body: &RigidBody2D) -> bool {
+        body.handle.is_some() && self.entities.get(&entity) == body.handle.as_ref()
+    }
+
+    /// Creates the body and colliders for an entity at it's transform.
+    pub(crate) fn insert(
+        &mut self,
+        entity: Entity,
+        body: &RigidBody2D,
+        collider: &Collider2D,
+        transform: &Transform,
+    ) -> RigidBodyHandle {
+        self.remove(entity);
+        let status = match body.body_type {
+            BodyType2D::Static => BodyStatus::Static,
+            BodyType2D::Dynamic => BodyStatus::Dynamic,
+            BodyType2D::Kinematic => BodyStatus::Kinematic,
+        };
+        let handle = self.bodies.insert(
+            RigidBodyBuilder::new(status)
+                .position(isometry(transform))
+                .build(),
+        );
+
+        let scale = Vec2::new(transform.scale.x.abs(), transform.scale.y.abs());
+        let sensor = collider.sensor;
+        for (offset, shape) in collider.shapes.iter() {
+            let builder = match *shape {
+                Shape2D::Rect(half_size) => {
+                    ColliderBuilder::cuboid(half_size.x * scale.x, half_size.y * scale.y)
+                }
+                Shape2D::Circle(radius) => ColliderBuilder::ball(radius * scale.x.max(scale.y)),
+                Shape2D::Capsule {
+                    half_height,
+                    radius,
+                } => ColliderBuilder::capsule_y(half_height * scale.y, radius * scale.x),
+            };
+            let collider = builder
+                .translation(offset.x * transform.scale.x, offset.y * transform.scale.y)
+                .friction(collider.friction)
+                .restitution(collider.restitution)
+                .density(collider.density)
+                .sensor(collider.sensor)
+                .build();
+            let collider = self.colliders.insert(collider, handle, &mut self.bodies);
+            self.collider_entities.insert(collider, (entity, sensor));
+        }
+
+        // The colliders set the mass, so the rotation is locked after adding them.
+        if body.fixed_rotation {
+            if let Some(mut body) = self.bodies.get_mut(handle) {
+                body.mass_properties.inv_principal_inertia_sqrt = 0.0;
+                body.angvel = 0.0;
+            }
+        }
+        self.entities.insert(entity, handle);
+        handle
+    }
+
+    /// Removes the entity's body and it's colliders.
+    pub(crate) fn remove(&mut self, entity: Entity) {
+        if let Some(handle) = self.entities.remove(&entity) {
+            self.bodies
+                .remove(handle, &mut self.colliders, &mut self.joints);
+            self.collider_entities
+                .retain(|_, (owner, _)| *owner != entity);
+        }
+    }
+
+    /// Removes the bodies of entities `keep` returns false for.
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(Entity) -> bool) {
+        let removed: Vec<Entity> = self
+            .entities
+            .keys()
+            .copied()
+            .filter(|entity| !keep(*entity))
+            .collect();
+        for entity in removed {
+            self.remove(entity);
+        }
+    }
+
+    /// Kinematic bodies move to their transform over the next step.
+    pub(crate) fn move_kinematic(&mut self, body: &RigidBody2D, transform: &Transform) {
+        if let Some(mut body) = body.handle.and_then(|handle| self.bodies.get_mut(handle)) {
+            body.set_next_kinematic_position(isometry(transform));
+        }
+    }
+
+    /// Runs as many fixed steps as fit in `delta_time`, returns true if it stepped.
+    /// The events are replaced by the ones from these steps.
+    pub(crate) fn step(&mut self, delta_time: f32) -> bool {
+        self.events.clear();
+        let timestep = self.timestep.max(0.001);
+        self.accumulator = (self.accumulator + delta_time).min(timestep * self.max_steps as f32);
+        self.integration_parameters.set_dt(timestep);
+        let gravity = Vector2::new(self.gravity.x, self.gravity.y);
+        let mut stepped = false;
+        while self.accumulator >= timestep {
+            self.accumulator -= timestep;
+            self.pipeline.step(
+                &gravity,
+                &self.integration_parameters,
+                &mut self.broad_phase,
+                &mut self.narrow_phase,
+                &mut self.bodies,
+                &mut self.colliders,
+                &mut self.joints,
+                &self.event_collector,
+            );
+            stepped = true;
+        }
+        self.collect_events();
+        stepped
+    }
+
+    // Turns rapier's started and stopped events into enter and exit events, pairs that were
+    // already touching and didn't change get a stay event.
+    fn collect_events(&mut self) {
+        let contacts = self.contact_events.try_iter().map(|event| match event {
+            ContactEvent::Started(collider1, collider2) => ((collider1, collider2), true),
+            ContactEvent::Stopped(collider1, collider2) => ((collider1, collider2), false),
+        });
+        let proximities = self.proximity_events.try_iter().filter_map(|event| {
+            let pair = (event.collider1, event.collider2);
+            match (event.prev_status, event.new_status) {
+                (Proximity::Intersecting, Proximity::Intersecting) => None,
+                (_, Proximity::Intersecting) => Some((pair, true)),
+                (Proximity::Intersecting, _) => Some((pair, false)),
+                _ => None,
+            }
+        });
+        let changes: Vec<_> = contacts.chain(proximities).collect();
+
+        let mut changed = HashSet::new();
+        for (pair, started) in changes {
+            changed.insert(pair);
+            if !started {
+                if let Some(touch) = self.touching.remove(&pair) {
+                    self.events.push(touch.event(Phase2D::Exit));
+                }
+                continue;
+            }
+            let touch = match self.touch(pair) {
+                Some(touch) => touch,
+                None => continue,
+            };
+            if self.touching.insert(pair, touch).is_none() {
+                self.events.push(touch.event(Phase2D::Enter));
+            }
+        }
+
+        // Removed colliders don't always get a stopped event.
+        let collider_entities = &self.collider_entities;
+        let removed: Vec<_> = self
+            .touching
+            .keys()
+            .copied()
+            .filter(|(collider1, collider2)| {
+                !collider_entities.contains_key(collider1)
+                    || !collider_entities.contains_key(collider2)
+            })
+            .collect();
+        for pair in removed {
+            changed.insert(pair);
+            let touch = self.touching.remove(&pair).unwrap();
+            self.events.push(touch.event(Phase2D::Exit));
+        }
+
+        for (pair, touch) in self.touching.iter() {
+            if !changed.contains(pair) {
+                self.events.push(touch.event(Phase2D::Stay));
+            }
+        }
+    }
+
+    // The entities of two colliders, a sensor's entity goes first.
+    fn touch(&self, (collider1, collider2): (ColliderHandle, ColliderHandle)) -> Option<Touch> {
+        let (entity1, sensor1) = *self.collider_entities.get(&collider1)?;
+        let (entity2, sensor2) = *self.collider_entities.get(&collider2)?;
+        let entities = if sensor2 && !sensor1 {
+            (entity2, entity1)
+        } else {
+            (entity1, entity2)
+        };
+        Some(Touch {
+            entities,
+            trigger: sensor1 || sensor2,
+        })
+    }
+
+    /// Moves the transform to a dynamic body, keeping it's z and scale.
+    pub(crate) fn sync_transform(&self, body: &RigidBody2D, transform: &mut Transform) {
+        let body = match body.handle.and_then(|handle| self.bodies.get(handle)) {
+            Some(body) if body.is_dynamic() => body,
+            _ => return,
+        };
+        let translation = body.position.translation.vector;
+        transform.position.x = translation.x;
+        transform.position.y = translation.y;
+        transform.rotation = nalgebra_glm::quat_angle_axis(
+            body.position.rotation.angle(),
+            &Vec3::new(0.0, 0.0, 1.0),
+        );
+        transform.update();
+    }
+}
+
+// The transform on the XY plane, only it's rotation around the Z axis is kept.
+fn isometry(transform: &Transform) -> Isometry2<f32> {
+    let x_axis = nalgebra_glm::quat_rotate_vec3(&transform.rotation, &Vec3::new(1.0, 0.0, 0.0));
+    Isometry2::new(
+        Vector2::new(transform.position.x, transform.position.y),
+        x_axis.y.atan2(x_axis.x),
+    )
+}
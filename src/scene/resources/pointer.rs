@@ -0,0 +1,188 @@
+use legion::prelude::*;
+use nalgebra_glm::{Vec2, Vec3, Vec4};
+use std::collections::HashMap;
+
+use crate::{
+    core::input::{Input, MouseButton},
+    graphics::{
+        bounds::Aabb,
+        queries::{ray_aabb, Ray},
+    },
+    scene::components,
+    AssetManager,
+};
+
+const MOUSE_BUTTONS: [MouseButton; 5] = [
+    MouseButton::Left,
+    MouseButton::Middle,
+    MouseButton::Right,
+    MouseButton::X1,
+    MouseButton::X2,
+];
+
+/// Something the pointer did to an entity this frame.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PointerEvent {
+    /// The cursor started hovering over the entity.
+    Enter(Entity),
+    /// The cursor stopped hovering over the entity.
+    Leave(Entity),
+    /// A mouse button was pressed while hovering over the entity.
+    Click(Entity, MouseButton),
+}
+
+impl PointerEvent {
+    pub fn entity(&self) -> Entity {
+        match self {
+            PointerEvent::Enter(entity) => *entity,
+            PointerEvent::Leave(entity) => *entity,
+            PointerEvent::Click(entity, _) => *entity,
+        }
+    }
+}
+
+/// Keeps track of which mesh entity is under the cursor and the pointer events for the current frame.
+/// Meshes are tested using their bounding box.
+/// Events bubble up to the entity's parents, see `events_for`.
+/// Note: This only covers 3D meshes, imgui handles it's own input. Nothing is hovered while the
+/// cursor is over the UI.
+#[derive(Default)]
+pub struct Pointer {
+    /// The entity currently under the cursor.
+    pub hovered: Option<Entity>,
+    /// Where the cursor ray hit the hovered entity's bounds.
+    pub hit_position: Option<Vec3>,
+    /// Set by the application when imgui wants the mouse.
+    pub(crate) ui_has_mouse: bool,
+    events: Vec<PointerEvent>,
+    // The entity each event happened to followed by it's parents.
+    paths: Vec<Vec<Entity>>,
+    bounds: HashMap<String, (Vec3, Vec3)>,
+}
+
+impl Pointer {
+    /// All pointer events for this frame.
+    pub fn events(&self) -> &[PointerEvent] {
+        &self.events
+    }
+
+    /// The longest parent chain events bubble up, guards against parent loops.
+    const MAX_DEPTH: usize = 64;
+
+    /// Pointer events for a specific entity, including events on any of it's descendants.
+    /// Use `PointerEvent::entity` to find out which entity was actually hit.
+    pub fn events_for(&self, entity: Entity) -> impl Iterator<Item = &PointerEvent> {
+        self.events
+            .iter()
+            .zip(self.paths.iter())
+            .filter(move |(_, path)| path.contains(&entity))
+            .map(|(event, _)| event)
+    }
+
+    fn push(&mut self, world: &World, event: PointerEvent) {
+        let mut path = vec![event.entity()];
+        while path.len() < Self::MAX_DEPTH {
+            match world.get_component::<components::Parent>(*path.last().unwrap()) {
+                Some(parent) if !path.contains(&parent.0) => path.push(parent.0),
+                _ => break,
+            }
+        }
+        self.events.push(event);
+        self.paths.push(path);
+    }
+
+    pub(crate) fn update(&mut self, world: &World, resources: &Resources) {
+        self.events.clear();
+        self.paths.clear();
+
+        let hit = if self.ui_has_mouse {
+            None
+        } else {
+            self.cast(world, resources)
+        };
+        let hovered = hit.map(|(entity, _)| entity);
+        self.hit_position = hit.map(|(_, position)| position);
+
+        if hovered != self.hovered {
+            if let Some(previous) = self.hovered {
+                self.push(world, PointerEvent::Leave(previous));
+            }
+            if let Some(current) = hovered {
+                self.push(world, PointerEvent::Enter(current));
+            }
+            self.hovered = hovered;
+        }
+
+        if let (Some(entity), Some(input)) = (self.hovered, resources.get::<Input>()) {
+            for button in MOUSE_BUTTONS.iter() {
+                if input.is_mouse_button_pressed(*button) {
+                    self.push(world, PointerEvent::Click(entity, *button));
+                }
+            }
+        }
+    }
+
+    /// Finds the closest mesh under the cursor.
+    fn cast(&mut self, world: &World, resources: &Resources) -> Option<(Entity, Vec3)> {
+        let asset_manager = resources.get::<AssetManager>()?;
+        let input = resources.get::<Input>()?;
+        let sc_desc = resources.get::<wgpu::SwapChainDescriptor>()?;
+        let screen_size = Vec2::new(sc_desc.width as f32, sc_desc.height as f32);
+
+        let camera_query = <Read<components::CameraData>>::query();
+        let camera = camera_query.iter(world).find(|camera| camera.active)?;
+        let (origin, direction) = camera.screen_to_ray(input.mouse_position, screen_size);
+
+        let mut closest: Option<(Entity, f32)> = None;
+        let visibility = resources.get::<super::Visibility>();
+        let mesh_query = <(Read<components::Mesh>, Read<components::Transform>)>::query();
+        for (entity, (mesh, transform)) in mesh_query.iter_entities(world) {
+            if visibility
+                .as_ref()
+                .map_or(false, |visibility| !visibility.is_visible(entity))
+            {
+                continue;
+            }
+            let (min, max) = *self
+                .bounds
+                .entry(mesh.mesh_name.clone())
+                .or_insert_with(|| {
+                    let asset_mesh = asset_manager.get_mesh(mesh.mesh_name.clone());
+                    mesh_bounds(asset_mesh)
+                });
+
+            // Move the ray into the mesh's local space so we can test against it's bounds.
+            let world_to_local = nalgebra_glm::inverse(&transform.calculate_matrix());
+            let local_origin = world_to_local * Vec4::new(origin.x, origin.y, origin.z, 1.0);
+            let local_direction =
+                world_to_local * Vec4::new(direction.x, direction.y, direction.z, 0.0);
+
+            let local_ray = Ray {
+                origin: local_origin.xyz(),
+                direction: local_direction.xyz(),
+            };
+            if let Some(distance) = ray_aabb(&local_ray, &Aabb::new(min, max)) {
+                // The local direction isn't normalized so distances are comparable across meshes.
+                if closest.map_or(true, |(_, closest)| distance < closest) {
+                    closest = Some((entity, distance));
+                }
+            }
+        }
+
+        closest.map(|(entity, distance)| (entity, origin + direction * distance))
+    }
+}
+
+fn mesh_bounds(mesh: &crate::graphics::mesh::Mesh) -> (Vec3, Vec3) {
+    let mut min = Vec3::new(std::f32::MAX, std::f32::MAX, std::f32::MAX);
+    let mut max = Vec3::new(std::f32::MIN, std::f32::MIN, std::f32::MIN);
+    for vertex in mesh
+        .sub_meshes
+        .iter()
+        .flat_map(|sub_mesh| sub_mesh.vertices.iter())
+    {
+        min = nalgebra_glm::min2(&min, &vertex.position);
+        max = nalgebra_glm::max2(&max, &vertex.position);
+    }
+    (min, max)
+}
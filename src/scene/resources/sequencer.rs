@@ -0,0 +1,218 @@
+use crate::scene::timeline::{Timeline, Track};
+
+/// Something a playing timeline wants the app to do, see `Sequencer::drain_events`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SequencerEvent {
+    /// A key on an `Audio` track was reached.
+    Audio(String),
+    /// A key on an `Events` track was reached.
+    Event(String),
+    /// The timeline reached it's end and stopped, looping timelines never finish.
+    Finished,
+}
+
+/// Plays a `Timeline`, a resource. Remember to add `scene::systems::sequencer::create()` to
+/// your scene's schedule, it moves the entities and cuts between cameras.
+/// Seeking applies the timeline at the new time without firing the keys in between, so it can
+/// be used to scrub through a cutscene while editing it.
+pub struct Sequencer {
+    timeline: Option<Timeline>,
+    time: f32,
+    pub speed: f32,
+    pub looping: bool,
+    playing: bool,
+    // Keys up to this time already fired, None fires the ones at the start too.
+    fired_until: Option<f32>,
+    // Seeking while paused still has to be applied once.
+    dirty: bool,
+    events: Vec<SequencerEvent>,
+}
+
+impl Default for Sequencer {
+    fn default() -> Self {
+        Self {
+            timeline: None,
+            time: 0.0,
+            speed: 1.0,
+            looping: false,
+            playing: false,
+            fired_until: None,
+            dirty: false,
+            events: Vec::new(),
+        }
+    }
+}
+
+impl Sequencer {
+    /// Starts playing a timeline from the beginning.
+    pub fn play(&mut self, mut timeline: Timeline) {
+        timeline.sort();
+        self.timeline = Some(timeline);
+        self.time = 0.0;
+        self.playing = true;
+        self.fired_until = None;
+        self.dirty = true;
+    }
+
+    /// Stops and forgets the timeline, entities stay where it left them.
+    pub fn stop(&mut self) {
+        self.timeline = None;
+        self.playing = false;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn resume(&mut self) {
+        self.playing = self.timeline.is_some();
+    }
+
+    /// Jumps to `time` in seconds, keys that are skipped over don't fire.
+    pub fn seek(&mut self, time: f32) {
+        let length = self.length();
+        self.time = time.max(0.0).min(length);
+        self.fired_until = Some(self.time);
+        self.dirty = true;
+    }
+
+    pub fn timeline(&self) -> Option<&Timeline> {
+        self.timeline.as_ref()
+    }
+
+    /// Current time in seconds.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn length(&self) -> f32 {
+        self.timeline.as_ref().map_or(0.0, Timeline::length)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// The audio cues and events that were reached since the last call.
+    pub fn drain_events(&mut self) -> impl Iterator<Item = SequencerEvent> + '_ {
+        self.events.drain(..)
+    }
+
+    /// Moves the time forward and fires the keys that were passed, true if the timeline has to
+    /// be applied to the world.
+    pub(crate) fn advance(&mut self, delta_time: f32) -> bool {
+        let length = self.length();
+        if self.timeline.is_none() || !self.playing {
+            return std::mem::replace(&mut self.dirty, false) && self.timeline.is_some();
+        }
+
+        let mut time = (self.time + delta_time * self.speed).max(0.0);
+        let mut finished = false;
+        if time >= length {
+            if self.looping && length > 0.0 {
+                self.fire(length);
+                self.fired_until = None;
+                time = time.rem_euclid(length);
+            } else {
+                time = length;
+                finished = true;
+            }
+        }
+        self.time = time;
+        if self
+            .fired_until
+            .map_or(true, |fired_until| time >= fired_until)
+        {
+            self.fire(time);
+        }
+        self.fired_until = Some(time);
+        if finished {
+            self.playing = false;
+            self.events.push(SequencerEvent::Finished);
+        }
+        self.dirty = false;
+        true
+    }
+
+    // Fires the audio and event keys after `fired_until` up to and including `time`.
+    fn fire(&mut self, time: f32) {
+        let fired_until = self.fired_until;
+        let reached = |key_time: f32| fired_until.map_or(true, |until| key_time > until);
+        let timeline = match self.timeline.as_ref() {
+            Some(timeline) => timeline,
+            None => return,
+        };
+        for track in timeline.tracks.iter() {
+            let (keys, event): (_, fn(String) -> SequencerEvent) = match track {
+                Track::Audio(keys) => (keys, SequencerEvent::Audio),
+                Track::Events(keys) => (keys, SequencerEvent::Event),
+                _ => continue,
+            };
+            let keys = keys
+                .iter()
+                .filter(|key| reached(key.time) && key.time <= time)
+                .map(|key| event(key.value.clone()));
+            self.events.extend(keys);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::timeline::Key;
+
+    fn timeline() -> Timeline {
+        Timeline {
+            duration: 4.0,
+            tracks: vec![Track::Events(vec![
+                Key::new(0.0, "start".to_string()),
+                Key::new(2.0, "middle".to_string()),
+            ])],
+            ..Timeline::default()
+        }
+    }
+
+    fn events(sequencer: &mut Sequencer) -> Vec<SequencerEvent> {
+        sequencer.drain_events().collect()
+    }
+
+    #[test]
+    fn test_sequencer_fires_keys_once() {
+        let mut sequencer = Sequencer::default();
+        sequencer.play(timeline());
+        assert!(sequencer.advance(1.0));
+        assert_eq!(
+            events(&mut sequencer),
+            vec![SequencerEvent::Event("start".into())]
+        );
+        sequencer.advance(0.5);
+        assert!(events(&mut sequencer).is_empty());
+        sequencer.advance(3.0);
+        assert_eq!(
+            events(&mut sequencer),
+            vec![
+                SequencerEvent::Event("middle".into()),
+                SequencerEvent::Finished
+            ]
+        );
+        assert!(!sequencer.is_playing());
+        assert!(!sequencer.advance(1.0));
+    }
+
+    #[test]
+    fn test_sequencer_seek_and_loop() {
+        let mut sequencer = Sequencer::default();
+        sequencer.looping = true;
+        sequencer.play(timeline());
+        sequencer.seek(3.0);
+        sequencer.advance(0.5);
+        assert!(events(&mut sequencer).is_empty());
+        sequencer.advance(1.0);
+        assert_eq!(
+            events(&mut sequencer),
+            vec![SequencerEvent::Event("start".into())]
+        );
+        assert_eq!(sequencer.time(), 0.5);
+    }
+}
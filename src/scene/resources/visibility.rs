@@ -0,0 +1,52 @@
+use legion::prelude::*;
+use std::collections::HashSet;
+
+use crate::scene::components::{Parent, Visible};
+
+/// The computed visibility for every entity, hiding a parent hides all of it's descendants.
+/// Rendering uses this instead of reading `Visible` directly.
+/// Rebuilt by the engine every time the scene updates, like the `EntityIndex`.
+#[derive(Default)]
+pub struct Visibility {
+    hidden: HashSet<Entity>,
+}
+
+impl Visibility {
+    /// The longest parent chain we'll walk, guards against parent loops.
+    const MAX_DEPTH: usize = 64;
+
+    pub fn is_visible(&self, entity: Entity) -> bool {
+        !self.hidden.contains(&entity)
+    }
+
+    pub(crate) fn update(&mut self, world: &World) {
+        self.hidden.clear();
+
+        let visible_query = <Read<Visible>>::query();
+        for (entity, visible) in visible_query.iter_entities(world) {
+            if !visible.0 {
+                self.hidden.insert(entity);
+            }
+        }
+        if self.hidden.is_empty() {
+            return;
+        }
+
+        let parent_query = <Read<Parent>>::query();
+        let mut hidden_children = Vec::new();
+        for (entity, parent) in parent_query.iter_entities(world) {
+            let mut current = parent.0;
+            for _ in 0..Self::MAX_DEPTH {
+                if self.hidden.contains(&current) {
+                    hidden_children.push(entity);
+                    break;
+                }
+                match world.get_component::<Parent>(current) {
+                    Some(parent) => current = parent.0,
+                    None => break,
+                }
+            }
+        }
+        self.hidden.extend(hidden_children);
+    }
+}
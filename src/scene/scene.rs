@@ -1,4 +1,5 @@
-use super::resources;
+use super::{resources, SceneDesc};
+use crate::{app_builder::Stages, AssetError, AssetManager};
 use legion::prelude::*;
 use legion::systems::schedule::Builder;
 
@@ -31,12 +32,72 @@ impl Scene {
         }
     }
 
-    pub(crate) fn update(&mut self, delta_time: f32, resources: &mut Resources) {
+    /// Spawns the entities from a RON `SceneDesc` into this scene's world.
+    /// The path is relative to the asset folder and the meshes it uses have to be loaded already.
+    /// It's read through the asset manager's virtual file system so scenes can live in a pack,
+    /// scenes written by older versions are migrated first.
+    pub fn load<T: Into<String>>(
+        &mut self,
+        path: T,
+        resources: &Resources,
+    ) -> Result<Vec<Entity>, AssetError> {
+        let path = path.into();
+        let source = {
+            let asset_manager = resources.get::<AssetManager>().unwrap();
+            let source = asset_manager.vfs().read_to_string(&path)?;
+            asset_manager
+                .migrations()
+                .migrate("scene", &source)
+                .map_err(|error| AssetError::decode(&path, error))?
+        };
+        let desc = SceneDesc::from_ron(&source).map_err(|error| AssetError::decode(&path, error))?;
+        desc.spawn(&mut self.world, resources)
+    }
+
+    /// Writes the scene's transforms, meshes, materials, lights and cameras to a RON file.
+    /// The path is relative to the asset folder.
+    pub fn save<T: Into<String>>(&self, path: T, resources: &Resources) -> Result<(), AssetError> {
+        let desc = SceneDesc::from_world(&self.world, resources);
+        let path = resources
+            .get::<AssetManager>()
+            .unwrap()
+            .resolve_path(path.into());
+        let path = path.to_string_lossy().to_string();
+        let source = desc
+            .to_ron()
+            .map_err(|error| AssetError::decode(&path, error))?;
+        std::fs::write(&path, source).map_err(|error| AssetError::io(&path, error))
+    }
+
+    pub(crate) fn update(
+        &mut self,
+        delta_time: f32,
+        resources: &mut Resources,
+        stages: &mut Stages,
+    ) {
         {
             let mut delta = resources.get_mut::<resources::DeltaTime>().unwrap();
             *delta = resources::DeltaTime(delta_time);
         }
 
+        {
+            let mut entity_index = resources.get_mut::<resources::EntityIndex>().unwrap();
+            entity_index.update(&self.world);
+        }
+
+        {
+            let mut visibility = resources.get_mut::<resources::Visibility>().unwrap();
+            visibility.update(&self.world);
+        }
+
+        {
+            let mut pointer = resources.get_mut::<resources::Pointer>().unwrap();
+            pointer.update(&self.world, resources);
+        }
+
+        stages.pre_update.execute(&mut self.world, resources);
         self.game_schedule.execute(&mut self.world, resources);
+        stages.update.execute(&mut self.world, resources);
+        stages.post_update.execute(&mut self.world, resources);
     }
 }
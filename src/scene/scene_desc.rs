@@ -0,0 +1,629 @@
+use legion::prelude::*;
+use nalgebra_glm::{Quat, Vec3};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{
+    assets::{AssetError, SCENE_VERSION},
+    core::Uuid,
+    scene::components::{
+        self, camera_data::ProjectionData, DirectionalLightData, PointLightData, SpotLightData,
+    },
+    scene::ComponentRegistry,
+    AssetManager,
+};
+
+/// A level stored as RON, load it with `Scene::load` and write it back out with `Scene::save`.
+/// ```ron
+/// (
+///     version: 1,
+///     entities: [
+///         (
+///             name: Some("floor"),
+///             transform: Some((scale: (10.0, 1.0, 10.0))),
+///             mesh: Some("cube.gltf"),
+///         ),
+///         (
+///             transform: Some((position: (0.0, 5.0, 0.0))),
+///             light: Some(Point(color: (1.0, 0.9, 0.8), intensity: 2.0, range: 15.0)),
+///         ),
+///         (
+///             camera: Some((
+///                 projection: Perspective(fov: 70.0, z_near: 0.1, z_far: 100.0),
+///                 position: (0.0, 2.0, 8.0),
+///             )),
+///         ),
+///     ],
+/// )
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneDesc {
+    /// The format version the file was written with, see `Migrations`.
+    #[serde(default = "scene_version")]
+    pub version: u32,
+    pub entities: Vec<EntityDesc>,
+}
+
+fn scene_version() -> u32 {
+    SCENE_VERSION
+}
+
+impl Default for SceneDesc {
+    fn default() -> Self {
+        Self {
+            version: SCENE_VERSION,
+            entities: Vec::new(),
+        }
+    }
+}
+
+/// One entity in a `SceneDesc`, every part is optional.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EntityDesc {
+    pub name: Option<String>,
+    /// The entity's `EntityId`, kept so references to it survive renames and reloads.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Uuid>,
+    /// Meshes and lights need a transform, one is created for them if it's left out.
+    pub transform: Option<TransformDesc>,
+    /// The mesh's file name in the asset manager.
+    pub mesh: Option<String>,
+    /// The mesh's asset id, see `AssetManager::asset_id`. When the id is known it wins over
+    /// `mesh`, so the scene still finds a mesh file that was renamed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mesh_id: Option<Uuid>,
+    /// Which of the mesh's materials to use, counting from the mesh's first material.
+    /// Left out it's the mesh's first material.
+    pub material: Option<u32>,
+    pub light: Option<LightDesc>,
+    pub camera: Option<CameraDesc>,
+    /// Components registered with the `ComponentRegistry`, as RON by their registered name.
+    /// `components: {"health": "(current: 10.0)"}`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub components: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransformDesc {
+    pub position: (f32, f32, f32),
+    /// A quaternion stored as (x, y, z, w).
+    pub rotation: (f32, f32, f32, f32),
+    pub scale: (f32, f32, f32),
+}
+
+impl Default for TransformDesc {
+    fn default() -> Self {
+        Self {
+            position: (0.0, 0.0, 0.0),
+            rotation: (0.0, 0.0, 0.0, 1.0),
+            scale: (1.0, 1.0, 1.0),
+        }
+    }
+}
+
+/// Shadow settings aren't stored, lights get the defaults.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LightDesc {
+    Directional {
+        direction: (f32, f32, f32),
+        color: (f32, f32, f32),
+        #[serde(default)]
+        casts_shadows: bool,
+    },
+    Point {
+        color: (f32, f32, f32),
+        intensity: f32,
+        range: f32,
+    },
+    Spot {
+        color: (f32, f32, f32),
+        intensity: f32,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+        #[serde(default)]
+        casts_shadows: bool,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ProjectionDesc {
+    /// The fov is in degrees.
+    Perspective { fov: f32, z_near: f32, z_far: f32 },
+    Orthographic {
+        world_height: f32,
+        z_near: f32,
+        z_far: f32,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraDesc {
+    pub projection: ProjectionDesc,
+    #[serde(default = "default_camera_active")]
+    pub active: bool,
+    #[serde(default)]
+    pub position: (f32, f32, f32),
+    #[serde(default)]
+    pub yaw: f32,
+    #[serde(default)]
+    pub pitch: f32,
+}
+
+fn default_camera_active() -> bool {
+    true
+}
+
+fn to_vec3(value: (f32, f32, f32)) -> Vec3 {
+    Vec3::new(value.0, value.1, value.2)
+}
+
+fn from_vec3(value: &Vec3) -> (f32, f32, f32) {
+    (value.x, value.y, value.z)
+}
+
+// Spawns the entity with it's first component, legion can't insert an entity without any.
+fn attach<T: Component>(world: &mut World, entity: &mut Option<Entity>, component: T) {
+    match entity {
+        Some(entity) => world.add_component(*entity, component).unwrap(),
+        None => *entity = Some(world.insert((), vec![(component,)])[0]),
+    }
+}
+
+// Like `attach` but overwrites the component if the entity already has one.
+pub(crate) fn set<T: Component>(world: &mut World, entity: &mut Option<Entity>, component: T) {
+    if let Some(existing) = *entity {
+        if let Some(mut current) = world.get_component_mut::<T>(existing) {
+            *current = component;
+            return;
+        }
+    }
+    attach(world, entity, component);
+}
+
+impl TransformDesc {
+    pub(crate) fn from_transform(transform: &components::Transform) -> Self {
+        Self {
+            position: from_vec3(&transform.position),
+            rotation: (
+                transform.rotation.i,
+                transform.rotation.j,
+                transform.rotation.k,
+                transform.rotation.w,
+            ),
+            scale: from_vec3(&transform.scale),
+        }
+    }
+
+    pub(crate) fn apply(&self, transform: &mut components::Transform) {
+        transform.position = to_vec3(self.position);
+        let (x, y, z, w) = self.rotation;
+        transform.rotation = Quat::new(w, x, y, z);
+        transform.scale = to_vec3(self.scale);
+        transform.update();
+    }
+}
+
+impl LightDesc {
+    pub(crate) fn from_entity(world: &World, entity: Entity) -> Option<Self> {
+        if let Some(light) = world.get_component::<DirectionalLightData>(entity) {
+            Some(LightDesc::Directional {
+                direction: from_vec3(&light.direction),
+                color: from_vec3(&light.color),
+                casts_shadows: light.casts_shadows,
+            })
+        } else if let Some(light) = world.get_component::<PointLightData>(entity) {
+            Some(LightDesc::Point {
+                color: from_vec3(&light.color),
+                intensity: light.intensity,
+                range: light.range,
+            })
+        } else if let Some(light) = world.get_component::<SpotLightData>(entity) {
+            Some(LightDesc::Spot {
+                color: from_vec3(&light.color),
+                intensity: light.intensity,
+                range: light.range,
+                inner_angle: light.inner_angle,
+                outer_angle: light.outer_angle,
+                casts_shadows: light.casts_shadows,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Updates the entity's light if it's the same kind, otherwise a new light is attached.
+    /// Settings the description doesn't store are only kept in the first case.
+    pub(crate) fn attach(&self, world: &mut World, entity: &mut Option<Entity>) {
+        if let Some(entity) = *entity {
+            match *self {
+                LightDesc::Directional {
+                    direction,
+                    color,
+                    casts_shadows,
+                } => {
+                    if let Some(mut light) = world.get_component_mut::<DirectionalLightData>(entity)
+                    {
+                        light.direction = to_vec3(direction);
+                        light.color = to_vec3(color);
+                        light.casts_shadows = casts_shadows;
+                        return;
+                    }
+                }
+                LightDesc::Point {
+                    color,
+                    intensity,
+                    range,
+                } => {
+                    if let Some(mut light) = world.get_component_mut::<PointLightData>(entity) {
+                        light.color = to_vec3(color);
+                        light.intensity = intensity;
+                        light.range = range;
+                        return;
+                    }
+                }
+                LightDesc::Spot {
+                    color,
+                    intensity,
+                    range,
+                    inner_angle,
+                    outer_angle,
+                    casts_shadows,
+                } => {
+                    if let Some(mut light) = world.get_component_mut::<SpotLightData>(entity) {
+                        light.color = to_vec3(color);
+                        light.intensity = intensity;
+                        light.range = range;
+                        light.inner_angle = inner_angle;
+                        light.outer_angle = outer_angle;
+                        light.casts_shadows = casts_shadows;
+                        return;
+                    }
+                }
+            }
+            remove_lights(world, entity);
+        }
+
+        match *self {
+            LightDesc::Directional {
+                direction,
+                color,
+                casts_shadows,
+            } => {
+                let light = DirectionalLightData {
+                    direction: to_vec3(direction),
+                    color: to_vec3(color),
+                    casts_shadows,
+                    ..DirectionalLightData::default()
+                };
+                attach(world, entity, light);
+            }
+            LightDesc::Point {
+                color,
+                intensity,
+                range,
+            } => {
+                let light = PointLightData {
+                    color: to_vec3(color),
+                    intensity,
+                    range,
+                    ..PointLightData::default()
+                };
+                attach(world, entity, light);
+            }
+            LightDesc::Spot {
+                color,
+                intensity,
+                range,
+                inner_angle,
+                outer_angle,
+                casts_shadows,
+            } => {
+                let light = SpotLightData {
+                    color: to_vec3(color),
+                    intensity,
+                    range,
+                    inner_angle,
+                    outer_angle,
+                    casts_shadows,
+                    ..SpotLightData::default()
+                };
+                attach(world, entity, light);
+            }
+        }
+    }
+}
+
+pub(crate) fn remove_lights(world: &mut World, entity: Entity) {
+    let _ = world.remove_component::<DirectionalLightData>(entity);
+    let _ = world.remove_component::<PointLightData>(entity);
+    let _ = world.remove_component::<SpotLightData>(entity);
+}
+
+impl CameraDesc {
+    pub(crate) fn from_camera(camera: &components::CameraData) -> Self {
+        Self {
+            projection: match camera.projection_data() {
+                ProjectionData::Perspective { fov, z_near, z_far } => ProjectionDesc::Perspective {
+                    fov: *fov,
+                    z_near: *z_near,
+                    z_far: *z_far,
+                },
+                ProjectionData::Orthographic {
+                    world_height,
+                    z_near,
+                    z_far,
+                } => ProjectionDesc::Orthographic {
+                    world_height: *world_height,
+                    z_near: *z_near,
+                    z_far: *z_far,
+                },
+            },
+            active: camera.active,
+            position: from_vec3(&camera.position),
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+        }
+    }
+
+    pub(crate) fn to_camera(&self, width: f32, height: f32) -> components::CameraData {
+        let mut camera = match self.projection {
+            ProjectionDesc::Perspective { fov, z_near, z_far } => {
+                components::CameraData::new_perspective(fov, width, height, z_near, z_far)
+            }
+            ProjectionDesc::Orthographic {
+                world_height,
+                z_near,
+                z_far,
+            } => {
+                components::CameraData::new_orthographic(world_height, width, height, z_near, z_far)
+            }
+        };
+        camera.active = self.active;
+        camera.position = to_vec3(self.position);
+        camera.yaw = self.yaw;
+        camera.pitch = self.pitch;
+        camera
+    }
+}
+
+// The lowest material index the mesh uses, entity materials count from it.
+fn first_material(asset_manager: &AssetManager, mesh_name: &str) -> Option<u32> {
+    asset_manager
+        .try_get_mesh(mesh_name.to_string())
+        .ok()?
+        .sub_meshes
+        .iter()
+        .map(|sub_mesh| sub_mesh.material_index)
+        .min()
+}
+
+impl EntityDesc {
+    /// The mesh's current file name and the absolute material index for it, None without a
+    /// mesh. Fails if the mesh isn't loaded.
+    pub(crate) fn resolve_mesh(
+        &self,
+        asset_manager: &AssetManager,
+    ) -> Result<Option<(String, u32)>, AssetError> {
+        let mesh_name = self
+            .mesh_id
+            .and_then(|id| asset_manager.asset_name(id))
+            .map(str::to_string)
+            .or_else(|| self.mesh.clone());
+        match mesh_name {
+            Some(mesh_name) => {
+                asset_manager.try_get_mesh(mesh_name.clone())?;
+                let first_material = first_material(asset_manager, &mesh_name).unwrap_or(0);
+                Ok(Some((
+                    mesh_name,
+                    first_material + self.material.unwrap_or(0),
+                )))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn from_entity(
+        world: &World,
+        asset_manager: &AssetManager,
+        registry: &ComponentRegistry,
+        entity: Entity,
+    ) -> Self {
+        let mesh = world
+            .get_component::<components::Mesh>(entity)
+            .map(|mesh| mesh.mesh_name.clone());
+        let material = match (&mesh, world.get_component::<components::Material>(entity)) {
+            (Some(mesh_name), Some(material)) => first_material(asset_manager, mesh_name)
+                .map(|first_material| material.index.saturating_sub(first_material))
+                .filter(|offset| *offset != 0),
+            _ => None,
+        };
+
+        let mesh_id = mesh
+            .as_ref()
+            .and_then(|mesh_name| asset_manager.asset_id(mesh_name));
+
+        EntityDesc {
+            name: world
+                .get_component::<components::Name>(entity)
+                .map(|name| name.0.clone()),
+            id: world
+                .get_component::<components::EntityId>(entity)
+                .map(|id| id.0),
+            transform: world
+                .get_component::<components::Transform>(entity)
+                .map(|transform| TransformDesc::from_transform(&transform)),
+            mesh,
+            mesh_id,
+            material,
+            light: LightDesc::from_entity(world, entity),
+            camera: world
+                .get_component::<components::CameraData>(entity)
+                .map(|camera| CameraDesc::from_camera(&camera)),
+            components: registry.save(world, entity),
+        }
+    }
+
+    /// Adds the described components to `entity`, or spawns it if it's None.
+    /// Components that already exist are updated in place so anything the description doesn't
+    /// cover is kept, mesh, light and camera components are removed when they're left out.
+    /// `mesh` comes from `resolve_mesh`.
+    pub(crate) fn apply(
+        &self,
+        world: &mut World,
+        resources: &Resources,
+        entity: &mut Option<Entity>,
+        mesh: Option<(String, u32)>,
+    ) {
+        if self.transform.is_some() || mesh.is_some() || self.light.is_some() {
+            let transform_desc = self.transform.unwrap_or_default();
+            let mut updated = false;
+            if let Some(existing) = *entity {
+                if let Some(mut transform) =
+                    world.get_component_mut::<components::Transform>(existing)
+                {
+                    transform_desc.apply(&mut transform);
+                    updated = true;
+                }
+            }
+            if !updated {
+                let mut transform = components::Transform::new_from_resources(resources);
+                transform_desc.apply(&mut transform);
+                attach(world, entity, transform);
+            }
+        }
+        if let Some(name) = &self.name {
+            set(world, entity, components::Name::new(name.clone()));
+        }
+        if let Some(id) = self.id {
+            set(world, entity, components::EntityId(id));
+        }
+        match mesh {
+            Some((mesh_name, material)) => {
+                set(world, entity, components::Mesh::new(mesh_name));
+                set(world, entity, components::Material::new(material));
+            }
+            None => {
+                if let Some(entity) = *entity {
+                    let _ = world.remove_component::<components::Mesh>(entity);
+                    let _ = world.remove_component::<components::Material>(entity);
+                }
+            }
+        }
+        match &self.light {
+            Some(light) => light.attach(world, entity),
+            None => {
+                if let Some(entity) = *entity {
+                    remove_lights(world, entity);
+                }
+            }
+        }
+        match &self.camera {
+            Some(camera_desc) => {
+                let (width, height) = {
+                    let sc_desc = resources.get::<wgpu::SwapChainDescriptor>().unwrap();
+                    (sc_desc.width as f32, sc_desc.height as f32)
+                };
+                set(world, entity, camera_desc.to_camera(width, height));
+            }
+            None => {
+                if let Some(entity) = *entity {
+                    let _ = world.remove_component::<components::CameraData>(entity);
+                }
+            }
+        }
+        if let Some(registry) = resources.get::<ComponentRegistry>() {
+            registry.load(world, entity, &self.components);
+        }
+    }
+}
+
+impl SceneDesc {
+    pub fn from_ron(source: &str) -> Result<Self, ron::de::Error> {
+        ron::de::from_str(source)
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::ser::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+
+    /// Spawns every entity into the world and returns them in the same order, entries without any
+    /// components are skipped. Fails without spawning anything if a mesh isn't loaded.
+    pub fn spawn(
+        &self,
+        world: &mut World,
+        resources: &Resources,
+    ) -> Result<Vec<Entity>, AssetError> {
+        // Resolve everything first so a bad mesh doesn't leave half a scene behind.
+        let meshes = {
+            let asset_manager = resources.get::<AssetManager>().unwrap();
+            self.entities
+                .iter()
+                .map(|entity| entity.resolve_mesh(&asset_manager))
+                .collect::<Result<Vec<_>, AssetError>>()?
+        };
+
+        let mut entities = Vec::with_capacity(self.entities.len());
+        for (desc, mesh) in self.entities.iter().zip(meshes) {
+            let mut entity = None;
+            desc.apply(world, resources, &mut entity, mesh);
+            entities.extend(entity);
+        }
+
+        Ok(entities)
+    }
+
+    /// Builds a description of every entity with a transform or camera.
+    /// Components the format doesn't know about are skipped unless they're in the
+    /// `ComponentRegistry`.
+    pub fn from_world(world: &World, resources: &Resources) -> Self {
+        let asset_manager = resources.get::<AssetManager>().unwrap();
+        let registry = resources.get::<ComponentRegistry>().unwrap();
+
+        let mut entities = Vec::new();
+        for (entity, _) in <Read<components::Transform>>::query().iter_entities(world) {
+            entities.push(entity);
+        }
+        for (entity, _) in <Read<components::CameraData>>::query().iter_entities(world) {
+            if !entities.contains(&entity) {
+                entities.push(entity);
+            }
+        }
+
+        let entities = entities
+            .into_iter()
+            .map(|entity| EntityDesc::from_entity(world, &asset_manager, &registry, entity))
+            .collect();
+
+        Self {
+            version: SCENE_VERSION,
+            entities,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scene_desc_round_trip() {
+        let source = r#"(
+            entities: [
+                (name: Some("floor"), mesh: Some("cube.gltf"), material: Some(1)),
+                (light: Some(Point(color: (1.0, 1.0, 1.0), intensity: 2.0, range: 5.0))),
+                (camera: Some((projection: Perspective(fov: 70.0, z_near: 0.1, z_far: 100.0)))),
+            ],
+        )"#;
+        let desc = SceneDesc::from_ron(source).unwrap();
+        assert_eq!(desc.entities.len(), 3);
+        assert_eq!(desc.entities[0].transform, None);
+        assert_eq!(desc.entities[0].material, Some(1));
+        assert!(desc.entities[2].camera.unwrap().active);
+
+        let saved = desc.to_ron().unwrap();
+        assert_eq!(SceneDesc::from_ron(&saved).unwrap(), desc);
+    }
+}
@@ -0,0 +1,47 @@
+use legion::prelude::*;
+
+use crate::{
+    scene::{components, resources::DeltaTime},
+    AssetManager,
+};
+
+/// Advances every `Animator` and samples it's clip into joint matrices.
+/// Add this to your scene's schedule builder.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("animation")
+        .read_resource::<DeltaTime>()
+        .read_resource::<AssetManager>()
+        .with_query(<(Write<components::Animator>, Read<components::Mesh>)>::query())
+        .build(|_, mut world, (delta_time, asset_manager), query| {
+            for (mut animator, mesh) in query.iter_mut(&mut world) {
+                let asset_mesh = match asset_manager.try_get_mesh(mesh.mesh_name.clone()) {
+                    Ok(asset_mesh) => asset_mesh,
+                    Err(_) => continue,
+                };
+                let skeleton = match asset_mesh.skeleton.as_ref() {
+                    Some(skeleton) => skeleton,
+                    None => continue,
+                };
+
+                let mut pose = skeleton.rest_pose();
+                let clip = animator
+                    .clip
+                    .as_ref()
+                    .and_then(|name| asset_mesh.get_animation(name));
+                if let Some(clip) = clip {
+                    if animator.playing {
+                        animator.time += delta_time.0 * animator.speed;
+                        if animator.looping && clip.duration > 0.0 {
+                            animator.time = animator.time.rem_euclid(clip.duration);
+                        } else if animator.time > clip.duration || animator.time < 0.0 {
+                            animator.time = animator.time.max(0.0).min(clip.duration);
+                            animator.playing = false;
+                        }
+                    }
+                    clip.sample(animator.time, &mut pose);
+                }
+
+                animator.joint_matrices = skeleton.joint_matrices(&pose);
+            }
+        })
+}
@@ -0,0 +1,81 @@
+use legion::prelude::*;
+use nalgebra_glm::Vec3;
+use winit::event::VirtualKeyCode;
+
+use crate::{
+    core::input::Input,
+    scene::{
+        components::{self, camera_controller::look_direction},
+        resources::DeltaTime,
+    },
+};
+
+/// Drives the `FlyCamera` and `OrbitCamera` controllers of active cameras from the input.
+/// Add this to your scene's schedule builder.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("camera_controllers")
+        .read_resource::<DeltaTime>()
+        .read_resource::<Input>()
+        // A camera with both controllers is only driven by the fly controller, so the queries
+        // never write to the same camera.
+        .with_query(<(Write<components::CameraData>, Read<components::FlyCamera>)>::query())
+        .with_query(
+            <(
+                Write<components::CameraData>,
+                Write<components::OrbitCamera>,
+            )>::query()
+            .filter(!component::<components::FlyCamera>()),
+        )
+        .build(
+            |_, mut world, (delta_time, input), (fly_query, orbit_query)| {
+                let up = Vec3::new(0.0, 1.0, 0.0);
+                let turn = |camera: &mut components::CameraData, sensitivity: f32| {
+                    camera.yaw -= input.mouse_delta.x * sensitivity;
+                    camera.pitch -= input.mouse_delta.y * sensitivity;
+                    // Straight up or down would flip the view over.
+                    let limit = std::f32::consts::FRAC_PI_2 - 0.001;
+                    camera.pitch = camera.pitch.max(-limit).min(limit);
+                };
+
+                for (mut camera, fly) in fly_query.iter_mut(&mut world) {
+                    if !camera.active {
+                        continue;
+                    }
+                    if fly
+                        .look_button
+                        .map_or(true, |button| input.is_mouse_button_down(button))
+                    {
+                        turn(&mut camera, fly.sensitivity);
+                    }
+                    let forward = look_direction(camera.yaw, camera.pitch);
+                    let right = forward.cross(&up).normalize();
+                    let direction =
+                        components::FlyCamera::key_direction(|key| input.is_key_down(key));
+                    let mut speed = fly.speed * delta_time.0;
+                    if input.is_key_down(VirtualKeyCode::LShift) {
+                        speed *= fly.fast_multiplier;
+                    }
+                    let position = camera.position
+                        + (right * direction.x + up * direction.y + forward * direction.z) * speed;
+                    camera.position = position;
+                    camera.update_view(position, position + forward, up);
+                }
+
+                for (mut camera, mut orbit) in orbit_query.iter_mut(&mut world) {
+                    if !camera.active {
+                        continue;
+                    }
+                    if orbit
+                        .orbit_button
+                        .map_or(true, |button| input.is_mouse_button_down(button))
+                    {
+                        turn(&mut camera, orbit.sensitivity);
+                    }
+                    orbit.zoom(input.mouse_wheel().y);
+                    let eye = orbit.eye(camera.yaw, camera.pitch);
+                    camera.position = eye;
+                    camera.update_view(eye, orbit.target, up);
+                }
+            },
+        )
+}
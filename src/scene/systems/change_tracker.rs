@@ -0,0 +1,16 @@
+use legion::prelude::*;
+
+use crate::scene::resources::{ChangeTracker, Tracked};
+
+/// Updates the `ChangeTracker<T>` resource, add it before any systems that read the tracker.
+pub fn create<T: Tracked>() -> Box<dyn Schedulable> {
+    SystemBuilder::new(format!("change_tracker_{}", std::any::type_name::<T>()))
+        .write_resource::<ChangeTracker<T>>()
+        .with_query(<Read<T>>::query())
+        .build(|_, world, tracker, query| {
+            let components = query
+                .iter_entities(&world)
+                .map(|(entity, component)| (entity, component.snapshot()));
+            tracker.update(components);
+        })
+}
@@ -1 +1,6 @@
-
+pub mod animation;
+pub mod camera_controller;
+pub mod change_tracker;
+pub mod sequencer;
+#[cfg(feature = "physics2d")]
+pub mod physics2d;
@@ -0,0 +1,41 @@
+use legion::prelude::*;
+use std::collections::HashSet;
+
+use crate::scene::{
+    components::{BodyType2D, Collider2D, RigidBody2D, Transform},
+    resources::{DeltaTime, Physics2D},
+};
+
+/// Steps the `Physics2D` world. New `RigidBody2D`s get their body and colliders, kinematic
+/// bodies follow their transform and dynamic bodies move their transform.
+/// Add this to your scene's schedule builder, before anything that reads the transforms.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("physics2d")
+        .read_resource::<DeltaTime>()
+        .write_resource::<Physics2D>()
+        .with_query(<(Write<RigidBody2D>, Read<Collider2D>, Read<Transform>)>::query())
+        .with_query(<(Read<RigidBody2D>, Write<Transform>)>::query())
+        .build(
+            |_, mut world, (delta_time, physics), (body_query, transform_query)| {
+                let mut alive = HashSet::new();
+                for (entity, (mut body, collider, transform)) in
+                    body_query.iter_entities_mut(&mut world)
+                {
+                    if !physics.has_body(entity, &body) {
+                        body.handle = Some(physics.insert(entity, &body, &collider, &transform));
+                    } else if body.body_type == BodyType2D::Kinematic {
+                        physics.move_kinematic(&body, &transform);
+                    }
+                    alive.insert(entity);
+                }
+                physics.retain(|entity| alive.contains(&entity));
+
+                if !physics.step(delta_time.0) {
+                    return;
+                }
+                for (body, mut transform) in transform_query.iter_mut(&mut world) {
+                    physics.sync_transform(&body, &mut transform);
+                }
+            },
+        )
+}
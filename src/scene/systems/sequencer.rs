@@ -0,0 +1,100 @@
+use legion::prelude::*;
+use nalgebra_glm::{Quat, Vec3};
+use std::collections::HashMap;
+
+use crate::scene::{
+    components,
+    resources::{DeltaTime, EntityIndex, Sequencer},
+    timeline::{current_key, sample_transform, Track},
+};
+
+/// Advances the `Sequencer` and applies it's timeline to the world, cutting between cameras,
+/// moving entities and setting the clip and time of their `Animator`.
+/// Add this to your scene's schedule builder before `animation::create()`.
+pub fn create() -> Box<dyn Schedulable> {
+    SystemBuilder::new("sequencer")
+        .read_resource::<DeltaTime>()
+        .read_resource::<EntityIndex>()
+        .write_resource::<Sequencer>()
+        .with_query(<(Write<components::CameraData>,)>::query())
+        .with_query(<(Write<components::Transform>,)>::query())
+        .with_query(<(Write<components::Animator>,)>::query())
+        .build(
+            |_,
+             mut world,
+             (delta_time, entity_index, sequencer),
+             (camera_query, transform_query, animator_query)| {
+                if !sequencer.advance(delta_time.0) {
+                    return;
+                }
+                let time = sequencer.time();
+                let timeline = match sequencer.timeline() {
+                    Some(timeline) => timeline,
+                    None => return,
+                };
+
+                let mut camera = None;
+                let mut transforms: HashMap<Entity, (Vec3, Quat, Vec3)> = HashMap::new();
+                let mut clips: HashMap<Entity, (String, f32)> = HashMap::new();
+                for track in timeline.tracks.iter() {
+                    match track {
+                        Track::CameraCuts(keys) => {
+                            camera = current_key(keys, time)
+                                .and_then(|key| entity_index.find_by_name(&key.value))
+                                .or(camera);
+                        }
+                        Track::Transform { target, keys } => {
+                            let entity = entity_index.find_by_name(target);
+                            if let (Some(entity), Some(transform)) =
+                                (entity, sample_transform(keys, time))
+                            {
+                                transforms.insert(entity, transform);
+                            }
+                        }
+                        Track::Animation { target, keys } => {
+                            let entity = entity_index.find_by_name(target);
+                            if let (Some(entity), Some(key)) = (entity, current_key(keys, time)) {
+                                clips.insert(entity, (key.value.clone(), time - key.time));
+                            }
+                        }
+                        Track::Audio(_) | Track::Events(_) => (),
+                    }
+                }
+
+                for (entity, (mut transform,)) in transform_query.iter_entities_mut(&mut world) {
+                    if let Some((position, rotation, scale)) = transforms.get(&entity) {
+                        transform.position = *position;
+                        transform.rotation = *rotation;
+                        transform.scale = *scale;
+                        transform.update();
+                    }
+                }
+
+                // Cameras keep their own position and view, they follow their transform track.
+                for (entity, (mut camera_data,)) in camera_query.iter_entities_mut(&mut world) {
+                    if let Some(camera) = camera {
+                        camera_data.active = entity == camera;
+                    }
+                    if let Some((position, rotation, _)) = transforms.get(&entity) {
+                        let forward =
+                            nalgebra_glm::quat_rotate_vec3(rotation, &Vec3::new(0.0, 0.0, -1.0));
+                        let up =
+                            nalgebra_glm::quat_rotate_vec3(rotation, &Vec3::new(0.0, 1.0, 0.0));
+                        camera_data.position = *position;
+                        camera_data.update_view(*position, position + forward, up);
+                    }
+                }
+
+                // The sequencer owns the clip's time so scrubbing poses the entity too.
+                for (entity, (mut animator,)) in animator_query.iter_entities_mut(&mut world) {
+                    if let Some((clip, clip_time)) = clips.get(&entity) {
+                        if animator.clip.as_ref() != Some(clip) {
+                            animator.clip = Some(clip.clone());
+                        }
+                        animator.time = clip_time * animator.speed;
+                        animator.playing = false;
+                    }
+                }
+            },
+        )
+}
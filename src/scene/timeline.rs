@@ -0,0 +1,217 @@
+use legion::prelude::Resources;
+use nalgebra_glm::{Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use super::TransformDesc;
+use crate::{
+    assets::{AssetError, TIMELINE_VERSION},
+    AssetManager,
+};
+
+/// A cutscene stored as RON, play it with `Sequencer::play`.
+/// Tracks find their entities by `Name`, keys don't have to be in order.
+/// ```ron
+/// (
+///     tracks: [
+///         CameraCuts([(time: 0.0, value: "wide"), (time: 4.0, value: "close_up")]),
+///         Transform(
+///             target: "close_up",
+///             keys: [
+///                 (time: 4.0, value: (position: (0.0, 2.0, 5.0))),
+///                 (time: 8.0, value: (position: (2.0, 2.0, 3.0))),
+///             ],
+///         ),
+///         Animation(target: "hero", keys: [(time: 1.0, value: "wave")]),
+///         Audio([(time: 4.0, value: "sting.ogg")]),
+///         Events([(time: 8.0, value: "open_door")]),
+///     ],
+/// )
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Timeline {
+    /// The format version the file was written with, see `Migrations`.
+    pub version: u32,
+    /// Length in seconds, it's never shorter than the last key.
+    pub duration: f32,
+    pub tracks: Vec<Track>,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            version: TIMELINE_VERSION,
+            duration: 0.0,
+            tracks: Vec::new(),
+        }
+    }
+}
+
+/// Something that happens at a time on a track.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Key<T> {
+    pub time: f32,
+    pub value: T,
+}
+
+impl<T> Key<T> {
+    pub fn new(time: f32, value: T) -> Self {
+        Self { time, value }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Track {
+    /// Makes the named camera the active one from each key on.
+    CameraCuts(Vec<Key<String>>),
+    /// Moves the named entity between the transforms linearly, cameras follow it too.
+    Transform {
+        target: String,
+        keys: Vec<Key<TransformDesc>>,
+    },
+    /// Plays clips on the named entity's `Animator`, each one until the next key.
+    Animation {
+        target: String,
+        keys: Vec<Key<String>>,
+    },
+    /// Sounds for the app to play, they come out of `Sequencer::drain_events`.
+    Audio(Vec<Key<String>>),
+    /// Named events for the app, they come out of `Sequencer::drain_events`.
+    Events(Vec<Key<String>>),
+}
+
+impl Track {
+    fn last_time(&self) -> f32 {
+        let times: Vec<f32> = match self {
+            Track::CameraCuts(keys)
+            | Track::Animation { keys, .. }
+            | Track::Audio(keys)
+            | Track::Events(keys) => keys.iter().map(|key| key.time).collect(),
+            Track::Transform { keys, .. } => keys.iter().map(|key| key.time).collect(),
+        };
+        times.into_iter().fold(0.0, f32::max)
+    }
+
+    fn sort(&mut self) {
+        let by_time = |a: f32, b: f32| a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal);
+        match self {
+            Track::CameraCuts(keys)
+            | Track::Animation { keys, .. }
+            | Track::Audio(keys)
+            | Track::Events(keys) => keys.sort_by(|a, b| by_time(a.time, b.time)),
+            Track::Transform { keys, .. } => keys.sort_by(|a, b| by_time(a.time, b.time)),
+        }
+    }
+}
+
+impl Timeline {
+    pub fn from_ron(source: &str) -> Result<Self, ron::de::Error> {
+        ron::de::from_str(source)
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::ser::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+
+    /// Reads a timeline through the asset manager's virtual file system, the path is relative
+    /// to the asset folder. Files written by older versions are migrated first.
+    pub fn load<T: Into<String>>(path: T, resources: &Resources) -> Result<Self, AssetError> {
+        let path = path.into();
+        let asset_manager = resources.get::<AssetManager>().unwrap();
+        let source = asset_manager.vfs().read_to_string(&path)?;
+        let source = asset_manager
+            .migrations()
+            .migrate("timeline", &source)
+            .map_err(|error| AssetError::decode(&path, error))?;
+        Self::from_ron(&source).map_err(|error| AssetError::decode(&path, error))
+    }
+
+    /// Length in seconds, `duration` or the time of the last key if that's later.
+    pub fn length(&self) -> f32 {
+        self.tracks
+            .iter()
+            .map(Track::last_time)
+            .fold(self.duration.max(0.0), f32::max)
+    }
+
+    // Puts every track's keys in order, the sampling below expects them to be.
+    pub(crate) fn sort(&mut self) {
+        for track in self.tracks.iter_mut() {
+            track.sort();
+        }
+    }
+}
+
+/// The last key at or before `time`, None before the first one.
+pub(crate) fn current_key<T>(keys: &[Key<T>], time: f32) -> Option<&Key<T>> {
+    keys.iter().take_while(|key| key.time <= time).last()
+}
+
+/// The transform at `time` as (position, rotation, scale), held at the first and last keys.
+pub(crate) fn sample_transform(
+    keys: &[Key<TransformDesc>],
+    time: f32,
+) -> Option<(Vec3, Quat, Vec3)> {
+    let to_parts = |desc: &TransformDesc| {
+        let (x, y, z, w) = desc.rotation;
+        (
+            Vec3::new(desc.position.0, desc.position.1, desc.position.2),
+            Quat::new(w, x, y, z),
+            Vec3::new(desc.scale.0, desc.scale.1, desc.scale.2),
+        )
+    };
+    let next = keys.iter().position(|key| key.time > time);
+    let (from, to) = match next {
+        Some(0) => return keys.first().map(|key| to_parts(&key.value)),
+        Some(next) => (&keys[next - 1], &keys[next]),
+        None => return keys.last().map(|key| to_parts(&key.value)),
+    };
+    let t = (time - from.time) / (to.time - from.time);
+    let (from_position, from_rotation, from_scale) = to_parts(&from.value);
+    let (to_position, to_rotation, to_scale) = to_parts(&to.value);
+    Some((
+        nalgebra_glm::lerp(&from_position, &to_position, t),
+        nalgebra_glm::quat_slerp(&from_rotation, &to_rotation, t),
+        nalgebra_glm::lerp(&from_scale, &to_scale, t),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: f32) -> TransformDesc {
+        TransformDesc {
+            position: (x, 0.0, 0.0),
+            ..TransformDesc::default()
+        }
+    }
+
+    #[test]
+    fn test_timeline_sampling() {
+        let keys = vec![Key::new(1.0, at(0.0)), Key::new(3.0, at(4.0))];
+        let x = |time| sample_transform(&keys, time).unwrap().0.x;
+        assert_eq!(x(0.0), 0.0);
+        assert_eq!(x(2.0), 2.0);
+        assert_eq!(x(5.0), 4.0);
+        assert!(sample_transform(&[], 1.0).is_none());
+    }
+
+    #[test]
+    fn test_timeline_length() {
+        let mut timeline = Timeline {
+            duration: 2.0,
+            tracks: vec![Track::Events(vec![
+                Key::new(5.0, "b".to_string()),
+                Key::new(1.0, "a".to_string()),
+            ])],
+            ..Timeline::default()
+        };
+        assert_eq!(timeline.length(), 5.0);
+        timeline.sort();
+        match &timeline.tracks[0] {
+            Track::Events(keys) => assert_eq!(current_key(keys, 2.0).unwrap().value, "a"),
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use legion::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{
+    assets::AssetError,
+    scene::{components, ComponentRegistry, EntityDesc, RemovalHooks},
+    AssetManager,
+};
+
+/// The described state of every named entity, see `EntityDesc` for what's included.
+/// Entities are matched up by their `Name`, so an editor and a game that loaded the same
+/// scene can talk about the same entities. Unnamed entities are left out.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    pub entities: BTreeMap<String, EntityDesc>,
+}
+
+/// The changes between two snapshots, it can be sent to another process and applied there.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorldDiff {
+    /// Entities that are new or whose description changed.
+    pub changed: Vec<EntityDesc>,
+    /// Names of the entities that went away.
+    pub removed: Vec<String>,
+}
+
+impl WorldSnapshot {
+    pub fn from_world(world: &World, resources: &Resources) -> Self {
+        let asset_manager = resources.get::<AssetManager>().unwrap();
+        let registry = resources.get::<ComponentRegistry>().unwrap();
+        let mut entities = BTreeMap::new();
+        for (entity, name) in <Read<components::Name>>::query().iter_entities(world) {
+            if entities.contains_key(&name.0) {
+                log::warn!("World Snapshot: more than one entity is named {}.", name.0);
+                continue;
+            }
+            entities.insert(
+                name.0.clone(),
+                EntityDesc::from_entity(world, &asset_manager, &registry, entity),
+            );
+        }
+        Self { entities }
+    }
+
+    /// What needs to be applied to a world matching `self` to make it match `newer`.
+    pub fn diff(&self, newer: &WorldSnapshot) -> WorldDiff {
+        let changed = newer
+            .entities
+            .iter()
+            .filter(|(name, desc)| self.entities.get(*name) != Some(desc))
+            .map(|(_, desc)| desc.clone())
+            .collect();
+        let removed = self
+            .entities
+            .keys()
+            .filter(|name| !newer.entities.contains_key(*name))
+            .cloned()
+            .collect();
+        WorldDiff { changed, removed }
+    }
+}
+
+impl WorldDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    pub fn from_ron(source: &str) -> Result<Self, ron::de::Error> {
+        ron::de::from_str(source)
+    }
+
+    /// Writes the diff on a single line.
+    pub fn to_ron(&self) -> Result<String, ron::ser::Error> {
+        ron::ser::to_string(self)
+    }
+
+    /// Updates, spawns and despawns entities by name. Components the descriptions don't cover
+    /// are left alone. Fails without changing anything if a mesh isn't loaded.
+    pub fn apply(&self, world: &mut World, resources: &Resources) -> Result<(), AssetError> {
+        let meshes = {
+            let asset_manager = resources.get::<AssetManager>().unwrap();
+            self.changed
+                .iter()
+                .map(|desc| desc.resolve_mesh(&asset_manager))
+                .collect::<Result<Vec<_>, AssetError>>()?
+        };
+
+        let named: Vec<(Entity, String)> = <Read<components::Name>>::query()
+            .iter_entities(world)
+            .map(|(entity, name)| (entity, name.0.clone()))
+            .collect();
+        let find = |name: &Option<String>| {
+            named
+                .iter()
+                .find(|(_, current)| Some(current) == name.as_ref())
+                .map(|(entity, _)| *entity)
+        };
+
+        for (desc, mesh) in self.changed.iter().zip(meshes) {
+            let mut entity = find(&desc.name);
+            desc.apply(world, resources, &mut entity, mesh);
+        }
+
+        let removal_hooks = resources.get::<RemovalHooks>().unwrap();
+        for name in self.removed.iter() {
+            if let Some(entity) = find(&Some(name.clone())) {
+                removal_hooks.despawn(world, resources, entity);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::TransformDesc;
+
+    fn entity(name: &str, x: f32) -> EntityDesc {
+        EntityDesc {
+            name: Some(name.to_string()),
+            transform: Some(TransformDesc {
+                position: (x, 0.0, 0.0),
+                ..TransformDesc::default()
+            }),
+            ..EntityDesc::default()
+        }
+    }
+
+    fn snapshot(entities: Vec<EntityDesc>) -> WorldSnapshot {
+        WorldSnapshot {
+            entities: entities
+                .into_iter()
+                .map(|desc| (desc.name.clone().unwrap(), desc))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_world_diff() {
+        let old = snapshot(vec![entity("a", 0.0), entity("b", 0.0), entity("c", 0.0)]);
+        let new = snapshot(vec![entity("a", 0.0), entity("b", 1.0), entity("d", 0.0)]);
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.changed, vec![entity("b", 1.0), entity("d", 0.0)]);
+        assert_eq!(diff.removed, vec!["c".to_string()]);
+        assert!(new.diff(&new).is_empty());
+
+        let sent = diff.to_ron().unwrap();
+        assert!(!sent.contains('\n'));
+        assert_eq!(WorldDiff::from_ron(&sent).unwrap(), diff);
+    }
+}
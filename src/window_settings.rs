@@ -0,0 +1,83 @@
+use winit::{
+    dpi::LogicalSize,
+    event_loop::EventLoop,
+    monitor::MonitorHandle,
+    window::{Fullscreen, WindowBuilder},
+};
+
+/// How the window covers the screen.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WindowMode {
+    Windowed,
+    /// A borderless window the size of the monitor, switching to and from it is quick.
+    Borderless,
+    /// Takes over the monitor in it's biggest video mode.
+    Fullscreen,
+}
+
+impl WindowMode {
+    pub(crate) fn fullscreen(self, monitor: MonitorHandle) -> Option<Fullscreen> {
+        match self {
+            WindowMode::Windowed => None,
+            WindowMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+            WindowMode::Fullscreen => monitor
+                .video_modes()
+                .max_by_key(|mode| {
+                    let size = mode.size();
+                    (size.width * size.height, mode.refresh_rate())
+                })
+                .map(Fullscreen::Exclusive),
+        }
+    }
+
+    pub(crate) fn from_fullscreen(fullscreen: Option<Fullscreen>) -> Self {
+        match fullscreen {
+            None => WindowMode::Windowed,
+            Some(Fullscreen::Borderless(_)) => WindowMode::Borderless,
+            Some(Fullscreen::Exclusive(_)) => WindowMode::Fullscreen,
+        }
+    }
+}
+
+/// The window the application opens, see `AppBuilder::with_window_settings`.
+/// The mode and whether it's resizable can be changed later with `Application::set_window_mode`
+/// and `Application::set_resizable`.
+#[derive(Debug, Clone)]
+pub struct WindowSettings {
+    pub title: String,
+    /// Size of the window when it isn't fullscreen.
+    pub size: LogicalSize<u32>,
+    pub resizable: bool,
+    pub mode: WindowMode,
+}
+
+impl WindowSettings {
+    pub fn new<T: Into<String>>(title: T, size: LogicalSize<u32>) -> Self {
+        Self {
+            title: title.into(),
+            size,
+            resizable: true,
+            mode: WindowMode::Windowed,
+        }
+    }
+
+    pub fn with_resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: WindowMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// A window builder with these settings, fullscreen modes use the primary monitor.
+    /// Pass it to `Application::new` when not using `AppBuilder`.
+    pub fn window_builder(&self, event_loop: &EventLoop<()>) -> WindowBuilder {
+        WindowBuilder::new()
+            .with_title(self.title.clone())
+            .with_inner_size(self.size)
+            .with_resizable(self.resizable)
+            .with_fullscreen(self.mode.fullscreen(event_loop.primary_monitor()))
+    }
+}